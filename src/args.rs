@@ -23,6 +23,8 @@ pub mod cmdline {
     pub const MODE: &str = "mode";
     pub const PROJECTION: &str = "projection";
     pub const HELP: &str = "help";
+    pub const DEBUG: &str = "debug";
+    pub const GL_DEBUG: &str = "gl-debug";
 }
 
 #[derive(Debug)]
@@ -39,7 +41,11 @@ pub enum Mode {
 
 #[derive(Debug)]
 pub struct Parameters {
-    pub mode: Mode
+    pub mode: Mode,
+    /// Enables debugging aids (e.g., the GPU resource inspector).
+    pub debug: bool,
+    /// Enables capturing of OpenGL debug output into the log.
+    pub gl_debug: bool
 }
 
 impl Parameters {
@@ -116,6 +122,9 @@ fn collect_options<I: Iterator<Item=String>>(
 /// Returns Ok(None) if help was requested.
 pub fn parse_command_line<I: Iterator<Item=String>>(stream: I) -> Result<Parameters, String> {
     let mut mode_found = false;
+    let mut mode = GUIMode::Selectable;
+    let mut debug = false;
+    let mut gl_debug = false;
 
     let mut stream = stream.skip(1); // skip the binary name
 
@@ -125,17 +134,23 @@ pub fn parse_command_line<I: Iterator<Item=String>>(stream: I) -> Result<Paramet
                 if arg.starts_with("--") {
                     if &arg[2..] == cmdline::MODE {
                         mode_found = true;
+                    } else if &arg[2..] == cmdline::DEBUG {
+                        debug = true;
+                    } else if &arg[2..] == cmdline::GL_DEBUG {
+                        gl_debug = true;
                     } else {
-                        return Err(format!("invalid option: {}, expected: --{}", arg, cmdline::MODE));
+                        return Err(format!(
+                            "invalid option: {}, expected: --{}, --{} or --{}",
+                            arg, cmdline::MODE, cmdline::DEBUG, cmdline::GL_DEBUG
+                        ));
                     }
                 } else if mode_found {
                     match arg.as_str() {
-                        cmdline::PROJECTION => {
-                            return Ok(Parameters{ mode: Mode::GUI(GUIMode::Projection) });
-                        },
+                        cmdline::PROJECTION => mode = GUIMode::Projection,
 
                         _ => { return Err(format!("unrecognized value: {}", arg)); }
                     }
+                    mode_found = false;
                 } else {
                     return Err(format!("invalid option: {}, expected: --{}", arg, cmdline::MODE));
                 }
@@ -144,5 +159,5 @@ pub fn parse_command_line<I: Iterator<Item=String>>(stream: I) -> Result<Paramet
         }
     }
 
-    Ok(Parameters{ mode: Mode::GUI(GUIMode::Selectable) })
+    Ok(Parameters{ mode: Mode::GUI(mode), debug, gl_debug })
 }
@@ -0,0 +1,322 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Priority of background threads (the worker and its helpers) and throttling of export writes, so that long tasks
+//! interfere less with other programs (e.g., capture software). Settings apply to tasks started after a change.
+
+use crate::config::{BackgroundConfig, Configuration};
+use crate::logging;
+use std::cell::Cell;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const BYTES_PER_MIB: f64 = 1024.0 * 1024.0;
+
+/// Writes may proceed without waiting until this much time's worth of the allowed rate is used up.
+const THROTTLE_BURST: Duration = Duration::from_secs(1);
+
+#[derive(Copy, Clone, Debug, PartialEq, strum::EnumIter)]
+pub enum ThreadPriority {
+    Normal,
+    BelowNormal,
+    /// Runs only when the system is otherwise idle.
+    Idle
+}
+
+impl ThreadPriority {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThreadPriority::Normal => "normal",
+            ThreadPriority::BelowNormal => "below normal",
+            ThreadPriority::Idle => "idle"
+        }
+    }
+
+    pub fn config_name(&self) -> &'static str {
+        match self {
+            ThreadPriority::Normal => "normal",
+            ThreadPriority::BelowNormal => "below_normal",
+            ThreadPriority::Idle => "idle"
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub priority: ThreadPriority,
+    /// Maximum rate (in MiB/s) of writing exported files; `None` means unlimited.
+    pub max_write_rate: Option<f32>
+}
+
+impl Settings {
+    const DEFAULT: Settings = Settings{ priority: ThreadPriority::Normal, max_write_rate: None };
+
+    pub fn from_config(config: &Configuration) -> Settings {
+        Settings{
+            priority: config.background_priority().unwrap_or(ThreadPriority::Normal),
+            max_write_rate: config.max_write_rate_mib().filter(|rate| *rate > 0.0)
+        }
+    }
+
+    pub fn store(&self, config: &mut Configuration) {
+        config.set_background_priority(self.priority);
+        config.set_max_write_rate_mib(self.max_write_rate.unwrap_or(0.0));
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Settings { Settings::DEFAULT }
+}
+
+static SETTINGS: Mutex<Settings> = Mutex::new(Settings::DEFAULT);
+
+/// Message about a failed priority change, not yet shown to the user.
+static PRIORITY_NOTICE: Mutex<Option<String>> = Mutex::new(None);
+
+thread_local! {
+    /// Priority most recently applied to the current thread.
+    static APPLIED_PRIORITY: Cell<Option<ThreadPriority>> = Cell::new(None);
+}
+
+pub fn settings() -> Settings { *SETTINGS.lock().unwrap() }
+
+pub fn set_settings(value: Settings) { *SETTINGS.lock().unwrap() = value; }
+
+/// Returns (and clears) the message about a failed priority change, if any.
+pub fn take_priority_notice() -> Option<String> { PRIORITY_NOTICE.lock().unwrap().take() }
+
+/// Applies the priority from the current settings to the calling thread (if not already applied); to be called by
+/// background threads at start and before each task.
+pub fn apply_priority() {
+    let priority = settings().priority;
+    if APPLIED_PRIORITY.with(|applied| applied.replace(Some(priority))) == Some(priority) { return; }
+
+    if let Err(e) = set_current_thread_priority(priority) {
+        logging::log_warning!("Could not set priority of background thread to {}: {}.", priority.name(), e);
+        // on Linux, raising the nice value is allowed, but lowering it again requires privileges
+        let hint = if cfg!(target_os = "linux") {
+            "\n\nRestart Vislumino to run background tasks with a higher priority."
+        } else {
+            ""
+        };
+        *PRIORITY_NOTICE.lock().unwrap() =
+            Some(format!("Could not set the priority of background tasks to {}:\n{}.{}", priority.name(), e, hint));
+    }
+}
+
+/// Sets the scheduling priority of the calling thread; on Linux, also its I/O priority (on a best-effort basis).
+#[cfg(target_os = "linux")]
+pub fn set_current_thread_priority(priority: ThreadPriority) -> Result<(), std::io::Error> {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    const IOPRIO_CLASS_BEST_EFFORT: libc::c_int = 2;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+
+    let (nice, io_priority) = match priority {
+        ThreadPriority::Normal => (0, IOPRIO_CLASS_BEST_EFFORT << IOPRIO_CLASS_SHIFT | 4),
+        ThreadPriority::BelowNormal => (10, IOPRIO_CLASS_BEST_EFFORT << IOPRIO_CLASS_SHIFT | 7),
+        ThreadPriority::Idle => (19, IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT)
+    };
+
+    // on Linux the nice value is per-thread
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, nice) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // not all I/O schedulers support priorities
+    let _ = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid, io_priority) };
+
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn set_current_thread_priority(priority: ThreadPriority) -> Result<(), std::io::Error> {
+    use winapi::um::processthreadsapi::{GetCurrentThread, SetThreadPriority};
+    use winapi::um::winbase::{THREAD_PRIORITY_BELOW_NORMAL, THREAD_PRIORITY_IDLE, THREAD_PRIORITY_NORMAL};
+
+    let value = match priority {
+        ThreadPriority::Normal => THREAD_PRIORITY_NORMAL,
+        ThreadPriority::BelowNormal => THREAD_PRIORITY_BELOW_NORMAL,
+        ThreadPriority::Idle => THREAD_PRIORITY_IDLE
+    };
+
+    if unsafe { SetThreadPriority(GetCurrentThread(), value as i32) } == 0 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Not supported (e.g., on macOS the nice value applies to the whole process).
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn set_current_thread_priority(_priority: ThreadPriority) -> Result<(), std::io::Error> {
+    Ok(())
+}
+
+/// Source of time for `WriteThrottle`.
+pub trait Clock {
+    /// Returns time elapsed since an arbitrary (fixed) moment.
+    fn now(&self) -> Duration;
+
+    fn sleep(&self, duration: Duration);
+}
+
+pub struct SystemClock {
+    start: Instant
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock { SystemClock{ start: Instant::now() } }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration { self.start.elapsed() }
+
+    fn sleep(&self, duration: Duration) { std::thread::sleep(duration); }
+}
+
+/// Limits the average rate of writes (token bucket); short bursts (up to `THROTTLE_BURST` worth of the rate)
+/// are not delayed.
+pub struct WriteThrottle<C: Clock> {
+    clock: C,
+    /// Bytes per second.
+    rate: f64,
+    /// Bytes which may be written without waiting; negative after a write larger than the available amount.
+    available: f64,
+    last_update: Duration
+}
+
+impl<C: Clock> WriteThrottle<C> {
+    /// Creates a throttle allowing `max_rate` MiB/s.
+    pub fn new(max_rate: f32, clock: C) -> WriteThrottle<C> {
+        let rate = max_rate.max(f32::MIN_POSITIVE) as f64 * BYTES_PER_MIB;
+        let last_update = clock.now();
+
+        WriteThrottle{ clock, rate, available: rate * THROTTLE_BURST.as_secs_f64(), last_update }
+    }
+
+    fn update(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.last_update).as_secs_f64();
+        self.available = (self.available + elapsed * self.rate).min(self.rate * THROTTLE_BURST.as_secs_f64());
+        self.last_update = now;
+    }
+
+    /// Accounts for `num_bytes` having been written; sleeps as long as needed to keep to the maximum rate.
+    pub fn wrote(&mut self, num_bytes: u64) {
+        self.update();
+        self.available -= num_bytes as f64;
+        if self.available < 0.0 {
+            self.clock.sleep(Duration::from_secs_f64(-self.available / self.rate));
+            self.update();
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    /// Time advances only by sleeping and by `advance`.
+    #[derive(Clone, Default)]
+    struct FakeClock {
+        time: Rc<Cell<Duration>>,
+        slept: Rc<Cell<Duration>>
+    }
+
+    impl FakeClock {
+        fn advance(&self, duration: Duration) { self.time.set(self.time.get() + duration); }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Duration { self.time.get() }
+
+        fn sleep(&self, duration: Duration) {
+            self.advance(duration);
+            self.slept.set(self.slept.get() + duration);
+        }
+    }
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn assert_secs(expected: f64, actual: Duration) {
+        assert!((actual.as_secs_f64() - expected).abs() < 1.0e-6, "expected {} s, got {:?}", expected, actual);
+    }
+
+    #[test]
+    fn writes_are_limited_to_max_rate() {
+        let clock = FakeClock::default();
+        let mut throttle = WriteThrottle::new(2.0, clock.clone());
+
+        // the first second's worth is a burst
+        throttle.wrote(2 * MIB);
+        assert_secs(0.0, clock.slept.get());
+
+        for _ in 0..4 { throttle.wrote(MIB); }
+        assert_secs(2.0, clock.slept.get());
+        assert_secs(2.0, clock.now());
+    }
+
+    #[test]
+    fn slow_writes_are_not_delayed() {
+        let clock = FakeClock::default();
+        let mut throttle = WriteThrottle::new(1.0, clock.clone());
+
+        for _ in 0..10 {
+            // e.g., rendering of the next frame
+            clock.advance(Duration::from_secs(2));
+            throttle.wrote(MIB);
+        }
+        assert_secs(0.0, clock.slept.get());
+    }
+
+    #[test]
+    fn idle_time_does_not_accumulate_beyond_burst() {
+        let clock = FakeClock::default();
+        let mut throttle = WriteThrottle::new(1.0, clock.clone());
+
+        clock.advance(Duration::from_secs(100));
+        throttle.wrote(3 * MIB);
+        assert_secs(2.0, clock.slept.get());
+    }
+
+    #[test]
+    fn large_write_waits_proportionally() {
+        let clock = FakeClock::default();
+        let mut throttle = WriteThrottle::new(4.0, clock.clone());
+
+        throttle.wrote(4 * MIB);
+        throttle.wrote(10 * MIB);
+        assert_secs(2.5, clock.slept.get());
+        // nothing is available after the wait
+        throttle.wrote(MIB);
+        assert_secs(2.75, clock.slept.get());
+    }
+
+    #[test]
+    fn lowering_priority_succeeds() {
+        // uses a separate thread, as the priority may not be raised back without privileges (for the same reason,
+        // `Normal` is not tested: the tests may be run with a lowered priority)
+        std::thread::spawn(|| {
+            for priority in [ThreadPriority::BelowNormal, ThreadPriority::Idle] {
+                set_current_thread_priority(priority).unwrap();
+            }
+        }).join().unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Minimal timing harness for performance regression tests of hot code paths. Such tests are named `perf_*` and
+//! ignored by default (timings are meaningful only in optimized builds); run them with
+//! `cargo test --release -- --ignored --nocapture perf_`.
+//!
+//! Each test prints its timings and fails if the mean exceeds a budget set well above the expected value (so that
+//! only gross slowdowns are reported, not noise).
+
+use std::time::{Duration, Instant};
+
+pub struct Measurement {
+    pub name: String,
+    pub iterations: u32,
+    pub mean: Duration,
+    pub min: Duration
+}
+
+impl Measurement {
+    /// Panics if the mean duration exceeds `budget`.
+    pub fn assert_within(&self, budget: Duration) {
+        assert!(
+            self.mean <= budget,
+            "{}: mean {:?} exceeds the budget of {:?}", self.name, self.mean, budget
+        );
+    }
+}
+
+/// Calls `f` `iterations` times (after a warm-up call) and prints the mean and minimum durations.
+pub fn measure<T>(name: &str, iterations: u32, mut f: impl FnMut() -> T) -> Measurement {
+    assert!(iterations > 0);
+
+    std::hint::black_box(f());
+
+    let mut total = Duration::ZERO;
+    let mut min = Duration::MAX;
+    for _ in 0..iterations {
+        let start = Instant::now();
+        std::hint::black_box(f());
+        let elapsed = start.elapsed();
+        total += elapsed;
+        min = min.min(elapsed);
+    }
+
+    let measurement = Measurement{ name: name.to_string(), iterations, mean: total / iterations, min };
+    println!(
+        "{}: mean {:?}, min {:?} ({} iterations)",
+        measurement.name, measurement.mean, measurement.min, measurement.iterations
+    );
+
+    measurement
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_every_iteration() {
+        let mut calls = 0;
+        let measurement = measure("counting", 5, || calls += 1);
+        // including the warm-up call
+        assert_eq!(6, calls);
+        assert_eq!(5, measurement.iterations);
+        assert!(measurement.min <= measurement.mean);
+        measurement.assert_within(Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,208 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Cancellation of background tasks, observed also in the middle of long operations (e.g., decoding of a large file).
+
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A `CancellableReader` checks for cancellation after reading this many bytes.
+pub const POLL_INTERVAL_BYTES: u64 = 64 * 1024;
+
+/// Shared between the GUI (which cancels) and a worker (which checks).
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken { CancelToken::default() }
+
+    pub fn cancel(&self) { self.0.store(true, Ordering::Relaxed); }
+
+    pub fn is_cancelled(&self) -> bool { self.0.load(Ordering::Relaxed) }
+}
+
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "cancelled") }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Fails with `Cancelled` (wrapped in an I/O error) once the token gets cancelled; checks the token at the first read
+/// and then every `POLL_INTERVAL_BYTES`.
+pub struct CancellableReader<R> {
+    inner: R,
+    token: CancelToken,
+    bytes_since_poll: u64,
+    polled: bool
+}
+
+impl<R> CancellableReader<R> {
+    pub fn new(inner: R, token: CancelToken) -> CancellableReader<R> {
+        CancellableReader{ inner, token, bytes_since_poll: 0, polled: false }
+    }
+}
+
+impl<R: Read> Read for CancellableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if !self.polled || self.bytes_since_poll >= POLL_INTERVAL_BYTES {
+            if self.token.is_cancelled() {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, Cancelled));
+            }
+            self.polled = true;
+            self.bytes_since_poll = 0;
+        }
+
+        // never reads past the next poll
+        let max_len = (POLL_INTERVAL_BYTES - self.bytes_since_poll).min(buf.len() as u64) as usize;
+        let num_read = self.inner.read(&mut buf[..max_len])?;
+        self.bytes_since_poll += num_read as u64;
+
+        Ok(num_read)
+    }
+}
+
+impl<R: Seek> Seek for CancellableReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> { self.inner.seek(pos) }
+}
+
+mod tests {
+    use super::*;
+    use image::ImageEncoder;
+    use std::io::Cursor;
+    use std::sync::atomic::AtomicU64;
+
+    type ByteCounter = Arc<AtomicU64>;
+
+    /// Serves data in small chunks; cancels `token` (as if the user clicked "Cancel") after `cancel_after` bytes.
+    struct SlowReader {
+        data: Cursor<Vec<u8>>,
+        token: CancelToken,
+        cancel_after: u64,
+        bytes_served: ByteCounter
+    }
+
+    impl Read for SlowReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            const CHUNK: usize = 1000;
+            let len = buf.len().min(CHUNK);
+            let num_read = self.data.read(&mut buf[..len])?;
+            let served = self.bytes_served.fetch_add(num_read as u64, Ordering::Relaxed) + num_read as u64;
+            if served >= self.cancel_after { self.token.cancel(); }
+            Ok(num_read)
+        }
+    }
+
+    impl Seek for SlowReader {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> { self.data.seek(pos) }
+    }
+
+    fn slow_reader(data: Vec<u8>, token: &CancelToken, cancel_after: u64) -> (SlowReader, ByteCounter) {
+        let bytes_served = Arc::new(AtomicU64::new(0));
+        let reader = SlowReader{
+            data: Cursor::new(data),
+            token: token.clone(),
+            cancel_after,
+            bytes_served: bytes_served.clone()
+        };
+
+        (reader, bytes_served)
+    }
+
+    fn is_cancellation(error: &std::io::Error) -> bool {
+        error.get_ref().map_or(false, |e| e.is::<Cancelled>())
+    }
+
+    /// PNG with poorly compressible contents.
+    fn large_png(width: u32, height: u32) -> Vec<u8> {
+        let mut state = 12345u32;
+        let pixels: Vec<u8> = (0..width * height * 3).map(|_| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as u8
+        }).collect();
+
+        let mut png = vec![];
+        image::codecs::png::PngEncoder::new(&mut png)
+            .write_image(&pixels, width, height, image::ColorType::Rgb8)
+            .unwrap();
+
+        png
+    }
+
+    #[test]
+    fn reading_stops_within_poll_interval() {
+        let token = CancelToken::new();
+        let cancel_after = 3 * POLL_INTERVAL_BYTES + 123;
+        let (reader, bytes_served) = slow_reader(vec![0; 10 * POLL_INTERVAL_BYTES as usize], &token, cancel_after);
+
+        let mut data = vec![];
+        let error = CancellableReader::new(reader, token.clone()).read_to_end(&mut data).unwrap_err();
+
+        assert!(is_cancellation(&error));
+        let served = bytes_served.load(Ordering::Relaxed);
+        assert!(served >= cancel_after);
+        assert!(served <= cancel_after + POLL_INTERVAL_BYTES, "{} bytes after cancellation", served - cancel_after);
+    }
+
+    #[test]
+    fn reading_completes_if_not_cancelled() {
+        let token = CancelToken::new();
+        let (reader, _) = slow_reader(vec![7; 5 * POLL_INTERVAL_BYTES as usize / 2], &token, u64::MAX);
+
+        let mut data = vec![];
+        CancellableReader::new(reader, token.clone()).read_to_end(&mut data).unwrap();
+        assert_eq!(5 * POLL_INTERVAL_BYTES as usize / 2, data.len());
+        assert!(!token.is_cancelled());
+    }
+
+    #[test]
+    fn already_cancelled_reader_reads_nothing() {
+        let token = CancelToken::new();
+        token.cancel();
+        let (reader, bytes_served) = slow_reader(vec![0; 100], &token, u64::MAX);
+
+        let mut buf = [0u8; 10];
+        assert!(is_cancellation(&CancellableReader::new(reader, token).read(&mut buf).unwrap_err()));
+        assert_eq!(0, bytes_served.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn image_decoding_stops_within_poll_interval() {
+        let png = large_png(512, 512);
+        let png_len = png.len() as u64;
+        assert!(png_len > 8 * POLL_INTERVAL_BYTES);
+
+        let token = CancelToken::new();
+        let cancel_after = 2 * POLL_INTERVAL_BYTES;
+        let (reader, bytes_served) = slow_reader(png, &token, cancel_after);
+
+        let result = crate::image_utils::decode_image(
+            std::io::BufReader::new(CancellableReader::new(reader, token.clone())),
+            Some(image::ImageFormat::Png)
+        );
+
+        assert!(result.is_err());
+        // `BufReader` may additionally read ahead up to its capacity
+        let served = bytes_served.load(Ordering::Relaxed);
+        assert!(served <= cancel_after + POLL_INTERVAL_BYTES + 8 * 1024, "{} of {} bytes read", served, png_len);
+    }
+}
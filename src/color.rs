@@ -0,0 +1,361 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Minimal color management: source images are transformed at load time into sRGB (the working space), and back
+//! into their original encoding at export.
+
+use ga_image::{Image, PixelFormat};
+use std::io::Read;
+use std::path::Path;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// gAMA chunk values are stored multiplied by this factor.
+const PNG_GAMMA_SCALE: f32 = 100000.0;
+
+/// Tone curve of source image values.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interpretation {
+    Srgb,
+    Linear,
+    /// Linear intensity = value^gamma (e.g., 2.2).
+    Gamma(f32)
+}
+
+impl Default for Interpretation {
+    fn default() -> Interpretation { Interpretation::Srgb }
+}
+
+impl Interpretation {
+    pub fn to_config_string(&self) -> String {
+        match self {
+            Interpretation::Srgb => "srgb".to_string(),
+            Interpretation::Linear => "linear".to_string(),
+            Interpretation::Gamma(gamma) => format!("gamma:{}", gamma)
+        }
+    }
+
+    pub fn from_config_string(s: &str) -> Option<Interpretation> {
+        match s {
+            "srgb" => Some(Interpretation::Srgb),
+            "linear" => Some(Interpretation::Linear),
+            _ => match s.strip_prefix("gamma:")?.parse::<f32>() {
+                Ok(gamma) if gamma > 0.0 => Some(Interpretation::Gamma(gamma)),
+                _ => None
+            }
+        }
+    }
+
+    /// Converts value in [0; 1] to linear intensity.
+    fn to_linear(&self, value: f32) -> f32 {
+        match self {
+            Interpretation::Srgb => srgb_to_linear(value),
+            Interpretation::Linear => value,
+            Interpretation::Gamma(gamma) => value.powf(*gamma)
+        }
+    }
+
+    /// Converts linear intensity in [0; 1] to value.
+    fn from_linear(&self, linear: f32) -> f32 {
+        match self {
+            Interpretation::Srgb => linear_to_srgb(linear),
+            Interpretation::Linear => linear,
+            Interpretation::Gamma(gamma) => linear.powf(1.0 / gamma)
+        }
+    }
+}
+
+/// Color information found in an image file.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Detected {
+    Interpretation(Interpretation),
+    /// Embedded ICC profile (not interpreted).
+    IccProfile
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(linear: f32) -> f32 {
+    if linear <= 0.0031308 { linear * 12.92 } else { 1.055 * linear.powf(1.0 / 2.4) - 0.055 }
+}
+
+fn to_u8(value: f32) -> u8 {
+    (value * 255.0).round().max(0.0).min(255.0) as u8
+}
+
+fn to_u16(value: f32) -> u16 {
+    (value * 65535.0).round().max(0.0).min(65535.0) as u16
+}
+
+/// Returns lookup table converting 8-bit values of `interpretation` to sRGB.
+pub fn decode_lut(interpretation: Interpretation) -> [u8; 256] {
+    let mut lut = [0u8; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        *entry = to_u8(linear_to_srgb(interpretation.to_linear(i as f32 / 255.0)));
+    }
+
+    lut
+}
+
+/// Returns lookup table converting 8-bit sRGB values back to `interpretation` (inverse of `decode_lut`, up to
+/// rounding).
+pub fn encode_lut(interpretation: Interpretation) -> [u8; 256] {
+    let mut lut = [0u8; 256];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        *entry = to_u8(interpretation.from_linear(srgb_to_linear(i as f32 / 255.0)));
+    }
+
+    lut
+}
+
+/// Like `decode_lut`, but for 16-bit values (65536 entries).
+pub fn decode_lut16(interpretation: Interpretation) -> Vec<u16> {
+    (0..=u16::MAX)
+        .map(|i| to_u16(linear_to_srgb(interpretation.to_linear(i as f32 / u16::MAX as f32))))
+        .collect()
+}
+
+/// Like `encode_lut`, but for 16-bit values (65536 entries).
+pub fn encode_lut16(interpretation: Interpretation) -> Vec<u16> {
+    (0..=u16::MAX)
+        .map(|i| to_u16(interpretation.from_linear(srgb_to_linear(i as f32 / u16::MAX as f32))))
+        .collect()
+}
+
+/// Returns a table converting 16-bit sRGB values back to `interpretation` as values in [0; 255] (meant for
+/// dithering to 8 bits, see `dither::to_rgb8`).
+pub fn encode_curve(interpretation: Interpretation) -> Vec<f32> {
+    (0..=u16::MAX)
+        .map(|i| interpretation.from_linear(srgb_to_linear(i as f32 / u16::MAX as f32)) * 255.0)
+        .collect()
+}
+
+/// Applies `lut` to an 8-bit image (does nothing for an identity table).
+pub fn apply_lut(image: &mut Image, lut: &[u8; 256]) {
+    assert!(image.pixel_format() == PixelFormat::RGB8 || image.pixel_format() == PixelFormat::Mono8);
+
+    apply_lut_to_values(image.raw_pixels_mut(), lut);
+}
+
+/// Like `apply_lut`, but for raw 8-bit values.
+pub fn apply_lut_to_values(values: &mut [u8], lut: &[u8; 256]) {
+    if lut.iter().enumerate().all(|(i, v)| i == *v as usize) { return; }
+
+    for value in values { *value = lut[*value as usize]; }
+}
+
+/// Like `apply_lut`, but for an RGB16 image (`lut` as returned by `decode_lut16` or `encode_lut16`).
+pub fn apply_lut16(image: &mut Image, lut: &[u16]) {
+    assert!(image.pixel_format() == PixelFormat::RGB16);
+    if is_identity16(lut) { return; }
+
+    let row_len = 3 * image.width() as usize;
+    for y in 0..image.height() {
+        for value in &mut image.line_mut::<u16>(y)[..row_len] { *value = lut[*value as usize]; }
+    }
+}
+
+/// Like `apply_lut16`, but for raw 16-bit values.
+pub fn apply_lut16_to_values(values: &mut [u16], lut: &[u16]) {
+    if is_identity16(lut) { return; }
+
+    for value in values { *value = lut[*value as usize]; }
+}
+
+fn is_identity16(lut: &[u16]) -> bool {
+    lut.iter().enumerate().all(|(i, v)| i == *v as usize)
+}
+
+/// Looks for color information in PNG chunks preceding the image data; returns `None` if there is none
+/// (or the data is not a PNG).
+pub fn detect_png(data: &[u8]) -> Option<Detected> {
+    if data.len() < PNG_SIGNATURE.len() || data[..PNG_SIGNATURE.len()] != PNG_SIGNATURE { return None; }
+
+    let mut gamma = None;
+    let mut pos = PNG_SIGNATURE.len();
+    while pos + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let chunk_type = &data[pos + 4..pos + 8];
+        let contents = data.get(pos + 8..pos + 8 + length)?;
+
+        match chunk_type {
+            // sRGB and iCCP take precedence over gAMA
+            b"sRGB" => return Some(Detected::Interpretation(Interpretation::Srgb)),
+            b"iCCP" => return Some(Detected::IccProfile),
+            b"gAMA" if length == 4 => {
+                let value = u32::from_be_bytes(contents.try_into().unwrap());
+                if value > 0 { gamma = Some(PNG_GAMMA_SCALE / value as f32); }
+            },
+            b"IDAT" | b"IEND" => break,
+            _ => ()
+        }
+
+        pos += 8 + length + 4; // length, type, contents, CRC
+    }
+
+    gamma.map(|gamma| Detected::Interpretation(
+        if (gamma - 1.0).abs() < 0.01 { Interpretation::Linear } else { Interpretation::Gamma(gamma) }
+    ))
+}
+
+/// Reads the initial part of a PNG file and looks for color information; see `detect_png`.
+pub fn detect_file<P: AsRef<Path>>(path: P) -> Option<Detected> {
+    // color chunks must precede image data, which in practice starts within the first few kilobytes,
+    // unless there is a large embedded ICC profile (whose chunk header is still found at the beginning)
+    const MAX_HEADER_LEN: u64 = 64 * 1024;
+
+    let mut data = vec![];
+    std::fs::File::open(path).ok()?.take(MAX_HEADER_LEN).read_to_end(&mut data).ok()?;
+
+    detect_png(&data).or_else(|| {
+        // truncated iCCP chunk contents
+        if find_chunk_type(&data, b"iCCP") { Some(Detected::IccProfile) } else { None }
+    })
+}
+
+fn find_chunk_type(data: &[u8], chunk_type: &[u8; 4]) -> bool {
+    data.starts_with(&PNG_SIGNATURE) && data.windows(4).any(|w| w == chunk_type)
+}
+
+mod tests {
+    use super::*;
+
+    fn png_chunk(chunk_type: &[u8; 4], contents: &[u8]) -> Vec<u8> {
+        let mut result = (contents.len() as u32).to_be_bytes().to_vec();
+        result.extend_from_slice(chunk_type);
+        result.extend_from_slice(contents);
+        result.extend_from_slice(&[0, 0, 0, 0]); // CRC is not checked
+        result
+    }
+
+    fn png(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut result = PNG_SIGNATURE.to_vec();
+        result.extend(png_chunk(b"IHDR", &[0; 13]));
+        for chunk in chunks { result.extend_from_slice(chunk); }
+        result.extend(png_chunk(b"IDAT", &[1, 2, 3]));
+        result.extend(png_chunk(b"IEND", &[]));
+        result
+    }
+
+    #[test]
+    fn srgb_round_trip_is_identity() {
+        let decode = decode_lut(Interpretation::Srgb);
+        let encode = encode_lut(Interpretation::Srgb);
+        for i in 0..256 {
+            assert_eq!(i as u8, decode[i]);
+            assert_eq!(i as u8, encode[decode[i] as usize]);
+        }
+    }
+
+    #[test]
+    fn encode_curve_matches_lut() {
+        for interpretation in [Interpretation::Srgb, Interpretation::Linear, Interpretation::Gamma(2.2)] {
+            let curve = encode_curve(interpretation);
+            let lut = encode_lut(interpretation);
+            for (i, value) in lut.iter().enumerate() {
+                assert_eq!(*value, curve[i * 257].round() as u8);
+            }
+        }
+    }
+
+    #[test]
+    fn non_srgb_round_trip_within_rounding() {
+        for interpretation in [Interpretation::Linear, Interpretation::Gamma(2.2), Interpretation::Gamma(1.8)] {
+            let decode = decode_lut(interpretation);
+            let encode = encode_lut(interpretation);
+            for i in 0..256 {
+                let round_trip = encode[decode[i] as usize] as i32;
+                // pure gamma curves are flatter than sRGB's linear segment, so the darkest values get merged
+                let tolerance = if i < 32 { 4 } else { 1 };
+                assert!(
+                    (round_trip - i as i32).abs() <= tolerance, "{:?}: {} -> {}", interpretation, i, round_trip
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sixteen_bit_tables_match_8_bit_ones() {
+        for interpretation in [Interpretation::Srgb, Interpretation::Linear, Interpretation::Gamma(2.2)] {
+            let (decode, decode16) = (decode_lut(interpretation), decode_lut16(interpretation));
+            let (encode, encode16) = (encode_lut(interpretation), encode_lut16(interpretation));
+            for i in 0..256 {
+                let scaled = |lut16: &[u16]| ((lut16[i * 257] as u32 + 128) / 257) as i32;
+                assert!((scaled(&decode16) - decode[i] as i32).abs() <= 1, "{:?}: decode {}", interpretation, i);
+                assert!((scaled(&encode16) - encode[i] as i32).abs() <= 1, "{:?}: encode {}", interpretation, i);
+            }
+        }
+        // identity tables are skipped when applied
+        assert!(is_identity16(&decode_lut16(Interpretation::Srgb)));
+    }
+
+    #[test]
+    fn linear_values_are_brightened() {
+        let decode = decode_lut(Interpretation::Linear);
+        assert_eq!(0, decode[0]);
+        assert_eq!(255, decode[255]);
+        // 18% grey
+        assert_eq!(118, decode[46]);
+    }
+
+    #[test]
+    fn gamma_detected_from_png_chunk() {
+        let data = png(&[png_chunk(b"gAMA", &45455u32.to_be_bytes())]);
+        match detect_png(&data) {
+            Some(Detected::Interpretation(Interpretation::Gamma(gamma))) => assert!((gamma - 2.2).abs() < 0.001),
+            other => panic!("unexpected result: {:?}", other)
+        }
+
+        let data = png(&[png_chunk(b"gAMA", &100000u32.to_be_bytes())]);
+        assert_eq!(Some(Detected::Interpretation(Interpretation::Linear)), detect_png(&data));
+    }
+
+    #[test]
+    fn srgb_and_icc_chunks_take_precedence() {
+        let data = png(&[png_chunk(b"gAMA", &45455u32.to_be_bytes()), png_chunk(b"sRGB", &[0])]);
+        assert_eq!(Some(Detected::Interpretation(Interpretation::Srgb)), detect_png(&data));
+
+        let data = png(&[png_chunk(b"iCCP", b"profile\0\0data"), png_chunk(b"gAMA", &45455u32.to_be_bytes())]);
+        assert_eq!(Some(Detected::IccProfile), detect_png(&data));
+    }
+
+    #[test]
+    fn no_detection_without_color_chunks_or_for_non_png() {
+        assert_eq!(None, detect_png(&png(&[])));
+        assert_eq!(None, detect_png(b"II*\0 not a PNG"));
+        // chunk after image data is ignored
+        let mut data = png(&[]);
+        data.extend(png_chunk(b"sRGB", &[0]));
+        assert_eq!(None, detect_png(&data));
+    }
+
+    #[test]
+    fn config_string_round_trip() {
+        for interpretation in [Interpretation::Srgb, Interpretation::Linear, Interpretation::Gamma(1.8)] {
+            assert_eq!(
+                Some(interpretation),
+                Interpretation::from_config_string(&interpretation.to_config_string())
+            );
+        }
+        assert_eq!(None, Interpretation::from_config_string("gamma:-1"));
+        assert_eq!(None, Interpretation::from_config_string("other"));
+    }
+}
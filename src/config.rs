@@ -20,16 +20,87 @@
 //TODO: add support for OsStr values (file system paths which may be not UTF-8)
 
 use configparser::ini::Ini;
+use crate::background::ThreadPriority;
+use crate::color::Interpretation;
+use crate::fmt;
+use crate::logging;
+use crate::projection::{DisplaySettings, ExportPreset, OverlaySettings, Planet};
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use strum::IntoEnumIterator;
 
 const CONFIG_FILE_NAME: &str = "vislumino.ini";
+const TEMP_FILE_EXT: &str = "tmp";
+const BACKUP_FILE_EXT: &str = "bak";
+const LOCK_FILE_EXT: &str = "lock";
+
+/// Number of rotating backups of the configuration file kept unless changed by the user.
+pub const DEFAULT_NUM_BACKUPS: u32 = 5;
+
+/// Maximum number of rotating backups of the configuration file.
+pub const MAX_NUM_BACKUPS: u32 = 20;
+
+/// Maximum time for which changes remain unsaved (unless saved earlier, e.g., on focus loss).
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
 
 mod ids {
+    pub mod dialogs {
+        pub const GROUP: &str = "DialogGeometry";
+    }
+
+    pub mod gui {
+        pub const GROUP: &str = "Gui";
+
+        pub const MAX_RENDER_FAILURES: &str = "MaxRenderFailures";
+        pub const FORMAT_PREFERENCES: &str = "FormatPreferences";
+        pub const LOG_TO_FILE: &str = "LogToFile";
+        pub const FONT_SIZE: &str = "FontSize";
+        pub const NUM_BACKUPS: &str = "NumConfigBackups";
+        pub const REDUCE_MOTION: &str = "ReduceMotion";
+        pub const GL_DEBUG_OUTPUT: &str = "GlDebugOutput";
+    }
+
+    pub mod background {
+        pub const GROUP: &str = "BackgroundTasks";
+
+        pub const PRIORITY: &str = "Priority";
+        pub const MAX_WRITE_RATE_MIB: &str = "MaxWriteRateMiB";
+    }
+
+    pub mod shortcuts {
+        pub const GROUP: &str = "KeyboardShortcuts";
+    }
+
     pub mod pproj {
         pub const GROUP: &str = "PlanetaryProjection";
 
         pub const PROJECTION_EXPORT_PATH: &str = "ProjectionExportPath";
         pub const LOAD_PATH: &str = "LoadPath";
+        pub const LOAD_DECIMATION: &str = "LoadDecimation";
+        pub const LOAD_BINNING: &str = "LoadBinning";
+        pub const POST_EXPORT_COMMAND: &str = "PostExportCommand";
+        pub const POST_EXPORT_COMMAND_ENABLED: &str = "PostExportCommandEnabled";
+        pub const VRAM_BUDGET_MIB: &str = "VramBudgetMiB";
+        pub const LOAD_INTERPRETATION: &str = "LoadInterpretation";
+        pub const LOAD_SKIP_FAILED_FRAMES: &str = "LoadSkipFailedFrames";
+        pub const VIEW_SOUTH_UP: &str = "ViewSouthUp";
+        pub const VIEW_MIRRORED: &str = "ViewMirrored";
+        pub const LOAD_CACHE_ON_DISK: &str = "LoadCacheOnDisk";
+        pub const VIEW_DISPLAY_SETTINGS: &str = "ViewDisplaySettings";
+        pub const AUTO_PROJECTION_VIEW: &str = "AutoProjectionView";
+        pub const AUTO_PROJECTION_VIEW_HINT_SHOWN: &str = "AutoProjectionViewHintShown";
+        pub const SINGLE_MAP_EXPORT_PATH: &str = "SingleMapExportPath";
+        pub const DEFAULT_PLANET: &str = "DefaultPlanet";
+        pub const FRAME_CROSSFADE: &str = "FrameCrossfade";
+        pub const FRAME_CROSSFADE_MS: &str = "FrameCrossfadeMs";
+        pub const SESSION_GAP_MINUTES: &str = "SessionGapMinutes";
+        pub const SOURCE_OVERLAY_SETTINGS: &str = "SourceOverlaySettings";
+        pub const EXPORT_PRESETS: &str = "ExportPresets";
+        pub const DIAGNOSTIC_RENDERING_OPTION: &str = "DiagnosticRenderingOption";
     }
 }
 
@@ -39,28 +110,411 @@ pub trait ProjectionConfig {
 
     fn projection_export_path(&self) -> Option<PathBuf>;
     fn set_projection_export_path(&mut self, value: &str);
+
+    fn load_decimation(&self) -> Option<u32>;
+    fn set_load_decimation(&mut self, value: u32);
+
+    fn load_binning(&self) -> Option<u32>;
+    fn set_load_binning(&mut self, value: u32);
+
+    fn load_interpretation(&self) -> Option<Interpretation>;
+    fn set_load_interpretation(&mut self, value: Interpretation);
+
+    fn load_skip_failed_frames(&self) -> Option<bool>;
+    fn set_load_skip_failed_frames(&mut self, value: bool);
+
+    fn post_export_command(&self) -> Option<String>;
+    fn set_post_export_command(&mut self, value: &str);
+
+    fn post_export_command_enabled(&self) -> Option<bool>;
+    fn set_post_export_command_enabled(&mut self, value: bool);
+
+    /// Maximum estimated GPU memory (in MiB) which may be used by loaded images.
+    fn vram_budget_mib(&self) -> Option<u32>;
+    fn set_vram_budget_mib(&mut self, value: u32);
+
+    /// Default display orientation of new projection and globe views (does not affect the data or exports).
+    fn view_south_up(&self) -> Option<bool>;
+    fn set_view_south_up(&mut self, value: bool);
+
+    fn view_mirrored(&self) -> Option<bool>;
+    fn set_view_mirrored(&mut self, value: bool);
+
+    /// If true, the load cache (image metadata and detected disks) is kept between sessions.
+    fn load_cache_on_disk(&self) -> Option<bool>;
+    fn set_load_cache_on_disk(&mut self, value: bool);
+
+    /// Default display settings of new projection views.
+    fn projection_view_display_settings(&self) -> Option<DisplaySettings>;
+    fn set_projection_view_display_settings(&mut self, value: &DisplaySettings);
+
+    /// If true, a projection view is opened automatically after the first images have been loaded.
+    fn auto_projection_view(&self) -> Option<bool>;
+    fn set_auto_projection_view(&mut self, value: bool);
+
+    /// The hint explaining the automatically opened projection view has been shown.
+    fn auto_projection_view_hint_shown(&self) -> Option<bool>;
+    fn set_auto_projection_view_hint_shown(&mut self, value: bool);
+
+    /// Folder of the most recently saved single map (see "Save current map...").
+    fn single_map_export_path(&self) -> Option<PathBuf>;
+    fn set_single_map_export_path(&mut self, value: &str);
+
+    /// Planet selected in the source view after loading images for the first time.
+    fn default_planet(&self) -> Option<Planet>;
+    fn set_default_planet(&mut self, value: Planet);
+
+    /// If true, projection and globe views crossfade between consecutive frames.
+    fn frame_crossfade(&self) -> Option<bool>;
+    fn set_frame_crossfade(&mut self, value: bool);
+
+    /// Duration of the crossfade between frames in milliseconds.
+    fn frame_crossfade_ms(&self) -> Option<u32>;
+    fn set_frame_crossfade_ms(&mut self, value: u32);
+
+    /// Longest gap between loaded frames (in minutes) not treated as a break between observing sessions.
+    fn session_gap_minutes(&self) -> Option<u32>;
+    fn set_session_gap_minutes(&mut self, value: u32);
+
+    /// Color of the disk outline and parallels in the source view.
+    fn source_overlay_settings(&self) -> Option<OverlaySettings>;
+    fn set_source_overlay_settings(&mut self, value: &OverlaySettings);
+
+    /// Export presets defined by the user.
+    fn export_presets(&self) -> Option<Vec<ExportPreset>>;
+    fn set_export_presets(&mut self, value: &[ExportPreset]);
+
+    /// Whether projection views offer rendering of a longitude/latitude pattern instead of the images.
+    fn diagnostic_rendering_option(&self) -> Option<bool>;
+    fn set_diagnostic_rendering_option(&mut self, value: bool);
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DialogGeometry {
+    pub position: [f32; 2],
+    pub size: [f32; 2]
+}
+
+pub trait GuiConfig {
+    fn dialog_geometry(&self, dialog_title: &str) -> Option<DialogGeometry>;
+    fn set_dialog_geometry(&mut self, dialog_title: &str, value: &DialogGeometry);
+
+    /// Number of consecutive rendering failures after which restarting the program is advised.
+    fn max_render_failures(&self) -> Option<u32>;
+    fn set_max_render_failures(&mut self, value: u32);
+
+    /// Key chord (e.g., "Ctrl+O"; empty if unbound) of the action with the given ID.
+    fn keyboard_shortcut(&self, action_id: &str) -> Option<String>;
+    fn set_keyboard_shortcut(&mut self, action_id: &str, value: &str);
+
+    /// Formatting of displayed numbers.
+    fn format_preferences(&self) -> Option<fmt::Preferences>;
+    fn set_format_preferences(&mut self, value: &fmt::Preferences);
+
+    /// Whether log entries are also written to a file in the configuration directory.
+    fn log_to_file(&self) -> Option<bool>;
+    fn set_log_to_file(&mut self, value: bool);
+
+    /// Logical font size (before applying the display's scale factor).
+    fn font_size(&self) -> Option<f32>;
+    fn set_font_size(&mut self, value: f32);
+
+    /// Number of rotating backups of the configuration file (0: none are made).
+    fn num_backups(&self) -> Option<u32>;
+    fn set_num_backups(&mut self, value: u32);
+
+    /// If true, animations (crossfades, globe navigation, blinking) are disabled.
+    fn reduce_motion(&self) -> Option<bool>;
+    fn set_reduce_motion(&mut self, value: bool);
+
+    /// Whether OpenGL debug output is captured into the log (takes effect after restart).
+    fn gl_debug_output(&self) -> Option<bool>;
+    fn set_gl_debug_output(&mut self, value: bool);
+}
+
+pub trait BackgroundConfig {
+    /// Priority of the worker thread (and its helpers).
+    fn background_priority(&self) -> Option<ThreadPriority>;
+    fn set_background_priority(&mut self, value: ThreadPriority);
+
+    /// Maximum rate (in MiB/s) of writing exported files; 0 means unlimited.
+    fn max_write_rate_mib(&self) -> Option<f32>;
+    fn set_max_write_rate_mib(&mut self, value: f32);
+}
+
+/// Lock file (containing the owner's process ID) marking the configuration as being in use by a running instance;
+/// removed on drop.
+struct InstanceLock {
+    path: PathBuf
+}
+
+impl InstanceLock {
+    /// Returns (lock, another instance holds the lock). The lock is not acquired if another instance holds it;
+    /// a lock file left over by an instance which is no longer running is replaced.
+    fn acquire(path: PathBuf) -> (Option<InstanceLock>, bool) {
+        let mut stale_removed = false;
+        loop {
+            // creating the file only if it does not exist ensures that of instances started at the same time,
+            // only one finds the lock free
+            let created = std::fs::OpenOptions::new().write(true).create_new(true).open(&path)
+                .and_then(|mut file| file.write_all(std::process::id().to_string().as_bytes()));
+
+            match created {
+                Ok(()) => return (Some(InstanceLock{ path }), false),
+
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let owner = std::fs::read_to_string(&path).ok().and_then(|s| s.trim().parse::<u32>().ok());
+                    // an unreadable lock file may be still being written by its owner
+                    let owner_running = owner.map_or(true, |pid| pid != std::process::id() && process_running(pid));
+                    if owner_running || stale_removed { return (None, true); }
+
+                    logging::log_info!(
+                        "Removing lock file {} left over by an instance which is no longer running.",
+                        path.to_string_lossy()
+                    );
+                    if let Err(e) = std::fs::remove_file(&path) {
+                        logging::log_warning!("Could not remove lock file {}: {}.", path.to_string_lossy(), e);
+                        return (None, true);
+                    }
+                    stale_removed = true;
+                },
+
+                Err(e) => {
+                    logging::log_warning!("Could not create lock file {}: {}.", path.to_string_lossy(), e);
+                    return (None, false);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // the file could have been replaced by another instance (which found this one not running)
+        let owner = std::fs::read_to_string(&self.path).ok().and_then(|s| s.trim().parse::<u32>().ok());
+        if owner == Some(std::process::id()) { let _ = std::fs::remove_file(&self.path); }
+    }
+}
+
+/// Returns `true` if a process with the given ID is running (or if it cannot be determined).
+#[cfg(target_os = "linux")]
+fn process_running(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+#[cfg(windows)]
+fn process_running(pid: u32) -> bool {
+    use winapi::shared::minwindef::DWORD;
+    use winapi::shared::winerror::ERROR_INVALID_PARAMETER;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::minwinbase::STILL_ACTIVE;
+    use winapi::um::processthreadsapi::{GetExitCodeProcess, OpenProcess};
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+
+    let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    // fails also for processes of other users, which are assumed to be running
+    if handle.is_null() {
+        return std::io::Error::last_os_error().raw_os_error() != Some(ERROR_INVALID_PARAMETER as i32);
+    }
+    let mut exit_code: DWORD = 0;
+    let result = unsafe { GetExitCodeProcess(handle, &mut exit_code) };
+    unsafe { CloseHandle(handle); }
+
+    result == 0 || exit_code == STILL_ACTIVE
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn process_running(_pid: u32) -> bool { true }
+
+/// Decides when changes are written to the configuration file, so that many changes in quick succession result
+/// in a single write.
+#[derive(Default)]
+struct SaveSchedule {
+    /// Time of the oldest unsaved change.
+    pending_since: Option<Instant>
+}
+
+impl SaveSchedule {
+    fn mark(&mut self, now: Instant) {
+        if self.pending_since.is_none() { self.pending_since = Some(now); }
+    }
+
+    fn is_pending(&self) -> bool { self.pending_since.is_some() }
+
+    fn is_due(&self, now: Instant) -> bool {
+        self.pending_since.map_or(false, |since| now.saturating_duration_since(since) >= SAVE_INTERVAL)
+    }
+
+    fn clear(&mut self) { self.pending_since = None; }
 }
 
 pub struct Configuration {
-    config_file: Ini
+    config_file: Ini,
+    file_path: PathBuf,
+    /// (group, key) pairs modified by this instance; on store, only these overwrite the current file contents.
+    dirty_keys: HashSet<(String, String)>,
+    save_schedule: SaveSchedule,
+    another_instance_running: bool,
+    /// There was no configuration file at startup.
+    first_run: bool,
+    /// The configuration file has been backed up by this instance; done only before the first store, so that
+    /// the backups span several sessions rather than a few minutes.
+    backed_up: Cell<bool>,
+    _lock: Option<InstanceLock>
+}
+
+/// Rotating backup of the configuration file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backup {
+    /// 1 is the most recent.
+    pub slot: u32,
+    pub path: PathBuf,
+    /// Time of creating the backup.
+    pub modified: Option<SystemTime>
 }
 
 impl Configuration {
-    pub fn store(&self) -> Result<(), std::io::Error> {
-        self.config_file.write(config_file_path())
+    /// Re-reads the configuration file and overwrites only the values changed by this instance, so that
+    /// changes saved in the meantime by another instance are preserved.
+    pub fn store(&mut self) -> Result<(), std::io::Error> {
+        if self.dirty_keys.is_empty() { return Ok(()); }
+
+        let mut current = Ini::new_cs();
+        if self.file_path.exists() && current.load(&self.file_path).is_err() {
+            current = Ini::new_cs();
+        }
+
+        for (group, key) in &self.dirty_keys {
+            current.set(group, key, self.config_file.get(group, key));
+        }
+
+        if !self.backed_up.replace(true) {
+            if let Err(e) = rotate_backups(&self.file_path, self.backups_to_keep()) {
+                logging::log_warning!("Could not back up configuration: {}.", e);
+            }
+        }
+
+        write_atomically(&current, &self.file_path)?;
+        self.dirty_keys.clear();
+
+        Ok(())
+    }
+
+    fn backups_to_keep(&self) -> u32 {
+        self.num_backups().unwrap_or(DEFAULT_NUM_BACKUPS).min(MAX_NUM_BACKUPS)
+    }
+
+    /// Returns the existing backups of the configuration file, the most recent first.
+    pub fn backups(&self) -> Vec<Backup> { list_backups(&self.file_path) }
+
+    /// Replaces the configuration (in memory and on disk) with `backup`; the current file is backed up first.
+    /// Settings already in use elsewhere are not updated.
+    pub fn restore_backup(&mut self, backup: &Backup) -> Result<(), Box<dyn Error>> {
+        let mut restored = Ini::new_cs();
+        restored.load(&backup.path)?;
+
+        rotate_backups(&self.file_path, self.backups_to_keep())?;
+        write_atomically(&restored, &self.file_path)?;
+
+        self.config_file = restored;
+        self.dirty_keys.clear();
+        self.save_schedule.clear();
+
+        Ok(())
     }
 
     pub fn new() -> Configuration {
+        Configuration::with_path(config_file_path())
+    }
+
+    /// Uses the given configuration file instead of the default one.
+    pub fn with_path(file_path: PathBuf) -> Configuration {
+        let (lock, another_instance_running) = InstanceLock::acquire(file_path.with_extension(LOCK_FILE_EXT));
+
+        if another_instance_running {
+            logging::log_warning!(
+                "Another instance of Vislumino appears to be running; settings changes may be lost."
+            );
+        }
+
+        let mut configuration = Configuration::from_file(file_path);
+        configuration.another_instance_running = another_instance_running;
+        configuration._lock = lock;
+
+        configuration
+    }
+
+    fn from_file(file_path: PathBuf) -> Configuration {
         let mut config_file = Ini::new_cs();
-        let file_path = config_file_path();
-        if config_file.load(file_path.clone()).is_err() {
-            println!(
-                "Could not load configuration from {}. A new configuration file will be created.",
+        let first_run = !file_path.exists();
+
+        if first_run {
+            logging::log_info!(
+                "Configuration file {} not found. A new one will be created.",
                 file_path.to_string_lossy()
             );
+        } else if let Err(e) = config_file.load(&file_path) {
+            let backup_path = file_path.with_extension(format!("ini.{}", BACKUP_FILE_EXT));
+            logging::log_warning!(
+                "Could not load configuration from {} ({}). Using defaults; previous file saved as {}.",
+                file_path.to_string_lossy(),
+                e,
+                backup_path.to_string_lossy()
+            );
+            if let Err(e) = std::fs::copy(&file_path, &backup_path) {
+                logging::log_error!("Error backing up configuration: {}.", e);
+            }
+            config_file = Ini::new_cs();
         }
 
-        Configuration{ config_file }
+        Configuration{
+            config_file,
+            file_path,
+            dirty_keys: HashSet::new(),
+            save_schedule: Default::default(),
+            another_instance_running: false,
+            first_run,
+            backed_up: Cell::new(false),
+            _lock: None
+        }
+    }
+
+    /// Returns true if at startup another instance was detected to be using the configuration.
+    pub fn another_instance_running(&self) -> bool { self.another_instance_running }
+
+    /// Returns true if there was no configuration file at startup (e.g., on a fresh install).
+    pub fn first_run(&self) -> bool { self.first_run }
+
+    /// Schedules saving of the changes; they are written by `flush_if_due` at most `SAVE_INTERVAL` later.
+    pub fn save_soon(&mut self) {
+        self.save_schedule.mark(Instant::now());
+    }
+
+    /// Immediately saves the changes (if any).
+    pub fn flush(&mut self) -> Result<(), std::io::Error> {
+        self.flush_at(Instant::now(), true)
+    }
+
+    /// Saves the changes if the oldest of them has remained unsaved for `SAVE_INTERVAL`; to be called periodically.
+    pub fn flush_if_due(&mut self) -> Result<(), std::io::Error> {
+        self.flush_at(Instant::now(), false)
+    }
+
+    fn flush_at(&mut self, now: Instant, force: bool) -> Result<(), std::io::Error> {
+        if !self.save_schedule.is_pending() || (!force && !self.save_schedule.is_due(now)) { return Ok(()); }
+
+        self.save_schedule.clear();
+        let result = self.store();
+        // retried later rather than in every frame
+        if result.is_err() { self.save_schedule.mark(now); }
+
+        result
+    }
+
+    fn set_value(&mut self, group: &str, key: &str, value: &str) {
+        self.config_file.set(group, key, Some(value.into()));
+        self.dirty_keys.insert((group.to_string(), key.to_string()));
+        self.save_soon();
     }
 }
 
@@ -73,7 +527,7 @@ impl ProjectionConfig for Configuration {
     }
 
     fn set_projection_export_path(&mut self, value: &str) {
-        self.config_file.set(ids::pproj::GROUP, ids::pproj::PROJECTION_EXPORT_PATH, Some(value.into()));
+        self.set_value(ids::pproj::GROUP, ids::pproj::PROJECTION_EXPORT_PATH, value);
     }
 
     fn load_path(&self) -> Option<PathBuf> {
@@ -84,18 +538,674 @@ impl ProjectionConfig for Configuration {
     }
 
     fn set_load_path(&mut self, value: &str) {
-        self.config_file.set(ids::pproj::GROUP, ids::pproj::LOAD_PATH, Some(value.into()));
+        self.set_value(ids::pproj::GROUP, ids::pproj::LOAD_PATH, value);
+    }
+
+    fn load_decimation(&self) -> Option<u32> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::LOAD_DECIMATION)?.parse::<u32>().ok()
+    }
+
+    fn set_load_decimation(&mut self, value: u32) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::LOAD_DECIMATION, &value.to_string());
+    }
+
+    fn load_binning(&self) -> Option<u32> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::LOAD_BINNING)?.parse::<u32>().ok()
+    }
+
+    fn set_load_binning(&mut self, value: u32) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::LOAD_BINNING, &value.to_string());
+    }
+
+    fn load_interpretation(&self) -> Option<Interpretation> {
+        Interpretation::from_config_string(&self.config_file.get(ids::pproj::GROUP, ids::pproj::LOAD_INTERPRETATION)?)
+    }
+
+    fn set_load_interpretation(&mut self, value: Interpretation) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::LOAD_INTERPRETATION, &value.to_config_string());
+    }
+
+    fn load_skip_failed_frames(&self) -> Option<bool> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::LOAD_SKIP_FAILED_FRAMES)?.parse::<bool>().ok()
+    }
+
+    fn set_load_skip_failed_frames(&mut self, value: bool) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::LOAD_SKIP_FAILED_FRAMES, &value.to_string());
+    }
+
+    fn post_export_command(&self) -> Option<String> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::POST_EXPORT_COMMAND)
+    }
+
+    fn set_post_export_command(&mut self, value: &str) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::POST_EXPORT_COMMAND, value);
+    }
+
+    fn post_export_command_enabled(&self) -> Option<bool> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::POST_EXPORT_COMMAND_ENABLED)?.parse::<bool>().ok()
+    }
+
+    fn set_post_export_command_enabled(&mut self, value: bool) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::POST_EXPORT_COMMAND_ENABLED, &value.to_string());
+    }
+
+    fn vram_budget_mib(&self) -> Option<u32> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::VRAM_BUDGET_MIB)?.parse::<u32>().ok()
+    }
+
+    fn set_vram_budget_mib(&mut self, value: u32) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::VRAM_BUDGET_MIB, &value.to_string());
+    }
+
+    fn view_south_up(&self) -> Option<bool> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::VIEW_SOUTH_UP)?.parse::<bool>().ok()
+    }
+
+    fn set_view_south_up(&mut self, value: bool) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::VIEW_SOUTH_UP, &value.to_string());
+    }
+
+    fn view_mirrored(&self) -> Option<bool> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::VIEW_MIRRORED)?.parse::<bool>().ok()
+    }
+
+    fn set_view_mirrored(&mut self, value: bool) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::VIEW_MIRRORED, &value.to_string());
+    }
+
+    fn load_cache_on_disk(&self) -> Option<bool> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::LOAD_CACHE_ON_DISK)?.parse::<bool>().ok()
+    }
+
+    fn set_load_cache_on_disk(&mut self, value: bool) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::LOAD_CACHE_ON_DISK, &value.to_string());
+    }
+
+    fn projection_view_display_settings(&self) -> Option<DisplaySettings> {
+        DisplaySettings::from_config_string(
+            &self.config_file.get(ids::pproj::GROUP, ids::pproj::VIEW_DISPLAY_SETTINGS)?
+        )
+    }
+
+    fn set_projection_view_display_settings(&mut self, value: &DisplaySettings) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::VIEW_DISPLAY_SETTINGS, &value.to_config_string());
+    }
+
+    fn auto_projection_view(&self) -> Option<bool> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::AUTO_PROJECTION_VIEW)?.parse::<bool>().ok()
+    }
+
+    fn set_auto_projection_view(&mut self, value: bool) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::AUTO_PROJECTION_VIEW, &value.to_string());
+    }
+
+    fn auto_projection_view_hint_shown(&self) -> Option<bool> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::AUTO_PROJECTION_VIEW_HINT_SHOWN)?.parse::<bool>().ok()
+    }
+
+    fn set_auto_projection_view_hint_shown(&mut self, value: bool) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::AUTO_PROJECTION_VIEW_HINT_SHOWN, &value.to_string());
+    }
+
+    fn single_map_export_path(&self) -> Option<PathBuf> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::SINGLE_MAP_EXPORT_PATH).map(PathBuf::from)
+    }
+
+    fn set_single_map_export_path(&mut self, value: &str) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::SINGLE_MAP_EXPORT_PATH, value);
+    }
+
+    fn default_planet(&self) -> Option<Planet> {
+        let value = self.config_file.get(ids::pproj::GROUP, ids::pproj::DEFAULT_PLANET)?;
+        Planet::iter().find(|p| p.name() == value)
+    }
+
+    fn set_default_planet(&mut self, value: Planet) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::DEFAULT_PLANET, value.name());
+    }
+
+    fn frame_crossfade(&self) -> Option<bool> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::FRAME_CROSSFADE)?.parse::<bool>().ok()
+    }
+
+    fn set_frame_crossfade(&mut self, value: bool) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::FRAME_CROSSFADE, &value.to_string());
+    }
+
+    fn frame_crossfade_ms(&self) -> Option<u32> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::FRAME_CROSSFADE_MS)?.parse::<u32>().ok()
+    }
+
+    fn set_frame_crossfade_ms(&mut self, value: u32) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::FRAME_CROSSFADE_MS, &value.to_string());
+    }
+
+    fn session_gap_minutes(&self) -> Option<u32> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::SESSION_GAP_MINUTES)?.parse::<u32>().ok()
+    }
+
+    fn set_session_gap_minutes(&mut self, value: u32) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::SESSION_GAP_MINUTES, &value.to_string());
+    }
+
+    fn source_overlay_settings(&self) -> Option<OverlaySettings> {
+        OverlaySettings::from_config_string(
+            &self.config_file.get(ids::pproj::GROUP, ids::pproj::SOURCE_OVERLAY_SETTINGS)?
+        )
+    }
+
+    fn set_source_overlay_settings(&mut self, value: &OverlaySettings) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::SOURCE_OVERLAY_SETTINGS, &value.to_config_string());
+    }
+
+    fn export_presets(&self) -> Option<Vec<ExportPreset>> {
+        let value = self.config_file.get(ids::pproj::GROUP, ids::pproj::EXPORT_PRESETS)?;
+        Some(ExportPreset::list_from_config_string(&value))
+    }
+
+    fn set_export_presets(&mut self, value: &[ExportPreset]) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::EXPORT_PRESETS, &ExportPreset::list_to_config_string(value));
+    }
+
+    fn diagnostic_rendering_option(&self) -> Option<bool> {
+        self.config_file.get(ids::pproj::GROUP, ids::pproj::DIAGNOSTIC_RENDERING_OPTION)?.parse::<bool>().ok()
+    }
+
+    fn set_diagnostic_rendering_option(&mut self, value: bool) {
+        self.set_value(ids::pproj::GROUP, ids::pproj::DIAGNOSTIC_RENDERING_OPTION, &value.to_string());
     }
 }
 
+impl GuiConfig for Configuration {
+    fn dialog_geometry(&self, dialog_title: &str) -> Option<DialogGeometry> {
+        let s = self.config_file.get(ids::dialogs::GROUP, &dialog_key(dialog_title))?;
+        let values: Vec<f32> = s.split(',').map(|v| v.trim().parse::<f32>()).collect::<Result<_, _>>().ok()?;
+        if values.len() != 4 { return None; }
+
+        Some(DialogGeometry{ position: [values[0], values[1]], size: [values[2], values[3]] })
+    }
+
+    fn set_dialog_geometry(&mut self, dialog_title: &str, value: &DialogGeometry) {
+        self.set_value(
+            ids::dialogs::GROUP,
+            &dialog_key(dialog_title),
+            &format!("{},{},{},{}", value.position[0], value.position[1], value.size[0], value.size[1])
+        );
+    }
+
+    fn max_render_failures(&self) -> Option<u32> {
+        self.config_file.get(ids::gui::GROUP, ids::gui::MAX_RENDER_FAILURES)?.parse::<u32>().ok()
+    }
+
+    fn set_max_render_failures(&mut self, value: u32) {
+        self.set_value(ids::gui::GROUP, ids::gui::MAX_RENDER_FAILURES, &value.to_string());
+    }
+
+    fn keyboard_shortcut(&self, action_id: &str) -> Option<String> {
+        self.config_file.get(ids::shortcuts::GROUP, action_id)
+    }
+
+    fn set_keyboard_shortcut(&mut self, action_id: &str, value: &str) {
+        self.set_value(ids::shortcuts::GROUP, action_id, value);
+    }
+
+    fn format_preferences(&self) -> Option<fmt::Preferences> {
+        fmt::Preferences::from_config_string(&self.config_file.get(ids::gui::GROUP, ids::gui::FORMAT_PREFERENCES)?)
+    }
+
+    fn set_format_preferences(&mut self, value: &fmt::Preferences) {
+        self.set_value(ids::gui::GROUP, ids::gui::FORMAT_PREFERENCES, &value.to_config_string());
+    }
+
+    fn log_to_file(&self) -> Option<bool> {
+        self.config_file.get(ids::gui::GROUP, ids::gui::LOG_TO_FILE)?.parse::<bool>().ok()
+    }
+
+    fn set_log_to_file(&mut self, value: bool) {
+        self.set_value(ids::gui::GROUP, ids::gui::LOG_TO_FILE, &value.to_string());
+    }
+
+    fn font_size(&self) -> Option<f32> {
+        self.config_file.get(ids::gui::GROUP, ids::gui::FONT_SIZE)?.parse::<f32>().ok()
+    }
+
+    fn set_font_size(&mut self, value: f32) {
+        self.set_value(ids::gui::GROUP, ids::gui::FONT_SIZE, &value.to_string());
+    }
+
+    fn num_backups(&self) -> Option<u32> {
+        self.config_file.get(ids::gui::GROUP, ids::gui::NUM_BACKUPS)?.parse::<u32>().ok()
+    }
+
+    fn set_num_backups(&mut self, value: u32) {
+        self.set_value(ids::gui::GROUP, ids::gui::NUM_BACKUPS, &value.to_string());
+    }
+
+    fn reduce_motion(&self) -> Option<bool> {
+        self.config_file.get(ids::gui::GROUP, ids::gui::REDUCE_MOTION)?.parse::<bool>().ok()
+    }
+
+    fn set_reduce_motion(&mut self, value: bool) {
+        self.set_value(ids::gui::GROUP, ids::gui::REDUCE_MOTION, &value.to_string());
+    }
+
+    fn gl_debug_output(&self) -> Option<bool> {
+        self.config_file.get(ids::gui::GROUP, ids::gui::GL_DEBUG_OUTPUT)?.parse::<bool>().ok()
+    }
+
+    fn set_gl_debug_output(&mut self, value: bool) {
+        self.set_value(ids::gui::GROUP, ids::gui::GL_DEBUG_OUTPUT, &value.to_string());
+    }
+}
+
+impl BackgroundConfig for Configuration {
+    fn background_priority(&self) -> Option<ThreadPriority> {
+        let value = self.config_file.get(ids::background::GROUP, ids::background::PRIORITY)?;
+        ThreadPriority::iter().find(|p| p.config_name() == value)
+    }
+
+    fn set_background_priority(&mut self, value: ThreadPriority) {
+        self.set_value(ids::background::GROUP, ids::background::PRIORITY, value.config_name());
+    }
+
+    fn max_write_rate_mib(&self) -> Option<f32> {
+        self.config_file.get(ids::background::GROUP, ids::background::MAX_WRITE_RATE_MIB)?.parse::<f32>().ok()
+    }
+
+    fn set_max_write_rate_mib(&mut self, value: f32) {
+        self.set_value(ids::background::GROUP, ids::background::MAX_WRITE_RATE_MIB, &value.to_string());
+    }
+}
+
+/// Converts dialog title to a configuration key.
+fn dialog_key(dialog_title: &str) -> String {
+    dialog_title.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
 impl Drop for Configuration {
+    /// Backstop for changes not yet flushed; also runs when unwinding after a panic (the configuration is owned
+    /// by the GUI state), so that the changes are not lost.
     fn drop(&mut self) {
+        if std::thread::panicking() { logging::log_info!("Saving configuration after a panic."); }
         if let Err(e) = self.store() {
-            eprintln!("Error saving configuration: {}.", e.to_string());
+            logging::log_error!("Error saving configuration: {}.", e);
         }
     }
 }
 
+/// Writes to a temporary file first, then renames it, so that a crash cannot leave a truncated file behind.
+fn write_atomically(ini: &Ini, path: &Path) -> Result<(), std::io::Error> {
+    let temp_path = path.with_extension(format!("ini.{}", TEMP_FILE_EXT));
+    std::fs::write(&temp_path, ini.writes())?;
+    std::fs::rename(&temp_path, path)
+}
+
+/// Returns path of backup `slot` of the configuration file `file_path` (e.g., "vislumino.ini.1").
+fn backup_path(file_path: &Path, slot: u32) -> PathBuf {
+    let mut path = file_path.as_os_str().to_owned();
+    path.push(format!(".{}", slot));
+
+    path.into()
+}
+
+/// Copies `file_path` to backup slot 1, moving the existing backups to the following slots; the backups in slot
+/// `count` and above (left over if the number of backups has been reduced) are removed. Apart from the removal, does
+/// nothing if `count` is 0 or the file does not exist.
+fn rotate_backups(file_path: &Path, count: u32) -> Result<(), std::io::Error> {
+    for slot in count.max(1)..=MAX_NUM_BACKUPS {
+        let path = backup_path(file_path, slot);
+        if path.exists() { std::fs::remove_file(&path)?; }
+    }
+
+    if count == 0 || !file_path.exists() { return Ok(()); }
+
+    for slot in (1..count).rev() {
+        let path = backup_path(file_path, slot);
+        if path.exists() { std::fs::rename(&path, backup_path(file_path, slot + 1))?; }
+    }
+
+    std::fs::copy(file_path, backup_path(file_path, 1))?;
+
+    Ok(())
+}
+
+/// Returns the existing backups of the configuration file `file_path`, the most recent first.
+fn list_backups(file_path: &Path) -> Vec<Backup> {
+    (1..=MAX_NUM_BACKUPS).filter_map(|slot| {
+        let path = backup_path(file_path, slot);
+        let metadata = std::fs::metadata(&path).ok()?;
+        if !metadata.is_file() { return None; }
+
+        Some(Backup{ slot, modified: metadata.modified().ok(), path })
+    }).collect()
+}
+
 fn config_file_path() -> PathBuf {
-    Path::new(&dirs::config_dir().or(Some(Path::new("").to_path_buf())).unwrap()).join(CONFIG_FILE_NAME)
+    config_dir_file_path(CONFIG_FILE_NAME)
+}
+
+/// Returns path of a file stored in the same directory as the configuration file.
+pub fn config_dir_file_path(file_name: &str) -> PathBuf {
+    Path::new(&dirs::config_dir().or(Some(Path::new("").to_path_buf())).unwrap()).join(file_name)
+}
+
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vislumino-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn second_instance_finds_the_lock_taken() {
+        let path = test_dir("instance-lock").join(CONFIG_FILE_NAME).with_extension(LOCK_FILE_EXT);
+
+        let (first, first_found_locked) = InstanceLock::acquire(path.clone());
+        assert!(first.is_some() && !first_found_locked);
+        let (second, second_found_locked) = InstanceLock::acquire(path.clone());
+        assert!(second.is_none() && second_found_locked);
+
+        drop(second);
+        assert!(path.exists());
+        drop(first);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", windows))]
+    fn lock_of_exited_instance_is_replaced() {
+        let path = test_dir("stale-lock").join(CONFIG_FILE_NAME).with_extension(LOCK_FILE_EXT);
+        // greater than the max. process ID on Linux
+        std::fs::write(&path, "999999999").unwrap();
+
+        let (lock, found_locked) = InstanceLock::acquire(path.clone());
+        assert!(lock.is_some() && !found_locked);
+        assert_eq!(std::process::id().to_string(), std::fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn concurrent_instances_keep_each_others_changes() {
+        let path = test_dir("merge").join(CONFIG_FILE_NAME);
+
+        let mut first = Configuration::from_file(path.clone());
+        let mut second = Configuration::from_file(path.clone());
+
+        first.set_load_path("/first/load");
+        second.set_projection_export_path("/second/export");
+
+        first.store().unwrap();
+        second.store().unwrap();
+
+        let merged = Configuration::from_file(path.clone());
+        assert_eq!(Some(PathBuf::from("/first/load")), merged.load_path());
+        assert_eq!(Some(PathBuf::from("/second/export")), merged.projection_export_path());
+    }
+
+    #[test]
+    fn later_store_overwrites_only_changed_keys() {
+        let path = test_dir("overwrite").join(CONFIG_FILE_NAME);
+
+        let mut first = Configuration::from_file(path.clone());
+        first.set_load_path("/first/load");
+        first.set_projection_export_path("/first/export");
+        first.store().unwrap();
+
+        let mut second = Configuration::from_file(path.clone());
+        second.set_load_path("/second/load");
+
+        first.set_projection_export_path("/first/export2");
+        first.store().unwrap();
+        second.store().unwrap();
+
+        let merged = Configuration::from_file(path.clone());
+        assert_eq!(Some(PathBuf::from("/second/load")), merged.load_path());
+        assert_eq!(Some(PathBuf::from("/first/export2")), merged.projection_export_path());
+    }
+
+    #[test]
+    fn stored_keys_are_not_written_again() {
+        let path = test_dir("stored-keys").join(CONFIG_FILE_NAME);
+
+        let mut first = Configuration::from_file(path.clone());
+        first.set_load_path("/first/load");
+        first.store().unwrap();
+
+        let mut second = Configuration::from_file(path.clone());
+        second.set_load_path("/second/load");
+        second.store().unwrap();
+
+        first.set_projection_export_path("/first/export");
+        first.store().unwrap();
+
+        let merged = Configuration::from_file(path.clone());
+        assert_eq!(Some(PathBuf::from("/second/load")), merged.load_path());
+        assert_eq!(Some(PathBuf::from("/first/export")), merged.projection_export_path());
+    }
+
+    #[test]
+    fn atomic_write_leaves_no_temporary_file() {
+        let path = test_dir("atomic").join(CONFIG_FILE_NAME);
+        std::fs::write(&path, "[PlanetaryProjection]\nLoadPath=/old\n").unwrap();
+
+        let mut config = Configuration::from_file(path.clone());
+        config.set_load_path("/new");
+        config.store().unwrap();
+
+        assert!(!path.with_extension(format!("ini.{}", TEMP_FILE_EXT)).exists());
+        assert_eq!(Some(PathBuf::from("/new")), Configuration::from_file(path.clone()).load_path());
+    }
+
+    #[test]
+    fn missing_file_means_first_run() {
+        let path = test_dir("first-run").join(CONFIG_FILE_NAME);
+
+        let mut config = Configuration::from_file(path.clone());
+        assert!(config.first_run());
+        config.set_default_planet(Planet::Mars);
+        config.store().unwrap();
+
+        let config = Configuration::from_file(path.clone());
+        assert!(!config.first_run());
+        assert!(config.default_planet() == Some(Planet::Mars));
+    }
+
+    fn slots(backups: &[Backup]) -> Vec<u32> { backups.iter().map(|backup| backup.slot).collect() }
+
+    fn backup_contents(path: &Path, slot: u32) -> String {
+        std::fs::read_to_string(backup_path(path, slot)).unwrap()
+    }
+
+    #[test]
+    fn backups_are_rotated() {
+        let path = test_dir("backup-rotation").join(CONFIG_FILE_NAME);
+        for version in 1..=4 {
+            std::fs::write(&path, format!("{}", version)).unwrap();
+            rotate_backups(&path, 3).unwrap();
+        }
+
+        assert_eq!(vec![1, 2, 3], slots(&list_backups(&path)));
+        assert_eq!("4", backup_contents(&path, 1));
+        assert_eq!("2", backup_contents(&path, 3));
+    }
+
+    #[test]
+    fn rotation_skips_missing_slots() {
+        let path = test_dir("backup-missing-slots").join(CONFIG_FILE_NAME);
+        std::fs::write(backup_path(&path, 1), "b").unwrap();
+        std::fs::write(backup_path(&path, 3), "a").unwrap();
+        std::fs::write(&path, "c").unwrap();
+
+        assert_eq!(vec![1, 3], slots(&list_backups(&path)));
+        rotate_backups(&path, 5).unwrap();
+        assert_eq!(vec![1, 2, 4], slots(&list_backups(&path)));
+        assert_eq!(["c", "b", "a"], [1, 2, 4].map(|slot| backup_contents(&path, slot)));
+    }
+
+    #[test]
+    fn reducing_count_removes_backups_above_it() {
+        let path = test_dir("backup-reduced-count").join(CONFIG_FILE_NAME);
+        for version in 1..=5 {
+            std::fs::write(&path, format!("{}", version)).unwrap();
+            rotate_backups(&path, 5).unwrap();
+        }
+        assert_eq!(vec![1, 2, 3, 4, 5], slots(&list_backups(&path)));
+
+        std::fs::write(&path, "6").unwrap();
+        rotate_backups(&path, 2).unwrap();
+        assert_eq!(vec![1, 2], slots(&list_backups(&path)));
+        assert_eq!(["6", "5"], [1, 2].map(|slot| backup_contents(&path, slot)));
+
+        rotate_backups(&path, 0).unwrap();
+        assert!(list_backups(&path).is_empty());
+    }
+
+    #[test]
+    fn no_backups_are_made_when_disabled() {
+        let path = test_dir("backup-disabled").join(CONFIG_FILE_NAME);
+        std::fs::write(&path, "").unwrap();
+
+        let mut config = Configuration::from_file(path.clone());
+        config.set_num_backups(0);
+        config.store().unwrap();
+        assert!(list_backups(&path).is_empty());
+    }
+
+    #[test]
+    fn store_backs_up_once_per_instance() {
+        let path = test_dir("backup-once").join(CONFIG_FILE_NAME);
+
+        let mut config = Configuration::from_file(path.clone());
+        config.set_load_decimation(2);
+        config.store().unwrap();
+        // there was no file to back up
+        assert!(list_backups(&path).is_empty());
+        drop(config);
+
+        let mut config = Configuration::from_file(path.clone());
+        config.set_load_decimation(3);
+        config.store().unwrap();
+        config.set_load_decimation(4);
+        config.store().unwrap();
+        assert_eq!(vec![1], slots(&config.backups()));
+    }
+
+    #[test]
+    fn backup_is_restored() {
+        let path = test_dir("backup-restore").join(CONFIG_FILE_NAME);
+
+        let mut config = Configuration::from_file(path.clone());
+        config.set_load_decimation(2);
+        config.store().unwrap();
+        drop(config);
+
+        let mut config = Configuration::from_file(path.clone());
+        config.set_load_decimation(7);
+        config.store().unwrap();
+
+        let backup = config.backups()[0].clone();
+        config.restore_backup(&backup).unwrap();
+        assert_eq!(Some(2), config.load_decimation());
+        drop(config);
+
+        assert_eq!(Some(2), Configuration::from_file(path.clone()).load_decimation());
+        // the replaced configuration has been backed up
+        assert!(backup_contents(&path, 1).contains("LoadDecimation=7"));
+    }
+
+    #[test]
+    fn corrupt_file_is_backed_up() {
+        let path = test_dir("corrupt").join(CONFIG_FILE_NAME);
+        std::fs::write(&path, [0xFFu8, 0xFE, 0x00, 0x5B]).unwrap();
+
+        let config = Configuration::from_file(path.clone());
+        assert_eq!(None, config.load_path());
+        assert!(path.with_extension(format!("ini.{}", BACKUP_FILE_EXT)).exists());
+    }
+
+    #[test]
+    fn new_projection_views_inherit_stored_display_settings() {
+        let path = test_dir("display-settings").join(CONFIG_FILE_NAME);
+
+        let config = Configuration::from_file(path.clone());
+        assert_eq!(DisplaySettings::default(), DisplaySettings::from_config(&config));
+        drop(config);
+
+        let mut settings = DisplaySettings::default();
+        settings.grid_shown = true;
+        settings.grid_color = [0.0, 1.0, 0.5, 0.4];
+        settings.grid_vert_spacing = 0.1;
+
+        let mut config = Configuration::from_file(path.clone());
+        config.set_projection_view_display_settings(&settings);
+        config.store().unwrap();
+        drop(config);
+
+        assert_eq!(settings, DisplaySettings::from_config(&Configuration::from_file(path.clone())));
+    }
+
+    #[test]
+    fn changes_are_saved_after_interval() {
+        let path = test_dir("save-interval").join(CONFIG_FILE_NAME);
+
+        let mut config = Configuration::with_path(path.clone());
+        assert!(path.with_extension(LOCK_FILE_EXT).exists());
+        let start = Instant::now();
+        config.set_load_path("/first");
+        config.flush_at(start + Duration::from_secs(10), false).unwrap();
+        // the second change does not postpone saving of the first
+        config.set_projection_export_path("/export");
+        config.flush_at(start + Duration::from_secs(20), false).unwrap();
+        assert!(!path.exists());
+        assert!(config.save_schedule.is_pending());
+
+        config.flush_at(start + SAVE_INTERVAL + Duration::from_secs(1), false).unwrap();
+        assert!(!config.save_schedule.is_pending());
+        let saved = Configuration::from_file(path.clone());
+        assert_eq!(Some(PathBuf::from("/first")), saved.load_path());
+        assert_eq!(Some(PathBuf::from("/export")), saved.projection_export_path());
+
+        drop(config);
+        assert!(!path.with_extension(LOCK_FILE_EXT).exists());
+    }
+
+    #[test]
+    fn flush_saves_immediately_only_if_changed() {
+        let path = test_dir("flush").join(CONFIG_FILE_NAME);
+
+        let mut config = Configuration::from_file(path.clone());
+        config.flush().unwrap();
+        assert!(!path.exists());
+
+        config.set_load_binning(2);
+        config.flush().unwrap();
+        assert_eq!(Some(2), Configuration::from_file(path.clone()).load_binning());
+
+        // modified by another instance in the meantime; nothing is pending here, so it is not overwritten
+        std::fs::write(&path, "[PlanetaryProjection]\nLoadBinning=3\n").unwrap();
+        config.flush().unwrap();
+        assert_eq!(Some(3), Configuration::from_file(path.clone()).load_binning());
+    }
+
+    #[test]
+    fn failed_save_is_retried_later() {
+        let dir = test_dir("save-failure");
+        // a directory in place of the file makes saving fail
+        let path = dir.join(CONFIG_FILE_NAME);
+        std::fs::create_dir_all(path.with_extension(format!("ini.{}", TEMP_FILE_EXT))).unwrap();
+
+        let mut config = Configuration::from_file(path.clone());
+        config.set_load_path("/path");
+        let now = Instant::now() + SAVE_INTERVAL;
+        assert!(config.flush_at(now, false).is_err());
+        assert!(config.save_schedule.is_pending());
+        assert!(!config.save_schedule.is_due(now));
+        assert!(config.save_schedule.is_due(now + SAVE_INTERVAL));
+
+        std::fs::remove_dir_all(path.with_extension(format!("ini.{}", TEMP_FILE_EXT))).unwrap();
+        config.flush().unwrap();
+        assert_eq!(Some(PathBuf::from("/path")), Configuration::from_file(path).load_path());
+    }
 }
@@ -0,0 +1,181 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Crash reports. A panic hook saves a report in the configuration directory; it is offered to the user
+//! on the next start.
+
+use crate::config;
+use crate::logging::{self, Entry};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const FILE_NAME: &str = "vislumino_crash_report.txt";
+
+/// Information about the OpenGL implementation (set once it has been gathered).
+static GL_INFO: OnceLock<String> = OnceLock::new();
+
+/// Contents of a crash report.
+pub struct Report<'a> {
+    pub version: &'a str,
+    pub thread: Option<&'a str>,
+    pub message: &'a str,
+    /// Source code location of the panic.
+    pub location: Option<String>,
+    pub backtrace: &'a str,
+    pub gl_info: Option<&'a str>,
+    pub log_entries: &'a [Entry]
+}
+
+impl Report<'_> {
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        text += "Vislumino crash report\n\n";
+        text += &format!("Version: {}\n", self.version.trim());
+        text += &format!("Thread: {}\n", self.thread.unwrap_or("(unnamed)"));
+        text += &format!("Message: {}\n", self.message);
+        text += &format!("Location: {}\n", self.location.as_deref().unwrap_or("(unknown)"));
+        text += &format!("OpenGL: {}\n", self.gl_info.unwrap_or("(not available)"));
+
+        text += "\nBacktrace:\n";
+        text += self.backtrace;
+        if !self.backtrace.ends_with('\n') { text.push('\n'); }
+
+        text += &format!("\nRecent log entries ({}):\n", self.log_entries.len());
+        for entry in self.log_entries {
+            text += &entry.to_text();
+            text.push('\n');
+        }
+
+        text
+    }
+}
+
+pub fn file_path() -> PathBuf { config::config_dir_file_path(FILE_NAME) }
+
+/// Returns the crash report saved by a previous session (if any).
+pub fn pending() -> Option<PathBuf> {
+    let path = file_path();
+    if path.is_file() { Some(path) } else { None }
+}
+
+pub fn set_gl_info(info: String) {
+    let _ = GL_INFO.set(info);
+}
+
+/// Installs a panic hook which (after the default one) saves a crash report and informs the user.
+/// Only the first panic is reported.
+pub fn install_hook(version: &'static str) {
+    static REPORTED: AtomicBool = AtomicBool::new(false);
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        if REPORTED.swap(true, Ordering::SeqCst) { return; }
+
+        // nothing here may panic (it would abort the process)
+        let message = match (info.payload().downcast_ref::<&str>(), info.payload().downcast_ref::<String>()) {
+            (Some(message), _) => *message,
+            (_, Some(message)) => message.as_str(),
+            _ => "(no message)"
+        };
+        let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+        let log_entries = logging::recent_entries();
+        let thread = std::thread::current();
+        let report = Report{
+            version,
+            thread: thread.name(),
+            message,
+            location: info.location().map(|location| location.to_string()),
+            backtrace: &backtrace,
+            gl_info: GL_INFO.get().map(|info| info.as_str()),
+            log_entries: &log_entries
+        };
+
+        let path = file_path();
+        let saved = std::fs::write(&path, report.to_text()).is_ok();
+        let text = if saved {
+            format!(
+                "Vislumino has encountered an unexpected error and will exit (settings will be saved if possible).\
+                \n\nA crash report has been saved to:\n{}",
+                path.to_string_lossy()
+            )
+        } else {
+            format!("Vislumino has encountered an unexpected error and will exit:\n{}", message)
+        };
+        let _ = native_dialog::MessageDialog::new()
+            .set_type(native_dialog::MessageType::Error)
+            .set_title("Vislumino")
+            .set_text(&text)
+            .show_alert();
+    }));
+}
+
+mod tests {
+    use super::*;
+    use crate::logging::Severity;
+    use std::time::Duration;
+
+    #[test]
+    fn report_contains_all_information() {
+        let log_entries = vec![
+            Entry{ time: Duration::from_millis(1500), severity: Severity::Info, message: "loading".into() },
+            Entry{ time: Duration::from_secs(3), severity: Severity::Error, message: "failed".into() }
+        ];
+        let report = Report{
+            version: "0.1.0 (commit abc)\n",
+            thread: Some("main"),
+            message: "index out of bounds",
+            location: Some("src/projection/source_view.rs:100:5".into()),
+            backtrace: "0: vislumino::main",
+            gl_info: Some("OpenGL 3.3"),
+            log_entries: &log_entries
+        };
+
+        let text = report.to_text();
+        assert!(text.contains("Version: 0.1.0 (commit abc)\n"));
+        assert!(text.contains("Thread: main\n"));
+        assert!(text.contains("Message: index out of bounds\n"));
+        assert!(text.contains("Location: src/projection/source_view.rs:100:5\n"));
+        assert!(text.contains("OpenGL: OpenGL 3.3\n"));
+        assert!(text.contains("Backtrace:\n0: vislumino::main\n"));
+        assert!(text.ends_with(&format!(
+            "Recent log entries (2):\n{}\n{}\n", log_entries[0].to_text(), log_entries[1].to_text()
+        )));
+    }
+
+    #[test]
+    fn missing_information_is_marked() {
+        let report = Report{
+            version: "0.1.0",
+            thread: None,
+            message: "",
+            location: None,
+            backtrace: "",
+            gl_info: None,
+            log_entries: &[]
+        };
+
+        let text = report.to_text();
+        assert!(text.contains("Thread: (unnamed)\n"));
+        assert!(text.contains("Location: (unknown)\n"));
+        assert!(text.contains("OpenGL: (not available)\n"));
+        assert!(text.ends_with("Recent log entries (0):\n"));
+    }
+}
@@ -17,6 +17,7 @@
 // along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
 //
 
+use crate::image_utils::BitDepth;
 use ga_image::PixelFormat;
 use glium::{CapabilitiesSource};
 
@@ -71,28 +72,46 @@ pub enum ProgramData {
     Projection(crate::projection::ProgramData)
 }
 
+/// Creates a texture with the bit depth of `image` (RGB8 or RGB16). Fails if the image exceeds the maximum texture
+/// size of `display` or has another pixel format.
 pub fn create_texture_from_image(image: &ga_image::Image, display: &glium::Display)
--> glium::Texture2d {
+-> Result<glium::Texture2d, Box<dyn std::error::Error>> {
     let max_texture_size = display.get_capabilities().max_texture_size as u32;
 
     if image.width() > max_texture_size || image.height() > max_texture_size {
-        panic!("image too big"); //TODO: handle gracefully
+        return Err(format!(
+            "image too large ({}x{} pixels; the maximum is {2}x{2})", image.width(), image.height(), max_texture_size
+        ).into());
     }
 
     //TODO: handle other formats
-    assert!(image.pixel_format() == PixelFormat::RGB8);
-
-    let texture = glium::Texture2d::with_format(
-        display,
-        glium::texture::RawImage2d{
-            data: std::borrow::Cow::<[u8]>::from(image.pixels::<u8>()),
-            width: image.width(),
-            height: image.height(),
-            format: glium::texture::ClientFormat::U8U8U8
-        },
-        glium::texture::UncompressedFloatFormat::U8U8U8,
-        glium::texture::MipmapsOption::NoMipmap
-    ).unwrap();
-
-    texture
+    let texture = match image.pixel_format() {
+        PixelFormat::RGB8 => glium::Texture2d::with_format(
+            display,
+            glium::texture::RawImage2d{
+                data: std::borrow::Cow::<[u8]>::from(image.pixels::<u8>()),
+                width: image.width(),
+                height: image.height(),
+                format: glium::texture::ClientFormat::U8U8U8
+            },
+            BitDepth::Eight.texture_format(),
+            glium::texture::MipmapsOption::NoMipmap
+        )?,
+
+        PixelFormat::RGB16 => glium::Texture2d::with_format(
+            display,
+            glium::texture::RawImage2d{
+                data: std::borrow::Cow::<[u16]>::from(image.pixels::<u16>()),
+                width: image.width(),
+                height: image.height(),
+                format: glium::texture::ClientFormat::U16U16U16
+            },
+            BitDepth::Sixteen.texture_format(),
+            glium::texture::MipmapsOption::NoMipmap
+        )?,
+
+        other => return Err(format!("unsupported pixel format: {:?}", other).into())
+    };
+
+    Ok(texture)
 }
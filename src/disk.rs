@@ -19,9 +19,44 @@
 
 use cgmath::{EuclideanSpace, Point2};
 
-/// Returns (center, diameter).
+/// Values up to this percentage of the image's maximum are treated as background by `find_planetary_disk`.
+pub const DEFAULT_THRESHOLD_PERCENT: f32 = 2.0;
+
+/// Minimum number of limb pixels required to fit the disk of a planet extending outside the image.
+const MIN_LIMB_POINTS: usize = 16;
+
+/// Values (8-bit) at or above this level are treated as saturated (clipped).
+pub const SATURATION_LEVEL: u8 = 254;
+
+/// Fraction of saturated pixels within the disk above which the user is warned.
+pub const SATURATION_WARNING_FRACTION: f32 = 0.05;
+
+/// Half-width (radians) of the wedges around the equatorial axis over which the limb profile is averaged.
+const AXIS_HALF_WEDGE: f32 = 3.0 * std::f32::consts::PI / 180.0;
+
+/// Number of sampled directions on each side of the equatorial axis within `AXIS_HALF_WEDGE`.
+const AXIS_HALF_WEDGE_STEPS: i32 = 3;
+
+/// Minimum extent (in pixels) of the mask beyond the steepest descent for the mask to be considered to include
+/// a halo.
+const MIN_HALO_WIDTH: f32 = 1.5;
+
+/// Returns (center, diameter). The disk may extend outside the image (then the center may lie outside, too).
 pub fn find_planetary_disk(image: &ga_image::Image) -> Result<(Point2<f32>, f32), ()> {
+    find_planetary_disk_with_threshold(image, DEFAULT_THRESHOLD_PERCENT)
+}
+
+/// Returns (center, equatorial diameter). Values up to `threshold_percent` (0-100) of the image's maximum are treated
+/// as background; a higher threshold helps with a bright background or a halo around the disk.
+///
+/// If the disk extends outside the image, a circle is fitted to the visible part of the limb.
+pub fn find_planetary_disk_with_threshold(
+    image: &ga_image::Image,
+    threshold_percent: f32
+) -> Result<(Point2<f32>, f32), ()> {
     let mut image8 = image.convert_pix_fmt(ga_image::PixelFormat::Mono8, None);
+    // kept for locating the limb (`image8` becomes a mask)
+    let values = image8.clone();
 
     let mut max_value = 0;
     for y in 0..image8.height() {
@@ -31,11 +66,14 @@ pub fn find_planetary_disk(image: &ga_image::Image) -> Result<(Point2<f32>, f32)
         }
     }
 
-    // cut the lower 2% of signal to prevent bright background's effect on centroid calculation
+    if max_value == 0 { return Err(()); } // blank image
+
+    // cut the lower part of signal to prevent bright background's effect on centroid calculation
+    let threshold = threshold_percent.max(0.0).min(100.0) / 100.0 * max_value as f32;
     for y in 0..image.height() {
         let line = image8.line_mut::<u8>(y);
         for value in line {
-            if *value as i32 <= 2i32 * max_value as i32 / 100 {
+            if *value as f32 <= threshold {
                 *value = 0;
             } else {
                 *value = 0xFF;
@@ -57,6 +95,7 @@ pub fn find_planetary_disk(image: &ga_image::Image) -> Result<(Point2<f32>, f32)
 
     let mut r_lower_bound = 2;
     let mut r_upper_bound = *centroid_distances_to_img_boundaries.iter().min().unwrap();
+    if r_upper_bound <= r_lower_bound { return fit_limb_arc(&image8); }
 
     let is_outside_disk = |circle: &[Point2<i32>]| {
         let pixels = image8.pixels::<u8>();
@@ -67,90 +106,515 @@ pub fn find_planetary_disk(image: &ga_image::Image) -> Result<(Point2<f32>, f32)
         true
     };
 
-    let min_circle = rasterize_circle(c_int, r_lower_bound as u32);
-    if is_outside_disk(&min_circle) { return Err(()); } // disk is less than 2 pixels in radius
+    let mut rasterizer = CircleRasterizer::default();
+
+    // disk is less than 2 pixels in radius
+    if is_outside_disk(rasterizer.rasterize(c_int, r_lower_bound as u32)) { return Err(()); }
 
-    let max_circle = rasterize_circle(c_int, r_upper_bound as u32);
-    if !is_outside_disk(&max_circle) { return Err(()); } // disk extends outside the image
+    // disk extends outside the image
+    if !is_outside_disk(rasterizer.rasterize(c_int, r_upper_bound as u32)) { return fit_limb_arc(&image8); }
 
-    let radius;
+    let mask_radius;
 
     loop {
         let r_delta = (r_upper_bound - r_lower_bound) / 2;
         if r_delta == 0 {
-            radius = r_lower_bound;
+            mask_radius = r_lower_bound;
             break;
         }
 
         let r_mid = r_lower_bound + r_delta;
-        let mid_circle = rasterize_circle(c_int, r_mid);
-        if !is_outside_disk(&mid_circle) {
+        if !is_outside_disk(rasterizer.rasterize(c_int, r_mid)) {
             r_lower_bound = r_mid;
         } else {
             r_upper_bound = r_mid;
         }
     }
 
-    Ok((centroid, (radius * 2) as f32))
+    // the mask may include a halo (e.g., bloom around an overexposed disk); the limb is where the brightness drops
+    // most steeply along the equator (the mask's longer axis)
+    let max_radius = (mask_radius + 2).min(*centroid_distances_to_img_boundaries.iter().min().unwrap());
+    let profile = axis_profile(&values, centroid, major_axis_angle(&image8, centroid), max_radius);
+    let radius = match steepest_descent_radius(&profile) {
+        Some(radius) if mask_radius as f32 - radius >= MIN_HALO_WIDTH => radius,
+        // no halo: the mask ends at the limb
+        _ => mask_radius as f32
+    };
+
+    Ok((centroid, 2.0 * radius))
 }
 
-// Returns circle points clockwise (in a right-handed coordinate system), starting from the leftmost point.
-fn rasterize_circle(center: Point2<i32>, radius: u32) -> Vec<Point2<i32>> {
-    let mut octant = vec![];
+/// Returns the angle (radians, from the X axis) of the longer principal axis of the non-zero pixels of `mask`
+/// (Mono8) around `centroid`, i.e., of the equator of a flattened disk.
+fn major_axis_angle(mask: &ga_image::Image, centroid: Point2<f32>) -> f32 {
+    let (mut mxx, mut myy, mut mxy) = (0.0f64, 0.0f64, 0.0f64);
+    for y in 0..mask.height() {
+        let dy = (y as f32 - centroid.y) as f64;
+        for (x, value) in mask.line::<u8>(y).iter().take(mask.width() as usize).enumerate() {
+            if *value == 0 { continue; }
+            let dx = (x as f32 - centroid.x) as f64;
+            mxx += dx * dx;
+            myy += dy * dy;
+            mxy += dx * dy;
+        }
+    }
+
+    (0.5 * (2.0 * mxy).atan2(mxx - myy)) as f32
+}
 
-    let mut point = Point2{ x: -(radius as i32), y: 0 };
+/// Returns the value of `image` (Mono8) at `pos` (bilinear interpolation); `None` outside the image.
+fn sample(image: &ga_image::Image, pos: Point2<f32>) -> Option<f32> {
+    if pos.x < 0.0 || pos.y < 0.0 { return None; }
+    let (x0, y0) = (pos.x.floor() as u32, pos.y.floor() as u32);
+    if x0 + 1 >= image.width() || y0 + 1 >= image.height() { return None; }
+    let (fx, fy) = (pos.x - x0 as f32, pos.y - y0 as f32);
 
-    // is `Some` if the point having x=y belongs to the circle
-    let mut diagonal_point: Option<Point2<i32>> = None;
+    let line0 = image.line::<u8>(y0);
+    let line1 = image.line::<u8>(y0 + 1);
+    let (x0, x1) = (x0 as usize, x0 as usize + 1);
+    let top = line0[x0] as f32 * (1.0 - fx) + line0[x1] as f32 * fx;
+    let bottom = line1[x0] as f32 * (1.0 - fx) + line1[x1] as f32 * fx;
 
-    while -point.x > point.y {
-        point.x += 1;
-        point.y += 1;
-        if point.x.pow(2) + point.y.pow(2) < radius.pow(2) as i32 {
-            point.x -= 1;
+    Some(top * (1.0 - fy) + bottom * fy)
+}
+
+/// Returns mean values of `image` (Mono8) at distances 0, 1, ..., `max_radius` from `center` along both directions
+/// of the axis at `angle` (radians), within `AXIS_HALF_WEDGE` of it. The limb of a flattened disk is then found at
+/// its equatorial radius (which averaging over whole rings would place at the polar one).
+fn axis_profile(image: &ga_image::Image, center: Point2<f32>, angle: f32, max_radius: u32) -> Vec<f32> {
+    let mut directions = vec![];
+    for i in -AXIS_HALF_WEDGE_STEPS..=AXIS_HALF_WEDGE_STEPS {
+        let a = angle + i as f32 * AXIS_HALF_WEDGE / AXIS_HALF_WEDGE_STEPS as f32;
+        directions.push((a.cos(), a.sin()));
+        directions.push((-a.cos(), -a.sin()));
+    }
+
+    (0..=max_radius).map(|r| {
+        let r = r as f32;
+        let (sum, count) = directions.iter()
+            .filter_map(|(dx, dy)| sample(image, Point2{ x: center.x + r * dx, y: center.y + r * dy }))
+            .fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+        if count > 0 { sum / count as f32 } else { 0.0 }
+    }).collect()
+}
+
+/// Returns the radius (with subpixel precision) at which `profile` decreases most steeply; `None` if it does not
+/// decrease anywhere.
+fn steepest_descent_radius(profile: &[f32]) -> Option<f32> {
+    if profile.len() < 3 { return None; }
+
+    // `descents[i]` corresponds to radius `i + 1`
+    let descents: Vec<f32> = profile.windows(3).map(|w| (w[0] - w[2]) / 2.0).collect();
+    let (idx, max_descent) = descents.iter().copied().enumerate()
+        .fold((0, 0.0), |best, (i, d)| if d > best.1 { (i, d) } else { best });
+    if max_descent <= 0.0 { return None; }
+
+    // parabola through the peak and its neighbors
+    let mut offset = 0.0;
+    if idx > 0 && idx + 1 < descents.len() {
+        let (prev, next) = (descents[idx - 1], descents[idx + 1]);
+        let denominator = prev - 2.0 * max_descent + next;
+        if denominator < 0.0 { offset = (0.5 * (prev - next) / denominator).clamp(-0.5, 0.5); }
+    }
+
+    Some((idx + 1) as f32 + offset)
+}
+
+/// Returns the fraction of pixels within the disk which are saturated (see `SATURATION_LEVEL`).
+pub fn saturated_fraction(image: &ga_image::Image, center: Point2<f32>, diameter: f32) -> f32 {
+    let image8 = image.convert_pix_fmt(ga_image::PixelFormat::Mono8, None);
+    let radius = diameter / 2.0;
+
+    let mut num_inside = 0usize;
+    let mut num_saturated = 0usize;
+    for y in 0..image8.height() {
+        let dy = y as f32 - center.y;
+        if dy.abs() > radius { continue; }
+        for (x, value) in image8.line::<u8>(y).iter().take(image8.width() as usize).enumerate() {
+            let dx = x as f32 - center.x;
+            if dx * dx + dy * dy <= radius * radius {
+                num_inside += 1;
+                if *value >= SATURATION_LEVEL { num_saturated += 1; }
+            }
         }
-        if point.x.abs() == point.y.abs() {
-            diagonal_point = Some(point);
-        } else {
-            octant.push(point);
-        }
-    }
-
-    let mut points = vec![];
-
-    // Order of filling octants:
-    //
-    //               y
-    //               ^
-    //               |
-    //         oct2  |  oct3
-    //        +      ^       +
-    //     oct_1     |     oct4
-    // ----+---------0------------+----->x
-    //     oct8      |     oct5
-    //        +      |       +
-    //         oct7  |  oct6
-    //               |
-
-
-    points.push(Point2{ x: -(radius as i32), y: 0 });
-    points.extend_from_slice(&octant);                                         // octant 1
-    match diagonal_point { Some(ref p) => points.push(*p), _ => () }
-    points.extend(octant.iter().rev().map(|p| Point2{ x: -p.y, y: -p.x }));    // octant 2
-    points.push(Point2{ x: 0, y: radius as i32 });
-    points.extend(octant.iter().map(|p| Point2{ x: p.y, y: -p.x })); // octant 3
-    match diagonal_point { Some(ref p) => points.push(Point2{ x: -p.x, y: p.y }), _ => () }
-    points.extend(octant.iter().rev().map(|p| Point2{ x: -p.x, y: p.y }));     // octant 4
-    points.push(Point2{ x: radius as i32, y: 0 });
-    points.extend(octant.iter().map(|p| Point2{ x: -p.x, y: -p.y }));          // octant 5
-    match diagonal_point { Some(ref p) => points.push(Point2{ x: -p.x, y: -p.y }), _ => () }
-    points.extend(octant.iter().rev().map(|p| Point2{ x: p.y, y: p.x }));      // octant 6
-    points.push(Point2{ x: 0, y: -(radius as i32) });
-    points.extend(octant.iter().map(|p| Point2{ x: -p.y, y: p.x }));           // octant 7
-    match diagonal_point { Some(ref p) => points.push(Point2{ x: p.x, y: -p.y }), _ => () }
-    points.extend(octant.iter().rev().map(|p| Point2{ x: p.x, y: -p.y }));     // octant 8
-
-    for p in &mut points { *p += center.to_vec(); }
-
-    points
+    }
+
+    if num_inside == 0 { 0.0 } else { num_saturated as f32 / num_inside as f32 }
+}
+
+/// Fits a circle to the limb of the disk in `mask` (Mono8, 0 = background, 0xFF = disk), ignoring the disk's parts
+/// clipped by image edges. Returns (center, diameter).
+fn fit_limb_arc(mask: &ga_image::Image) -> Result<(Point2<f32>, f32), ()> {
+    let width = mask.width() as i32;
+    let height = mask.height() as i32;
+    let value = |x: i32, y: i32| mask.line::<u8>(y as u32)[x as usize];
+
+    // disk pixels adjacent to background; pixels on image edges are skipped, as the disk may be clipped there
+    let mut limb = vec![];
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            if value(x, y) != 0
+                && (value(x - 1, y) == 0 || value(x + 1, y) == 0 || value(x, y - 1) == 0 || value(x, y + 1) == 0) {
+                limb.push(Point2{ x: x as f64, y: y as f64 });
+            }
+        }
+    }
+
+    let (center, radius) = fit_circle(&limb).ok_or(())?;
+    if radius < 2.0 { return Err(()); }
+
+    Ok((center.cast::<f32>().unwrap(), 2.0 * radius as f32))
+}
+
+/// Returns (center, radius) of the least-squares (algebraic) circle fit to `points`; `None` if the points are too few
+/// or collinear.
+fn fit_circle(points: &[Point2<f64>]) -> Option<(Point2<f64>, f64)> {
+    if points.len() < MIN_LIMB_POINTS { return None; }
+
+    // coordinates relative to the mean position for numerical stability
+    let mean = Point2::centroid(points);
+
+    // minimizes Σ(x² + y² + a·x + b·y + c)²; normal equations M·[a, b, c] = v
+    let mut m = [[0.0f64; 3]; 3];
+    let mut v = [0.0f64; 3];
+    for point in points {
+        let (x, y) = (point.x - mean.x, point.y - mean.y);
+        let row = [x, y, 1.0];
+        let z = -(x * x + y * y);
+        for i in 0..3 {
+            for j in 0..3 { m[i][j] += row[i] * row[j]; }
+            v[i] += row[i] * z;
+        }
+    }
+
+    let det3 = |m: &[[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+
+    let det = det3(&m);
+    if det.abs() <= 1.0e-12 * m[0][0] * m[1][1] * m[2][2] { return None; }
+
+    // Cramer's rule
+    let mut solution = [0.0; 3];
+    for (k, value) in solution.iter_mut().enumerate() {
+        let mut mk = m;
+        for i in 0..3 { mk[i][k] = v[i]; }
+        *value = det3(&mk) / det;
+    }
+    let [a, b, c] = solution;
+
+    let center = Point2{ x: -a / 2.0, y: -b / 2.0 };
+    let r_sq = center.x * center.x + center.y * center.y - c;
+    if r_sq <= 0.0 { return None; }
+
+    Some((Point2{ x: center.x + mean.x, y: center.y + mean.y }, r_sq.sqrt()))
+}
+
+/// Rasterizes circles into buffers reused between calls (`find_planetary_disk` rasterizes a circle in every step
+/// of its search).
+#[derive(Default)]
+struct CircleRasterizer {
+    octant: Vec<Point2<i32>>,
+    points: Vec<Point2<i32>>
+}
+
+impl CircleRasterizer {
+    /// Returns circle points clockwise (in a right-handed coordinate system), starting from the leftmost point.
+    fn rasterize(&mut self, center: Point2<i32>, radius: u32) -> &[Point2<i32>] {
+        let octant = &mut self.octant;
+        let points = &mut self.points;
+        octant.clear();
+        points.clear();
+
+        let mut point = Point2{ x: -(radius as i32), y: 0 };
+
+        // is `Some` if the point having x=y belongs to the circle
+        let mut diagonal_point: Option<Point2<i32>> = None;
+
+        while -point.x > point.y {
+            point.x += 1;
+            point.y += 1;
+            if point.x.pow(2) + point.y.pow(2) < radius.pow(2) as i32 {
+                point.x -= 1;
+            }
+            if point.x.abs() == point.y.abs() {
+                diagonal_point = Some(point);
+            } else {
+                octant.push(point);
+            }
+        }
+
+        // Order of filling octants:
+        //
+        //               y
+        //               ^
+        //               |
+        //         oct2  |  oct3
+        //        +      ^       +
+        //     oct_1     |     oct4
+        // ----+---------0------------+----->x
+        //     oct8      |     oct5
+        //        +      |       +
+        //         oct7  |  oct6
+        //               |
+
+
+        points.push(Point2{ x: -(radius as i32), y: 0 });
+        points.extend_from_slice(octant);                                          // octant 1
+        match diagonal_point { Some(ref p) => points.push(*p), _ => () }
+        points.extend(octant.iter().rev().map(|p| Point2{ x: -p.y, y: -p.x }));    // octant 2
+        points.push(Point2{ x: 0, y: radius as i32 });
+        points.extend(octant.iter().map(|p| Point2{ x: p.y, y: -p.x }));           // octant 3
+        match diagonal_point { Some(ref p) => points.push(Point2{ x: -p.x, y: p.y }), _ => () }
+        points.extend(octant.iter().rev().map(|p| Point2{ x: -p.x, y: p.y }));     // octant 4
+        points.push(Point2{ x: radius as i32, y: 0 });
+        points.extend(octant.iter().map(|p| Point2{ x: -p.x, y: -p.y }));          // octant 5
+        match diagonal_point { Some(ref p) => points.push(Point2{ x: -p.x, y: -p.y }), _ => () }
+        points.extend(octant.iter().rev().map(|p| Point2{ x: p.y, y: p.x }));      // octant 6
+        points.push(Point2{ x: 0, y: -(radius as i32) });
+        points.extend(octant.iter().map(|p| Point2{ x: -p.y, y: p.x }));           // octant 7
+        match diagonal_point { Some(ref p) => points.push(Point2{ x: p.x, y: -p.y }), _ => () }
+        points.extend(octant.iter().rev().map(|p| Point2{ x: p.x, y: -p.y }));     // octant 8
+
+        for p in points.iter_mut() { *p += center.to_vec(); }
+
+        &self.points
+    }
+}
+
+mod tests {
+    use super::*;
+    use ga_image::{Image, PixelFormat};
+
+    /// Returns a Mono8 image with `background` value and a disk of `disk_value`.
+    fn disk_image(width: u32, height: u32, center: [f32; 2], radius: f32, disk_value: u8, background: u8) -> Image {
+        let mut image = Image::new(width, height, None, PixelFormat::Mono8, None, true);
+        for y in 0..height {
+            for (x, value) in image.line_mut::<u8>(y).iter_mut().take(width as usize).enumerate() {
+                let (dx, dy) = (x as f32 - center[0], y as f32 - center[1]);
+                *value = if dx * dx + dy * dy <= radius * radius { disk_value } else { background };
+            }
+        }
+
+        image
+    }
+
+    #[test]
+    fn finds_disk_on_dark_background() {
+        let image = disk_image(120, 100, [50.0, 45.0], 20.0, 200, 0);
+
+        let (center, diameter) = find_planetary_disk(&image).unwrap();
+        assert!((center.x - 50.0).abs() < 0.5, "center.x = {}", center.x);
+        assert!((center.y - 45.0).abs() < 0.5, "center.y = {}", center.y);
+        assert!((diameter - 40.0).abs() <= 2.0, "diameter = {}", diameter);
+    }
+
+    #[test]
+    fn bright_background_requires_higher_threshold() {
+        // background at 5% of the disk's brightness
+        let image = disk_image(120, 100, [60.0, 50.0], 25.0, 200, 10);
+
+        // with the default threshold, the background is taken for a disk extending outside the image
+        assert!(find_planetary_disk(&image).is_err());
+
+        let (center, diameter) = find_planetary_disk_with_threshold(&image, 10.0).unwrap();
+        assert!((center.x - 60.0).abs() < 0.5, "center.x = {}", center.x);
+        assert!((center.y - 50.0).abs() < 0.5, "center.y = {}", center.y);
+        assert!((diameter - 50.0).abs() <= 2.0, "diameter = {}", diameter);
+    }
+
+    #[test]
+    fn threshold_above_disk_brightness_finds_nothing() {
+        let mut image = disk_image(120, 100, [60.0, 50.0], 25.0, 100, 0);
+        // a single bright pixel (e.g., a star) sets the maximum
+        image.line_mut::<u8>(5)[5] = 255;
+
+        assert!(find_planetary_disk(&image).is_ok());
+        assert!(find_planetary_disk_with_threshold(&image, 50.0).is_err());
+    }
+
+    fn assert_disk(expected_center: [f32; 2], expected_diameter: f32, image: &Image) {
+        let (center, diameter) = find_planetary_disk(image).unwrap();
+        assert!((center.x - expected_center[0]).abs() < 1.0, "center.x = {}", center.x);
+        assert!((center.y - expected_center[1]).abs() < 1.0, "center.y = {}", center.y);
+        assert!((diameter - expected_diameter).abs() <= 2.0, "diameter = {}", diameter);
+    }
+
+    #[test]
+    fn finds_disk_clipped_by_image_edge() {
+        // a third of the disk outside the left edge
+        assert_disk([10.0, 50.0], 60.0, &disk_image(120, 100, [10.0, 50.0], 30.0, 200, 0));
+        // clipped by the bottom and right edges
+        assert_disk([105.0, 90.0], 50.0, &disk_image(120, 100, [105.0, 90.0], 25.0, 200, 0));
+    }
+
+    #[test]
+    fn finds_disk_with_center_outside_image() {
+        assert_disk([-8.0, 40.0], 60.0, &disk_image(120, 100, [-8.0, 40.0], 30.0, 200, 0));
+    }
+
+    #[test]
+    fn circle_is_fitted_to_arc() {
+        let center = Point2{ x: 30.5, y: -12.25 };
+        let radius = 40.0;
+        // a quarter of the circle
+        let points: Vec<Point2<f64>> = (0..50).map(|i| {
+            let angle = i as f64 / 50.0 * std::f64::consts::FRAC_PI_2;
+            Point2{ x: center.x + radius * angle.cos(), y: center.y + radius * angle.sin() }
+        }).collect();
+
+        let (fitted_center, fitted_radius) = fit_circle(&points).unwrap();
+        assert!((fitted_center.x - center.x).abs() < 1.0e-6 && (fitted_center.y - center.y).abs() < 1.0e-6);
+        assert!((fitted_radius - radius).abs() < 1.0e-6);
+
+        let collinear: Vec<Point2<f64>> = (0..50).map(|i| Point2{ x: i as f64, y: 2.0 * i as f64 }).collect();
+        assert!(fit_circle(&collinear).is_none());
+    }
+
+    /// Returns a Mono8 image of a saturated disk surrounded by an exponentially decaying halo of `halo_value`
+    /// (at the limb) and `halo_scale` (in pixels).
+    fn bloomed_disk_image(size: u32, center: [f32; 2], radius: f32, halo_value: f32, halo_scale: f32) -> Image {
+        let mut image = Image::new(size, size, None, PixelFormat::Mono8, None, true);
+        for y in 0..size {
+            for (x, value) in image.line_mut::<u8>(y).iter_mut().take(size as usize).enumerate() {
+                let r = ((x as f32 - center[0]).powi(2) + (y as f32 - center[1]).powi(2)).sqrt();
+                *value = if r <= radius { 255 } else { (halo_value * (-(r - radius) / halo_scale).exp()) as u8 };
+            }
+        }
+
+        image
+    }
+
+    #[test]
+    fn limb_is_found_despite_bloom() {
+        // the halo stays above the default threshold up to ~20 pixels beyond the limb
+        for (radius, halo_value, halo_scale) in [(20.0, 80.0, 8.0), (30.0, 120.0, 5.0), (25.5, 60.0, 12.0)] {
+            let image = bloomed_disk_image(160, [80.0, 75.0], radius, halo_value, halo_scale);
+            let (center, diameter) = find_planetary_disk(&image).unwrap();
+            assert!((center.x - 80.0).abs() < 0.5 && (center.y - 75.0).abs() < 0.5, "center = {:?}", center);
+            assert!((diameter - 2.0 * radius).abs() <= 1.5, "radius = {}, diameter = {}", radius, diameter);
+        }
+    }
+
+    /// Returns a Mono8 image of a disk flattened to `polar_radius`, with the equator at `angle` (radians) from
+    /// the X axis. The disk is limb-darkened by `limb_darkening` (0-1, brightness at the limb: 255 · (1 - ld)),
+    /// surrounded by a halo as in `bloomed_disk_image`.
+    fn flattened_disk_image(
+        size: u32,
+        center: [f32; 2],
+        equatorial_radius: f32,
+        polar_radius: f32,
+        angle: f32,
+        limb_darkening: f32,
+        halo: (f32, f32)
+    ) -> Image {
+        let (halo_value, halo_scale) = halo;
+        let mut image = Image::new(size, size, None, PixelFormat::Mono8, None, true);
+        for y in 0..size {
+            for (x, value) in image.line_mut::<u8>(y).iter_mut().take(size as usize).enumerate() {
+                let (dx, dy) = (x as f32 - center[0], y as f32 - center[1]);
+                let (u, v) = (dx * angle.cos() + dy * angle.sin(), -dx * angle.sin() + dy * angle.cos());
+                // normalized elliptical distance (1 at the limb)
+                let q = ((u / equatorial_radius).powi(2) + (v / polar_radius).powi(2)).sqrt();
+                *value = if q <= 1.0 {
+                    (255.0 * (1.0 - limb_darkening * (1.0 - (1.0 - q * q).sqrt()))) as u8
+                } else {
+                    (halo_value * (-(q - 1.0) * equatorial_radius / halo_scale).exp()) as u8
+                };
+            }
+        }
+
+        image
+    }
+
+    #[test]
+    fn limb_of_flattened_disk_is_found_at_equatorial_radius() {
+        for angle in [0.0, 0.5, 1.2] {
+            for limb_darkening in [0.0, 0.3, 0.6] {
+                for halo in [(0.0, 1.0), (80.0, 8.0)] {
+                    let image = flattened_disk_image(300, [150.0, 148.0], 100.0, 93.5, angle, limb_darkening, halo);
+                    let (center, diameter) = find_planetary_disk(&image).unwrap();
+                    let case = format!("angle = {}, limb darkening = {}, halo = {:?}", angle, limb_darkening, halo);
+                    assert!((center.x - 150.0).abs() < 0.5 && (center.y - 148.0).abs() < 0.5, "{}: {:?}", case, center);
+                    // the polar diameter is 187
+                    assert!((diameter - 200.0).abs() <= 2.0, "{}: diameter = {}", case, diameter);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn steepest_descent_is_located_with_subpixel_precision() {
+        // symmetric drop centered at radius 3
+        assert_eq!(Some(3.0), steepest_descent_radius(&[10.0, 10.0, 9.0, 5.0, 1.0, 0.0, 0.0]));
+
+        // steeper on the inner side: the peak moves inwards
+        let radius = steepest_descent_radius(&[10.0, 10.0, 8.0, 4.0, 1.0, 0.5, 0.0]).unwrap();
+        assert!(radius > 2.5 && radius < 3.0, "radius = {}", radius);
+
+        assert_eq!(None, steepest_descent_radius(&[1.0, 2.0, 3.0, 4.0]));
+        assert_eq!(None, steepest_descent_radius(&[1.0, 1.0]));
+    }
+
+    #[test]
+    fn saturated_fraction_covers_only_the_disk() {
+        let image = bloomed_disk_image(100, [50.0, 50.0], 20.0, 80.0, 8.0);
+        assert!((saturated_fraction(&image, Point2{ x: 50.0, y: 50.0 }, 40.0) - 1.0).abs() < 1.0e-6);
+
+        // a disk twice as large: a quarter of its area is saturated
+        let fraction = saturated_fraction(&image, Point2{ x: 50.0, y: 50.0 }, 80.0);
+        assert!((fraction - 0.25).abs() < 0.02, "fraction = {}", fraction);
+
+        let image = disk_image(100, 100, [50.0, 50.0], 20.0, 200, 0);
+        assert_eq!(0.0, saturated_fraction(&image, Point2{ x: 50.0, y: 50.0 }, 40.0));
+    }
+
+    #[test]
+    fn blank_image_has_no_disk() {
+        let image = Image::new(64, 64, None, PixelFormat::Mono8, None, true);
+        assert!(find_planetary_disk(&image).is_err());
+    }
+
+    #[test]
+    fn reused_rasterizer_gives_the_same_circles() {
+        let center = Point2{ x: 500, y: 480 };
+        let mut rasterizer = CircleRasterizer::default();
+        let large = rasterizer.rasterize(center, 300).to_vec();
+        let small = rasterizer.rasterize(Point2{ x: -3, y: 7 }, 5).to_vec();
+
+        assert_eq!(small, CircleRasterizer::default().rasterize(Point2{ x: -3, y: 7 }, 5));
+        assert_eq!(large, CircleRasterizer::default().rasterize(center, 300));
+        assert_eq!(Point2{ x: 200, y: 480 }, large[0]);
+        for p in &large {
+            let r = (((p.x - center.x).pow(2) + (p.y - center.y).pow(2)) as f32).sqrt();
+            assert!((r - 300.0).abs() <= 1.0, "point {:?} at distance {}", p, r);
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn perf_find_planetary_disk() {
+        let image = disk_image(1024, 1024, [500.0, 530.0], 300.0, 200, 0);
+        crate::bench::measure("find_planetary_disk (1024x1024)", 20, || find_planetary_disk(&image).unwrap())
+            .assert_within(std::time::Duration::from_millis(200));
+    }
+
+    #[test]
+    #[ignore]
+    fn perf_rasterize_circle() {
+        const RADIUS: u32 = 5000;
+        let center = Point2{ x: 0, y: 0 };
+
+        // allocating new buffers for every circle (as before `CircleRasterizer` was introduced)
+        crate::bench::measure("rasterize circle, new buffers (radius 5000)", 200, || {
+            CircleRasterizer::default().rasterize(center, RADIUS).len()
+        }).assert_within(std::time::Duration::from_millis(5));
+
+        let mut rasterizer = CircleRasterizer::default();
+        crate::bench::measure("rasterize circle, reused buffers (radius 5000)", 200, || {
+            rasterizer.rasterize(center, RADIUS).len()
+        }).assert_within(std::time::Duration::from_millis(5));
+    }
 }
@@ -0,0 +1,210 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Quantization of high-precision image values to 8 bits with dithering, which hides banding in smooth gradients.
+
+use ga_image::{Image, PixelFormat};
+
+/// Normalized 8×8 Bayer threshold matrix.
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [ 0, 32,  8, 40,  2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44,  4, 36, 14, 46,  6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [ 3, 35, 11, 43,  1, 33,  9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47,  7, 39, 13, 45,  5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21]
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, strum::EnumIter)]
+pub enum Method {
+    /// Fast, position-dependent threshold pattern (Bayer matrix).
+    Ordered,
+    /// Error diffusion; less visible pattern, but slower.
+    FloydSteinberg
+}
+
+impl Method {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Method::Ordered => "ordered",
+            Method::FloydSteinberg => "Floyd–Steinberg"
+        }
+    }
+}
+
+fn quantize(value: f32) -> u8 { value.round().max(0.0).min(255.0) as u8 }
+
+/// Quantizes `values` (`width`×`height` pixels with `channels` interleaved channels, in [0; 255]) to 8 bits; each
+/// channel is processed independently.
+pub fn dither(values: &[f32], width: usize, height: usize, channels: usize, method: Method) -> Vec<u8> {
+    assert_eq!(width * height * channels, values.len());
+
+    let mut output = vec![0u8; values.len()];
+    if values.is_empty() { return output; }
+
+    let num_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    match method {
+        Method::Ordered => {
+            // rows are independent
+            let row_len = width * channels;
+            let rows_per_chunk = (height + num_threads - 1) / num_threads;
+            crossbeam::thread::scope(|scope| {
+                for (chunk_idx, (src, dest)) in values.chunks(rows_per_chunk * row_len)
+                    .zip(output.chunks_mut(rows_per_chunk * row_len))
+                    .enumerate()
+                {
+                    scope.spawn(move |_| ordered(src, dest, width, channels, chunk_idx * rows_per_chunk));
+                }
+            }).unwrap();
+        },
+
+        Method::FloydSteinberg => {
+            // the error propagates across rows, but channels are independent
+            let mut channel_outputs = vec![vec![]; channels];
+            crossbeam::thread::scope(|scope| {
+                for (channel, channel_output) in channel_outputs.iter_mut().enumerate() {
+                    scope.spawn(move |_| {
+                        *channel_output = floyd_steinberg(values, width, height, channels, channel);
+                    });
+                }
+            }).unwrap();
+
+            for (channel, channel_output) in channel_outputs.iter().enumerate() {
+                for (dest, value) in output.iter_mut().skip(channel).step_by(channels).zip(channel_output) {
+                    *dest = *value;
+                }
+            }
+        }
+    }
+
+    output
+}
+
+/// `first_row`: index of the first row of `src` in the whole image.
+fn ordered(src: &[f32], dest: &mut [u8], width: usize, channels: usize, first_row: usize) {
+    for (idx, (value, dest)) in src.iter().zip(dest.iter_mut()).enumerate() {
+        let x = idx / channels % width;
+        let y = first_row + idx / (channels * width);
+        let threshold = (BAYER_8X8[y % 8][x % 8] as f32 + 0.5) / 64.0 - 0.5;
+        *dest = quantize(value + threshold);
+    }
+}
+
+/// Returns the dithered values of `channel`.
+fn floyd_steinberg(values: &[f32], width: usize, height: usize, channels: usize, channel: usize) -> Vec<u8> {
+    let mut output = Vec::with_capacity(width * height);
+
+    // errors diffused to the current and to the next row
+    let mut current_errors = vec![0.0f32; width + 2];
+    let mut next_errors = vec![0.0f32; width + 2];
+    for y in 0..height {
+        for x in 0..width {
+            let value = values[(y * width + x) * channels + channel] + current_errors[x + 1];
+            let quantized = quantize(value);
+            let error = value - quantized as f32;
+
+            current_errors[x + 2] += error * 7.0 / 16.0;
+            next_errors[x] += error * 3.0 / 16.0;
+            next_errors[x + 1] += error * 5.0 / 16.0;
+            next_errors[x + 2] += error * 1.0 / 16.0;
+
+            output.push(quantized);
+        }
+        std::mem::swap(&mut current_errors, &mut next_errors);
+        next_errors.iter_mut().for_each(|e| *e = 0.0);
+    }
+
+    output
+}
+
+/// Returns an RGB8 image with values of `image` (RGB16) mapped via `curve` (65536 entries in [0; 255], e.g.,
+/// see `color::encode_curve`) and dithered.
+pub fn to_rgb8(image: &Image, curve: &[f32], method: Method) -> Image {
+    assert!(image.pixel_format() == PixelFormat::RGB16);
+    assert_eq!(65536, curve.len());
+
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut values = Vec::with_capacity(3 * width * height);
+    for y in 0..image.height() {
+        values.extend(image.line::<u16>(y)[..3 * width].iter().map(|value| curve[*value as usize]));
+    }
+
+    Image::new_from_pixels(
+        image.width(),
+        image.height(),
+        None,
+        PixelFormat::RGB8,
+        None,
+        dither(&values, width, height, 3, method)
+    )
+}
+
+mod tests {
+    use super::*;
+
+    fn gradient(width: usize, height: usize, channels: usize) -> Vec<f32> {
+        (0..width * height * channels).map(|i| (i / channels % width) as f32 / (width - 1) as f32 * 255.0).collect()
+    }
+
+    #[test]
+    fn values_stay_in_range() {
+        let values = gradient(64, 8, 3);
+        for method in [Method::Ordered, Method::FloydSteinberg] {
+            assert_eq!(vec![0, 255], dither(&[-3.0, 260.0], 2, 1, 1, method));
+
+            // no value is farther than one level from the input
+            let output = dither(&values, 64, 8, 3, method);
+            for (value, output) in values.iter().zip(&output) {
+                assert!((*output as f32 - value).abs() <= 1.0 + 1.0e-3, "{} -> {}", value, output);
+            }
+        }
+    }
+
+    #[test]
+    fn ordered_dithering_is_deterministic() {
+        let values = gradient(100, 37, 3);
+        let output = dither(&values, 100, 37, 3, Method::Ordered);
+        assert_eq!(output, dither(&values, 100, 37, 3, Method::Ordered));
+
+        // the pattern depends on the position, not on how the rows are split between threads
+        let mut single = vec![0u8; values.len()];
+        ordered(&values, &mut single, 100, 3, 0);
+        assert_eq!(single, output);
+    }
+
+    #[test]
+    fn uniform_areas_do_not_drift() {
+        for method in [Method::Ordered, Method::FloydSteinberg] {
+            let output = dither(&vec![100.0; 64 * 64], 64, 64, 1, method);
+            assert!(output.iter().all(|value| *value == 100));
+        }
+    }
+
+    #[test]
+    fn average_level_is_preserved() {
+        let (width, height) = (64, 64);
+        for method in [Method::Ordered, Method::FloydSteinberg] {
+            let output = dither(&vec![100.25; width * height], width, height, 1, method);
+            let mean = output.iter().map(|value| *value as f64).sum::<f64>() / (width * height) as f64;
+            assert!((mean - 100.25).abs() < 0.05, "{}: {}", method.name(), mean);
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Reading of FITS files holding a single image (grayscale or 3-plane color) in the primary HDU.
+
+use std::error::Error;
+use std::io::Read;
+use std::path::Path;
+
+const BLOCK_SIZE: usize = 2880;
+const CARD_SIZE: usize = 80;
+
+/// Value of BZERO which marks unsigned 16-bit data stored as signed integers.
+const UNSIGNED_16_BZERO: f64 = 32768.0;
+
+/// Floating-point values are expected within [0; 1]; if an image has larger values, the range [0; 65535] is assumed
+/// (e.g., 16-bit data converted to floating-point by a processing pipeline).
+const FLOAT_16_BIT_RANGE_MAX: f64 = 65535.0;
+
+pub const EXTENSIONS: [&str; 3] = ["fit", "fits", "fts"];
+
+/// Returns `true` if the extension of `path` is one of `EXTENSIONS`.
+pub fn is_fits_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Header {
+    pub width: u32,
+    pub height: u32,
+    /// 1 (grayscale) or 3 (color, stored as consecutive R, G, B planes).
+    pub num_planes: u32,
+    /// 8, 16, -32 or -64 (unsigned 8-bit, signed 16-bit, 32- and 64-bit floating-point samples).
+    pub bitpix: i32,
+    /// Physical value = `bzero` + `bscale` · stored value.
+    pub bzero: f64,
+    pub bscale: f64,
+    /// Rows are stored from the top (ROWORDER = 'TOP-DOWN'); by default, FITS rows are stored from the bottom.
+    pub top_down: bool
+}
+
+impl Header {
+    pub fn pixel_format(&self) -> ga_image::PixelFormat {
+        use ga_image::PixelFormat;
+
+        let color = self.num_planes == 3;
+        match self.bitpix {
+            8 => if color { PixelFormat::RGB8 } else { PixelFormat::Mono8 },
+            16 => if color { PixelFormat::RGB16 } else { PixelFormat::Mono16 },
+            _ => if color { PixelFormat::RGB32f } else { PixelFormat::Mono32f }
+        }
+    }
+
+    fn bytes_per_sample(&self) -> usize { (self.bitpix.unsigned_abs() / 8) as usize }
+
+    fn data_len(&self) -> usize {
+        self.width as usize * self.height as usize * self.num_planes as usize * self.bytes_per_sample()
+    }
+}
+
+/// Returns the value of a header card (without the comment, quotes of strings are kept), if the card has one.
+fn card_value(card: &str) -> Option<&str> {
+    if card.get(8..10) != Some("= ") { return None; }
+    let value = &card[10..];
+    let value = match value.trim_start().strip_prefix('\'') {
+        // a string; may contain '/', quotes inside are doubled
+        Some(_) => match value.rfind('\'') {
+            Some(end) => &value[..end + 1],
+            None => value
+        },
+        None => value.split('/').next().unwrap_or("")
+    };
+    Some(value.trim())
+}
+
+fn parse_number(keyword: &str, value: &str) -> Result<f64, Box<dyn Error>> {
+    // Fortran-style exponents are allowed
+    value.replace(|c: char| c == 'D' || c == 'd', "E").parse::<f64>()
+        .map_err(|_| format!("invalid value of {}: {}", keyword, value).into())
+}
+
+fn parse_int(keyword: &str, value: &str) -> Result<i64, Box<dyn Error>> {
+    value.parse::<i64>().map_err(|_| format!("invalid value of {}: {}", keyword, value).into())
+}
+
+/// Reads the primary header; `reader` is left at the start of the data.
+pub fn read_header<R: Read>(reader: &mut R) -> Result<Header, Box<dyn Error>> {
+    let mut bitpix = None;
+    let mut axes: Vec<Option<i64>> = vec![];
+    let mut bzero = 0.0;
+    let mut bscale = 1.0;
+    let mut top_down = false;
+
+    let mut block = [0u8; BLOCK_SIZE];
+    let mut first_card = true;
+    let mut end_found = false;
+    while !end_found {
+        reader.read_exact(&mut block).map_err(|_| "unexpected end of FITS header")?;
+        for card in block.chunks_exact(CARD_SIZE) {
+            let card = std::str::from_utf8(card).map_err(|_| "invalid characters in FITS header")?;
+            let keyword = card[..8].trim_end();
+
+            if first_card {
+                if keyword != "SIMPLE" || card_value(card) != Some("T") { return Err("not a FITS file".into()); }
+                first_card = false;
+                continue;
+            }
+
+            let value = match card_value(card) {
+                Some(value) => value,
+                None => {
+                    if keyword == "END" {
+                        end_found = true;
+                        break;
+                    }
+                    continue;
+                }
+            };
+
+            match keyword {
+                "BITPIX" => bitpix = Some(parse_int(keyword, value)?),
+                "NAXIS" => axes.resize(parse_int(keyword, value)?.clamp(0, 999) as usize, None),
+                "BZERO" => bzero = parse_number(keyword, value)?,
+                "BSCALE" => bscale = parse_number(keyword, value)?,
+                "ROWORDER" => top_down = value.trim_matches('\'').trim() == "TOP-DOWN",
+                _ => if let Some(axis) = keyword.strip_prefix("NAXIS").and_then(|n| n.parse::<usize>().ok()) {
+                    if (1..=axes.len()).contains(&axis) { axes[axis - 1] = Some(parse_int(keyword, value)?); }
+                }
+            }
+        }
+    }
+
+    let bitpix = bitpix.ok_or("BITPIX missing in FITS header")? as i32;
+    if ![8, 16, -32, -64].contains(&bitpix) {
+        return Err(format!("unsupported FITS sample format (BITPIX = {})", bitpix).into());
+    }
+
+    let axes = axes.into_iter()
+        .collect::<Option<Vec<_>>>()
+        .ok_or("axis length missing in FITS header")?;
+    let (width, height, num_planes) = match axes[..] {
+        [width, height] => (width, height, 1),
+        [width, height, planes] if planes == 1 || planes == 3 => (width, height, planes),
+        _ => return Err(format!("unsupported FITS image dimensions: {:?}", axes).into())
+    };
+    if width <= 0 || height <= 0 || width > u32::MAX as i64 || height > u32::MAX as i64 {
+        return Err(format!("invalid FITS image size: {}x{}", width, height).into());
+    }
+
+    Ok(Header{
+        width: width as u32,
+        height: height as u32,
+        num_planes: num_planes as u32,
+        bitpix,
+        bzero,
+        bscale,
+        top_down
+    })
+}
+
+/// Decodes the image in the primary HDU. 16-bit data are converted using BZERO and BSCALE (unsigned 16-bit values
+/// are restored exactly) and clamped to [0; 65535]; floating-point data are scaled to [0; 1]
+/// (see `FLOAT_16_BIT_RANGE_MAX`).
+pub fn decode<R: Read>(mut reader: R) -> Result<image::DynamicImage, Box<dyn Error>> {
+    let header = read_header(&mut reader)?;
+
+    let mut data = vec![0u8; header.data_len()];
+    reader.read_exact(&mut data).map_err(|_| "unexpected end of FITS data")?;
+
+    let samples = samples(&header, &data);
+    let (width, height) = (header.width, header.height);
+    let pixels = interleaved(&header, &samples);
+    let too_short = || -> Box<dyn Error> { "FITS data shorter than the image requires".into() };
+
+    Ok(match (header.bitpix, header.num_planes) {
+        (8, 1) => image::DynamicImage::ImageLuma8(
+            image::GrayImage::from_raw(width, height, pixels.iter().map(|v| *v as u8).collect()).ok_or_else(too_short)?
+        ),
+        (8, _) => image::DynamicImage::ImageRgb8(
+            image::RgbImage::from_raw(width, height, pixels.iter().map(|v| *v as u8).collect()).ok_or_else(too_short)?
+        ),
+        (16, 1) => image::DynamicImage::ImageLuma16(
+            image::ImageBuffer::from_raw(width, height, pixels.iter().map(|v| *v as u16).collect())
+                .ok_or_else(too_short)?
+        ),
+        (16, _) => image::DynamicImage::ImageRgb16(
+            image::ImageBuffer::from_raw(width, height, pixels.iter().map(|v| *v as u16).collect())
+                .ok_or_else(too_short)?
+        ),
+        _ => {
+            let scale = if pixels.iter().any(|v| *v > 1.0) { 1.0 / FLOAT_16_BIT_RANGE_MAX } else { 1.0 };
+            // there is no grayscale floating-point variant of `DynamicImage`
+            let rgb = if header.num_planes == 1 {
+                pixels.iter().flat_map(|v| [(v * scale) as f32; 3]).collect()
+            } else {
+                pixels.iter().map(|v| (v * scale) as f32).collect()
+            };
+            image::DynamicImage::ImageRgb32F(image::Rgb32FImage::from_raw(width, height, rgb).ok_or_else(too_short)?)
+        }
+    })
+}
+
+/// Returns physical values of big-endian `data` (integer values clamped to the range of their type).
+fn samples(header: &Header, data: &[u8]) -> Vec<f64> {
+    let (bzero, bscale) = (header.bzero, header.bscale);
+    match header.bitpix {
+        8 => data.iter().map(|v| (bzero + bscale * *v as f64).round().clamp(0.0, 255.0)).collect(),
+
+        16 => if bzero == UNSIGNED_16_BZERO && bscale == 1.0 {
+            // exact: the stored value with the sign bit flipped
+            data.chunks_exact(2).map(|v| (u16::from_be_bytes([v[0], v[1]]) ^ 0x8000) as f64).collect()
+        } else {
+            data.chunks_exact(2)
+                .map(|v| (bzero + bscale * i16::from_be_bytes([v[0], v[1]]) as f64).round().clamp(0.0, 65535.0))
+                .collect()
+        },
+
+        -32 => data.chunks_exact(4)
+            .map(|v| (bzero + bscale * f32::from_be_bytes([v[0], v[1], v[2], v[3]]) as f64).max(0.0))
+            .collect(),
+
+        _ => data.chunks_exact(8)
+            .map(|v| (bzero + bscale * f64::from_be_bytes([v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]])).max(0.0))
+            .collect()
+    }
+}
+
+/// Converts planar `samples` (rows stored as specified in `header`) to interleaved pixels starting from the top-left.
+fn interleaved(header: &Header, samples: &[f64]) -> Vec<f64> {
+    let (width, height) = (header.width as usize, header.height as usize);
+    let num_planes = header.num_planes as usize;
+    let plane_len = width * height;
+
+    let mut pixels = Vec::with_capacity(samples.len());
+    for y in 0..height {
+        let src_y = if header.top_down { y } else { height - 1 - y };
+        for x in 0..width {
+            for plane in 0..num_planes {
+                pixels.push(samples[plane * plane_len + src_y * width + x]);
+            }
+        }
+    }
+
+    pixels
+}
+
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+
+    /// Returns a FITS file with the specified header cards (after SIMPLE) and data.
+    fn fits_file(cards: &[String], data: &[u8]) -> Vec<u8> {
+        let mut file = vec![];
+        let mut add_card = |card: &str| file.extend_from_slice(format!("{:<80}", card).as_bytes());
+        add_card(&format!("{:<8}= {:>20}", "SIMPLE", "T"));
+        for card in cards { add_card(card); }
+        add_card("END");
+        file.resize((file.len() + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE, b' ');
+
+        file.extend_from_slice(data);
+        file.resize((file.len() + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE, 0);
+
+        file
+    }
+
+    fn card(keyword: &str, value: &str) -> String { format!("{:<8}= {:>20} / comment", keyword, value) }
+
+    fn image_cards(bitpix: i32, width: u32, height: u32, num_planes: Option<u32>) -> Vec<String> {
+        let mut cards = vec![
+            card("BITPIX", &bitpix.to_string()),
+            card("NAXIS", if num_planes.is_some() { "3" } else { "2" }),
+            card("NAXIS1", &width.to_string()),
+            card("NAXIS2", &height.to_string())
+        ];
+        if let Some(num_planes) = num_planes { cards.push(card("NAXIS3", &num_planes.to_string())); }
+        cards
+    }
+
+    #[test]
+    fn unsigned_16_bit_data_are_restored_exactly() {
+        let mut cards = image_cards(16, 2, 2, None);
+        cards.push(card("BZERO", "32768"));
+        cards.push(card("BSCALE", "1.0"));
+        // bottom row first
+        let stored: Vec<u8> = [-32768i16, -1, 0, 32767].iter().flat_map(|v| v.to_be_bytes()).collect();
+
+        let image = decode(std::io::Cursor::new(fits_file(&cards, &stored))).unwrap();
+        match image {
+            image::DynamicImage::ImageLuma16(buf) => assert_eq!(&[32768, 65535, 0, 32767], buf.as_raw().as_slice()),
+            other => panic!("unexpected image {:?}", other.color())
+        }
+    }
+
+    #[test]
+    fn signed_16_bit_data_are_scaled_and_clamped() {
+        let mut cards = image_cards(16, 3, 1, None);
+        cards.push(card("BSCALE", "2.0D0"));
+        let stored: Vec<u8> = [-5i16, 100, 20000].iter().flat_map(|v| v.to_be_bytes()).collect();
+
+        let image = decode(std::io::Cursor::new(fits_file(&cards, &stored))).unwrap();
+        assert_eq!(&[0, 200, 40000], image.as_luma16().unwrap().as_raw().as_slice());
+    }
+
+    #[test]
+    fn color_planes_are_interleaved_from_the_top() {
+        let mut cards = image_cards(8, 2, 2, Some(3));
+        cards.push(card("ROWORDER", "'TOP-DOWN'"));
+        let planes = [
+            1, 2, 3, 4, // R
+            10, 20, 30, 40, // G
+            100, 110, 120, 130 // B
+        ];
+
+        let image = decode(std::io::Cursor::new(fits_file(&cards, &planes))).unwrap();
+        assert_eq!((2, 2), image.dimensions());
+        assert_eq!(
+            &[1, 10, 100, 2, 20, 110, 3, 30, 120, 4, 40, 130],
+            image.as_rgb8().unwrap().as_raw().as_slice()
+        );
+    }
+
+    #[test]
+    fn float_data_are_normalized() {
+        let cards = image_cards(-32, 2, 1, None);
+        let stored: Vec<u8> = [0.25f32, 0.5].iter().flat_map(|v| v.to_be_bytes()).collect();
+        let image = decode(std::io::Cursor::new(fits_file(&cards, &stored))).unwrap();
+        assert_eq!(&[0.25, 0.25, 0.25, 0.5, 0.5, 0.5], image.as_rgb32f().unwrap().as_raw().as_slice());
+
+        let stored: Vec<u8> = [0.0f32, 65535.0].iter().flat_map(|v| v.to_be_bytes()).collect();
+        let image = decode(std::io::Cursor::new(fits_file(&cards, &stored))).unwrap();
+        assert_eq!(&[0.0, 0.0, 0.0, 1.0, 1.0, 1.0], image.as_rgb32f().unwrap().as_raw().as_slice());
+    }
+
+    #[test]
+    fn header_is_read_without_data() {
+        let mut cards = image_cards(16, 640, 480, Some(3));
+        cards.push(card("OBJECT", "'Jupiter / GRS'"));
+        let file = fits_file(&cards, &[]);
+        let header = read_header(&mut std::io::Cursor::new(file)).unwrap();
+        assert_eq!((640, 480, 3), (header.width, header.height, header.num_planes));
+        assert_eq!(ga_image::PixelFormat::RGB16, header.pixel_format());
+        assert!(!header.top_down);
+    }
+
+    #[test]
+    fn unsupported_files_are_rejected() {
+        assert!(read_header(&mut std::io::Cursor::new(vec![b' '; BLOCK_SIZE])).is_err());
+        assert!(read_header(&mut std::io::Cursor::new(fits_file(&image_cards(32, 2, 2, None), &[]))).is_err());
+        assert!(read_header(&mut std::io::Cursor::new(fits_file(&image_cards(8, 2, 2, Some(2)), &[]))).is_err());
+
+        // missing data
+        let file = fits_file(&image_cards(8, 2000, 2000, None), &[]);
+        assert!(decode(std::io::Cursor::new(file)).is_err());
+    }
+
+    #[test]
+    fn fits_extensions_are_recognized() {
+        assert!(is_fits_path(Path::new("/data/jupiter_0001.FITS")));
+        assert!(is_fits_path(Path::new("mars.fit")));
+        assert!(!is_fits_path(Path::new("mars.tif")));
+    }
+}
@@ -0,0 +1,361 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Formatting of values presented to the user (according to `Preferences`). Values written to files must not depend
+//! on the preferences and are formatted with the `machine_*` functions.
+
+use cgmath::Deg;
+use std::time::Duration;
+use strum::IntoEnumIterator;
+
+const KM_PER_MILE: f64 = 1.609344;
+
+#[derive(Copy, Clone, Debug, PartialEq, strum::EnumIter)]
+pub enum AngleFormat {
+    /// E.g., 12.5°.
+    Decimal,
+    /// E.g., 12°30′.
+    DegreesMinutes
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, strum::EnumIter)]
+pub enum UnitSystem {
+    Metric,
+    Imperial
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, strum::EnumIter)]
+pub enum DecimalSeparator {
+    Point,
+    Comma
+}
+
+impl AngleFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AngleFormat::Decimal => "decimal degrees",
+            AngleFormat::DegreesMinutes => "degrees and minutes"
+        }
+    }
+
+    fn config_name(&self) -> &'static str {
+        match self {
+            AngleFormat::Decimal => "decimal",
+            AngleFormat::DegreesMinutes => "dm"
+        }
+    }
+}
+
+impl UnitSystem {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnitSystem::Metric => "metric (km)",
+            UnitSystem::Imperial => "imperial (mi)"
+        }
+    }
+
+    fn config_name(&self) -> &'static str {
+        match self {
+            UnitSystem::Metric => "metric",
+            UnitSystem::Imperial => "imperial"
+        }
+    }
+}
+
+impl DecimalSeparator {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DecimalSeparator::Point => "point (1.5)",
+            DecimalSeparator::Comma => "comma (1,5)"
+        }
+    }
+
+    fn config_name(&self) -> &'static str {
+        match self {
+            DecimalSeparator::Point => "point",
+            DecimalSeparator::Comma => "comma"
+        }
+    }
+
+    fn character(&self) -> char {
+        match self {
+            DecimalSeparator::Point => '.',
+            DecimalSeparator::Comma => ','
+        }
+    }
+
+    /// Separator of thousands which cannot be confused with the decimal separator.
+    fn thousands_character(&self) -> char {
+        match self {
+            DecimalSeparator::Point => ',',
+            DecimalSeparator::Comma => '.'
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Preferences {
+    pub angle_format: AngleFormat,
+    pub unit_system: UnitSystem,
+    pub decimal_separator: DecimalSeparator,
+    pub thousands_separator: bool
+}
+
+impl Default for Preferences {
+    fn default() -> Preferences {
+        Preferences{
+            angle_format: AngleFormat::Decimal,
+            unit_system: UnitSystem::Metric,
+            decimal_separator: DecimalSeparator::Point,
+            thousands_separator: false
+        }
+    }
+}
+
+impl Preferences {
+    pub fn to_config_string(&self) -> String {
+        format!(
+            "angle_format={};unit_system={};decimal_separator={};thousands_separator={}",
+            self.angle_format.config_name(),
+            self.unit_system.config_name(),
+            self.decimal_separator.config_name(),
+            self.thousands_separator
+        )
+    }
+
+    /// Parses the output of `to_config_string`; missing and unknown keys are ignored.
+    pub fn from_config_string(s: &str) -> Option<Preferences> {
+        let mut prefs = Preferences::default();
+
+        for pair in s.split(';').filter(|pair| !pair.trim().is_empty()) {
+            let (key, value) = pair.split_once('=')?;
+            let value = value.trim();
+            match key.trim() {
+                "angle_format" => prefs.angle_format = AngleFormat::iter().find(|f| f.config_name() == value)?,
+                "unit_system" => prefs.unit_system = UnitSystem::iter().find(|u| u.config_name() == value)?,
+                "decimal_separator" =>
+                    prefs.decimal_separator = DecimalSeparator::iter().find(|d| d.config_name() == value)?,
+                "thousands_separator" => prefs.thousands_separator = value.parse().ok()?,
+                _ => ()
+            }
+        }
+
+        Some(prefs)
+    }
+}
+
+/// Formats `value` with `decimals` decimal places.
+pub fn format_number(value: f64, decimals: usize, prefs: &Preferences) -> String {
+    let machine = machine_number(value, decimals);
+    let (sign, unsigned) = match machine.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", machine.as_str())
+    };
+    let (integer, fraction) = match unsigned.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (unsigned, None)
+    };
+
+    let mut result = sign.to_string();
+    for (i, digit) in integer.chars().enumerate() {
+        if prefs.thousands_separator && i > 0 && (integer.len() - i) % 3 == 0 {
+            result.push(prefs.decimal_separator.thousands_character());
+        }
+        result.push(digit);
+    }
+    if let Some(fraction) = fraction {
+        result.push(prefs.decimal_separator.character());
+        result += fraction;
+    }
+
+    result
+}
+
+/// Formats `value`; `decimals` is the number of decimal places of decimal degrees (with `AngleFormat::DegreesMinutes`,
+/// minutes are shown with a similar precision).
+pub fn format_angle(value: Deg<f32>, decimals: usize, prefs: &Preferences) -> String {
+    match prefs.angle_format {
+        AngleFormat::Decimal => format!("{}°", format_number(value.0 as f64, decimals, prefs)),
+
+        AngleFormat::DegreesMinutes => {
+            // 0.01° = 0.6′
+            let minute_decimals = decimals.saturating_sub(2);
+            let scale = 10f64.powi(minute_decimals as i32);
+            let total_minutes = (value.0.abs() as f64 * 60.0 * scale).round() / scale;
+            let degrees = (total_minutes / 60.0).floor();
+            let minutes = total_minutes - 60.0 * degrees;
+
+            let minutes_str = format_number(minutes, minute_decimals, prefs);
+            format!(
+                "{}{}°{}{}′",
+                if value.0 < 0.0 && total_minutes > 0.0 { "-" } else { "" },
+                format_number(degrees, 0, prefs),
+                if minutes < 10.0 { "0" } else { "" },
+                minutes_str
+            )
+        }
+    }
+}
+
+/// Formats a length given in kilometers in the preferred unit system.
+pub fn format_length(km: f64, decimals: usize, prefs: &Preferences) -> String {
+    match prefs.unit_system {
+        UnitSystem::Metric => format!("{} km", format_number(km, decimals, prefs)),
+        UnitSystem::Imperial => format!("{} mi", format_number(km / KM_PER_MILE, decimals, prefs))
+    }
+}
+
+/// Formats `value` as hours, minutes and seconds (fractional seconds are shown only for durations below a minute).
+pub fn format_duration(value: Duration, prefs: &Preferences) -> String {
+    let secs = value.as_secs();
+    if secs < 60 {
+        format!("{} s", format_number(value.as_secs_f64(), 1, prefs))
+    } else if secs < 3600 {
+        format!("{} min {:02} s", secs / 60, secs % 60)
+    } else {
+        format!("{} h {:02} min {:02} s", format_number((secs / 3600) as f64, 0, prefs), secs / 60 % 60, secs % 60)
+    }
+}
+
+/// Formats `value` as "H:MM:SS" (rounded to whole seconds).
+pub fn format_hms(value: Duration) -> String {
+    let secs = value.as_secs_f64().round() as u64;
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Parses a duration given as seconds, "M:SS" or "H:MM:SS" (seconds may have a fractional part, with either
+/// decimal separator).
+pub fn parse_hms(s: &str) -> Option<Duration> {
+    let parts: Vec<&str> = s.trim().split(':').map(|part| part.trim()).collect();
+    if parts.len() > 3 { return None; }
+
+    let (seconds, minutes_hours) = parts.split_last()?;
+    let seconds: f64 = seconds.replace(',', ".").parse().ok()?;
+    if !seconds.is_finite() || seconds < 0.0 || (!minutes_hours.is_empty() && seconds >= 60.0) { return None; }
+
+    let mut minutes = 0;
+    for (i, part) in minutes_hours.iter().enumerate() {
+        let value: u64 = part.parse().ok()?;
+        // only the leading value is not limited
+        if i > 0 && value >= 60 { return None; }
+        minutes = minutes * 60 + value;
+    }
+
+    Some(Duration::from_secs(minutes * 60) + Duration::from_secs_f64(seconds))
+}
+
+/// Formats `value` for writing to a file: with `decimals` decimal places, a decimal point and no thousands separator.
+pub fn machine_number(value: f64, decimals: usize) -> String {
+    format!("{:.*}", decimals, value)
+}
+
+mod tests {
+    use super::*;
+
+    fn prefs(decimal_separator: DecimalSeparator, thousands_separator: bool) -> Preferences {
+        Preferences{ decimal_separator, thousands_separator, ..Default::default() }
+    }
+
+    #[test]
+    fn formats_numbers() {
+        assert_eq!("1234567.50", format_number(1234567.5, 2, &prefs(DecimalSeparator::Point, false)));
+        assert_eq!("1,234,567.50", format_number(1234567.5, 2, &prefs(DecimalSeparator::Point, true)));
+        assert_eq!("1234567,50", format_number(1234567.5, 2, &prefs(DecimalSeparator::Comma, false)));
+        assert_eq!("1.234.567,50", format_number(1234567.5, 2, &prefs(DecimalSeparator::Comma, true)));
+        assert_eq!("-123,4", format_number(-123.4, 1, &prefs(DecimalSeparator::Comma, true)));
+        assert_eq!("-1,000", format_number(-999.6, 0, &prefs(DecimalSeparator::Point, true)));
+        assert_eq!("0.05", format_number(0.05, 2, &prefs(DecimalSeparator::Point, true)));
+    }
+
+    #[test]
+    fn formats_angles() {
+        let decimal = Preferences::default();
+        let dm = Preferences{ angle_format: AngleFormat::DegreesMinutes, ..Default::default() };
+        let dm_comma = Preferences{ decimal_separator: DecimalSeparator::Comma, ..dm };
+
+        assert_eq!("12.5°", format_angle(Deg(12.5), 1, &decimal));
+        assert_eq!("12°30′", format_angle(Deg(12.5), 1, &dm));
+        assert_eq!("-3°05′", format_angle(Deg(-3.0833), 2, &dm));
+        assert_eq!("0°07,50′", format_angle(Deg(0.125), 4, &dm_comma));
+        // minutes rounded up to a full degree
+        assert_eq!("13°00′", format_angle(Deg(12.999), 1, &dm));
+        // no negative zero
+        assert_eq!("0°00′", format_angle(Deg(-0.001), 1, &dm));
+    }
+
+    #[test]
+    fn formats_lengths() {
+        let metric = Preferences::default();
+        let imperial = Preferences{ unit_system: UnitSystem::Imperial, ..Default::default() };
+        assert_eq!("100.0 km", format_length(100.0, 1, &metric));
+        assert_eq!("62.1 mi", format_length(100.0, 1, &imperial));
+        assert_eq!("71,492 km", format_length(71492.0, 0, &Preferences{ thousands_separator: true, ..metric }));
+    }
+
+    #[test]
+    fn formats_durations() {
+        let comma = prefs(DecimalSeparator::Comma, false);
+        assert_eq!("2,5 s", format_duration(Duration::from_millis(2500), &comma));
+        assert_eq!("2 min 05 s", format_duration(Duration::from_secs(125), &comma));
+        assert_eq!("9 h 55 min 30 s", format_duration(Duration::from_secs(9 * 3600 + 55 * 60 + 30), &comma));
+    }
+
+    #[test]
+    fn formats_and_parses_hms() {
+        assert_eq!("0:00:00", format_hms(Duration::ZERO));
+        assert_eq!("0:02:06", format_hms(Duration::from_millis(125_500)));
+        assert_eq!("10:05:30", format_hms(Duration::from_secs(10 * 3600 + 5 * 60 + 30)));
+
+        assert_eq!(Some(Duration::from_millis(2500)), parse_hms("2,5"));
+        assert_eq!(Some(Duration::from_secs(90)), parse_hms("90"));
+        assert_eq!(Some(Duration::from_secs(125)), parse_hms(" 2:05 "));
+        assert_eq!(Some(Duration::from_secs(75 * 60)), parse_hms("75:00"));
+        assert_eq!(Some(Duration::from_secs(3600 + 125)), parse_hms("1:02:05"));
+        assert_eq!(Some(Duration::from_secs(3600 + 125)), parse_hms(&format_hms(Duration::from_secs(3600 + 125))));
+
+        assert!(parse_hms("").is_none());
+        assert!(parse_hms("1:60").is_none());
+        assert!(parse_hms("1:60:00").is_none());
+        assert!(parse_hms("-5").is_none());
+        assert!(parse_hms("1:2:3:4").is_none());
+        assert!(parse_hms("a:00").is_none());
+    }
+
+    #[test]
+    fn machine_numbers_ignore_preferences() {
+        assert_eq!("1234.57", machine_number(1234.567, 2));
+        assert_eq!("-0.5", machine_number(-0.5, 1));
+    }
+
+    #[test]
+    fn preferences_round_trip_through_config_string() {
+        for angle_format in AngleFormat::iter() {
+            for unit_system in UnitSystem::iter() {
+                for decimal_separator in DecimalSeparator::iter() {
+                    for thousands_separator in [false, true] {
+                        let prefs = Preferences{ angle_format, unit_system, decimal_separator, thousands_separator };
+                        assert_eq!(Some(prefs), Preferences::from_config_string(&prefs.to_config_string()));
+                    }
+                }
+            }
+        }
+        assert_eq!(Some(Preferences::default()), Preferences::from_config_string(""));
+        assert_eq!(None, Preferences::from_config_string("angle_format=radians"));
+    }
+}
@@ -0,0 +1,345 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Optional capture of OpenGL debug output (enabled with `--gl-debug` or in Settings; takes effect after restart).
+//! The driver may invoke the callback on any thread, so messages are sent over a channel and logged by the main
+//! thread; repeats of a message are rate-limited.
+
+use crate::logging;
+use glium::CapabilitiesSource;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// A message is logged at most once per this interval; repeats in the meantime are counted.
+const REPEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Messages not yet logged by the main thread; further ones are dropped.
+const CHANNEL_CAPACITY: usize = 1000;
+
+/// Distinct messages remembered for rate-limiting; above this, those not logged recently are forgotten.
+const MAX_DISTINCT_MESSAGES: usize = 1000;
+
+pub const HIGH_SEVERITY_WARNING: &str = "The OpenGL driver reported a high-severity problem (see the log for details). \
+    Rendering or export results may be incorrect.";
+
+/// Set (before creating the contexts) if debug output has been requested.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GlContext {
+    Main,
+    Worker
+}
+
+impl GlContext {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GlContext::Main => "main",
+            GlContext::Worker => "worker"
+        }
+    }
+}
+
+/// Passed to the callback as user data to identify the context.
+static CONTEXT_TAGS: [GlContext; 2] = [GlContext::Main, GlContext::Worker];
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Message {
+    pub context: GlContext,
+    pub source: u32,
+    pub gl_type: u32,
+    pub id: u32,
+    pub severity: u32,
+    pub text: String
+}
+
+impl Message {
+    fn log_severity(&self) -> logging::Severity {
+        match self.severity {
+            gl::DEBUG_SEVERITY_HIGH => logging::Severity::Error,
+            gl::DEBUG_SEVERITY_MEDIUM => logging::Severity::Warning,
+            _ => logging::Severity::Info
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let source = match self.source {
+            gl::DEBUG_SOURCE_API => "API",
+            gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+            gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+            gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+            gl::DEBUG_SOURCE_APPLICATION => "application",
+            _ => "other source"
+        };
+        let gl_type = match self.gl_type {
+            gl::DEBUG_TYPE_ERROR => "error",
+            gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+            gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+            gl::DEBUG_TYPE_PORTABILITY => "portability",
+            gl::DEBUG_TYPE_PERFORMANCE => "performance",
+            _ => "message"
+        };
+        format!("OpenGL ({} context) {} {} {}: {}", self.context.name(), source, gl_type, self.id, self.text.trim_end())
+    }
+}
+
+/// Limits logging of repeated messages: a message is logged at most once per interval, together with the number of
+/// its repeats suppressed since it was last logged (repeats not followed by a logged occurrence are not reported).
+pub struct RateLimiter {
+    interval: Duration,
+    /// Values: (time of the last logged occurrence, number of suppressed repeats).
+    messages: HashMap<Message, (Instant, usize)>
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration) -> RateLimiter {
+        RateLimiter{ interval, messages: HashMap::new() }
+    }
+
+    /// Returns the number of suppressed repeats if `message` (received at `now`) is to be logged.
+    pub fn check(&mut self, message: &Message, now: Instant) -> Option<usize> {
+        if let Some((last_logged, suppressed)) = self.messages.get_mut(message) {
+            if now.duration_since(*last_logged) < self.interval {
+                *suppressed += 1;
+                None
+            } else {
+                *last_logged = now;
+                Some(std::mem::replace(suppressed, 0))
+            }
+        } else {
+            if self.messages.len() >= MAX_DISTINCT_MESSAGES {
+                let interval = self.interval;
+                self.messages.retain(|_, (last_logged, _)| now.duration_since(*last_logged) < interval);
+            }
+            self.messages.insert(message.clone(), (now, 0));
+            Some(0)
+        }
+    }
+
+    pub fn num_distinct(&self) -> usize { self.messages.len() }
+}
+
+/// Converts received messages to log entries.
+pub struct Forwarder {
+    limiter: RateLimiter,
+    high_severity_seen: bool,
+    /// Set on the first high-severity message; cleared by `take_warning`.
+    warning_pending: bool
+}
+
+impl Forwarder {
+    pub fn new(repeat_interval: Duration) -> Forwarder {
+        Forwarder{ limiter: RateLimiter::new(repeat_interval), high_severity_seen: false, warning_pending: false }
+    }
+
+    /// Returns the log entry (if any) for `message` received at `now`.
+    pub fn process(&mut self, message: &Message, now: Instant) -> Option<(logging::Severity, String)> {
+        if message.severity == gl::DEBUG_SEVERITY_HIGH && !self.high_severity_seen {
+            self.high_severity_seen = true;
+            self.warning_pending = true;
+        }
+        let suppressed = self.limiter.check(message, now)?;
+        let mut text = message.to_text();
+        if suppressed > 0 { text += &format!(" (repeated {} more times)", suppressed); }
+
+        Some((message.log_severity(), text))
+    }
+
+    /// Returns `true` (once per program run) if a high-severity message has been received.
+    pub fn take_warning(&mut self) -> bool {
+        std::mem::replace(&mut self.warning_pending, false)
+    }
+}
+
+fn channel() -> &'static (crossbeam::channel::Sender<Message>, crossbeam::channel::Receiver<Message>) {
+    static CHANNEL: OnceLock<(crossbeam::channel::Sender<Message>, crossbeam::channel::Receiver<Message>)> =
+        OnceLock::new();
+    CHANNEL.get_or_init(|| crossbeam::channel::bounded(CHANNEL_CAPACITY))
+}
+
+thread_local! {
+    static FORWARDER: RefCell<Forwarder> = RefCell::new(Forwarder::new(REPEAT_INTERVAL));
+}
+
+/// Must be called before creating the OpenGL contexts, which then request debug output.
+pub fn set_requested(value: bool) {
+    REQUESTED.store(value, Ordering::Relaxed);
+}
+
+pub fn requested() -> bool { REQUESTED.load(Ordering::Relaxed) }
+
+extern "system" fn callback(
+    source: gl::types::GLenum,
+    gl_type: gl::types::GLenum,
+    id: gl::types::GLuint,
+    severity: gl::types::GLenum,
+    length: gl::types::GLsizei,
+    message: *const gl::types::GLchar,
+    user_param: *mut std::ffi::c_void
+) {
+    if message.is_null() || user_param.is_null() { return; }
+    // SAFETY: `user_param` points to an element of `CONTEXT_TAGS`; `message` is valid for the duration of the call
+    // (`length` excludes the terminating null; a negative value means it is not given)
+    let (context, text) = unsafe {
+        let context = *(user_param as *const GlContext);
+        let text = if length >= 0 {
+            String::from_utf8_lossy(std::slice::from_raw_parts(message as *const u8, length as usize)).into_owned()
+        } else {
+            std::ffi::CStr::from_ptr(message).to_string_lossy().into_owned()
+        };
+        (context, text)
+    };
+    // must not block the driver
+    let _ = channel().0.try_send(Message{ context, source, gl_type, id, severity, text });
+}
+
+/// Enables debug output of the current context (if requested and supported); to be called on the thread
+/// which uses `context`. Replaces glium's own callback (if any).
+pub fn install<C: CapabilitiesSource>(context: GlContext, capabilities: &C) {
+    if !requested() { return; }
+
+    let supported = capabilities.get_extensions().gl_khr_debug
+        || *capabilities.get_version() >= glium::Version(glium::Api::Gl, 4, 3);
+    if !supported || !gl::DebugMessageCallback::is_loaded() || !gl::Enable::is_loaded() {
+        logging::log_info!("OpenGL debug output is not supported by the {} context.", context.name());
+        return;
+    }
+
+    let tag = CONTEXT_TAGS.iter().find(|tag| **tag == context).unwrap();
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        gl::DebugMessageCallback(Some(callback), tag as *const GlContext as *const std::ffi::c_void);
+    }
+    logging::log_info!("OpenGL debug output enabled for the {} context.", context.name());
+}
+
+/// Logs the messages received so far; to be called by the main thread every frame.
+pub fn forward_to_log() {
+    let now = Instant::now();
+    FORWARDER.with(|forwarder| {
+        let mut forwarder = forwarder.borrow_mut();
+        for message in channel().1.try_iter() {
+            if let Some((severity, text)) = forwarder.process(&message, now) {
+                logging::log(severity, text);
+            }
+        }
+    });
+}
+
+/// See `Forwarder::take_warning`.
+pub fn take_warning() -> bool {
+    FORWARDER.with(|forwarder| forwarder.borrow_mut().take_warning())
+}
+
+mod tests {
+    use super::*;
+
+    fn message(id: u32, severity: u32) -> Message {
+        Message{
+            context: GlContext::Main,
+            source: gl::DEBUG_SOURCE_API,
+            gl_type: gl::DEBUG_TYPE_ERROR,
+            id,
+            severity,
+            text: "invalid operation".to_string()
+        }
+    }
+
+    #[test]
+    fn repeats_within_interval_are_suppressed_and_counted() {
+        let mut limiter = RateLimiter::new(Duration::from_secs(10));
+        let t0 = Instant::now();
+        let msg = message(1, gl::DEBUG_SEVERITY_MEDIUM);
+
+        assert_eq!(Some(0), limiter.check(&msg, t0));
+        assert_eq!(None, limiter.check(&msg, t0 + Duration::from_secs(1)));
+        assert_eq!(None, limiter.check(&msg, t0 + Duration::from_secs(9)));
+        assert_eq!(Some(2), limiter.check(&msg, t0 + Duration::from_secs(10)));
+        // the interval counts from the last logged occurrence
+        assert_eq!(None, limiter.check(&msg, t0 + Duration::from_secs(19)));
+        assert_eq!(Some(1), limiter.check(&msg, t0 + Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn distinct_messages_are_limited_separately() {
+        let mut limiter = RateLimiter::new(Duration::from_secs(10));
+        let t0 = Instant::now();
+        let msg = message(1, gl::DEBUG_SEVERITY_MEDIUM);
+        let mut other_context = msg.clone();
+        other_context.context = GlContext::Worker;
+        let mut other_text = msg.clone();
+        other_text.text = "out of memory".to_string();
+
+        assert_eq!(Some(0), limiter.check(&msg, t0));
+        assert_eq!(Some(0), limiter.check(&message(2, gl::DEBUG_SEVERITY_MEDIUM), t0));
+        assert_eq!(Some(0), limiter.check(&other_context, t0));
+        assert_eq!(Some(0), limiter.check(&other_text, t0));
+        assert_eq!(None, limiter.check(&msg, t0));
+    }
+
+    #[test]
+    fn old_messages_are_forgotten_when_too_many() {
+        let mut limiter = RateLimiter::new(Duration::from_secs(10));
+        let t0 = Instant::now();
+        for id in 0..MAX_DISTINCT_MESSAGES as u32 { limiter.check(&message(id, gl::DEBUG_SEVERITY_LOW), t0); }
+        assert_eq!(MAX_DISTINCT_MESSAGES, limiter.num_distinct());
+
+        let new_id = MAX_DISTINCT_MESSAGES as u32;
+        assert_eq!(Some(0), limiter.check(&message(new_id, gl::DEBUG_SEVERITY_LOW), t0 + Duration::from_secs(10)));
+        assert_eq!(1, limiter.num_distinct());
+    }
+
+    #[test]
+    fn forwarded_entry_reports_suppressed_repeats_and_severity() {
+        let mut forwarder = Forwarder::new(Duration::from_secs(10));
+        let t0 = Instant::now();
+        let msg = message(1282, gl::DEBUG_SEVERITY_MEDIUM);
+
+        let (severity, text) = forwarder.process(&msg, t0).unwrap();
+        assert_eq!(logging::Severity::Warning, severity);
+        assert_eq!("OpenGL (main context) API error 1282: invalid operation", text);
+        assert!(forwarder.process(&msg, t0).is_none());
+        assert!(forwarder.process(&msg, t0).is_none());
+        let (_, text) = forwarder.process(&msg, t0 + Duration::from_secs(10)).unwrap();
+        assert!(text.ends_with("(repeated 2 more times)"));
+
+        let (severity, _) = forwarder.process(&message(1, gl::DEBUG_SEVERITY_NOTIFICATION), t0).unwrap();
+        assert_eq!(logging::Severity::Info, severity);
+    }
+
+    #[test]
+    fn warning_given_once_per_run() {
+        let mut forwarder = Forwarder::new(Duration::from_secs(10));
+        let t0 = Instant::now();
+
+        forwarder.process(&message(1, gl::DEBUG_SEVERITY_MEDIUM), t0);
+        assert!(!forwarder.take_warning());
+
+        let (severity, _) = forwarder.process(&message(2, gl::DEBUG_SEVERITY_HIGH), t0).unwrap();
+        assert_eq!(logging::Severity::Error, severity);
+        assert!(forwarder.take_warning());
+        assert!(!forwarder.take_warning());
+
+        forwarder.process(&message(3, gl::DEBUG_SEVERITY_HIGH), t0 + Duration::from_secs(20));
+        assert!(!forwarder.take_warning());
+    }
+}
@@ -0,0 +1,44 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Headless OpenGL rendering outside of the main window (e.g., for tests of shaders).
+
+use glium::glutin;
+
+/// Returns a headless renderer with an OpenGL 3.3 context (may be called on any thread); `None` if no context can be
+/// created (e.g., on a machine without a display server or GPU).
+pub fn create_renderer() -> Option<glium::HeadlessRenderer> {
+    #[cfg(unix)]
+    use glutin::platform::unix::EventLoopExtUnix;
+    #[cfg(windows)]
+    use glutin::platform::windows::EventLoopExtWindows;
+
+    // panics if there is no display server
+    let event_loop = std::panic::catch_unwind(glutin::event_loop::EventLoop::<()>::new_any_thread).ok()?;
+    let context = glutin::ContextBuilder::new()
+        .with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, (3, 3)))
+        .with_gl_profile(glutin::GlProfile::Core)
+        .build_headless(&event_loop, glutin::dpi::PhysicalSize{ width: 16, height: 16 })
+        .ok()?;
+    let context = unsafe { context.make_current() }.ok()?;
+    // used by, e.g., `image_utils::image_from_texture`
+    crate::runner::load_raw_gl_functions(|symbol| context.get_proc_address(symbol) as _);
+
+    glium::HeadlessRenderer::new(context).ok()
+}
@@ -0,0 +1,209 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Registry of GL resources created on the main thread (for debugging purposes only; it does not own them).
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResourceInfo {
+    /// Object owning the resource (e.g., a view).
+    pub owner: String,
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub format: &'static str,
+    pub bytes_per_pixel: u32,
+    pub num_samples: u32
+}
+
+impl ResourceInfo {
+    /// Estimated size (drivers may use padding or compression).
+    pub fn bytes(&self) -> u64 {
+        self.width as u64 * self.height as u64 * self.bytes_per_pixel as u64 * self.num_samples as u64
+    }
+}
+
+/// Returns (format name, bytes per pixel).
+pub fn color_format_info(format: glium::texture::UncompressedFloatFormat) -> (&'static str, u32) {
+    use glium::texture::UncompressedFloatFormat;
+
+    match format {
+        UncompressedFloatFormat::U8U8U8 => ("RGB8", 3),
+        UncompressedFloatFormat::U8U8U8U8 => ("RGBA8", 4),
+        UncompressedFloatFormat::U16U16U16 => ("RGB16", 6),
+        UncompressedFloatFormat::U8 => ("R8", 1),
+        UncompressedFloatFormat::U16 => ("R16", 2),
+        _ => ("other", 4)
+    }
+}
+
+pub struct Registry {
+    next_id: u64,
+    entries: BTreeMap<u64, ResourceInfo>
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry{ next_id: 0, entries: BTreeMap::new() }
+    }
+
+    fn insert(&mut self, info: ResourceInfo) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.insert(id, info);
+
+        id
+    }
+
+    fn remove(&mut self, id: u64) {
+        self.entries.remove(&id);
+    }
+
+    /// Returns entries in the order of registration.
+    pub fn entries(&self) -> impl Iterator<Item=&ResourceInfo> {
+        self.entries.values()
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.entries.values().map(|e| e.bytes()).sum()
+    }
+}
+
+thread_local! {
+    static REGISTRY: RefCell<Registry> = RefCell::new(Registry::new());
+}
+
+/// Registration of a resource; unregisters it when dropped (keep it next to the resource).
+pub struct Registration {
+    id: u64
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        // the registry may be already gone if the thread is exiting
+        let _ = REGISTRY.try_with(|registry| registry.borrow_mut().remove(self.id));
+    }
+}
+
+pub fn register(info: ResourceInfo) -> Registration {
+    Registration{ id: REGISTRY.with(|registry| registry.borrow_mut().insert(info)) }
+}
+
+pub fn register_texture(
+    owner: &str,
+    name: &str,
+    texture: &glium::Texture2d,
+    format: glium::texture::UncompressedFloatFormat
+) -> Registration {
+    let (format, bytes_per_pixel) = color_format_info(format);
+
+    register(ResourceInfo{
+        owner: owner.to_string(),
+        name: name.to_string(),
+        width: texture.width(),
+        height: texture.height(),
+        format,
+        bytes_per_pixel,
+        num_samples: 1
+    })
+}
+
+/// Returns (all registered resources, their total size in bytes).
+pub fn snapshot() -> (Vec<ResourceInfo>, u64) {
+    REGISTRY.with(|registry| {
+        let registry = registry.borrow();
+        (registry.entries().cloned().collect(), registry.total_bytes())
+    })
+}
+
+/// Returns snapshot as text (one resource per line).
+pub fn format_snapshot(entries: &[ResourceInfo], total_bytes: u64) -> String {
+    let mut result = String::new();
+    for e in entries {
+        result += &format!(
+            "{}\t{}\t{}x{}\t{}{}\t{}\n",
+            e.owner,
+            e.name,
+            e.width,
+            e.height,
+            e.format,
+            if e.num_samples > 1 { format!(" ({} samples)", e.num_samples) } else { "".to_string() },
+            e.bytes()
+        );
+    }
+    result += &format!("total: {} resources, {} bytes\n", entries.len(), total_bytes);
+
+    result
+}
+
+mod tests {
+    use super::*;
+
+    fn info(name: &str, width: u32, height: u32, bytes_per_pixel: u32) -> ResourceInfo {
+        ResourceInfo{
+            owner: "test".to_string(),
+            name: name.to_string(),
+            width,
+            height,
+            format: "fake",
+            bytes_per_pixel,
+            num_samples: 1
+        }
+    }
+
+    #[test]
+    fn bookkeeping_of_inserted_and_removed_entries() {
+        let mut registry = Registry::new();
+        let a = registry.insert(info("a", 10, 10, 3));
+        let b = registry.insert(info("b", 20, 10, 4));
+        assert_eq!(300 + 800, registry.total_bytes());
+
+        registry.remove(a);
+        assert_eq!(vec!["b"], registry.entries().map(|e| e.name.as_str()).collect::<Vec<_>>());
+        assert_eq!(800, registry.total_bytes());
+
+        registry.remove(b);
+        assert_eq!(0, registry.entries().count());
+    }
+
+    #[test]
+    fn registration_removed_on_drop() {
+        let first = register(info("first", 100, 100, 3));
+        {
+            let _second = register(info("second", 100, 100, 3));
+            assert_eq!(2, snapshot().0.len());
+        }
+        let (entries, total_bytes) = snapshot();
+        assert_eq!(1, entries.len());
+        assert_eq!("first", entries[0].name);
+        assert_eq!(30_000, total_bytes);
+
+        drop(first);
+        assert!(snapshot().0.is_empty());
+    }
+
+    #[test]
+    fn multisampled_size() {
+        let mut i = info("ms", 10, 10, 4);
+        i.num_samples = 8;
+        assert_eq!(3200, i.bytes());
+    }
+}
@@ -0,0 +1,148 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Recovery from failed rendering (e.g., after a GPU context loss on suspend/resume). A failure is logged and
+//! the view re-renders in the next frame; after too many consecutive failures the user is advised to restart.
+
+use crate::logging;
+use std::cell::RefCell;
+
+pub const DEFAULT_MAX_CONSECUTIVE_FAILURES: u32 = 10;
+
+pub const RESTART_ADVICE: &str = "Rendering keeps failing (the GPU driver may have been reset). \
+    Restarting Vislumino is recommended (settings have been saved).";
+
+/// Counts consecutive rendering failures (of all views).
+pub struct FailureTracker {
+    consecutive_failures: u32,
+    max_consecutive_failures: u32,
+    /// Set when `max_consecutive_failures` is reached; cleared by `take_restart_advice`.
+    restart_advised: bool
+}
+
+impl FailureTracker {
+    pub fn new(max_consecutive_failures: u32) -> FailureTracker {
+        FailureTracker{
+            consecutive_failures: 0,
+            max_consecutive_failures: max_consecutive_failures.max(1),
+            restart_advised: false
+        }
+    }
+
+    pub fn consecutive_failures(&self) -> u32 { self.consecutive_failures }
+
+    /// Records the result of a rendering attempt; returns `true` if it succeeded.
+    pub fn record<E: std::fmt::Display>(&mut self, what: &str, result: Result<(), E>) -> bool {
+        match result {
+            Ok(()) => {
+                self.consecutive_failures = 0;
+                true
+            },
+
+            Err(e) => {
+                logging::log_warning!("Rendering of {} failed: {}; will retry.", what, e);
+                self.consecutive_failures += 1;
+                // advised once per series of failures
+                if self.consecutive_failures == self.max_consecutive_failures { self.restart_advised = true; }
+                false
+            }
+        }
+    }
+
+    /// Returns `true` (once per series of failures) if the maximum number of consecutive failures has been reached.
+    pub fn take_restart_advice(&mut self) -> bool {
+        std::mem::replace(&mut self.restart_advised, false)
+    }
+}
+
+thread_local! {
+    static TRACKER: RefCell<FailureTracker> = RefCell::new(FailureTracker::new(DEFAULT_MAX_CONSECUTIVE_FAILURES));
+}
+
+pub fn set_max_consecutive_failures(value: u32) {
+    TRACKER.with(|tracker| *tracker.borrow_mut() = FailureTracker::new(value));
+}
+
+/// Runs `render` (which is to render `what`); returns `false` if it failed, in which case the caller should try
+/// again in the next frame.
+pub fn render_checked<E: std::fmt::Display, F: FnOnce() -> Result<(), E>>(what: &str, render: F) -> bool {
+    let result = render();
+    TRACKER.with(|tracker| tracker.borrow_mut().record(what, result))
+}
+
+/// See `FailureTracker::take_restart_advice`.
+pub fn take_restart_advice() -> bool {
+    TRACKER.with(|tracker| tracker.borrow_mut().take_restart_advice())
+}
+
+mod tests {
+    use super::*;
+
+    /// Simulated GPU error.
+    struct ContextLost;
+
+    impl std::fmt::Display for ContextLost {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "context lost") }
+    }
+
+    fn fail(tracker: &mut FailureTracker) -> bool { tracker.record("test", Err(ContextLost)) }
+
+    fn succeed(tracker: &mut FailureTracker) -> bool { tracker.record::<ContextLost>("test", Ok(())) }
+
+    #[test]
+    fn success_resets_failure_count() {
+        let mut tracker = FailureTracker::new(3);
+        assert!(!fail(&mut tracker));
+        assert!(!fail(&mut tracker));
+        assert_eq!(2, tracker.consecutive_failures());
+
+        assert!(succeed(&mut tracker));
+        assert_eq!(0, tracker.consecutive_failures());
+        assert!(!tracker.take_restart_advice());
+    }
+
+    #[test]
+    fn restart_advised_once_after_max_failures() {
+        let mut tracker = FailureTracker::new(3);
+        fail(&mut tracker);
+        fail(&mut tracker);
+        assert!(!tracker.take_restart_advice());
+
+        fail(&mut tracker);
+        assert!(tracker.take_restart_advice());
+        assert!(!tracker.take_restart_advice());
+
+        // further failures of the same series do not repeat the advice
+        fail(&mut tracker);
+        assert!(!tracker.take_restart_advice());
+
+        // a new series does
+        succeed(&mut tracker);
+        for _ in 0..3 { fail(&mut tracker); }
+        assert!(tracker.take_restart_advice());
+    }
+
+    #[test]
+    fn render_checked_reports_failure() {
+        set_max_consecutive_failures(1);
+        assert!(render_checked("test", || Ok::<(), ContextLost>(())));
+        assert!(!render_checked("test", || Err(ContextLost)));
+        assert!(take_restart_advice());
+    }
+}
@@ -0,0 +1,170 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Skipping renderings of views which are not visible (and limiting them while the application is not focused).
+
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Minimum interval between renderings of a visible view while the application is not focused.
+const UNFOCUSED_RENDER_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Rendering statistics of a view (for debugging purposes only).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderCounts {
+    /// Object owning the throttle (e.g., a view).
+    pub owner: String,
+    pub visible: bool,
+    pub rendered: u64,
+    pub skipped: u64
+}
+
+thread_local! {
+    static COUNTS: RefCell<BTreeMap<u64, RenderCounts>> = RefCell::new(BTreeMap::new());
+    static NEXT_ID: Cell<u64> = Cell::new(0);
+}
+
+/// Returns rendering statistics of all existing throttles (in the order of creation).
+pub fn counts() -> Vec<RenderCounts> {
+    COUNTS.with(|counts| counts.borrow().values().cloned().collect())
+}
+
+fn update_counts(id: u64, f: impl FnOnce(&mut RenderCounts)) {
+    COUNTS.with(|counts| if let Some(entry) = counts.borrow_mut().get_mut(&id) { f(entry); });
+}
+
+/// Decides whether a view may render; a view which skips a rendering has to mark it as pending and repeat it when
+/// allowed again.
+pub struct RenderThrottle {
+    /// Key in `COUNTS`.
+    id: u64,
+    /// The view's window is shown (not collapsed, not an inactive docked tab).
+    visible: bool,
+    app_focused: bool,
+    last_rendering: Cell<Option<Instant>>
+}
+
+impl RenderThrottle {
+    /// Initially the view is considered visible.
+    pub fn new(owner: &str) -> RenderThrottle {
+        let id = NEXT_ID.with(|next_id| next_id.replace(next_id.get() + 1));
+        COUNTS.with(|counts| counts.borrow_mut().insert(
+            id, RenderCounts{ owner: owner.to_string(), visible: true, ..Default::default() }
+        ));
+
+        RenderThrottle{ id, visible: true, app_focused: true, last_rendering: Cell::new(None) }
+    }
+
+    pub fn set_app_focused(&mut self, value: bool) { self.app_focused = value; }
+
+    /// Returns `true` if the view has just become visible.
+    pub fn set_visible(&mut self, value: bool) -> bool {
+        let became_visible = value && !self.visible;
+        self.visible = value;
+        update_counts(self.id, |counts| counts.visible = value);
+
+        became_visible
+    }
+
+    /// Returns `true` if the view may render at `now`.
+    pub fn allows(&self, now: Instant) -> bool {
+        self.visible && (self.app_focused || self.last_rendering.get().map_or(true, |last| {
+            now.saturating_duration_since(last) >= UNFOCUSED_RENDER_INTERVAL
+        }))
+    }
+
+    /// Like `allows`, but also records the rendering (or its skipping).
+    pub fn begin_rendering(&self, now: Instant) -> bool {
+        let allowed = self.allows(now);
+        if allowed { self.last_rendering.set(Some(now)); }
+        update_counts(self.id, |counts| if allowed { counts.rendered += 1; } else { counts.skipped += 1; });
+
+        allowed
+    }
+}
+
+impl Drop for RenderThrottle {
+    fn drop(&mut self) {
+        // the counts may be already gone if the thread is exiting
+        let _ = COUNTS.try_with(|counts| counts.borrow_mut().remove(&self.id));
+    }
+}
+
+mod tests {
+    use super::*;
+
+    fn counts_of(owner: &str) -> RenderCounts {
+        counts().into_iter().find(|c| c.owner == owner).unwrap()
+    }
+
+    fn expected(owner: &str, visible: bool, rendered: u64, skipped: u64) -> RenderCounts {
+        RenderCounts{ owner: owner.to_string(), visible, rendered, skipped }
+    }
+
+    #[test]
+    fn hidden_view_renders_zero_times_during_playback() {
+        let mut throttle = RenderThrottle::new("hidden");
+        assert!(!throttle.set_visible(false));
+        let t0 = Instant::now();
+        for i in 0..100 {
+            assert!(!throttle.begin_rendering(t0 + i * Duration::from_millis(40)));
+        }
+        assert_eq!(expected("hidden", false, 0, 100), counts_of("hidden"));
+
+        assert!(throttle.set_visible(true));
+        assert!(throttle.begin_rendering(t0 + Duration::from_secs(5)));
+        assert_eq!(1, counts_of("hidden").rendered);
+    }
+
+    #[test]
+    fn visible_view_renders_every_time_when_focused() {
+        let throttle = RenderThrottle::new("visible");
+        let t0 = Instant::now();
+        for _ in 0..10 { assert!(throttle.begin_rendering(t0)); }
+        assert_eq!(expected("visible", true, 10, 0), counts_of("visible"));
+    }
+
+    #[test]
+    fn unfocused_application_limits_rendering_rate() {
+        let mut throttle = RenderThrottle::new("unfocused");
+        throttle.set_app_focused(false);
+        let t0 = Instant::now();
+        let interval = Duration::from_millis(50);
+        let num_rendered = (0..20).filter(|i| throttle.begin_rendering(t0 + *i * interval)).count();
+        // 1 s of playback at 20 fps
+        assert_eq!(4, num_rendered);
+
+        throttle.set_app_focused(true);
+        assert!(throttle.begin_rendering(t0 + 20 * interval));
+        assert!(throttle.begin_rendering(t0 + 20 * interval));
+    }
+
+    #[test]
+    fn counts_are_removed_with_the_throttle() {
+        let throttle = RenderThrottle::new("dropped");
+        // a new owner of the same name (e.g., a re-created view) has its own counts
+        let replacement = RenderThrottle::new("dropped");
+        assert_eq!(2, counts().iter().filter(|c| c.owner == "dropped").count());
+        drop(throttle);
+        assert_eq!(1, counts().iter().filter(|c| c.owner == "dropped").count());
+        drop(replacement);
+        assert!(!counts().iter().any(|c| c.owner == "dropped"));
+    }
+}
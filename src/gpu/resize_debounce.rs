@@ -0,0 +1,117 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Delaying re-creation of a view's draw buffers while its window is being resized.
+
+use std::time::{Duration, Instant};
+
+/// Time the requested size of a view has to stay unchanged before its buffers are re-created.
+pub const DEBOUNCE_INTERVAL: Duration = Duration::from_millis(150);
+
+/// Decides when a view's draw buffer is to be re-created for a new size; while the size keeps changing (e.g.,
+/// when dragging a window's edge), the existing buffer is kept and its contents are shown stretched.
+#[derive(Default)]
+pub struct ResizeDebounce {
+    /// Requested size (different from the buffer's) and the time since which it has been requested.
+    pending: Option<([u32; 2], Instant)>,
+    /// The buffer has had the requested size at least once; until then, resizing is not delayed.
+    settled: bool
+}
+
+impl ResizeDebounce {
+    /// Returns `true` if a buffer of `current` size is to be re-created at `now` to have `requested` size
+    /// (to be called every frame).
+    pub fn resize_now(&mut self, current: [u32; 2], requested: [u32; 2], now: Instant) -> bool {
+        if requested == current {
+            self.pending = None;
+            self.settled = true;
+            return false;
+        }
+
+        if !self.settled { return true; }
+
+        match self.pending {
+            Some((size, since)) if size == requested => {
+                let stable = now.saturating_duration_since(since) >= DEBOUNCE_INTERVAL;
+                if stable { self.pending = None; }
+                stable
+            },
+
+            _ => {
+                self.pending = Some((requested, now));
+                false
+            }
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    #[test]
+    fn first_size_is_applied_immediately() {
+        let mut debounce = ResizeDebounce::default();
+        assert!(debounce.resize_now([1, 1], [640, 480], Instant::now()));
+    }
+
+    #[test]
+    fn resizing_is_delayed_until_size_is_stable() {
+        let mut debounce = ResizeDebounce::default();
+        let t0 = Instant::now();
+        assert!(!debounce.resize_now([640, 480], [640, 480], t0));
+
+        // dragging the window's edge for 1 s
+        for i in 1..=60 {
+            assert!(!debounce.resize_now([640, 480], [640 + i, 480], t0 + i * FRAME));
+        }
+        let drag_end = t0 + 60 * FRAME;
+        assert!(!debounce.resize_now([640, 480], [700, 480], drag_end + DEBOUNCE_INTERVAL - FRAME));
+        assert!(debounce.resize_now([640, 480], [700, 480], drag_end + DEBOUNCE_INTERVAL));
+
+        // the buffer has been re-created
+        assert!(!debounce.resize_now([700, 480], [700, 480], drag_end + DEBOUNCE_INTERVAL + FRAME));
+    }
+
+    #[test]
+    fn pause_shorter_than_interval_restarts_waiting_on_next_change() {
+        let mut debounce = ResizeDebounce::default();
+        let t0 = Instant::now();
+        debounce.resize_now([640, 480], [640, 480], t0);
+
+        assert!(!debounce.resize_now([640, 480], [650, 480], t0));
+        assert!(!debounce.resize_now([640, 480], [650, 480], t0 + DEBOUNCE_INTERVAL / 2));
+        assert!(!debounce.resize_now([640, 480], [660, 480], t0 + DEBOUNCE_INTERVAL));
+        assert!(!debounce.resize_now([640, 480], [660, 480], t0 + DEBOUNCE_INTERVAL + DEBOUNCE_INTERVAL / 2));
+        assert!(debounce.resize_now([640, 480], [660, 480], t0 + 2 * DEBOUNCE_INTERVAL));
+    }
+
+    #[test]
+    fn returning_to_buffer_size_cancels_pending_resize() {
+        let mut debounce = ResizeDebounce::default();
+        let t0 = Instant::now();
+        debounce.resize_now([640, 480], [640, 480], t0);
+
+        assert!(!debounce.resize_now([640, 480], [650, 480], t0));
+        assert!(!debounce.resize_now([640, 480], [640, 480], t0 + FRAME));
+        assert!(!debounce.resize_now([640, 480], [650, 480], t0 + DEBOUNCE_INTERVAL));
+        assert!(debounce.resize_now([640, 480], [650, 480], t0 + 2 * DEBOUNCE_INTERVAL));
+    }
+}
@@ -0,0 +1,128 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Texture size limits of the main and the worker's OpenGL contexts (which may be provided by different GPUs,
+//! e.g., on hybrid-graphics laptops).
+
+use std::cell::Cell;
+
+/// The worker's limit is considered drastically smaller if the main context's limit is at least this many times
+/// larger.
+const DRASTIC_RATIO: u32 = 2;
+
+/// Maximum texture sizes of both contexts. The worker reports its limit once at startup (see `spawn_worker`);
+/// until then, only the main context's limit is known.
+pub struct TextureLimits {
+    main: u32,
+    worker: Cell<Option<u32>>,
+    receiver: crossbeam::channel::Receiver<u32>,
+    /// The user has been informed about the worker's drastically smaller limit.
+    reported: Cell<bool>
+}
+
+impl TextureLimits {
+    /// `receiver`: receives the worker context's maximum texture size.
+    pub fn new(main: u32, receiver: crossbeam::channel::Receiver<u32>) -> TextureLimits {
+        TextureLimits{ main, worker: Cell::new(None), receiver, reported: Cell::new(false) }
+    }
+
+    fn worker(&self) -> Option<u32> {
+        if self.worker.get().is_none() {
+            if let Ok(limit) = self.receiver.try_recv() { self.worker.set(Some(limit)); }
+        }
+        self.worker.get()
+    }
+
+    /// Returns the maximum texture size usable in both contexts; to be used for all textures used by worker tasks.
+    pub fn max_texture_size(&self) -> u32 {
+        self.worker().map_or(self.main, |worker| worker.min(self.main))
+    }
+
+    /// Returns `true` if the worker's limit is drastically smaller than the main context's.
+    pub fn worker_limited(&self) -> bool {
+        self.worker().map_or(false, |worker| worker.saturating_mul(DRASTIC_RATIO) <= self.main)
+    }
+
+    /// Returns the limits (main, worker) if the worker is limited and the user has not been informed yet.
+    pub fn take_report(&self) -> Option<(u32, u32)> {
+        if self.reported.get() || !self.worker_limited() { return None; }
+        self.reported.set(true);
+        self.worker().map(|worker| (self.main, worker))
+    }
+}
+
+/// Returns `true` if a texture of `size` does not exceed `max_texture_size`.
+pub fn fits(size: [u32; 2], max_texture_size: u32) -> bool {
+    size[0] <= max_texture_size && size[1] <= max_texture_size
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimum_of_both_limits_is_used_once_reported() {
+        let (sender, receiver) = crossbeam::channel::bounded(1);
+        let limits = TextureLimits::new(16384, receiver);
+        assert_eq!(16384, limits.max_texture_size());
+        assert!(!limits.worker_limited());
+
+        sender.send(4096).unwrap();
+        assert_eq!(4096, limits.max_texture_size());
+        assert!(limits.worker_limited());
+        // the sender may be gone afterwards
+        drop(sender);
+        assert_eq!(4096, limits.max_texture_size());
+    }
+
+    #[test]
+    fn larger_worker_limit_does_not_raise_the_main_one() {
+        let (sender, receiver) = crossbeam::channel::bounded(1);
+        let limits = TextureLimits::new(8192, receiver);
+        sender.send(16384).unwrap();
+        assert_eq!(8192, limits.max_texture_size());
+        assert!(!limits.worker_limited());
+        assert_eq!(None, limits.take_report());
+    }
+
+    #[test]
+    fn drastically_smaller_worker_limit_is_reported_once() {
+        let (sender, receiver) = crossbeam::channel::bounded(1);
+        let limits = TextureLimits::new(16384, receiver);
+        sender.send(12288).unwrap();
+        assert!(!limits.worker_limited());
+        assert_eq!(None, limits.take_report());
+
+        let (sender, receiver) = crossbeam::channel::bounded(1);
+        let limits = TextureLimits::new(16384, receiver);
+        sender.send(8192).unwrap();
+        assert_eq!(Some((16384, 8192)), limits.take_report());
+        assert_eq!(None, limits.take_report());
+    }
+
+    #[test]
+    fn sizes_are_checked_against_the_effective_limit() {
+        let (sender, receiver) = crossbeam::channel::bounded(1);
+        let limits = TextureLimits::new(16384, receiver);
+        assert!(fits([10000, 5000], limits.max_texture_size()));
+
+        sender.send(8192).unwrap();
+        assert!(!fits([10000, 5000], limits.max_texture_size()));
+        assert!(fits([8192, 8192], limits.max_texture_size()));
+    }
+}
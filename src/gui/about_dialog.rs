@@ -17,12 +17,15 @@
 // along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
 //
 
+use crate::config::Configuration;
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+
 const TITLE: &str = "About";
 
-pub fn handle_about_dialog(ui: &imgui::Ui, show: bool) {
+pub fn handle_about_dialog(ui: &imgui::Ui, config: &mut Configuration, show: bool) {
     if show { ui.open_popup(TITLE); }
 
-    ui.popup_modal(TITLE).build(ui, || {
+    modal::modal(ui, config, TITLE, KeyBindings::all(), |key_action, _| {
         ui.text(format!(r#"Vislumino - Astronomy Visualization Tools
 Copyright © 2022 Filip Szczerek <ga.software@yahoo.com>
 
@@ -33,7 +36,7 @@ licensed under GNU General Public License v3 and you are welcome
 to redistribute it under certain conditions. See the LICENSE file for details.
 "#, crate::VERSION_STRING));
         ui.separator();
-        if ui.button("Close") {
+        if modal::default_button(ui, "Close") || key_action != KeyAction::None {
             ui.close_current_popup();
         }
     });
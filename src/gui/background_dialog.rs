@@ -0,0 +1,103 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::background::{self, ThreadPriority};
+use crate::config::Configuration;
+use crate::gui;
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+use strum::IntoEnumIterator;
+
+const TITLE: &str = "Background tasks";
+
+const DEFAULT_MAX_WRITE_RATE: f32 = 20.0;
+
+/// Shows (in a non-modal window, as it is reported by background threads at any time) the most recent failure
+/// to change the priority of background tasks.
+pub fn handle_priority_notice(ui: &imgui::Ui, gui_state: &mut gui::GuiState) {
+    if let Some(notice) = background::take_priority_notice() { gui_state.priority_notice = Some(notice); }
+    let notice = match &gui_state.priority_notice {
+        Some(notice) => notice,
+        None => return
+    };
+
+    let mut close = false;
+    imgui::Window::new(ui, "Background task priority")
+        .always_auto_resize(true)
+        .collapsible(false)
+        .build(|| {
+            ui.text(notice);
+            ui.separator();
+            if ui.button("Close") { close = true; }
+        });
+    if close { gui_state.priority_notice = None; }
+}
+
+pub fn handle_background_dialog(ui: &imgui::Ui, gui_state: &mut gui::GuiState, config: &mut Configuration, show: bool) {
+    if show {
+        gui_state.provisional_background = Some(background::settings());
+        ui.open_popup(TITLE);
+    }
+
+    modal::modal(ui, config, TITLE, KeyBindings::all(), |key_action, config| {
+        let settings = match &mut gui_state.provisional_background {
+            Some(settings) => settings,
+            None => return
+        };
+
+        let priorities: Vec<ThreadPriority> = ThreadPriority::iter().collect();
+        let names: Vec<&str> = priorities.iter().map(ThreadPriority::name).collect();
+        let mut index = priorities.iter().position(|p| *p == settings.priority).unwrap();
+        gui::add_text_before(ui, "priority");
+        gui::tooltip(ui, "Priority of background tasks (e.g., exporting) relative to other programs.");
+        if ui.combo_simple_string("##background-priority", &mut index, &names) {
+            settings.priority = priorities[index];
+        }
+
+        let mut limited = settings.max_write_rate.is_some();
+        if ui.checkbox("limit export writing to", &mut limited) {
+            settings.max_write_rate = if limited { Some(DEFAULT_MAX_WRITE_RATE) } else { None };
+        }
+        if let Some(rate) = &mut settings.max_write_rate {
+            ui.same_line();
+            let w = ui.push_item_width(ui.calc_text_size("MMMMMMMMMMMM")[0]);
+            if ui.input_float("##max-write-rate", rate).step(1.0).display_format("%0.1f MiB/s").build() {
+                *rate = rate.max(0.1);
+            }
+            w.end();
+        }
+
+        ui.text_disabled("Changes apply to tasks started afterwards.");
+        #[cfg(target_os = "linux")]
+        ui.text_disabled("(Raising the priority again may require restarting Vislumino.)");
+
+        ui.separator();
+
+        if modal::default_button(ui, "OK") || key_action == KeyAction::Accept {
+            background::set_settings(*settings);
+            settings.store(config);
+            gui_state.provisional_background = None;
+            ui.close_current_popup();
+        }
+        ui.same_line();
+        if ui.button("Cancel") || key_action == KeyAction::Cancel {
+            gui_state.provisional_background = None;
+            ui.close_current_popup();
+        }
+    });
+}
@@ -0,0 +1,160 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::config::{self, Backup, Configuration, GuiConfig};
+use crate::fmt;
+use crate::gui;
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+use std::time::SystemTime;
+
+const TITLE: &str = "Configuration backups";
+const RESTORE_TITLE: &str = "Restore configuration";
+
+#[derive(Default)]
+pub struct ConfigBackupDialog {
+    /// Number of backups to keep, as edited.
+    num_backups: u32,
+    backups: Vec<Backup>,
+    /// Index in `backups`.
+    selected: Option<usize>
+}
+
+/// Returns a description of `backup` (e.g., "#1, made 2 h 5 min ago").
+fn backup_label(backup: &Backup, now: SystemTime, format: &fmt::Preferences) -> String {
+    match backup.modified.and_then(|modified| now.duration_since(modified).ok()) {
+        Some(age) => format!("#{}, made {} ago", backup.slot, fmt::format_duration(age, format)),
+        None => format!("#{}", backup.slot)
+    }
+}
+
+pub fn handle_config_backup_dialog(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
+    show: bool
+) {
+    if show {
+        gui_state.config_backup_dialog = ConfigBackupDialog{
+            num_backups: config.num_backups().unwrap_or(config::DEFAULT_NUM_BACKUPS),
+            backups: config.backups(),
+            selected: None
+        };
+        ui.open_popup(TITLE);
+    }
+
+    modal::modal(ui, config, TITLE, KeyBindings::all(), |key_action, config| {
+        let dialog = &mut gui_state.config_backup_dialog;
+
+        let mut enabled = dialog.num_backups > 0;
+        if ui.checkbox("keep backups", &mut enabled) {
+            dialog.num_backups = if enabled { config::DEFAULT_NUM_BACKUPS } else { 0 };
+        }
+        gui::tooltip(ui, "Before the configuration is first saved in a session, the previous file is copied \
+            (the most recent copy has number 1; older ones are renumbered and the oldest removed).");
+        if enabled {
+            ui.same_line();
+            let mut value = dialog.num_backups as i32;
+            let w = ui.push_item_width(ui.calc_text_size("MMMMMMM")[0]);
+            if ui.input_int("##num-config-backups", &mut value).step(1).build() {
+                dialog.num_backups = (value.max(1) as u32).min(config::MAX_NUM_BACKUPS);
+            }
+            w.end();
+        }
+
+        ui.separator();
+        ui.text("Available backups:");
+        if dialog.backups.is_empty() { ui.text_disabled("(none)"); }
+        let now = SystemTime::now();
+        for (idx, backup) in dialog.backups.iter().enumerate() {
+            if ui.radio_button_bool(backup_label(backup, now, &gui_state.format), dialog.selected == Some(idx)) {
+                dialog.selected = Some(idx);
+            }
+        }
+
+        let token = ui.begin_disabled(dialog.selected.is_none());
+        if ui.button("Restore...") { ui.open_popup(RESTORE_TITLE); }
+        token.end();
+        gui::tooltip(ui, "Replace the current configuration with the selected backup.");
+
+        ui.separator();
+
+        if modal::default_button(ui, "OK") || key_action == KeyAction::Accept {
+            config.set_num_backups(dialog.num_backups);
+            ui.close_current_popup();
+        }
+        ui.same_line();
+        if ui.button("Cancel") || key_action == KeyAction::Cancel {
+            ui.close_current_popup();
+        }
+
+        if let Some(message_box) = handle_restore_confirmation(ui, gui_state, config) {
+            ui.open_popup(&message_box.title);
+            gui_state.message_box = Some(message_box);
+            let dialog = &mut gui_state.config_backup_dialog;
+            dialog.num_backups = config.num_backups().unwrap_or(config::DEFAULT_NUM_BACKUPS);
+            dialog.backups = config.backups();
+            dialog.selected = None;
+        }
+
+        gui::handle_message_box(ui, gui_state, config);
+    });
+}
+
+/// Returns the message describing the result once the selected backup has been restored (or failed to be).
+fn handle_restore_confirmation(
+    ui: &imgui::Ui,
+    gui_state: &gui::GuiState,
+    config: &mut Configuration
+) -> Option<gui::MessageBox> {
+    let mut result = None;
+
+    modal::modal(ui, config, RESTORE_TITLE, KeyBindings::all(), |key_action, config| {
+        let dialog = &gui_state.config_backup_dialog;
+        let backup = match dialog.selected.and_then(|idx| dialog.backups.get(idx)) {
+            Some(backup) => backup.clone(),
+            None => { ui.close_current_popup(); return; }
+        };
+
+        ui.text(format!(
+            "Replace the current configuration with backup {}?",
+            backup_label(&backup, SystemTime::now(), &gui_state.format)
+        ));
+        ui.text_disabled("The current configuration is backed up first.");
+
+        if modal::default_button(ui, "Restore") || key_action == KeyAction::Accept {
+            result = Some(match config.restore_backup(&backup) {
+                Ok(()) => gui::MessageBox{
+                    title: "Configuration restored".to_string(),
+                    message: "Restart Vislumino for all restored settings to take effect.".to_string()
+                },
+                Err(e) => gui::MessageBox{
+                    title: "Error".to_string(),
+                    message: format!("Could not restore {}: {}.", backup.path.to_string_lossy(), e)
+                }
+            });
+            ui.close_current_popup();
+        }
+        ui.same_line();
+        if ui.button("Cancel") || key_action == KeyAction::Cancel {
+            ui.close_current_popup();
+        }
+    });
+
+    result
+}
@@ -0,0 +1,70 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Offers the crash report saved by the previous session.
+
+use crate::config::Configuration;
+use crate::crash_report;
+use crate::gui;
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+use crate::logging;
+
+const TITLE: &str = "Crash report";
+
+/// `show`: the dialog is to be opened (if there is a crash report); called in every frame.
+pub fn handle_crash_report_dialog(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
+    show: bool
+) {
+    if show {
+        gui_state.crash_report = crash_report::pending();
+        if gui_state.crash_report.is_some() { ui.open_popup(TITLE); }
+    }
+
+    modal::modal(ui, config, TITLE, KeyBindings::all(), |key_action, _| {
+        let path = match &gui_state.crash_report {
+            Some(path) => path.clone(),
+            None => { ui.close_current_popup(); return; }
+        };
+
+        ui.text("Vislumino did not exit properly during the previous session. A crash report has been saved:");
+        ui.text_disabled(path.to_string_lossy());
+        ui.text("Please consider sending it to the author together with a description of what you were doing.");
+        ui.separator();
+
+        if ui.button("Show file") { gui::file_manager::reveal(&path); }
+        gui::tooltip(ui, "Show the report in the file manager.");
+        ui.same_line();
+        if ui.button("Delete") {
+            if let Err(e) = std::fs::remove_file(&path) {
+                logging::log_error!("Error deleting crash report {}: {}.", path.to_string_lossy(), e);
+            }
+            gui_state.crash_report = None;
+            ui.close_current_popup();
+        }
+        ui.same_line();
+        if modal::default_button(ui, "Close") || key_action == KeyAction::Accept || key_action == KeyAction::Cancel {
+            gui_state.crash_report = None;
+            ui.close_current_popup();
+        }
+        gui::tooltip(ui, "Keep the report; it will be offered again at the next start.");
+    });
+}
@@ -0,0 +1,246 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Short crossfade between consecutive frames shown in a view.
+
+use crate::data::Vertex2;
+use crate::gpu::registry;
+use crate::gui::{DrawBuffer, GuiState};
+use crate::gui::motion::AnimationSource;
+use glium::{Surface, Texture2d, uniform};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+pub const MIN_DURATION: Duration = Duration::from_millis(100);
+
+pub const MAX_DURATION: Duration = Duration::from_millis(200);
+
+pub const DEFAULT_DURATION: Duration = Duration::from_millis(150);
+
+pub const ANIMATION: AnimationSource = AnimationSource{ name: "frame crossfade", duration: default_duration };
+
+/// Returns the fade duration to use when `configured` is set (zero if motion is to be reduced).
+pub fn duration(gui_state: &GuiState, configured: Duration) -> Duration { gui_state.animation_duration(configured) }
+
+fn default_duration(gui_state: &GuiState) -> Duration { duration(gui_state, DEFAULT_DURATION) }
+
+/// Decides when to fade and how far the fade has progressed.
+#[derive(Debug, Default)]
+struct FadeTiming {
+    /// Zero disables fading.
+    duration: Duration,
+    /// Start of the fade in progress.
+    start: Option<Instant>,
+    last_frame_change: Option<Instant>
+}
+
+impl FadeTiming {
+    fn set_duration(&mut self, duration: Duration) {
+        if duration != self.duration {
+            self.duration = duration;
+            self.start = None;
+        }
+    }
+
+    /// To be called when a different frame is about to be shown; returns `true` if a fade starts.
+    fn on_frame_changed(&mut self, now: Instant) -> bool {
+        let since_last_change = self.last_frame_change.map(|t| now.saturating_duration_since(t));
+        self.last_frame_change = Some(now);
+
+        // frames changing faster than the fade would finish (e.g., during playback) are shown without fading
+        let bypassed = since_last_change.map_or(false, |interval| interval < self.duration);
+        self.start = if self.duration.is_zero() || bypassed { None } else { Some(now) };
+
+        self.start.is_some()
+    }
+
+    /// Returns the weight (from [0; 1)) of the new frame at `now`; `None` if no fade is in progress.
+    fn blend(&self, now: Instant) -> Option<f32> {
+        let start = self.start?;
+        let t = now.saturating_duration_since(start).as_secs_f32() / self.duration.as_secs_f32();
+        if t < 1.0 { Some(t) } else { None }
+    }
+
+    /// Ends a finished fade; returns `true` if the view has to be rendered again (fade in progress or just finished).
+    fn step(&mut self, now: Instant) -> bool {
+        match self.start {
+            None => false,
+            Some(_) => {
+                if self.blend(now).is_none() { self.start = None; }
+                true
+            }
+        }
+    }
+}
+
+/// Keeps a copy of a view's contents from before a frame change and blends it with the new contents.
+pub struct Crossfade {
+    timing: FadeTiming,
+    /// Has the same size and orientation as the view's storage buffer.
+    previous: Option<Texture2d>,
+    registration: Option<registry::Registration>,
+    owner: String,
+    display: glium::Display,
+    prog: Rc<glium::Program>,
+    unit_quad: Rc<glium::VertexBuffer<Vertex2>>
+}
+
+impl Crossfade {
+    pub fn new(
+        owner: &str,
+        display: &glium::Display,
+        prog: &Rc<glium::Program>,
+        unit_quad: &Rc<glium::VertexBuffer<Vertex2>>
+    ) -> Crossfade {
+        Crossfade{
+            timing: Default::default(),
+            previous: None,
+            registration: None,
+            owner: owner.to_string(),
+            display: display.clone(),
+            prog: Rc::clone(prog),
+            unit_quad: Rc::clone(unit_quad)
+        }
+    }
+
+    /// Sets the fade duration; zero disables fading.
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.timing.set_duration(duration);
+        if duration.is_zero() {
+            self.previous = None;
+            self.registration = None;
+        }
+    }
+
+    /// To be called before rendering a different frame into `buf`; saves its current contents if a fade starts.
+    pub fn on_frame_changed(&mut self, buf: &DrawBuffer) {
+        if !self.timing.on_frame_changed(Instant::now()) { return; }
+
+        let size = (buf.width(), buf.height());
+        if self.previous.as_ref().map(|p| (p.width(), p.height())) != Some(size) {
+            self.previous = Texture2d::empty_with_format(
+                &self.display,
+                glium::texture::UncompressedFloatFormat::U8U8U8,
+                glium::texture::MipmapsOption::NoMipmap,
+                size.0,
+                size.1
+            ).ok();
+            self.registration = self.previous.as_ref().map(|previous| registry::register_texture(
+                &self.owner, "crossfade", previous, glium::texture::UncompressedFloatFormat::U8U8U8
+            ));
+        }
+        match &self.previous {
+            Some(previous) => buf.storage_buf().as_surface().fill(
+                &previous.as_surface(), glium::uniforms::MagnifySamplerFilter::Nearest
+            ),
+            None => self.timing.start = None
+        }
+    }
+
+    /// Advances the fade; returns `true` if the view has to be rendered again. To be called in every GUI frame.
+    pub fn step(&mut self) -> bool { self.timing.step(Instant::now()) }
+
+    /// Blends the saved contents into `buf`'s storage buffer (to be called after `DrawBuffer::update_storage_buf`).
+    pub fn apply(&self, buf: &DrawBuffer) -> Result<(), glium::DrawError> {
+        let (blend, previous) = match (self.timing.blend(Instant::now()), &self.previous) {
+            (Some(blend), Some(previous)) => (blend, previous),
+            _ => return Ok(())
+        };
+        // the view has been resized during the fade
+        if previous.width() != buf.width() || previous.height() != buf.height() { return Ok(()); }
+
+        let uniforms = uniform! {
+            previous_texture: previous.sampled(),
+            opacity: 1.0 - blend
+        };
+
+        let mut fbo = glium::framebuffer::SimpleFrameBuffer::new(&self.display, &**buf.storage_buf()).unwrap();
+        fbo.draw(
+            &*self.unit_quad,
+            &glium::index::NoIndices(glium::index::PrimitiveType::TriangleFan),
+            &self.prog,
+            &uniforms,
+            &glium::DrawParameters{ blend: glium::Blend::alpha_blending(), ..Default::default() }
+        )
+    }
+}
+
+mod tests {
+    use super::*;
+
+    const DURATION: Duration = Duration::from_millis(150);
+
+    fn timing() -> FadeTiming {
+        let mut timing = FadeTiming::default();
+        timing.set_duration(DURATION);
+        timing
+    }
+
+    #[test]
+    fn fade_progresses_and_ends_after_duration() {
+        let mut timing = timing();
+        let t0 = Instant::now();
+        assert!(timing.on_frame_changed(t0));
+        assert_eq!(Some(0.0), timing.blend(t0));
+        assert!((timing.blend(t0 + DURATION / 2).unwrap() - 0.5).abs() < 1.0e-5);
+
+        assert!(timing.step(t0 + DURATION / 2));
+        // one more rendering after the end shows only the new frame
+        assert!(timing.step(t0 + DURATION));
+        assert_eq!(None, timing.blend(t0 + DURATION));
+        assert!(!timing.step(t0 + 2 * DURATION));
+    }
+
+    #[test]
+    fn disabled_fade_never_starts() {
+        let mut timing = FadeTiming::default();
+        let t0 = Instant::now();
+        assert!(!timing.on_frame_changed(t0));
+        assert!(!timing.on_frame_changed(t0 + Duration::from_secs(1)));
+        assert!(!timing.step(t0 + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn fast_frame_changes_bypass_the_fade() {
+        let mut timing = timing();
+        let t0 = Instant::now();
+        assert!(timing.on_frame_changed(t0));
+        // playback at 20 fps
+        let interval = Duration::from_millis(50);
+        for i in 1..5 {
+            assert!(!timing.on_frame_changed(t0 + i * interval));
+            assert_eq!(None, timing.blend(t0 + i * interval));
+        }
+        // stepping manually after playback stops
+        let t1 = t0 + 4 * interval + Duration::from_secs(1);
+        assert!(timing.on_frame_changed(t1));
+        assert_eq!(Some(0.0), timing.blend(t1));
+    }
+
+    #[test]
+    fn changing_duration_cancels_fade() {
+        let mut timing = timing();
+        let t0 = Instant::now();
+        assert!(timing.on_frame_changed(t0));
+        timing.set_duration(Duration::from_millis(200));
+        assert_eq!(None, timing.blend(t0));
+        timing.set_duration(Duration::ZERO);
+        assert!(!timing.on_frame_changed(t0 + Duration::from_secs(1)));
+    }
+}
@@ -17,6 +17,7 @@
 // along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
 //
 
+use crate::gpu::registry;
 use glium::Surface;
 use glium::texture::{
     depth_texture2d_multisample::DepthTexture2dMultisample,
@@ -74,7 +75,12 @@ pub struct DrawBuffer {
     /// GL program to handle texture copying with multi-sampling.
     texture_copy_multi_gl_prog: Rc<glium::Program>,
 
-    unit_quad: Rc<glium::VertexBuffer<crate::data::Vertex2>>
+    unit_quad: Rc<glium::VertexBuffer<crate::data::Vertex2>>,
+
+    /// (owner, name) under which the buffers are shown in the GPU resource registry.
+    registry_name: Option<(String, String)>,
+
+    registrations: Vec<registry::Registration>
 }
 
 impl DrawBuffer {
@@ -91,10 +97,58 @@ impl DrawBuffer {
         self.id = id;
         self.draw_bufs = draw_bufs;
         self.storage_buf = storage_buf;
+        self.update_registrations();
+    }
+
+    /// Registers the buffers in the GPU resource registry; they stay registered (also after re-creation on resize)
+    /// until dropped.
+    pub fn register(&mut self, owner: &str, name: &str) {
+        self.registry_name = Some((owner.to_string(), name.to_string()));
+        self.update_registrations();
+    }
+
+    fn update_registrations(&mut self) {
+        self.registrations.clear();
+        let (width, height) = (self.width(), self.height());
+
+        if let Some((owner, name)) = &self.registry_name {
+            let num_samples = match self.draw_bufs.sampling() {
+                Sampling::Single => 1,
+                Sampling::Multi => NUM_SAMPLES
+            };
+            let (format, bytes_per_pixel) = registry::color_format_info(COLOR_FORMAT);
+
+            self.registrations.push(registry::register(registry::ResourceInfo{
+                owner: owner.clone(),
+                name: format!("{} (draw)", name),
+                width,
+                height,
+                format,
+                bytes_per_pixel,
+                num_samples
+            }));
+
+            self.registrations.push(registry::register(registry::ResourceInfo{
+                owner: owner.clone(),
+                name: format!("{} (depth)", name),
+                width,
+                height,
+                format: "D24",
+                bytes_per_pixel: 4,
+                num_samples
+            }));
+
+            self.registrations.push(registry::register_texture(
+                owner,
+                &format!("{} (storage)", name),
+                &self.storage_buf,
+                COLOR_FORMAT
+            ));
+        }
     }
 
     /// If something was rendered using the result of `frame_buf()`, this method must be called afterwards.
-    pub fn update_storage_buf(&self) {
+    pub fn update_storage_buf(&self) -> Result<(), glium::DrawError> {
         let mut fbo = glium::framebuffer::SimpleFrameBuffer::new(&self.display, &*self.storage_buf).unwrap();
 
         match &self.draw_bufs {
@@ -109,7 +163,7 @@ impl DrawBuffer {
                     &self.texture_copy_single_gl_prog,
                     &uniforms,
                     &Default::default()
-                ).unwrap();
+                )
             },
 
             Buffers::MultiSampling(draw_buf, _) => {
@@ -123,9 +177,9 @@ impl DrawBuffer {
                     &self.texture_copy_multi_gl_prog,
                     &uniforms,
                     &Default::default()
-                ).unwrap();
+                )
             },
-        };
+        }
     }
 
     pub fn storage_buf(&self) -> &Rc<Texture2d> {
@@ -174,7 +228,9 @@ impl DrawBuffer {
             storage_buf,
             unit_quad: Rc::clone(unit_quad),
             texture_copy_single_gl_prog: Rc::clone(texture_copy_single_gl_prog),
-            texture_copy_multi_gl_prog: Rc::clone(texture_copy_multi_gl_prog)
+            texture_copy_multi_gl_prog: Rc::clone(texture_copy_multi_gl_prog),
+            registry_name: None,
+            registrations: vec![]
         }
     }
 
@@ -188,11 +244,12 @@ impl DrawBuffer {
         width: u32,
         height: u32
     ) -> DrawBuffer {
+        // zero-sized textures cannot be created
         let (id, draw_bufs, storage_buf) = DrawBuffer::create(
             sampling,
             &None,
-            width,
-            height,
+            width.max(1),
+            height.max(1),
             COLOR_FORMAT,
             display,
             &mut renderer.borrow_mut()
@@ -206,7 +263,9 @@ impl DrawBuffer {
             storage_buf,
             unit_quad: Rc::clone(unit_quad),
             texture_copy_single_gl_prog: Rc::clone(texture_copy_single_gl_prog),
-            texture_copy_multi_gl_prog: Rc::clone(texture_copy_multi_gl_prog)
+            texture_copy_multi_gl_prog: Rc::clone(texture_copy_multi_gl_prog),
+            registry_name: None,
+            registrations: vec![]
         }
     }
 
@@ -290,13 +349,14 @@ impl DrawBuffer {
         (id, draw_bufs, storage_buf)
     }
 
-    /// If size changes, underlying texture is created anew.
+    /// If size changes, underlying texture is created anew. Zero sizes (e.g., of a minimized or collapsed view)
+    /// are ignored. Returns `true` if the buffer has been re-created.
     pub fn update_size(
         &mut self,
         width: u32,
         height: u32
     ) -> bool {
-        if width != self.storage_buf.width() || height != self.storage_buf.height() {
+        if needs_resize([self.width(), self.height()], [width, height]) {
             let (id, draw_bufs, storage_buf) = DrawBuffer::create(
                 self.draw_bufs.sampling(),
                 &Some(self.id),
@@ -309,6 +369,7 @@ impl DrawBuffer {
             self.id = id;
             self.draw_bufs = draw_bufs;
             self.storage_buf = storage_buf;
+            self.update_registrations();
 
             true
         } else {
@@ -316,3 +377,27 @@ impl DrawBuffer {
         }
     }
 }
+
+/// Returns `true` if a buffer of size `current` has to be re-created to have `requested` size (never if any of
+/// the requested dimensions is zero).
+fn needs_resize(current: [u32; 2], requested: [u32; 2]) -> bool {
+    requested[0] != 0 && requested[1] != 0 && requested != current
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_size_does_not_cause_resize() {
+        assert!(!needs_resize([256, 256], [0, 100]));
+        assert!(!needs_resize([256, 256], [100, 0]));
+        assert!(!needs_resize([256, 256], [0, 0]));
+    }
+
+    #[test]
+    fn resize_after_restoring_non_zero_size() {
+        assert!(!needs_resize([256, 256], [256, 256]));
+        assert!(needs_resize([256, 256], [300, 256]));
+        assert!(needs_resize([256, 256], [256, 1]));
+    }
+}
@@ -0,0 +1,114 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Helpers for native file dialogs seeded with stored (possibly no longer existing) paths.
+
+use crate::gui;
+use std::path::{Path, PathBuf};
+
+/// Returns the nearest existing directory among `path` and its ancestors; `None` if there is none (e.g., the drive
+/// has been unplugged).
+pub fn nearest_existing_ancestor(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.is_dir())
+        .map(|ancestor| ancestor.to_path_buf())
+}
+
+/// Returns the location to open a file dialog at: the nearest existing ancestor of `stored_path`, or the home
+/// directory.
+pub fn initial_location(stored_path: Option<&Path>) -> PathBuf {
+    stored_path
+        .and_then(nearest_existing_ancestor)
+        .or_else(dirs::home_dir)
+        .unwrap_or_default()
+}
+
+/// Returns the result of a file dialog; if the dialog failed, shows a message box (which has to be handled within
+/// the current window or popup) and returns `None`.
+pub fn checked<T>(ui: &imgui::Ui, gui_state: &mut gui::GuiState, result: native_dialog::Result<T>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            gui_state.message_box = Some(gui::MessageBox{
+                title: "Error".to_string(),
+                message: format!("Could not show the file dialog: {}.", e)
+            });
+            ui.open_popup("Error");
+            None
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vislumino-test-file-dialog-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn existing_directory_is_its_own_nearest_ancestor() {
+        let dir = temp_dir("existing");
+        assert_eq!(Some(dir.clone()), nearest_existing_ancestor(&dir));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_directories_are_skipped() {
+        let dir = temp_dir("missing");
+        assert_eq!(Some(dir.clone()), nearest_existing_ancestor(&dir.join("renamed").join("project")));
+
+        // a file is not a valid location
+        let file = dir.join("frame.png");
+        std::fs::write(&file, b"").unwrap();
+        assert_eq!(Some(dir.clone()), nearest_existing_ancestor(&file));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn relative_path_without_existing_ancestors() {
+        assert_eq!(None, nearest_existing_ancestor(Path::new("")));
+        assert_eq!(None, nearest_existing_ancestor(Path::new("vislumino-nonexistent-1/vislumino-nonexistent-2")));
+    }
+
+    #[test]
+    fn missing_path_falls_back_to_home_directory() {
+        let dir = temp_dir("fallback");
+        assert_eq!(dir, initial_location(Some(&dir.join("a").join("b"))));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(dirs::home_dir().unwrap_or_default(), initial_location(None));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn nonexistent_drive_falls_back_to_home_directory() {
+        // find a drive letter which is not mapped
+        let drive = ('D'..='Z').rev().map(|letter| format!("{}:\\", letter)).find(|drive| !Path::new(drive).exists());
+        if let Some(drive) = drive {
+            let path = Path::new(&drive).join("Projects").join("Jupiter");
+            assert_eq!(None, nearest_existing_ancestor(&path));
+            assert_eq!(dirs::home_dir().unwrap_or_default(), initial_location(Some(&path)));
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Showing files in the system's file manager.
+
+use crate::logging;
+use std::path::Path;
+use std::process::Command;
+
+/// Opens the folder containing `path` in the system's file manager, with the file selected where supported.
+/// Does not block; failures are logged.
+pub fn reveal(path: &Path) {
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        match reveal_command(&path).status() {
+            Err(e) => logging::log_error!("Failed to open the folder of {}: {}.", path.to_string_lossy(), e),
+            // Explorer reports failure even when it succeeds
+            Ok(status) if !status.success() && !cfg!(windows) => logging::log_warning!(
+                "Opening the folder of {} failed ({}).", path.to_string_lossy(), status
+            ),
+            Ok(_) => ()
+        }
+    });
+}
+
+#[cfg(windows)]
+fn reveal_command(path: &Path) -> Command {
+    use std::os::windows::process::CommandExt;
+
+    // Explorer parses its command line on its own and expects the path quoted after the comma
+    let mut arg = std::ffi::OsString::from("/select,\"");
+    arg.push(path.as_os_str());
+    arg.push("\"");
+
+    let mut command = Command::new("explorer");
+    command.raw_arg(arg);
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn reveal_command(path: &Path) -> Command {
+    let mut command = Command::new("open");
+    command.arg("-R").arg(path);
+    command
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn reveal_command(path: &Path) -> Command {
+    // file managers differ in how (or whether) they can select a file, so only the folder is opened
+    let mut command = Command::new("xdg-open");
+    command.arg(path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new(".")));
+    command
+}
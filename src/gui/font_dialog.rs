@@ -17,21 +17,27 @@
 // along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
 //
 
+use crate::config::{Configuration, GuiConfig};
 use crate::runner;
 use crate::gui;
+use crate::gui::modal::{self, KeyAction, KeyBindings};
 
 const TITLE: &str = "Font";
 
+pub const MIN_FONT_SIZE: f32 = 5.0;
+pub const MAX_FONT_SIZE: f32 = 50.0;
+
 pub fn handle_font_dialog(
     ui: &imgui::Ui,
     gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
     show: bool
 ) -> Option<runner::FontSizeRequest> {
     if show { ui.open_popup(TITLE); }
 
     let mut result = None;
 
-    ui.popup_modal(TITLE).build(ui, || {
+    modal::modal(ui, config, TITLE, KeyBindings::all(), |key_action, config| {
         let mut value = if let Some(fs) = gui_state.provisional_font_size {
             fs
         } else {
@@ -44,21 +50,23 @@ pub fn handle_font_dialog(
             .display_format("%0.1f")
             .enter_returns_true(true)
             .build() {
-            if value > 50.0 { value = 50.0 } else if value < 5.0 { value = 5.0 };
+            value = value.max(MIN_FONT_SIZE).min(MAX_FONT_SIZE);
             gui_state.provisional_font_size = Some(value);
             result = Some(runner::FontSizeRequest(value));
         }
 
         ui.separator();
 
-        if ui.button("OK") {
+        if modal::default_button(ui, "OK") || key_action == KeyAction::Accept {
             ui.close_current_popup();
             result = Some(runner::FontSizeRequest(value));
+            gui_state.font_size = value;
+            config.set_font_size(value);
             gui_state.provisional_font_size = None;
         }
         ui.same_line();
 
-        if ui.button("Cancel") {
+        if ui.button("Cancel") || key_action == KeyAction::Cancel {
             ui.close_current_popup();
             if gui_state.provisional_font_size.is_some() {
                 result = Some(runner::FontSizeRequest(gui_state.font_size));
@@ -0,0 +1,85 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use cgmath::Deg;
+use crate::config::{Configuration, GuiConfig};
+use crate::fmt::{self, AngleFormat, DecimalSeparator, UnitSystem};
+use crate::gui;
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+use strum::IntoEnumIterator;
+
+const TITLE: &str = "Number format";
+
+/// Shows a combo box choosing one of `T`'s values.
+fn handle_choice<T: IntoEnumIterator + PartialEq + Copy>(
+    ui: &imgui::Ui,
+    label: &str,
+    id: &str,
+    value: &mut T,
+    name: fn(&T) -> &'static str
+) {
+    let values: Vec<T> = T::iter().collect();
+    let names: Vec<&str> = values.iter().map(name).collect();
+    let mut index = values.iter().position(|v| v == value).unwrap();
+    gui::add_text_before(ui, label);
+    if ui.combo_simple_string(id, &mut index, &names) { *value = values[index]; }
+}
+
+pub fn handle_format_dialog(ui: &imgui::Ui, gui_state: &mut gui::GuiState, config: &mut Configuration, show: bool) {
+    if show {
+        gui_state.provisional_format = Some(gui_state.format);
+        ui.open_popup(TITLE);
+    }
+
+    modal::modal(ui, config, TITLE, KeyBindings::all(), |key_action, config| {
+        let prefs = match &mut gui_state.provisional_format {
+            Some(prefs) => prefs,
+            None => return
+        };
+
+        handle_choice(ui, "angles", "##angle-format", &mut prefs.angle_format, AngleFormat::name);
+        handle_choice(ui, "units", "##unit-system", &mut prefs.unit_system, UnitSystem::name);
+        handle_choice(
+            ui, "decimal separator", "##decimal-separator", &mut prefs.decimal_separator, DecimalSeparator::name
+        );
+        ui.checkbox("thousands separator", &mut prefs.thousands_separator);
+
+        ui.text_disabled(format!(
+            "Example: {}, {}, {}",
+            fmt::format_angle(Deg(-12.51), 2, prefs),
+            fmt::format_length(71492.0, 1, prefs),
+            fmt::format_number(1234.5, 1, prefs)
+        ));
+        ui.text_disabled("(Exported files always use a decimal point.)");
+
+        ui.separator();
+
+        if modal::default_button(ui, "OK") || key_action == KeyAction::Accept {
+            gui_state.format = *prefs;
+            config.set_format_preferences(prefs);
+            gui_state.provisional_format = None;
+            ui.close_current_popup();
+        }
+        ui.same_line();
+        if ui.button("Cancel") || key_action == KeyAction::Cancel {
+            gui_state.provisional_format = None;
+            ui.close_current_popup();
+        }
+    });
+}
@@ -0,0 +1,102 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::gpu::{registry, render_throttle};
+use crate::gui;
+
+/// Shows the list of GL resources known to the GPU resource registry (if the inspector is open).
+pub fn handle_gpu_inspector(ui: &imgui::Ui, gui_state: &mut gui::GuiState) {
+    if !gui_state.gpu_inspector_open { return; }
+
+    let (entries, total_bytes) = registry::snapshot();
+    let mut opened = true;
+    let mut log_clicked = false;
+
+    imgui::Window::new(ui, "GPU resources")
+        .size([640.0, 400.0], imgui::Condition::FirstUseEver)
+        .opened(&mut opened)
+        .build(|| {
+            ui.text(format!("{} resources, {:.1} MiB (estimated)", entries.len(), to_mib(total_bytes)));
+            ui.same_line();
+            if ui.button("Log snapshot to file...") { log_clicked = true; }
+            ui.separator();
+
+            ui.columns(4, "##gpu-resources", true);
+            for header in ["Owner", "Name", "Size/format", "MiB"] {
+                ui.text(header);
+                ui.next_column();
+            }
+            ui.separator();
+            for e in &entries {
+                ui.text(&e.owner);
+                ui.next_column();
+                ui.text(&e.name);
+                ui.next_column();
+                ui.text(format!(
+                    "{}x{} {}{}",
+                    e.width,
+                    e.height,
+                    e.format,
+                    if e.num_samples > 1 { format!(" ({} samples)", e.num_samples) } else { "".to_string() }
+                ));
+                ui.next_column();
+                ui.text(format!("{:.2}", to_mib(e.bytes())));
+                ui.next_column();
+            }
+            ui.columns(1, "##gpu-resources", false);
+
+            ui.separator();
+            ui.text("View renderings (hidden views skip them)");
+            for counts in render_throttle::counts() {
+                ui.text(format!(
+                    "{}: {} rendered, {} skipped{}",
+                    counts.owner,
+                    counts.rendered,
+                    counts.skipped,
+                    if counts.visible { "" } else { " (hidden)" }
+                ));
+            }
+        });
+
+    gui_state.gpu_inspector_open = opened;
+
+    if log_clicked { log_snapshot(ui, gui_state, &registry::format_snapshot(&entries, total_bytes)); }
+}
+
+fn to_mib(bytes: u64) -> f64 {
+    bytes as f64 / (1 << 20) as f64
+}
+
+fn log_snapshot(ui: &imgui::Ui, gui_state: &mut gui::GuiState, snapshot: &str) {
+    let path = gui::file_dialog::checked(
+        ui,
+        gui_state,
+        native_dialog::FileDialog::new().add_filter("text files", &["txt"]).show_save_single_file()
+    ).flatten();
+
+    if let Some(path) = path {
+        if let Err(e) = std::fs::write(&path, snapshot) {
+            gui_state.message_box = Some(gui::MessageBox{
+                title: "Error".to_string(),
+                message: format!("Could not write {}: {}.", path.to_string_lossy(), e)
+            });
+            ui.open_popup("Error");
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::config::{self, Configuration, GuiConfig};
+use crate::gui;
+use crate::logging::{self, LogBuffer, Severity};
+use std::io::Write;
+use strum::IntoEnumIterator;
+
+const LOG_FILE_NAME: &str = "vislumino.log";
+
+const WARNING_COLOR: [f32; 4] = [1.0, 0.8, 0.0, 1.0];
+const ERROR_COLOR: [f32; 4] = [1.0, 0.3, 0.3, 1.0];
+
+pub struct LogWindow {
+    pub open: bool,
+    buffer: LogBuffer,
+    min_severity: Severity,
+    auto_scroll: bool,
+    file: Option<std::fs::File>,
+    /// The log file has already been (re)created in this session; later it is only appended to.
+    file_created: bool,
+    /// Opening the log file has failed; not retried until writing to file is re-enabled.
+    file_failed: bool
+}
+
+impl Default for LogWindow {
+    fn default() -> LogWindow {
+        LogWindow{
+            open: false,
+            buffer: LogBuffer::new(logging::DEFAULT_CAPACITY),
+            min_severity: Severity::Info,
+            auto_scroll: true,
+            file: None,
+            file_created: false,
+            file_failed: false
+        }
+    }
+}
+
+impl LogWindow {
+    fn open_file(&mut self) {
+        let path = config::config_dir_file_path(LOG_FILE_NAME);
+        let mut options = std::fs::OpenOptions::new();
+        if self.file_created { options.append(true); } else { options.write(true).create(true).truncate(true); }
+
+        match options.open(&path) {
+            Ok(mut file) => {
+                // later the file receives only new entries; write out the ones logged before it was enabled
+                if !self.file_created { let _ = file.write_all(self.buffer.to_text(Severity::Info).as_bytes()); }
+                self.file = Some(file);
+                self.file_created = true;
+            },
+
+            Err(e) => {
+                self.file_failed = true;
+                logging::log_error!("Could not open log file {}: {}.", path.to_string_lossy(), e);
+            }
+        }
+    }
+}
+
+/// Collects entries logged since the previous frame and shows the log window (if open); to be called in every frame.
+pub fn handle_log_window(ui: &imgui::Ui, gui_state: &mut gui::GuiState, config: &mut Configuration) {
+    let log = &mut gui_state.log_window;
+
+    let log_to_file = config.log_to_file().unwrap_or(false);
+    if log_to_file && log.file.is_none() && !log.file_failed { log.open_file(); }
+
+    let new_entries = logging::drain_into(&mut log.buffer);
+    if let Some(file) = &mut log.file {
+        for entry in &new_entries {
+            if writeln!(file, "{}", entry.to_text()).is_err() { break; }
+        }
+    }
+
+    if !log.open { return; }
+
+    let mut opened = true;
+    let mut file_toggled = false;
+
+    imgui::Window::new(ui, "Log")
+        .size([720.0, 360.0], imgui::Condition::FirstUseEver)
+        .opened(&mut opened)
+        .build(|| {
+            let severities: Vec<Severity> = Severity::iter().collect();
+            let names: Vec<&str> = severities.iter().map(Severity::name).collect();
+            let mut index = severities.iter().position(|s| *s == log.min_severity).unwrap();
+            gui::add_text_before(ui, "min. severity");
+            let w = ui.push_item_width(ui.calc_text_size("warning")[0] * 2.0);
+            if ui.combo_simple_string("##log-min-severity", &mut index, &names) {
+                log.min_severity = severities[index];
+            }
+            w.end();
+            ui.same_line();
+            ui.checkbox("auto-scroll", &mut log.auto_scroll);
+            ui.same_line();
+            if ui.button("Copy to clipboard") { ui.set_clipboard_text(log.buffer.to_text(log.min_severity)); }
+            gui::tooltip(ui, "Copies the shown entries, e.g., to paste them into a bug report.");
+            ui.same_line();
+            if ui.button("Clear") { log.buffer.clear(); }
+            ui.same_line();
+            let mut value = log_to_file;
+            if ui.checkbox("write to file", &mut value) { file_toggled = true; }
+            gui::tooltip(ui, &format!(
+                "Appends entries to {} (overwritten at program start).",
+                config::config_dir_file_path(LOG_FILE_NAME).to_string_lossy()
+            ));
+            ui.separator();
+
+            ui.child_window("##log-entries").build(|| {
+                if log.buffer.num_discarded() > 0 {
+                    ui.text_disabled(format!("({} earlier entries discarded)", log.buffer.num_discarded()));
+                }
+                for entry in log.buffer.entries(log.min_severity) {
+                    match entry.severity {
+                        Severity::Info => ui.text(entry.to_text()),
+                        Severity::Warning => ui.text_colored(WARNING_COLOR, entry.to_text()),
+                        Severity::Error => ui.text_colored(ERROR_COLOR, entry.to_text())
+                    }
+                }
+                // keep following new entries unless the user has scrolled up
+                if log.auto_scroll && ui.scroll_y() >= ui.scroll_max_y() {
+                    ui.set_scroll_here_y_with_ratio(1.0);
+                }
+            });
+        });
+
+    log.open = opened;
+
+    if file_toggled {
+        config.set_log_to_file(!log_to_file);
+        log.file = None;
+        log.file_failed = false;
+    }
+}
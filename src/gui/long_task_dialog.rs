@@ -17,6 +17,11 @@
 // along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
 //
 
+use crate::cancellation::CancelToken;
+use crate::config::Configuration;
+use crate::gui;
+use crate::gui::modal::{self, KeyBindings};
+use crate::logging;
 use crossbeam::channel::TryRecvError;
 
 pub struct ProgressMsg {
@@ -31,13 +36,49 @@ impl ProgressMsg {
     }
 }
 
+/// How the progress of a long task is shown.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Presentation {
+    /// Modal dialog blocking the rest of the UI.
+    Modal,
+    /// Non-modal progress window; the rest of the UI remains usable (except for actions guarded by the running task).
+    Background,
+    /// The task has ended. Its errors (if any) are reported by its owner in a modal message box.
+    Done
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PresentationEvent {
+    /// The user has clicked "Run in background".
+    SendToBackground,
+    /// The task has ended (successfully, with an error or cancelled).
+    Finished
+}
+
+impl Presentation {
+    /// `background_allowed`: the task may run while the rest of the UI is in use.
+    pub fn next(self, event: PresentationEvent, background_allowed: bool) -> Presentation {
+        match (self, event) {
+            (Presentation::Done, _) | (_, PresentationEvent::Finished) => Presentation::Done,
+            (Presentation::Modal, PresentationEvent::SendToBackground) if !background_allowed => Presentation::Modal,
+            (_, PresentationEvent::SendToBackground) => Presentation::Background
+        }
+    }
+}
+
 /// Note: reports end of task only if `progress_receiver` becomes disconnected; owners of the receiver must remember to
 /// disconnect one way or another (by getting dropped, or by dropping just the sender).
 pub struct LongTaskDialog {
     title: String,
     info: String,
     progress: f32,
-    progress_receiver: crossbeam::channel::Receiver<ProgressMsg>
+    progress_receiver: crossbeam::channel::Receiver<ProgressMsg>,
+    /// If set, cancelling the dialog cancels this token.
+    cancel_token: Option<CancelToken>,
+    /// Cancel has been clicked; waiting for the task to acknowledge it.
+    cancel_requested: bool,
+    background_allowed: bool,
+    presentation: Presentation
 }
 
 impl LongTaskDialog {
@@ -46,37 +87,165 @@ impl LongTaskDialog {
             title,
             info,
             progress: 0.0,
-            progress_receiver
+            progress_receiver,
+            cancel_token: None,
+            cancel_requested: false,
+            background_allowed: false,
+            presentation: Presentation::Modal
+        }
+    }
+
+    pub fn with_cancel_token(mut self, cancel_token: CancelToken) -> LongTaskDialog {
+        self.cancel_token = Some(cancel_token);
+        self
+    }
+
+    /// Allows the user to demote the dialog to a non-modal progress window. Only for tasks which do not conflict
+    /// with the UI's use in the meantime (beyond what the guards of running tasks already prevent).
+    pub fn with_background_allowed(mut self) -> LongTaskDialog {
+        self.background_allowed = true;
+        self
+    }
+
+    pub fn cancel_token(&self) -> Option<&CancelToken> { self.cancel_token.as_ref() }
+
+    /// Returns `true` if the task runs without blocking the UI.
+    pub fn in_background(&self) -> bool { self.presentation == Presentation::Background }
+
+    /// Returns `true` if the task can still be cancelled (i.e., it has not completed and cancelling has not been
+    /// requested yet).
+    fn cancel_enabled(&self) -> bool {
+        !self.cancel_requested && self.presentation != Presentation::Done && self.progress < 1.0
+    }
+
+    /// Processes all pending progress messages.
+    fn receive_progress(&mut self) {
+        loop {
+            match self.progress_receiver.try_recv() {
+                Ok(msg) => {
+                    self.info = msg.info;
+                    self.progress = msg.progress;
+                },
+
+                Err(e) => {
+                    match e {
+                        TryRecvError::Disconnected => self.on_event(PresentationEvent::Finished),
+                        TryRecvError::Empty => ()
+                    }
+                    break;
+                }
+            }
         }
     }
+
+    fn on_event(&mut self, event: PresentationEvent) {
+        self.presentation = self.presentation.next(event, self.background_allowed);
+    }
 }
 
 /// Returns true if the task is still in progress.
-pub fn handle_long_task<F: Fn()>(ui: &imgui::Ui, long_task: &mut LongTaskDialog, on_cancel: F) -> bool {
-    let mut in_progress = true;
-
-    ui.open_popup(&long_task.title);
-    ui.popup_modal(&long_task.title).build(ui, || {
-        match long_task.progress_receiver.try_recv() {
-            Ok(msg) => {
-                long_task.info = msg.info;
-                long_task.progress = msg.progress;
-            },
-
-            Err(e) => match e {
-                TryRecvError::Disconnected => in_progress = false,
-                TryRecvError::Empty => ()
-            }
+///
+/// Cancelling requires an explicit click (there is no keyboard shortcut), so that a long export is not aborted
+/// by accident. The dialog's cancel token (if any) is cancelled before calling `on_cancel`.
+pub fn handle_long_task<F: Fn()>(
+    ui: &imgui::Ui,
+    config: &mut Configuration,
+    long_task: &mut LongTaskDialog,
+    on_cancel: F
+) -> bool {
+    long_task.receive_progress();
+
+    let title = long_task.title.clone();
+    match long_task.presentation {
+        Presentation::Modal => {
+            ui.open_popup(&title);
+            modal::modal(ui, config, &title, KeyBindings::none(), |_, _| {
+                show_progress(ui, long_task, &on_cancel);
+
+                if long_task.background_allowed && !long_task.cancel_requested {
+                    ui.same_line();
+                    if ui.button("Run in background") {
+                        long_task.on_event(PresentationEvent::SendToBackground);
+                        ui.close_current_popup();
+                    }
+                    gui::tooltip(ui, "Show the progress in a small window and keep using the program meanwhile.");
+                }
+            });
+        },
+
+        Presentation::Background => {
+            imgui::Window::new(ui, &format!("{}###long-task-background", title))
+                .always_auto_resize(true)
+                .collapsible(false)
+                .build(|| show_progress(ui, long_task, &on_cancel));
+        },
+
+        Presentation::Done => ()
+    }
+
+    long_task.presentation != Presentation::Done
+}
+
+fn show_progress<F: Fn()>(ui: &imgui::Ui, long_task: &mut LongTaskDialog, on_cancel: &F) {
+    ui.text(&long_task.info);
+
+    imgui::ProgressBar::new(long_task.progress)
+        .overlay_text(&format!("{:.1}%", 100.0 * long_task.progress))
+        .build(ui);
+
+    if long_task.cancel_requested {
+        ui.text_disabled("Cancelling...");
+    } else {
+        // the task may have completed already, with only its disconnection pending
+        let token = ui.begin_disabled(!long_task.cancel_enabled());
+        if ui.button("Cancel") {
+            long_task.cancel_requested = true;
+            logging::log_info!("{}: cancel requested at {:.1}%.", long_task.title, 100.0 * long_task.progress);
+            if let Some(cancel_token) = &long_task.cancel_token { cancel_token.cancel(); }
+            on_cancel();
         }
+        token.end();
+    }
+}
+
+mod tests {
+    use super::*;
 
-        ui.text(&long_task.info);
+    #[test]
+    fn modal_is_sent_to_background_only_if_allowed() {
+        assert_eq!(Presentation::Background, Presentation::Modal.next(PresentationEvent::SendToBackground, true));
+        assert_eq!(Presentation::Modal, Presentation::Modal.next(PresentationEvent::SendToBackground, false));
+        assert_eq!(
+            Presentation::Background,
+            Presentation::Background.next(PresentationEvent::SendToBackground, true)
+        );
+    }
 
-        imgui::ProgressBar::new(long_task.progress)
-            .overlay_text(&format!("{:.1}%", 100.0 * long_task.progress))
-            .build(ui);
+    #[test]
+    fn finished_task_is_done() {
+        for presentation in [Presentation::Modal, Presentation::Background, Presentation::Done] {
+            assert_eq!(Presentation::Done, presentation.next(PresentationEvent::Finished, true));
+        }
+        assert_eq!(Presentation::Done, Presentation::Done.next(PresentationEvent::SendToBackground, true));
+    }
 
-        if ui.button("Cancel") { on_cancel(); }
-    });
+    #[test]
+    fn cancel_is_disabled_once_task_completes() {
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        let mut dialog = LongTaskDialog::new("Task".to_string(), String::new(), receiver);
+        sender.send(ProgressMsg::new("Working".to_string(), 0.5)).unwrap();
+        dialog.receive_progress();
+        assert!(dialog.cancel_enabled());
 
-    in_progress
+        sender.send(ProgressMsg::new("Still working".to_string(), 0.9)).unwrap();
+        sender.send(ProgressMsg::new("Done".to_string(), 1.0)).unwrap();
+        dialog.receive_progress();
+        assert_eq!("Done", dialog.info);
+        assert!(!dialog.cancel_enabled());
+
+        drop(sender);
+        dialog.receive_progress();
+        assert_eq!(Presentation::Done, dialog.presentation);
+        assert!(!dialog.cancel_enabled());
+    }
 }
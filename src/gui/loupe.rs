@@ -0,0 +1,184 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Magnifier of a view's texture around the mouse cursor (shown while a key is held).
+
+use crate::gui::shortcuts::Action;
+use crate::gui::GuiState;
+
+pub const MIN_ZOOM: f32 = 2.0;
+
+pub const MAX_ZOOM: f32 = 16.0;
+
+const DEFAULT_ZOOM: f32 = 4.0;
+
+/// Zoom change per mouse wheel step.
+const WHEEL_ZOOM_FACTOR: f32 = 1.25;
+
+/// Size of the magnified area (logical pixels).
+const LOUPE_SIZE: f32 = 160.0;
+
+const CROSSHAIR_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 0.8];
+
+pub struct Loupe {
+    /// Magnification of the texture's pixels.
+    zoom: f32
+}
+
+impl Default for Loupe {
+    fn default() -> Loupe { Loupe{ zoom: DEFAULT_ZOOM } }
+}
+
+impl Loupe {
+    pub fn zoom(&self) -> f32 { self.zoom }
+
+    pub fn set_zoom(&mut self, value: f32) { self.zoom = value.max(MIN_ZOOM).min(MAX_ZOOM); }
+}
+
+/// Part of a texture shown in the loupe.
+#[derive(Debug, PartialEq)]
+pub struct UvWindow {
+    pub uv0: [f32; 2],
+    pub uv1: [f32; 2],
+    /// Position of the cursor in the window (0 to 1; off-center if the window has been clamped at the texture's
+    /// borders).
+    pub cursor: [f32; 2]
+}
+
+/// Returns the part of a texture of `texture_size` (physical pixels) to be magnified `zoom` times around
+/// `cursor_uv` in a loupe of `loupe_size` (logical pixels). The window is shifted to stay within the texture;
+/// if the texture is too small, all of it is shown.
+pub fn uv_window(
+    cursor_uv: [f32; 2],
+    texture_size: [u32; 2],
+    loupe_size: f32,
+    zoom: f32,
+    hidpi_factor: f32
+) -> UvWindow {
+    let mut window = UvWindow{ uv0: [0.0; 2], uv1: [1.0; 2], cursor: [0.0; 2] };
+    for i in 0..2 {
+        let cursor = cursor_uv[i].max(0.0).min(1.0);
+        let extent = (loupe_size * hidpi_factor / zoom / texture_size[i].max(1) as f32).min(1.0);
+        let start = (cursor - extent / 2.0).max(0.0).min(1.0 - extent);
+        window.uv0[i] = start;
+        window.uv1[i] = start + extent;
+        window.cursor[i] = (cursor - start) / extent;
+    }
+
+    window
+}
+
+/// Returns `true` if the loupe is to be shown over the hovered view.
+pub fn active(ui: &imgui::Ui, gui_state: &GuiState) -> bool {
+    !ui.io().want_text_input && gui_state.shortcut_held(ui, Action::Loupe)
+}
+
+/// Shows the loupe at the mouse cursor; to be called while hovering an `imgui::Image` of `texture_id`.
+///
+/// # Parameters
+///
+/// * `cursor` - Cursor position in the displayed image (0 to 1).
+/// * `uv_bounds` - Texture coordinates the image has been displayed with.
+/// * `texture_size` - Physical size of the texture.
+/// * `info` - Shown below the magnified image.
+///
+pub fn show(
+    ui: &imgui::Ui,
+    gui_state: &mut GuiState,
+    texture_id: imgui::TextureId,
+    texture_size: [u32; 2],
+    cursor: [f32; 2],
+    uv_bounds: ([f32; 2], [f32; 2]),
+    info: &str
+) {
+    let wheel = ui.io().mouse_wheel;
+    if wheel != 0.0 {
+        let zoom = gui_state.loupe.zoom() * WHEEL_ZOOM_FACTOR.powf(wheel);
+        gui_state.loupe.set_zoom(zoom);
+    }
+
+    let hidpi_f = gui_state.hidpi_factor() as f32;
+    let window = uv_window(cursor, texture_size, LOUPE_SIZE, gui_state.loupe.zoom(), hidpi_f);
+    // the image may be displayed flipped; `uv_bounds` map displayed positions to texture coordinates linearly
+    let (uv0, uv1) = uv_bounds;
+    let to_texture = |pos: [f32; 2]| [
+        uv0[0] + pos[0] * (uv1[0] - uv0[0]),
+        uv0[1] + pos[1] * (uv1[1] - uv0[1])
+    ];
+    let size = [
+        LOUPE_SIZE.min(texture_size[0] as f32 * gui_state.loupe.zoom() / hidpi_f),
+        LOUPE_SIZE.min(texture_size[1] as f32 * gui_state.loupe.zoom() / hidpi_f)
+    ];
+
+    ui.tooltip(|| {
+        imgui::Image::new(texture_id, size).uv0(to_texture(window.uv0)).uv1(to_texture(window.uv1)).build(ui);
+
+        let min = ui.item_rect_min();
+        let x = min[0] + window.cursor[0] * size[0];
+        let y = min[1] + window.cursor[1] * size[1];
+        let draw_list = ui.get_window_draw_list();
+        draw_list.add_line([x, min[1]], [x, min[1] + size[1]], CROSSHAIR_COLOR).build();
+        draw_list.add_line([min[0], y], [min[0] + size[0], y], CROSSHAIR_COLOR).build();
+
+        ui.text(format!("×{:.1} (mouse wheel to change)", gui_state.loupe.zoom()));
+        ui.text(info);
+    });
+}
+
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: [f32; 2], actual: [f32; 2]) {
+        assert!((expected[0] - actual[0]).abs() < 1.0e-5 && (expected[1] - actual[1]).abs() < 1.0e-5,
+            "expected {:?}, got {:?}", expected, actual);
+    }
+
+    #[test]
+    fn window_is_centered_on_cursor() {
+        // 100 logical = 100 physical pixels show 25 texture pixels
+        let window = uv_window([0.5, 0.25], [1000, 500], 100.0, 4.0, 1.0);
+        assert_close([0.4875, 0.225], window.uv0);
+        assert_close([0.5125, 0.275], window.uv1);
+        assert_close([0.5, 0.5], window.cursor);
+    }
+
+    #[test]
+    fn window_is_clamped_at_borders() {
+        let window = uv_window([0.01, 0.99], [1000, 500], 100.0, 4.0, 1.0);
+        assert_close([0.0, 0.95], window.uv0);
+        assert_close([0.025, 1.0], window.uv1);
+        assert_close([0.4, 0.8], window.cursor);
+    }
+
+    #[test]
+    fn hidpi_scaling_covers_more_texture_pixels() {
+        // 100 logical = 200 physical pixels show 50 texture pixels
+        let window = uv_window([0.5, 0.5], [1000, 1000], 100.0, 4.0, 2.0);
+        assert_close([0.475, 0.475], window.uv0);
+        assert_close([0.525, 0.525], window.uv1);
+    }
+
+    #[test]
+    fn small_texture_is_shown_whole() {
+        let window = uv_window([0.75, 0.5], [10, 1000], 100.0, 4.0, 1.0);
+        assert_close([0.0, 0.45], window.uv0);
+        assert_close([1.0, 0.55], window.uv1);
+        assert_close([0.75, 0.5], window.cursor);
+    }
+}
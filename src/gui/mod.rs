@@ -17,22 +17,43 @@
 // along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
 //
 
+use crate::background;
+use crate::config::Configuration;
 use crate::data;
+use crate::fmt;
+use crate::gpu::texture_limits::TextureLimits;
+use crate::logging;
 use crate::projection;
 use crate::runner;
-use glium::glutin;
 use std::cell::RefCell;
 use std::rc::Rc;
 
 pub mod about_dialog;
+pub mod background_dialog;
+pub mod config_backup_dialog;
+pub mod crash_report_dialog;
+pub mod crossfade;
 pub mod draw_buffer;
+pub mod file_dialog;
+pub mod file_manager;
 pub mod font_dialog;
+pub mod format_dialog;
+pub mod gpu_inspector;
+pub mod log_window;
 pub mod long_task_dialog;
+pub mod loupe;
+pub mod modal;
+pub mod motion;
+pub mod setup_dialog;
+pub mod shortcuts;
+pub mod shortcuts_dialog;
 
 pub use draw_buffer::DrawBuffer;
 
 const MODE_OF_OPERATION_POPUP_TITLE: &str = "Choose mode of operation";
 
+const EXIT_CONFIRMATION_TITLE: &str = "Exit";
+
 pub struct MessageBox {
     pub title: String,
     pub message: String
@@ -45,20 +66,81 @@ pub struct GuiState {
     pub mouse_drag_origin: [f32; 2],
     pub message_box: Option<MessageBox>,
     pub font_size: f32,
-    pub provisional_font_size: Option<f32>
+    pub provisional_font_size: Option<f32>,
+    /// Whether the GPU resource inspector window is shown.
+    pub gpu_inspector_open: bool,
+    pub log_window: log_window::LogWindow,
+    pub shortcuts: shortcuts::Shortcuts,
+    pub loupe: loupe::Loupe,
+    /// Formatting of displayed numbers.
+    pub format: fmt::Preferences,
+    /// Edited in the number format dialog.
+    pub provisional_format: Option<fmt::Preferences>,
+    /// Edited in the background tasks dialog.
+    pub provisional_background: Option<background::Settings>,
+    /// Failure to change the priority of background tasks; shown until dismissed.
+    pub priority_notice: Option<String>,
+    /// Edited in the setup dialog.
+    pub provisional_setup: Option<setup_dialog::Setup>,
+    /// The setup dialog is to be shown (e.g., chosen from a menu).
+    pub setup_requested: bool,
+    /// The first run (which shows the setup dialog) has been checked for.
+    first_run_checked: bool,
+    /// A crash report of the previous session has been checked for.
+    crash_report_checked: bool,
+    /// Crash report being offered in the crash report dialog.
+    pub crash_report: Option<std::path::PathBuf>,
+    pub shortcuts_dialog: shortcuts_dialog::ShortcutsDialog,
+    pub config_backup_dialog: config_backup_dialog::ConfigBackupDialog,
+    /// Kind of the focused window; set by the window itself in each frame.
+    pub focused_window: Option<shortcuts::WindowKind>,
+    /// Action triggered via keyboard in the current frame.
+    triggered_shortcut: Option<shortcuts::Action>,
+    /// The main window does not have focus (views render less often; see `gpu::render_throttle`).
+    app_unfocused: bool,
+    /// The main window's title shows the unsaved changes marker.
+    window_title_marked: bool,
+    /// Animations are disabled (see `motion`).
+    reduce_motion: bool,
+    /// Exiting (discarding unsaved changes) is being confirmed.
+    exit_confirmation_open: bool
 }
 
 impl GuiState {
-    pub fn new(hidpi_factor: f64, font_size: f32) -> GuiState {
+    pub fn new(hidpi_factor: f64, font_size: f32, debug: bool) -> GuiState {
         GuiState{
             hidpi_factor,
             font_size,
             mode_selection_activated: false,
+            gpu_inspector_open: debug,
             ..Default::default()
         }
     }
 
     pub fn hidpi_factor(&self) -> f64 { self.hidpi_factor }
+
+    pub fn shortcut_triggered(&self, action: shortcuts::Action) -> bool { self.triggered_shortcut == Some(action) }
+
+    /// Returns `true` if the key chord of `action` is being held down.
+    pub fn shortcut_held(&self, ui: &imgui::Ui, action: shortcuts::Action) -> bool {
+        self.shortcuts.chord(action).map_or(false, |chord| chord.held(ui))
+    }
+
+    pub fn app_focused(&self) -> bool { !self.app_unfocused }
+
+    pub fn reduce_motion(&self) -> bool { self.reduce_motion }
+
+    pub fn set_reduce_motion(&mut self, value: bool) { self.reduce_motion = value; }
+
+    /// Returns the duration to use for an animation normally lasting `default`; zero if motion is to be reduced.
+    pub fn animation_duration(&self, default: std::time::Duration) -> std::time::Duration {
+        if self.reduce_motion { std::time::Duration::ZERO } else { default }
+    }
+
+    /// To be called at the start of every frame.
+    pub fn update_app_focus(&mut self, events: &runner::FrameEvents) {
+        if let Some(focused) = events.focused { self.app_unfocused = !focused; }
+    }
 }
 
 pub fn handle_gui(
@@ -68,7 +150,8 @@ pub fn handle_gui(
     gui_state: &mut GuiState,
     renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>,
     display: &glium::Display,
-    worker_context: &mut Option<glutin::Context<glutin::NotCurrent>>
+    bg_task_sender: &crossbeam::channel::Sender<projection::MainToWorkerMsg>,
+    texture_limits: &Rc<TextureLimits>
 ) -> Option<runner::FontSizeRequest> {
     unsafe { imgui::sys::igDockSpaceOverViewport(
         imgui::sys::igGetMainViewport(),
@@ -76,24 +159,163 @@ pub fn handle_gui(
         std::ptr::null()
     ); }
 
-    if program_data.is_none() && !gui_state.mode_selection_activated {
+    // focus of windows is as reported in the previous frame
+    gui_state.triggered_shortcut = shortcuts::dispatch(
+        &gui_state.shortcuts,
+        &shortcuts::ShortcutKeys::from_ui(ui),
+        gui_state.focused_window.take()
+    );
+
+    // a crash report of the previous session is offered as soon as the main window is shown
+    let show_crash_report = !gui_state.crash_report_checked;
+    gui_state.crash_report_checked = true;
+    with_config(base, program_data, |config| {
+        crash_report_dialog::handle_crash_report_dialog(ui, gui_state, config, show_crash_report)
+    });
+    // the setup and the mode selection wait until the crash report is dealt with (only one popup can be opened
+    // at the top level)
+    let crash_report_open = gui_state.crash_report.is_some();
+
+    // on the first run, the setup is offered as soon as the main window is shown
+    let first_run = !crash_report_open && !gui_state.first_run_checked
+        && with_config(base, program_data, |config| config.first_run());
+    if !crash_report_open { gui_state.first_run_checked = true; }
+    let show_setup = !crash_report_open && (std::mem::take(&mut gui_state.setup_requested) || first_run);
+
+    // the mode selection waits until the setup is done
+    if program_data.is_none() && !gui_state.mode_selection_activated && !show_setup && !crash_report_open
+        && gui_state.provisional_setup.is_none() {
         ui.open_popup(MODE_OF_OPERATION_POPUP_TITLE);
         gui_state.mode_selection_activated = true;
     }
 
-    if let Some(program_data) = program_data {
-        match program_data {
-            data::ProgramData::Projection(program_data) => projection::handle_gui(
-                program_data,
-                ui,
-                gui_state,
-                renderer,
-                display
-            )
+    gpu_inspector::handle_gpu_inspector(ui, gui_state);
+    background_dialog::handle_priority_notice(ui, gui_state);
+
+    with_config(base, program_data, |config| log_window::handle_log_window(ui, gui_state, config));
+
+    let mut font_size_request = None;
+    let mut close = false;
+
+    if let Some(mode_data) = program_data {
+        match mode_data {
+            data::ProgramData::Projection(mode_data) => {
+                font_size_request = projection::handle_gui(mode_data, ui, gui_state, renderer, display);
+                close = mode_data.close_requested() && !mode_data.task_in_progress();
+            }
         }
     } else {
-        handle_mode_selection(base, program_data, ui, display, worker_context);
-        None
+        handle_mode_selection(base, program_data, ui, display, bg_task_sender, texture_limits);
+    }
+
+    let setup_result = with_config(base, program_data, |config| {
+        setup_dialog::handle_setup_dialog(ui, gui_state, config, show_setup)
+    });
+    if let Some(folder) = setup_result.export_folder {
+        if let Some(data::ProgramData::Projection(mode_data)) = program_data {
+            mode_data.export_dialog().borrow_mut().set_output_path(folder);
+        }
+    }
+    if setup_result.font_size_request.is_some() { font_size_request = setup_result.font_size_request; }
+
+    if close {
+        match program_data.take() {
+            Some(data::ProgramData::Projection(mode_data)) => *base = Some(mode_data.into_base()),
+            None => ()
+        }
+        // the worker thread stays alive and will serve the next mode
+        gui_state.mode_selection_activated = false;
+    }
+
+    font_size_request
+}
+
+/// Handles a request to close the main window, asking for confirmation if there are unsaved changes; returns `true`
+/// if the program is to exit. To be called in every frame.
+pub fn handle_exit(
+    base: &mut Option<data::BaseProgramData>,
+    program_data: &mut Option<data::ProgramData>,
+    ui: &imgui::Ui,
+    gui_state: &mut GuiState,
+    close_requested: bool
+) -> bool {
+    if close_requested {
+        let unsaved = match program_data {
+            Some(data::ProgramData::Projection(mode_data)) => mode_data.session_state().is_dirty(),
+            None => false
+        };
+        // a repeated request exits without waiting for the confirmation
+        if !unsaved || gui_state.exit_confirmation_open { return true; }
+        ui.open_popup(EXIT_CONFIRMATION_TITLE);
+        gui_state.exit_confirmation_open = true;
+    }
+    if !gui_state.exit_confirmation_open { return false; }
+
+    let mut decision = None;
+    with_config(base, program_data, |config| {
+        modal::modal(ui, config, EXIT_CONFIRMATION_TITLE, modal::KeyBindings::all(), |key_action, _| {
+            ui.text(projection::UNSAVED_CHANGES_WARNING);
+            ui.separator();
+
+            if modal::default_button(ui, "Exit") || key_action == modal::KeyAction::Accept {
+                decision = Some(true);
+                ui.close_current_popup();
+            }
+            ui.same_line();
+            if ui.button("Cancel") || key_action == modal::KeyAction::Cancel {
+                decision = Some(false);
+                ui.close_current_popup();
+            }
+        });
+    });
+    if decision.is_some() { gui_state.exit_confirmation_open = false; }
+
+    decision == Some(true)
+}
+
+/// Returns a new title of the main window if its unsaved changes marker needs to change; to be called in every frame.
+pub fn window_title_request(
+    program_data: &Option<data::ProgramData>,
+    gui_state: &mut GuiState
+) -> Option<runner::WindowTitleRequest> {
+    let dirty = match program_data {
+        Some(data::ProgramData::Projection(mode_data)) => mode_data.session_state().is_dirty(),
+        None => false
+    };
+    if dirty == gui_state.window_title_marked { return None; }
+
+    gui_state.window_title_marked = dirty;
+    Some(runner::WindowTitleRequest(projection::marked_title(runner::WINDOW_TITLE, dirty)))
+}
+
+/// Saves configuration changes periodically and when the main window loses focus (so that a crash does not lose
+/// all of them); to be called in every frame.
+pub fn handle_config_saving(
+    base: &mut Option<data::BaseProgramData>,
+    program_data: &mut Option<data::ProgramData>,
+    focus_lost: bool
+) {
+    with_config(base, program_data, |config| flush_config(config, focus_lost));
+}
+
+/// Calls `f` with the configuration, owned by the base data or (if a mode is active) by the mode's data; returns
+/// the default value if there is neither.
+fn with_config<T: Default>(
+    base: &mut Option<data::BaseProgramData>,
+    program_data: &mut Option<data::ProgramData>,
+    f: impl FnOnce(&mut Configuration) -> T
+) -> T {
+    match (base, program_data) {
+        (Some(base), _) => f(&mut base.config),
+        (None, Some(data::ProgramData::Projection(mode_data))) => f(&mut mode_data.base().borrow_mut().config),
+        (None, None) => T::default()
+    }
+}
+
+fn flush_config(config: &mut Configuration, focus_lost: bool) {
+    let result = if focus_lost { config.flush() } else { config.flush_if_due() };
+    if let Err(e) = result {
+        logging::log_error!("Error saving configuration: {}.", e);
     }
 }
 
@@ -106,7 +328,8 @@ fn handle_mode_selection(
     program_data: &mut Option<data::ProgramData>,
     ui: &imgui::Ui,
     display: &glium::Display,
-    worker_context: &mut Option<glium::glutin::Context<glium::glutin::NotCurrent>>
+    bg_task_sender: &crossbeam::channel::Sender<projection::MainToWorkerMsg>,
+    texture_limits: &Rc<TextureLimits>
 ) {
     unsafe { imgui::sys::igSetNextWindowSize(
         imgui::sys::ImVec2{ x: 600.0, y: 300.0 }, //TODO: use 1/2 of program's window size
@@ -119,7 +342,8 @@ fn handle_mode_selection(
             *program_data = Some(data::ProgramData::Projection(projection::ProgramData::new(
                 base.take().unwrap(),
                 display,
-                worker_context.take().unwrap()
+                bg_task_sender.clone(),
+                texture_limits.clone()
             )));
 
             ui.close_current_popup();
@@ -134,7 +358,9 @@ fn handle_mode_selection(
             about_clicked = true;
         }
 
-        about_dialog::handle_about_dialog(ui, about_clicked);
+        if let Some(base) = base {
+            about_dialog::handle_about_dialog(ui, &mut base.config, about_clicked);
+        }
     });
 }
 
@@ -170,8 +396,9 @@ pub fn adjust_pos_for_exact_hidpi_scaling(
 
     ui.set_cursor_screen_pos([adjusted_pos_x, adjusted_pos_y]);
 
-    let mut size = ui.content_region_avail();
-    size[1] -= vertical_space_after;
+    // the window may be too small (e.g., docked in a narrow column) to leave any space
+    let avail = ui.content_region_avail();
+    let size = [avail[0].max(0.0), (avail[1] - vertical_space_after).max(0.0)];
 
     let mut adjusted_size_x = size[0].trunc();
     if (adjusted_size_x * hidpi_factor).fract() != 0.0 {
@@ -250,6 +477,25 @@ pub fn tooltip(ui: &imgui::Ui, text: &str) {
     }
 }
 
+/// Returns `text` shortened (its middle part replaced with "...") so that its width given by `width_of` does not
+/// exceed `max_width`.
+pub fn truncate_middle<F: Fn(&str) -> f32>(text: &str, max_width: f32, width_of: F) -> String {
+    // `max_width` may be negative (e.g., for a very narrow window)
+    if text.is_empty() || width_of(text) <= max_width { return text.to_string(); }
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut num_kept = chars.len() - 1;
+    loop {
+        // the end (e.g., a file name's number and extension) is more informative, so it keeps the extra character
+        let num_head = num_kept / 2;
+        let mut shortened: String = chars[..num_head].iter().collect();
+        shortened += "...";
+        shortened.extend(&chars[chars.len() - (num_kept - num_head)..]);
+        if num_kept == 0 || width_of(&shortened) <= max_width { return shortened; }
+        num_kept -= 1;
+    }
+}
+
 /// Returns adjusted `image_size` (preserving w/h ratio) so that image touches the container from inside.
 pub fn touch_from_inside(image_size: [u32; 2], container_size: [f32; 2]) -> [f32; 2] {
     let container_wh_ratio = container_size[0] / container_size[1];
@@ -278,14 +524,44 @@ pub fn fill_vertically(image_size: [u32; 2], container_size: [f32; 2]) -> [f32;
 }
 
 
-pub fn handle_message_box(ui: &imgui::Ui, gui_state: &GuiState) {
+pub fn handle_message_box(ui: &imgui::Ui, gui_state: &GuiState, config: &mut Configuration) {
     if let Some(message_box) = &gui_state.message_box {
-        ui.popup_modal(&message_box.title).build(ui, || {
+        modal::modal(ui, config, &message_box.title, modal::KeyBindings::all(), |key_action, _| {
             ui.text(&message_box.message);
             ui.separator();
-            if ui.button("Close") {
+            if modal::default_button(ui, "Close") || key_action != modal::KeyAction::None {
                 ui.close_current_popup();
             }
         });
     }
 }
+
+mod tests {
+    use super::*;
+
+    fn num_chars(text: &str) -> f32 { text.chars().count() as f32 }
+
+    #[test]
+    fn short_text_is_not_truncated() {
+        assert_eq!("frame_001.png", truncate_middle("frame_001.png", 13.0, num_chars));
+    }
+
+    #[test]
+    fn empty_text_is_kept_for_negative_width() {
+        assert_eq!("", truncate_middle("", -10.0, num_chars));
+        assert_eq!("...", truncate_middle("frame_001.png", -10.0, num_chars));
+    }
+
+    #[test]
+    fn long_text_is_truncated_in_the_middle() {
+        assert_eq!("fram...1.png", truncate_middle("frame_001.png", 12.0, num_chars));
+        assert_eq!("fram....png", truncate_middle("frame_001.png", 11.5, |text| num_chars(text) + 0.5));
+        // non-ASCII characters are not split
+        assert_eq!("żół...png", truncate_middle("żółw_żółł.png", 9.0, num_chars));
+    }
+
+    #[test]
+    fn too_narrow_space_leaves_ellipsis() {
+        assert_eq!("...", truncate_middle("frame_001.png", 1.0, num_chars));
+    }
+}
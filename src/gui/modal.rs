@@ -0,0 +1,153 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::config::{Configuration, DialogGeometry, GuiConfig};
+
+/// Keyboard state relevant to modal dialogs.
+#[derive(Default)]
+pub struct ModalKeys {
+    pub escape_pressed: bool,
+    pub enter_pressed: bool,
+    /// A text input widget is being edited (it consumes Enter and Escape itself).
+    pub text_input_active: bool,
+    /// The dialog is the focused window (i.e., not covered by a nested popup).
+    pub dialog_focused: bool
+}
+
+impl ModalKeys {
+    fn from_ui(ui: &imgui::Ui) -> ModalKeys {
+        ModalKeys{
+            escape_pressed: ui.is_key_pressed(imgui::Key::Escape),
+            enter_pressed: ui.is_key_pressed(imgui::Key::Enter),
+            text_input_active: ui.io().want_text_input,
+            dialog_focused: ui.is_window_focused()
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum KeyAction {
+    None,
+    /// Trigger the dialog's default action.
+    Accept,
+    /// Trigger the dialog's cancel/close action.
+    Cancel
+}
+
+#[derive(Copy, Clone)]
+pub struct KeyBindings {
+    pub escape_cancels: bool,
+    pub enter_accepts: bool
+}
+
+impl KeyBindings {
+    pub fn all() -> KeyBindings { KeyBindings{ escape_cancels: true, enter_accepts: true } }
+
+    pub fn none() -> KeyBindings { KeyBindings{ escape_cancels: false, enter_accepts: false } }
+}
+
+pub fn dispatch_keys(keys: &ModalKeys, bindings: KeyBindings) -> KeyAction {
+    if !keys.dialog_focused || keys.text_input_active {
+        KeyAction::None
+    } else if keys.escape_pressed && bindings.escape_cancels {
+        KeyAction::Cancel
+    } else if keys.enter_pressed && bindings.enter_accepts {
+        KeyAction::Accept
+    } else {
+        KeyAction::None
+    }
+}
+
+/// Shows a modal popup (opened elsewhere with `ui.open_popup(title)`), restoring its last position and size
+/// from `config` and storing them back when changed. `contents` receives the action triggered via keyboard
+/// (and `config`, for use by nested dialogs).
+pub fn modal<F: FnOnce(KeyAction, &mut Configuration)>(
+    ui: &imgui::Ui,
+    config: &mut Configuration,
+    title: &str,
+    bindings: KeyBindings,
+    contents: F
+) {
+    if let Some(geometry) = config.dialog_geometry(title) {
+        unsafe {
+            imgui::sys::igSetNextWindowPos(
+                imgui::sys::ImVec2{ x: geometry.position[0], y: geometry.position[1] },
+                imgui::sys::ImGuiCond_Appearing as i32,
+                imgui::sys::ImVec2{ x: 0.0, y: 0.0 }
+            );
+            imgui::sys::igSetNextWindowSize(
+                imgui::sys::ImVec2{ x: geometry.size[0], y: geometry.size[1] },
+                imgui::sys::ImGuiCond_Appearing as i32
+            );
+        }
+    }
+
+    ui.popup_modal(title).build(ui, || {
+        let geometry = DialogGeometry{ position: ui.window_pos(), size: ui.window_size() };
+        if config.dialog_geometry(title).as_ref() != Some(&geometry) {
+            config.set_dialog_geometry(title, &geometry);
+        }
+
+        contents(dispatch_keys(&ModalKeys::from_ui(ui), bindings), config);
+    });
+}
+
+/// Button marked as the dialog's default action (triggered also by Enter).
+pub fn default_button(ui: &imgui::Ui, label: &str) -> bool {
+    let token = ui.push_style_color(imgui::StyleColor::Button, ui.style_color(imgui::StyleColor::ButtonActive));
+    let clicked = ui.button(label);
+    token.pop();
+
+    clicked
+}
+
+mod tests {
+    use super::*;
+
+    fn keys(escape_pressed: bool, enter_pressed: bool) -> ModalKeys {
+        ModalKeys{ escape_pressed, enter_pressed, text_input_active: false, dialog_focused: true }
+    }
+
+    #[test]
+    fn escape_cancels_and_enter_accepts() {
+        assert_eq!(KeyAction::Cancel, dispatch_keys(&keys(true, false), KeyBindings::all()));
+        assert_eq!(KeyAction::Accept, dispatch_keys(&keys(false, true), KeyBindings::all()));
+        assert_eq!(KeyAction::None, dispatch_keys(&keys(false, false), KeyBindings::all()));
+    }
+
+    #[test]
+    fn unbound_keys_do_nothing() {
+        assert_eq!(KeyAction::None, dispatch_keys(&keys(true, true), KeyBindings::none()));
+        assert_eq!(
+            KeyAction::Accept,
+            dispatch_keys(&keys(true, true), KeyBindings{ escape_cancels: false, enter_accepts: true })
+        );
+    }
+
+    #[test]
+    fn keys_ignored_during_text_input_or_without_focus() {
+        let mut k = keys(true, true);
+        k.text_input_active = true;
+        assert_eq!(KeyAction::None, dispatch_keys(&k, KeyBindings::all()));
+
+        let mut k = keys(true, true);
+        k.dialog_focused = false;
+        assert_eq!(KeyAction::None, dispatch_keys(&k, KeyBindings::all()));
+    }
+}
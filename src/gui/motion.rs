@@ -0,0 +1,49 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Reduced motion preference and the registry of UI animations which respect it.
+
+use crate::gui::GuiState;
+use std::time::Duration;
+
+/// An animated UI feature; its timing is obtained via `GuiState::animation_duration`.
+pub struct AnimationSource {
+    pub name: &'static str,
+    /// Returns the duration used by the feature (with its default settings); zero if it does not animate.
+    pub duration: fn(&GuiState) -> Duration
+}
+
+/// Returns the operating system's preference for reduced motion, if it can be determined.
+pub fn os_prefers_reduced_motion() -> Option<bool> {
+    if cfg!(target_os = "linux") {
+        let output = std::process::Command::new("gsettings")
+            .args(["get", "org.gnome.desktop.interface", "enable-animations"])
+            .output()
+            .ok()?;
+        if !output.status.success() { return None; }
+
+        match String::from_utf8_lossy(&output.stdout).trim() {
+            "true" => Some(false),
+            "false" => Some(true),
+            _ => None
+        }
+    } else {
+        None
+    }
+}
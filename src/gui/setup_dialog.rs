@@ -0,0 +1,259 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Choice of default settings, offered on the first run (when there is no configuration file yet).
+
+use crate::config::{Configuration, GuiConfig, ProjectionConfig};
+use crate::gui;
+use crate::gui::font_dialog::{MAX_FONT_SIZE, MIN_FONT_SIZE};
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+use crate::logging;
+use crate::projection::Planet;
+use crate::runner;
+use std::path::{Path, PathBuf};
+use strum::IntoEnumIterator;
+
+const TITLE: &str = "Setup";
+
+/// Name of the folder created for exported images.
+const EXPORT_FOLDER_NAME: &str = "Vislumino";
+
+/// Platform folders the default export folder is derived from.
+pub struct BaseFolders {
+    pub pictures: Option<PathBuf>,
+    pub documents: Option<PathBuf>,
+    pub home: Option<PathBuf>
+}
+
+impl BaseFolders {
+    pub fn from_platform() -> BaseFolders {
+        BaseFolders{ pictures: dirs::picture_dir(), documents: dirs::document_dir(), home: dirs::home_dir() }
+    }
+}
+
+/// Returns the default export folder: `EXPORT_FOLDER_NAME` in the first available of Pictures, Documents and
+/// the home folder.
+pub fn default_export_folder(base: &BaseFolders) -> Option<PathBuf> {
+    base.pictures.as_ref()
+        .or(base.documents.as_ref())
+        .or(base.home.as_ref())
+        .map(|folder| folder.join(EXPORT_FOLDER_NAME))
+}
+
+/// Creates `folder` (with missing parents) unless it already exists; returns an error message on failure (e.g.,
+/// missing permissions).
+pub fn create_export_folder(folder: &Path) -> Result<(), String> {
+    if folder.is_dir() { return Ok(()); }
+
+    std::fs::create_dir_all(folder).map_err(|e| format!("Could not create folder {}: {}.", folder.to_string_lossy(), e))
+}
+
+/// Values edited in the setup dialog.
+pub struct Setup {
+    export_folder: String,
+    planet: Planet,
+    font_size: f32,
+    /// Reason of the last failure to finish the setup.
+    error: Option<String>
+}
+
+impl Setup {
+    fn new(config: &Configuration, font_size: f32) -> Setup {
+        Setup{
+            export_folder: config.projection_export_path()
+                .or_else(|| default_export_folder(&BaseFolders::from_platform()))
+                .map(|folder| folder.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            planet: config.default_planet().unwrap_or(Planet::Jupiter),
+            font_size,
+            error: None
+        }
+    }
+}
+
+/// Result of handling the setup dialog in the current frame.
+#[derive(Default)]
+pub struct SetupResult {
+    /// Font size to apply (a preview or the final value).
+    pub font_size_request: Option<runner::FontSizeRequest>,
+    /// Export folder stored when the setup has been finished.
+    pub export_folder: Option<PathBuf>
+}
+
+pub fn handle_setup_dialog(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
+    show: bool
+) -> SetupResult {
+    if show {
+        gui_state.provisional_setup = Some(Setup::new(config, gui_state.font_size));
+        ui.open_popup(TITLE);
+    }
+
+    let mut result = SetupResult::default();
+
+    modal::modal(ui, config, TITLE, KeyBindings::all(), |key_action, config| {
+        let setup = match &mut gui_state.provisional_setup {
+            Some(setup) => setup,
+            None => return
+        };
+
+        ui.text("Choose the defaults to start with; all of them can be changed later.");
+        ui.separator();
+
+        gui::add_text_before(ui, "export folder");
+        gui::tooltip(ui, "Default folder for exported images; it will be created if missing.");
+        let w = ui.push_item_width(ui.calc_text_size("M")[0] * 30.0);
+        ui.input_text("##setup-export-folder", &mut setup.export_folder).build();
+        w.end();
+        ui.same_line();
+        let browse_clicked = ui.button("Browse...");
+
+        let planets: Vec<Planet> = Planet::iter().collect();
+        let names: Vec<&str> = planets.iter().map(|planet| planet.name()).collect();
+        let mut index = planets.iter().position(|planet| *planet == setup.planet).unwrap();
+        gui::add_text_before(ui, "default planet");
+        gui::tooltip(ui, "Planet selected in the source view after loading images.");
+        if ui.combo_simple_string("##setup-planet", &mut index, &names) { setup.planet = planets[index]; }
+
+        gui::add_text_before(ui, "font size");
+        gui::tooltip(ui, "Press Enter to preview the font size.");
+        if ui.input_float("##setup-font-size", &mut setup.font_size)
+            .step(0.5)
+            .display_format("%0.1f")
+            .enter_returns_true(true)
+            .build() {
+            setup.font_size = setup.font_size.max(MIN_FONT_SIZE).min(MAX_FONT_SIZE);
+            result.font_size_request = Some(runner::FontSizeRequest(setup.font_size));
+        }
+
+        if let Some(error) = &setup.error {
+            ui.text_colored([1.0, 0.3, 0.3, 1.0], error);
+        }
+
+        ui.separator();
+
+        let finish = modal::default_button(ui, "Finish") || key_action == KeyAction::Accept;
+        ui.same_line();
+        let skip = ui.button("Skip") || key_action == KeyAction::Cancel;
+        gui::tooltip(ui, "Keep the current settings (the setup can be run again via the Settings menu).");
+
+        if finish {
+            let folder = PathBuf::from(setup.export_folder.trim());
+            let created = if folder.as_os_str().is_empty() { Ok(()) } else { create_export_folder(&folder) };
+            match created {
+                Ok(()) => {
+                    if !folder.as_os_str().is_empty() {
+                        config.set_projection_export_path(&folder.to_string_lossy());
+                        result.export_folder = Some(folder);
+                    }
+                    config.set_default_planet(setup.planet);
+                    config.set_font_size(setup.font_size);
+                    gui_state.font_size = setup.font_size;
+                    result.font_size_request = Some(runner::FontSizeRequest(setup.font_size));
+                    gui_state.provisional_setup = None;
+                    ui.close_current_popup();
+                },
+
+                Err(message) => {
+                    // the dialog stays open, so that another folder can be chosen
+                    logging::log_warning!("{}", message);
+                    setup.error = Some(message);
+                }
+            }
+        } else if skip {
+            // storing the current font size creates the configuration file, so the setup is not offered again
+            config.set_font_size(gui_state.font_size);
+            result.font_size_request = Some(runner::FontSizeRequest(gui_state.font_size));
+            gui_state.provisional_setup = None;
+            ui.close_current_popup();
+        }
+
+        if browse_clicked { choose_export_folder(ui, gui_state); }
+
+        gui::handle_message_box(ui, gui_state, config);
+    });
+
+    result
+}
+
+fn choose_export_folder(ui: &imgui::Ui, gui_state: &mut gui::GuiState) {
+    let current = gui_state.provisional_setup.as_ref().map(|setup| PathBuf::from(&setup.export_folder));
+    let location = gui::file_dialog::initial_location(current.as_deref());
+    let folder = gui::file_dialog::checked(
+        ui,
+        gui_state,
+        native_dialog::FileDialog::new().set_location(&location).show_open_single_dir()
+    ).flatten();
+
+    if let (Some(folder), Some(setup)) = (folder, &mut gui_state.provisional_setup) {
+        setup.export_folder = folder.to_string_lossy().to_string();
+        setup.error = None;
+    }
+}
+
+mod tests {
+    use super::*;
+
+    fn base_folders(pictures: Option<&str>, documents: Option<&str>, home: Option<&str>) -> BaseFolders {
+        BaseFolders{
+            pictures: pictures.map(PathBuf::from),
+            documents: documents.map(PathBuf::from),
+            home: home.map(PathBuf::from)
+        }
+    }
+
+    #[test]
+    fn export_folder_is_placed_in_first_available_base_folder() {
+        let expected = |base: &str| Some(Path::new(base).join(EXPORT_FOLDER_NAME));
+
+        let base = base_folders(Some("/home/user/Pictures"), Some("/home/user/Documents"), Some("/home/user"));
+        assert_eq!(expected("/home/user/Pictures"), default_export_folder(&base));
+
+        let base = base_folders(None, Some("/home/user/Documents"), Some("/home/user"));
+        assert_eq!(expected("/home/user/Documents"), default_export_folder(&base));
+
+        let base = base_folders(None, None, Some("/home/user"));
+        assert_eq!(expected("/home/user"), default_export_folder(&base));
+
+        assert_eq!(None, default_export_folder(&base_folders(None, None, None)));
+    }
+
+    #[test]
+    fn export_folder_is_created_with_parents() {
+        let base = std::env::temp_dir().join(format!("vislumino-test-setup-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let folder = default_export_folder(
+            &BaseFolders{ pictures: None, documents: Some(base.join("Documents")), home: None }
+        ).unwrap();
+
+        assert!(create_export_folder(&folder).is_ok());
+        assert!(folder.is_dir());
+        // an existing folder is fine
+        assert!(create_export_folder(&folder).is_ok());
+
+        // a file in place of the folder (like a read-only location) reports an error instead of panicking
+        let blocked = base.join("file");
+        std::fs::write(&blocked, "").unwrap();
+        assert!(create_export_folder(&blocked.join(EXPORT_FOLDER_NAME)).is_err());
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,365 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Registry of keyboard shortcuts (actions with rebindable key chords) and their dispatch.
+
+use crate::config::{Configuration, GuiConfig};
+use glium::glutin::event::VirtualKeyCode;
+
+/// Key names (as shown and stored in configuration) of keys which can be used in shortcuts.
+const KEYS: &[(VirtualKeyCode, &str)] = &[
+    (VirtualKeyCode::A, "A"), (VirtualKeyCode::B, "B"), (VirtualKeyCode::C, "C"), (VirtualKeyCode::D, "D"),
+    (VirtualKeyCode::E, "E"), (VirtualKeyCode::F, "F"), (VirtualKeyCode::G, "G"), (VirtualKeyCode::H, "H"),
+    (VirtualKeyCode::I, "I"), (VirtualKeyCode::J, "J"), (VirtualKeyCode::K, "K"), (VirtualKeyCode::L, "L"),
+    (VirtualKeyCode::M, "M"), (VirtualKeyCode::N, "N"), (VirtualKeyCode::O, "O"), (VirtualKeyCode::P, "P"),
+    (VirtualKeyCode::Q, "Q"), (VirtualKeyCode::R, "R"), (VirtualKeyCode::S, "S"), (VirtualKeyCode::T, "T"),
+    (VirtualKeyCode::U, "U"), (VirtualKeyCode::V, "V"), (VirtualKeyCode::W, "W"), (VirtualKeyCode::X, "X"),
+    (VirtualKeyCode::Y, "Y"), (VirtualKeyCode::Z, "Z"),
+    (VirtualKeyCode::Key0, "0"), (VirtualKeyCode::Key1, "1"), (VirtualKeyCode::Key2, "2"),
+    (VirtualKeyCode::Key3, "3"), (VirtualKeyCode::Key4, "4"), (VirtualKeyCode::Key5, "5"),
+    (VirtualKeyCode::Key6, "6"), (VirtualKeyCode::Key7, "7"), (VirtualKeyCode::Key8, "8"),
+    (VirtualKeyCode::Key9, "9"),
+    (VirtualKeyCode::F1, "F1"), (VirtualKeyCode::F2, "F2"), (VirtualKeyCode::F3, "F3"), (VirtualKeyCode::F4, "F4"),
+    (VirtualKeyCode::F5, "F5"), (VirtualKeyCode::F6, "F6"), (VirtualKeyCode::F7, "F7"), (VirtualKeyCode::F8, "F8"),
+    (VirtualKeyCode::F9, "F9"), (VirtualKeyCode::F10, "F10"), (VirtualKeyCode::F11, "F11"),
+    (VirtualKeyCode::F12, "F12"),
+    (VirtualKeyCode::Left, "Left"), (VirtualKeyCode::Right, "Right"), (VirtualKeyCode::Up, "Up"),
+    (VirtualKeyCode::Down, "Down"), (VirtualKeyCode::Home, "Home"), (VirtualKeyCode::End, "End"),
+    (VirtualKeyCode::PageUp, "PageUp"), (VirtualKeyCode::PageDown, "PageDown"),
+    (VirtualKeyCode::Insert, "Insert"), (VirtualKeyCode::Delete, "Delete"), (VirtualKeyCode::Space, "Space"),
+    (VirtualKeyCode::Period, "."), (VirtualKeyCode::Comma, ","), (VirtualKeyCode::Minus, "-"),
+    (VirtualKeyCode::Equals, "="), (VirtualKeyCode::Slash, "/"), (VirtualKeyCode::Backslash, "\\"),
+    (VirtualKeyCode::Semicolon, ";"), (VirtualKeyCode::Apostrophe, "'"), (VirtualKeyCode::LBracket, "["),
+    (VirtualKeyCode::RBracket, "]"), (VirtualKeyCode::Grave, "`")
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Chord {
+    pub key: VirtualKeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool
+}
+
+impl Chord {
+    /// Parses a chord in the format produced by `to_string`, e.g., "Ctrl+Shift+Right" (case-insensitive).
+    pub fn parse(s: &str) -> Option<Chord> {
+        let s = s.trim();
+        // the key itself may be one of the punctuation characters, so split off the modifiers first
+        let (modifiers, key_name) = match s.rfind('+') {
+            Some(pos) if pos + 1 < s.len() => (&s[..pos], &s[pos + 1..]),
+            _ => ("", s)
+        };
+
+        let key = KEYS.iter().find(|(_, name)| name.eq_ignore_ascii_case(key_name.trim()))?.0;
+        let mut chord = Chord{ key, ctrl: false, shift: false, alt: false };
+        if !modifiers.is_empty() {
+            for modifier in modifiers.split('+') {
+                let flag = match modifier.trim().to_ascii_lowercase().as_str() {
+                    "ctrl" => &mut chord.ctrl,
+                    "shift" => &mut chord.shift,
+                    "alt" => &mut chord.alt,
+                    _ => return None
+                };
+                if *flag { return None; }
+                *flag = true;
+            }
+        }
+
+        Some(chord)
+    }
+
+    /// Returns the chord whose key has been pressed in the current frame (or is being auto-repeated), if any.
+    pub fn pressed(ui: &imgui::Ui) -> Option<Chord> {
+        let io = ui.io();
+        KEYS.iter()
+            .find(|(key, _)| ui.is_key_index_pressed(*key as i32))
+            .map(|(key, _)| Chord{ key: *key, ctrl: io.key_ctrl, shift: io.key_shift, alt: io.key_alt })
+    }
+
+    /// Returns `true` if the chord's key is being held down (with exactly the chord's modifiers).
+    pub fn held(&self, ui: &imgui::Ui) -> bool {
+        let io = ui.io();
+        ui.is_key_index_down(self.key as i32)
+            && io.key_ctrl == self.ctrl && io.key_shift == self.shift && io.key_alt == self.alt
+    }
+}
+
+impl std::fmt::Display for Chord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ctrl { write!(f, "Ctrl+")?; }
+        if self.shift { write!(f, "Shift+")?; }
+        if self.alt { write!(f, "Alt+")?; }
+        let name = KEYS.iter().find(|(key, _)| *key == self.key).map(|(_, name)| *name).unwrap_or("?");
+        write!(f, "{}", name)
+    }
+}
+
+/// Kinds of windows which can have their own shortcuts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WindowKind {
+    SourceView
+}
+
+/// Where a shortcut is active.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Context {
+    Global,
+    /// Only when a window of the given kind is focused.
+    Window(WindowKind)
+}
+
+impl Context {
+    /// Returns true if a chord can trigger actions of both contexts at the same time.
+    fn overlaps(&self, other: &Context) -> bool {
+        *self == Context::Global || *other == Context::Global || self == other
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Context::Global => "global",
+            Context::Window(WindowKind::SourceView) => "source view"
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    LoadImages,
+    NewProjectionView,
+    NewGlobeView,
+    TogglePlayback,
+    NextFrame,
+    PreviousFrame,
+    FirstFrame,
+    LastFrame,
+    /// Shows the magnifier over the hovered view while held.
+    Loupe
+}
+
+pub struct Shortcut {
+    pub action: Action,
+    /// Configuration key.
+    pub id: &'static str,
+    pub name: &'static str,
+    pub context: Context,
+    pub default_chord: Option<Chord>,
+    pub chord: Option<Chord>
+}
+
+/// All actions which can be triggered via keyboard, with their current key chords.
+pub struct Shortcuts {
+    shortcuts: Vec<Shortcut>
+}
+
+impl Default for Shortcuts {
+    fn default() -> Shortcuts {
+        let mut shortcuts = Shortcuts{ shortcuts: vec![] };
+
+        shortcuts.register(Action::LoadImages, "LoadImages", "Load images", Context::Global, "Ctrl+O");
+        shortcuts.register(
+            Action::NewProjectionView, "NewProjectionView", "New projection view", Context::Global, ""
+        );
+        shortcuts.register(Action::NewGlobeView, "NewGlobeView", "New globe view", Context::Global, "");
+        shortcuts.register(Action::Loupe, "Loupe", "Magnifier (hold)", Context::Global, "Z");
+
+        let source_view = Context::Window(WindowKind::SourceView);
+        shortcuts.register(Action::TogglePlayback, "TogglePlayback", "Play/pause", source_view, "Space");
+        shortcuts.register(Action::NextFrame, "NextFrame", "Next frame", source_view, "Right");
+        shortcuts.register(Action::PreviousFrame, "PreviousFrame", "Previous frame", source_view, "Left");
+        shortcuts.register(Action::FirstFrame, "FirstFrame", "First frame", source_view, "Home");
+        shortcuts.register(Action::LastFrame, "LastFrame", "Last frame", source_view, "End");
+
+        shortcuts
+    }
+}
+
+impl Shortcuts {
+    /// Returns the default shortcuts overridden by those stored in `config`.
+    pub fn from_config(config: &Configuration) -> Shortcuts {
+        let mut shortcuts = Shortcuts::default();
+        for shortcut in &mut shortcuts.shortcuts {
+            // an empty value means the action is unbound
+            if let Some(value) = config.keyboard_shortcut(shortcut.id) {
+                if value.trim().is_empty() {
+                    shortcut.chord = None;
+                } else if let Some(chord) = Chord::parse(&value) {
+                    shortcut.chord = Some(chord);
+                }
+            }
+        }
+
+        shortcuts
+    }
+
+    /// Adds an action; `default_chord` is in the format accepted by `Chord::parse` (empty for none).
+    fn register(
+        &mut self,
+        action: Action,
+        id: &'static str,
+        name: &'static str,
+        context: Context,
+        default_chord: &str
+    ) {
+        assert!(self.shortcuts.iter().all(|s| s.action != action && s.id != id));
+        let default_chord = if default_chord.is_empty() { None } else { Some(Chord::parse(default_chord).unwrap()) };
+        self.shortcuts.push(Shortcut{ action, id, name, context, default_chord, chord: default_chord });
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Shortcut> { self.shortcuts.iter() }
+
+    fn get(&self, action: Action) -> &Shortcut { self.shortcuts.iter().find(|s| s.action == action).unwrap() }
+
+    pub fn chord(&self, action: Action) -> Option<Chord> { self.get(action).chord }
+
+    /// Assigns `chord` to `action` and stores it in `config`. Conflicts are not checked (see `conflict`).
+    pub fn set_chord(&mut self, action: Action, chord: Option<Chord>, config: &mut Configuration) {
+        let shortcut = self.shortcuts.iter_mut().find(|s| s.action == action).unwrap();
+        shortcut.chord = chord;
+        config.set_keyboard_shortcut(shortcut.id, &chord.map(|c| c.to_string()).unwrap_or_default());
+    }
+
+    pub fn reset_to_defaults(&mut self, config: &mut Configuration) {
+        let defaults: Vec<_> = self.shortcuts.iter().map(|s| (s.action, s.default_chord)).collect();
+        for (action, chord) in defaults { self.set_chord(action, chord, config); }
+    }
+
+    /// Returns another action which would be triggered by `chord` together with `action`.
+    pub fn conflict(&self, action: Action, chord: Chord) -> Option<Action> {
+        let context = self.get(action).context;
+        self.shortcuts.iter()
+            .find(|s| s.action != action && s.chord == Some(chord) && s.context.overlaps(&context))
+            .map(|s| s.action)
+    }
+
+    pub fn name(&self, action: Action) -> &'static str { self.get(action).name }
+
+    /// Returns the action triggered by `chord` when a window of kind `focused` (if any) has focus; actions
+    /// specific to the focused window take precedence over global ones.
+    pub fn action_for(&self, chord: Chord, focused: Option<WindowKind>) -> Option<Action> {
+        let matching = |s: &&Shortcut| s.chord == Some(chord);
+        self.shortcuts.iter()
+            .filter(matching)
+            .find(|s| Some(s.context) == focused.map(Context::Window))
+            .or_else(|| self.shortcuts.iter().filter(matching).find(|s| s.context == Context::Global))
+            .map(|s| s.action)
+    }
+}
+
+/// Keyboard state relevant to shortcuts.
+#[derive(Default)]
+pub struct ShortcutKeys {
+    pub chord: Option<Chord>,
+    /// A text input widget is being edited (it consumes key presses itself).
+    pub text_input_active: bool,
+    /// A popup (modal dialog or menu) is open.
+    pub popup_open: bool
+}
+
+impl ShortcutKeys {
+    pub fn from_ui(ui: &imgui::Ui) -> ShortcutKeys {
+        ShortcutKeys{
+            chord: Chord::pressed(ui),
+            text_input_active: ui.io().want_text_input,
+            popup_open: unsafe { imgui::sys::igIsPopupOpen_Str(
+                std::ptr::null(),
+                (imgui::sys::ImGuiPopupFlags_AnyPopupId | imgui::sys::ImGuiPopupFlags_AnyPopupLevel) as i32
+            ) }
+        }
+    }
+}
+
+pub fn dispatch(shortcuts: &Shortcuts, keys: &ShortcutKeys, focused: Option<WindowKind>) -> Option<Action> {
+    if keys.text_input_active || keys.popup_open {
+        None
+    } else {
+        shortcuts.action_for(keys.chord?, focused)
+    }
+}
+
+mod tests {
+    use super::*;
+
+    fn chord(key: VirtualKeyCode, ctrl: bool, shift: bool, alt: bool) -> Chord { Chord{ key, ctrl, shift, alt } }
+
+    #[test]
+    fn parses_chords() {
+        assert_eq!(Some(chord(VirtualKeyCode::Right, true, true, false)), Chord::parse("Ctrl+Shift+Right"));
+        assert_eq!(Some(chord(VirtualKeyCode::O, true, false, false)), Chord::parse(" ctrl + o "));
+        assert_eq!(Some(chord(VirtualKeyCode::Period, false, false, false)), Chord::parse("."));
+        assert_eq!(Some(chord(VirtualKeyCode::Minus, false, false, true)), Chord::parse("Alt+-"));
+        assert_eq!(None, Chord::parse(""));
+        assert_eq!(None, Chord::parse("Ctrl+"));
+        assert_eq!(None, Chord::parse("Ctrl+Ctrl+A"));
+        assert_eq!(None, Chord::parse("Meta+A"));
+        assert_eq!(None, Chord::parse("Ctrl+Enter"));
+    }
+
+    #[test]
+    fn formatted_chords_parse_back() {
+        for (key, _) in KEYS {
+            for &(ctrl, shift, alt) in &[(false, false, false), (true, false, true), (true, true, true)] {
+                let c = chord(*key, ctrl, shift, alt);
+                assert_eq!(Some(c), Chord::parse(&c.to_string()));
+            }
+        }
+        assert_eq!("Ctrl+Alt+F5", chord(VirtualKeyCode::F5, true, false, true).to_string());
+    }
+
+    #[test]
+    fn default_shortcuts_do_not_conflict() {
+        let shortcuts = Shortcuts::default();
+        for s in shortcuts.iter() {
+            if let Some(c) = s.chord { assert_eq!(None, shortcuts.conflict(s.action, c)); }
+        }
+    }
+
+    #[test]
+    fn detects_conflicts_in_overlapping_contexts() {
+        let shortcuts = Shortcuts::default();
+        let ctrl_o = chord(VirtualKeyCode::O, true, false, false);
+        let right = chord(VirtualKeyCode::Right, false, false, false);
+
+        // global vs. window-specific
+        assert_eq!(Some(Action::LoadImages), shortcuts.conflict(Action::NextFrame, ctrl_o));
+        assert_eq!(Some(Action::NextFrame), shortcuts.conflict(Action::NewGlobeView, right));
+        // same window
+        assert_eq!(Some(Action::NextFrame), shortcuts.conflict(Action::PreviousFrame, right));
+        // an action does not conflict with itself
+        assert_eq!(None, shortcuts.conflict(Action::NextFrame, right));
+    }
+
+    #[test]
+    fn dispatch_honors_focus_and_text_input() {
+        let mut shortcuts = Shortcuts::default();
+        let period = chord(VirtualKeyCode::Period, false, false, false);
+        shortcuts.shortcuts.iter_mut().find(|s| s.action == Action::NextFrame).unwrap().chord = Some(period);
+
+        let keys = ShortcutKeys{ chord: Some(period), ..Default::default() };
+        assert_eq!(Some(Action::NextFrame), dispatch(&shortcuts, &keys, Some(WindowKind::SourceView)));
+        assert_eq!(None, dispatch(&shortcuts, &keys, None));
+
+        let keys = ShortcutKeys{ chord: Some(period), text_input_active: true, ..Default::default() };
+        assert_eq!(None, dispatch(&shortcuts, &keys, Some(WindowKind::SourceView)));
+
+        let keys = ShortcutKeys{ chord: Some(chord(VirtualKeyCode::O, true, false, false)), ..Default::default() };
+        assert_eq!(Some(Action::LoadImages), dispatch(&shortcuts, &keys, Some(WindowKind::SourceView)));
+        assert_eq!(Some(Action::LoadImages), dispatch(&shortcuts, &keys, None));
+    }
+}
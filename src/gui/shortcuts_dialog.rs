@@ -0,0 +1,119 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::config::Configuration;
+use crate::gui;
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+use crate::gui::shortcuts::{Action, Chord};
+
+const TITLE: &str = "Keyboard shortcuts";
+
+#[derive(Default)]
+pub struct ShortcutsDialog {
+    /// Action whose new chord is awaited.
+    capturing: Option<Action>,
+    /// Captured chord which is already used by another action; needs confirmation.
+    conflict: Option<(Action, Chord, Action)>
+}
+
+pub fn handle_shortcuts_dialog(ui: &imgui::Ui, gui_state: &mut gui::GuiState, config: &mut Configuration, show: bool) {
+    if show {
+        gui_state.shortcuts_dialog = Default::default();
+        ui.open_popup(TITLE);
+    }
+
+    // Enter and Escape can be a part of the captured chord
+    let capturing = gui_state.shortcuts_dialog.capturing.is_some();
+    let bindings = if capturing { KeyBindings::none() } else { KeyBindings::all() };
+
+    modal::modal(ui, config, TITLE, bindings, |key_action, config| {
+        let dialog = &mut gui_state.shortcuts_dialog;
+        let shortcuts = &mut gui_state.shortcuts;
+
+        if let Some(action) = dialog.capturing {
+            if ui.is_key_pressed(imgui::Key::Escape) {
+                dialog.capturing = None;
+            } else if ui.is_key_pressed(imgui::Key::Backspace) {
+                shortcuts.set_chord(action, None, config);
+                dialog.capturing = None;
+            } else if let Some(chord) = Chord::pressed(ui) {
+                match shortcuts.conflict(action, chord) {
+                    None => shortcuts.set_chord(action, Some(chord), config),
+                    Some(other) => dialog.conflict = Some((action, chord, other))
+                }
+                dialog.capturing = None;
+            }
+        }
+
+        ui.text("Click a shortcut and press the new key combination.");
+        ui.text_disabled("(Esc: cancel, Backspace: remove the shortcut)");
+        ui.separator();
+
+        ui.columns(3, "##shortcuts", false);
+        for header in ["Action", "Shortcut", "Active in"] {
+            ui.text(header);
+            ui.next_column();
+        }
+        ui.separator();
+        let actions: Vec<_> = shortcuts.iter().map(|s| (s.action, s.name, s.context, s.chord)).collect();
+        for (action, name, context, chord) in actions {
+            ui.text(name);
+            ui.next_column();
+            let label = if dialog.capturing == Some(action) {
+                "press keys...".to_string()
+            } else {
+                chord.map(|c| c.to_string()).unwrap_or_else(|| "(none)".to_string())
+            };
+            if ui.button(format!("{}##{:?}", label, action)) && dialog.conflict.is_none() {
+                dialog.capturing = Some(action);
+            }
+            ui.next_column();
+            ui.text(context.description());
+            ui.next_column();
+        }
+        ui.columns(1, "##shortcuts", false);
+
+        if let Some((action, chord, other)) = dialog.conflict {
+            ui.separator();
+            ui.text_colored(
+                [1.0, 0.3, 0.3, 1.0],
+                format!("{} is already used by \"{}\".", chord, shortcuts.name(other))
+            );
+            if ui.button("Reassign") {
+                shortcuts.set_chord(other, None, config);
+                shortcuts.set_chord(action, Some(chord), config);
+                dialog.conflict = None;
+            }
+            ui.same_line();
+            if ui.button("Keep current") { dialog.conflict = None; }
+        }
+
+        ui.separator();
+
+        if ui.button("Reset to defaults") {
+            shortcuts.reset_to_defaults(config);
+            *dialog = Default::default();
+        }
+        ui.same_line();
+        if modal::default_button(ui, "Close") || key_action == KeyAction::Accept || key_action == KeyAction::Cancel {
+            ui.close_current_popup();
+            *dialog = Default::default();
+        }
+    });
+}
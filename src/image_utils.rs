@@ -21,11 +21,98 @@ use ga_image;
 use glium::GlObject;
 use image;
 use image::GenericImageView;
+use crate::cancellation::{CancellableReader, CancelToken};
+use crate::fits;
 use std::error::Error;
+use std::io::{BufRead, Seek};
 use std::path::Path;
 
+/// Bits per channel with which source images are kept in textures (and exported frames are saved).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BitDepth {
+    Eight,
+    /// Used for images with more than 8 bits per channel (including floating-point ones).
+    Sixteen
+}
+
+impl Default for BitDepth {
+    fn default() -> BitDepth { BitDepth::Eight }
+}
+
+impl BitDepth {
+    pub fn of(pixel_format: ga_image::PixelFormat) -> BitDepth {
+        use ga_image::PixelFormat;
+
+        match pixel_format {
+            PixelFormat::Mono8
+            | PixelFormat::RGB8
+            | PixelFormat::RGBA8
+            | PixelFormat::CfaRGGB8
+            | PixelFormat::CfaGRBG8
+            | PixelFormat::CfaGBRG8
+            | PixelFormat::CfaBGGR8 => BitDepth::Eight,
+
+            _ => BitDepth::Sixteen
+        }
+    }
+
+    pub fn of_color_type(color_type: image::ColorType) -> BitDepth {
+        if color_type.bytes_per_pixel() > color_type.channel_count() { BitDepth::Sixteen } else { BitDepth::Eight }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BitDepth::Eight => "8-bit",
+            BitDepth::Sixteen => "16-bit"
+        }
+    }
+
+    /// Returns the format of RGB textures with this depth.
+    pub fn texture_format(&self) -> glium::texture::UncompressedFloatFormat {
+        match self {
+            BitDepth::Eight => glium::texture::UncompressedFloatFormat::U8U8U8,
+            BitDepth::Sixteen => glium::texture::UncompressedFloatFormat::U16U16U16
+        }
+    }
+
+    /// Returns the size of an RGB pixel.
+    pub fn bytes_per_pixel(&self) -> u64 {
+        match self {
+            BitDepth::Eight => 3,
+            BitDepth::Sixteen => 6
+        }
+    }
+}
+
+/// Returns the bit depth of the image in `path`; only the header is read for PNG, TIFF, BMP and FITS files.
+pub fn get_bit_depth(path: &Path) -> Result<BitDepth, Box<dyn Error>> {
+    use image::ImageDecoder;
+    use image::codecs::{bmp::BmpDecoder, png::PngDecoder, tiff::TiffDecoder};
+
+    if fits::is_fits_path(path) {
+        let header = fits::read_header(&mut std::io::BufReader::new(std::fs::File::open(path)?))?;
+        return Ok(BitDepth::of(header.pixel_format()));
+    }
+
+    let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    let color_type = match image::ImageFormat::from_path(path).ok() {
+        Some(image::ImageFormat::Png) => PngDecoder::new(reader)?.color_type(),
+        Some(image::ImageFormat::Tiff) => TiffDecoder::new(reader)?.color_type(),
+        Some(image::ImageFormat::Bmp) => BmpDecoder::new(reader)?.color_type(),
+        _ => return Ok(BitDepth::of(get_metadata(path)?.2))
+    };
+
+    Ok(BitDepth::of_color_type(color_type))
+}
+
 /// Returns (width, height, pixel format).
 pub fn get_metadata<P: AsRef<Path>>(path: P) -> Result<(u32, u32, ga_image::PixelFormat), Box<dyn Error>> {
+    if fits::is_fits_path(path.as_ref()) {
+        // only the header is read
+        let header = fits::read_header(&mut std::io::BufReader::new(std::fs::File::open(path)?))?;
+        return Ok((header.width, header.height, header.pixel_format()));
+    }
+
     let image = image::open(path)?;
     get_metadata_from_image(&image)
 }
@@ -51,19 +138,368 @@ fn get_metadata_from_image(image: &image::DynamicImage) -> Result<(u32, u32, ga_
 }
 
 pub fn load_image(path: &std::path::Path) -> Result<ga_image::Image, Box<dyn Error>> {
-    let src_image = image::open(path)?;
+    load_image_cancellable(path, &CancelToken::new())
+}
+
+/// Like `load_image`, but stops reading the file soon after `cancel` gets cancelled.
+pub fn load_image_cancellable(path: &std::path::Path, cancel: &CancelToken) -> Result<ga_image::Image, Box<dyn Error>> {
+    let file = std::fs::File::open(path)?;
+    let reader = std::io::BufReader::new(CancellableReader::new(file, cancel.clone()));
+    let src_image = if fits::is_fits_path(path) {
+        fits::decode(reader)?
+    } else {
+        decode_image(reader, image::ImageFormat::from_path(path).ok())?
+    };
+
+    rgb8_image(&src_image)
+}
+
+/// Converts `image` to RGB8 (alpha is discarded, 16-bit values are rounded).
+pub fn rgb8_image(image: &image::DynamicImage) -> Result<ga_image::Image, Box<dyn Error>> {
+    use image::DynamicImage;
+
+    // rejects unsupported pixel formats
+    get_metadata_from_image(image)?;
+
+    match image {
+        DynamicImage::ImageLuma8(buf) => rgb8_from_samples(&buf.as_flat_samples(), |v| v),
+        DynamicImage::ImageRgb8(buf) => rgb8_from_samples(&buf.as_flat_samples(), |v| v),
+        DynamicImage::ImageRgba8(buf) => rgb8_from_samples(&buf.as_flat_samples(), |v| v),
+        DynamicImage::ImageLuma16(buf) => rgb8_from_samples(&buf.as_flat_samples(), u16_to_u8),
+        DynamicImage::ImageRgb16(buf) => rgb8_from_samples(&buf.as_flat_samples(), u16_to_u8),
+        DynamicImage::ImageRgba16(buf) => rgb8_from_samples(&buf.as_flat_samples(), u16_to_u8),
+        other => rgb8_from_samples(&other.to_rgb8().as_flat_samples(), |v| v)
+    }
+}
+
+/// Rounds to the nearest 8-bit value (same as the conversions of the `image` crate).
+fn u16_to_u8(value: u16) -> u8 {
+    ((value as u32 + 128) / 257) as u8
+}
+
+/// Converts 1- to 4-channel samples to a tightly packed RGB8 image; rows of `samples` may be padded (e.g., aligned
+/// to 4 bytes, as in BMP files). A single channel is treated as gray, channels beyond the third are discarded.
+fn rgb8_from_samples<T: Copy>(
+    samples: &image::flat::FlatSamples<&[T]>,
+    to_u8: impl Fn(T) -> u8
+) -> Result<ga_image::Image, Box<dyn Error>> {
+    let layout = &samples.layout;
+    let num_channels = layout.channels as usize;
+    if !(1..=4).contains(&num_channels) {
+        return Err(format!("unsupported number of channels: {}", num_channels).into());
+    }
+    match layout.min_length() {
+        Some(length) if length <= samples.samples.len() => (),
+        _ => return Err("image data shorter than its layout requires".into())
+    }
+
+    let mut pixels = Vec::with_capacity(layout.width as usize * layout.height as usize * 3);
+    pack_rgb(samples, to_u8, &mut pixels);
+
+    Ok(ga_image::Image::new_from_pixels(
+        layout.width, layout.height, None, ga_image::PixelFormat::RGB8, None, pixels
+    ))
+}
+
+/// Replaces the contents of `pixels` with `samples` converted to tightly packed RGB (see `rgb8_from_samples`;
+/// the layout must have been validated). Does not allocate if `pixels` has enough capacity.
+fn pack_rgb<T: Copy, U: Copy>(samples: &image::flat::FlatSamples<&[T]>, convert: impl Fn(T) -> U, pixels: &mut Vec<U>) {
+    let layout = &samples.layout;
+    let num_channels = layout.channels as usize;
+    let (width, height) = (layout.width as usize, layout.height as usize);
+    pixels.clear();
+    for y in 0..height {
+        let row = &samples.samples[y * layout.height_stride..];
+        for x in 0..width {
+            let sample = |channel: usize| convert(row[x * layout.width_stride + channel * layout.channel_stride]);
+            if num_channels < 3 {
+                let value = sample(0);
+                pixels.extend_from_slice(&[value, value, value]);
+            } else {
+                pixels.extend_from_slice(&[sample(0), sample(1), sample(2)]);
+            }
+        }
+    }
+}
 
-    let (width, height, _) = get_metadata_from_image(&src_image)?;
+/// Decodes an image from `reader`; if `format` is not specified, it is guessed from the contents.
+pub fn decode_image<R: BufRead + Seek>(
+    reader: R,
+    format: Option<image::ImageFormat>
+) -> Result<image::DynamicImage, Box<dyn Error>> {
+    let mut reader = image::io::Reader::new(reader);
+    match format {
+        Some(format) => reader.set_format(format),
+        None => reader = reader.with_guessed_format()?
+    }
+
+    Ok(reader.decode()?)
+}
+
+/// Buffers reused for loading subsequent images of a sequence, so that loading does not allocate per frame.
+///
+/// Once they have grown to the size of the largest frame, loading a sequence of any length holds about two frames'
+/// worth of pixels (the decoded file contents and their RGB8 conversion; twice that for 16-bit files), plus
+/// the decoder's internal buffers. To verify, watch the resident memory of the process (e.g., `top -p <PID>` or
+/// Task Manager) while loading a long sequence without the cache: it must level off after the first frame instead
+/// of growing with the number of frames (until the textures are uploaded to a GPU sharing system memory).
+#[derive(Default)]
+pub struct StagingBuffers {
+    /// Decoded file contents (stored in `u32`s to be suitably aligned for 16-bit and floating-point samples).
+    decoded: Vec<u32>,
+    /// Bit depth images are converted to; selects `rgb8` or `rgb16`.
+    depth: BitDepth,
+    /// Bit depth of the last loaded image before conversion.
+    source_depth: BitDepth,
+    /// Tightly packed RGB8 pixels.
+    rgb8: Vec<u8>,
+    /// Tightly packed RGB16 pixels.
+    rgb16: Vec<u16>,
+    width: u32,
+    height: u32
+}
+
+impl StagingBuffers {
+    /// Creates buffers converting images to RGB with `depth`.
+    pub fn new(depth: BitDepth) -> StagingBuffers {
+        StagingBuffers{ depth, ..Default::default() }
+    }
+
+    pub fn depth(&self) -> BitDepth { self.depth }
+
+    /// Returns the bit depth of the last loaded image (before its conversion to `depth`).
+    pub fn source_depth(&self) -> BitDepth { self.source_depth }
+
+    /// Returns the RGB8 pixels of the last loaded image (empty if `depth` is `BitDepth::Sixteen`).
+    pub fn pixels(&self) -> &[u8] { &self.rgb8 }
+
+    pub fn pixels_mut(&mut self) -> &mut [u8] { &mut self.rgb8 }
+
+    /// Returns the RGB16 pixels of the last loaded image (empty if `depth` is `BitDepth::Eight`).
+    pub fn pixels16(&self) -> &[u16] { &self.rgb16 }
 
-    let src_buffer = src_image.into_rgb8(); //TODO: handle other bit depths
+    pub fn pixels16_mut(&mut self) -> &mut [u16] { &mut self.rgb16 }
 
-    let layout = src_buffer.as_flat_samples().layout;
-    assert!(layout.height_stride == layout.width as usize * layout.channels as usize); //TODO: handle line padding
-    let pixels = src_buffer.into_vec();
+    pub fn width(&self) -> u32 { self.width }
+
+    pub fn height(&self) -> u32 { self.height }
+
+    /// Returns the last loaded image converted to Mono8 (the only per-frame allocation; meant for disk detection).
+    pub fn mono8_image(&self) -> ga_image::Image {
+        let pixels = match self.depth {
+            BitDepth::Eight => self.rgb8.chunks_exact(3)
+                .map(|rgb| ((rgb[0] as u32 + rgb[1] as u32 + rgb[2] as u32 + 1) / 3) as u8)
+                .collect(),
+
+            BitDepth::Sixteen => self.rgb16.chunks_exact(3)
+                .map(|rgb| u16_to_u8(((rgb[0] as u32 + rgb[1] as u32 + rgb[2] as u32 + 1) / 3) as u16))
+                .collect()
+        };
+
+        ga_image::Image::new_from_pixels(self.width, self.height, None, ga_image::PixelFormat::Mono8, None, pixels)
+    }
+
+    /// Decodes an image into the buffers and converts it to RGB (as `rgb8_image` for 8 bits); if `format` is not
+    /// specified, it is guessed from the contents. Common formats are decoded directly into the buffers; others are
+    /// decoded into a temporary image first.
+    pub fn decode<R: BufRead + Seek>(
+        &mut self,
+        reader: R,
+        format: Option<image::ImageFormat>
+    ) -> Result<(), Box<dyn Error>> {
+        use image::codecs::{bmp::BmpDecoder, png::PngDecoder, tiff::TiffDecoder};
+
+        let mut reader = image::io::Reader::new(reader);
+        match format {
+            Some(format) => reader.set_format(format),
+            None => reader = reader.with_guessed_format()?
+        }
+
+        match reader.format() {
+            Some(image::ImageFormat::Png) => self.decode_with(PngDecoder::new(reader.into_inner())?),
+            Some(image::ImageFormat::Tiff) => self.decode_with(TiffDecoder::new(reader.into_inner())?),
+            Some(image::ImageFormat::Bmp) => self.decode_with(BmpDecoder::new(reader.into_inner())?),
+            _ => self.set_image(&reader.decode()?)
+        }
+    }
+
+    /// Converts a video frame to RGB (demosaicing raw color frames) and stores it in the buffers. Unlike files,
+    /// frames are read by the video into a new image each time.
+    pub fn set_frame(&mut self, frame: &ga_image::Image) {
+        self.source_depth = BitDepth::of(frame.pixel_format());
+        let row_len = 3 * frame.width() as usize;
+        let demosaic = Some(ga_image::DemosaicMethod::HqLinear);
+        match self.depth {
+            BitDepth::Eight => {
+                let frame = frame.convert_pix_fmt(ga_image::PixelFormat::RGB8, demosaic);
+                self.rgb8.clear();
+                for y in 0..frame.height() { self.rgb8.extend_from_slice(&frame.line::<u8>(y)[..row_len]); }
+            },
+
+            BitDepth::Sixteen => {
+                let frame = frame.convert_pix_fmt(ga_image::PixelFormat::RGB16, demosaic);
+                self.rgb16.clear();
+                for y in 0..frame.height() { self.rgb16.extend_from_slice(&frame.line::<u16>(y)[..row_len]); }
+            }
+        }
+        self.width = frame.width();
+        self.height = frame.height();
+    }
 
-    let image = ga_image::Image::new_from_pixels(width, height, None, ga_image::PixelFormat::RGB8, None, pixels);
+    /// Converts `image` to RGB (as `rgb8_image` for 8 bits) and stores it in the buffers.
+    fn set_image(&mut self, image: &image::DynamicImage) -> Result<(), Box<dyn Error>> {
+        match self.depth {
+            BitDepth::Eight => {
+                let image = rgb8_image(image)?;
+                self.rgb8.clear();
+                self.rgb8.extend_from_slice(image.pixels::<u8>());
+            },
 
-    Ok(image)
+            BitDepth::Sixteen => {
+                // rejects unsupported pixel formats
+                get_metadata_from_image(image)?;
+                self.rgb16.clear();
+                self.rgb16.extend_from_slice(image.to_rgb16().as_raw());
+            }
+        }
+        self.source_depth = BitDepth::of_color_type(image.color());
+        self.width = image.width();
+        self.height = image.height();
+        Ok(())
+    }
+
+    fn decode_with<'a, D: image::ImageDecoder<'a>>(&mut self, decoder: D) -> Result<(), Box<dyn Error>> {
+        use image::ColorType;
+        use image::flat::{FlatSamples, SampleLayout};
+
+        let (width, height) = decoder.dimensions();
+        let color_type = decoder.color_type();
+        let num_channels = color_type.channel_count();
+        let total_bytes = decoder.total_bytes() as usize;
+
+        self.decoded.resize((total_bytes + 3) / 4, 0);
+        // SAFETY: `u8` has no alignment requirements and any bytes are valid `u8` values
+        let (_, bytes, _) = unsafe { self.decoded.align_to_mut::<u8>() };
+        decoder.read_image(&mut bytes[..total_bytes])?;
+
+        let layout = SampleLayout::row_major_packed(num_channels, width, height);
+        let (depth, rgb8, rgb16) = (self.depth, &mut self.rgb8, &mut self.rgb16);
+        match color_type {
+            ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8 => {
+                // SAFETY: as above
+                let (_, bytes, _) = unsafe { self.decoded.align_to::<u8>() };
+                let samples = FlatSamples{ samples: &bytes[..total_bytes], layout, color_hint: None };
+                match depth {
+                    BitDepth::Eight => pack_rgb(&samples, |v| v, rgb8),
+                    BitDepth::Sixteen => pack_rgb(&samples, |v| v as u16 * 257, rgb16)
+                }
+            },
+
+            // the decoder provides multi-byte samples in native byte order
+            ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => {
+                // SAFETY: `decoded` is aligned for `u16`, and any bytes are valid `u16` values
+                let (_, values, _) = unsafe { self.decoded.align_to::<u16>() };
+                let samples = FlatSamples{ samples: &values[..total_bytes / 2], layout, color_hint: None };
+                match depth {
+                    BitDepth::Eight => pack_rgb(&samples, u16_to_u8, rgb8),
+                    BitDepth::Sixteen => pack_rgb(&samples, |v| v, rgb16)
+                }
+            },
+
+            ColorType::Rgb32F | ColorType::Rgba32F => {
+                // SAFETY: `decoded` is aligned for `f32`, and any bytes are valid `f32` values
+                let (_, values, _) = unsafe { self.decoded.align_to::<f32>() };
+                let samples = FlatSamples{ samples: &values[..total_bytes / 4], layout, color_hint: None };
+                match depth {
+                    BitDepth::Eight => pack_rgb(&samples, |v| (v.max(0.0).min(1.0) * 255.0).round() as u8, rgb8),
+                    BitDepth::Sixteen => pack_rgb(&samples, |v| (v.max(0.0).min(1.0) * 65535.0).round() as u16, rgb16)
+                }
+            },
+
+            other => return Err(format!("unsupported pixel format {:?}", other).into())
+        }
+
+        self.source_depth = BitDepth::of_color_type(color_type);
+        self.width = width;
+        self.height = height;
+
+        Ok(())
+    }
+
+    /// Averages blocks of `factor`×`factor` pixels in place; trailing columns and rows not filling a whole block
+    /// are discarded.
+    pub fn bin(&mut self, factor: u32) -> Result<(), Box<dyn Error>> {
+        if factor == 0 { return Err("binning factor cannot be zero".into()); }
+        if factor == 1 { return Ok(()); }
+
+        let width = self.width / factor;
+        let height = self.height / factor;
+        if width == 0 || height == 0 {
+            return Err(
+                format!("image too small ({}x{}) for {}x{} binning", self.width, self.height, factor, factor).into()
+            );
+        }
+
+        match self.depth {
+            BitDepth::Eight => bin_rgb(&mut self.rgb8, self.width, factor, [width, height], |v| v as u8),
+            BitDepth::Sixteen => bin_rgb(&mut self.rgb16, self.width, factor, [width, height], |v| v as u16)
+        }
+        self.width = width;
+        self.height = height;
+
+        Ok(())
+    }
+}
+
+/// Averages blocks of `factor`×`factor` pixels of tightly packed RGB `pixels` (`src_width` pixels per row) in place,
+/// leaving an image of `size`.
+fn bin_rgb<T: Copy + Into<u32>>(
+    pixels: &mut Vec<T>,
+    src_width: u32,
+    factor: u32,
+    size: [u32; 2],
+    from_u32: impl Fn(u32) -> T
+) {
+    let [width, height] = size;
+
+    // an output row is written only after all its source rows have been read, and lies before them
+    let src_row_len = 3 * src_width as usize;
+    let num_summed = factor * factor;
+    let mut sums = vec![0u32; 3 * width as usize];
+    for y in 0..height as usize {
+        sums.iter_mut().for_each(|s| *s = 0);
+        for src_y in y * factor as usize..(y + 1) * factor as usize {
+            let src_row = &pixels[src_y * src_row_len..(src_y + 1) * src_row_len];
+            for (x, sum) in sums.chunks_exact_mut(3).enumerate() {
+                for src_x in x * factor as usize..(x + 1) * factor as usize {
+                    for ch in 0..3 { sum[ch] += Into::<u32>::into(src_row[3 * src_x + ch]); }
+                }
+            }
+        }
+
+        let dest_row = &mut pixels[3 * y * width as usize..3 * (y + 1) * width as usize];
+        for (dest, sum) in dest_row.iter_mut().zip(sums.iter()) {
+            *dest = from_u32((sum + num_summed / 2) / num_summed);
+        }
+    }
+
+    pixels.truncate(3 * width as usize * height as usize);
+}
+
+/// Like `StagingBuffers::decode`, but reads from `path`; stops reading the file soon after `cancel` gets cancelled.
+pub fn load_image_staged(
+    path: &std::path::Path,
+    cancel: &CancelToken,
+    staging: &mut StagingBuffers
+) -> Result<(), Box<dyn Error>> {
+    let file = std::fs::File::open(path)?;
+    let reader = std::io::BufReader::new(CancellableReader::new(file, cancel.clone()));
+    if fits::is_fits_path(path) {
+        // not decoded directly into the buffers
+        staging.set_image(&fits::decode(reader)?)
+    } else {
+        staging.decode(reader, image::ImageFormat::from_path(path).ok())
+    }
 }
 
 pub fn image_from_texture(texture: &glium::Texture2d) -> ga_image::Image {
@@ -86,3 +522,358 @@ pub fn image_from_texture(texture: &glium::Texture2d) -> ga_image::Image {
 
     image
 }
+
+/// Like `image_from_texture`, but returns an RGB16 image (e.g., for textures with more than 8 bits per channel).
+pub fn image_from_texture_rgb16(texture: &glium::Texture2d) -> ga_image::Image {
+    let mut image = ga_image::Image::new(
+        texture.width(),
+        texture.height(),
+        None,
+        ga_image::PixelFormat::RGB16,
+        None,
+        false
+    );
+
+    unsafe {
+        gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 2);
+        gl::PixelStorei(gl::PACK_ROW_LENGTH, 0);
+        gl::BindTexture(gl::TEXTURE_2D, texture.get_id());
+        gl::GetTexImage(gl::TEXTURE_2D, 0, gl::RGB, gl::UNSIGNED_SHORT, image.raw_pixels_mut().as_ptr() as _);
+    }
+
+    image
+}
+
+/// Encodes RGB8 or RGB16 (in native byte order) `pixels` as PNG with fixed compression and filter settings
+/// (independent of the encoder's defaults) and without time-varying chunks, so that identical pixels always give
+/// identical files.
+pub fn encode_png_reproducible(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    color_type: image::ColorType
+) -> Result<Vec<u8>, image::ImageError> {
+    use image::ImageEncoder;
+    use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+
+    let mut png = vec![];
+    PngEncoder::new_with_quality(&mut png, CompressionType::Default, FilterType::Sub)
+        .write_image(pixels, width, height, color_type)?;
+
+    Ok(png)
+}
+
+mod tests {
+    use super::*;
+    use ga_image::PixelFormat;
+    use image::flat::{FlatSamples, SampleLayout};
+    use std::io::Cursor;
+
+    /// Returns samples with rows padded to `height_stride` with `filler`.
+    fn padded_samples<T: Copy>(
+        packed: &[T],
+        channels: u8,
+        width: u32,
+        height: u32,
+        height_stride: usize,
+        filler: T
+    ) -> Vec<T> {
+        let row_len = width as usize * channels as usize;
+        let mut samples = vec![];
+        for row in packed.chunks_exact(row_len).take(height as usize) {
+            samples.extend_from_slice(row);
+            samples.resize(samples.len() + height_stride - row_len, filler);
+        }
+        samples
+    }
+
+    fn layout(channels: u8, width: u32, height: u32, height_stride: usize) -> SampleLayout {
+        SampleLayout{
+            channels,
+            channel_stride: 1,
+            width,
+            width_stride: channels as usize,
+            height,
+            height_stride
+        }
+    }
+
+    /// Test pattern of 8-bit RGB values.
+    fn rgb_pattern(width: u32, height: u32) -> Vec<u8> {
+        (0..height).flat_map(|y| (0..width).flat_map(move |x| (0..3).map(move |c| {
+            ((x * 7 + y * 31 + c * 85) % 256) as u8
+        }))).collect()
+    }
+
+    /// Returns a 24-bit BMP file with rows aligned to 4 bytes; `pixels` are RGB values from the top-left.
+    fn bmp_file(width: u32, height: u32, pixels: &[u8], top_down: bool) -> Vec<u8> {
+        const HEADERS_SIZE: u32 = 14 + 40;
+        let row_len = 3 * width as usize;
+        let row_size = (row_len + 3) / 4 * 4;
+        let data_size = (row_size * height as usize) as u32;
+
+        let mut bmp = vec![];
+        bmp.extend_from_slice(b"BM");
+        bmp.extend_from_slice(&(HEADERS_SIZE + data_size).to_le_bytes());
+        bmp.extend_from_slice(&[0; 4]);
+        bmp.extend_from_slice(&HEADERS_SIZE.to_le_bytes());
+
+        // BITMAPINFOHEADER; negative height means rows are stored from the top
+        bmp.extend_from_slice(&40u32.to_le_bytes());
+        bmp.extend_from_slice(&(width as i32).to_le_bytes());
+        bmp.extend_from_slice(&(if top_down { -(height as i32) } else { height as i32 }).to_le_bytes());
+        bmp.extend_from_slice(&1u16.to_le_bytes()); // planes
+        bmp.extend_from_slice(&24u16.to_le_bytes()); // bits per pixel
+        bmp.extend_from_slice(&0u32.to_le_bytes()); // no compression
+        bmp.extend_from_slice(&data_size.to_le_bytes());
+        bmp.extend_from_slice(&2835i32.to_le_bytes()); // horizontal resolution (72 DPI)
+        bmp.extend_from_slice(&2835i32.to_le_bytes()); // vertical resolution
+        bmp.extend_from_slice(&0u32.to_le_bytes()); // palette size
+        bmp.extend_from_slice(&0u32.to_le_bytes()); // important colors
+
+        for i in 0..height as usize {
+            let y = if top_down { i } else { height as usize - 1 - i };
+            for rgb in pixels[y * row_len..(y + 1) * row_len].chunks_exact(3) {
+                bmp.extend_from_slice(&[rgb[2], rgb[1], rgb[0]]);
+            }
+            bmp.resize(bmp.len() + row_size - row_len, 0);
+        }
+
+        bmp
+    }
+
+    #[test]
+    fn padded_8_bit_rows_are_packed() {
+        let packed = rgb_pattern(5, 3);
+        let samples = padded_samples(&packed, 3, 5, 3, 16, 0xEE);
+        let flat = FlatSamples{ samples: &samples[..], layout: layout(3, 5, 3, 16), color_hint: None };
+
+        let image = rgb8_from_samples(&flat, |v| v).unwrap();
+        assert_eq!(PixelFormat::RGB8, image.pixel_format());
+        for y in 0..3 {
+            assert_eq!(&packed[y as usize * 15..(y as usize + 1) * 15], &image.line::<u8>(y)[..15]);
+        }
+    }
+
+    #[test]
+    fn padded_16_bit_rgba_rows_are_packed() {
+        // 3x2 RGBA, rows padded by 2 samples
+        let packed: Vec<u16> = (0..24).map(|i| i * 2570).collect();
+        let samples = padded_samples(&packed, 4, 3, 2, 14, 0xFFFF);
+        let flat = FlatSamples{ samples: &samples[..], layout: layout(4, 3, 2, 14), color_hint: None };
+
+        let image = rgb8_from_samples(&flat, u16_to_u8).unwrap();
+        for y in 0..2 {
+            let expected: Vec<u8> = packed[y * 12..(y + 1) * 12]
+                .chunks_exact(4)
+                .flat_map(|rgba| rgba[..3].iter().map(|v| (v / 257) as u8))
+                .collect();
+            assert_eq!(&expected[..], &image.line::<u8>(y as u32)[..9]);
+        }
+    }
+
+    #[test]
+    fn padded_mono_rows_become_gray() {
+        let samples: Vec<u8> = vec![10, 20, 30, 0, 40, 50, 60, 0];
+        let flat = FlatSamples{ samples: &samples[..], layout: layout(1, 3, 2, 4), color_hint: None };
+
+        let image = rgb8_from_samples(&flat, |v| v).unwrap();
+        assert_eq!(&[40, 40, 40, 50, 50, 50, 60, 60, 60], &image.line::<u8>(1)[..9]);
+    }
+
+    #[test]
+    fn too_short_samples_are_rejected() {
+        let samples = vec![0u8; 3 * 5 * 3];
+        let flat = FlatSamples{ samples: &samples[..], layout: layout(3, 5, 3, 16), color_hint: None };
+        assert!(rgb8_from_samples(&flat, |v| v).is_err());
+    }
+
+    #[test]
+    fn odd_width_bmp_is_loaded_pixel_exact() {
+        const WIDTH: u32 = 33;
+        const HEIGHT: u32 = 4;
+        let pixels = rgb_pattern(WIDTH, HEIGHT);
+
+        // rows stored from the bottom (usual) and from the top
+        for top_down in [false, true] {
+            let bmp = bmp_file(WIDTH, HEIGHT, &pixels, top_down);
+            let decoded = decode_image(Cursor::new(bmp), Some(image::ImageFormat::Bmp)).unwrap();
+            let image = rgb8_image(&decoded).unwrap();
+
+            assert_eq!((WIDTH, HEIGHT), (image.width(), image.height()));
+            for y in 0..HEIGHT {
+                let row_len = 3 * WIDTH as usize;
+                assert_eq!(
+                    &pixels[y as usize * row_len..(y as usize + 1) * row_len],
+                    &image.line::<u8>(y)[..row_len],
+                    "row {}, top-down: {}", y, top_down
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn odd_width_16_bit_tiff_is_loaded_pixel_exact() {
+        const WIDTH: u32 = 33;
+        const HEIGHT: u32 = 3;
+        let values: Vec<u16> = (0..WIDTH * HEIGHT * 4).map(|i| (i * 397 % 65536) as u16).collect();
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_ne_bytes()).collect();
+
+        let mut tiff = Cursor::new(vec![]);
+        image::codecs::tiff::TiffEncoder::new(&mut tiff)
+            .encode(&bytes, WIDTH, HEIGHT, image::ColorType::Rgba16)
+            .unwrap();
+        tiff.set_position(0);
+
+        let image = rgb8_image(&decode_image(tiff, Some(image::ImageFormat::Tiff)).unwrap()).unwrap();
+        for y in 0..HEIGHT {
+            let expected: Vec<u8> = values[(y * WIDTH * 4) as usize..((y + 1) * WIDTH * 4) as usize]
+                .chunks_exact(4)
+                .flat_map(|rgba| rgba[..3].iter().map(|v| u16_to_u8(*v)))
+                .collect();
+            assert_eq!(&expected[..], &image.line::<u8>(y)[..3 * WIDTH as usize]);
+        }
+    }
+
+    fn staged(width: u32, height: u32, rgb8: Vec<u8>) -> StagingBuffers {
+        StagingBuffers{ rgb8, width, height, ..Default::default() }
+    }
+
+    #[test]
+    fn staged_bmp_matches_decoded_image() {
+        const WIDTH: u32 = 33;
+        const HEIGHT: u32 = 4;
+        let pixels = rgb_pattern(WIDTH, HEIGHT);
+        let bmp = bmp_file(WIDTH, HEIGHT, &pixels, false);
+
+        let mut staging = StagingBuffers::default();
+        staging.decode(Cursor::new(&bmp), Some(image::ImageFormat::Bmp)).unwrap();
+        assert_eq!((WIDTH, HEIGHT), (staging.width(), staging.height()));
+        assert_eq!(&pixels[..], staging.pixels());
+
+        let decoded = rgb8_image(&decode_image(Cursor::new(&bmp), None).unwrap()).unwrap();
+        assert_eq!(decoded.pixels::<u8>(), staging.pixels());
+    }
+
+    #[test]
+    fn staging_buffers_are_reused_for_subsequent_frames() {
+        const WIDTH: u32 = 64;
+        const HEIGHT: u32 = 48;
+
+        let mut staging = StagingBuffers::default();
+        let mut buffers = None;
+        for frame in 0..5u8 {
+            let pixels: Vec<u8> = rgb_pattern(WIDTH, HEIGHT).iter().map(|v| v.wrapping_add(frame)).collect();
+            staging.decode(Cursor::new(bmp_file(WIDTH, HEIGHT, &pixels, false)), None).unwrap();
+            assert_eq!(&pixels[..], staging.pixels());
+
+            let current = (staging.decoded.as_ptr(), staging.decoded.capacity(), staging.rgb8.as_ptr());
+            if let Some(buffers) = buffers { assert_eq!(buffers, current, "frame {} reallocated", frame); }
+            buffers = Some(current);
+        }
+    }
+
+    #[test]
+    fn mono_image_averages_channels() {
+        let staging = staged(2, 1, vec![10, 20, 31, 255, 255, 254]);
+        let mono = staging.mono8_image();
+        assert_eq!(PixelFormat::Mono8, mono.pixel_format());
+        assert_eq!(&[20u8, 255u8], &mono.line::<u8>(0)[..2]);
+    }
+
+    #[test]
+    fn binning_discards_incomplete_blocks() {
+        // 5x3 image (gray); 2x2 binning uses only the top-left 4x2 area
+        let values: Vec<u8> = vec![
+            1, 3, 10, 20, 99,
+            3, 5, 30, 40, 99,
+            99, 99, 99, 99, 99
+        ];
+        let mut staging = staged(5, 3, values.iter().flat_map(|v| [*v; 3]).collect());
+
+        staging.bin(2).unwrap();
+        assert_eq!((2, 1), (staging.width(), staging.height()));
+        assert_eq!(&[3u8, 3, 3, 25, 25, 25], staging.pixels());
+    }
+
+    #[test]
+    fn binning_full_range_values_does_not_overflow() {
+        let mut staging = staged(3, 3, [255u8, 100, 200].repeat(9));
+
+        staging.bin(3).unwrap();
+        assert_eq!((1, 1), (staging.width(), staging.height()));
+        assert_eq!(&[255u8, 100, 200], staging.pixels());
+    }
+
+    #[test]
+    fn binning_16_bit_rgb_does_not_overflow() {
+        let rgb16: Vec<u16> = (0..3 * 3 * 3).map(|i| if i % 3 == 0 { 0xFFFF } else { 1000 * (i as u16 % 3) }).collect();
+        let mut staging = StagingBuffers{ depth: BitDepth::Sixteen, rgb16, width: 3, height: 3, ..Default::default() };
+
+        staging.bin(3).unwrap();
+        assert_eq!((1, 1), (staging.width(), staging.height()));
+        assert_eq!(&[0xFFFFu16, 1000, 2000], staging.pixels16());
+    }
+
+    #[test]
+    fn binning_rejects_too_small_image() {
+        assert!(staged(2, 2, vec![0; 12]).bin(3).is_err());
+    }
+
+    #[test]
+    fn sixteen_bit_values_are_kept_and_binned() {
+        const WIDTH: u32 = 4;
+        const HEIGHT: u32 = 2;
+        let values: Vec<u16> = (0..WIDTH * HEIGHT * 3).map(|i| (i * 2731 % 65536) as u16).collect();
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        let mut tiff = Cursor::new(vec![]);
+        image::codecs::tiff::TiffEncoder::new(&mut tiff)
+            .encode(&bytes, WIDTH, HEIGHT, image::ColorType::Rgb16)
+            .unwrap();
+
+        let mut staging = StagingBuffers::new(BitDepth::Sixteen);
+        staging.decode(Cursor::new(tiff.into_inner()), Some(image::ImageFormat::Tiff)).unwrap();
+        assert_eq!(BitDepth::Sixteen, staging.source_depth());
+        assert_eq!(&values[..], staging.pixels16());
+        assert!(staging.pixels().is_empty());
+
+        staging.bin(2).unwrap();
+        assert_eq!((2, 1), (staging.width(), staging.height()));
+        // blue channel of the right output pixel: columns 2 and 3 of both rows
+        let sum: u32 = [2, 3, 6, 7].iter().map(|pixel| values[3 * pixel + 2] as u32).sum();
+        assert_eq!(((sum + 2) / 4) as u16, staging.pixels16()[5]);
+    }
+
+    #[test]
+    fn binning_16_bit_sums_beyond_u16_range_does_not_overflow() {
+        // 256 full-range values per channel
+        let mut pixels = [u16::MAX, 1000, 40000].repeat(16 * 16);
+
+        bin_rgb(&mut pixels, 16, 16, [1, 1], |v| v as u16);
+        assert_eq!(vec![u16::MAX, 1000, 40000], pixels);
+    }
+
+    #[test]
+    fn eight_bit_file_is_expanded_in_16_bit_buffers() {
+        let pixels = rgb_pattern(8, 2);
+        let mut staging = StagingBuffers::new(BitDepth::Sixteen);
+        staging.decode(Cursor::new(bmp_file(8, 2, &pixels, false)), None).unwrap();
+        assert_eq!(BitDepth::Eight, staging.source_depth());
+        let expected: Vec<u16> = pixels.iter().map(|v| *v as u16 * 257).collect();
+        assert_eq!(&expected[..], staging.pixels16());
+    }
+
+    #[test]
+    fn reproducible_png_is_identical_across_runs() {
+        let pixels: Vec<u8> = (0..16 * 8 * 3).map(|i| (i * 37 % 251) as u8).collect();
+
+        let first = encode_png_reproducible(&pixels, 16, 8, image::ColorType::Rgb8).unwrap();
+        let second = encode_png_reproducible(&pixels, 16, 8, image::ColorType::Rgb8).unwrap();
+        assert_eq!(first, second);
+        assert!(!first.windows(4).any(|chunk| chunk == b"tIME"));
+
+        let decoded = image::load_from_memory(&first).unwrap().to_rgb8();
+        assert_eq!(pixels, decoded.into_raw());
+    }
+}
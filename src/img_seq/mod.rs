@@ -23,6 +23,13 @@ mod ser;
 pub use image_list::create_image_list;
 pub use ser::open_ser_video;
 
+use std::path::Path;
+
+/// Returns `true` if `path` has the extension of a SER video.
+pub fn is_ser_path(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()).map_or(false, |ext| ext.eq_ignore_ascii_case("ser"))
+}
+
 #[derive(Debug)]
 pub struct ImgSeqError {
     description: String
@@ -48,3 +55,15 @@ pub trait ImageSequence {
 
     fn num_images(&self) -> usize;
 }
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ser_paths_are_recognized_by_extension() {
+        assert!(is_ser_path(Path::new("/data/jupiter.ser")));
+        assert!(is_ser_path(Path::new("mars.SER")));
+        assert!(!is_ser_path(Path::new("mars.tif")));
+        assert!(!is_ser_path(Path::new("ser")));
+    }
+}
@@ -0,0 +1,268 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Diagnostic log. Entries are printed to the console and sent (from any thread) over a channel, which the GUI
+//! drains into a `LogBuffer` shown in the log window.
+
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Number of entries kept by the log window; the oldest ones are discarded first.
+pub const DEFAULT_CAPACITY: usize = 2000;
+
+/// Max. number of entries waiting in the channel; the oldest ones are discarded first. (The channel is drained
+/// in every GUI frame, but no frames are processed while the main window is minimized.)
+const MAX_PENDING_ENTRIES: usize = DEFAULT_CAPACITY;
+
+/// Number of the most recent entries included in crash reports.
+const NUM_RECENT_ENTRIES: usize = 100;
+
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, strum::EnumIter)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error"
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// Time since program start.
+    pub time: Duration,
+    pub severity: Severity,
+    pub message: String
+}
+
+impl Entry {
+    pub fn to_text(&self) -> String {
+        format!("[{:10.3}] {:<7} {}", self.time.as_secs_f64(), self.severity.name(), self.message)
+    }
+}
+
+/// Ring buffer of log entries.
+pub struct LogBuffer {
+    entries: VecDeque<Entry>,
+    capacity: usize,
+    /// Number of entries discarded due to exceeding capacity.
+    num_discarded: usize
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> LogBuffer {
+        LogBuffer{ entries: VecDeque::new(), capacity: capacity.max(1), num_discarded: 0 }
+    }
+
+    pub fn push(&mut self, entry: Entry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+            self.num_discarded += 1;
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn num_discarded(&self) -> usize { self.num_discarded }
+
+    /// Returns entries (oldest first) of at least the given severity.
+    pub fn entries(&self, min_severity: Severity) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().filter(move |e| e.severity >= min_severity)
+    }
+
+    /// Returns entries of at least the given severity as text, one line per entry.
+    pub fn to_text(&self, min_severity: Severity) -> String {
+        let mut text = String::new();
+        if self.num_discarded > 0 {
+            text += &format!("({} earlier entries discarded)\n", self.num_discarded);
+        }
+        for entry in self.entries(min_severity) {
+            text += &entry.to_text();
+            text.push('\n');
+        }
+
+        text
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.num_discarded = 0;
+    }
+}
+
+fn start_time() -> Instant {
+    static START: OnceLock<Instant> = OnceLock::new();
+    *START.get_or_init(Instant::now)
+}
+
+/// Number of entries discarded from the channel since the last `drain_into`.
+static NUM_DISCARDED_PENDING: AtomicUsize = AtomicUsize::new(0);
+
+fn channel() -> &'static (crossbeam::channel::Sender<Entry>, crossbeam::channel::Receiver<Entry>) {
+    static CHANNEL: OnceLock<(crossbeam::channel::Sender<Entry>, crossbeam::channel::Receiver<Entry>)> =
+        OnceLock::new();
+    CHANNEL.get_or_init(crossbeam::channel::unbounded)
+}
+
+/// The most recent entries (independently of the log window, which may not be shown or even created).
+fn recent() -> &'static Mutex<LogBuffer> {
+    static RECENT: OnceLock<Mutex<LogBuffer>> = OnceLock::new();
+    RECENT.get_or_init(|| Mutex::new(LogBuffer::new(NUM_RECENT_ENTRIES)))
+}
+
+/// Returns the most recent entries (oldest first); empty if they cannot be accessed right now (e.g., when
+/// called from a panic hook while another thread is logging).
+pub fn recent_entries() -> Vec<Entry> {
+    match recent().try_lock() {
+        Ok(recent) => recent.entries(Severity::Info).cloned().collect(),
+        Err(_) => vec![]
+    }
+}
+
+/// Marks the program start (entry times are relative to it); to be called first thing in `main`.
+pub fn init() {
+    start_time();
+}
+
+/// Logs a message (use the `log_info!`, `log_warning!`, `log_error!` macros instead of calling it directly).
+pub fn log(severity: Severity, message: String) {
+    let entry = Entry{ time: start_time().elapsed(), severity, message };
+    match severity {
+        Severity::Info => println!("{}", entry.message),
+        Severity::Warning | Severity::Error => eprintln!("{}", entry.message)
+    }
+    if let Ok(mut recent) = recent().lock() { recent.push(entry.clone()); }
+    let num_discarded = send_bounded(channel(), entry, MAX_PENDING_ENTRIES);
+    if num_discarded > 0 { NUM_DISCARDED_PENDING.fetch_add(num_discarded, Ordering::Relaxed); }
+}
+
+/// Sends `entry`, first discarding the oldest entries waiting in the channel so that at most `max_pending` remain;
+/// returns the number of discarded entries.
+fn send_bounded(
+    channel: &(crossbeam::channel::Sender<Entry>, crossbeam::channel::Receiver<Entry>),
+    entry: Entry,
+    max_pending: usize
+) -> usize {
+    let mut num_discarded = 0;
+    while channel.1.len() >= max_pending.max(1) && channel.1.try_recv().is_ok() { num_discarded += 1; }
+    let _ = channel.0.send(entry);
+
+    num_discarded
+}
+
+/// Moves entries logged so far (by all threads) to `buffer`; returns them, e.g., to be written to a file.
+pub fn drain_into(buffer: &mut LogBuffer) -> Vec<Entry> {
+    buffer.num_discarded += NUM_DISCARDED_PENDING.swap(0, Ordering::Relaxed);
+    let received: Vec<Entry> = channel().1.try_iter().collect();
+    for entry in &received { buffer.push(entry.clone()); }
+
+    received
+}
+
+macro_rules! log_info {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Severity::Info, format!($($arg)*)) }
+}
+
+macro_rules! log_warning {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Severity::Warning, format!($($arg)*)) }
+}
+
+macro_rules! log_error {
+    ($($arg:tt)*) => { $crate::logging::log($crate::logging::Severity::Error, format!($($arg)*)) }
+}
+
+pub(crate) use {log_error, log_info, log_warning};
+
+mod tests {
+    use super::*;
+
+    fn entry(secs: u64, severity: Severity, message: &str) -> Entry {
+        Entry{ time: Duration::from_secs(secs), severity, message: message.to_string() }
+    }
+
+    fn messages(buffer: &LogBuffer, min_severity: Severity) -> Vec<&str> {
+        buffer.entries(min_severity).map(|e| e.message.as_str()).collect()
+    }
+
+    #[test]
+    fn oldest_entries_are_discarded_when_full() {
+        let mut buffer = LogBuffer::new(3);
+        for (i, message) in ["a", "b", "c", "d", "e"].iter().enumerate() {
+            buffer.push(entry(i as u64, Severity::Info, message));
+        }
+        assert_eq!(vec!["c", "d", "e"], messages(&buffer, Severity::Info));
+        assert_eq!(2, buffer.num_discarded());
+
+        buffer.clear();
+        assert!(messages(&buffer, Severity::Info).is_empty());
+        assert_eq!(0, buffer.num_discarded());
+    }
+
+    #[test]
+    fn entries_are_filtered_by_min_severity() {
+        let mut buffer = LogBuffer::new(10);
+        buffer.push(entry(0, Severity::Info, "loading"));
+        buffer.push(entry(1, Severity::Error, "failed"));
+        buffer.push(entry(2, Severity::Warning, "slow"));
+
+        assert_eq!(vec!["loading", "failed", "slow"], messages(&buffer, Severity::Info));
+        assert_eq!(vec!["failed", "slow"], messages(&buffer, Severity::Warning));
+        assert_eq!(vec!["failed"], messages(&buffer, Severity::Error));
+    }
+
+    #[test]
+    fn text_contains_time_severity_and_message() {
+        let mut buffer = LogBuffer::new(1);
+        buffer.push(entry(1, Severity::Info, "first"));
+        buffer.push(Entry{ time: Duration::from_millis(12_345), ..entry(0, Severity::Warning, "second") });
+
+        assert_eq!("(1 earlier entries discarded)\n[    12.345] warning second\n", buffer.to_text(Severity::Info));
+        assert_eq!("(1 earlier entries discarded)\n", buffer.to_text(Severity::Error));
+    }
+
+    #[test]
+    fn oldest_pending_entries_are_discarded_when_not_drained() {
+        let channel = crossbeam::channel::unbounded();
+        let num_discarded: usize = ["a", "b", "c", "d", "e"].iter().enumerate()
+            .map(|(i, message)| send_bounded(&channel, entry(i as u64, Severity::Info, message), 3))
+            .sum();
+
+        assert_eq!(2, num_discarded);
+        let pending: Vec<String> = channel.1.try_iter().map(|e| e.message).collect();
+        assert_eq!(vec!["c", "d", "e"], pending);
+    }
+
+    #[test]
+    fn entries_from_other_threads_are_drained() {
+        std::thread::spawn(|| log_warning!("from worker {}", 1)).join().unwrap();
+
+        let mut buffer = LogBuffer::new(DEFAULT_CAPACITY);
+        let drained = drain_into(&mut buffer);
+        assert!(drained.iter().any(|e| e.message == "from worker 1" && e.severity == Severity::Warning));
+        assert_eq!(drained.len(), buffer.entries(Severity::Info).count());
+    }
+}
@@ -22,4 +22,7 @@ pub trait LongForegroundTask {
     fn step(&mut self) -> bool;
 
     fn cancel(&mut self);
+
+    /// Returns the title and text of a message to be shown after the task has ended (e.g., an error).
+    fn end_message(&self) -> Option<(String, String)> { None }
 }
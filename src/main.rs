@@ -18,15 +18,28 @@
 //
 
 mod args;
+mod background;
+#[cfg(test)]
+mod bench;
+mod cancellation;
+mod color;
 mod config;
+mod crash_report;
 mod data;
 mod disk;
+mod dither;
+mod fits;
+mod fmt;
+mod gpu;
 mod gui;
 mod image_utils;
 mod img_seq;
+mod logging;
 mod long_fg_task;
+mod normalization;
 mod projection;
 mod runner;
+mod stacking;
 mod subscriber;
 
 const VERSION_STRING: &'static str = include_str!(concat!(env!("OUT_DIR"), "/version"));
@@ -54,7 +67,7 @@ fn run_program() -> bool {
 
     match args::parse_command_line(std::env::args()) {
         Ok(config) => match config.mode {
-            args::Mode::GUI(mode) => run_gui(mode),
+            args::Mode::GUI(mode) => run_gui(mode, config.debug, config.gl_debug),
 
             args::Mode::PrintHelp => return true,
         },
@@ -68,12 +81,34 @@ fn run_program() -> bool {
     true
 }
 
-fn run_gui(mode: args::GUIMode) {
+fn run_gui(mode: args::GUIMode, debug: bool, gl_debug: bool) {
     const DEFAULT_FONT_SIZE: f32 = 15.0;
-    let (runner, worker_context) = runner::create_runner(DEFAULT_FONT_SIZE);
-    let mut worker_context_opt: Option<_> = Some(worker_context);
 
-    let mut base = Some(data::BaseProgramData{ config: config::Configuration::new() });
+    let config = config::Configuration::new();
+    let font_size = config::GuiConfig::font_size(&config)
+        .map(|size| size.max(gui::font_dialog::MIN_FONT_SIZE).min(gui::font_dialog::MAX_FONT_SIZE))
+        .unwrap_or(DEFAULT_FONT_SIZE);
+    gpu::gl_debug::set_requested(gl_debug || config::GuiConfig::gl_debug_output(&config).unwrap_or(false));
+    let (runner, worker_context) = runner::create_runner(font_size);
+
+    background::set_settings(background::Settings::from_config(&config));
+
+    // the worker thread is shared by successive modes
+    let (bg_task_sender, worker_limit) = projection::spawn_worker(worker_context);
+    let texture_limits = std::rc::Rc::new(gpu::texture_limits::TextureLimits::new(
+        glium::CapabilitiesSource::get_capabilities(runner.display()).max_texture_size as u32,
+        worker_limit
+    ));
+    let shortcuts = gui::shortcuts::Shortcuts::from_config(&config);
+    let format = config::GuiConfig::format_preferences(&config).unwrap_or_default();
+    let reduce_motion = config::GuiConfig::reduce_motion(&config)
+        .or_else(gui::motion::os_prefers_reduced_motion)
+        .unwrap_or(false);
+    gpu::render_check::set_max_consecutive_failures(
+        config::GuiConfig::max_render_failures(&config).unwrap_or(gpu::render_check::DEFAULT_MAX_CONSECUTIVE_FAILURES)
+    );
+
+    let mut base = Some(data::BaseProgramData{ config });
 
     let mut data: Option<data::ProgramData> = match mode {
         args::GUIMode::Selectable => None,
@@ -81,17 +116,30 @@ fn run_gui(mode: args::GUIMode) {
         args::GUIMode::Projection => Some(data::ProgramData::Projection(projection::ProgramData::new(
             base.take().unwrap(),
             runner.display(),
-            worker_context_opt.take().unwrap()
+            bg_task_sender.clone(),
+            texture_limits.clone()
         )))
     };
 
-    let mut gui_state = gui::GuiState::new(runner.platform().hidpi_factor(), DEFAULT_FONT_SIZE);
-
-    runner.main_loop(move |_, ui, display, renderer| {
-        gui::handle_gui(&mut base, &mut data, ui, &mut gui_state, renderer, display, &mut worker_context_opt)
+    let mut gui_state = gui::GuiState::new(runner.platform().hidpi_factor(), font_size, debug);
+    gui_state.shortcuts = shortcuts;
+    gui_state.format = format;
+    gui_state.set_reduce_motion(reduce_motion);
+
+    runner.main_loop(move |run, ui, display, renderer, frame_events| {
+        gui_state.update_app_focus(frame_events);
+        gpu::gl_debug::forward_to_log();
+        let font_size = gui::handle_gui(
+            &mut base, &mut data, ui, &mut gui_state, renderer, display, &bg_task_sender, &texture_limits
+        );
+        if gui::handle_exit(&mut base, &mut data, ui, &mut gui_state, frame_events.close_requested) { *run = false; }
+        gui::handle_config_saving(&mut base, &mut data, frame_events.focus_lost);
+        runner::FrameRequests{ font_size, window_title: gui::window_title_request(&data, &mut gui_state) }
     });
 }
 
 fn main() {
+    logging::init();
+    crash_report::install_hook(VERSION_STRING);
     std::process::exit(if run_program() { 0 } else { 1 });
 }
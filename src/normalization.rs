@@ -0,0 +1,117 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use cgmath::Point2;
+use ga_image::{Image, PixelFormat};
+
+/// Fraction of the disk radius used for brightness measurement (the limb is excluded, as it is the most sensitive
+/// to seeing and to disk position errors).
+const MEASURED_RADIUS_FRACTION: f32 = 0.8;
+
+/// Returns mean intensity (in [0; 255]) of an RGB8 image inside the planetary disk; `None` if the disk does not
+/// cover any pixels.
+pub fn disk_mean_brightness(image: &Image, disk_center: Point2<f32>, disk_diameter: f32) -> Option<f32> {
+    assert!(image.pixel_format() == PixelFormat::RGB8);
+
+    let radius = MEASURED_RADIUS_FRACTION * disk_diameter / 2.0;
+    let y_range = (disk_center.y - radius).floor().max(0.0) as u32
+        ..((disk_center.y + radius).ceil().max(0.0) as u32).min(image.height());
+    let x_range = (disk_center.x - radius).floor().max(0.0) as u32
+        ..((disk_center.x + radius).ceil().max(0.0) as u32).min(image.width());
+
+    let mut sum = 0.0f64;
+    let mut count = 0usize;
+    for y in y_range {
+        let line = image.line::<u8>(y);
+        let dy = y as f32 + 0.5 - disk_center.y;
+        for x in x_range.clone() {
+            let dx = x as f32 + 0.5 - disk_center.x;
+            if dx * dx + dy * dy <= radius * radius {
+                let pixel = &line[3 * x as usize..3 * x as usize + 3];
+                sum += (pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) as f64 / 3.0;
+                count += 1;
+            }
+        }
+    }
+
+    if count > 0 { Some((sum / count as f64) as f32) } else { None }
+}
+
+/// Returns per-frame gain factors which equalize frames' brightness to the median brightness of the sequence.
+/// Frames with non-positive brightness get gain 1.0.
+pub fn exposure_gains(brightness: &[f32]) -> Vec<f32> {
+    let mut sorted: Vec<f32> = brightness.iter().copied().filter(|b| *b > 0.0).collect();
+    if sorted.is_empty() { return vec![1.0; brightness.len()]; }
+
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let median = if sorted.len() % 2 == 1 {
+        sorted[sorted.len() / 2]
+    } else {
+        (sorted[sorted.len() / 2 - 1] + sorted[sorted.len() / 2]) / 2.0
+    };
+
+    brightness.iter().map(|b| if *b > 0.0 { median / b } else { 1.0 }).collect()
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn brightness_measured_inside_disk_only() {
+        // 10×10 image: bright disk of diameter 6 at (5, 5), the rest bright as well except for the corners
+        let mut pixels = vec![200u8; 10 * 10 * 3];
+        for (y, x) in [(0, 0), (0, 9), (9, 0), (9, 9)] {
+            for c in 0..3 { pixels[3 * (y * 10 + x) + c] = 0; }
+        }
+        pixels[3 * (5 * 10 + 5)] = 50; // pixel inside the disk; its mean intensity is (50 + 200 + 200) / 3
+        let image = Image::new_from_pixels(10, 10, None, PixelFormat::RGB8, None, pixels);
+
+        let brightness = disk_mean_brightness(&image, Point2{ x: 5.0, y: 5.0 }, 6.0).unwrap();
+        assert!(brightness < 200.0 && brightness > 190.0);
+
+        assert!(disk_mean_brightness(&image, Point2{ x: -100.0, y: -100.0 }, 6.0).is_none());
+    }
+
+    #[test]
+    fn gains_equalize_to_median() {
+        let brightness = [100.0, 110.0, 90.0];
+        let gains = exposure_gains(&brightness);
+        for (b, g) in brightness.iter().zip(gains.iter()) {
+            assert!((b * g - 100.0).abs() < 1.0e-4);
+        }
+    }
+
+    #[test]
+    fn cloud_dimmed_frame_does_not_affect_other_frames() {
+        let brightness = [100.0, 102.0, 98.0, 40.0, 101.0, 99.0, 100.0];
+        let gains = exposure_gains(&brightness);
+
+        assert!((gains[3] - 2.5).abs() < 1.0e-4);
+        for (idx, gain) in gains.iter().enumerate() {
+            if idx != 3 { assert!((gain - 1.0).abs() < 0.03); }
+        }
+    }
+
+    #[test]
+    fn invalid_brightness_gets_unit_gain() {
+        assert_eq!(vec![1.0, 1.0], exposure_gains(&[0.0, 0.0]));
+        assert_eq!(vec![1.0, 1.0], exposure_gains(&[50.0, 0.0]));
+        assert!(exposure_gains(&[]).is_empty());
+    }
+}
@@ -0,0 +1,307 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Assembly of color maps from three mono map sequences (e.g., exported one after another from recordings made
+//! through R, G, B filters).
+
+use crate::image_utils;
+use crate::projection::winjupos;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+pub const NUM_CHANNELS: usize = 3;
+
+pub const CHANNEL_NAMES: [&str; NUM_CHANNELS] = ["red", "green", "blue"];
+
+const MAP_EXTENSIONS: [&str; 4] = ["png", "tif", "tiff", "bmp"];
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChannelLevels {
+    pub gain: f32,
+    pub offset: f32
+}
+
+impl Default for ChannelLevels {
+    fn default() -> ChannelLevels { ChannelLevels{ gain: 1.0, offset: 0.0 } }
+}
+
+/// Sequence of maps assigned to a channel.
+pub struct MapSequence {
+    folder: PathBuf,
+    paths: Vec<PathBuf>,
+    /// Julian dates (UTC) of `paths`; present if all file names contain them (see `winjupos::file_name`).
+    times: Option<Vec<f64>>,
+    planet: Option<String>,
+    /// Size of the first map.
+    size: [u32; 2]
+}
+
+impl MapSequence {
+    /// Collects the maps (sorted by file name) from `folder`.
+    pub fn from_folder(folder: &Path) -> Result<MapSequence, Box<dyn Error>> {
+        let mut paths = vec![];
+        for entry in std::fs::read_dir(folder)? {
+            let path = entry?.path();
+            let is_map = path.extension()
+                .and_then(|ext| ext.to_str())
+                .map_or(false, |ext| MAP_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+            if is_map && path.is_file() { paths.push(path); }
+        }
+        if paths.is_empty() { return Err("no maps found".into()); }
+        paths.sort();
+
+        let parsed: Option<Vec<(f64, String)>> = paths.iter()
+            .map(|path| path.file_name().and_then(|name| name.to_str()).and_then(winjupos::parse_file_name))
+            .collect();
+        let (times, planet) = match parsed {
+            Some(parsed) => (Some(parsed.iter().map(|(time, _)| *time).collect()), Some(parsed[0].1.clone())),
+            None => (None, None)
+        };
+
+        let (width, height, _) = image_utils::get_metadata(&paths[0])?;
+
+        Ok(MapSequence{ folder: folder.to_path_buf(), paths, times, planet, size: [width, height] })
+    }
+
+    pub fn folder(&self) -> &Path { &self.folder }
+
+    pub fn paths(&self) -> &[PathBuf] { &self.paths }
+
+    pub fn times(&self) -> Option<&[f64]> { self.times.as_deref() }
+
+    pub fn planet(&self) -> Option<&str> { self.planet.as_deref() }
+
+    pub fn size(&self) -> [u32; 2] { self.size }
+
+    pub fn frames(&self) -> ChannelFrames { ChannelFrames{ num_frames: self.paths.len(), times: self.times() } }
+}
+
+#[derive(Copy, Clone)]
+pub struct ChannelFrames<'a> {
+    pub num_frames: usize,
+    /// Julian dates of the frames (ascending).
+    pub times: Option<&'a [f64]>
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Pairing {
+    /// Frame index of each channel (0 for unassigned channels), for each composite frame.
+    pub frames: Vec<[usize; NUM_CHANNELS]>,
+    /// Channel whose frames determine the composite frames (the one with the most frames).
+    pub reference: usize,
+    /// Largest time difference (in seconds) between paired frames; `None` if the frames are paired by index.
+    pub max_time_difference: Option<f64>
+}
+
+/// Pairs frames of the assigned channels. If all of them have frame times, each frame of the reference channel
+/// is paired with the nearest-time frames of the others; otherwise, frame indices are mapped proportionally.
+pub fn pair_frames(channels: &[Option<ChannelFrames>; NUM_CHANNELS]) -> Pairing {
+    let reference = channels.iter().enumerate()
+        .filter_map(|(i, channel)| channel.map(|channel| (i, channel.num_frames)))
+        .fold(None, |best: Option<(usize, usize)>, (i, n)| match best {
+            Some((_, best_n)) if best_n >= n => best,
+            _ => Some((i, n))
+        });
+    let (reference, num_frames) = match reference {
+        Some(reference) => reference,
+        None => return Pairing{ frames: vec![], reference: 0, max_time_difference: None }
+    };
+
+    let all_timed = channels.iter().flatten().all(|channel| channel.times.is_some());
+    let mut max_time_difference = if all_timed { Some(0.0_f64) } else { None };
+
+    let frames = (0..num_frames).map(|idx| {
+        let mut paired = [0; NUM_CHANNELS];
+        for (frame_idx, channel) in paired.iter_mut().zip(channels.iter()) {
+            let channel = match channel { Some(channel) => channel, None => continue };
+            if all_timed {
+                let time = channels[reference].unwrap().times.unwrap()[idx];
+                let times = channel.times.unwrap();
+                *frame_idx = nearest(times, time);
+                let difference = (times[*frame_idx] - time).abs() * SECONDS_PER_DAY;
+                max_time_difference = max_time_difference.map(|max| max.max(difference));
+            } else {
+                *frame_idx = proportional_index(idx, num_frames, channel.num_frames);
+            }
+        }
+        paired
+    }).collect();
+
+    Pairing{ frames, reference, max_time_difference }
+}
+
+/// Returns the index of the element of `times` (ascending, non-empty) nearest to `time`.
+fn nearest(times: &[f64], time: f64) -> usize {
+    let idx = times.partition_point(|&t| t < time);
+    if idx == 0 {
+        0
+    } else if idx == times.len() || time - times[idx - 1] <= times[idx] - time {
+        idx - 1
+    } else {
+        idx
+    }
+}
+
+/// Maps frame `idx` of a sequence of `num_ref_frames` to a sequence of `num_frames` spanning the same time.
+fn proportional_index(idx: usize, num_ref_frames: usize, num_frames: usize) -> usize {
+    if num_ref_frames <= 1 {
+        0
+    } else {
+        (idx as f64 * (num_frames - 1) as f64 / (num_ref_frames - 1) as f64).round() as usize
+    }
+}
+
+/// Returns the size of composite maps: that of the widest channel map (the others are resampled to it).
+pub fn composite_size(sizes: &[Option<[u32; 2]>; NUM_CHANNELS]) -> Option<[u32; 2]> {
+    sizes.iter().flatten().fold(None, |widest: Option<[u32; 2]>, &size| match widest {
+        Some(widest) if widest[0] >= size[0] => Some(widest),
+        _ => Some(size)
+    })
+}
+
+/// Returns the horizontal and vertical scale factors of resampling a map of `size` to `target`.
+pub fn resampling_factors(size: [u32; 2], target: [u32; 2]) -> [f64; 2] {
+    [target[0] as f64 / size[0] as f64, target[1] as f64 / size[1] as f64]
+}
+
+/// Returns the file name of exported composite frame `idx`; `time` and `planet` (if known) give a WinJUPOS-style
+/// name instead.
+pub fn output_file_name(idx: usize, time: Option<f64>, planet: Option<&str>) -> String {
+    match (time, planet) {
+        (Some(time), Some(planet)) => winjupos::file_name(time, planet),
+        _ => format!("composite_{:05}.png", idx + 1)
+    }
+}
+
+/// Returns file names of all exported composite frames (see `output_file_name`). WinJUPOS-style names have a resolution
+/// of 6 s; frames which would get the same name as an earlier one are given a numeric suffix instead.
+pub fn output_file_names(times: &[Option<f64>], planet: Option<&str>) -> Vec<String> {
+    let mut counts = std::collections::HashMap::<String, usize>::new();
+    times.iter().enumerate().map(|(idx, time)| {
+        let name = output_file_name(idx, *time, planet);
+        let count = counts.entry(name.clone()).or_insert(0);
+        *count += 1;
+        if *count == 1 {
+            name
+        } else {
+            let path = Path::new(&name);
+            format!(
+                "{}_{}.{}",
+                path.file_stem().unwrap().to_string_lossy(),
+                count,
+                path.extension().unwrap().to_string_lossy()
+            )
+        }
+    }).collect()
+}
+
+mod tests {
+    use super::*;
+
+    const MINUTE: f64 = 1.0 / 1440.0;
+
+    #[test]
+    fn frames_are_paired_by_nearest_time() {
+        let red = [0.0, 2.0 * MINUTE, 4.0 * MINUTE, 6.0 * MINUTE];
+        let green = [0.4 * MINUTE, 3.3 * MINUTE];
+        let blue = [-1.0 * MINUTE, 1.2 * MINUTE, 5.0 * MINUTE];
+
+        let pairing = pair_frames(&[
+            Some(ChannelFrames{ num_frames: red.len(), times: Some(&red) }),
+            Some(ChannelFrames{ num_frames: green.len(), times: Some(&green) }),
+            Some(ChannelFrames{ num_frames: blue.len(), times: Some(&blue) })
+        ]);
+
+        assert_eq!(0, pairing.reference);
+        assert_eq!(vec![[0, 0, 1], [1, 0, 1], [2, 1, 2], [3, 1, 2]], pairing.frames);
+        // red frame 3 (6 min) vs. green frame 1 (3.3 min)
+        assert!((pairing.max_time_difference.unwrap() - 162.0).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn frames_are_paired_by_index_without_times() {
+        let red = [0.0, 1.0];
+        let pairing = pair_frames(&[
+            Some(ChannelFrames{ num_frames: 2, times: Some(&red) }),
+            Some(ChannelFrames{ num_frames: 5, times: None }),
+            None
+        ]);
+
+        assert_eq!(1, pairing.reference);
+        assert_eq!(None, pairing.max_time_difference);
+        assert_eq!(vec![[0, 0, 0], [0, 1, 0], [1, 2, 0], [1, 3, 0], [1, 4, 0]], pairing.frames);
+    }
+
+    #[test]
+    fn single_frame_channel_is_paired_with_all_frames() {
+        let pairing = pair_frames(&[
+            Some(ChannelFrames{ num_frames: 1, times: None }),
+            None,
+            Some(ChannelFrames{ num_frames: 3, times: None })
+        ]);
+
+        assert_eq!(vec![[0, 0, 0], [0, 0, 1], [0, 0, 2]], pairing.frames);
+    }
+
+    #[test]
+    fn no_channels_give_no_frames() {
+        let pairing = pair_frames(&[None, None, None]);
+        assert!(pairing.frames.is_empty());
+    }
+
+    #[test]
+    fn maps_are_resampled_to_the_widest() {
+        let target = composite_size(&[Some([800, 400]), Some([1200, 600]), Some([1000, 500])]).unwrap();
+        assert_eq!([1200, 600], target);
+        assert_eq!([1.5, 1.5], resampling_factors([800, 400], target));
+        assert_eq!([1.0, 1.0], resampling_factors([1200, 600], target));
+
+        // for equal widths, the first channel's map wins
+        assert_eq!(Some([1000, 400]), composite_size(&[None, Some([1000, 400]), Some([1000, 500])]));
+        assert_eq!(None, composite_size(&[None, None, None]));
+    }
+
+    #[test]
+    fn output_names_follow_winjupos_convention_if_times_are_known() {
+        let jd = crate::projection::ephem::julian_date(2022, 8, 15, 1, 30, 24.0);
+        assert_eq!("2022-08-15-0130_4-Jupiter.png", output_file_name(0, Some(jd), Some("Jupiter")));
+        assert_eq!("composite_00003.png", output_file_name(2, None, Some("Jupiter")));
+    }
+
+    #[test]
+    fn frames_closer_than_name_resolution_get_distinct_names() {
+        let jd = crate::projection::ephem::julian_date(2022, 8, 15, 1, 30, 24.0);
+        let second = 1.0 / 86400.0;
+        assert_eq!(
+            vec![
+                "2022-08-15-0130_4-Jupiter.png",
+                "2022-08-15-0130_4-Jupiter_2.png",
+                "2022-08-15-0130_5-Jupiter.png",
+                "2022-08-15-0130_4-Jupiter_3.png"
+            ],
+            output_file_names(&[Some(jd), Some(jd + 2.0 * second), Some(jd + 6.0 * second), Some(jd)], Some("Jupiter"))
+        );
+        assert_eq!(
+            vec!["composite_00001.png", "composite_00002.png"],
+            output_file_names(&[None, None], Some("Jupiter"))
+        );
+    }
+}
@@ -0,0 +1,588 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! View combining three mono map sequences into color maps.
+
+use crate::config::{Configuration, ProjectionConfig};
+use crate::data;
+use crate::gui;
+use crate::gui::draw_buffer::Sampling;
+use crate::gui::DrawBuffer;
+use crate::gui::long_task_dialog::{LongTaskDialog, ProgressMsg};
+use crate::image_utils;
+use crate::logging;
+use crate::long_fg_task::LongForegroundTask;
+use crate::projection::composite::{self, ChannelLevels, MapSequence, NUM_CHANNELS, Pairing};
+use crate::projection::map_save;
+use glium::{texture::Texture2d, uniform, Surface};
+use std::cell::RefCell;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+const MAX_GAIN: f32 = 4.0;
+
+const MAX_OFFSET: f32 = 0.5;
+
+#[derive(Default)]
+struct Channel {
+    sequence: Option<MapSequence>,
+    levels: ChannelLevels,
+    /// Loaded frame of `sequence` and its texture.
+    texture: Option<(usize, Rc<Texture2d>)>
+}
+
+pub struct CompositeView {
+    unique_id: u32,
+    channels: [Channel; NUM_CHANNELS],
+    pairing: Pairing,
+    /// Shown composite frame.
+    frame_idx: usize,
+    draw_buf: DrawBuffer,
+    gl_prog: Rc<glium::Program>,
+    unit_quad: Rc<glium::VertexBuffer<data::Vertex2>>,
+    /// Bound in place of unassigned channels.
+    blank_texture: Rc<Texture2d>,
+    display: glium::Display,
+    /// Error of loading the shown frame.
+    load_error: Option<String>,
+    /// Destination of "Save current map" awaiting confirmation of overwriting.
+    pending_map_path: Option<PathBuf>
+}
+
+impl CompositeView {
+    pub fn new(
+        unique_id: u32,
+        gl_objects: &crate::projection::data::OpenGlObjects,
+        display: &glium::Display,
+        renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>
+    ) -> CompositeView {
+        let mut draw_buf = DrawBuffer::new(
+            Sampling::Single,
+            &gl_objects.texture_copy_single,
+            &gl_objects.texture_copy_multi,
+            &gl_objects.unit_quad,
+            display,
+            renderer
+        );
+        draw_buf.register(&format!("Composite view {}", unique_id), "view");
+
+        let blank_texture = Rc::new(Texture2d::empty_with_format(
+            display,
+            glium::texture::UncompressedFloatFormat::U8U8U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            1,
+            1
+        ).unwrap());
+
+        let view = CompositeView{
+            unique_id,
+            channels: Default::default(),
+            pairing: composite::pair_frames(&[None, None, None]),
+            frame_idx: 0,
+            draw_buf,
+            gl_prog: Rc::clone(&gl_objects.composite),
+            unit_quad: Rc::clone(&gl_objects.unit_quad),
+            blank_texture,
+            display: display.clone(),
+            load_error: None,
+            pending_map_path: None
+        };
+
+        if let Err(e) = view.render() { logging::log_error!("Error rendering composite view: {}.", e); }
+
+        view
+    }
+
+    pub fn id(&self) -> u32 { self.unique_id }
+
+    pub fn num_frames(&self) -> usize { self.pairing.frames.len() }
+
+    pub fn frame_idx(&self) -> usize { self.frame_idx }
+
+    /// Size of the composite maps.
+    pub fn size(&self) -> Option<[u32; 2]> {
+        let mut sizes = [None; NUM_CHANNELS];
+        for (size, channel) in sizes.iter_mut().zip(self.channels.iter()) {
+            *size = channel.sequence.as_ref().map(|sequence| sequence.size());
+        }
+        composite::composite_size(&sizes)
+    }
+
+    /// Julian date (UTC) of composite frame `idx`, if the frames have been paired by time.
+    pub fn frame_time(&self, idx: usize) -> Option<f64> {
+        if self.pairing.max_time_difference.is_none() { return None; }
+        let reference = &self.channels[self.pairing.reference];
+        reference.sequence.as_ref()?.times().map(|times| times[self.pairing.frames[idx][self.pairing.reference]])
+    }
+
+    /// Planet named in the file names of the reference channel.
+    pub fn planet(&self) -> Option<&str> {
+        self.channels[self.pairing.reference].sequence.as_ref().and_then(|sequence| sequence.planet())
+    }
+
+    /// Assigns `sequence` to `channel` (or clears it) and shows the first composite frame.
+    pub fn set_channel(&mut self, channel: usize, sequence: Option<MapSequence>) {
+        self.channels[channel] = Channel{ sequence, levels: self.channels[channel].levels, texture: None };
+
+        let mut frames = [None; NUM_CHANNELS];
+        for (channel_frames, channel) in frames.iter_mut().zip(self.channels.iter()) {
+            *channel_frames = channel.sequence.as_ref().map(|sequence| sequence.frames());
+        }
+        self.pairing = composite::pair_frames(&frames);
+
+        if let Some([width, height]) = self.size() { self.draw_buf.update_size(width, height); }
+        self.show_frame(0);
+    }
+
+    pub fn set_levels(&mut self, channel: usize, levels: ChannelLevels) {
+        self.channels[channel].levels = levels;
+        if let Err(e) = self.render() { logging::log_error!("Error rendering composite view: {}.", e); }
+    }
+
+    /// Loads the channel maps of composite frame `idx` and renders them; on failure, the error is shown
+    /// in the view.
+    pub fn show_frame(&mut self, idx: usize) {
+        self.frame_idx = idx.min(self.num_frames().max(1) - 1);
+        self.load_error = self.load_frame().err().map(|e| e.to_string());
+        if let Err(e) = self.render() { logging::log_error!("Error rendering composite view: {}.", e); }
+    }
+
+    fn load_frame(&mut self) -> Result<(), Box<dyn Error>> {
+        let paired = match self.pairing.frames.get(self.frame_idx) {
+            Some(paired) => *paired,
+            None => return Ok(())
+        };
+
+        for (channel, &frame_idx) in self.channels.iter_mut().zip(paired.iter()) {
+            let sequence = match &channel.sequence { Some(sequence) => sequence, None => continue };
+            if channel.texture.as_ref().map_or(false, |(loaded_idx, _)| *loaded_idx == frame_idx) { continue; }
+
+            channel.texture = None;
+            let path = &sequence.paths()[frame_idx];
+            let texture = image_utils::load_image(path)
+                .and_then(|image| data::create_texture_from_image(&image, &self.display))
+                .map_err(|e| format!("could not load {}: {}", path.to_string_lossy(), e))?;
+            channel.texture = Some((frame_idx, Rc::new(texture)));
+        }
+
+        Ok(())
+    }
+
+    fn render(&self) -> Result<(), glium::DrawError> {
+        let texture = |channel: &Channel| channel.texture.as_ref().map_or(
+            Rc::clone(&self.blank_texture), |(_, texture)| Rc::clone(texture)
+        );
+        let [red_map, green_map, blue_map] = [
+            texture(&self.channels[0]), texture(&self.channels[1]), texture(&self.channels[2])
+        ];
+        let levels = |f: fn(&ChannelLevels) -> f32| {
+            [f(&self.channels[0].levels), f(&self.channels[1].levels), f(&self.channels[2].levels)]
+        };
+        let assigned = |channel: &Channel| if channel.texture.is_some() { 1.0f32 } else { 0.0 };
+
+        let uniforms = uniform! {
+            red_map: red_map.sampled(),
+            green_map: green_map.sampled(),
+            blue_map: blue_map.sampled(),
+            gains: levels(|levels| levels.gain),
+            offsets: levels(|levels| levels.offset),
+            assigned: [assigned(&self.channels[0]), assigned(&self.channels[1]), assigned(&self.channels[2])]
+        };
+
+        let mut target = self.draw_buf.frame_buf();
+        target.clear_color(0.0, 0.0, 0.0, 1.0);
+        target.draw(
+            &*self.unit_quad,
+            &glium::index::NoIndices(glium::index::PrimitiveType::TriangleFan),
+            &self.gl_prog,
+            &uniforms,
+            &Default::default()
+        )?;
+
+        self.draw_buf.update_storage_buf()
+    }
+
+    /// Returns the shown composite map.
+    fn current_map(&self) -> Result<ga_image::Image, Box<dyn Error>> {
+        if let Some(error) = &self.load_error { return Err(error.clone().into()); }
+        Ok(image_utils::image_from_texture(self.draw_buf.storage_buf()))
+    }
+}
+
+/// Exports all composite frames, one per GUI frame.
+pub struct CompositeExportTask {
+    view: Rc<RefCell<CompositeView>>,
+    output_dir: PathBuf,
+    /// Names of all exported frames (unique even if WinJUPOS-style names of some frames would be the same).
+    file_names: Vec<String>,
+    next_frame: usize,
+    /// Frame shown before the export; shown again afterwards.
+    shown_frame: usize,
+    /// Dropped when the task ends (which ends the progress dialog).
+    progress_sender: Option<crossbeam::channel::Sender<ProgressMsg>>,
+    end_message: Option<(String, String)>
+}
+
+impl CompositeExportTask {
+    pub fn new(
+        view: &Rc<RefCell<CompositeView>>,
+        output_dir: &Path
+    ) -> (CompositeExportTask, crossbeam::channel::Receiver<ProgressMsg>) {
+        let (progress_sender, progress_receiver) = crossbeam::channel::bounded(1);
+        let shown_frame = view.borrow().frame_idx();
+        let (file_names, num_suffixed) = {
+            let view = view.borrow();
+            let times: Vec<Option<f64>> = (0..view.num_frames()).map(|idx| view.frame_time(idx)).collect();
+            let num_distinct = times.iter().enumerate()
+                .map(|(idx, time)| composite::output_file_name(idx, *time, view.planet()))
+                .collect::<std::collections::HashSet<_>>()
+                .len();
+            (composite::output_file_names(&times, view.planet()), times.len() - num_distinct)
+        };
+        if num_suffixed > 0 {
+            logging::log_warning!(
+                "{} composite frames are less than 6 s apart from another frame; a suffix was added to their names.",
+                num_suffixed
+            );
+        }
+
+        (CompositeExportTask{
+            view: Rc::clone(view),
+            output_dir: output_dir.to_path_buf(),
+            file_names,
+            next_frame: 0,
+            shown_frame,
+            progress_sender: Some(progress_sender),
+            end_message: None
+        }, progress_receiver)
+    }
+
+    fn export_frame(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut view = self.view.borrow_mut();
+        view.show_frame(self.next_frame);
+        let path = self.output_dir.join(&self.file_names[self.next_frame]);
+        map_save::write_map(&view.current_map()?, &path)
+            .map_err(|e| format!("could not save {}: {}", path.to_string_lossy(), e))?;
+        self.next_frame += 1;
+
+        Ok(())
+    }
+
+    fn end(&mut self, message: Option<(&str, String)>) {
+        self.view.borrow_mut().show_frame(self.shown_frame);
+        self.progress_sender = None;
+        self.end_message = message.map(|(title, text)| (title.to_string(), text));
+    }
+}
+
+impl LongForegroundTask for CompositeExportTask {
+    fn step(&mut self) -> bool {
+        if self.progress_sender.is_none() { return false; }
+
+        let num_frames = self.view.borrow().num_frames();
+        if self.next_frame == num_frames {
+            logging::log_info!("Exported {} composite maps to {}.", num_frames, self.output_dir.to_string_lossy());
+            self.end(None);
+            return false;
+        }
+
+        match self.export_frame() {
+            Ok(()) => {
+                let _ = self.progress_sender.as_ref().unwrap().try_send(ProgressMsg::new(
+                    format!("Exported frame {} of {}.", self.next_frame, num_frames),
+                    self.next_frame as f32 / num_frames as f32
+                ));
+                true
+            },
+
+            Err(e) => {
+                logging::log_error!("Composite export failed: {}.", e);
+                self.end(Some(("Error", format!("Composite export failed: {}.", e))));
+                false
+            }
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.end(None);
+    }
+
+    fn end_message(&self) -> Option<(String, String)> { self.end_message.clone() }
+}
+
+/// Returns `false` if the view has been closed.
+pub fn handle_composite_view(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
+    view_rc: &Rc<RefCell<CompositeView>>,
+    long_task_dialog: &RefCell<Option<LongTaskDialog>>,
+    long_fg_task: &RefCell<Option<Box<dyn LongForegroundTask>>>
+) -> bool {
+    let mut opened = true;
+    let mut export_clicked = false;
+    let mut save_map_clicked = false;
+    let task_in_progress = long_task_dialog.borrow().is_some();
+
+    let mut view = view_rc.borrow_mut();
+    let num_frames = view.num_frames();
+
+    imgui::Window::new(ui, &format!("Composite###composite-view-{}", view.id()))
+        .size([640.0, 640.0], imgui::Condition::FirstUseEver)
+        .opened(&mut opened)
+        .build(|| {
+            let token = ui.begin_disabled(task_in_progress || num_frames == 0);
+            if ui.button("Export...") { export_clicked = true; }
+            gui::tooltip(ui, "Export all composite frames to a folder.");
+            ui.same_line();
+            if ui.button("Save current map...") { save_map_clicked = true; }
+            token.end();
+            gui::tooltip(ui, "Save the displayed composite map to a single file.");
+
+            for channel in 0..NUM_CHANNELS {
+                handle_channel_controls(ui, gui_state, config, &mut view, channel);
+            }
+            // the message box has been opened within this window
+            gui::handle_message_box(ui, gui_state, config);
+
+            match view.pairing.max_time_difference {
+                Some(difference) => ui.text(format!(
+                    "{} frames, paired by time (max. difference: {:.0} s)", num_frames, difference
+                )),
+                None if num_frames > 0 => ui.text(format!("{} frames, paired by index", num_frames)),
+                None => ui.text("Select map folders (e.g., exported sequences) for the channels.")
+            }
+            if let Some(size) = view.size() {
+                ui.same_line();
+                ui.text(format!("; {}×{}", size[0], size[1]));
+            }
+
+            if num_frames > 1 {
+                let mut value = view.frame_idx() as i32 + 1;
+                if imgui::Slider::new(format!("frame##composite-frame-{}", view.id()), 1, num_frames as i32)
+                    .build(ui, &mut value)
+                {
+                    view.show_frame(value as usize - 1);
+                }
+            }
+
+            if let Some(error) = &view.load_error { ui.text_colored([1.0, 0.3, 0.3, 1.0], error); }
+
+            ui.separator();
+            if let Some(size) = view.size() {
+                let avail = ui.content_region_avail();
+                let scale = (avail[0] / size[0] as f32).min(avail[1] / size[1] as f32).max(0.0);
+                imgui::Image::new(view.draw_buf.id(), [scale * size[0] as f32, scale * size[1] as f32]).build(ui);
+            }
+        });
+
+    drop(view);
+
+    if save_map_clicked { handle_save_map(ui, gui_state, config, view_rc, long_task_dialog, long_fg_task); }
+    handle_map_overwrite_dialog(ui, gui_state, config, view_rc, long_task_dialog, long_fg_task);
+
+    if export_clicked { handle_export(ui, gui_state, config, view_rc, long_task_dialog, long_fg_task); }
+
+    opened
+}
+
+fn handle_channel_controls(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
+    view: &mut CompositeView,
+    channel: usize
+) {
+    let id = view.id();
+    let name = composite::CHANNEL_NAMES[channel];
+
+    ui.separator();
+    ui.text(format!("{}:", name));
+    ui.same_line();
+    if ui.button(format!("Select folder...##composite-{}-{}", name, id)) {
+        let location = gui::file_dialog::initial_location(config.projection_export_path().as_deref());
+        let folder = gui::file_dialog::checked(
+            ui,
+            gui_state,
+            native_dialog::FileDialog::new().set_location(&location).show_open_single_dir()
+        ).flatten();
+
+        if let Some(folder) = folder {
+            match MapSequence::from_folder(&folder) {
+                Ok(sequence) => view.set_channel(channel, Some(sequence)),
+                Err(e) => {
+                    gui_state.message_box = Some(gui::MessageBox{
+                        title: "Error".to_string(),
+                        message: format!("Could not use {}: {}.", folder.to_string_lossy(), e)
+                    });
+                    ui.open_popup("Error");
+                }
+            }
+        }
+    }
+
+    let description = view.channels[channel].sequence.as_ref().map(|sequence| format!(
+        "{} ({} maps, {}×{})",
+        sequence.folder().to_string_lossy(), sequence.paths().len(), sequence.size()[0], sequence.size()[1]
+    ));
+    if let Some(description) = description {
+        ui.same_line();
+        if ui.button(format!("Clear##composite-{}-{}", name, id)) { view.set_channel(channel, None); }
+        ui.same_line();
+        ui.text(description);
+    }
+
+    let mut levels = view.channels[channel].levels;
+    let mut changed = false;
+    let w = ui.push_item_width(ui.calc_text_size("M")[0] * 12.0);
+    changed |= imgui::Slider::new(format!("gain##composite-{}-{}", name, id), 0.0, MAX_GAIN)
+        .display_format("%.2f")
+        .build(ui, &mut levels.gain);
+    ui.same_line();
+    changed |= imgui::Slider::new(format!("offset##composite-{}-{}", name, id), -MAX_OFFSET, MAX_OFFSET)
+        .display_format("%.3f")
+        .build(ui, &mut levels.offset);
+    w.end();
+    if changed { view.set_levels(channel, levels); }
+}
+
+fn handle_save_map(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
+    view: &Rc<RefCell<CompositeView>>,
+    long_task_dialog: &RefCell<Option<LongTaskDialog>>,
+    long_fg_task: &RefCell<Option<Box<dyn LongForegroundTask>>>
+) {
+    let location = gui::file_dialog::initial_location(config.single_map_export_path().as_deref());
+    let file_name = {
+        let view = view.borrow();
+        composite::output_file_name(view.frame_idx(), view.frame_time(view.frame_idx()), view.planet())
+    };
+    let path = gui::file_dialog::checked(ui, gui_state, native_dialog::FileDialog::new()
+        .set_location(&location)
+        .set_filename(&file_name)
+        .add_filter("PNG", &["png"])
+        .add_filter("TIFF", &["tif", "tiff"])
+        .show_save_single_file()
+    ).flatten();
+    let path = match path {
+        Some(path) => map_save::with_image_extension(path),
+        None => return
+    };
+
+    if let Some(dir) = path.parent() { config.set_single_map_export_path(&dir.to_string_lossy()); }
+
+    if path.exists() {
+        view.borrow_mut().pending_map_path = Some(path);
+        ui.open_popup(map_save::OVERWRITE_TITLE);
+    } else {
+        save_map(ui, gui_state, &view.borrow(), &path, long_task_dialog, long_fg_task);
+    }
+}
+
+fn handle_map_overwrite_dialog(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
+    view: &Rc<RefCell<CompositeView>>,
+    long_task_dialog: &RefCell<Option<LongTaskDialog>>,
+    long_fg_task: &RefCell<Option<Box<dyn LongForegroundTask>>>
+) {
+    let path = match &view.borrow().pending_map_path {
+        Some(path) => path.clone(),
+        None => return
+    };
+
+    // `Some(overwrite)` once the user has decided
+    let mut decision = None;
+    gui::modal::modal(ui, config, map_save::OVERWRITE_TITLE, gui::modal::KeyBindings::all(), |key_action, _| {
+        ui.text(format!("{} already exists. Overwrite it?", path.to_string_lossy()));
+        ui.separator();
+
+        if gui::modal::default_button(ui, "Overwrite") || key_action == gui::modal::KeyAction::Accept {
+            decision = Some(true);
+            ui.close_current_popup();
+        }
+        ui.same_line();
+        if ui.button("Cancel") || key_action == gui::modal::KeyAction::Cancel {
+            decision = Some(false);
+            ui.close_current_popup();
+        }
+    });
+
+    if let Some(overwrite) = decision {
+        view.borrow_mut().pending_map_path = None;
+        if overwrite { save_map(ui, gui_state, &view.borrow(), &path, long_task_dialog, long_fg_task); }
+    }
+}
+
+/// Saves the shown composite map as `path`; a large file is written by a long foreground task.
+fn save_map(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    view: &CompositeView,
+    path: &Path,
+    long_task_dialog: &RefCell<Option<LongTaskDialog>>,
+    long_fg_task: &RefCell<Option<Box<dyn LongForegroundTask>>>
+) {
+    let result = view.current_map().and_then(|image| map_save::save_map(&image, path));
+
+    match result {
+        Ok(None) => logging::log_info!("Saved {}.", path.to_string_lossy()),
+
+        Ok(Some((task, progress_receiver))) => {
+            *long_fg_task.borrow_mut() = Some(Box::new(task));
+            *long_task_dialog.borrow_mut() =
+                Some(LongTaskDialog::new("Saving map".to_string(), "".to_string(), progress_receiver));
+        },
+
+        Err(e) => {
+            gui_state.message_box = Some(gui::MessageBox{
+                title: "Error".to_string(),
+                message: format!("Could not save {}: {}.", path.to_string_lossy(), e)
+            });
+            ui.open_popup("Error");
+        }
+    }
+}
+
+fn handle_export(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
+    view: &Rc<RefCell<CompositeView>>,
+    long_task_dialog: &RefCell<Option<LongTaskDialog>>,
+    long_fg_task: &RefCell<Option<Box<dyn LongForegroundTask>>>
+) {
+    let location = gui::file_dialog::initial_location(config.projection_export_path().as_deref());
+    let output_dir = gui::file_dialog::checked(
+        ui,
+        gui_state,
+        native_dialog::FileDialog::new().set_location(&location).show_open_single_dir()
+    ).flatten();
+
+    if let Some(output_dir) = output_dir {
+        let (task, progress_receiver) = CompositeExportTask::new(view, &output_dir);
+        *long_fg_task.borrow_mut() = Some(Box::new(task));
+        *long_task_dialog.borrow_mut() =
+            Some(LongTaskDialog::new("Exporting composite".to_string(), "".to_string(), progress_receiver));
+    }
+}
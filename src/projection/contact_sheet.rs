@@ -0,0 +1,231 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Overview image of an exported sequence: a grid of numbered thumbnails.
+
+use crate::image_utils;
+use std::error::Error;
+use std::path::Path;
+
+pub const FILE_NAME: &str = "contact_sheet.png";
+
+const THUMBNAIL_WIDTH: u32 = 256;
+
+/// Space between thumbnails (in pixels).
+const SPACING: u32 = 4;
+
+/// Size of a label's glyph pixel.
+const LABEL_SCALE: u32 = 2;
+
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
+/// Digits 0-9; each row is a 3-bit mask (MSB = left column).
+const DIGIT_GLYPHS: [[u8; GLYPH_HEIGHT as usize]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111]
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GridLayout {
+    pub columns: u32,
+    pub rows: u32
+}
+
+/// Returns grid layout of `num_thumbnails` thumbnails of the given size making the sheet as close to square
+/// as possible.
+pub fn grid_layout(num_thumbnails: usize, thumbnail_width: u32, thumbnail_height: u32) -> GridLayout {
+    if num_thumbnails == 0 { return GridLayout{ columns: 0, rows: 0 }; }
+
+    // columns · w ≈ rows · h, rows ≈ n / columns
+    let wh_ratio = thumbnail_width as f64 / thumbnail_height as f64;
+    let columns = ((num_thumbnails as f64 / wh_ratio).sqrt().round() as u32).max(1).min(num_thumbnails as u32);
+    let rows = (num_thumbnails as u32 + columns - 1) / columns;
+
+    GridLayout{ columns, rows }
+}
+
+/// Returns position (in pixels) of the top-left corner of thumbnail `idx` on the sheet.
+pub fn thumbnail_position(layout: GridLayout, idx: usize, thumbnail_width: u32, thumbnail_height: u32) -> [u32; 2] {
+    let column = idx as u32 % layout.columns;
+    let row = idx as u32 / layout.columns;
+
+    [
+        SPACING + column * (thumbnail_width + SPACING),
+        SPACING + row * (thumbnail_height + SPACING)
+    ]
+}
+
+/// Collects downscaled copies of exported frames.
+pub struct ContactSheet {
+    thumbnails: Vec<image::RgbImage>
+}
+
+impl ContactSheet {
+    pub fn new() -> ContactSheet {
+        ContactSheet{ thumbnails: vec![] }
+    }
+
+    /// Adds a downscaled copy of `frame` (RGB8); the frame itself is not kept.
+    pub fn add(&mut self, frame: &ga_image::Image) {
+        assert!(frame.pixel_format() == ga_image::PixelFormat::RGB8);
+
+        let frame = image::RgbImage::from_raw(frame.width(), frame.height(), frame.raw_pixels().to_vec()).unwrap();
+        let width = THUMBNAIL_WIDTH.min(frame.width());
+        let height = ((frame.height() as u64 * width as u64 / frame.width() as u64) as u32).max(1);
+
+        self.thumbnails.push(image::imageops::thumbnail(&frame, width, height));
+    }
+
+    /// Composes the thumbnails (labeled with frame numbers) into a single image.
+    pub fn compose(&self) -> Option<image::RgbImage> {
+        let first = self.thumbnails.first()?;
+        let (thumb_w, thumb_h) = first.dimensions();
+
+        let layout = grid_layout(self.thumbnails.len(), thumb_w, thumb_h);
+        let mut sheet = image::RgbImage::new(
+            SPACING + layout.columns * (thumb_w + SPACING),
+            SPACING + layout.rows * (thumb_h + SPACING)
+        );
+
+        for (idx, thumbnail) in self.thumbnails.iter().enumerate() {
+            let [x, y] = thumbnail_position(layout, idx, thumb_w, thumb_h);
+            image::imageops::replace(&mut sheet, thumbnail, x as i64, y as i64);
+            draw_label(&mut sheet, &format!("{}", idx + 1), x + SPACING, y + SPACING);
+        }
+
+        Some(sheet)
+    }
+
+    /// Saves the sheet as `FILE_NAME` in `output_dir` (does nothing if there are no thumbnails); see
+    /// `image_utils::encode_png_reproducible` for `reproducible`.
+    pub fn save(&self, output_dir: &Path, reproducible: bool) -> Result<(), Box<dyn Error>> {
+        if let Some(sheet) = self.compose() {
+            if reproducible {
+                let png = image_utils::encode_png_reproducible(
+                    sheet.as_raw(), sheet.width(), sheet.height(), image::ColorType::Rgb8
+                )?;
+                std::fs::write(output_dir.join(FILE_NAME), png)?;
+            } else {
+                sheet.save(output_dir.join(FILE_NAME))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Draws `text` (digits only) in white on a black background, with the top-left corner at (`x`, `y`).
+fn draw_label(image: &mut image::RgbImage, text: &str, x: u32, y: u32) {
+    let glyph_advance = (GLYPH_WIDTH + 1) * LABEL_SCALE;
+    let width = text.len() as u32 * glyph_advance + LABEL_SCALE;
+    let height = (GLYPH_HEIGHT + 2) * LABEL_SCALE;
+
+    for py in y..(y + height).min(image.height()) {
+        for px in x..(x + width).min(image.width()) {
+            image.put_pixel(px, py, image::Rgb([0, 0, 0]));
+        }
+    }
+
+    for (i, c) in text.chars().enumerate() {
+        let glyph = match c.to_digit(10) {
+            Some(digit) => &DIGIT_GLYPHS[digit as usize],
+            None => continue
+        };
+
+        let glyph_x = x + LABEL_SCALE + i as u32 * glyph_advance;
+        let glyph_y = y + LABEL_SCALE;
+        for (row, mask) in glyph.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if mask & (1 << (GLYPH_WIDTH - 1 - column)) == 0 { continue; }
+
+                for dy in 0..LABEL_SCALE {
+                    for dx in 0..LABEL_SCALE {
+                        let px = glyph_x + column * LABEL_SCALE + dx;
+                        let py = glyph_y + row as u32 * LABEL_SCALE + dy;
+                        if px < image.width() && py < image.height() {
+                            image.put_pixel(px, py, image::Rgb([255, 255, 255]));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_of_square_thumbnails_is_near_square() {
+        assert_eq!(GridLayout{ columns: 0, rows: 0 }, grid_layout(0, 256, 256));
+        assert_eq!(GridLayout{ columns: 1, rows: 1 }, grid_layout(1, 256, 256));
+        assert_eq!(GridLayout{ columns: 3, rows: 3 }, grid_layout(9, 256, 256));
+        assert_eq!(GridLayout{ columns: 3, rows: 4 }, grid_layout(10, 256, 256));
+        assert_eq!(GridLayout{ columns: 32, rows: 32 }, grid_layout(1000, 256, 256));
+    }
+
+    #[test]
+    fn layout_of_wide_thumbnails_has_more_rows() {
+        // 2:1 thumbnails; 2 columns × 4 rows give a square sheet
+        assert_eq!(GridLayout{ columns: 2, rows: 4 }, grid_layout(8, 256, 128));
+        // tall thumbnails; there are never more columns than thumbnails
+        assert_eq!(GridLayout{ columns: 2, rows: 1 }, grid_layout(2, 64, 256));
+    }
+
+    #[test]
+    fn layout_covers_all_thumbnails() {
+        for n in 1..200 {
+            let layout = grid_layout(n, 256, 100);
+            assert!((layout.columns * layout.rows) as usize >= n);
+            assert!(((layout.columns * (layout.rows - 1)) as usize) < n, "{}: {:?}", n, layout);
+        }
+    }
+
+    #[test]
+    fn thumbnail_positions_do_not_overlap() {
+        let layout = GridLayout{ columns: 3, rows: 2 };
+        assert_eq!([SPACING, SPACING], thumbnail_position(layout, 0, 100, 50));
+        assert_eq!([SPACING + 2 * (100 + SPACING), SPACING], thumbnail_position(layout, 2, 100, 50));
+        assert_eq!([SPACING, SPACING + 50 + SPACING], thumbnail_position(layout, 3, 100, 50));
+    }
+
+    #[test]
+    fn sheet_contains_all_frames() {
+        let mut sheet = ContactSheet::new();
+        for _ in 0..5 {
+            sheet.add(&ga_image::Image::new(512, 256, None, ga_image::PixelFormat::RGB8, None, true));
+        }
+        let image = sheet.compose().unwrap();
+
+        // thumbnails 256×128, 2:1, 5 frames: 2 columns × 3 rows
+        assert_eq!(SPACING + 2 * (256 + SPACING), image.width());
+        assert_eq!(SPACING + 3 * (128 + SPACING), image.height());
+
+        assert!(ContactSheet::new().compose().is_none());
+    }
+}
@@ -0,0 +1,267 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Longitude coverage of a sequence: each frame provides usable data (emission angle below a threshold) within
+//! an interval of longitudes around its central meridian; the intervals are shifted by the planet's rotation.
+//!
+//! Longitudes are in degrees, increasing rightwards in the projection (i.e., in the direction opposite to the shift
+//! of subsequent frames), with 0 at the central meridian of the first frame.
+
+use cgmath::{Angle, Deg};
+use crate::projection::source_view::SourceParameters;
+use std::time::Duration;
+
+const FULL_CIRCLE: f32 = 360.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Interval {
+    pub start: f32,
+    pub end: f32
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CircumferenceCoverage {
+    /// Within [0; 1].
+    pub covered_fraction: f32,
+    /// Largest uncovered span of longitudes (in degrees).
+    pub largest_gap: f32
+}
+
+/// Returns the planet's rotation between subsequent frames; negative for retrograde rotation.
+pub fn rotation_per_frame(src_params: &SourceParameters) -> Deg<f32> {
+    Deg(src_params.rotation_direction.sign() * FULL_CIRCLE * src_params.frame_interval.as_secs_f32()
+        / src_params.sidereal_rotation_period.as_secs_f32())
+}
+
+/// Returns the planet's rotation (in degrees) between the first and the last of `num_frames` frames spaced by
+/// `frame_interval`.
+pub fn accumulated_rotation(num_frames: usize, frame_interval: Duration, rotation_period: Duration) -> f64 {
+    FULL_CIRCLE as f64 * num_frames.saturating_sub(1) as f64 * frame_interval.as_secs_f64()
+        / rotation_period.as_secs_f64()
+}
+
+/// Returns `interval` rounded to the nearest multiple of `resolution`.
+pub fn rounded_interval(interval: Duration, resolution: Duration) -> Duration {
+    resolution * (interval.as_secs_f64() / resolution.as_secs_f64()).round() as u32
+}
+
+/// Returns the error of the accumulated rotation by the last frame (in degrees; see `accumulated_rotation`) if
+/// `frame_interval` were rounded to a multiple of `resolution`.
+pub fn interval_rounding_drift(
+    num_frames: usize,
+    frame_interval: Duration,
+    resolution: Duration,
+    rotation_period: Duration
+) -> f64 {
+    accumulated_rotation(num_frames, rounded_interval(frame_interval, resolution), rotation_period)
+        - accumulated_rotation(num_frames, frame_interval, rotation_period)
+}
+
+/// Returns the maximum error of the accumulated rotation by the last frame (in degrees) if the frame interval is
+/// known only to within `resolution` (i.e., it has been rounded and may be off by up to half of `resolution`).
+pub fn max_interval_rounding_drift(num_frames: usize, resolution: Duration, rotation_period: Duration) -> f64 {
+    accumulated_rotation(num_frames, resolution / 2, rotation_period)
+}
+
+/// Returns half-width of the interval of longitudes on the equator seen at emission angle below `max_emission_angle`
+/// (for a planet whose axis is tilted by `inclination` towards or away from the observer).
+pub fn usable_half_width(max_emission_angle: Deg<f32>, inclination: Deg<f32>) -> Deg<f32> {
+    // emission angle e of an equatorial point at longitude λ from the central meridian: cos e = cos λ · cos i
+    let cos_lon = max_emission_angle.cos() / inclination.cos();
+    if cos_lon >= 1.0 { Deg(0.0) } else { Deg::acos(cos_lon.max(-1.0)) }
+}
+
+/// Returns intervals of usable longitudes of subsequent frames.
+pub fn frame_intervals(num_frames: usize, rotation_per_frame: Deg<f32>, half_width: Deg<f32>) -> Vec<Interval> {
+    (0..num_frames).map(|idx| {
+        let center = -(idx as f32) * rotation_per_frame.0;
+        Interval{ start: center - half_width.0, end: center + half_width.0 }
+    }).collect()
+}
+
+/// Returns the number of intervals covering the centers of `num_bins` equal bins spanning [`start`; `end`].
+pub fn count_coverage(intervals: &[Interval], start: f32, end: f32, num_bins: usize) -> Vec<u32> {
+    if num_bins == 0 || end <= start { return vec![0; num_bins]; }
+
+    let bin_width = (end - start) / num_bins as f32;
+
+    // each interval increments the counts of a range of bins; ranges are accumulated as differences
+    let mut deltas = vec![0i64; num_bins + 1];
+    for interval in intervals {
+        let first = ((interval.start - start) / bin_width - 0.5).ceil().max(0.0);
+        let last = ((interval.end - start) / bin_width - 0.5).floor().min((num_bins - 1) as f32);
+        if first > last { continue; }
+
+        deltas[first as usize] += 1;
+        deltas[last as usize + 1] -= 1;
+    }
+
+    let mut count = 0;
+    deltas[..num_bins].iter().map(|delta| {
+        count += delta;
+        count as u32
+    }).collect()
+}
+
+/// Returns the coverage of the whole circumference (longitudes wrap around every 360°).
+pub fn circumference_coverage(intervals: &[Interval]) -> CircumferenceCoverage {
+    // intervals brought into [0; 360), split where they wrap around
+    let mut wrapped = vec![];
+    for interval in intervals {
+        if interval.end - interval.start >= FULL_CIRCLE {
+            return CircumferenceCoverage{ covered_fraction: 1.0, largest_gap: 0.0 };
+        }
+        if interval.end <= interval.start { continue; }
+
+        let start = interval.start.rem_euclid(FULL_CIRCLE);
+        let end = start + (interval.end - interval.start);
+        if end > FULL_CIRCLE {
+            wrapped.push(Interval{ start, end: FULL_CIRCLE });
+            wrapped.push(Interval{ start: 0.0, end: end - FULL_CIRCLE });
+        } else {
+            wrapped.push(Interval{ start, end });
+        }
+    }
+
+    if wrapped.is_empty() {
+        return CircumferenceCoverage{ covered_fraction: 0.0, largest_gap: FULL_CIRCLE };
+    }
+
+    wrapped.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+
+    let mut merged: Vec<Interval> = vec![];
+    for interval in wrapped {
+        match merged.last_mut() {
+            Some(last) if interval.start <= last.end => last.end = last.end.max(interval.end),
+            _ => merged.push(interval)
+        }
+    }
+
+    let covered: f32 = merged.iter().map(|i| i.end - i.start).sum();
+
+    let mut largest_gap = FULL_CIRCLE - merged.last().unwrap().end + merged[0].start;
+    for pair in merged.windows(2) {
+        largest_gap = largest_gap.max(pair[1].start - pair[0].end);
+    }
+
+    CircumferenceCoverage{ covered_fraction: (covered / FULL_CIRCLE).min(1.0), largest_gap }
+}
+
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: f32, actual: f32) {
+        assert!((expected - actual).abs() < 1.0e-3, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn half_width_shrinks_with_inclination() {
+        assert_close(60.0, usable_half_width(Deg(60.0), Deg(0.0)).0);
+        assert!(usable_half_width(Deg(60.0), Deg(20.0)).0 < 60.0);
+        // the equator is seen at a larger emission angle than the maximum even at the central meridian
+        assert_close(0.0, usable_half_width(Deg(30.0), Deg(40.0)).0);
+    }
+
+    #[test]
+    fn frames_are_shifted_by_rotation() {
+        let intervals = frame_intervals(3, Deg(10.0), Deg(60.0));
+        assert_eq!(Interval{ start: -60.0, end: 60.0 }, intervals[0]);
+        assert_eq!(Interval{ start: -80.0, end: 40.0 }, intervals[2]);
+    }
+
+    #[test]
+    fn overlapping_intervals_are_counted() {
+        let intervals = [Interval{ start: 0.0, end: 20.0 }, Interval{ start: 10.0, end: 30.0 }];
+        // bin centers: 5, 15, 25, 35
+        assert_eq!(vec![1, 2, 1, 0], count_coverage(&intervals, 0.0, 40.0, 4));
+
+        // intervals extending beyond the range, or lying outside of it
+        let intervals = [Interval{ start: -100.0, end: 16.0 }, Interval{ start: 50.0, end: 60.0 }];
+        assert_eq!(vec![1, 1, 0, 0], count_coverage(&intervals, 0.0, 40.0, 4));
+    }
+
+    #[test]
+    fn coverage_and_largest_gap() {
+        let intervals = [
+            Interval{ start: 0.0, end: 90.0 },
+            Interval{ start: 45.0, end: 135.0 },
+            Interval{ start: 180.0, end: 200.0 }
+        ];
+        let coverage = circumference_coverage(&intervals);
+        assert_close(155.0 / 360.0, coverage.covered_fraction);
+        assert_close(160.0, coverage.largest_gap);
+    }
+
+    #[test]
+    fn intervals_wrap_around() {
+        let intervals = [Interval{ start: -30.0, end: 30.0 }, Interval{ start: 340.0, end: 350.0 }];
+        let coverage = circumference_coverage(&intervals);
+        assert_close(60.0 / 360.0, coverage.covered_fraction);
+        assert_close(300.0, coverage.largest_gap);
+    }
+
+    #[test]
+    fn rounding_drift_over_representative_sequences() {
+        let jupiter = Duration::from_secs_f64(35729.685);
+        let second = Duration::from_secs(1);
+
+        // 2 hours at 1 frame per minute: the last frame may be off by 0.6°
+        assert_close(0.6045, max_interval_rounding_drift(121, second, jupiter) as f32);
+        assert_close(72.5447, accumulated_rotation(121, Duration::from_secs(60), jupiter) as f32);
+
+        // an interval of 30.4 s rounded down to 30 s over 3 hours (361 frames)
+        let drift = interval_rounding_drift(361, Duration::from_secs_f64(30.4), second, jupiter);
+        assert_close(-1.4508, drift as f32);
+
+        // rounding up
+        let drift = interval_rounding_drift(61, Duration::from_secs_f64(119.6), second, jupiter);
+        assert_close(0.2418, drift as f32);
+
+        // Mars rotates ~2.5 times slower
+        let mars = Duration::from_secs_f64(88642.663);
+        assert_close(0.2437, max_interval_rounding_drift(121, second, mars) as f32);
+    }
+
+    #[test]
+    fn no_drift_for_exact_intervals_and_single_frames() {
+        let period = Duration::from_secs(36000);
+        let second = Duration::from_secs(1);
+
+        assert_eq!(0.0, interval_rounding_drift(500, Duration::from_secs(45), second, period));
+        assert_eq!(0.0, interval_rounding_drift(1, Duration::from_secs_f64(45.3), second, period));
+        assert_eq!(0.0, max_interval_rounding_drift(1, second, period));
+        assert_eq!(0.0, max_interval_rounding_drift(0, second, period));
+
+        assert_eq!(Duration::from_secs(45), rounded_interval(Duration::from_secs_f64(45.3), second));
+        assert_eq!(Duration::from_millis(45_300), rounded_interval(Duration::from_secs_f64(45.3004), second / 1000));
+    }
+
+    #[test]
+    fn long_sequence_covers_everything() {
+        let intervals = frame_intervals(100, Deg(5.0), Deg(60.0));
+        let coverage = circumference_coverage(&intervals);
+        assert_close(1.0, coverage.covered_fraction);
+        assert_close(0.0, coverage.largest_gap);
+
+        assert_eq!(
+            CircumferenceCoverage{ covered_fraction: 0.0, largest_gap: 360.0 },
+            circumference_coverage(&[])
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Rendering of frame projections on the CPU (equivalent of `projection_view::render_projection`). Unlike rendering
+//! by the GPU, whose results depend on the graphics card and driver, it gives identical images for identical input
+//! on any machine running the same build. Used by reproducible exports.
+
+use cgmath::Point2;
+use crate::projection::phase;
+use crate::projection::projection_view::{self, ProjectionType};
+use crate::projection::source_view::SourceParameters;
+
+/// Returns the projection of frame `source_image_idx` (`source`; RGB8 or RGB16) as an image of `size` and
+/// `pixel_format` (RGB8 or RGB16), laid out like the export's draw buffer after `render_projection` (first row
+/// at the bottom, see `image_utils::image_from_texture`). Unlike `render_projection`, does not support
+/// the diagnostic pattern.
+pub fn render_projection(
+    source: &ga_image::Image,
+    source_image_idx: usize,
+    size: [u32; 2],
+    pixel_format: ga_image::PixelFormat,
+    src_params: &SourceParameters,
+    rotation_comp: f32,
+    projection_type: ProjectionType,
+    limb_feather: f32
+) -> ga_image::Image {
+    let mut output = ga_image::Image::new(size[0], size[1], None, pixel_format, None, true);
+    let gain = src_params.frame_gain(source_image_idx);
+
+    for row in 0..size[1] {
+        for col in 0..size[0] {
+            // the pixel centers are sampled, as by the rasterizer
+            let pos = [(col as f32 + 0.5) / size[0] as f32, 1.0 - (row as f32 + 0.5) / size[1] as f32];
+            let (longitude, latitude) = match projection_view::projection_coords(
+                pos, source_image_idx, src_params, rotation_comp, projection_type
+            ) {
+                Some(coords) => coords,
+                None => continue
+            };
+
+            let source_pos =
+                projection_view::frame_source_image_position(src_params, source_image_idx, longitude, latitude);
+            if !within(source, source_pos) { continue; }
+
+            let alpha = projection_view::frame_limb_feather_alpha(
+                src_params, source_image_idx, longitude, latitude, limb_feather
+            ) * phase::terminator_alpha(src_params, longitude, latitude);
+
+            let color = sample(source, source_pos).map(|value| alpha * gain * value);
+            set_pixel(&mut output, col, row, color);
+        }
+    }
+
+    output
+}
+
+/// Returns true if `pos` (in pixels) lies within `image` (bounds test in `projection.frag`).
+fn within(image: &ga_image::Image, pos: Point2<f32>) -> bool {
+    pos.x >= 0.0 && pos.y >= 0.0 && pos.x <= image.width() as f32 && pos.y <= image.height() as f32
+}
+
+/// Returns normalized (within [0; 1]) value of `channel` of pixel (`x`, `y`) of `image` (RGB8 or RGB16).
+fn value(image: &ga_image::Image, x: u32, y: u32, channel: usize) -> f32 {
+    let idx = 3 * x as usize + channel;
+    match image.pixel_format() {
+        ga_image::PixelFormat::RGB8 => image.line::<u8>(y)[idx] as f32 / 255.0,
+        ga_image::PixelFormat::RGB16 => image.line::<u16>(y)[idx] as f32 / 65535.0,
+        other => panic!("unsupported pixel format: {:?}", other)
+    }
+}
+
+/// Returns color of `image` at `pos` (in pixels) interpolated bilinearly between pixel centers, with edge pixels
+/// extended outwards (as the texture sampling in `projection.frag`).
+fn sample(image: &ga_image::Image, pos: Point2<f32>) -> [f32; 3] {
+    let x = (pos.x - 0.5).max(0.0).min((image.width() - 1) as f32);
+    let y = (pos.y - 0.5).max(0.0).min((image.height() - 1) as f32);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(image.width() - 1), (y0 + 1).min(image.height() - 1));
+    let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+
+    [0, 1, 2].map(|channel| {
+        let top = (1.0 - tx) * value(image, x0, y0, channel) + tx * value(image, x1, y0, channel);
+        let bottom = (1.0 - tx) * value(image, x0, y1, channel) + tx * value(image, x1, y1, channel);
+        (1.0 - ty) * top + ty * bottom
+    })
+}
+
+/// Sets pixel (`x`, `y`) of `image` (RGB8 or RGB16) to `color` (clamped to [0; 1] and rounded, as when the GPU
+/// writes to a normalized integer target).
+fn set_pixel(image: &mut ga_image::Image, x: u32, y: u32, color: [f32; 3]) {
+    let idx = 3 * x as usize;
+    match image.pixel_format() {
+        ga_image::PixelFormat::RGB8 => {
+            let line = image.line_mut::<u8>(y);
+            for (channel, value) in color.iter().enumerate() {
+                line[idx + channel] = (value.max(0.0).min(1.0) * 255.0).round() as u8;
+            }
+        },
+        ga_image::PixelFormat::RGB16 => {
+            let line = image.line_mut::<u16>(y);
+            for (channel, value) in color.iter().enumerate() {
+                line[idx + channel] = (value.max(0.0).min(1.0) * 65535.0).round() as u16;
+            }
+        },
+        other => panic!("unsupported pixel format: {:?}", other)
+    }
+}
+
+mod tests {
+    use super::*;
+
+    fn params() -> SourceParameters {
+        SourceParameters{
+            disk_center: Point2{ x: 50.0, y: 50.0 },
+            disk_diameter: 80.0,
+            frame_gains: vec![0.5],
+            ..SourceParameters::test_default()
+        }
+    }
+
+    fn uniform_image(width: u32, height: u32, value: u8) -> ga_image::Image {
+        ga_image::Image::new_from_pixels(
+            width, height, None, ga_image::PixelFormat::RGB8, None, vec![value; (3 * width * height) as usize]
+        )
+    }
+
+    #[test]
+    fn uniform_disk_is_scaled_by_gain() {
+        let output = render_projection(
+            &uniform_image(100, 100, 200),
+            0,
+            [64, 32],
+            ga_image::PixelFormat::RGB8,
+            &params(),
+            0.0,
+            ProjectionType::Equirectangular,
+            0.0
+        );
+
+        // 0.5 · 200, rounded after normalization
+        assert_eq!(&[100, 100, 100], &output.line::<u8>(16)[3 * 32..3 * 33]);
+    }
+
+    #[test]
+    fn disk_parts_outside_source_image_are_black() {
+        let mut params = params();
+        // the western half of the disk extends beyond the left edge of the image
+        params.disk_center = Point2{ x: 10.0, y: 50.0 };
+
+        let output = render_projection(
+            &uniform_image(100, 100, 200),
+            0,
+            [64, 32],
+            ga_image::PixelFormat::RGB16,
+            &params,
+            0.0,
+            ProjectionType::Equirectangular,
+            0.0
+        );
+
+        let row = output.line::<u16>(16);
+        assert_eq!(&[0, 0, 0], &row[3 * 2..3 * 3]);
+        // 0.5 · 200/255 of the full range
+        assert_eq!(&[25700, 25700, 25700], &row[3 * 60..3 * 61]);
+    }
+}
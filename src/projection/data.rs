@@ -19,11 +19,24 @@
 
 use cgmath::{Angle, Deg, Rad};
 use crate::config::ProjectionConfig;
-use crate::data::{BaseProgramData, Vertex2, Vertex3};
+use crate::data::{BaseProgramData, TextureId, Vertex2, Vertex3};
+use crate::image_utils::BitDepth;
+use crate::gpu::texture_limits::TextureLimits;
 use crate::gui::long_task_dialog::LongTaskDialog;
 use crate::long_fg_task::LongForegroundTask;
-use crate::projection::{ExportDialog, GlobeView, ProjectionView, SourceView, worker};
-use glium::{glutin, program};
+use crate::projection::{
+    CompositeView, DisplayOrientation, DisplaySettings, ExportDialog, GlobeView, ProjectionView, SourceView, worker
+};
+use crate::projection::globe_view::GlobeTarget;
+use crate::projection::projection_view::LinkedCursor;
+use crate::projection::disk_confirmation::DiskConfirmation;
+use crate::projection::imported_map::{ImportedMap, MapImportDialog};
+use crate::projection::linking::{self, LinkGroup};
+use crate::projection::load_cache::{self, FileStamp, LoadCache};
+use crate::projection::load_options_dialog::{DEFAULT_VRAM_BUDGET_MIB, LoadOptions, LoadOptionsDialog};
+use crate::projection::session_check::SessionCheck;
+use crate::projection::session_state::SessionState;
+use glium::program;
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -34,6 +47,11 @@ pub struct LonLatVertex {
 }
 glium::implement_vertex!(LonLatVertex, lonlat_position);
 
+impl LonLatVertex {
+    /// Returns (longitude, latitude) in degrees.
+    pub fn lonlat(&self) -> [f32; 2] { self.lonlat_position }
+}
+
 #[derive(Clone)]
 pub struct LonLatGlBuffers {
     pub vertices: Rc<glium::VertexBuffer<LonLatVertex>>,
@@ -43,20 +61,135 @@ pub struct LonLatGlBuffers {
 pub struct OpenGlObjects {
     pub texture_copy_single: Rc<glium::Program>,
     pub texture_copy_multi: Rc<glium::Program>,
+    pub display_stretch: Rc<glium::Program>,
     pub projection: Rc<glium::Program>,
+    pub reprojection: Rc<glium::Program>,
     pub solid_color_2d: Rc<glium::Program>,
     pub solid_color_3d: Rc<glium::Program>,
     pub globe_texturing: Rc<glium::Program>,
+    pub composite: Rc<glium::Program>,
+    pub texturing_saturation: Rc<glium::Program>,
+    pub crossfade: Rc<glium::Program>,
+    pub map_comparison: Rc<glium::Program>,
     pub unit_quad: Rc<glium::VertexBuffer<Vertex2>>,
     pub unit_circle: Rc<glium::VertexBuffer<Vertex3>>,
     pub globe_mesh: LonLatGlBuffers
 }
 
+/// Files selected for loading, awaiting confirmation of load options.
+#[derive(Clone)]
+pub struct PendingLoad {
+    pub paths: Vec<std::path::PathBuf>,
+    /// Stamps of `paths` at the time of selection (`None` if the metadata could not be read).
+    pub stamps: Vec<Option<FileStamp>>,
+    /// Dimensions before binning.
+    pub dimensions: [u32; 2],
+    /// Bit depth of the first file; the others must match it.
+    pub bit_depth: BitDepth
+}
+
 pub struct ImageLoading {
     pub textures: Vec<Rc<glium::Texture2d>>,
+    /// Files corresponding to `textures`.
+    pub paths: Vec<std::path::PathBuf>,
+    /// Stamps of `paths`.
+    pub stamps: Vec<Option<FileStamp>>,
+    pub load_options: LoadOptions,
+    pub bit_depth: BitDepth,
     pub receiver: crossbeam::channel::Receiver<worker::LoadImagesResultMsg>
 }
 
+pub struct FrameStacking {
+    /// Size of groups of frames to replace with their stacks; `None`: all frames are stacked into the average frame.
+    pub group_size: Option<usize>,
+    pub receiver: crossbeam::channel::Receiver<worker::StackFramesResultMsg>
+}
+
+pub struct BrightnessMeasurement {
+    pub receiver: crossbeam::channel::Receiver<worker::MeasureBrightnessResultMsg>
+}
+
+pub struct LimbMeasurement {
+    pub receiver: crossbeam::channel::Receiver<worker::MeasureLimbAnglesResultMsg>
+}
+
+/// Textures used by a running background task, which refers to them only by IDs (as `Rc`s cannot be sent to
+/// the worker thread). Keeps them alive until the task ends, even if the source images get replaced meanwhile.
+pub struct TexturesInUse<T = glium::Texture2d> {
+    _textures: Vec<Rc<T>>,
+    ids: Vec<TextureId>
+}
+
+impl<T> TexturesInUse<T> {
+    pub fn new(textures: &[Rc<T>], id_of: impl Fn(&T) -> TextureId) -> TexturesInUse<T> {
+        TexturesInUse{
+            ids: textures.iter().map(|texture| id_of(texture)).collect(),
+            _textures: textures.to_vec()
+        }
+    }
+
+    /// IDs to be sent to the worker.
+    pub fn ids(&self) -> &[TextureId] { &self.ids }
+
+    pub fn contains_all(&self, ids: &[TextureId]) -> bool { ids.iter().all(|id| self.ids.contains(id)) }
+}
+
+/// Shown for the widgets disabled by an `ExportLock`.
+pub const EXPORT_LOCK_REASON: &str = "Locked until the running export finishes or is cancelled.";
+
+/// Held while an export runs. The worker uses a snapshot of the source parameters and of the exporting projection
+/// view's settings, so these must not be edited meanwhile (the views would no longer show what is being exported).
+/// Also keeps the exported textures alive. Released by dropping, once the export ends (see `poll_export_end`).
+pub struct ExportLock<T = glium::Texture2d> {
+    projection_view_id: u32,
+    textures: Option<TexturesInUse<T>>
+}
+
+impl<T> ExportLock<T> {
+    /// `textures`: source textures used by the export (if it reads them from VRAM).
+    pub fn new(projection_view_id: u32, textures: Option<TexturesInUse<T>>) -> ExportLock<T> {
+        ExportLock{ projection_view_id, textures }
+    }
+
+    /// Returns `true` if the settings of projection view `id` are locked (the source parameters always are).
+    pub fn locks_projection_view(&self, id: u32) -> bool { self.projection_view_id == id }
+
+    /// Returns the ID of the exporting projection view.
+    pub fn projection_view_id(&self) -> u32 { self.projection_view_id }
+
+    pub fn textures(&self) -> Option<&TexturesInUse<T>> { self.textures.as_ref() }
+}
+
+pub enum ExportEnd {
+    Message(worker::ProjectionResultMsg),
+    /// The worker has stopped without sending the final message.
+    WorkerDisconnected
+}
+
+/// Checks if the running export has ended (i.e., its final message has arrived or the worker has disconnected); if
+/// so, clears `export_result` and releases the export lock.
+pub fn poll_export_end<T>(
+    export_result: &RefCell<Option<crossbeam::channel::Receiver<worker::ProjectionResultMsg>>>,
+    export_lock: &RefCell<Option<ExportLock<T>>>
+) -> Option<ExportEnd> {
+    let end = match &*export_result.borrow() {
+        Some(receiver) => match receiver.try_recv() {
+            Ok(msg) => Some(ExportEnd::Message(msg)),
+            Err(crossbeam::channel::TryRecvError::Empty) => None,
+            Err(crossbeam::channel::TryRecvError::Disconnected) => Some(ExportEnd::WorkerDisconnected)
+        },
+        // not expected, but a lock without a running export must not stay forever
+        None => { *export_lock.borrow_mut() = None; None }
+    };
+
+    if end.is_some() {
+        *export_result.borrow_mut() = None;
+        *export_lock.borrow_mut() = None;
+    }
+
+    end
+}
+
 pub struct ProgramData {
     base: RefCell<BaseProgramData>,
 
@@ -66,10 +199,14 @@ pub struct ProgramData {
 
     source_view: Option<SourceView>, // empty until images are loaded for the first time
 
+    link_groups: Vec<Rc<LinkGroup>>,
+
     globe_views: RefCell<Vec<Rc<RefCell<GlobeView>>>>,
 
     projection_views: RefCell<Vec<Rc<RefCell<ProjectionView>>>>,
 
+    composite_views: RefCell<Vec<Rc<RefCell<CompositeView>>>>,
+
     long_task_dialog: RefCell<Option<LongTaskDialog>>,
 
     long_fg_task: RefCell<Option<Box<dyn LongForegroundTask>>>,
@@ -78,14 +215,59 @@ pub struct ProgramData {
 
     export_dialog: RefCell<ExportDialog>,
 
-    image_loading: Option<ImageLoading>
+    image_loading: Option<ImageLoading>,
+
+    pending_load: Option<PendingLoad>,
+
+    /// Files and options of the most recently started loading (used to reload the images after a GPU context loss).
+    last_load: Option<(PendingLoad, LoadOptions)>,
+
+    /// Loaded frames whose timestamps suggest more than one observing session.
+    session_check: RefCell<Option<SessionCheck>>,
+
+    disk_confirmation: RefCell<Option<DiskConfirmation>>,
+
+    /// Map selected to be opened on the globe, awaiting confirmation of its settings.
+    map_import: RefCell<Option<MapImportDialog>>,
+
+    load_options_dialog: RefCell<LoadOptionsDialog>,
+
+    frame_stacking: Option<FrameStacking>,
+
+    brightness_measurement: Option<BrightnessMeasurement>,
+    limb_measurement: Option<LimbMeasurement>,
+
+    load_cache: LoadCache,
+
+    /// The user has requested closing of the projection mode.
+    close_requested: bool,
+
+    export_result: RefCell<Option<crossbeam::channel::Receiver<worker::ProjectionResultMsg>>>,
+
+    /// Present while an export is running.
+    export_lock: RefCell<Option<ExportLock>>,
+
+    /// Cursor position shared between projection views.
+    linked_cursor: LinkedCursor,
+
+    /// Location to be centered in a globe view which the user has been offered to create.
+    pending_globe_target: Option<GlobeTarget>,
+
+    session_state: SessionState,
+
+    /// ID of the most recently focused projection view (its settings are stored in A/B snapshots).
+    active_projection_view: Option<u32>,
+
+    /// Texture size limits of the main and the worker's contexts.
+    texture_limits: Rc<TextureLimits>
 }
 
 impl ProgramData {
     pub fn new(
         base: BaseProgramData,
         display: &glium::Display,
-        worker_context: glutin::Context<glutin::NotCurrent>
+        bg_task_sender: crossbeam::channel::Sender<worker::MainToWorkerMsg>,
+        texture_limits: Rc<TextureLimits>
     ) -> ProgramData {
         let texture_copy_single = Rc::new(program!(display,
             330 => {
@@ -101,6 +283,13 @@ impl ProgramData {
             }
         ).unwrap());
 
+        let display_stretch = Rc::new(program!(display,
+            330 => {
+                vertex: include_str!("../resources/shaders/pass-through.vert"),
+                fragment: include_str!("../resources/shaders/display_stretch.frag"),
+            }
+        ).unwrap());
+
         let projection = Rc::new(program!(display,
             330 => {
                 vertex: include_str!("../resources/shaders/transform_2d.vert"),
@@ -108,6 +297,13 @@ impl ProgramData {
             }
         ).unwrap());
 
+        let reprojection = Rc::new(program!(display,
+            330 => {
+                vertex: include_str!("../resources/shaders/pass-through.vert"),
+                fragment: include_str!("../resources/shaders/reprojection.frag"),
+            }
+        ).unwrap());
+
         let solid_color_2d = Rc::new(program!(display,
             330 => {
                 vertex: include_str!("../resources/shaders/transform_2d.vert"),
@@ -129,50 +325,187 @@ impl ProgramData {
             }
         ).unwrap());
 
+        let composite = Rc::new(program!(display,
+            330 => {
+                vertex: include_str!("../resources/shaders/pass-through.vert"),
+                fragment: include_str!("../resources/shaders/composite.frag")
+            }
+        ).unwrap());
+
+        let texturing_saturation = Rc::new(program!(display,
+            330 => {
+                vertex: include_str!("../resources/shaders/pass-through.vert"),
+                fragment: include_str!("../resources/shaders/texturing_saturation.frag")
+            }
+        ).unwrap());
+
+        let crossfade = Rc::new(program!(display,
+            330 => {
+                vertex: include_str!("../resources/shaders/pass-through.vert"),
+                fragment: include_str!("../resources/shaders/crossfade.frag")
+            }
+        ).unwrap());
+
+        let map_comparison = Rc::new(program!(display,
+            330 => {
+                vertex: include_str!("../resources/shaders/pass-through.vert"),
+                fragment: include_str!("../resources/shaders/map_comparison.frag")
+            }
+        ).unwrap());
+
         let globe_mesh = create_globe_mesh(cgmath::Deg(2.0), display);
 
         let gl_objects = OpenGlObjects{
             texture_copy_single,
             texture_copy_multi,
+            display_stretch,
             projection,
+            reprojection,
             solid_color_2d,
             solid_color_3d,
             globe_texturing,
+            composite,
+            texturing_saturation,
+            crossfade,
+            map_comparison,
             unit_quad: create_unit_quad(display),
             unit_circle: create_unit_circle(256, display),
             globe_mesh
         };
 
-        let (bg_task_sender, bg_task_receiver) = crossbeam::channel::unbounded();
-
-        std::thread::spawn(move || { crate::projection::worker::worker(worker_context, bg_task_receiver); });
-
         let export_dialog = RefCell::new(ExportDialog::new(
             "Export images".to_string(),
-            base.config.projection_export_path().into()
+            base.config.projection_export_path().into(),
+            base.config.post_export_command().unwrap_or_default(),
+            base.config.post_export_command_enabled().unwrap_or(false),
+            base.config.export_presets().unwrap_or_default()
+        ));
+
+        let load_options_dialog = RefCell::new(LoadOptionsDialog::new(
+            "Load options".to_string(),
+            LoadOptions{
+                decimation: base.config.load_decimation().unwrap_or(1).max(1),
+                binning: base.config.load_binning().unwrap_or(1).max(1),
+                interpretation: base.config.load_interpretation().unwrap_or_default(),
+                skip_failed_frames: base.config.load_skip_failed_frames().unwrap_or(false)
+            },
+            base.config.vram_budget_mib().unwrap_or(DEFAULT_VRAM_BUDGET_MIB),
+            base.config.load_cache_on_disk().unwrap_or(false)
         ));
 
+        let cache_file_path = match base.config.load_cache_on_disk() {
+            Some(true) => Some(load_cache::default_file_path()),
+            _ => None
+        };
+        let load_cache = LoadCache::new(cache_file_path, load_cache::DEFAULT_MAX_ENTRIES);
+
         ProgramData{
             base: RefCell::new(base),
             id_counter: Rc::new(RefCell::new(0)),
             gl_objects,
             source_view: None,
+            link_groups: (0..linking::NUM_GROUPS).map(LinkGroup::new).collect(),
             globe_views: RefCell::new(vec![]),
             projection_views: RefCell::new(vec![]),
+            composite_views: RefCell::new(vec![]),
             long_fg_task: RefCell::new(None),
             long_task_dialog: RefCell::new(None),
             bg_task_sender,
             export_dialog,
-            image_loading: None
+            image_loading: None,
+            pending_load: None,
+            last_load: None,
+            session_check: RefCell::new(None),
+            disk_confirmation: RefCell::new(None),
+            map_import: RefCell::new(None),
+            load_options_dialog,
+            frame_stacking: None,
+            brightness_measurement: None,
+            limb_measurement: None,
+            load_cache,
+            close_requested: false,
+            export_result: RefCell::new(None),
+            export_lock: RefCell::new(None),
+            linked_cursor: Default::default(),
+            pending_globe_target: None,
+            session_state: Default::default(),
+            active_projection_view: None,
+            texture_limits
         }
     }
 
     pub fn base(&self) -> &RefCell<BaseProgramData> { &self.base }
 
+    /// Releases all resources of the projection mode (views, textures, GL programs); returns the data to be passed
+    /// to the next mode.
+    pub fn into_base(self) -> BaseProgramData { self.base.into_inner() }
+
+    pub fn close_requested(&self) -> bool { self.close_requested }
+
+    /// Requests closing of the projection mode; a running task gets cancelled (the mode is to be closed once
+    /// `task_in_progress` returns `false`).
+    pub fn request_close(&mut self) {
+        self.close_requested = true;
+
+        if let Some(long_task_dialog) = &*self.long_task_dialog.borrow() {
+            if let Some(cancel_token) = long_task_dialog.cancel_token() { cancel_token.cancel(); }
+
+            if let Some(long_fg_task) = &mut *self.long_fg_task.borrow_mut() {
+                long_fg_task.cancel();
+            } else if long_task_dialog.cancel_token().is_none() {
+                self.bg_task_sender.send(worker::MainToWorkerMsg::Cancel).unwrap();
+            }
+        }
+    }
+
+    /// Returns `true` if a task has been started and its result has not been received yet.
+    pub fn task_in_progress(&self) -> bool {
+        self.long_task_dialog.borrow().is_some()
+            || self.image_loading.is_some()
+            || self.frame_stacking.is_some()
+            || self.brightness_measurement.is_some()
+            || self.limb_measurement.is_some()
+            || self.export_result.borrow().is_some()
+    }
+
     pub fn image_loading(&self) -> &Option<ImageLoading> { &self.image_loading }
 
     pub fn image_loading_mut(&mut self) -> &mut Option<ImageLoading> { &mut self.image_loading }
 
+    pub fn pending_load(&self) -> &Option<PendingLoad> { &self.pending_load }
+
+    pub fn pending_load_mut(&mut self) -> &mut Option<PendingLoad> { &mut self.pending_load }
+
+    pub fn last_load(&self) -> &Option<(PendingLoad, LoadOptions)> { &self.last_load }
+
+    pub fn last_load_mut(&mut self) -> &mut Option<(PendingLoad, LoadOptions)> { &mut self.last_load }
+
+    pub fn load_options_dialog(&self) -> &RefCell<LoadOptionsDialog> { &self.load_options_dialog }
+
+    pub fn session_check(&self) -> &RefCell<Option<SessionCheck>> { &self.session_check }
+
+    pub fn disk_confirmation(&self) -> &RefCell<Option<DiskConfirmation>> { &self.disk_confirmation }
+
+    pub fn map_import(&self) -> &RefCell<Option<MapImportDialog>> { &self.map_import }
+
+    pub fn frame_stacking(&self) -> &Option<FrameStacking> { &self.frame_stacking }
+
+    pub fn frame_stacking_mut(&mut self) -> &mut Option<FrameStacking> { &mut self.frame_stacking }
+
+    pub fn brightness_measurement(&self) -> &Option<BrightnessMeasurement> { &self.brightness_measurement }
+
+    pub fn brightness_measurement_mut(&mut self) -> &mut Option<BrightnessMeasurement> {
+        &mut self.brightness_measurement
+    }
+
+    pub fn limb_measurement(&self) -> &Option<LimbMeasurement> { &self.limb_measurement }
+
+    pub fn limb_measurement_mut(&mut self) -> &mut Option<LimbMeasurement> { &mut self.limb_measurement }
+
+    pub fn load_cache(&self) -> &LoadCache { &self.load_cache }
+
+    pub fn load_cache_mut(&mut self) -> &mut LoadCache { &mut self.load_cache }
+
     pub fn long_fg_task(&self) -> &RefCell<Option<Box<dyn LongForegroundTask>>> { &self.long_fg_task }
 
     pub fn long_task_dialog(&self) -> &RefCell<Option<LongTaskDialog>> { &self.long_task_dialog }
@@ -188,6 +521,8 @@ impl ProgramData {
 
     pub fn source_view_mut(&mut self) -> &mut Option<SourceView> { &mut self.source_view }
 
+    pub fn link_groups(&self) -> &[Rc<LinkGroup>] { &self.link_groups }
+
     pub fn globe_views(&self) -> &RefCell<Vec<Rc<RefCell<GlobeView>>>> { &self.globe_views }
 
     pub fn projection_views(&self) -> &RefCell<Vec<Rc<RefCell<ProjectionView>>>> { &self.projection_views }
@@ -196,7 +531,7 @@ impl ProgramData {
         &mut self,
         display: &glium::Display,
         renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>
-    ) {
+    ) -> Rc<RefCell<ProjectionView>> {
         let id = self.new_unique_id();
 
         let source_view = self.source_view.as_mut().unwrap();
@@ -209,20 +544,24 @@ impl ProgramData {
             &source_view.current_image(),
             source_view.current_image_idx(),
             source_view.src_params().clone(),
-            0.0
+            0.0,
+            DisplayOrientation::from_config(&self.base.borrow().config),
+            DisplaySettings::from_config(&self.base.borrow().config)
         )));
 
         source_view.subscribe_current_img(Rc::downgrade(&projection_view) as _);
         source_view.subscribe_src_params(Rc::downgrade(&projection_view) as _);
 
-        self.projection_views.borrow_mut().push(projection_view);
+        self.projection_views.borrow_mut().push(Rc::clone(&projection_view));
+
+        projection_view
     }
 
     pub fn add_globe_view(
         &mut self,
         display: &glium::Display,
         renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>
-    ) {
+    ) -> Rc<RefCell<GlobeView>> {
         let id = self.new_unique_id();
 
         let source_view = self.source_view.as_mut().unwrap();
@@ -234,18 +573,77 @@ impl ProgramData {
             renderer,
             &source_view.current_image(),
             source_view.current_image_idx(),
-            source_view.src_params().clone()
+            source_view.src_params().clone(),
+            DisplayOrientation::from_config(&self.base.borrow().config)
         )));
 
         source_view.subscribe_current_img(Rc::downgrade(&globe_view) as _);
         source_view.subscribe_src_params(Rc::downgrade(&globe_view) as _);
 
-        self.globe_views.borrow_mut().push(globe_view);
+        self.globe_views.borrow_mut().push(Rc::clone(&globe_view));
+
+        globe_view
+    }
+
+    /// Globe views showing an imported map do not subscribe to the source view (which may not exist).
+    pub fn add_map_globe_view(
+        &mut self,
+        display: &glium::Display,
+        renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>,
+        map: ImportedMap
+    ) -> Rc<RefCell<GlobeView>> {
+        let id = self.new_unique_id();
+        let globe_view = Rc::new(RefCell::new(GlobeView::with_map(
+            id,
+            &self.gl_objects,
+            display,
+            renderer,
+            map,
+            DisplayOrientation::from_config(&self.base.borrow().config)
+        )));
+        self.globe_views.borrow_mut().push(Rc::clone(&globe_view));
+
+        globe_view
+    }
+
+    pub fn composite_views(&self) -> &RefCell<Vec<Rc<RefCell<CompositeView>>>> { &self.composite_views }
+
+    /// Composite views do not subscribe to the source view (they combine map sequences loaded from disk).
+    pub fn add_composite_view(
+        &mut self,
+        display: &glium::Display,
+        renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>
+    ) -> Rc<RefCell<CompositeView>> {
+        let id = self.new_unique_id();
+        let composite_view = Rc::new(RefCell::new(CompositeView::new(id, &self.gl_objects, display, renderer)));
+        self.composite_views.borrow_mut().push(Rc::clone(&composite_view));
+
+        composite_view
     }
 
     pub fn bg_task_sender(&self) -> &crossbeam::channel::Sender<worker::MainToWorkerMsg> { &self.bg_task_sender }
 
     pub fn export_dialog(&self) -> &RefCell<ExportDialog> { &self.export_dialog }
+
+    pub fn export_result(&self) -> &RefCell<Option<crossbeam::channel::Receiver<worker::ProjectionResultMsg>>> {
+        &self.export_result
+    }
+
+    pub fn export_lock(&self) -> &RefCell<Option<ExportLock>> { &self.export_lock }
+
+    pub fn linked_cursor(&self) -> &LinkedCursor { &self.linked_cursor }
+
+    pub fn pending_globe_target_mut(&mut self) -> &mut Option<GlobeTarget> { &mut self.pending_globe_target }
+
+    pub fn session_state(&self) -> &SessionState { &self.session_state }
+
+    pub fn session_state_mut(&mut self) -> &mut SessionState { &mut self.session_state }
+
+    pub fn active_projection_view(&self) -> Option<u32> { self.active_projection_view }
+
+    pub fn set_active_projection_view(&mut self, id: Option<u32>) { self.active_projection_view = id; }
+
+    pub fn texture_limits(&self) -> &Rc<TextureLimits> { &self.texture_limits }
 }
 
 pub fn create_unit_quad(display: &dyn glium::backend::Facade) -> Rc<glium::VertexBuffer<Vertex2>> {
@@ -291,10 +689,39 @@ pub fn create_half_parallel(
     glium::VertexBuffer::new(display, &points).unwrap()
 }
 
+/// Generates user-facing half of meridian (`longitude` within [-90°, 90°], 0° facing the user).
+pub fn create_half_meridian(
+    longitude: Deg<f32>,
+    num_segments: usize,
+    display: &impl glium::backend::Facade
+) -> glium::VertexBuffer<Vertex3> {
+    let mut points = vec![];
+
+    for i in 0..=num_segments {
+        let latitude = Deg::<f32>(-90.0) + Deg(180.0) / num_segments as f32 * i as f32;
+        let radius = latitude.cos();
+
+        points.push(Vertex3{ position: [radius * longitude.sin(), latitude.sin(), radius * longitude.cos()] });
+    }
+
+    glium::VertexBuffer::new(display, &points).unwrap()
+}
+
 fn create_globe_mesh(
     step: cgmath::Deg<f64>,
     display: &glium::Display
 ) -> LonLatGlBuffers {
+    let (vertex_data, index_data) = globe_mesh_data(step);
+
+    let vertices = Rc::new(glium::VertexBuffer::new(display, &vertex_data).unwrap());
+    let indices = Rc::new(glium::IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &index_data).unwrap());
+
+    LonLatGlBuffers{ vertices, indices }
+}
+
+/// Returns vertices and triangle indices of a globe mesh with nodes every `step` of longitude and latitude; the poles
+/// are single vertices (the last two). The columns at -180° and 180° coincide and are joined by zero-area triangles.
+pub fn globe_mesh_data(step: cgmath::Deg<f64>) -> (Vec<LonLatVertex>, Vec<u32>) {
     assert!((360.0 / step.0).fract() == 0.0);
 
     let grid_size_lon = (360.0 / step.0) as usize + 1;
@@ -347,8 +774,115 @@ fn create_globe_mesh(
         index_data.push(n_cap_idx);
     }
 
-    let vertices = Rc::new(glium::VertexBuffer::new(display, &vertex_data).unwrap());
-    let indices = Rc::new(glium::IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &index_data).unwrap());
+    (vertex_data, index_data)
+}
 
-    LonLatGlBuffers{ vertices, indices }
+mod tests {
+    use super::*;
+
+    /// Stands in for a GPU texture; counts the instances alive.
+    struct FakeTexture {
+        id: TextureId,
+        alive: Rc<RefCell<u32>>
+    }
+
+    impl FakeTexture {
+        fn new(id: TextureId, alive: &Rc<RefCell<u32>>) -> Rc<FakeTexture> {
+            *alive.borrow_mut() += 1;
+            Rc::new(FakeTexture{ id, alive: Rc::clone(alive) })
+        }
+    }
+
+    impl Drop for FakeTexture {
+        fn drop(&mut self) { *self.alive.borrow_mut() -= 1; }
+    }
+
+    #[test]
+    fn textures_in_use_outlive_replaced_source() {
+        let alive = Rc::new(RefCell::new(0));
+        let mut source_images = vec![FakeTexture::new(5, &alive), FakeTexture::new(7, &alive)];
+
+        let in_use = TexturesInUse::new(&source_images, |texture| texture.id);
+        assert_eq!(&[5, 7], in_use.ids());
+
+        // new images loaded during the task
+        source_images = vec![FakeTexture::new(9, &alive)];
+        assert_eq!(3, *alive.borrow());
+
+        // the task's final message has arrived
+        drop(in_use);
+        assert_eq!(1, *alive.borrow());
+        assert_eq!(9, source_images[0].id);
+    }
+
+    #[test]
+    fn textures_in_use_recognize_registered_ids() {
+        let alive = Rc::new(RefCell::new(0));
+        let textures = vec![FakeTexture::new(1, &alive), FakeTexture::new(2, &alive)];
+        let in_use = TexturesInUse::new(&textures, |texture| texture.id);
+
+        assert!(in_use.contains_all(&[2, 1]));
+        assert!(in_use.contains_all(&[]));
+        assert!(!in_use.contains_all(&[1, 3]));
+    }
+
+    fn lock_export(
+        view_id: u32,
+        alive: &Rc<RefCell<u32>>,
+        export_result: &RefCell<Option<crossbeam::channel::Receiver<worker::ProjectionResultMsg>>>,
+        export_lock: &RefCell<Option<ExportLock<FakeTexture>>>
+    ) -> crossbeam::channel::Sender<worker::ProjectionResultMsg> {
+        let textures = vec![FakeTexture::new(1, alive), FakeTexture::new(2, alive)];
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        *export_result.borrow_mut() = Some(receiver);
+        *export_lock.borrow_mut() = Some(ExportLock::new(view_id, Some(TexturesInUse::new(&textures, |t| t.id))));
+
+        sender
+    }
+
+    #[test]
+    fn export_lock_is_held_until_final_message() {
+        let alive = Rc::new(RefCell::new(0));
+        let export_result = RefCell::new(None);
+        let export_lock = RefCell::new(None);
+
+        let sender = lock_export(3, &alive, &export_result, &export_lock);
+        assert!(export_lock.borrow().as_ref().unwrap().locks_projection_view(3));
+        assert!(!export_lock.borrow().as_ref().unwrap().locks_projection_view(4));
+
+        assert!(poll_export_end(&export_result, &export_lock).is_none());
+        assert!(export_lock.borrow().is_some());
+        assert_eq!(2, *alive.borrow());
+
+        sender.send(worker::ProjectionResultMsg::Cancelled(1, 5)).unwrap();
+        assert!(matches!(
+            poll_export_end(&export_result, &export_lock),
+            Some(ExportEnd::Message(worker::ProjectionResultMsg::Cancelled(1, 5)))
+        ));
+        assert!(export_lock.borrow().is_none());
+        assert!(export_result.borrow().is_none());
+        assert_eq!(0, *alive.borrow());
+
+        // nothing running
+        assert!(poll_export_end(&export_result, &export_lock).is_none());
+    }
+
+    #[test]
+    fn export_lock_is_released_if_worker_fails() {
+        let alive = Rc::new(RefCell::new(0));
+        let export_result = RefCell::new(None);
+        let export_lock = RefCell::new(None);
+
+        let sender = lock_export(3, &alive, &export_result, &export_lock);
+        drop(sender);
+        assert!(matches!(poll_export_end(&export_result, &export_lock), Some(ExportEnd::WorkerDisconnected)));
+        assert!(export_lock.borrow().is_none());
+        assert_eq!(0, *alive.borrow());
+
+        // a lock left without an export
+        let _sender = lock_export(3, &alive, &export_result, &export_lock);
+        *export_result.borrow_mut() = None;
+        assert!(poll_export_end(&export_result, &export_lock).is_none());
+        assert!(export_lock.borrow().is_none());
+    }
 }
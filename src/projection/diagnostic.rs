@@ -0,0 +1,84 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Diagnostic rendering: a procedural longitude/latitude pattern shown instead of the source images, so that
+//! distortions and misalignments of the mapping are easy to see. Must match `diagnostic_color` in the shaders.
+
+use cgmath::Deg;
+
+/// Size of the checkerboard's cells.
+pub const CELL_SIZE: Deg<f32> = Deg(30.0);
+
+/// Brightness of the darker cells of the checkerboard (relative to the lighter ones).
+const DARK_CELL_BRIGHTNESS: f32 = 0.5;
+
+/// Returns the pattern's color at the given planetocentric longitude (from the central meridian) and latitude:
+/// the hue follows the longitude (red at 0°, a full cycle every 360°), the brightness increases from the south
+/// pole (0.25) to the north pole (1.0), and alternate `CELL_SIZE` cells are darkened.
+pub fn diagnostic_color(longitude: Deg<f32>, latitude: Deg<f32>) -> [f32; 3] {
+    let hue = (longitude.0 / 360.0).rem_euclid(1.0);
+    let brightness = 0.25 + 0.75 * ((latitude.0 + 90.0) / 180.0).max(0.0).min(1.0);
+    let cell = (longitude.0 / CELL_SIZE.0).floor() + (latitude.0 / CELL_SIZE.0).floor();
+    let cell_brightness = if cell.rem_euclid(2.0) == 0.0 { 1.0 } else { DARK_CELL_BRIGHTNESS };
+
+    hue_to_rgb(hue).map(|value| value * brightness * cell_brightness)
+}
+
+/// Returns fully saturated color of the given hue (0 to 1).
+fn hue_to_rgb(hue: f32) -> [f32; 3] {
+    [0.0, 4.0, 2.0].map(|offset: f32| (((hue * 6.0 + offset).rem_euclid(6.0) - 3.0).abs() - 1.0).max(0.0).min(1.0))
+}
+
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: [f32; 3], actual: [f32; 3]) {
+        assert!((0..3).all(|i| (expected[i] - actual[i]).abs() < 1.0e-5), "expected {:?}, got {:?}", expected, actual);
+    }
+
+    #[test]
+    fn hue_follows_longitude() {
+        assert_close([1.0, 0.0, 0.0], hue_to_rgb(0.0));
+        assert_close([1.0, 1.0, 0.0], hue_to_rgb(1.0 / 6.0));
+        assert_close([0.0, 1.0, 0.0], hue_to_rgb(2.0 / 6.0));
+        assert_close([0.0, 0.0, 1.0], hue_to_rgb(4.0 / 6.0));
+        assert_close([1.0, 0.0, 0.5], hue_to_rgb(11.0 / 12.0));
+    }
+
+    #[test]
+    fn known_colors() {
+        // central meridian, equator: red, light cell
+        assert_close([0.625, 0.0, 0.0], diagnostic_color(Deg(0.0), Deg(0.0)));
+        // 120° from the central meridian, north pole: green, dark cell
+        assert_close([0.0, DARK_CELL_BRIGHTNESS, 0.0], diagnostic_color(Deg(120.0), Deg(90.0)));
+        // just west of the central meridian and south of the equator: light cell
+        let brightness = 0.25 + 0.75 * 89.0 / 180.0;
+        assert_close([brightness, 0.0, brightness / 60.0], diagnostic_color(Deg(-1.0), Deg(-1.0)));
+
+        // adjacent cells differ in brightness
+        let west = diagnostic_color(Deg(-1.0), Deg(1.0));
+        let east = diagnostic_color(Deg(1.0), Deg(1.0));
+        assert!((west[0] - DARK_CELL_BRIGHTNESS * east[0]).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn longitude_wraps_around() {
+        assert_close(diagnostic_color(Deg(-90.0), Deg(10.0)), diagnostic_color(Deg(270.0), Deg(10.0)));
+    }
+}
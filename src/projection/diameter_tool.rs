@@ -0,0 +1,214 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Measurement of the disk by clicking the two ends of the equator in the source image.
+
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Point2};
+
+/// Points closer than this (in pixels) do not define a disk.
+const MIN_DISTANCE: f32 = 1.0;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Measurement {
+    pub center: Point2<f32>,
+    pub diameter: f32,
+    /// Direction of the line between the points, measured from the X axis towards Y (i.e., clockwise, as Y points
+    /// down); within (-90°; 90°].
+    pub equator_angle: Deg<f32>
+}
+
+impl Measurement {
+    /// Returns roll (see `SourceParameters::roll`) implied by the equator direction; `image_mirror` are the sign
+    /// multipliers undoing mirroring of the image (see `SourceParameters::image_mirror`).
+    pub fn roll(&self, image_mirror: [f32; 2]) -> Deg<f32> {
+        // mirroring by a single axis reverses the angle (as in `field_rotation::limb_angle`)
+        let angle = self.equator_angle.0 * image_mirror[0] * image_mirror[1];
+        Deg(if angle <= -90.0 { angle + 180.0 } else { angle })
+    }
+}
+
+/// Returns the disk with `p1` and `p2` at the ends of its equator; `None` if the points (nearly) coincide.
+pub fn measure(p1: Point2<f32>, p2: Point2<f32>) -> Option<Measurement> {
+    let delta = p2 - p1;
+    let diameter = delta.magnitude();
+    if diameter < MIN_DISTANCE { return None; }
+
+    let angle = delta.y.atan2(delta.x).to_degrees();
+    let equator_angle = if angle <= -90.0 { angle + 180.0 } else if angle > 90.0 { angle - 180.0 } else { angle };
+
+    Some(Measurement{ center: p1.midpoint(p2), diameter, equator_angle: Deg(equator_angle) })
+}
+
+/// Converts a screen position to image coordinates (in pixels, from the top-left corner of the first pixel) of
+/// an image of `image_size` shown at `img_pos` (screen) with `displayed_size`.
+pub fn image_position(
+    screen_pos: [f32; 2],
+    img_pos: [f32; 2],
+    displayed_size: [f32; 2],
+    image_size: [u32; 2]
+) -> Point2<f32> {
+    Point2{
+        x: (screen_pos[0] - img_pos[0]) / displayed_size[0] * image_size[0] as f32,
+        y: (screen_pos[1] - img_pos[1]) / displayed_size[1] * image_size[1] as f32
+    }
+}
+
+/// Inverse of `image_position`.
+pub fn screen_position(
+    image_pos: Point2<f32>,
+    img_pos: [f32; 2],
+    displayed_size: [f32; 2],
+    image_size: [u32; 2]
+) -> [f32; 2] {
+    [
+        img_pos[0] + image_pos.x / image_size[0] as f32 * displayed_size[0],
+        img_pos[1] + image_pos.y / image_size[1] as f32 * displayed_size[1]
+    ]
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum DiameterTool {
+    #[default]
+    Inactive,
+    AwaitingFirstPoint,
+    AwaitingSecondPoint(Point2<f32>)
+}
+
+impl DiameterTool {
+    pub fn active(&self) -> bool { *self != DiameterTool::Inactive }
+
+    pub fn activate(&mut self) { *self = DiameterTool::AwaitingFirstPoint; }
+
+    pub fn cancel(&mut self) { *self = DiameterTool::Inactive; }
+
+    pub fn first_point(&self) -> Option<Point2<f32>> {
+        match self {
+            DiameterTool::AwaitingSecondPoint(point) => Some(*point),
+            _ => None
+        }
+    }
+
+    /// Handles a click at `pos` (image coordinates); returns the measurement once both points have been placed
+    /// (the tool is then deactivated). A second point coinciding with the first one is ignored.
+    pub fn click(&mut self, pos: Point2<f32>) -> Option<Measurement> {
+        match *self {
+            DiameterTool::Inactive => None,
+
+            DiameterTool::AwaitingFirstPoint => {
+                *self = DiameterTool::AwaitingSecondPoint(pos);
+                None
+            },
+
+            DiameterTool::AwaitingSecondPoint(first) => {
+                let measurement = measure(first, pos);
+                if measurement.is_some() { *self = DiameterTool::Inactive; }
+                measurement
+            }
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: f32, actual: f32) {
+        assert!((expected - actual).abs() < 1.0e-4, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn measurement_gives_diameter_center_and_angle() {
+        let m = measure(Point2{ x: 10.0, y: 20.0 }, Point2{ x: 40.0, y: 60.0 }).unwrap();
+        assert_close(50.0, m.diameter);
+        assert_eq!(Point2{ x: 25.0, y: 40.0 }, m.center);
+        assert_close(53.130_1, m.equator_angle.0);
+
+        // the order of points does not matter
+        let reversed = measure(Point2{ x: 40.0, y: 60.0 }, Point2{ x: 10.0, y: 20.0 }).unwrap();
+        assert_close(m.diameter, reversed.diameter);
+        assert_eq!(m.center, reversed.center);
+        assert_close(m.equator_angle.0, reversed.equator_angle.0);
+
+        let horizontal = measure(Point2{ x: 50.0, y: 5.0 }, Point2{ x: 10.0, y: 5.0 }).unwrap();
+        assert_close(0.0, horizontal.equator_angle.0);
+        let vertical = measure(Point2{ x: 0.0, y: 50.0 }, Point2{ x: 0.0, y: 10.0 }).unwrap();
+        assert_close(90.0, vertical.equator_angle.0);
+
+        assert!(measure(Point2{ x: 3.0, y: 3.0 }, Point2{ x: 3.5, y: 3.0 }).is_none());
+    }
+
+    #[test]
+    fn roll_follows_mirroring() {
+        let m = measure(Point2{ x: 0.0, y: 0.0 }, Point2{ x: 10.0, y: 1.0 }).unwrap();
+        assert!(m.roll([1.0, 1.0]).0 > 5.0);
+        assert_close(-m.roll([1.0, 1.0]).0, m.roll([-1.0, 1.0]).0);
+        assert_close(m.roll([1.0, 1.0]).0, m.roll([-1.0, -1.0]).0);
+
+        // a vertical equator stays within (-90°; 90°]
+        let vertical = measure(Point2{ x: 0.0, y: 0.0 }, Point2{ x: 0.0, y: 10.0 }).unwrap();
+        assert_close(90.0, vertical.roll([-1.0, 1.0]).0);
+    }
+
+    #[test]
+    fn screen_and_image_positions_are_inverse() {
+        let (img_pos, displayed_size, image_size) = ([12.5, 40.0], [333.0, 250.0], [640, 480]);
+        for screen_pos in [[12.5, 40.0], [345.5, 290.0], [100.25, 77.75]] {
+            let image_pos = image_position(screen_pos, img_pos, displayed_size, image_size);
+            let back = screen_position(image_pos, img_pos, displayed_size, image_size);
+            assert_close(screen_pos[0], back[0]);
+            assert_close(screen_pos[1], back[1]);
+        }
+
+        assert_eq!(Point2{ x: 640.0, y: 480.0 }, image_position([345.5, 290.0], img_pos, displayed_size, image_size));
+        assert_eq!(Point2{ x: 320.0, y: 240.0 }, image_position([179.0, 165.0], img_pos, displayed_size, image_size));
+    }
+
+    #[test]
+    fn tool_measures_after_two_clicks() {
+        let mut tool = DiameterTool::default();
+        assert!(!tool.active());
+        assert_eq!(None, tool.click(Point2{ x: 1.0, y: 1.0 }));
+        assert!(!tool.active());
+
+        tool.activate();
+        assert_eq!(None, tool.click(Point2{ x: 10.0, y: 10.0 }));
+        assert_eq!(Some(Point2{ x: 10.0, y: 10.0 }), tool.first_point());
+
+        // coinciding point is ignored
+        assert_eq!(None, tool.click(Point2{ x: 10.0, y: 10.0 }));
+        assert!(tool.active());
+
+        let m = tool.click(Point2{ x: 110.0, y: 10.0 }).unwrap();
+        assert_close(100.0, m.diameter);
+        assert!(!tool.active());
+    }
+
+    #[test]
+    fn cancelling_discards_the_first_point() {
+        let mut tool = DiameterTool::default();
+        tool.activate();
+        tool.click(Point2{ x: 10.0, y: 10.0 });
+        tool.cancel();
+        assert!(!tool.active());
+        assert_eq!(None, tool.first_point());
+
+        tool.activate();
+        assert_eq!(None, tool.click(Point2{ x: 50.0, y: 10.0 }));
+        assert!(tool.active());
+    }
+}
@@ -0,0 +1,312 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Confirmation of the disk detected in the first loaded frame, before the loaded images are applied.
+
+use cgmath::Point2;
+use crate::config::Configuration;
+use crate::data;
+use crate::fmt;
+use crate::gpu::render_check;
+use crate::gui;
+use crate::gui::draw_buffer::{DrawBuffer, Sampling};
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+use crate::image_utils::BitDepth;
+use crate::logging;
+use crate::projection;
+use crate::projection::load_cache::FileStamp;
+use crate::projection::load_options_dialog::LoadOptions;
+use crate::projection::overlay_color;
+use crate::projection::source_view;
+use crate::projection::worker::{DiskInfo, FirstFrame};
+use glium::{Surface, Texture2d, uniform};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+pub const TITLE: &str = "Confirm planetary disk";
+
+/// Longer side of the preview (in pixels).
+const PREVIEW_SIZE: u32 = 480;
+
+const MAX_THRESHOLD_PERCENT: f32 = 50.0;
+
+/// Owner name of resources shown in the GPU resource registry.
+const REGISTRY_OWNER: &str = "Disk confirmation";
+
+pub enum DiskConfirmationResult {
+    Pending,
+    Accepted(DiskInfo),
+    /// The disk is accepted, but the user wants to adjust it in the source view.
+    AdjustManually(DiskInfo)
+}
+
+/// Loaded images awaiting confirmation of the disk detected in the first of them.
+pub struct DiskConfirmation {
+    pub textures: Vec<Rc<Texture2d>>,
+    /// Files corresponding to `textures`.
+    pub paths: Vec<PathBuf>,
+    /// Stamps of `paths`.
+    pub stamps: Vec<Option<FileStamp>>,
+    pub load_options: LoadOptions,
+    /// Bit depth of `textures`.
+    pub bit_depth: BitDepth,
+    /// Describes files skipped during loading; shown after the confirmation.
+    pub skipped_files_message: Option<String>,
+    /// CPU copy of `textures[0]`.
+    first_frame: ga_image::Image,
+    disk: Option<DiskInfo>,
+    /// Fraction of saturated pixels within `disk`.
+    saturated_fraction: Option<f32>,
+    threshold_percent: f32,
+    threshold_shown: bool,
+    preview: DrawBuffer,
+    texture_copy_prog: Rc<glium::Program>,
+    solid_color_3d_prog: Rc<glium::Program>,
+    unit_quad: Rc<glium::VertexBuffer<data::Vertex2>>,
+    unit_circle: Rc<glium::VertexBuffer<data::Vertex3>>,
+    /// Rendering has failed and is to be repeated.
+    render_pending: bool
+}
+
+impl DiskConfirmation {
+    pub fn new(
+        gl_objects: &projection::data::OpenGlObjects,
+        display: &glium::Display,
+        renderer: &Rc<std::cell::RefCell<imgui_glium_renderer::Renderer>>,
+        textures: Vec<Rc<Texture2d>>,
+        paths: Vec<PathBuf>,
+        stamps: Vec<Option<FileStamp>>,
+        load_options: LoadOptions,
+        bit_depth: BitDepth,
+        first_frame: FirstFrame,
+        skipped_files_message: Option<String>
+    ) -> DiskConfirmation {
+        let size = preview_size([first_frame.image.width(), first_frame.image.height()]);
+        let mut preview = DrawBuffer::new_with_size(
+            Sampling::Single,
+            &gl_objects.texture_copy_single,
+            &gl_objects.texture_copy_multi,
+            &gl_objects.unit_quad,
+            display,
+            renderer,
+            size[0],
+            size[1]
+        );
+        preview.register(REGISTRY_OWNER, "preview");
+
+        let mut confirmation = DiskConfirmation{
+            textures,
+            paths,
+            stamps,
+            load_options,
+            bit_depth,
+            skipped_files_message,
+            first_frame: first_frame.image,
+            disk: first_frame.disk,
+            saturated_fraction: None,
+            threshold_percent: crate::disk::DEFAULT_THRESHOLD_PERCENT,
+            threshold_shown: false,
+            preview,
+            texture_copy_prog: Rc::clone(&gl_objects.texture_copy_single),
+            solid_color_3d_prog: Rc::clone(&gl_objects.solid_color_3d),
+            unit_quad: Rc::clone(&gl_objects.unit_quad),
+            unit_circle: Rc::clone(&gl_objects.unit_circle),
+            render_pending: false
+        };
+        confirmation.update_saturated_fraction();
+        if let Some(fraction) = confirmation.excessive_saturation() {
+            logging::log_warning!("{:.1}% of the disk in the first frame is saturated.", fraction * 100.0);
+        }
+        confirmation.render();
+
+        confirmation
+    }
+
+    /// Returns the fraction of saturated disk pixels if it calls for a warning.
+    fn excessive_saturation(&self) -> Option<f32> {
+        self.saturated_fraction.filter(|fraction| *fraction > crate::disk::SATURATION_WARNING_FRACTION)
+    }
+
+    fn update_saturated_fraction(&mut self) {
+        self.saturated_fraction = self.disk.map(
+            |disk| crate::disk::saturated_fraction(&self.first_frame, disk.center, disk.diameter)
+        );
+    }
+
+    fn image_size(&self) -> [u32; 2] { [self.first_frame.width(), self.first_frame.height()] }
+
+    fn redetect(&mut self) {
+        self.disk = crate::disk::find_planetary_disk_with_threshold(&self.first_frame, self.threshold_percent)
+            .ok()
+            .map(|(center, diameter)| DiskInfo{ center, diameter });
+        self.update_saturated_fraction();
+        self.render();
+    }
+
+    fn render(&mut self) {
+        self.render_pending = !render_check::render_checked("disk preview", || self.try_render());
+    }
+
+    fn try_render(&self) -> Result<(), glium::DrawError> {
+        let mut target = self.preview.frame_buf();
+
+        let uniforms = uniform! {
+            source_texture: self.textures[0].sampled()
+        };
+
+        target.draw(
+            &*self.unit_quad,
+            &glium::index::NoIndices(glium::index::PrimitiveType::TriangleFan),
+            &self.texture_copy_prog,
+            &uniforms,
+            &Default::default()
+        )?;
+
+        if let Some(disk) = &self.disk {
+            let transform = source_view::disk_outline_transform(
+                disk.center,
+                disk.diameter,
+                self.image_size(),
+                self.preview.width() as f32 / self.preview.height() as f32
+            );
+            source_view::draw_disk_outline(
+                &mut target,
+                &self.unit_circle,
+                &self.solid_color_3d_prog,
+                &transform,
+                overlay_color::DEFAULT_COLOR
+            )?;
+        }
+
+        self.preview.update_storage_buf()
+    }
+}
+
+/// Returns size of the preview of an image of `image_size` (aspect ratio is preserved).
+fn preview_size(image_size: [u32; 2]) -> [u32; 2] {
+    let scale = PREVIEW_SIZE as f32 / image_size[0].max(image_size[1]).max(1) as f32;
+
+    [
+        ((image_size[0] as f32 * scale).round() as u32).max(1),
+        ((image_size[1] as f32 * scale).round() as u32).max(1)
+    ]
+}
+
+/// Starting point for a manual adjustment if no disk has been found: a disk in the middle of the image.
+fn default_disk(image_size: [u32; 2]) -> DiskInfo {
+    DiskInfo{
+        center: Point2{ x: image_size[0] as f32 / 2.0, y: image_size[1] as f32 / 2.0 },
+        diameter: image_size[0].min(image_size[1]) as f32 / 2.0
+    }
+}
+
+/// The dialog has to be opened beforehand with `ui.open_popup(TITLE)`.
+pub fn handle_disk_confirmation(
+    ui: &imgui::Ui,
+    config: &mut Configuration,
+    format: &fmt::Preferences,
+    confirmation: &mut DiskConfirmation
+) -> DiskConfirmationResult {
+    let mut result = DiskConfirmationResult::Pending;
+
+    if confirmation.render_pending { confirmation.render(); }
+
+    // the loaded images must not be discarded by accident, so Escape does nothing
+    let bindings = KeyBindings{ escape_cancels: false, enter_accepts: true };
+    modal::modal(ui, config, TITLE, bindings, |key_action, _| {
+        ui.text("Check the planetary disk found in the first frame:");
+        imgui::Image::new(
+            confirmation.preview.id(),
+            [confirmation.preview.width() as f32, confirmation.preview.height() as f32]
+        ).build(ui);
+
+        match &confirmation.disk {
+            Some(disk) => ui.text(format!(
+                "Center: ({}; {}), diameter: {} px",
+                fmt::format_number(disk.center.x as f64, 1, format),
+                fmt::format_number(disk.center.y as f64, 1, format),
+                fmt::format_number(disk.diameter as f64, 1, format)
+            )),
+            None => ui.text_colored([1.0, 0.3, 0.3, 1.0], "Planetary disk not found.")
+        }
+        if let Some(fraction) = confirmation.excessive_saturation() {
+            ui.text_colored([1.0, 0.8, 0.2, 1.0], format!(
+                "{}% of the disk is saturated (overexposed); detail is lost there.",
+                fmt::format_number(fraction as f64 * 100.0, 1, format)
+            ));
+            gui::tooltip(ui, "Saturated areas can be highlighted in the source view (\"disk\" section).");
+        }
+
+        if confirmation.threshold_shown {
+            gui::add_text_before(ui, "threshold");
+            let mut value = confirmation.threshold_percent;
+            if imgui::Slider::new("##disk-threshold", 0.0, MAX_THRESHOLD_PERCENT)
+                .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                .display_format("%0.1f%%")
+                .build(ui, &mut value)
+            {
+                confirmation.threshold_percent = value;
+                confirmation.redetect();
+            }
+            gui::tooltip(ui, "Values up to this percentage of the brightest pixel are treated as background. \
+                Increase it if the background is bright or the disk has a halo.");
+        }
+
+        ui.separator();
+        let token = ui.begin_disabled(confirmation.disk.is_none());
+        if modal::default_button(ui, "Accept") || (key_action == KeyAction::Accept && confirmation.disk.is_some()) {
+            result = DiskConfirmationResult::Accepted(confirmation.disk.unwrap());
+            ui.close_current_popup();
+        }
+        token.end();
+        ui.same_line();
+
+        if ui.button("Adjust manually") {
+            let disk = confirmation.disk.unwrap_or_else(|| default_disk(confirmation.image_size()));
+            result = DiskConfirmationResult::AdjustManually(disk);
+            ui.close_current_popup();
+        }
+        gui::tooltip(ui, "Accept and adjust the disk in the source view.");
+
+        if !confirmation.threshold_shown {
+            ui.same_line();
+            if ui.button("Re-detect with different threshold") { confirmation.threshold_shown = true; }
+        }
+    });
+
+    result
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preview_preserves_aspect_ratio() {
+        assert_eq!([PREVIEW_SIZE, PREVIEW_SIZE / 2], preview_size([1000, 500]));
+        assert_eq!([PREVIEW_SIZE * 3 / 4, PREVIEW_SIZE], preview_size([300, 400]));
+        assert_eq!([PREVIEW_SIZE, 1], preview_size([10000, 1]));
+    }
+
+    #[test]
+    fn default_disk_fits_in_image() {
+        let disk = default_disk([640, 480]);
+        assert_eq!(Point2{ x: 320.0, y: 240.0 }, disk.center);
+        assert_eq!(240.0, disk.diameter);
+    }
+}
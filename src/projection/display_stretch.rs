@@ -0,0 +1,273 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Display-only stretch of the projection view: black and white point chosen on a histogram of the projection.
+//! Exported images are not affected.
+
+use crate::data;
+use crate::gui;
+use crate::image_utils;
+use crate::logging;
+use glium::{Surface, Texture2d, uniform};
+use std::error::Error;
+
+pub const NUM_BINS: usize = 64;
+
+/// Maximum width of the downscaled copy of the projection used to compute the histogram.
+const READBACK_WIDTH: u32 = 256;
+
+/// Minimum distance between the black and white point.
+const MIN_RANGE: f32 = 1.0 / 64.0;
+
+const HISTOGRAM_HEIGHT: f32 = 60.0;
+const HISTOGRAM_BG_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+const HISTOGRAM_BAR_COLOR: [f32; 4] = [0.7, 0.7, 0.7, 1.0];
+const HANDLE_COLOR: [f32; 4] = [1.0, 0.8, 0.0, 1.0];
+pub const BADGE_COLOR: [f32; 4] = [1.0, 0.8, 0.0, 1.0];
+
+/// Black and white point (in [0; 1]).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Levels {
+    pub black: f32,
+    pub white: f32
+}
+
+impl Default for Levels {
+    fn default() -> Levels { Levels{ black: 0.0, white: 1.0 } }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Handle {
+    Black,
+    White
+}
+
+impl Levels {
+    pub fn is_neutral(&self) -> bool { self.black <= 0.0 && self.white >= 1.0 }
+
+    /// Returns values of the `black_point` and `stretch_scale` uniforms of `display_stretch.frag`.
+    pub fn uniforms(&self) -> (f32, f32) { (self.black, 1.0 / (self.white - self.black)) }
+
+    /// Returns levels with `handle` moved to `value` (clamped to keep the black point below the white point).
+    fn with_handle(&self, handle: Handle, value: f32) -> Levels {
+        match handle {
+            Handle::Black => Levels{ black: value.max(0.0).min(self.white - MIN_RANGE), ..*self },
+            Handle::White => Levels{ white: value.min(1.0).max(self.black + MIN_RANGE), ..*self }
+        }
+    }
+
+    fn nearest_handle(&self, value: f32) -> Handle {
+        if (value - self.black).abs() <= (value - self.white).abs() { Handle::Black } else { Handle::White }
+    }
+}
+
+/// Returns the level corresponding to horizontal position `x` over a histogram starting at `x0`.
+fn level_at(x: f32, x0: f32, width: f32) -> f32 { ((x - x0) / width).max(0.0).min(1.0) }
+
+/// Returns histogram (with `num_bins` bins) of luma of RGB8 `pixels`; black pixels (the background outside
+/// the projected disk) are skipped.
+pub fn histogram(pixels: &[u8], num_bins: usize) -> Vec<u32> {
+    let mut bins = vec![0; num_bins];
+    for rgb in pixels.chunks_exact(3) {
+        if rgb.iter().all(|value| *value == 0) { continue; }
+        let luma = (0.2126 * rgb[0] as f32 + 0.7152 * rgb[1] as f32 + 0.0722 * rgb[2] as f32) / 255.0;
+        bins[((luma * num_bins as f32) as usize).min(num_bins - 1)] += 1;
+    }
+
+    bins
+}
+
+/// Returns heights of the histogram's bars relative to the highest one.
+fn bar_heights(histogram: &[u32], log_scale: bool) -> Vec<f32> {
+    let scaled = |count: u32| if log_scale { (1.0 + count as f32).ln() } else { count as f32 };
+    let max = histogram.iter().map(|count| scaled(*count)).fold(0.0, f32::max);
+    histogram.iter().map(|count| if max > 0.0 { scaled(*count) / max } else { 0.0 }).collect()
+}
+
+/// Renders a downscaled copy of `texture` and returns its histogram.
+pub fn compute_histogram(
+    texture: &Texture2d,
+    facade: &dyn glium::backend::Facade,
+    unit_quad: &glium::VertexBuffer<data::Vertex2>,
+    texture_copy_prog: &glium::Program
+) -> Result<Vec<u32>, Box<dyn Error>> {
+    let width = READBACK_WIDTH.min(texture.width()).max(1);
+    let height = ((texture.height() as u64 * width as u64 / texture.width().max(1) as u64) as u32).max(1);
+    let downscaled = Texture2d::empty_with_format(
+        facade,
+        glium::texture::UncompressedFloatFormat::U8U8U8,
+        glium::texture::MipmapsOption::NoMipmap,
+        width,
+        height
+    )?;
+    downscaled.as_surface().draw(
+        unit_quad,
+        &glium::index::NoIndices(glium::index::PrimitiveType::TriangleFan),
+        texture_copy_prog,
+        &uniform!{ source_texture: texture.sampled() },
+        &Default::default()
+    )?;
+
+    Ok(histogram(image_utils::image_from_texture(&downscaled).raw_pixels(), NUM_BINS))
+}
+
+#[derive(Default)]
+pub struct DisplayStretch {
+    levels: Levels,
+    /// The histogram's vertical axis is logarithmic.
+    log_scale: bool,
+    /// `None` if it needs to be computed (again).
+    histogram: Option<Vec<u32>>,
+    dragged: Option<Handle>
+}
+
+impl DisplayStretch {
+    pub fn levels(&self) -> Levels { self.levels }
+
+    pub fn reset(&mut self) { self.levels = Levels::default(); }
+
+    /// To be called when the projection changes.
+    pub fn invalidate_histogram(&mut self) { self.histogram = None; }
+}
+
+/// Shows the histogram (calculated with `compute_histogram` if needed) with draggable black and white point; returns
+/// `true` if the levels have changed.
+pub fn handle_stretch_controls<F: FnOnce() -> Result<Vec<u32>, Box<dyn Error>>>(
+    ui: &imgui::Ui,
+    stretch: &mut DisplayStretch,
+    compute_histogram: F
+) -> bool {
+    let prev_levels = stretch.levels;
+
+    let histogram = stretch.histogram.get_or_insert_with(|| compute_histogram().unwrap_or_else(|e| {
+        logging::log_warning!("Failed to compute the projection's histogram: {}.", e);
+        vec![0; NUM_BINS]
+    }));
+
+    let width = ui.content_region_avail()[0].max(NUM_BINS as f32);
+    let pos = ui.cursor_screen_pos();
+    ui.invisible_button("##stretch-histogram", [width, HISTOGRAM_HEIGHT]);
+    let mouse_level = level_at(ui.io().mouse_pos[0], pos[0], width);
+    if ui.is_item_activated() {
+        stretch.dragged = Some(stretch.levels.nearest_handle(mouse_level));
+    }
+    match stretch.dragged {
+        Some(handle) if ui.is_item_active() => stretch.levels = stretch.levels.with_handle(handle, mouse_level),
+        _ => stretch.dragged = None
+    }
+    gui::tooltip(ui, "Drag the black or white point (applied only to the display, not to exported images).");
+
+    let draw_list = ui.get_window_draw_list();
+    draw_list.add_rect(pos, [pos[0] + width, pos[1] + HISTOGRAM_HEIGHT], HISTOGRAM_BG_COLOR).filled(true).build();
+    let bin_width = width / histogram.len() as f32;
+    for (i, height) in bar_heights(histogram, stretch.log_scale).iter().enumerate() {
+        let x = pos[0] + i as f32 * bin_width;
+        draw_list
+            .add_rect(
+                [x, pos[1] + (1.0 - height) * HISTOGRAM_HEIGHT],
+                [x + bin_width, pos[1] + HISTOGRAM_HEIGHT],
+                HISTOGRAM_BAR_COLOR
+            )
+            .filled(true)
+            .build();
+    }
+    for level in [stretch.levels.black, stretch.levels.white] {
+        let x = pos[0] + level * width;
+        draw_list.add_line([x, pos[1]], [x, pos[1] + HISTOGRAM_HEIGHT], HANDLE_COLOR).thickness(2.0).build();
+    }
+
+    ui.checkbox("log. scale##stretch", &mut stretch.log_scale);
+    gui::tooltip(ui, "Logarithmic vertical axis of the histogram (shows faint features' values better).");
+    ui.same_line();
+    if ui.button("reset##stretch") { stretch.reset(); }
+
+    stretch.levels != prev_levels
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_skips_background() {
+        let pixels = [
+            0, 0, 0,
+            0, 0, 0,
+            255, 255, 255,
+            128, 128, 128,
+            10, 0, 0
+        ];
+        let bins = histogram(&pixels, 4);
+        assert_eq!(vec![1, 0, 1, 1], bins);
+    }
+
+    #[test]
+    fn histogram_uses_luma() {
+        // pure green is brighter than pure blue
+        let bins = histogram(&[0, 255, 0, 0, 0, 255], 10);
+        assert_eq!(1, bins[7]);
+        assert_eq!(1, bins[0]);
+    }
+
+    #[test]
+    fn bar_heights_are_relative_to_maximum() {
+        assert_eq!(vec![0.0, 0.5, 1.0], bar_heights(&[0, 50, 100], false));
+        let log = bar_heights(&[0, 9, 99], true);
+        assert_eq!(0.0, log[0]);
+        assert!((log[1] - 0.5).abs() < 1.0e-6);
+        assert_eq!(1.0, log[2]);
+        assert_eq!(vec![0.0, 0.0], bar_heights(&[0, 0], true));
+    }
+
+    #[test]
+    fn neutral_levels_map_to_identity() {
+        let levels = Levels::default();
+        assert!(levels.is_neutral());
+        assert_eq!((0.0, 1.0), levels.uniforms());
+    }
+
+    #[test]
+    fn handles_map_to_uniforms() {
+        let levels = Levels::default()
+            .with_handle(Handle::Black, level_at(125.0, 100.0, 100.0))
+            .with_handle(Handle::White, level_at(175.0, 100.0, 100.0));
+        assert_eq!(Levels{ black: 0.25, white: 0.75 }, levels);
+        assert!(!levels.is_neutral());
+        // values at the black and white point are mapped to 0 and 1
+        let (black_point, scale) = levels.uniforms();
+        assert_eq!(0.0, (0.25 - black_point) * scale);
+        assert_eq!(1.0, (0.75 - black_point) * scale);
+    }
+
+    #[test]
+    fn handles_cannot_cross() {
+        let levels = Levels{ black: 0.25, white: 0.75 };
+        assert_eq!(0.75 - MIN_RANGE, levels.with_handle(Handle::Black, 0.9).black);
+        assert_eq!(0.25 + MIN_RANGE, levels.with_handle(Handle::White, 0.1).white);
+        assert_eq!(0.0, levels.with_handle(Handle::Black, level_at(50.0, 100.0, 100.0)).black);
+        assert_eq!(1.0, levels.with_handle(Handle::White, level_at(250.0, 100.0, 100.0)).white);
+    }
+
+    #[test]
+    fn nearest_handle_is_dragged() {
+        let levels = Levels{ black: 0.25, white: 0.75 };
+        assert_eq!(Handle::Black, levels.nearest_handle(0.0));
+        assert_eq!(Handle::Black, levels.nearest_handle(0.45));
+        assert_eq!(Handle::White, levels.nearest_handle(0.55));
+    }
+}
@@ -0,0 +1,373 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Low-precision prediction of the apparent orientation of a planet's rotation axis.
+//!
+//! Heliocentric positions are computed from mean Keplerian elements (E. M. Standish, "Keplerian Elements for
+//! Approximate Positions of the Major Planets", valid 1800-2050), pole directions from the IAU WGCCRE report.
+//! Results are accurate to ca. 0.1°; the difference between UTC and TT is ignored.
+//! Equatorial coordinates refer to the J2000 equinox (precession is ignored).
+
+use crate::projection::Planet;
+
+const J2000: f64 = 2451545.0;
+const DAYS_PER_CENTURY: f64 = 36525.0;
+/// Obliquity of the ecliptic at J2000.
+const OBLIQUITY_J2000: f64 = 23.43928;
+/// Speed of light in AU per day.
+const SPEED_OF_LIGHT: f64 = 173.1446;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Body {
+    Venus,
+    Mars,
+    Jupiter,
+    Saturn
+}
+
+impl From<Planet> for Body {
+    fn from(planet: Planet) -> Body {
+        match planet {
+            Planet::Jupiter => Body::Jupiter,
+            Planet::Mars => Body::Mars,
+            Planet::Venus => Body::Venus
+        }
+    }
+}
+
+/// Predicted apparent orientation of a planet's rotation axis as seen from Earth.
+#[derive(Copy, Clone, Debug)]
+pub struct AxisOrientation {
+    /// Planetocentric latitude of the sub-Earth point (degrees); positive if the north pole is tilted towards
+    /// the observer.
+    pub sub_earth_latitude: f64,
+    /// Position angle of the north pole (degrees, in (-180°, 180°]), measured from celestial north towards east.
+    pub position_angle: f64,
+    /// Angle Sun-planet-Earth (degrees).
+    pub phase_angle: f64,
+    /// Position angle of the bright limb's midpoint (degrees, in (-180°, 180°]), measured from celestial north
+    /// towards east.
+    pub bright_limb_position_angle: f64,
+    /// Geocentric right ascension of the planet (degrees, in (-180°, 180°]).
+    pub right_ascension: f64,
+    /// Geocentric declination of the planet (degrees).
+    pub declination: f64
+}
+
+/// Mean orbital elements and their rates per Julian century.
+struct OrbitalElements {
+    semi_major_axis: [f64; 2],
+    eccentricity: [f64; 2],
+    inclination: [f64; 2],
+    mean_longitude: [f64; 2],
+    perihelion_longitude: [f64; 2],
+    ascending_node_longitude: [f64; 2]
+}
+
+const EARTH_MOON_BARYCENTER: OrbitalElements = OrbitalElements{
+    semi_major_axis: [1.00000261, 0.00000562],
+    eccentricity: [0.01671123, -0.00004392],
+    inclination: [-0.00001531, -0.01294668],
+    mean_longitude: [100.46457166, 35999.37244981],
+    perihelion_longitude: [102.93768193, 0.32327364],
+    ascending_node_longitude: [0.0, 0.0]
+};
+
+impl Body {
+    fn orbital_elements(&self) -> OrbitalElements {
+        match self {
+            Body::Venus => OrbitalElements{
+                semi_major_axis: [0.72333566, 0.00000390],
+                eccentricity: [0.00677672, -0.00004107],
+                inclination: [3.39467605, -0.00078890],
+                mean_longitude: [181.97909950, 58517.81538729],
+                perihelion_longitude: [131.60246718, 0.00268329],
+                ascending_node_longitude: [76.67984255, -0.27769418]
+            },
+
+            Body::Mars => OrbitalElements{
+                semi_major_axis: [1.52371034, 0.00001847],
+                eccentricity: [0.09339410, 0.00007882],
+                inclination: [1.84969142, -0.00813131],
+                mean_longitude: [-4.55343205, 19140.30268499],
+                perihelion_longitude: [-23.94362959, 0.44441088],
+                ascending_node_longitude: [49.55953891, -0.29257343]
+            },
+
+            Body::Jupiter => OrbitalElements{
+                semi_major_axis: [5.20288700, -0.00011607],
+                eccentricity: [0.04838624, -0.00013253],
+                inclination: [1.30439695, -0.00183714],
+                mean_longitude: [34.39644051, 3034.74612775],
+                perihelion_longitude: [14.72847983, 0.21252668],
+                ascending_node_longitude: [100.47390909, 0.20469106]
+            },
+
+            Body::Saturn => OrbitalElements{
+                semi_major_axis: [9.53667594, -0.00125060],
+                eccentricity: [0.05386179, -0.00050991],
+                inclination: [2.48599187, 0.00193609],
+                mean_longitude: [49.95424423, 1222.49362201],
+                perihelion_longitude: [92.59887831, -0.41897216],
+                ascending_node_longitude: [113.66242448, -0.28867794]
+            }
+        }
+    }
+
+    /// Returns right ascension and declination (degrees, J2000 equator) of the north pole.
+    fn north_pole(&self, centuries: f64) -> (f64, f64) {
+        match self {
+            Body::Venus => (272.76, 67.16),
+            Body::Mars => (317.68143 - 0.1061 * centuries, 52.88650 - 0.0609 * centuries),
+            Body::Jupiter => (268.056595 - 0.006499 * centuries, 64.495303 + 0.002413 * centuries),
+            Body::Saturn => (40.589 - 0.036 * centuries, 83.537 - 0.004 * centuries)
+        }
+    }
+}
+
+/// Returns the Julian date corresponding to the given UTC calendar date and time (Gregorian calendar).
+pub fn julian_date(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: f64) -> f64 {
+    let (y, m) = if month <= 2 { (year - 1, month + 12) } else { (year, month) };
+    let a = (y as f64 / 100.0).floor();
+    let b = 2.0 - a + (a / 4.0).floor();
+    let day_fraction = (hour as f64 + minute as f64 / 60.0 + second / 3600.0) / 24.0;
+
+    (365.25 * (y as f64 + 4716.0)).floor() + (30.6001 * (m as f64 + 1.0)).floor() + day as f64 + day_fraction + b - 1524.5
+}
+
+/// Returns the Gregorian calendar date (year, month, day) of the day starting at Julian date
+/// `julian_day_number - 0.5`.
+pub fn calendar_date(julian_day_number: i64) -> (i32, u32, u32) {
+    let z = julian_day_number as f64;
+    let alpha = ((z - 1867216.25) / 36524.25).floor();
+    let a = z + 1.0 + alpha - (alpha / 4.0).floor();
+    let b = a + 1524.0;
+    let c = ((b - 122.1) / 365.25).floor();
+    let d = (365.25 * c).floor();
+    let e = ((b - d) / 30.6001).floor();
+
+    let day = (b - d - (30.6001 * e).floor()) as u32;
+    let month = if e < 14.0 { e - 1.0 } else { e - 13.0 } as u32;
+    let year = if month > 2 { c - 4716.0 } else { c - 4715.0 } as i32;
+
+    (year, month, day)
+}
+
+/// Parses UTC date and time given as "YYYY-MM-DD HH:MM" or "YYYY-MM-DD HH:MM:SS" and returns the Julian date.
+pub fn parse_utc(s: &str) -> Option<f64> {
+    let mut parts = s.trim().split_whitespace();
+    let date = parts.next()?;
+    let time = parts.next()?;
+    if parts.next().is_some() { return None; }
+
+    let date: Vec<&str> = date.split('-').collect();
+    if date.len() != 3 { return None; }
+    let year: i32 = date[0].parse().ok()?;
+    let month: u32 = date[1].parse().ok()?;
+    let day: u32 = date[2].parse().ok()?;
+
+    let time: Vec<&str> = time.split(':').collect();
+    if time.len() < 2 || time.len() > 3 { return None; }
+    let hour: u32 = time[0].parse().ok()?;
+    let minute: u32 = time[1].parse().ok()?;
+    let second: f64 = if time.len() == 3 { time[2].parse().ok()? } else { 0.0 };
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59
+        || !(0.0..60.0).contains(&second) {
+        return None;
+    }
+
+    Some(julian_date(year, month, day, hour, minute, second))
+}
+
+/// Returns the Greenwich mean sidereal time (degrees, in [0°; 360°)) at the given Julian date (UT).
+pub fn greenwich_sidereal_time(julian_date: f64) -> f64 {
+    let t = (julian_date - J2000) / DAYS_PER_CENTURY;
+    let value = 280.46061837 + 360.98564736629 * (julian_date - J2000) + 0.000387933 * t * t - t * t * t / 38710000.0;
+
+    value.rem_euclid(360.0)
+}
+
+/// Returns heliocentric position (AU, J2000 equatorial frame).
+fn heliocentric_position(elements: &OrbitalElements, centuries: f64) -> [f64; 3] {
+    let value = |e: &[f64; 2]| e[0] + e[1] * centuries;
+
+    let a = value(&elements.semi_major_axis);
+    let e = value(&elements.eccentricity);
+    let incl = value(&elements.inclination).to_radians();
+    let mean_longitude = value(&elements.mean_longitude);
+    let perihelion_longitude = value(&elements.perihelion_longitude);
+    let node = value(&elements.ascending_node_longitude);
+
+    let arg_of_perihelion = (perihelion_longitude - node).to_radians();
+    let node = node.to_radians();
+    let mean_anomaly = (mean_longitude - perihelion_longitude).rem_euclid(360.0).to_radians();
+
+    let mut ecc_anomaly = mean_anomaly;
+    for _ in 0..10 {
+        ecc_anomaly -= (ecc_anomaly - e * ecc_anomaly.sin() - mean_anomaly) / (1.0 - e * ecc_anomaly.cos());
+    }
+
+    let xo = a * (ecc_anomaly.cos() - e);
+    let yo = a * (1.0 - e * e).sqrt() * ecc_anomaly.sin();
+
+    let (sin_w, cos_w) = arg_of_perihelion.sin_cos();
+    let (sin_n, cos_n) = node.sin_cos();
+    let (sin_i, cos_i) = incl.sin_cos();
+
+    let x = (cos_w * cos_n - sin_w * sin_n * cos_i) * xo + (-sin_w * cos_n - cos_w * sin_n * cos_i) * yo;
+    let y = (cos_w * sin_n + sin_w * cos_n * cos_i) * xo + (-sin_w * sin_n + cos_w * cos_n * cos_i) * yo;
+    let z = sin_w * sin_i * xo + cos_w * sin_i * yo;
+
+    let (sin_eps, cos_eps) = OBLIQUITY_J2000.to_radians().sin_cos();
+
+    [x, cos_eps * y - sin_eps * z, sin_eps * y + cos_eps * z]
+}
+
+/// Predicts the orientation of `body`'s rotation axis as seen from Earth at the specified Julian date.
+pub fn axis_orientation(body: Body, julian_date: f64) -> AxisOrientation {
+    let centuries = |jd: f64| (jd - J2000) / DAYS_PER_CENTURY;
+
+    let earth = heliocentric_position(&EARTH_MOON_BARYCENTER, centuries(julian_date));
+    let elements = body.orbital_elements();
+
+    // correct for light-time
+    let mut light_time = 0.0;
+    let mut dir = [0.0; 3];
+    for _ in 0..3 {
+        let planet = heliocentric_position(&elements, centuries(julian_date - light_time));
+        for i in 0..3 { dir[i] = planet[i] - earth[i]; }
+        light_time = (dir[0] * dir[0] + dir[1] * dir[1] + dir[2] * dir[2]).sqrt() / SPEED_OF_LIGHT;
+    }
+    let dist = light_time * SPEED_OF_LIGHT;
+    for value in dir.iter_mut() { *value /= dist; }
+
+    let planet = heliocentric_position(&elements, centuries(julian_date - light_time));
+    let sun_dist = (planet[0] * planet[0] + planet[1] * planet[1] + planet[2] * planet[2]).sqrt();
+    let cos_phase = (planet[0] * dir[0] + planet[1] * dir[1] + planet[2] * dir[2]) / sun_dist;
+    let phase_angle = cos_phase.max(-1.0).min(1.0).acos();
+
+    let ra = dir[1].atan2(dir[0]);
+    let dec = dir[2].asin();
+
+    let (pole_ra, pole_dec) = body.north_pole(centuries(julian_date));
+    let (pole_ra, pole_dec) = (pole_ra.to_radians(), pole_dec.to_radians());
+    let pole = [pole_dec.cos() * pole_ra.cos(), pole_dec.cos() * pole_ra.sin(), pole_dec.sin()];
+
+    let sub_earth_latitude = -(pole[0] * dir[0] + pole[1] * dir[1] + pole[2] * dir[2]).asin();
+    // position angle (at the planet) of the direction towards a point on the celestial sphere
+    let position_angle_of = |ra_to: f64, dec_to: f64| (dec_to.cos() * (ra_to - ra).sin()).atan2(
+        dec_to.sin() * dec.cos() - dec_to.cos() * dec.sin() * (ra_to - ra).cos()
+    );
+    let position_angle = position_angle_of(pole_ra, pole_dec);
+
+    // the Sun is seen from Earth in the direction opposite to Earth's heliocentric position
+    let sun_ra = (-earth[1]).atan2(-earth[0]);
+    let sun_dec = (-earth[2] / (earth[0] * earth[0] + earth[1] * earth[1] + earth[2] * earth[2]).sqrt()).asin();
+
+    AxisOrientation{
+        sub_earth_latitude: sub_earth_latitude.to_degrees(),
+        position_angle: position_angle.to_degrees(),
+        phase_angle: phase_angle.to_degrees(),
+        bright_limb_position_angle: position_angle_of(sun_ra, sun_dec).to_degrees(),
+        right_ascension: ra.to_degrees(),
+        declination: dec.to_degrees()
+    }
+}
+
+mod tests {
+    use super::*;
+
+    // reference values: J. Meeus, "Astronomical Algorithms", 2nd ed., examples 42.a, 43.a, 45.a
+
+    #[test]
+    fn julian_date_of_known_epochs() {
+        assert_eq!(J2000, julian_date(2000, 1, 1, 12, 0, 0.0));
+        assert_eq!(2448972.5, julian_date(1992, 12, 16, 0, 0, 0.0));
+        assert_eq!(Some(2451545.25), parse_utc("2000-01-01 18:00"));
+        assert_eq!(Some(2451545.25), parse_utc(" 2000-01-01  18:00:00 "));
+    }
+
+    #[test]
+    fn calendar_date_of_known_epochs() {
+        assert_eq!((2000, 1, 1), calendar_date(2451545));
+        assert_eq!((1992, 12, 16), calendar_date(2448973));
+        assert_eq!((2024, 2, 29), calendar_date((julian_date(2024, 2, 29, 23, 59, 0.0) + 0.5).floor() as i64));
+        assert_eq!((2023, 3, 1), calendar_date((julian_date(2023, 3, 1, 0, 0, 0.0) + 0.5).floor() as i64));
+    }
+
+    #[test]
+    fn sidereal_time_of_known_epochs() {
+        // Meeus, examples 12.a, 12.b
+        assert!((greenwich_sidereal_time(2446895.5) - 197.693195).abs() < 1.0e-4);
+        assert!((greenwich_sidereal_time(julian_date(1987, 4, 10, 19, 21, 0.0)) - 128.7378734).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn invalid_dates_are_rejected() {
+        assert_eq!(None, parse_utc(""));
+        assert_eq!(None, parse_utc("2000-01-01"));
+        assert_eq!(None, parse_utc("2000-13-01 00:00"));
+        assert_eq!(None, parse_utc("2000-01-01 24:00"));
+        assert_eq!(None, parse_utc("2000/01/01 12:00"));
+    }
+
+    /// Accuracy (degrees) stated in the module documentation.
+    const ORIENTATION_TOLERANCE: f64 = 0.1;
+
+    fn assert_orientation(body: Body, jd: f64, sub_earth_latitude: f64, position_angle: f64) {
+        let result = axis_orientation(body, jd);
+        assert!((result.sub_earth_latitude - sub_earth_latitude).abs() < ORIENTATION_TOLERANCE, "{:?}", result);
+        assert!((result.position_angle - position_angle).abs() < ORIENTATION_TOLERANCE, "{:?}", result);
+    }
+
+    #[test]
+    fn mars_orientation() {
+        assert_orientation(Body::Mars, 2448935.5, 12.44, 347.64 - 360.0);
+    }
+
+    #[test]
+    fn jupiter_orientation() {
+        assert_orientation(Body::Jupiter, 2448972.5, -2.48, 24.80);
+    }
+
+    #[test]
+    fn saturn_orientation() {
+        // Meeus gives ring plane parameters; Saturn's equator coincides with the ring plane
+        assert_orientation(Body::Saturn, 2448972.5, 16.44, 6.74);
+    }
+
+    #[test]
+    fn venus_at_greatest_elongation_is_half_lit_in_the_west() {
+        // greatest eastern elongation (evening visibility): the Sun is to the west of Venus
+        let result = axis_orientation(Body::Venus, julian_date(2023, 6, 4, 0, 0, 0.0));
+        assert!((result.phase_angle - 90.0).abs() < 5.0, "{:?}", result);
+        let bright_limb = result.bright_limb_position_angle;
+        assert!(bright_limb > -150.0 && bright_limb < -30.0, "{:?}", result);
+    }
+
+    #[test]
+    fn outer_planets_show_small_phase_angles() {
+        for days in (0..800).step_by(20) {
+            let jd = J2000 + days as f64;
+            assert!(axis_orientation(Body::Mars, jd).phase_angle < 48.0);
+            assert!(axis_orientation(Body::Jupiter, jd).phase_angle < 12.0);
+        }
+    }
+}
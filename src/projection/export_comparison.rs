@@ -0,0 +1,429 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Comparison of a projection view's current map with the one it showed when its last export started, to see how
+//! far the settings have drifted from what has already been saved.
+
+use crate::data;
+use crate::fmt;
+use crate::gpu::registry;
+use crate::gui;
+use crate::gui::DrawBuffer;
+use crate::gui::motion::AnimationSource;
+use crate::logging;
+use crate::projection;
+use glium::{Surface, Texture2d, uniform};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use strum::IntoEnumIterator;
+
+/// Longer side (in pixels) of the remembered copy of the exported map.
+const MAX_SNAPSHOT_SIZE: u32 = 1024;
+
+/// Size (in pixels) of the downscaled difference used to compute the RMS difference.
+const RMS_SIZE: u32 = 128;
+
+/// Multiplier of the displayed difference (the RMS difference is computed without it).
+const DIFFERENCE_GAIN: f32 = 4.0;
+
+const BLINK_INTERVAL: Duration = Duration::from_millis(500);
+
+pub const BLINK_ANIMATION: AnimationSource =
+    AnimationSource{ name: "export comparison blink", duration: blink_interval };
+
+/// Returns the interval of switching maps in the blink display; zero if they are to be switched manually.
+fn blink_interval(gui_state: &gui::GuiState) -> Duration { gui_state.animation_duration(BLINK_INTERVAL) }
+
+/// Values of the `mode` uniform of `map_comparison.frag`.
+#[derive(Copy, Clone)]
+enum Mode {
+    Current = 0,
+    Exported = 1,
+    Difference = 2
+}
+
+#[derive(Copy, Clone, PartialEq, strum::EnumIter)]
+enum Display {
+    Difference,
+    /// Alternates between the current and the exported map.
+    Blink
+}
+
+impl Display {
+    fn name(&self) -> &str {
+        match self {
+            Display::Difference => "difference",
+            Display::Blink => "blink"
+        }
+    }
+}
+
+/// Keeps the snapshot of the most recent successful export. A snapshot is taken when an export starts; it replaces
+/// the remembered one only once the export succeeds (and the remembered one is invalidated already at the start).
+struct LastExport<T> {
+    pending: Option<T>,
+    remembered: Option<T>
+}
+
+impl<T> Default for LastExport<T> {
+    fn default() -> LastExport<T> { LastExport{ pending: None, remembered: None } }
+}
+
+impl<T> LastExport<T> {
+    /// `snapshot`: `None` if it could not be taken.
+    fn on_export_started(&mut self, snapshot: Option<T>) {
+        self.remembered = None;
+        self.pending = snapshot;
+    }
+
+    fn on_export_ended(&mut self, succeeded: bool) {
+        let pending = self.pending.take();
+        if succeeded { self.remembered = pending; }
+    }
+
+    fn clear(&mut self) { self.remembered = None; }
+
+    fn remembered(&self) -> Option<&T> { self.remembered.as_ref() }
+}
+
+/// Copy of the map shown when an export started.
+struct ExportedMap {
+    /// Downscaled; has the same orientation as the view's projection buffer.
+    texture: Texture2d,
+    /// Frame shown by the view.
+    frame_idx: usize,
+    _registration: registry::Registration
+}
+
+pub struct ExportComparison {
+    last_export: LastExport<ExportedMap>,
+    shown: bool,
+    display: Display,
+    /// RMS difference (as a fraction of the full brightness range) between the current and the exported map.
+    rms: Option<f32>,
+    /// Last change of the blinking display.
+    blink_time: Instant,
+    blink_shows_exported: bool,
+    owner: String,
+    gl_display: glium::Display,
+    prog: Rc<glium::Program>,
+    unit_quad: Rc<glium::VertexBuffer<data::Vertex2>>
+}
+
+impl ExportComparison {
+    pub fn new(
+        gl_objects: &projection::data::OpenGlObjects,
+        display: &glium::Display,
+        registry_owner: &str
+    ) -> ExportComparison {
+        ExportComparison{
+            last_export: Default::default(),
+            shown: false,
+            display: Display::Difference,
+            rms: None,
+            blink_time: Instant::now(),
+            blink_shows_exported: false,
+            owner: registry_owner.to_string(),
+            gl_display: display.clone(),
+            prog: Rc::clone(&gl_objects.map_comparison),
+            unit_quad: Rc::clone(&gl_objects.unit_quad)
+        }
+    }
+
+    /// Returns `true` if the comparison is to be drawn instead of the current map.
+    pub fn is_shown(&self) -> bool { self.shown && self.last_export.remembered().is_some() }
+
+    /// Takes a snapshot of `map` (the view's projection buffer, showing frame `frame_idx`) as the export starts.
+    pub fn on_export_started(&mut self, map: &DrawBuffer, frame_idx: usize) {
+        let size = snapshot_size([map.width(), map.height()], MAX_SNAPSHOT_SIZE);
+        let snapshot = Texture2d::empty_with_format(
+            &self.gl_display,
+            glium::texture::UncompressedFloatFormat::U8U8U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            size[0],
+            size[1]
+        ).map(|texture| {
+            map.storage_buf().as_surface().fill(&texture.as_surface(), glium::uniforms::MagnifySamplerFilter::Linear);
+            let registration = registry::register_texture(
+                &self.owner, "last export", &texture, glium::texture::UncompressedFloatFormat::U8U8U8
+            );
+            ExportedMap{ texture, frame_idx, _registration: registration }
+        });
+        if let Err(e) = &snapshot {
+            logging::log_warning!("Could not remember the exported map for comparison: {}.", e);
+        }
+        self.last_export.on_export_started(snapshot.ok());
+        self.rms = None;
+    }
+
+    /// Remembers the snapshot taken at the start of the export if it has `succeeded`; `current_map`: the view's
+    /// projection buffer.
+    pub fn on_export_ended(&mut self, succeeded: bool, current_map: &Texture2d) {
+        self.last_export.on_export_ended(succeeded);
+        self.update_rms(current_map);
+    }
+
+    /// Forgets the remembered map.
+    pub fn clear(&mut self) {
+        self.last_export.clear();
+        self.rms = None;
+    }
+
+    /// Computes the RMS difference between `current_map` (the view's projection buffer) and the remembered map.
+    pub fn update_rms(&mut self, current_map: &Texture2d) {
+        self.rms = None;
+        if !self.is_shown() { return; }
+
+        let texture = match Texture2d::empty_with_format(
+            &self.gl_display,
+            glium::texture::UncompressedFloatFormat::U8U8U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            RMS_SIZE,
+            RMS_SIZE
+        ) {
+            Ok(texture) => texture,
+            Err(_) => return
+        };
+        if self.draw(&mut texture.as_surface(), current_map, Mode::Difference, 1.0, (0.0, 1.0)).is_ok() {
+            self.rms = Some(rms_difference(&crate::image_utils::image_from_texture(&texture)));
+        }
+    }
+
+    /// Draws the comparison of `current_map` (the view's projection buffer) and the remembered map into `target`;
+    /// `levels`: black point and stretch scale of the display stretch.
+    pub fn render<S: Surface>(
+        &self,
+        target: &mut S,
+        current_map: &Texture2d,
+        levels: (f32, f32)
+    ) -> Result<(), glium::DrawError> {
+        let mode = match self.display {
+            Display::Difference => Mode::Difference,
+            Display::Blink => if self.blink_shows_exported { Mode::Exported } else { Mode::Current }
+        };
+
+        self.draw(target, current_map, mode, DIFFERENCE_GAIN, levels)
+    }
+
+    fn draw<S: Surface>(
+        &self,
+        target: &mut S,
+        current_map: &Texture2d,
+        mode: Mode,
+        difference_gain: f32,
+        levels: (f32, f32)
+    ) -> Result<(), glium::DrawError> {
+        let exported = match self.last_export.remembered() {
+            Some(exported) => exported,
+            None => return Ok(())
+        };
+
+        let uniforms = uniform! {
+            current_map: current_map.sampled(),
+            exported_map: exported.texture.sampled()
+                .minify_filter(glium::uniforms::MinifySamplerFilter::Linear)
+                .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear),
+            mode: mode as i32,
+            difference_gain: difference_gain,
+            black_point: levels.0,
+            stretch_scale: levels.1
+        };
+
+        target.draw(
+            &*self.unit_quad,
+            &glium::index::NoIndices(glium::index::PrimitiveType::TriangleFan),
+            &self.prog,
+            &uniforms,
+            &Default::default()
+        )
+    }
+}
+
+/// Returns the size of a copy of an image of `size`, downscaled (keeping the aspect ratio) to fit in `max_size`.
+fn snapshot_size(size: [u32; 2], max_size: u32) -> [u32; 2] {
+    let longer = size[0].max(size[1]);
+    if longer <= max_size { return size; }
+
+    let scale = max_size as f64 / longer as f64;
+    [
+        ((size[0] as f64 * scale).round() as u32).max(1),
+        ((size[1] as f64 * scale).round() as u32).max(1)
+    ]
+}
+
+/// Returns the RMS value (as a fraction of the full range) of all values of `difference` (RGB8).
+fn rms_difference(difference: &ga_image::Image) -> f32 {
+    let mut sum_sq = 0.0f64;
+    let mut num_values = 0usize;
+    for y in 0..difference.height() {
+        for value in &difference.line::<u8>(y)[..3 * difference.width() as usize] {
+            sum_sq += (*value as f64 / 255.0).powi(2);
+            num_values += 1;
+        }
+    }
+
+    if num_values == 0 { 0.0 } else { (sum_sq / num_values as f64).sqrt() as f32 }
+}
+
+/// Shows the comparison controls of a projection view (which currently shows frame `frame_idx` in `current_map`, its
+/// projection buffer); returns `true` if the view has to be rendered again.
+pub fn handle_export_comparison(
+    ui: &imgui::Ui,
+    gui_state: &gui::GuiState,
+    comparison: &mut ExportComparison,
+    view_id: u32,
+    current_map: &Texture2d,
+    frame_idx: usize
+) -> bool {
+    let mut changed = false;
+
+    let exported_frame = comparison.last_export.remembered().map(|exported| exported.frame_idx);
+    let token = ui.begin_disabled(exported_frame.is_none());
+    if ui.checkbox(format!("compare with last export##{}", view_id), &mut comparison.shown) {
+        comparison.blink_time = Instant::now();
+        comparison.blink_shows_exported = false;
+        comparison.update_rms(current_map);
+        changed = true;
+    }
+    token.end();
+    gui::tooltip(ui, if exported_frame.is_some() {
+        "Shows how the current map differs from the map displayed when the last export of this view started."
+    } else {
+        "Available after an export of this view finishes."
+    });
+
+    let exported_frame = match exported_frame {
+        Some(idx) => idx,
+        None => return changed
+    };
+
+    for display in Display::iter() {
+        ui.same_line();
+        let label = format!("{}##export-comparison-{}", display.name(), view_id);
+        if ui.radio_button_bool(label, comparison.display == display) {
+            comparison.display = display;
+            changed = true;
+        }
+    }
+
+    ui.same_line();
+    if ui.button(format!("Clear##export-comparison-{}", view_id)) {
+        comparison.clear();
+        return true;
+    }
+    gui::tooltip(ui, "Forgets the map remembered at the last export.");
+
+    if !comparison.is_shown() { return changed; }
+
+    if comparison.display == Display::Blink {
+        let interval = blink_interval(gui_state);
+        let switch = if interval.is_zero() {
+            let clicked = ui.button(format!("Switch##export-comparison-{}", view_id));
+            gui::tooltip(ui, "Switches between the maps (automatic blinking is off due to reduced motion).");
+            ui.same_line();
+            clicked
+        } else {
+            comparison.blink_time.elapsed() >= interval
+        };
+        if switch {
+            comparison.blink_shows_exported = !comparison.blink_shows_exported;
+            comparison.blink_time = Instant::now();
+            changed = true;
+        }
+        ui.text_disabled(if comparison.blink_shows_exported { "exported" } else { "current" });
+        ui.same_line();
+    }
+
+    match comparison.rms {
+        Some(rms) => ui.text(format!(
+            "RMS difference: {}% of full range", fmt::format_number(100.0 * rms as f64, 2, &gui_state.format)
+        )),
+        None => ui.text_disabled("RMS difference unavailable")
+    }
+    if exported_frame != frame_idx {
+        ui.same_line();
+        ui.text_disabled(format!("(exported map shows frame {})", exported_frame + 1));
+    }
+
+    changed
+}
+
+mod tests {
+    use super::*;
+    use ga_image::{Image, PixelFormat};
+
+    #[test]
+    fn snapshot_is_remembered_once_the_export_succeeds() {
+        let mut last_export = LastExport::default();
+        last_export.on_export_started(Some(1));
+        assert_eq!(None, last_export.remembered());
+
+        last_export.on_export_ended(true);
+        assert_eq!(Some(&1), last_export.remembered());
+    }
+
+    #[test]
+    fn new_export_invalidates_remembered_snapshot() {
+        let mut last_export = LastExport::default();
+        last_export.on_export_started(Some(1));
+        last_export.on_export_ended(true);
+
+        last_export.on_export_started(Some(2));
+        assert_eq!(None, last_export.remembered());
+        // a failed or cancelled export leaves nothing to compare with
+        last_export.on_export_ended(false);
+        assert_eq!(None, last_export.remembered());
+
+        last_export.on_export_started(None);
+        last_export.on_export_ended(true);
+        assert_eq!(None, last_export.remembered());
+    }
+
+    #[test]
+    fn remembered_snapshot_can_be_cleared() {
+        let mut last_export = LastExport::default();
+        last_export.on_export_started(Some(1));
+        last_export.on_export_ended(true);
+        last_export.clear();
+        assert_eq!(None, last_export.remembered());
+        // ending again does not bring back the consumed snapshot
+        last_export.on_export_ended(true);
+        assert_eq!(None, last_export.remembered());
+    }
+
+    #[test]
+    fn snapshot_size_keeps_aspect_ratio() {
+        assert_eq!([800, 400], snapshot_size([800, 400], 1024));
+        assert_eq!([1024, 512], snapshot_size([2048, 1024], 1024));
+        assert_eq!([256, 1024], snapshot_size([1000, 4000], 1024));
+        assert_eq!([1024, 1], snapshot_size([100000, 10], 1024));
+    }
+
+    #[test]
+    fn rms_difference_covers_all_values() {
+        let difference = Image::new_from_pixels(2, 1, None, PixelFormat::RGB8, None, vec![
+            51, 51, 51,
+            0, 0, 0
+        ]);
+        assert!((rms_difference(&difference) - (0.2f32 * 0.2 / 2.0).sqrt()).abs() < 1.0e-6);
+
+        let identical = Image::new(4, 4, None, PixelFormat::RGB8, None, true);
+        assert_eq!(0.0, rms_difference(&identical));
+    }
+}
@@ -0,0 +1,170 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Detecting existing files which an export would overwrite or mix its output with.
+
+use crate::projection::output_format;
+use std::path::Path;
+
+/// Prefix of names of exported frames used unless changed by the user.
+pub const DEFAULT_FRAME_PREFIX: &str = "output_";
+
+/// Minimum number of digits of frame numbers in file names.
+pub const FRAME_NUMBER_DIGITS: usize = 5;
+
+/// Layout of WinJUPOS map names up to the planet name ('d' stands for a digit; see `winjupos::file_name`).
+const WINJUPOS_NAME_LAYOUT: &str = "dddd-dd-dd-dddd_d-";
+
+/// Pattern of names of exported frames. Frames saved in any output format match, as files of different formats
+/// would be mixed in the folder.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FramePattern {
+    /// "<prefix>NNNNN.<extension>".
+    Numbered(String),
+    /// "YYYY-MM-DD-HHMM_T-Planet.<extension>".
+    WinJupos
+}
+
+impl FramePattern {
+    pub fn matches(&self, file_name: &str) -> bool {
+        let stem = match output_format::EXTENSIONS.iter()
+            .find_map(|extension| file_name.strip_suffix(extension)?.strip_suffix('.'))
+        {
+            Some(stem) => stem,
+            None => return false
+        };
+
+        match self {
+            FramePattern::Numbered(prefix) => stem.strip_prefix(prefix.as_str()).map_or(false, |number| {
+                number.len() >= FRAME_NUMBER_DIGITS && number.chars().all(|c| c.is_ascii_digit())
+            }),
+
+            FramePattern::WinJupos => stem.len() > WINJUPOS_NAME_LAYOUT.len()
+                && stem.is_char_boundary(WINJUPOS_NAME_LAYOUT.len())
+                && stem[..WINJUPOS_NAME_LAYOUT.len()].chars().zip(WINJUPOS_NAME_LAYOUT.chars())
+                    .all(|(c, layout)| if layout == 'd' { c.is_ascii_digit() } else { c == layout })
+        }
+    }
+}
+
+/// Files created by an export in its output folder.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportOutputs {
+    pub frames: FramePattern,
+    /// Files with fixed names (e.g., the contact sheet).
+    pub other_files: Vec<&'static str>
+}
+
+/// Returns the sorted names of `existing` files which `outputs` would overwrite (files with the same names)
+/// or be mixed with (frames matching the same pattern, e.g., left from a longer sequence).
+pub fn conflicts(existing: &[String], outputs: &ExportOutputs) -> Vec<String> {
+    let mut result: Vec<String> = existing.iter()
+        .filter(|name| outputs.frames.matches(name) || outputs.other_files.contains(&name.as_str()))
+        .cloned()
+        .collect();
+    result.sort();
+
+    result
+}
+
+/// Returns the name of the exported frame with the given number.
+pub fn frame_file_name(prefix: &str, number: usize, extension: &str) -> String {
+    format!("{}{:0width$}.{}", prefix, number, extension, width = FRAME_NUMBER_DIGITS)
+}
+
+/// Returns `prefix` with the lowest number (starting from 2) inserted before its trailing non-alphanumeric
+/// characters, such that no `existing` file is a frame named with it.
+pub fn suffixed_prefix(prefix: &str, existing: &[String]) -> String {
+    let base_len = prefix.trim_end_matches(|c: char| !c.is_alphanumeric()).len();
+    let (base, separator) = prefix.split_at(base_len);
+
+    (2..).map(|n| format!("{}{}{}", base, n, separator))
+        .find(|candidate| !existing.iter().any(|name| FramePattern::Numbered(candidate.clone()).matches(name)))
+        .unwrap()
+}
+
+/// Returns names of files in `dir`.
+pub fn file_names(dir: &Path) -> std::io::Result<Vec<String>> {
+    let mut names = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() { names.push(entry.file_name().to_string_lossy().to_string()); }
+    }
+
+    Ok(names)
+}
+
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> { names.iter().map(|name| name.to_string()).collect() }
+
+    #[test]
+    fn frame_names_are_matched_by_their_pattern() {
+        assert_eq!("output_00001.png", frame_file_name(DEFAULT_FRAME_PREFIX, 1, "png"));
+        assert_eq!("jup_123456.tif", frame_file_name("jup_", 123456, "tif"));
+
+        let pattern = FramePattern::Numbered("jup_".to_string());
+        for number in [0, 1, 99999, 100000] { assert!(pattern.matches(&frame_file_name("jup_", number, "png"))); }
+    }
+
+    #[test]
+    fn numbered_frames_match_only_their_prefix() {
+        let pattern = FramePattern::Numbered(DEFAULT_FRAME_PREFIX.to_string());
+        assert!(pattern.matches("output_00001.png"));
+        assert!(pattern.matches("output_123456.png"));
+        assert!(!pattern.matches("output_0001.png"));
+        assert!(!pattern.matches("output2_00001.png"));
+        assert!(pattern.matches("output_00001.tif"));
+        assert!(!pattern.matches("output_00001.jpg"));
+        assert!(!pattern.matches("output_00001tif"));
+        assert!(!pattern.matches("output_0000a.png"));
+    }
+
+    #[test]
+    fn winjupos_maps_are_matched_regardless_of_time_and_planet() {
+        assert!(FramePattern::WinJupos.matches("2022-11-05-2130_4-Jupiter.png"));
+        assert!(FramePattern::WinJupos.matches("2023-01-02-0003_0-Mars.png"));
+        assert!(FramePattern::WinJupos.matches("2023-01-02-0003_0-Mars.tif"));
+        assert!(!FramePattern::WinJupos.matches("2022-11-05-2130_4-.png"));
+        assert!(!FramePattern::WinJupos.matches("2022-11-05-2130-Jupiter.png"));
+        assert!(!FramePattern::WinJupos.matches("output_00001.png"));
+    }
+
+    #[test]
+    fn stale_frames_and_sidecars_conflict() {
+        let existing = names(&["notes.txt", "output_00012.png", "index.json", "output_00001.png", "output2_00001.png"]);
+        let outputs = ExportOutputs{
+            frames: FramePattern::Numbered(DEFAULT_FRAME_PREFIX.to_string()),
+            other_files: vec!["index.json", "contact_sheet.png"]
+        };
+        assert_eq!(names(&["index.json", "output_00001.png", "output_00012.png"]), conflicts(&existing, &outputs));
+        assert!(conflicts(&names(&["notes.txt"]), &outputs).is_empty());
+    }
+
+    #[test]
+    fn suffix_avoids_existing_frames() {
+        assert_eq!("output2_", suffixed_prefix("output_", &names(&["output_00001.png"])));
+        assert_eq!(
+            "output3_",
+            suffixed_prefix("output_", &names(&["output_00001.png", "output2_00001.png", "output2_notes.txt"]))
+        );
+        assert_eq!("frame2", suffixed_prefix("frame", &[]));
+    }
+}
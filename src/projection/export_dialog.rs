@@ -17,21 +17,102 @@
 // along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
 //
 
+use crate::color::Interpretation;
+use crate::config::{Configuration, ProjectionConfig};
+use crate::dither;
 use crate::gui;
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+use crate::image_utils::BitDepth;
+use crate::projection::{contact_sheet, export_metadata, post_export, seams, winjupos};
+use crate::projection::export_conflicts::{self, ExportOutputs, FramePattern};
+use crate::projection::export_presets::{self, ExportSettings, Preset};
+use crate::projection::export_preview::{self, ExportPreview};
+use crate::projection::output_format::OutputFormat;
+use crate::projection::polar::{self, PolarProjection, PolarView, Pole};
+use strum::IntoEnumIterator;
 use std::path::PathBuf;
 
+const MISSING_FOLDER_TITLE: &str = "Missing output folder";
+const CONFLICTS_TITLE: &str = "Existing files";
+
+/// Maximum number of conflicting files listed by name.
+const MAX_LISTED_CONFLICTS: usize = 10;
+
 pub struct ExportDialog {
     title: String,
     output_path: Option<PathBuf>,
-    bounce_back: bool
+    /// Prefix of names of exported frames (except WinJUPOS maps).
+    frame_prefix: String,
+    /// Number in the name of the first exported frame (except WinJUPOS maps).
+    first_frame_number: usize,
+    /// Replace existing files of the same names as exported frames.
+    overwrite: bool,
+    /// `None`: not chosen by the user (see `output_format`).
+    output_format: Option<OutputFormat>,
+    /// Files in the output folder, listed when checking for conflicts before exporting.
+    existing_files: Vec<String>,
+    bounce_back: bool,
+    low_memory: bool,
+    contact_sheet: bool,
+    skip_failed_frames: bool,
+    /// Draw the projection view's grid over exported frames.
+    include_grid: bool,
+    /// Adjust frame brightness to minimize steps at seams (see `seams`).
+    match_seams: bool,
+    /// Export maps for WinJUPOS (see `winjupos`).
+    winjupos: bool,
+    /// Write geometry of exported maps (see `export_metadata`).
+    metadata: bool,
+    /// Export views from above a pole (see `polar`).
+    polar: bool,
+    polar_view: PolarView,
+    /// Dither frames when converting them to 8 bits (`None`: not chosen by the user, see `dithering`).
+    dithering: Option<bool>,
+    dither_method: dither::Method,
+    /// Encode files so that identical input and parameters give identical files (see `worker::Projection`).
+    reproducible: bool,
+    /// Presets defined by the user (in addition to `export_presets::built_in`).
+    user_presets: Vec<Preset>,
+    /// Name under which the current settings are to be saved as a preset.
+    preset_name: String,
+    /// Command template executed after a successful export (see `post_export`).
+    post_export_command: String,
+    post_export_command_enabled: bool
 }
 
 impl ExportDialog {
-    pub fn new(title: String, output_path: Option<PathBuf>) -> ExportDialog {
+    pub fn new(
+        title: String,
+        output_path: Option<PathBuf>,
+        post_export_command: String,
+        post_export_command_enabled: bool,
+        user_presets: Vec<Preset>
+    ) -> ExportDialog {
         ExportDialog{
             title,
             output_path,
-            bounce_back: false
+            frame_prefix: export_conflicts::DEFAULT_FRAME_PREFIX.to_string(),
+            first_frame_number: 1,
+            overwrite: false,
+            output_format: None,
+            existing_files: vec![],
+            bounce_back: false,
+            low_memory: false,
+            contact_sheet: false,
+            skip_failed_frames: false,
+            include_grid: false,
+            match_seams: false,
+            winjupos: false,
+            metadata: false,
+            polar: false,
+            polar_view: Default::default(),
+            dithering: None,
+            dither_method: dither::Method::FloydSteinberg,
+            reproducible: false,
+            user_presets,
+            preset_name: String::new(),
+            post_export_command,
+            post_export_command_enabled
         }
     }
 
@@ -39,31 +120,165 @@ impl ExportDialog {
 
     pub fn output_path(&self) -> PathBuf { self.output_path.as_ref().unwrap().clone() }
 
-    pub fn bounce_back(&self) -> bool { self.bounce_back }
+    pub fn set_output_path(&mut self, value: PathBuf) { self.output_path = Some(value); }
+
+    pub fn frame_prefix(&self) -> &str { &self.frame_prefix }
+
+    pub fn first_frame_number(&self) -> usize { self.first_frame_number }
+
+    /// If false, the export fails if any of the frames to be saved already exists.
+    pub fn overwrite(&self) -> bool { self.overwrite }
+
+    /// Returns the format of saved frames; unless chosen by the user, PNG of the source images' bit depth.
+    pub fn output_format(&self, source_bit_depth: BitDepth) -> OutputFormat {
+        self.output_format.unwrap_or(OutputFormat::png(source_bit_depth))
+    }
+
+    /// Returns `true` if frames are saved with 8 bits per channel (polar views always are).
+    pub fn eight_bit_output(&self, source_bit_depth: BitDepth) -> bool {
+        self.polar() || self.output_format(source_bit_depth).bit_depth() == BitDepth::Eight
+    }
+
+    /// Returns the files created in the output folder with the current options.
+    fn outputs(&self) -> ExportOutputs {
+        let mut other_files = vec![];
+        if self.winjupos { other_files.push(winjupos::INFO_FILE_NAME); }
+        if self.metadata() { other_files.push(export_metadata::FILE_NAME); }
+        if self.match_seams() { other_files.push(seams::FILE_NAME); }
+        if self.contact_sheet { other_files.push(contact_sheet::FILE_NAME); }
+
+        ExportOutputs{
+            frames: if self.winjupos {
+                FramePattern::WinJupos
+            } else {
+                FramePattern::Numbered(self.frame_prefix.clone())
+            },
+            other_files
+        }
+    }
+
+    pub fn bounce_back(&self) -> bool { self.bounce_back && !self.winjupos }
+
+    /// If true, source frames are re-loaded from files one at a time during export.
+    pub fn low_memory(&self) -> bool { self.low_memory }
+
+    /// If true, an overview image of all exported frames is created.
+    pub fn contact_sheet(&self) -> bool { self.contact_sheet }
+
+    /// If true, frames are exported as 360° maps named according to the WinJUPOS convention.
+    pub fn winjupos(&self) -> bool { self.winjupos }
+
+    /// If true, geometric calibration of all exported maps is saved in the output folder.
+    pub fn metadata(&self) -> bool { self.metadata && !self.polar() }
+
+    /// If true, frames are exported as views from above a pole (see `polar_view`).
+    pub fn polar(&self) -> bool { self.polar && !self.winjupos }
+
+    pub fn polar_view(&self) -> &PolarView { &self.polar_view }
+
+    /// Returns the dithering method used when converting frames to 8 bits, if enabled. Unless chosen by the user,
+    /// dithering is enabled when the rendered values are more precise than 8 bits (see `precision_reduced`).
+    pub fn dithering(&self, precision_reduced: bool) -> Option<dither::Method> {
+        if self.dithering.unwrap_or(precision_reduced || self.match_seams()) { Some(self.dither_method) } else { None }
+    }
+
+    /// If true, identical input and parameters give byte-for-byte identical output files.
+    pub fn reproducible(&self) -> bool { self.reproducible }
+
+    /// Returns the options covered by presets.
+    fn settings(&self) -> ExportSettings {
+        ExportSettings{
+            output_format: self.output_format,
+            winjupos: self.winjupos,
+            polar: self.polar,
+            bounce_back: self.bounce_back,
+            include_grid: self.include_grid,
+            match_seams: self.match_seams,
+            metadata: self.metadata,
+            dithering: self.dithering,
+            dither_method: self.dither_method,
+            contact_sheet: self.contact_sheet,
+            reproducible: self.reproducible
+        }
+    }
+
+    /// Sets all options covered by presets; WinJUPOS maps are not enabled if `winjupos_unavailable`.
+    fn apply_settings(&mut self, settings: &ExportSettings, winjupos_unavailable: bool) {
+        self.output_format = settings.output_format;
+        self.winjupos = settings.winjupos && !winjupos_unavailable;
+        self.polar = settings.polar;
+        self.bounce_back = settings.bounce_back;
+        self.include_grid = settings.include_grid;
+        self.match_seams = settings.match_seams;
+        self.metadata = settings.metadata;
+        self.dithering = settings.dithering;
+        self.dither_method = settings.dither_method;
+        self.contact_sheet = settings.contact_sheet;
+        self.reproducible = settings.reproducible;
+    }
+
+    /// Saves the current settings as a user preset named `name` (replacing one of the same name).
+    fn save_preset(&mut self, name: &str) {
+        let preset = Preset::new(name, self.settings());
+        match self.user_presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => self.user_presets.push(preset)
+        }
+    }
+
+    /// If true, frames which cannot be saved are skipped instead of aborting the export.
+    pub fn skip_failed_frames(&self) -> bool { self.skip_failed_frames }
+
+    /// If true, the projection view's grid is drawn over exported frames.
+    pub fn include_grid(&self) -> bool { self.include_grid && !self.winjupos && !self.polar() && !self.reproducible }
+
+    /// If true, frame brightness is matched at seams between consecutive frames.
+    pub fn match_seams(&self) -> bool { self.match_seams && !self.winjupos && !self.polar() }
+
+    /// Returns the post-export command template, if enabled.
+    pub fn post_export_command(&self) -> Option<&str> {
+        if self.post_export_command_enabled && !self.post_export_command.trim().is_empty() {
+            Some(&self.post_export_command)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns `true` if rendered values have more than 8 bits of precision before being saved, i.e., they are scaled
+/// by frame gains, converted to a tone curve other than sRGB, or come from 16-bit images saved as 8-bit files.
+pub fn precision_reduced(
+    frame_gains: &[f32],
+    interpretation: Interpretation,
+    source_bit_depth: BitDepth,
+    eight_bit_output: bool
+) -> bool {
+    interpretation != Interpretation::Srgb
+        || frame_gains.iter().any(|gain| *gain != 1.0)
+        || (source_bit_depth == BitDepth::Sixteen && eight_bit_output)
 }
 
-/// Returns `true` if dialog was accepted.
+/// Returns `true` if dialog was accepted. If `winjupos_unavailable` is set, it contains the reason why WinJUPOS maps
+/// cannot be exported. `precision_reduced`: see `precision_reduced`.
 pub fn handle_export_dialog(
     ui: &imgui::Ui,
     gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
     dialog: &mut ExportDialog,
+    preview: &mut ExportPreview,
+    winjupos_unavailable: Option<&str>,
+    precision_reduced: bool,
+    source_bit_depth: BitDepth
 ) -> bool {
     let mut result = false;
 
-    ui.popup_modal(&dialog.title).build(ui, || {
+    let title = dialog.title.clone();
+    modal::modal(ui, config, &title, KeyBindings::all(), |key_action, config| {
+        handle_presets(ui, config, dialog, winjupos_unavailable.is_some());
+        ui.separator();
+
         if ui.button("Output folder...") {
-            let prev_path = match &dialog.output_path {
-                Some(path) => path.clone(),
-                None => PathBuf::from("")
-            };
-            let path = native_dialog::FileDialog::new()
-                .set_location(&prev_path) // TODO: remember the MRU
-                .show_open_single_dir()
-                .unwrap();
-
-            if let Some(path) = path {
-                dialog.output_path = Some(path);
-            }
+            choose_output_folder(ui, gui_state, dialog);
         }
         ui.same_line();
         match &dialog.output_path {
@@ -71,29 +286,466 @@ pub fn handle_export_dialog(
             None => ui.text_disabled("(no folder selected)")
         }
 
+        let token = ui.begin_disabled(dialog.winjupos);
+        gui::add_text_before(ui, "file name prefix");
+        if ui.input_text("##frame-prefix", &mut dialog.frame_prefix).build() {
+            dialog.frame_prefix.retain(|c| !std::path::is_separator(c));
+        }
+        token.end();
+        let extension = dialog.output_format(source_bit_depth).extension();
+        let file_name = |number| export_conflicts::frame_file_name(&dialog.frame_prefix, number, extension);
+        let first = dialog.first_frame_number;
+        let names_tooltip = format!("Frames are saved as {}, {}, ...", file_name(first), file_name(first + 1));
+        gui::tooltip(ui, &names_tooltip);
+        let token = ui.begin_disabled(dialog.winjupos);
+        gui::add_text_before(ui, "first number");
+        let mut first_number = dialog.first_frame_number as i32;
+        if ui.input_int("##first-frame-number", &mut first_number).build() {
+            dialog.first_frame_number = first_number.max(0) as usize;
+        }
+        token.end();
+        gui::tooltip(ui, &names_tooltip);
+
+        gui::add_text_before(ui, "format");
+        let formats: Vec<OutputFormat> = OutputFormat::iter().collect();
+        let names: Vec<&str> = formats.iter().map(|format| format.name()).collect();
+        let mut index = formats.iter().position(|format| *format == dialog.output_format(source_bit_depth)).unwrap();
+        if ui.combo_simple_string("##output-format", &mut index, &names) {
+            dialog.output_format = Some(formats[index]);
+        }
+        gui::tooltip(ui, &format!(
+            "File format of saved frames (by default PNG with the bit depth of the source images, {}). Polar views \
+            are always saved with 8 bits per channel.", source_bit_depth.name()
+        ));
+
+        let token = ui.begin_disabled(winjupos_unavailable.is_some());
+        ui.checkbox("WinJUPOS map", &mut dialog.winjupos);
+        token.end();
+        gui::tooltip(ui, &match winjupos_unavailable {
+            Some(reason) => format!("Unavailable: {}.", reason),
+            None => format!(
+                "Save each frame as a 360° × 180° equirectangular map with longitude increasing leftwards, \
+                named after its observation time (YYYY-MM-DD-HHMM_T-Planet.{}).", extension
+            )
+        });
+        if dialog.winjupos {
+            ui.text_disabled("Projection type and rotation compensation of the view are ignored.");
+        }
+
+        let token = ui.begin_disabled(dialog.winjupos);
+        ui.checkbox("Polar view", &mut dialog.polar);
+        token.end();
+        gui::tooltip(ui, "Save each frame as seen from above a pole (the central meridian points downwards).");
+        if dialog.polar() { handle_polar_view_controls(ui, &mut dialog.polar_view); }
+
+        let token = ui.begin_disabled(dialog.winjupos);
         ui.checkbox("Back-and-forth sequence (1, 2, ... n-1, n, n-1, ... 2, 1)", &mut dialog.bounce_back);
+        token.end();
+
+        let token = ui.begin_disabled(dialog.winjupos || dialog.polar() || dialog.reproducible);
+        ui.checkbox("Include grid", &mut dialog.include_grid);
+        token.end();
+        gui::tooltip(ui, "Draw the projection view's grid (with its current color and spacing) over exported frames.");
+
+        let token = ui.begin_disabled(dialog.winjupos || dialog.polar());
+        ui.checkbox("Match seam brightness", &mut dialog.match_seams);
+        token.end();
+        gui::tooltip(ui, &format!(
+            "Adjust brightness of frames to minimize steps where consecutive frames overlap (requires rotation \
+            compensation). The applied gains are saved as {}.", seams::FILE_NAME
+        ));
+
+        let token = ui.begin_disabled(dialog.polar());
+        ui.checkbox("Save map geometry", &mut dialog.metadata);
+        token.end();
+        gui::tooltip(ui, &format!(
+            "Save scale, edge longitudes and latitude, projection type and observation time of each exported map \
+            as {} (for measurements with other tools).", export_metadata::FILE_NAME
+        ));
+
+        let token = ui.begin_disabled(!dialog.eight_bit_output(source_bit_depth));
+        let mut dithering = dialog.dithering(precision_reduced).is_some();
+        if ui.checkbox("Dither to 8 bits", &mut dithering) { dialog.dithering = Some(dithering); }
+        token.end();
+        gui::tooltip(ui, &format!(
+            "Render with 16 bits per channel and dither when saving 8-bit files, to avoid banding in smooth \
+            gradients.{}",
+            if precision_reduced || dialog.match_seams() {
+                " Recommended: frame brightness is adjusted or the output tone curve differs from sRGB."
+            } else {
+                ""
+            }
+        ));
+        if dithering && dialog.eight_bit_output(source_bit_depth) {
+            for method in dither::Method::iter() {
+                ui.same_line();
+                if ui.radio_button_bool(format!("{}##dither-method", method.name()), dialog.dither_method == method) {
+                    dialog.dither_method = method;
+                }
+            }
+        }
+
+        ui.checkbox("Low-memory export", &mut dialog.low_memory);
+        gui::tooltip(ui, "Re-load source frames from disk one at a time instead of using the already loaded ones.");
+
+        ui.checkbox("Overwrite existing files", &mut dialog.overwrite);
+        gui::tooltip(ui, "Otherwise the export is refused (before saving anything) if a file of the same name as \
+            any of the frames already exists.");
+
+        ui.checkbox("Skip frames which fail to save", &mut dialog.skip_failed_frames);
+        gui::tooltip(ui, "Saving is retried once; frames which still fail are listed after the export.");
+
+        ui.checkbox("Reproducible output", &mut dialog.reproducible);
+        gui::tooltip(ui, "Render frames on the CPU (slower, without the grid) and encode files with fixed settings, \
+            so that exporting again with the same input and parameters gives byte-for-byte identical files, \
+            regardless of the graphics card and driver.");
+
+        ui.checkbox("Create contact sheet", &mut dialog.contact_sheet);
+        gui::tooltip(ui, &format!(
+            "Save an overview of all exported frames (numbered thumbnails) as {}.", contact_sheet::FILE_NAME
+        ));
+
+        ui.checkbox("Run command after export", &mut dialog.post_export_command_enabled);
+        gui::tooltip(ui, "Command is executed by the system shell in the output folder after a successful export.");
+        let token = ui.begin_disabled(!dialog.post_export_command_enabled);
+        ui.input_text("##post-export-command", &mut dialog.post_export_command)
+            .hint("e.g.: prepare.bat {output_dir}")
+            .build();
+        gui::tooltip(ui, &format!(
+            "Placeholders: {}, {}, {}, {} (substituted values are quoted as needed).",
+            post_export::PLACEHOLDER_OUTPUT_DIR,
+            post_export::PLACEHOLDER_FRAME_COUNT,
+            post_export::PLACEHOLDER_PLANET,
+            post_export::PLACEHOLDER_PROJECTION
+        ));
+        token.end();
 
         ui.separator();
-        if ui.button("Export") {
-            if dialog.output_path.is_none() {
+        let token = ui.begin_disabled(dialog.winjupos && winjupos_unavailable.is_some());
+        if ui.button("Preview output...") { preview.open(ui); }
+        token.end();
+        gui::tooltip(ui, "Show a frame rendered with these settings at actual pixel size, as it will be saved.");
+        export_preview::handle_export_preview(ui, gui_state, config, preview);
+
+        if modal::default_button(ui, "Export") || key_action == KeyAction::Accept {
+            if dialog.output_path.as_ref().map_or(false, |path| !path.is_dir()) {
+                ui.open_popup(MISSING_FOLDER_TITLE);
+            } else if dialog.output_path.is_none() {
                 gui_state.message_box = Some(gui::MessageBox{
                     title: "Error".to_string(),
                     message: format!("Output folder not selected.")
                 });
                 ui.open_popup("Error");
+            } else if let (true, Some(reason)) = (dialog.winjupos, winjupos_unavailable) {
+                gui_state.message_box = Some(gui::MessageBox{
+                    title: "Error".to_string(),
+                    message: format!("Cannot export WinJUPOS maps: {}.", reason)
+                });
+                ui.open_popup("Error");
             } else {
-                result = true;
-                ui.close_current_popup();
+                dialog.existing_files =
+                    export_conflicts::file_names(dialog.output_path.as_ref().unwrap()).unwrap_or_default();
+                if export_conflicts::conflicts(&dialog.existing_files, &dialog.outputs()).is_empty() {
+                    result = true;
+                } else {
+                    ui.open_popup(CONFLICTS_TITLE);
+                }
             }
         }
         ui.same_line();
 
-        if ui.button("Cancel") {
+        if ui.button("Cancel") || key_action == KeyAction::Cancel {
+            ui.close_current_popup();
+        }
+
+        if handle_missing_folder(ui, gui_state, config, dialog) { result = true; }
+        if handle_conflicts(ui, gui_state, config, dialog) { result = true; }
+
+        if result {
+            config.set_post_export_command(&dialog.post_export_command);
+            config.set_post_export_command_enabled(dialog.post_export_command_enabled);
             ui.close_current_popup();
         }
 
-        gui::handle_message_box(ui, gui_state);
+        gui::handle_message_box(ui, gui_state, config);
     });
 
     result
 }
+
+/// Shows the preset selector and the controls for saving and deleting user presets.
+fn handle_presets(ui: &imgui::Ui, config: &mut Configuration, dialog: &mut ExportDialog, winjupos_unavailable: bool) {
+    let built_in = export_presets::built_in();
+    let presets: Vec<&Preset> = built_in.iter().chain(dialog.user_presets.iter()).collect();
+    let current = export_presets::matching(&presets, &dialog.settings());
+    let current_user_preset = current.and_then(|idx| idx.checked_sub(built_in.len()));
+
+    let mut names = vec!["(custom)"];
+    names.extend(presets.iter().map(|preset| preset.name.as_str()));
+    let mut index = current.map_or(0, |idx| idx + 1);
+    gui::add_text_before(ui, "preset");
+    let selected = if ui.combo_simple_string("##export-preset", &mut index, &names) && index > 0 {
+        Some(presets[index - 1].settings.clone())
+    } else {
+        None
+    };
+    gui::tooltip(ui, "Sets the options below (they remain editable); shows the preset matching the current options, \
+        if any. The output format, file name prefix and first frame number are not part of presets.");
+    if let Some(settings) = selected { dialog.apply_settings(&settings, winjupos_unavailable); }
+
+    ui.same_line();
+    let token = ui.begin_disabled(current_user_preset.is_none());
+    if ui.button("Delete##export-preset") {
+        dialog.user_presets.remove(current_user_preset.unwrap());
+        config.set_export_presets(&dialog.user_presets);
+    }
+    token.end();
+    gui::tooltip(ui, "Delete the selected user preset.");
+
+    ui.input_text("##export-preset-name", &mut dialog.preset_name).hint("preset name").build();
+    ui.same_line();
+    let name = Preset::new(&dialog.preset_name, Default::default()).name;
+    let token = ui.begin_disabled(name.is_empty() || built_in.iter().any(|preset| preset.name == name));
+    if ui.button("Save as preset") {
+        dialog.save_preset(&name);
+        config.set_export_presets(&dialog.user_presets);
+        dialog.preset_name.clear();
+    }
+    token.end();
+    gui::tooltip(ui, "Save the current options as a preset (replacing a user preset of the same name).");
+}
+
+fn choose_output_folder(ui: &imgui::Ui, gui_state: &mut gui::GuiState, dialog: &mut ExportDialog) {
+    let location = gui::file_dialog::initial_location(dialog.output_path.as_deref());
+    let path = gui::file_dialog::checked(
+        ui,
+        gui_state,
+        native_dialog::FileDialog::new().set_location(&location).show_open_single_dir()
+    ).flatten();
+
+    if let Some(path) = path {
+        dialog.output_path = Some(path);
+    }
+}
+
+/// Handles the prompt shown when the selected output folder no longer exists; returns `true` if the folder has been
+/// created (and the export can proceed).
+fn handle_missing_folder(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
+    dialog: &mut ExportDialog
+) -> bool {
+    let mut created = false;
+
+    modal::modal(ui, config, MISSING_FOLDER_TITLE, KeyBindings::all(), |key_action, config| {
+        let path = dialog.output_path.clone().unwrap_or_default();
+        ui.text(format!("The output folder {} does not exist.", path.to_string_lossy()));
+
+        if modal::default_button(ui, "Create") || key_action == KeyAction::Accept {
+            match std::fs::create_dir_all(&path) {
+                Ok(()) => {
+                    created = true;
+                    ui.close_current_popup();
+                },
+                Err(e) => {
+                    gui_state.message_box = Some(gui::MessageBox{
+                        title: "Error".to_string(),
+                        message: format!("Could not create {}: {}.", path.to_string_lossy(), e)
+                    });
+                    ui.open_popup("Error");
+                }
+            }
+        }
+        ui.same_line();
+
+        if ui.button("Choose another...") {
+            ui.close_current_popup();
+            choose_output_folder(ui, gui_state, dialog);
+        }
+        ui.same_line();
+
+        if ui.button("Cancel") || key_action == KeyAction::Cancel {
+            ui.close_current_popup();
+        }
+
+        gui::handle_message_box(ui, gui_state, config);
+    });
+
+    created
+}
+
+/// Handles the prompt listing existing files which the export would overwrite or be mixed with; returns `true`
+/// if the export can proceed.
+fn handle_conflicts(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
+    dialog: &mut ExportDialog
+) -> bool {
+    let mut proceed = false;
+
+    modal::modal(ui, config, CONFLICTS_TITLE, KeyBindings::all(), |key_action, config| {
+        let outputs = dialog.outputs();
+        let conflicts = export_conflicts::conflicts(&dialog.existing_files, &outputs);
+        let path = dialog.output_path.clone().unwrap_or_default();
+        ui.text(format!(
+            "The output folder {} contains files which the export would overwrite or be mixed with:",
+            path.to_string_lossy()
+        ));
+        ui.indent();
+        for name in conflicts.iter().take(MAX_LISTED_CONFLICTS) { ui.text(name); }
+        if conflicts.len() > MAX_LISTED_CONFLICTS {
+            ui.text_disabled(format!("(and {} more)", conflicts.len() - MAX_LISTED_CONFLICTS));
+        }
+        ui.unindent();
+
+        if let FramePattern::Numbered(prefix) = &outputs.frames {
+            if conflicts.iter().any(|name| outputs.frames.matches(name)) {
+                let suffixed = export_conflicts::suffixed_prefix(prefix, &dialog.existing_files);
+                if ui.button(format!("Use prefix \"{}\"", suffixed)) {
+                    dialog.frame_prefix = suffixed;
+                    // files with fixed names (if any) are still listed
+                    if export_conflicts::conflicts(&dialog.existing_files, &dialog.outputs()).is_empty() {
+                        proceed = true;
+                        ui.close_current_popup();
+                    }
+                }
+                gui::tooltip(ui, "Save frames under names not used in the folder yet.");
+                ui.same_line();
+            }
+        }
+
+        if ui.button("Overwrite") {
+            dialog.overwrite = true;
+            proceed = true;
+            ui.close_current_popup();
+        }
+        gui::tooltip(ui, "Export to this folder anyway (enables overwriting existing files); files of the same names \
+            are replaced, other ones remain.");
+        ui.same_line();
+
+        if ui.button("Choose another...") {
+            ui.close_current_popup();
+            choose_output_folder(ui, gui_state, dialog);
+        }
+        ui.same_line();
+
+        if ui.button("Cancel") || key_action == KeyAction::Cancel {
+            ui.close_current_popup();
+        }
+
+        gui::handle_message_box(ui, gui_state, config);
+    });
+
+    proceed
+}
+
+fn handle_polar_view_controls(ui: &imgui::Ui, view: &mut PolarView) {
+    ui.indent();
+
+    ui.text("pole:");
+    for pole in Pole::iter() {
+        ui.same_line();
+        if ui.radio_button_bool(format!("{}##polar-pole", pole.name()), view.pole == pole) { view.pole = pole; }
+    }
+
+    ui.text("projection:");
+    for projection in PolarProjection::iter() {
+        ui.same_line();
+        if ui.radio_button_bool(format!("{}##polar-projection", projection.name()), view.projection == projection) {
+            view.projection = projection;
+        }
+    }
+
+    let mut limit = view.latitude_limit.0 as f32;
+    let (min_limit, max_limit) = polar::LATITUDE_LIMIT_RANGE;
+    if imgui::Slider::new("latitude limit", min_limit as f32, max_limit as f32)
+        .display_format("%.0f°")
+        .build(ui, &mut limit) {
+        view.latitude_limit = cgmath::Deg(limit as f64);
+    }
+    gui::tooltip(ui, "The view extends from the pole down to this latitude (in the chosen hemisphere).");
+
+    let mut size = view.size as i32;
+    if ui.input_int("output size##polar-size", &mut size).step(64).build() {
+        view.size = (size.max(0) as u32).clamp(polar::SIZE_RANGE.0, polar::SIZE_RANGE.1);
+    }
+
+    ui.unindent();
+}
+
+mod tests {
+    use super::*;
+
+    fn dialog(user_presets: Vec<Preset>) -> ExportDialog {
+        ExportDialog::new("Export".to_string(), None, String::new(), false, user_presets)
+    }
+
+    #[test]
+    fn outputs_follow_options() {
+        let mut dialog = dialog(vec![]);
+        dialog.contact_sheet = true;
+        dialog.metadata = true;
+        assert_eq!(
+            ExportOutputs{
+                frames: FramePattern::Numbered(export_conflicts::DEFAULT_FRAME_PREFIX.to_string()),
+                other_files: vec![export_metadata::FILE_NAME, contact_sheet::FILE_NAME]
+            },
+            dialog.outputs()
+        );
+
+        dialog.winjupos = true;
+        assert_eq!(FramePattern::WinJupos, dialog.outputs().frames);
+        assert!(dialog.outputs().other_files.contains(&winjupos::INFO_FILE_NAME));
+    }
+
+    #[test]
+    fn precision_is_reduced_by_saving_16_bit_images_as_8_bit() {
+        let gains = [1.0, 1.0];
+        assert!(!precision_reduced(&gains, Interpretation::Srgb, BitDepth::Eight, true));
+        assert!(!precision_reduced(&gains, Interpretation::Srgb, BitDepth::Sixteen, false));
+        assert!(precision_reduced(&gains, Interpretation::Srgb, BitDepth::Sixteen, true));
+        assert!(precision_reduced(&[1.0, 1.2], Interpretation::Srgb, BitDepth::Eight, true));
+        assert!(precision_reduced(&gains, Interpretation::Linear, BitDepth::Eight, true));
+    }
+
+    #[test]
+    fn sixteen_bit_output_format_keeps_precision() {
+        let mut dialog = dialog(vec![]);
+        let reduced = |dialog: &ExportDialog| precision_reduced(
+            &[1.0], Interpretation::Srgb, BitDepth::Sixteen, dialog.eight_bit_output(BitDepth::Sixteen)
+        );
+        assert!(!reduced(&dialog));
+
+        dialog.output_format = Some(OutputFormat::Tiff8);
+        assert!(reduced(&dialog));
+    }
+
+    #[test]
+    fn applied_preset_sets_all_options() {
+        for preset in export_presets::built_in() {
+            let mut dialog = dialog(vec![]);
+            dialog.apply_settings(&preset.settings, false);
+            assert_eq!(preset.settings, dialog.settings());
+        }
+    }
+
+    #[test]
+    fn unavailable_winjupos_is_not_enabled_by_preset() {
+        let mut dialog = dialog(vec![]);
+        dialog.apply_settings(&ExportSettings{ winjupos: true, metadata: true, ..Default::default() }, true);
+        assert!(!dialog.winjupos());
+        assert!(dialog.metadata());
+    }
+
+    #[test]
+    fn saved_preset_replaces_one_of_the_same_name() {
+        let mut dialog = dialog(vec![Preset::new("mine", Default::default())]);
+        dialog.contact_sheet = true;
+        dialog.save_preset("mine");
+        dialog.save_preset("other");
+        assert_eq!(2, dialog.user_presets.len());
+        assert!(dialog.user_presets.iter().all(|preset| preset.settings.contact_sheet));
+    }
+}
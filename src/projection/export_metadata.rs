@@ -0,0 +1,337 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Geometry of exported maps, saved as `FILE_NAME` in the output folder for measuring feature positions with
+//! external tools.
+
+use cgmath::Deg;
+use crate::fmt;
+use crate::projection::projection_view::{self, FramePlacement, ProjectionType};
+use crate::projection::source_view::SourceParameters;
+use std::error::Error;
+use std::path::Path;
+
+pub const FILE_NAME: &str = "index.json";
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// Number of decimal places of saved angles.
+const ANGLE_DECIMALS: usize = 6;
+
+/// Number of decimal places of saved Julian dates (ca. 0.1 s).
+const JD_DECIMALS: usize = 6;
+
+pub struct ExportMetadata {
+    /// Julian date (UTC) of the first source frame, if known.
+    pub start_jd: Option<f64>,
+    /// Seconds since the first source frame for each source frame, if taken from the file names (otherwise the
+    /// frames are `SourceParameters::frame_interval` apart).
+    pub frame_offsets: Option<Vec<f64>>
+}
+
+impl ExportMetadata {
+    /// Returns Julian date (UTC) of source frame `idx`, if the observation time is known.
+    pub fn frame_time(&self, src_params: &SourceParameters, idx: usize) -> Option<f64> {
+        let offset = match &self.frame_offsets {
+            Some(offsets) => offsets[idx],
+            None => idx as f64 * src_params.frame_interval.as_secs_f64()
+        };
+        self.start_jd.map(|jd| jd + offset / SECONDS_PER_DAY)
+    }
+}
+
+/// Geometric calibration of a single exported map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapGeometry {
+    pub width: u32,
+    pub height: u32,
+    /// Degrees of longitude per pixel.
+    pub deg_per_pixel: f32,
+    /// Longitude at the map's left edge, relative to the central meridian of the map's source frame.
+    pub left_edge_longitude: Deg<f32>,
+    /// Longitude at the map's left edge, relative to the central meridian of the first source frame
+    /// (see `projection_view::planet_longitude`).
+    pub left_edge_planet_longitude: Deg<f32>,
+    /// If true, longitude increases leftwards.
+    pub longitude_increases_leftwards: bool,
+    /// Latitude at the map's top edge.
+    pub top_edge_latitude: Deg<f32>
+}
+
+impl MapGeometry {
+    /// Returns geometry of frame `idx` rendered into a strip of `width`×`height` pixels
+    /// (as in `projection_view::render_projection`).
+    pub fn strip(
+        src_params: &SourceParameters,
+        rotation_comp: f32,
+        idx: usize,
+        width: u32,
+        height: u32
+    ) -> MapGeometry {
+        let placement = FramePlacement::new(src_params, rotation_comp, idx);
+        let left_edge_longitude = placement.left_edge_longitude();
+
+        MapGeometry{
+            width,
+            height,
+            deg_per_pixel: placement.deg_per_pixel(width),
+            left_edge_longitude,
+            left_edge_planet_longitude: projection_view::planet_longitude(src_params, idx, left_edge_longitude),
+            longitude_increases_leftwards: false,
+            top_edge_latitude: Deg(90.0)
+        }
+    }
+
+    /// Returns geometry of frame `idx` saved as a WinJUPOS map (see `winjupos::full_map`) created from
+    /// a `hemisphere_width`×`height` strip rendered without rotation compensation.
+    pub fn winjupos(src_params: &SourceParameters, idx: usize, hemisphere_width: u32, height: u32) -> MapGeometry {
+        let placement = FramePlacement::new(src_params, 0.0, idx);
+        let deg_per_pixel = placement.deg_per_pixel(hemisphere_width);
+        // before flipping, the hemisphere starts `hemisphere_width / 2` pixels from the left edge of the map;
+        // afterwards, the former right edge becomes the left one
+        let width = 2 * hemisphere_width;
+        let left_edge_longitude = placement.left_edge_longitude()
+            + Deg(deg_per_pixel * (width - hemisphere_width / 2) as f32);
+
+        MapGeometry{
+            width,
+            height,
+            deg_per_pixel,
+            left_edge_longitude,
+            left_edge_planet_longitude: projection_view::planet_longitude(src_params, idx, left_edge_longitude),
+            longitude_increases_leftwards: true,
+            top_edge_latitude: Deg(90.0)
+        }
+    }
+}
+
+/// Describes a single output file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameEntry {
+    pub file_name: String,
+    /// Index of the source frame; bounce-back duplicates have the same source frame as the original.
+    pub source_frame: usize,
+    /// Julian date (UTC) of the source frame.
+    pub time_jd: Option<f64>,
+    pub geometry: MapGeometry
+}
+
+/// Returns `s` as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result += "\\\"",
+            '\\' => result += "\\\\",
+            '\n' => result += "\\n",
+            '\r' => result += "\\r",
+            '\t' => result += "\\t",
+            c if (c as u32) < 0x20 => result += &format!("\\u{:04x}", c as u32),
+            c => result.push(c)
+        }
+    }
+    result.push('"');
+
+    result
+}
+
+fn json_angle(value: Deg<f32>) -> String { fmt::machine_number(value.0 as f64, ANGLE_DECIMALS) }
+
+fn frame_to_json(entry: &FrameEntry) -> String {
+    let geometry = &entry.geometry;
+    let fields = [
+        ("file", json_string(&entry.file_name)),
+        ("source_frame", (entry.source_frame + 1).to_string()),
+        ("time_jd", entry.time_jd.map_or("null".to_string(), |jd| fmt::machine_number(jd, JD_DECIMALS))),
+        ("width", geometry.width.to_string()),
+        ("height", geometry.height.to_string()),
+        ("deg_per_pixel", json_angle(Deg(geometry.deg_per_pixel))),
+        ("left_edge_longitude", json_angle(geometry.left_edge_longitude)),
+        ("left_edge_planet_longitude", json_angle(geometry.left_edge_planet_longitude)),
+        ("longitude_increases_leftwards", geometry.longitude_increases_leftwards.to_string()),
+        ("top_edge_latitude", json_angle(geometry.top_edge_latitude))
+    ];
+
+    let fields: Vec<String> = fields.iter().map(|(name, value)| format!("{}: {}", json_string(name), value)).collect();
+
+    format!("{{ {} }}", fields.join(", "))
+}
+
+/// Returns the contents of `FILE_NAME`. Source frames are numbered from 1 (as in the output file names).
+pub fn to_json(projection_type: ProjectionType, standard_parallel: Deg<f32>, frames: &[FrameEntry]) -> String {
+    let mut contents = "{\n".to_string();
+    contents += &format!("  \"projection\": {},\n", json_string(projection_type.name()));
+    if projection_type == ProjectionType::LambertCylindricalEqualArea {
+        contents += &format!("  \"standard_parallel\": {},\n", json_angle(standard_parallel));
+    }
+    contents += "  \"frames\": [";
+    for (idx, entry) in frames.iter().enumerate() {
+        contents += if idx == 0 { "\n    " } else { ",\n    " };
+        contents += &frame_to_json(entry);
+    }
+    contents += if frames.is_empty() { "]\n}\n" } else { "\n  ]\n}\n" };
+
+    contents
+}
+
+/// Writes `FILE_NAME` in `output_dir`.
+pub fn save(
+    output_dir: &Path,
+    projection_type: ProjectionType,
+    standard_parallel: Deg<f32>,
+    frames: &[FrameEntry]
+) -> Result<(), Box<dyn Error>> {
+    std::fs::write(output_dir.join(FILE_NAME), to_json(projection_type, standard_parallel, frames))?;
+
+    Ok(())
+}
+
+mod tests {
+    use super::*;
+    use crate::projection::coverage;
+
+    fn assert_close(expected: f32, actual: f32, tolerance: f32) {
+        assert!((expected - actual).abs() < tolerance, "expected {}, got {}", expected, actual);
+    }
+
+    fn params() -> SourceParameters {
+        SourceParameters{
+            num_images: 4,
+            frame_interval: std::time::Duration::from_secs(300),
+            ..SourceParameters::test_default()
+        }
+    }
+
+    fn strip_geometry(params: &SourceParameters, rotation_comp: f32, idx: usize) -> MapGeometry {
+        let width = projection_view::strip_width(params, rotation_comp).ceil() as u32;
+        MapGeometry::strip(params, rotation_comp, idx, width, 157)
+    }
+
+    #[test]
+    fn left_edge_longitude_shifts_by_rotation_comp_per_frame() {
+        let params = params();
+        let rotation_comp = 10.0;
+        let first = strip_geometry(&params, rotation_comp, 0);
+        for idx in 1..params.num_images {
+            let geometry = strip_geometry(&params, rotation_comp, idx);
+            assert_eq!(first.deg_per_pixel, geometry.deg_per_pixel);
+            assert_close(
+                first.left_edge_longitude.0 + idx as f32 * rotation_comp * first.deg_per_pixel,
+                geometry.left_edge_longitude.0,
+                1.0e-3
+            );
+        }
+        // without compensation, each frame's central meridian is in the middle
+        let geometry = strip_geometry(&params, 0.0, 2);
+        assert_close(-90.0, geometry.left_edge_longitude.0, 1.0e-3);
+        assert_close(-0.5 * geometry.deg_per_pixel * geometry.width as f32, geometry.left_edge_longitude.0, 0.5);
+    }
+
+    #[test]
+    fn matching_rotation_comp_keeps_planet_longitude_of_left_edge() {
+        let params = params();
+        let rotation_comp =
+            projection_view::rotation_comp_to_pixels(coverage::rotation_per_frame(&params), params.disk_diameter);
+        let first = strip_geometry(&params, rotation_comp, 0);
+        for idx in 1..params.num_images {
+            assert_close(
+                first.left_edge_planet_longitude.0,
+                strip_geometry(&params, rotation_comp, idx).left_edge_planet_longitude.0,
+                0.05
+            );
+        }
+    }
+
+    #[test]
+    fn central_meridian_matches_rendered_position() {
+        let params = params();
+        let rotation_comp = -7.0;
+        let geometry = strip_geometry(&params, rotation_comp, 3);
+        let placement = FramePlacement::new(&params, rotation_comp, 3);
+        let cm_x = (placement.offset + 1.0) / 2.0 * geometry.width as f32;
+        assert_close(0.0, geometry.left_edge_longitude.0 + cm_x * geometry.deg_per_pixel, 1.0e-3);
+    }
+
+    #[test]
+    fn winjupos_map_spans_full_circle_from_the_left() {
+        let params = params();
+        let width = projection_view::strip_width(&params, 0.0).ceil() as u32;
+        let geometry = MapGeometry::winjupos(&params, 1, width, 157);
+        assert_eq!(2 * width, geometry.width);
+        assert!(geometry.longitude_increases_leftwards);
+        assert_close(360.0, geometry.deg_per_pixel * geometry.width as f32, 1.0);
+        assert_close(180.0, geometry.left_edge_longitude.0, 1.0);
+    }
+
+    #[test]
+    fn serialization() {
+        let geometry = MapGeometry{
+            width: 800,
+            height: 200,
+            deg_per_pixel: 0.25,
+            left_edge_longitude: Deg(-120.5),
+            left_edge_planet_longitude: Deg(-121.0),
+            longitude_increases_leftwards: false,
+            top_edge_latitude: Deg(90.0)
+        };
+        let frames = [
+            FrameEntry{
+                file_name: "output_00001.png".to_string(),
+                source_frame: 0,
+                time_jd: Some(2459806.5),
+                geometry: geometry.clone()
+            },
+            FrameEntry{ file_name: "a \"b\"\\c.png".to_string(), source_frame: 0, time_jd: None, geometry }
+        ];
+
+        let geometry_json = "\"width\": 800, \"height\": 200, \"deg_per_pixel\": 0.250000, \
+            \"left_edge_longitude\": -120.500000, \"left_edge_planet_longitude\": -121.000000, \
+            \"longitude_increases_leftwards\": false, \"top_edge_latitude\": 90.000000";
+        assert_eq!(
+            format!(
+                "{{\n  \"projection\": \"lambert\",\n  \"standard_parallel\": 30.000000,\n  \"frames\": [\n    \
+                {{ \"file\": \"output_00001.png\", \"source_frame\": 1, \"time_jd\": 2459806.500000, {} }},\n    \
+                {{ \"file\": \"a \\\"b\\\"\\\\c.png\", \"source_frame\": 1, \"time_jd\": null, {} }}\n  ]\n}}\n",
+                geometry_json, geometry_json
+            ),
+            to_json(ProjectionType::LambertCylindricalEqualArea, Deg(30.0), &frames)
+        );
+
+        assert_eq!(
+            "{\n  \"projection\": \"equirectangular\",\n  \"frames\": []\n}\n",
+            to_json(ProjectionType::Equirectangular, Deg(30.0), &[])
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn perf_to_json() {
+        let params = params();
+        let frames: Vec<FrameEntry> = (0..1000).map(|idx| FrameEntry{
+            file_name: format!("output_{:05}.png", idx + 1),
+            source_frame: idx % params.num_images,
+            time_jd: Some(2459806.5 + idx as f64 / 1440.0),
+            geometry: strip_geometry(&params, 10.0, idx % params.num_images)
+        }).collect();
+
+        crate::bench::measure("export metadata to JSON (1000 frames)", 20, || {
+            to_json(ProjectionType::LambertCylindricalEqualArea, Deg(30.0), &frames)
+        }).assert_within(std::time::Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,231 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Named combinations of export options.
+
+use crate::dither;
+use crate::projection::output_format::OutputFormat;
+use strum::IntoEnumIterator;
+
+/// Separates presets in a config string.
+const PRESET_SEPARATOR: char = '|';
+
+/// Characters which cannot be used in preset names (they separate values in config strings).
+const RESERVED_CHARS: [char; 3] = [PRESET_SEPARATOR, ';', '='];
+
+/// Export options covered by presets (i.e., excluding the output folder and options unrelated to the result).
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportSettings {
+    /// `None`: chosen automatically (see `ExportDialog::output_format`).
+    pub output_format: Option<OutputFormat>,
+    pub winjupos: bool,
+    pub polar: bool,
+    pub bounce_back: bool,
+    pub include_grid: bool,
+    pub match_seams: bool,
+    pub metadata: bool,
+    /// `None`: chosen automatically (see `ExportDialog::dithering`).
+    pub dithering: Option<bool>,
+    pub dither_method: dither::Method,
+    pub contact_sheet: bool,
+    pub reproducible: bool
+}
+
+impl Default for ExportSettings {
+    fn default() -> ExportSettings {
+        ExportSettings{
+            output_format: None,
+            winjupos: false,
+            polar: false,
+            bounce_back: false,
+            include_grid: false,
+            match_seams: false,
+            metadata: false,
+            dithering: None,
+            dither_method: dither::Method::FloydSteinberg,
+            contact_sheet: false,
+            reproducible: false
+        }
+    }
+}
+
+impl ExportSettings {
+    /// Returns settings as semicolon-separated "key=value" pairs.
+    pub fn to_config_string(&self) -> String {
+        format!(
+            "output_format={};winjupos={};polar={};bounce_back={};include_grid={};match_seams={};metadata={};\
+                dithering={};dither_method={:?};contact_sheet={};reproducible={}",
+            self.output_format.map_or("auto".to_string(), |f| format!("{:?}", f)),
+            self.winjupos, self.polar, self.bounce_back, self.include_grid, self.match_seams, self.metadata,
+            self.dithering.map_or("auto".to_string(), |d| d.to_string()), self.dither_method, self.contact_sheet,
+            self.reproducible
+        )
+    }
+
+    /// Parses the output of `to_config_string`; missing and unknown keys are ignored.
+    pub fn from_config_string(s: &str) -> Option<ExportSettings> {
+        let mut settings = ExportSettings::default();
+
+        for pair in s.split(';').filter(|pair| !pair.trim().is_empty()) {
+            let (key, value) = pair.split_once('=')?;
+            let value = value.trim();
+            match key.trim() {
+                "output_format" => settings.output_format = if value == "auto" {
+                    None
+                } else {
+                    Some(OutputFormat::iter().find(|format| format!("{:?}", format) == value)?)
+                },
+                "winjupos" => settings.winjupos = value.parse().ok()?,
+                "polar" => settings.polar = value.parse().ok()?,
+                "bounce_back" => settings.bounce_back = value.parse().ok()?,
+                "include_grid" => settings.include_grid = value.parse().ok()?,
+                "match_seams" => settings.match_seams = value.parse().ok()?,
+                "metadata" => settings.metadata = value.parse().ok()?,
+                "dithering" => settings.dithering = if value == "auto" { None } else { Some(value.parse().ok()?) },
+                "dither_method" => settings.dither_method =
+                    dither::Method::iter().find(|method| format!("{:?}", method) == value)?,
+                "contact_sheet" => settings.contact_sheet = value.parse().ok()?,
+                "reproducible" => settings.reproducible = value.parse().ok()?,
+                _ => ()
+            }
+        }
+
+        Some(settings)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preset {
+    pub name: String,
+    pub settings: ExportSettings
+}
+
+impl Preset {
+    /// Creates a preset; reserved characters are removed from `name`.
+    pub fn new(name: &str, settings: ExportSettings) -> Preset {
+        Preset{ name: name.trim().replace(&RESERVED_CHARS[..], ""), settings }
+    }
+
+    /// Returns `presets` as a config string.
+    pub fn list_to_config_string(presets: &[Preset]) -> String {
+        presets.iter()
+            .map(|preset| format!("name={};{}", preset.name, preset.settings.to_config_string()))
+            .collect::<Vec<_>>()
+            .join(&PRESET_SEPARATOR.to_string())
+    }
+
+    /// Parses the output of `list_to_config_string`; presets which cannot be parsed are skipped.
+    pub fn list_from_config_string(s: &str) -> Vec<Preset> {
+        s.split(PRESET_SEPARATOR).filter_map(|preset| {
+            let preset = preset.trim().strip_prefix("name=")?;
+            let (name, settings) = preset.split_once(';').unwrap_or((preset, ""));
+            if name.trim().is_empty() { return None; }
+            Some(Preset::new(name, ExportSettings::from_config_string(settings)?))
+        }).collect()
+    }
+}
+
+/// Returns the presets always available.
+pub fn built_in() -> Vec<Preset> {
+    vec![
+        Preset::new("Web animation", ExportSettings{
+            output_format: Some(OutputFormat::Png8),
+            bounce_back: true,
+            ..Default::default()
+        }),
+        Preset::new("Print map", ExportSettings{
+            output_format: Some(OutputFormat::Tiff16),
+            include_grid: true,
+            match_seams: true,
+            ..Default::default()
+        }),
+        Preset::new("Analysis", ExportSettings{
+            output_format: Some(OutputFormat::Tiff16),
+            metadata: true,
+            dithering: Some(false),
+            reproducible: true,
+            ..Default::default()
+        })
+    ]
+}
+
+/// Returns the index of the first of `presets` with settings equal to `settings`.
+pub fn matching(presets: &[&Preset], settings: &ExportSettings) -> Option<usize> {
+    presets.iter().position(|preset| preset.settings == *settings)
+}
+
+mod tests {
+    use super::*;
+
+    fn user_preset() -> Preset {
+        Preset::new("Polar | GIF", ExportSettings{
+            output_format: Some(OutputFormat::Png16),
+            polar: true,
+            dithering: Some(true),
+            dither_method: dither::Method::Ordered,
+            contact_sheet: true,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        for preset in built_in().iter().chain(std::iter::once(&user_preset())) {
+            assert_eq!(
+                Some(preset.settings.clone()),
+                ExportSettings::from_config_string(&preset.settings.to_config_string())
+            );
+        }
+        assert_eq!(Some(ExportSettings::default()), ExportSettings::from_config_string(""));
+        assert_eq!(None, ExportSettings::from_config_string("dither_method=random"));
+        assert_eq!(None, ExportSettings::from_config_string("output_format=Gif"));
+    }
+
+    #[test]
+    fn user_presets_round_trip() {
+        let presets = vec![user_preset(), Preset::new("defaults", Default::default())];
+        assert_eq!("Polar  GIF", presets[0].name);
+        assert_eq!(presets, Preset::list_from_config_string(&Preset::list_to_config_string(&presets)));
+        assert!(Preset::list_from_config_string("").is_empty());
+    }
+
+    #[test]
+    fn malformed_presets_are_skipped() {
+        let presets = Preset::list_from_config_string(
+            "name=a;polar=maybe|bounce_back=true|name=b;metadata=true|name=;polar=true"
+        );
+        assert_eq!(vec![Preset::new("b", ExportSettings{ metadata: true, ..Default::default() })], presets);
+    }
+
+    #[test]
+    fn matching_preset_is_found() {
+        let built_in = built_in();
+        let user = user_preset();
+        let presets: Vec<&Preset> = built_in.iter().chain(std::iter::once(&user)).collect();
+
+        assert_eq!(Some(0), matching(&presets, &ExportSettings{
+            output_format: Some(OutputFormat::Png8),
+            bounce_back: true,
+            ..Default::default()
+        }));
+        assert_eq!(None, matching(&presets, &ExportSettings{ bounce_back: true, ..Default::default() }));
+        assert_eq!(Some(3), matching(&presets, &user.settings));
+        assert_eq!(None, matching(&presets, &ExportSettings::default()));
+    }
+}
@@ -0,0 +1,265 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Preview of an exported frame at its actual pixel size. The frame is rendered by the worker in the same way as
+//! during the export (see `worker::PreviewFrame`).
+
+use crate::config::Configuration;
+use crate::gui;
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+use crate::projection::data::TexturesInUse;
+use crossbeam::channel::TryRecvError;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub const TITLE: &str = "Output preview";
+
+/// Part of the output image shown in the preview (in pixels).
+#[derive(Debug, PartialEq)]
+struct Region {
+    /// Top-left corner.
+    offset: [u32; 2],
+    size: [u32; 2]
+}
+
+/// Returns the part of an image of `image_size` which fits in an area of `area_size` (in physical pixels) when
+/// panned to `offset`; the region stays within the image.
+fn visible_region(image_size: [u32; 2], area_size: [u32; 2], offset: [f32; 2]) -> Region {
+    let axis = |i: usize| {
+        let size = image_size[i].min(area_size[i]);
+        ((offset[i].max(0.0).round() as u32).min(image_size[i] - size), size)
+    };
+    let (x, width) = axis(0);
+    let (y, height) = axis(1);
+
+    Region{ offset: [x, y], size: [width, height] }
+}
+
+/// Frame being rendered by the worker.
+struct PendingFrame {
+    frame_idx: usize,
+    receiver: crossbeam::channel::Receiver<Result<ga_image::Image, String>>,
+    /// Source textures used by the worker; kept alive until it finishes.
+    _textures: Option<TexturesInUse>
+}
+
+pub struct ExportPreview {
+    display: glium::Display,
+    renderer: Rc<RefCell<imgui_glium_renderer::Renderer>>,
+    texture_id: Option<imgui::TextureId>,
+    /// Size of the shown output image.
+    image_size: [u32; 2],
+    shown_frame: Option<usize>,
+    /// Frame chosen for previewing.
+    frame_idx: usize,
+    num_frames: usize,
+    /// The chosen frame is to be rendered (see `take_render_request`).
+    render_requested: bool,
+    pending: Option<PendingFrame>,
+    error: Option<String>,
+    /// Top-left corner of the shown region of the output image (in pixels).
+    offset: [f32; 2],
+    /// Value of `offset` when dragging started.
+    drag_start_offset: [f32; 2]
+}
+
+impl ExportPreview {
+    pub fn new(display: &glium::Display, renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>) -> ExportPreview {
+        ExportPreview{
+            display: display.clone(),
+            renderer: Rc::clone(renderer),
+            texture_id: None,
+            image_size: [0, 0],
+            shown_frame: None,
+            frame_idx: 0,
+            num_frames: 1,
+            render_requested: false,
+            pending: None,
+            error: None,
+            offset: [0.0, 0.0],
+            drag_start_offset: [0.0, 0.0]
+        }
+    }
+
+    pub fn set_num_frames(&mut self, num_frames: usize) {
+        self.num_frames = num_frames.max(1);
+        self.frame_idx = self.frame_idx.min(self.num_frames - 1);
+    }
+
+    /// Opens the preview (as a popup nested in the current one) and requests rendering of the chosen frame
+    /// (export settings may have changed since the last preview).
+    pub fn open(&mut self, ui: &imgui::Ui) {
+        self.render_requested = true;
+        self.error = None;
+        ui.open_popup(TITLE);
+    }
+
+    /// Returns the frame to be rendered with the current export settings, if requested.
+    pub fn take_render_request(&mut self) -> Option<usize> {
+        if std::mem::take(&mut self.render_requested) { Some(self.frame_idx) } else { None }
+    }
+
+    /// To be called after requesting the worker to render `frame_idx` (`textures`: source textures of the request).
+    pub fn set_pending(
+        &mut self,
+        frame_idx: usize,
+        receiver: crossbeam::channel::Receiver<Result<ga_image::Image, String>>,
+        textures: Option<TexturesInUse>
+    ) {
+        self.pending = Some(PendingFrame{ frame_idx, receiver, _textures: textures });
+    }
+
+    /// Shows the image rendered by the worker once it has been received.
+    fn update(&mut self) {
+        let result = match &self.pending {
+            None => return,
+            Some(pending) => match pending.receiver.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => Err("rendering has been interrupted".to_string())
+            }
+        };
+        let frame_idx = self.pending.take().unwrap().frame_idx;
+
+        match result {
+            Ok(image) => match self.show(&image) {
+                Ok(()) => {
+                    self.shown_frame = Some(frame_idx);
+                    self.error = None;
+                },
+                Err(e) => self.error = Some(e.to_string())
+            },
+            Err(e) => self.error = Some(e)
+        }
+    }
+
+    fn show(&mut self, image: &ga_image::Image) -> Result<(), Box<dyn std::error::Error>> {
+        let texture = crate::data::create_texture_from_image(image, &self.display)?;
+        self.image_size = [image.width(), image.height()];
+        let imgui_tex = imgui_glium_renderer::Texture{
+            texture: Rc::new(texture),
+            // shown at 1:1 scale
+            sampler: glium::uniforms::SamplerBehavior{
+                magnify_filter: glium::uniforms::MagnifySamplerFilter::Nearest,
+                minify_filter: glium::uniforms::MinifySamplerFilter::Nearest,
+                ..Default::default()
+            }
+        };
+
+        let mut renderer = self.renderer.borrow_mut();
+        match self.texture_id {
+            None => self.texture_id = Some(renderer.textures().insert(imgui_tex)),
+            Some(id) => { renderer.textures().replace(id, imgui_tex); }
+        }
+
+        Ok(())
+    }
+}
+
+/// Shows the preview opened with `ExportPreview::open`.
+pub fn handle_export_preview(
+    ui: &imgui::Ui,
+    gui_state: &gui::GuiState,
+    config: &mut Configuration,
+    preview: &mut ExportPreview
+) {
+    preview.update();
+
+    let bindings = KeyBindings{ escape_cancels: true, enter_accepts: false };
+    modal::modal(ui, config, TITLE, bindings, |key_action, _| {
+        gui::add_text_before(ui, "frame");
+        let mut value = preview.frame_idx as u32 + 1;
+        if imgui::Slider::new("##preview-frame", 1, preview.num_frames as u32)
+            .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+            .build(ui, &mut value)
+        {
+            preview.frame_idx = value as usize - 1;
+        }
+        // rendering may take a while, so it is not done while dragging the slider
+        if ui.is_item_deactivated_after_edit() { preview.render_requested = true; }
+
+        ui.same_line();
+        if ui.button("Close") || key_action == KeyAction::Cancel { ui.close_current_popup(); }
+
+        if preview.pending.is_some() {
+            ui.text_disabled("Rendering...");
+        } else if let Some(error) = &preview.error {
+            ui.text_colored([1.0, 0.3, 0.3, 1.0], format!("Rendering failed: {}.", error));
+        } else if let Some(frame_idx) = preview.shown_frame {
+            ui.text(format!(
+                "Frame {}, {}×{} pixels at actual size (drag to pan).",
+                frame_idx + 1, preview.image_size[0], preview.image_size[1]
+            ));
+        } else {
+            ui.text("");
+        }
+
+        let texture_id = match (preview.texture_id, preview.shown_frame) {
+            (Some(id), Some(_)) => id,
+            _ => return
+        };
+
+        let hidpi_factor = gui_state.hidpi_factor() as f32;
+        let area = gui::adjust_pos_for_exact_hidpi_scaling(ui, 0.0, hidpi_factor);
+        let region = visible_region(preview.image_size, area.physical_size, preview.offset);
+        preview.offset = [region.offset[0] as f32, region.offset[1] as f32];
+
+        let [width, height] = preview.image_size.map(|s| s as f32);
+        imgui::Image::new(texture_id, [region.size[0] as f32 / hidpi_factor, region.size[1] as f32 / hidpi_factor])
+            .uv0([region.offset[0] as f32 / width, region.offset[1] as f32 / height])
+            .uv1([
+                (region.offset[0] + region.size[0]) as f32 / width,
+                (region.offset[1] + region.size[1]) as f32 / height
+            ])
+            .build(ui);
+
+        if ui.is_item_clicked_with_button(imgui::MouseButton::Left) { preview.drag_start_offset = preview.offset; }
+        if ui.is_item_hovered() && ui.is_mouse_dragging(imgui::MouseButton::Left) {
+            let delta = ui.mouse_drag_delta_with_button(imgui::MouseButton::Left);
+            preview.offset = [
+                preview.drag_start_offset[0] - delta[0] * hidpi_factor,
+                preview.drag_start_offset[1] - delta[1] * hidpi_factor
+            ];
+        }
+    });
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_image_is_shown_whole() {
+        assert_eq!(
+            Region{ offset: [0, 0], size: [300, 200] },
+            visible_region([300, 200], [1000, 800], [50.0, 20.0])
+        );
+    }
+
+    #[test]
+    fn panning_stays_within_image() {
+        assert_eq!(
+            Region{ offset: [120, 30], size: [400, 300] },
+            visible_region([2000, 1000], [400, 300], [120.4, 29.6])
+        );
+        assert_eq!(
+            Region{ offset: [1600, 0], size: [400, 300] },
+            visible_region([2000, 1000], [400, 300], [5000.0, -10.0])
+        );
+    }
+}
@@ -0,0 +1,306 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Field rotation of alt-az mounted captures: the sky's orientation in the image follows the parallactic angle,
+//! which changes during the sequence. Per-frame roll is either predicted from the parallactic angle (given
+//! the observing site) or estimated from the orientation of the planet's flattened limb.
+
+use cgmath::{Angle, Deg, Point2, Rad};
+use crate::normalization;
+use crate::projection::ephem;
+use ga_image::{Image, PixelFormat};
+use std::time::Duration;
+
+/// Disk pixels are searched for within this fraction of the disk radius.
+const LIMB_SEARCH_RADIUS_FRACTION: f32 = 1.25;
+
+/// Pixels brighter than this fraction of the disk's mean brightness belong to the disk.
+const LIMB_THRESHOLD_FRACTION: f32 = 0.25;
+
+/// Minimum elongation (difference of the disk's principal second moments relative to their sum) for which the limb
+/// orientation is considered measurable (Jupiter: ca. 0.065, Mars: ca. 0.006).
+const MIN_ELONGATION: f64 = 0.01;
+
+/// Geographic location of the observing site.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Site {
+    pub latitude: Deg<f64>,
+    /// Positive towards east.
+    pub longitude: Deg<f64>
+}
+
+/// Returns the parallactic angle, i.e., the position angle (measured from celestial north towards east) of
+/// the direction towards the zenith, of an object at `hour_angle` and `declination` seen from `latitude`.
+pub fn parallactic_angle(hour_angle: Deg<f64>, declination: Deg<f64>, latitude: Deg<f64>) -> Deg<f64> {
+    Rad::atan2(
+        hour_angle.sin(),
+        latitude.tan() * declination.cos() - declination.sin() * hour_angle.cos()
+    ).into()
+}
+
+/// Returns per-frame roll offsets (relative to the first frame) caused by field rotation of an alt-az mounted
+/// camera observing `body` from `site`; the first frame is taken at `start_jd`.
+///
+/// The camera keeps its orientation with respect to the zenith, so the celestial north (and the planet's
+/// north pole) turns clockwise in the image as the parallactic angle grows.
+pub fn parallactic_roll_offsets(
+    body: ephem::Body,
+    site: Site,
+    start_jd: f64,
+    frame_interval: Duration,
+    num_frames: usize
+) -> Vec<Deg<f32>> {
+    let angle_at = |jd: f64| {
+        let orientation = ephem::axis_orientation(body, jd);
+        let hour_angle = ephem::greenwich_sidereal_time(jd) + site.longitude.0 - orientation.right_ascension;
+        parallactic_angle(Deg(hour_angle), Deg(orientation.declination), site.latitude)
+    };
+
+    let interval_days = frame_interval.as_secs_f64() / 86400.0;
+    let start = angle_at(start_jd);
+
+    (0..num_frames).map(|idx| {
+        let offset = (angle_at(start_jd + idx as f64 * interval_days) - start).normalize_signed();
+        Deg(offset.0 as f32)
+    }).collect()
+}
+
+/// Returns roll (see `SourceParameters::roll`) of the planet's flattened limb in an RGB8 image, determined from
+/// the orientation of the disk's principal axes; `None` if the disk is too close to circular (or not found).
+///
+/// `image_mirror` are the sign multipliers undoing mirroring of the image (see `SourceParameters::image_mirror`);
+/// the result is in (-90°; 90°].
+pub fn limb_angle(
+    image: &Image,
+    disk_center: Point2<f32>,
+    disk_diameter: f32,
+    image_mirror: [f32; 2]
+) -> Option<Deg<f32>> {
+    assert!(image.pixel_format() == PixelFormat::RGB8);
+
+    let threshold = LIMB_THRESHOLD_FRACTION * normalization::disk_mean_brightness(image, disk_center, disk_diameter)?;
+
+    let radius = LIMB_SEARCH_RADIUS_FRACTION * disk_diameter / 2.0;
+    let y_range = (disk_center.y - radius).floor().max(0.0) as u32
+        ..((disk_center.y + radius).ceil().max(0.0) as u32).min(image.height());
+    let x_range = (disk_center.x - radius).floor().max(0.0) as u32
+        ..((disk_center.x + radius).ceil().max(0.0) as u32).min(image.width());
+
+    // moments are accumulated relative to `disk_center` for precision
+    let (mut count, mut sum_x, mut sum_y, mut sum_xx, mut sum_yy, mut sum_xy) = (0usize, 0.0, 0.0, 0.0, 0.0, 0.0f64);
+    for y in y_range {
+        let line = image.line::<u8>(y);
+        let dy = (y as f32 + 0.5 - disk_center.y) as f64;
+        for x in x_range.clone() {
+            let pixel = &line[3 * x as usize..3 * x as usize + 3];
+            let value = (pixel[0] as u32 + pixel[1] as u32 + pixel[2] as u32) as f32 / 3.0;
+            if value > threshold {
+                let dx = (x as f32 + 0.5 - disk_center.x) as f64;
+                count += 1;
+                sum_x += dx;
+                sum_y += dy;
+                sum_xx += dx * dx;
+                sum_yy += dy * dy;
+                sum_xy += dx * dy;
+            }
+        }
+    }
+    if count == 0 { return None; }
+
+    let n = count as f64;
+    let (mean_x, mean_y) = (sum_x / n, sum_y / n);
+    let mu20 = sum_xx / n - mean_x * mean_x;
+    let mu02 = sum_yy / n - mean_y * mean_y;
+    let mu11 = sum_xy / n - mean_x * mean_y;
+
+    let elongation = (4.0 * mu11 * mu11 + (mu20 - mu02) * (mu20 - mu02)).sqrt() / (mu20 + mu02);
+    if elongation.is_nan() || elongation < MIN_ELONGATION { return None; }
+
+    // direction of the major axis (the equator), measured from the X axis towards Y; as Y points down, the angle
+    // grows clockwise, like roll
+    let major_axis_angle = 0.5 * (2.0 * mu11).atan2(mu20 - mu02);
+
+    // mirroring by a single axis reverses the angle
+    let angle = (major_axis_angle.to_degrees() as f32) * image_mirror[0] * image_mirror[1];
+
+    Some(Deg(if angle <= -90.0 { angle + 180.0 } else if angle > 90.0 { angle - 180.0 } else { angle }))
+}
+
+/// Returns per-frame roll offsets (relative to the first frame) following the slow trend of the measured limb
+/// angles (see `limb_angle`; `None` marks frames where the measurement failed). A quadratic fitted to the angles
+/// suppresses measurement noise; returns `None` if fewer than 3 frames have been measured.
+pub fn limb_roll_offsets(angles: &[Option<Deg<f32>>]) -> Option<Vec<Deg<f32>>> {
+    // angles are defined modulo 180°; keep all of them close to the first one
+    let reference = angles.iter().flatten().next()?.0 as f64;
+    let samples: Vec<(f64, f64)> = angles.iter().enumerate().filter_map(|(idx, angle)| angle.map(|angle| {
+        let diff = (angle.0 as f64 - reference + 90.0).rem_euclid(180.0) - 90.0;
+        (idx as f64, reference + diff)
+    })).collect();
+    if samples.len() < 3 { return None; }
+
+    let coeffs = fit_quadratic(&samples)?;
+    let value_at = |x: f64| coeffs[0] + coeffs[1] * x + coeffs[2] * x * x;
+
+    Some((0..angles.len()).map(|idx| Deg((value_at(idx as f64) - value_at(0.0)) as f32)).collect())
+}
+
+/// Returns coefficients [c0, c1, c2] of the least-squares fit y = c0 + c1·x + c2·x² to (x, y) samples.
+fn fit_quadratic(samples: &[(f64, f64)]) -> Option<[f64; 3]> {
+    // x values are normalized to [0; 1] to keep the normal equations well-conditioned
+    let x_max = samples.iter().map(|s| s.0).fold(0.0, f64::max).max(1.0);
+
+    // sums of x^k (k = 0..4) and of y·x^k (k = 0..2)
+    let mut sum_x = [0.0; 5];
+    let mut sum_xy = [0.0; 3];
+    for &(x, y) in samples {
+        let x = x / x_max;
+        let powers = [1.0, x, x * x, x * x * x, x * x * x * x];
+        for (sum, power) in sum_x.iter_mut().zip(powers.iter()) { *sum += power; }
+        for (sum, power) in sum_xy.iter_mut().zip(powers.iter()) { *sum += power * y; }
+    }
+
+    let det3 = |m: [[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let matrix = [
+        [sum_x[0], sum_x[1], sum_x[2]],
+        [sum_x[1], sum_x[2], sum_x[3]],
+        [sum_x[2], sum_x[3], sum_x[4]]
+    ];
+    let det = det3(matrix);
+    if det.abs() < 1.0e-12 { return None; }
+
+    // Cramer's rule
+    let coeff = |col: usize| {
+        let mut m = matrix;
+        for (row, value) in m.iter_mut().zip(sum_xy.iter()) { row[col] = *value; }
+        det3(m) / det
+    };
+
+    Some([coeff(0), coeff(1) / x_max, coeff(2) / (x_max * x_max)])
+}
+
+mod tests {
+    use super::*;
+
+    /// Returns an RGB8 image of a uniform disk of `flattening`, rotated by `roll` (clockwise).
+    fn flattened_disk_image(center: [f32; 2], radius: f32, flattening: f32, roll: Deg<f32>) -> Image {
+        let (width, height) = (160, 140);
+        let mut image = Image::new(width, height, None, PixelFormat::RGB8, None, true);
+        let (sin, cos) = (roll.sin(), roll.cos());
+        for y in 0..height {
+            let line = image.line_mut::<u8>(y);
+            for x in 0..width {
+                let (dx, dy) = (x as f32 + 0.5 - center[0], y as f32 + 0.5 - center[1]);
+                // coordinates along the equator and the polar axis
+                let (u, v) = (dx * cos + dy * sin, -dx * sin + dy * cos);
+                let polar_radius = radius * (1.0 - flattening);
+                let inside = u * u / (radius * radius) + v * v / (polar_radius * polar_radius) <= 1.0;
+                let value = if inside { 180 } else { 5 };
+                line[3 * x as usize..3 * x as usize + 3].copy_from_slice(&[value; 3]);
+            }
+        }
+
+        image
+    }
+
+    #[test]
+    fn parallactic_angle_is_zero_on_meridian_and_antisymmetric() {
+        assert!(parallactic_angle(Deg(0.0), Deg(-20.0), Deg(50.0)).0.abs() < 1.0e-9);
+
+        let east = parallactic_angle(Deg(-30.0), Deg(-20.0), Deg(50.0));
+        let west = parallactic_angle(Deg(30.0), Deg(-20.0), Deg(50.0));
+        // after the meridian, the zenith is to the east of the object
+        assert!(west.0 > 0.0);
+        assert!((east.0 + west.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn parallactic_angle_at_equator_is_right_angle_for_equatorial_objects() {
+        assert!((parallactic_angle(Deg(45.0), Deg(0.0), Deg(0.0)).0 - 90.0).abs() < 1.0e-9);
+        assert!((parallactic_angle(Deg(-45.0), Deg(0.0), Deg(0.0)).0 + 90.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn parallactic_offsets_are_symmetric_about_meridian_transit() {
+        let body = ephem::Body::Jupiter;
+        let transit_jd = ephem::julian_date(2022, 9, 26, 23, 0, 0.0);
+        let orientation = ephem::axis_orientation(body, transit_jd);
+        // place the site so that the planet transits the meridian at `transit_jd`
+        let site = Site{
+            latitude: Deg(50.0),
+            longitude: Deg(orientation.right_ascension - ephem::greenwich_sidereal_time(transit_jd))
+        };
+
+        // 2 hours, starting 1 hour before the transit
+        let num_frames = 121;
+        let offsets = parallactic_roll_offsets(
+            body, site, transit_jd - 1.0 / 24.0, Duration::from_secs(60), num_frames
+        );
+
+        assert_eq!(num_frames, offsets.len());
+        assert_eq!(0.0, offsets[0].0);
+        // the angle grows monotonically (from negative to positive)
+        assert!(offsets.windows(2).all(|w| w[1].0 > w[0].0));
+        let (middle, last) = (offsets[num_frames / 2].0, offsets[num_frames - 1].0);
+        assert!(middle > 5.0, "{}", middle);
+        assert!((last - 2.0 * middle).abs() < 0.1, "{} {}", middle, last);
+    }
+
+    #[test]
+    fn limb_angle_of_flattened_disk_is_measured() {
+        let center = Point2{ x: 80.0, y: 70.0 };
+        for roll in [-35.0, -5.0, 0.0, 20.0, 60.0] {
+            let image = flattened_disk_image([80.0, 70.0], 50.0, 0.065, Deg(roll));
+            let angle = limb_angle(&image, center, 100.0, [1.0, 1.0]).unwrap();
+            assert!((angle.0 - roll).abs() < 0.5, "{} {:?}", roll, angle);
+
+            let mirrored = limb_angle(&image, center, 100.0, [-1.0, 1.0]).unwrap();
+            assert!((mirrored.0 + roll).abs() < 0.5, "{} {:?}", roll, mirrored);
+        }
+    }
+
+    #[test]
+    fn limb_angle_of_circular_disk_is_not_measurable() {
+        let image = flattened_disk_image([80.0, 70.0], 50.0, 0.0, Deg(0.0));
+        assert!(limb_angle(&image, Point2{ x: 80.0, y: 70.0 }, 100.0, [1.0, 1.0]).is_none());
+    }
+
+    #[test]
+    fn limb_offsets_follow_trend() {
+        // a drift of 0.1° per frame plus alternating noise, with one failed measurement; wraps around ±90°
+        let angles: Vec<Option<Deg<f32>>> = (0..40).map(|idx| {
+            if idx == 7 { return None; }
+            let noise = if idx % 2 == 0 { 0.3 } else { -0.3 };
+            let angle = 88.0 + 0.1 * idx as f32 + noise;
+            Some(Deg(if angle > 90.0 { angle - 180.0 } else { angle }))
+        }).collect();
+
+        let offsets = limb_roll_offsets(&angles).unwrap();
+        assert_eq!(40, offsets.len());
+        assert_eq!(0.0, offsets[0].0);
+        for (idx, offset) in offsets.iter().enumerate() {
+            assert!((offset.0 - 0.1 * idx as f32).abs() < 0.15, "{} {:?}", idx, offset);
+        }
+
+        assert!(limb_roll_offsets(&[Some(Deg(1.0)), None, Some(Deg(2.0))]).is_none());
+    }
+}
@@ -0,0 +1,229 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Detection of image files which change or disappear between their selection and loading (e.g., still being written
+//! by capture software, renamed, or on a disconnected drive), and classification of the resulting load errors.
+
+use std::error::Error;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Files modified more recently than this may still be being written.
+const RECENT_MODIFICATION: Duration = Duration::from_secs(10);
+
+/// Interval between the two checks of a recently modified file.
+pub const STABILITY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Delay before the single retry of a file which failed to load for a possibly transient reason.
+pub const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FailureKind {
+    /// The file does not exist (anymore).
+    Missing,
+    /// The file changed while being checked or read.
+    StillBeingWritten,
+    /// The file ends prematurely.
+    Truncated,
+    /// Any other error (e.g., an unsupported format).
+    Other
+}
+
+impl FailureKind {
+    /// Returns `true` if loading the file again later may succeed.
+    pub fn transient(&self) -> bool { *self != FailureKind::Other }
+
+    fn description(&self) -> Option<&'static str> {
+        match self {
+            FailureKind::Missing => Some("file is missing (renamed, deleted or on a disconnected drive)"),
+            FailureKind::StillBeingWritten => Some("file is still being written"),
+            FailureKind::Truncated => Some("file is truncated"),
+            FailureKind::Other => None
+        }
+    }
+}
+
+/// Error of a file whose size or modification time has changed while it was being checked or read.
+#[derive(Debug)]
+pub struct FileChanged;
+
+impl std::fmt::Display for FileChanged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "size or modification time changed")
+    }
+}
+
+impl Error for FileChanged {}
+
+/// Size and modification time of a file.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FileState {
+    len: u64,
+    modified: Option<SystemTime>
+}
+
+impl FileState {
+    pub fn of(path: &Path) -> std::io::Result<FileState> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(FileState{ len: metadata.len(), modified: metadata.modified().ok() })
+    }
+}
+
+/// Returns `true` if the file may still be being written (a modification time in the future also counts, as it
+/// cannot be relied on).
+fn recently_modified(state: &FileState, now: SystemTime) -> bool {
+    state.modified.map_or(false, |modified| now.duration_since(modified).map_or(true, |age| age < RECENT_MODIFICATION))
+}
+
+/// Returns the state of the file at `path`. A recently modified file is checked again after `interval` and
+/// rejected (with `FileChanged`) if it has changed in the meantime.
+pub fn stable_state(path: &Path, interval: Duration, now: SystemTime) -> Result<FileState, Box<dyn Error>> {
+    let state = FileState::of(path)?;
+    if !recently_modified(&state, now) { return Ok(state); }
+
+    std::thread::sleep(interval);
+    if FileState::of(path)? != state { return Err(Box::new(FileChanged)); }
+
+    Ok(state)
+}
+
+/// Determines why loading a file has failed, from `error` and its sources.
+pub fn classify(error: &(dyn Error + 'static)) -> FailureKind {
+    let from_io = |error: &std::io::Error| match error.kind() {
+        std::io::ErrorKind::NotFound => Some(FailureKind::Missing),
+        std::io::ErrorKind::UnexpectedEof => Some(FailureKind::Truncated),
+        _ => None
+    };
+
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if error.is::<FileChanged>() { return FailureKind::StillBeingWritten; }
+
+        let kind = if let Some(io_error) = error.downcast_ref::<std::io::Error>() {
+            from_io(io_error)
+        } else if let Some(image::ImageError::IoError(io_error)) = error.downcast_ref::<image::ImageError>() {
+            from_io(io_error)
+        } else {
+            None
+        };
+        if let Some(kind) = kind { return kind; }
+
+        // some decoders report a premature end only in the message
+        let message = error.to_string().to_lowercase();
+        if ["unexpected end", "end of file", "eof"].iter().any(|s| message.contains(s)) {
+            return FailureKind::Truncated;
+        }
+
+        current = error.source();
+    }
+
+    FailureKind::Other
+}
+
+/// Returns the message of `error`, preceded by the reason of the failure if it has been recognized.
+pub fn describe(error: &(dyn Error + 'static)) -> String {
+    match classify(error).description() {
+        Some(description) => format!("{} ({})", description, error),
+        None => error.to_string()
+    }
+}
+
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn test_file(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("vislumino-test-file-stability-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("frame.png")
+    }
+
+    #[test]
+    fn only_recently_modified_files_are_suspect() {
+        let path = test_file("recent");
+        std::fs::write(&path, [0u8; 16]).unwrap();
+        let state = FileState::of(&path).unwrap();
+        let modified = state.modified.unwrap();
+
+        assert!(recently_modified(&state, modified + Duration::from_secs(1)));
+        assert!(!recently_modified(&state, modified + Duration::from_secs(3600)));
+        assert!(recently_modified(&state, modified - Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn unchanged_file_is_stable() {
+        let path = test_file("stable");
+        std::fs::write(&path, [0u8; 16]).unwrap();
+        let state = stable_state(&path, Duration::from_millis(20), SystemTime::now()).unwrap();
+        assert_eq!(16, state.len);
+    }
+
+    #[test]
+    fn growing_file_is_rejected() {
+        let path = test_file("growing");
+        let mut file = std::fs::File::create(&path).unwrap();
+        let writer = std::thread::spawn(move || {
+            for _ in 0..30 {
+                file.write_all(&[0u8; 1024]).unwrap();
+                file.flush().unwrap();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        });
+
+        std::thread::sleep(Duration::from_millis(20));
+        let error = stable_state(&path, Duration::from_millis(100), SystemTime::now()).unwrap_err();
+        assert_eq!(FailureKind::StillBeingWritten, classify(error.as_ref()));
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn missing_file_is_classified() {
+        let path = test_file("missing");
+        let error = stable_state(&path, Duration::ZERO, SystemTime::now()).unwrap_err();
+        assert_eq!(FailureKind::Missing, classify(error.as_ref()));
+        assert!(describe(error.as_ref()).starts_with("file is missing"));
+    }
+
+    #[test]
+    fn truncated_image_is_classified() {
+        let mut encoded = vec![];
+        let gradient = image::RgbImage::from_fn(64, 64, |x, y| image::Rgb([4 * x as u8, 4 * y as u8, 0]));
+        image::DynamicImage::ImageRgb8(gradient)
+            .write_to(&mut std::io::Cursor::new(&mut encoded), image::ImageOutputFormat::Png)
+            .unwrap();
+        encoded.truncate(encoded.len() / 2);
+
+        let error = crate::image_utils::StagingBuffers::default()
+            .decode(std::io::Cursor::new(encoded), Some(image::ImageFormat::Png))
+            .unwrap_err();
+        assert_eq!(FailureKind::Truncated, classify(error.as_ref()));
+
+        let io_error = image::ImageError::IoError(std::io::Error::from(std::io::ErrorKind::UnexpectedEof));
+        assert_eq!(FailureKind::Truncated, classify(&io_error));
+    }
+
+    #[test]
+    fn other_errors_are_not_transient() {
+        let error: Box<dyn Error> = "unsupported pixel format".into();
+        assert_eq!(FailureKind::Other, classify(error.as_ref()));
+        assert!(!classify(error.as_ref()).transient());
+        assert_eq!("unsupported pixel format", describe(error.as_ref()));
+    }
+}
@@ -0,0 +1,302 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Conversion between frame indices and elapsed time since the start of a sequence.
+
+use crate::config::Configuration;
+use crate::gui;
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+use crate::logging;
+use crate::projection::winjupos;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const OUT_OF_ORDER_TITLE: &str = "Frame times out of order";
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// Maximum difference from -24 h of a backward jump between consecutive timestamps which is treated as a day
+/// rollover (i.e., the time wrapped around at midnight, but the date was not advanced).
+const MAX_ROLLOVER_DEVIATION: f64 = 3.0 * 3600.0;
+
+/// Maximum number of out-of-order frames listed by name.
+const MAX_LISTED_FRAMES: usize = 10;
+
+/// Times of a sequence's frames.
+pub enum FrameTimes<'a> {
+    /// Frames are `interval` apart.
+    Uniform{ num_frames: usize, interval: Duration },
+    /// Seconds since the earliest frame, for each frame (not necessarily in ascending order).
+    Timestamps(&'a [f64])
+}
+
+impl FrameTimes<'_> {
+    fn num_frames(&self) -> usize {
+        match self {
+            FrameTimes::Uniform{ num_frames, .. } => *num_frames,
+            FrameTimes::Timestamps(times) => times.len()
+        }
+    }
+
+    /// Time of frame `idx` since the start of the sequence.
+    pub fn elapsed(&self, idx: usize) -> Duration {
+        match self {
+            FrameTimes::Uniform{ interval, .. } => *interval * idx as u32,
+            FrameTimes::Timestamps(times) => Duration::from_secs_f64(times[idx])
+        }
+    }
+
+    /// Time between the earliest and the latest frame.
+    pub fn span(&self) -> Duration {
+        match self {
+            FrameTimes::Uniform{ num_frames, .. } => self.elapsed(num_frames.saturating_sub(1)),
+            FrameTimes::Timestamps(times) => Duration::from_secs_f64(times.iter().copied().fold(0.0, f64::max))
+        }
+    }
+
+    /// Returns the index of the frame nearest to `elapsed` (of two equally near frames, the later one).
+    pub fn nearest_frame(&self, elapsed: Duration) -> usize {
+        let elapsed = elapsed.as_secs_f64();
+        match self {
+            FrameTimes::Uniform{ interval, .. } => {
+                if interval.is_zero() { return 0; }
+                ((elapsed / interval.as_secs_f64()).round() as usize).min(self.num_frames().saturating_sub(1))
+            },
+
+            FrameTimes::Timestamps(times) => {
+                let mut nearest = 0;
+                for (idx, time) in times.iter().enumerate() {
+                    let diff = (time - elapsed).abs();
+                    let nearest_diff = (times[nearest] - elapsed).abs();
+                    if diff < nearest_diff || (diff == nearest_diff && *time > times[nearest]) { nearest = idx; }
+                }
+                nearest
+            }
+        }
+    }
+}
+
+/// Adds a day to timestamps (seconds, in frame order) following each backward jump of about a day between
+/// consecutive ones; returns the number of such jumps.
+pub fn correct_day_rollover(times: &mut [f64]) -> usize {
+    let mut num_rollovers = 0;
+    let mut offset = 0.0;
+    for idx in 1..times.len() {
+        let jump = times[idx] + offset - times[idx - 1];
+        if (jump + SECONDS_PER_DAY).abs() <= MAX_ROLLOVER_DEVIATION {
+            offset += SECONDS_PER_DAY;
+            num_rollovers += 1;
+        }
+        times[idx] += offset;
+    }
+
+    num_rollovers
+}
+
+/// Applies `correct_day_rollover` to Julian dates (in frame order).
+pub fn correct_day_rollover_jd(julian_dates: &mut [f64]) -> usize {
+    let start = match julian_dates.first() { Some(start) => *start, None => return 0 };
+    let mut times: Vec<f64> = julian_dates.iter().map(|jd| (jd - start) * SECONDS_PER_DAY).collect();
+    let num_rollovers = correct_day_rollover(&mut times);
+    for (jd, time) in julian_dates.iter_mut().zip(&times) { *jd = start + time / SECONDS_PER_DAY; }
+
+    num_rollovers
+}
+
+/// Returns indices of timestamps (in frame order) earlier than the preceding one.
+pub fn out_of_order(times: &[f64]) -> Vec<usize> {
+    (1..times.len()).filter(|&idx| times[idx] < times[idx - 1]).collect()
+}
+
+/// Replaces timestamps at `indices` (ascending) with the preceding timestamp plus `interval`, then makes the
+/// timestamps relative to the earliest one again.
+pub fn fill_with_interval(times: &mut [f64], indices: &[usize], interval: Duration) {
+    for &idx in indices.iter().filter(|&&idx| idx > 0) { times[idx] = times[idx - 1] + interval.as_secs_f64(); }
+    let start = times.iter().copied().fold(f64::INFINITY, f64::min);
+    for time in times.iter_mut() { *time -= start; }
+}
+
+/// Returns times (seconds since the earliest frame) of frames loaded from WinJUPOS-style named files, corrected
+/// for day rollovers (see `correct_day_rollover`); `None` if not all names contain a time or all times are equal.
+pub fn timestamps_from_names(paths: &[PathBuf]) -> Option<Vec<f64>> {
+    let mut julian_dates: Vec<f64> = paths.iter()
+        .map(|path| path.file_name().and_then(|name| name.to_str()).and_then(winjupos::parse_file_name))
+        .map(|parsed| parsed.map(|(julian_date, _)| julian_date))
+        .collect::<Option<_>>()?;
+
+    let num_rollovers = correct_day_rollover_jd(&mut julian_dates);
+    if num_rollovers > 0 {
+        logging::log_info!("Frame times taken from file names corrected for {} day rollover(s).", num_rollovers);
+    }
+
+    let start = julian_dates.iter().copied().fold(f64::INFINITY, f64::min);
+    let times: Vec<f64> = julian_dates.iter().map(|jd| (jd - start) * SECONDS_PER_DAY).collect();
+    if times.iter().all(|time| *time == 0.0) { return None; }
+
+    Some(times)
+}
+
+pub enum OutOfOrderResult {
+    Pending,
+    /// Timestamps of the out-of-order frames are to follow from the frame interval.
+    UseInterval,
+    KeepTimestamps
+}
+
+/// Handles the prompt listing frames (`frames`: indices into `file_paths`) whose timestamps are earlier than those
+/// of the preceding frames.
+pub fn handle_out_of_order_prompt(
+    ui: &imgui::Ui,
+    config: &mut Configuration,
+    file_paths: &[PathBuf],
+    frames: &[usize],
+    interval: Duration
+) -> OutOfOrderResult {
+    let mut result = OutOfOrderResult::Pending;
+
+    modal::modal(ui, config, OUT_OF_ORDER_TITLE, KeyBindings::all(), |key_action, _| {
+        ui.text("Times taken from the names of these frames are earlier than those of the preceding frames:");
+        ui.indent();
+        for &idx in frames.iter().take(MAX_LISTED_FRAMES) {
+            let name = file_paths.get(idx).and_then(|path| path.file_name()).map(|name| name.to_string_lossy());
+            ui.text(format!("{}: {}", idx + 1, name.unwrap_or_default()));
+        }
+        if frames.len() > MAX_LISTED_FRAMES {
+            ui.text_disabled(format!("(and {} more)", frames.len() - MAX_LISTED_FRAMES));
+        }
+        ui.unindent();
+        ui.separator();
+
+        let label = format!("Use frame interval ({:.1} s) for these frames", interval.as_secs_f64());
+        if modal::default_button(ui, &label) || key_action == KeyAction::Accept {
+            result = OutOfOrderResult::UseInterval;
+            ui.close_current_popup();
+        }
+        gui::tooltip(ui, "Each of these frames is placed one frame interval after the preceding one.");
+        ui.same_line();
+
+        if ui.button("Keep times") || key_action == KeyAction::Cancel {
+            result = OutOfOrderResult::KeepTimestamps;
+            ui.close_current_popup();
+        }
+    });
+
+    result
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniform_times_round_to_nearest_frame() {
+        let times = FrameTimes::Uniform{ num_frames: 5, interval: Duration::from_secs(10) };
+        assert_eq!(Duration::from_secs(30), times.elapsed(3));
+        assert_eq!(Duration::from_secs(40), times.span());
+
+        assert_eq!(0, times.nearest_frame(Duration::ZERO));
+        assert_eq!(1, times.nearest_frame(Duration::from_millis(14_999)));
+        // halfway between frames
+        assert_eq!(2, times.nearest_frame(Duration::from_secs(15)));
+        assert_eq!(4, times.nearest_frame(Duration::from_secs(1000)));
+
+        for idx in 0..5 { assert_eq!(idx, times.nearest_frame(times.elapsed(idx))); }
+    }
+
+    #[test]
+    fn timestamps_may_be_non_uniform() {
+        let timestamps = [0.0, 10.0, 50.0, 60.0];
+        let times = FrameTimes::Timestamps(&timestamps);
+        assert_eq!(Duration::from_secs(50), times.elapsed(2));
+        assert_eq!(Duration::from_secs(60), times.span());
+
+        assert_eq!(1, times.nearest_frame(Duration::from_secs(29)));
+        assert_eq!(2, times.nearest_frame(Duration::from_secs(31)));
+        // halfway between frames
+        assert_eq!(2, times.nearest_frame(Duration::from_secs(30)));
+        assert_eq!(3, times.nearest_frame(Duration::from_secs(1000)));
+
+        for idx in 0..4 { assert_eq!(idx, times.nearest_frame(times.elapsed(idx))); }
+    }
+
+    #[test]
+    fn unordered_timestamps() {
+        let timestamps = [20.0, 0.0, 10.0];
+        let times = FrameTimes::Timestamps(&timestamps);
+        assert_eq!(Duration::from_secs(20), times.span());
+        assert_eq!(1, times.nearest_frame(Duration::from_secs(4)));
+        assert_eq!(0, times.nearest_frame(Duration::from_secs(15)));
+    }
+
+    #[test]
+    fn timestamps_are_taken_from_winjupos_names() {
+        let paths: Vec<PathBuf> = ["2022-03-10-2015_4-Jupiter.png", "2022-03-10-2017_4-Jupiter.png"].iter()
+            .map(PathBuf::from)
+            .collect();
+        let times = timestamps_from_names(&paths).unwrap();
+        assert!(times[0].abs() < 1.0e-3);
+        assert!((times[1] - 120.0).abs() < 1.0e-3);
+
+        assert!(timestamps_from_names(&[PathBuf::from("frame_0001.png"), PathBuf::from("frame_0002.png")]).is_none());
+        assert!(timestamps_from_names(&paths[..1]).is_none());
+    }
+
+    #[test]
+    fn midnight_crossing_is_corrected() {
+        // 23:59:57, 23:59:59, 00:00:01, 00:00:03 (seconds since midnight)
+        let mut times = [86397.0, 86399.0, 1.0, 3.0];
+        assert_eq!(vec![2], out_of_order(&times));
+
+        assert_eq!(1, correct_day_rollover(&mut times));
+        assert_eq!([86397.0, 86399.0, 86401.0, 86403.0], times);
+        assert!(out_of_order(&times).is_empty());
+
+        // the date in the names was not advanced after midnight
+        let paths: Vec<PathBuf> = [
+            "2022-03-10-2358_0-Jupiter.png", "2022-03-10-2359_0-Jupiter.png", "2022-03-10-0001_0-Jupiter.png"
+        ].iter().map(PathBuf::from).collect();
+        let times = timestamps_from_names(&paths).unwrap();
+        for (time, expected) in times.iter().zip([0.0, 60.0, 180.0]) { assert!((time - expected).abs() < 1.0e-3); }
+
+        let mut julian_dates = [2459649.499, 2459648.501];
+        assert_eq!(1, correct_day_rollover_jd(&mut julian_dates));
+        assert!((julian_dates[1] - 2459649.501).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn reordered_sequence_is_flagged_not_corrected() {
+        let mut times = [0.0, 20.0, 10.0, 30.0, 40.0];
+        assert_eq!(0, correct_day_rollover(&mut times));
+        assert_eq!([0.0, 20.0, 10.0, 30.0, 40.0], times);
+        assert_eq!(vec![2], out_of_order(&times));
+
+        fill_with_interval(&mut times, &[2], Duration::from_secs(5));
+        assert_eq!([0.0, 20.0, 25.0, 30.0, 40.0], times);
+        assert!(out_of_order(&times).is_empty());
+
+        // a jump back much shorter or longer than a day is not a rollover
+        let mut times = [50000.0, 10000.0, 200000.0, 20000.0];
+        assert_eq!(0, correct_day_rollover(&mut times));
+        assert_eq!(vec![1, 3], out_of_order(&times));
+
+        // the earliest frame may be an out-of-order one
+        let mut times = [100.0, 0.0];
+        fill_with_interval(&mut times, &out_of_order(&times), Duration::from_secs(10));
+        assert_eq!([0.0, 10.0], times);
+    }
+}
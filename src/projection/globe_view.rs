@@ -17,37 +17,92 @@
 // along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
 //
 
-use cgmath::{Basis3, Deg, InnerSpace, Matrix3, One, Rad, Rotation3, Vector3};
+use cgmath::{Angle, Basis3, Deg, InnerSpace, Matrix3, One, Quaternion, Rad, Rotation3, Vector3};
 use glium::{texture::Texture2d, uniform};
+use crate::config::Configuration;
 use crate::data::ToArray;
+use crate::gpu::render_check;
+use crate::gpu::render_throttle::RenderThrottle;
+use crate::gpu::resize_debounce::ResizeDebounce;
 use crate::gui;
 use crate::gui::draw_buffer::Sampling;
 use crate::gui::DrawBuffer;
+use crate::gui::crossfade::Crossfade;
 use crate::gui::long_task_dialog::LongTaskDialog;
+use crate::gui::motion::AnimationSource;
 use crate::projection;
 use crate::projection::{
-    data::LonLatGlBuffers,
+    coverage,
+    data::{self, LonLatGlBuffers},
+    imported_map::ImportedMap,
+    model_export::{self, ModelExportSettings},
+    projection_view::{self, ProjectionType},
     source_view::{SourceParameters},
+    SourceView,
     worker,
 };
 use crate::subscriber::Subscriber;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::error::Error;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 const MOUSE_WHEEL_ZOOM_FACTOR: f64 = 1.1;
 const PI_2: f32 = std::f32::consts::PI / 2.0;
 
+/// Duration of the animated rotation towards a navigation target.
+const NAVIGATION_DURATION: Duration = Duration::from_millis(500);
+
+pub const NAVIGATION_ANIMATION: AnimationSource =
+    AnimationSource{ name: "globe navigation", duration: navigation_duration };
+
+/// Returns the duration of navigation animations (zero if the globe is to be rotated instantly).
+fn navigation_duration(gui_state: &gui::GuiState) -> Duration { gui_state.animation_duration(NAVIGATION_DURATION) }
+
 #[derive(Copy, Clone, PartialEq)]
 pub enum DragRotation {
     NSEW,
     Free
 }
 
+/// Location to be centered in globe views.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GlobeTarget {
+    /// Longitude relative to the central meridian of frame 0 (see `projection_view::planet_longitude`).
+    pub planet_longitude: Deg<f32>,
+    pub latitude: Deg<f32>
+}
+
+/// Animated rotation towards a navigation target.
+struct Navigation {
+    start: Basis3<f64>,
+    target: Basis3<f64>,
+    /// N-S and E-W angles (see `nsew_orientation`) of `target`.
+    target_angles: (Rad<f64>, Rad<f64>),
+    started: Instant
+}
+
+/// Source view frames shown by a globe view.
+struct Frames {
+    image: Rc<Texture2d>,
+    image_idx: usize,
+    /// If set, the view shows this frame instead of following the source view's current frame.
+    pinned_frame: Option<usize>,
+    /// Most recently notified current frame of the source view.
+    live_image: (usize, Rc<Texture2d>),
+    src_params: SourceParameters
+}
+
+/// Contents textured onto the globe.
+enum Texturing {
+    Frames(Frames),
+    /// Does not depend on the source view.
+    Map(ImportedMap)
+}
+
 pub struct GlobeView {
     unique_id: u32,
-    source_image: Rc<Texture2d>,
-    source_image_idx: usize,
-    src_params: SourceParameters,
+    texturing: Texturing,
     draw_buf: DrawBuffer,
     gl_prog: Rc<glium::Program>,
     globe_mesh: LonLatGlBuffers,
@@ -57,6 +112,17 @@ pub struct GlobeView {
     angle_ew: Rad<f64>,
     zoom: f64,
     drag_rotation: DragRotation,
+    navigation: Option<Navigation>,
+    display_orientation: projection::DisplayOrientation,
+    /// Rendering has failed and is to be repeated.
+    render_pending: Cell<bool>,
+    display: glium::Display,
+    projection_prog: Rc<glium::Program>,
+    unit_quad: Rc<glium::VertexBuffer<data::Vertex2>>,
+    model_export: ModelExportSettings,
+    crossfade: Crossfade,
+    render_throttle: RenderThrottle,
+    resize_debounce: ResizeDebounce
 }
 
 impl GlobeView {
@@ -67,9 +133,43 @@ impl GlobeView {
         renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>,
         source_image: &Rc<Texture2d>,
         source_image_idx: usize,
-        src_params: SourceParameters
+        src_params: SourceParameters,
+        display_orientation: projection::DisplayOrientation
+    ) -> GlobeView {
+        let frames = Frames{
+            image: Rc::clone(source_image),
+            image_idx: source_image_idx,
+            pinned_frame: None,
+            live_image: (source_image_idx, Rc::clone(source_image)),
+            src_params
+        };
+
+        GlobeView::with_texturing(
+            unique_id, gl_objects, display, renderer, Texturing::Frames(frames), display_orientation
+        )
+    }
+
+    /// Creates a view showing `map` instead of the source view's frames.
+    pub fn with_map(
+        unique_id: u32,
+        gl_objects: &projection::data::OpenGlObjects,
+        display: &glium::Display,
+        renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>,
+        map: ImportedMap,
+        display_orientation: projection::DisplayOrientation
+    ) -> GlobeView {
+        GlobeView::with_texturing(unique_id, gl_objects, display, renderer, Texturing::Map(map), display_orientation)
+    }
+
+    fn with_texturing(
+        unique_id: u32,
+        gl_objects: &projection::data::OpenGlObjects,
+        display: &glium::Display,
+        renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>,
+        texturing: Texturing,
+        display_orientation: projection::DisplayOrientation
     ) -> GlobeView {
-        let draw_buf = DrawBuffer::new(
+        let mut draw_buf = DrawBuffer::new(
             Sampling::Single,
             &gl_objects.texture_copy_single,
             &gl_objects.texture_copy_multi,
@@ -77,12 +177,12 @@ impl GlobeView {
             display,
             renderer
         );
+        let owner = format!("Globe view {}", unique_id);
+        draw_buf.register(&owner, "view");
 
         let globe_view = GlobeView{
             unique_id,
-            source_image: Rc::clone(source_image),
-            source_image_idx,
-            src_params,
+            texturing,
             gl_prog: Rc::clone(&gl_objects.globe_texturing),
             globe_mesh: gl_objects.globe_mesh.clone(),
             draw_buf,
@@ -90,8 +190,18 @@ impl GlobeView {
             zoom: 0.75,
             orientation: Basis3::one(),
             drag_rotation: DragRotation::NSEW,
+            navigation: None,
             angle_ew: Rad(0.0),
-            angle_ns: Rad(0.0)
+            angle_ns: Rad(0.0),
+            display_orientation,
+            render_pending: Cell::new(false),
+            display: display.clone(),
+            projection_prog: Rc::clone(&gl_objects.projection),
+            unit_quad: Rc::clone(&gl_objects.unit_quad),
+            model_export: ModelExportSettings::default(),
+            crossfade: Crossfade::new(&owner, display, &gl_objects.crossfade, &gl_objects.unit_quad),
+            render_throttle: RenderThrottle::new(&owner),
+            resize_debounce: Default::default()
         };
 
         globe_view.render();
@@ -100,24 +210,54 @@ impl GlobeView {
     }
 
     fn render(&self) {
+        if !self.render_throttle.begin_rendering(Instant::now()) {
+            self.render_pending.set(true);
+            return;
+        }
+        self.render_pending.set(!render_check::render_checked("globe view", || self.try_render()));
+    }
+
+    /// Renders again if the previous rendering failed or was skipped.
+    fn render_if_pending(&self) {
+        if self.render_pending.get() { self.render(); }
+    }
+
+    fn try_render(&self) -> Result<(), glium::DrawError> {
         let mut target = self.draw_buf.frame_buf();
-        render_globe(
-            true,
-            self.source_image_idx,
-            &self.source_image,
-            &mut target,
-            &self.gl_prog,
-            &self.src_params,
-            self.orientation,
-            &self.globe_mesh,
-            self.zoom,
-            self.wh_ratio
-        );
-        self.draw_buf.update_storage_buf();
+        match &self.texturing {
+            Texturing::Frames(frames) => render_globe(
+                true,
+                frames.image_idx,
+                &frames.image,
+                &mut target,
+                &self.gl_prog,
+                &frames.src_params,
+                self.orientation,
+                &self.globe_mesh,
+                self.zoom,
+                self.wh_ratio
+            )?,
+
+            Texturing::Map(map) => render_map_globe(
+                true,
+                map,
+                &mut target,
+                &self.gl_prog,
+                self.orientation,
+                &self.globe_mesh,
+                self.zoom,
+                self.wh_ratio
+            )?
+        }
+        self.draw_buf.update_storage_buf()?;
+        self.crossfade.apply(&self.draw_buf)
     }
 
     pub fn update_size(&mut self, width: u32, height: u32) {
-        if height == 0 { return; }
+        if width == 0 || height == 0 { return; }
+
+        let current = [self.draw_buf.width(), self.draw_buf.height()];
+        if !self.resize_debounce.resize_now(current, [width, height], Instant::now()) { return; }
 
         if self.draw_buf.update_size(width, height) {
             self.wh_ratio = width as f32 / height as f32;
@@ -127,6 +267,20 @@ impl GlobeView {
 
     pub fn id(&self) -> u32 { self.unique_id }
 
+    fn frames(&self) -> Option<&Frames> {
+        match &self.texturing {
+            Texturing::Frames(frames) => Some(frames),
+            Texturing::Map(_) => None
+        }
+    }
+
+    fn frames_mut(&mut self) -> Option<&mut Frames> {
+        match &mut self.texturing {
+            Texturing::Frames(frames) => Some(frames),
+            Texturing::Map(_) => None
+        }
+    }
+
     fn display_buf_id(&self) -> imgui::TextureId { self.draw_buf.id() }
 
     pub fn zoom_by(&mut self, relative_zoom: f64) {
@@ -138,6 +292,12 @@ impl GlobeView {
     /// Elements of `start` and `end` denote normalized mouse position within the view,
     /// with values from [-1, 1] (i.e., bottom-left is [-1, -1], and top-right is [1, 1]).
     pub fn rotate_by_dragging(&mut self, start: [f32; 2], end: [f32; 2]) {
+        // dragging takes over from an unfinished navigation
+        if self.navigation.take().is_some() {
+            (self.angle_ns, self.angle_ew) = nsew_angles(self.orientation);
+            self.orientation = nsew_orientation(self.angle_ns, self.angle_ew);
+        }
+
         match self.drag_rotation {
             // simulates "space ball" rotation
             DragRotation::Free => {
@@ -162,39 +322,198 @@ impl GlobeView {
 
                 self.angle_ew += Rad(1.0 / self.zoom * (end[0] - start[0]) as f64);
 
-                let rotation_ns = Basis3::from_angle_y(self.angle_ns);
-                let rotation_ew = Basis3::from_angle_z(self.angle_ew);
-
-                self.orientation = rotation_ns * rotation_ew;
+                self.orientation = nsew_orientation(self.angle_ns, self.angle_ew);
             }
         }
 
         self.render();
     }
 
-    pub fn set_source_image(&mut self, source_image: &Rc<Texture2d>) {
-        self.source_image = Rc::clone(&source_image);
+    /// Starts rotating the globe (north-up) so that `target` is centered; replaces an unfinished navigation. Ignored
+    /// when showing an imported map (its longitudes are unrelated to the source frames).
+    pub fn navigate_to(&mut self, target: &GlobeTarget) {
+        let frames = match self.frames() { Some(frames) => frames, None => return };
+        let longitude = displayed_longitude(&frames.src_params, frames.image_idx, target.planet_longitude);
+        let target_angles = facing_angles(Deg(longitude.0 as f64), Deg(target.latitude.0 as f64));
+        self.navigation = Some(Navigation{
+            start: self.orientation,
+            target: nsew_orientation(target_angles.0, target_angles.1),
+            target_angles,
+            started: Instant::now()
+        });
+    }
+
+    /// Advances the navigation animation (if any) lasting `duration`; to be called in every frame.
+    fn step_navigation(&mut self, now: Instant, duration: Duration) {
+        let navigation = match &self.navigation {
+            Some(navigation) => navigation,
+            None => return
+        };
+
+        let t = if duration.is_zero() {
+            1.0
+        } else {
+            now.saturating_duration_since(navigation.started).as_secs_f64() / duration.as_secs_f64()
+        };
+        if t >= 1.0 {
+            self.orientation = navigation.target;
+            (self.angle_ns, self.angle_ew) = navigation.target_angles;
+            self.navigation = None;
+        } else {
+            self.orientation = interpolated_orientation(navigation.start, navigation.target, t);
+        }
+        self.render();
+    }
+
+    pub fn pinned_frame(&self) -> Option<usize> { self.frames().and_then(|frames| frames.pinned_frame) }
+
+    /// Makes the view show frame `idx` (whose texture is `image`) until unpinned.
+    pub fn pin_frame(&mut self, idx: usize, image: &Rc<Texture2d>) {
+        let frames = match self.frames_mut() { Some(frames) => frames, None => return };
+        frames.pinned_frame = Some(idx);
+        if frames.image_idx != idx || !Rc::ptr_eq(&frames.image, image) {
+            self.show_frame(idx, image);
+        }
+    }
+
+    /// Makes the view follow the source view's current frame again.
+    pub fn unpin_frame(&mut self) {
+        let (idx, image) = match self.frames_mut() {
+            Some(frames) => {
+                frames.pinned_frame = None;
+                frames.live_image.clone()
+            },
+            None => return
+        };
+        self.show_frame(idx, &image);
+    }
+
+    /// Shows frame `idx` (whose texture is `image`); crossfades if it differs from the displayed one.
+    fn show_frame(&mut self, idx: usize, image: &Rc<Texture2d>) {
+        let frames = match &mut self.texturing { Texturing::Frames(frames) => frames, Texturing::Map(_) => return };
+        if idx != frames.image_idx { self.crossfade.on_frame_changed(&self.draw_buf); }
+        frames.image_idx = idx;
+        frames.image = Rc::clone(image);
         self.render();
     }
+
+    /// Returns the equirectangular projection (north-up, `size`×`size` pixels, 180° of longitude) of the displayed
+    /// frame.
+    fn render_hemisphere(&self, frames: &Frames, size: u32) -> Result<ga_image::Image, Box<dyn Error>> {
+        let texture = Texture2d::empty_with_format(
+            &self.display,
+            glium::texture::UncompressedFloatFormat::U8U8U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            size,
+            size
+        )?;
+
+        projection_view::render_projection(
+            false,
+            frames.image_idx,
+            &frames.image,
+            &mut texture.as_surface(),
+            &self.unit_quad,
+            &self.projection_prog,
+            &frames.src_params,
+            0.0,
+            ProjectionType::Equirectangular,
+            0.0,
+            false
+        )?;
+
+        Ok(crate::image_utils::image_from_texture(&texture))
+    }
+
+    /// Exports the displayed frame or map as a 3D model; returns a description of the texture contents.
+    fn export_model(&self, obj_path: &std::path::Path) -> Result<String, Box<dyn Error>> {
+        let settings = &self.model_export;
+        let (texture, flattening, note) = match &self.texturing {
+            Texturing::Frames(frames) => {
+                let hemisphere = self.render_hemisphere(frames, settings.texture_width / 2)?;
+                (
+                    model_export::full_texture(&hemisphere, settings.fill),
+                    frames.src_params.flattening,
+                    model_export::data_note(frames.image_idx, settings.fill)
+                )
+            },
+
+            Texturing::Map(map) => (map.equirectangular_texture(settings.texture_width), 0.0, map.data_note())
+        };
+        let mesh = model_export::globe_model(model_export::MESH_STEP, flattening);
+        model_export::export_model(obj_path, &mesh, &texture)?;
+
+        Ok(note)
+    }
 }
 
 impl Subscriber<(usize, Rc<Texture2d>)> for GlobeView {
     fn notify(&mut self, value: &(usize, Rc<Texture2d>)) {
-        self.source_image_idx = value.0;
-        self.set_source_image(&value.1);
+        if let Some(frames) = self.frames_mut() {
+            frames.live_image = (value.0, Rc::clone(&value.1));
+            if frames.pinned_frame.is_none() {
+                self.show_frame(value.0, &value.1);
+            }
+        }
     }
 }
 
 impl Subscriber<SourceParameters> for GlobeView {
     fn notify(&mut self, value: &SourceParameters) {
-        self.src_params = value.clone();
-        self.render();
+        if let Some(frames) = self.frames_mut() {
+            frames.src_params = value.clone();
+            self.render();
+        }
     }
 }
 
+/// Returns the globe orientation after rotating by `angle_ew` around the polar axis, then by `angle_ns` towards
+/// the observer (as used by `DragRotation::NSEW`; the north pole stays up).
+fn nsew_orientation(angle_ns: Rad<f64>, angle_ew: Rad<f64>) -> Basis3<f64> {
+    Basis3::from_angle_y(angle_ns) * Basis3::from_angle_z(angle_ew)
+}
+
+/// Returns N-S and E-W angles (see `nsew_orientation`) which center the given location (longitude relative to
+/// the displayed frame's central meridian).
+fn facing_angles(longitude: Deg<f64>, latitude: Deg<f64>) -> (Rad<f64>, Rad<f64>) {
+    (Rad::from(latitude), -Rad::from(longitude))
+}
+
+/// Returns N-S and E-W angles (see `nsew_orientation`) centering the location which faces the observer
+/// in `orientation`; inverse of `nsew_orientation` (up to rolling around the line of sight).
+fn nsew_angles(orientation: Basis3<f64>) -> (Rad<f64>, Rad<f64>) {
+    // the globe point facing the observer (who looks from +X)
+    let m = Matrix3::from(orientation);
+    let facing = Vector3{ x: m.x.x, y: m.y.x, z: m.z.x };
+    let latitude = Rad::asin(facing.z.max(-1.0).min(1.0));
+    let longitude = Rad::atan2(facing.y, facing.x);
+
+    facing_angles(Deg::from(longitude), Deg::from(latitude))
+}
+
+/// Returns the orientation at fraction `t` (within [0; 1]) of the animated rotation from `start` to `target`
+/// (along the shortest path, easing in and out).
+fn interpolated_orientation(start: Basis3<f64>, target: Basis3<f64>, t: f64) -> Basis3<f64> {
+    let t = t.max(0.0).min(1.0);
+    let eased = t * t * (3.0 - 2.0 * t);
+
+    let q_start = Quaternion::from(start);
+    let mut q_target = Quaternion::from(target);
+    // `q` and `-q` denote the same rotation; choose the closer one
+    if q_start.dot(q_target) < 0.0 { q_target = -q_target; }
+
+    Basis3::from(q_start.slerp(q_target, eased))
+}
+
+/// Converts a longitude relative to the central meridian of frame 0 to one relative to the central meridian of frame
+/// `source_image_idx` (inverse of `projection_view::planet_longitude`).
+fn displayed_longitude(src_params: &SourceParameters, source_image_idx: usize, planet_longitude: Deg<f32>) -> Deg<f32> {
+    (planet_longitude + coverage::rotation_per_frame(src_params) * source_image_idx as f32).normalize_signed()
+}
+
 pub fn render_globe(
     vertical_flip: bool,
-    _source_image_idx: usize,
+    source_image_idx: usize,
     source_image: &glium::Texture2d,
     target: &mut impl glium::Surface,
     gl_prog: &glium::Program,
@@ -203,10 +522,10 @@ pub fn render_globe(
     globe_mesh: &LonLatGlBuffers,
     zoom : f64,
     wh_ratio: f32
-) {
+) -> Result<(), glium::DrawError> {
     let flattening_transform = Matrix3::<f32>::from_nonuniform_scale(1.0, 1.0 - src_params.flattening);
     let inclination_transform = cgmath::Basis3::from_angle_x(src_params.inclination);
-    let roll_transform = cgmath::Basis3::from_angle_z(-src_params.roll);
+    let roll_transform = cgmath::Basis3::from_angle_z(-src_params.frame_roll(source_image_idx));
     let globe_transform = Matrix3::from(roll_transform) * Matrix3::from(inclination_transform) * flattening_transform;
 
     let uniforms = uniform! {
@@ -218,15 +537,54 @@ pub fn render_globe(
         flattening: src_params.flattening,
         zoom: zoom as f32,
         wh_ratio: wh_ratio,
-        texture_vertical_flip: vertical_flip
+        texture_vertical_flip: vertical_flip,
+        image_mirror: src_params.image_mirror(),
+        gain: src_params.frame_gain(source_image_idx),
+        map_texturing: false
+    };
+
+    draw_globe(target, gl_prog, globe_mesh, &uniforms)
+}
+
+/// Like `render_globe`, but textures the globe (a sphere) with an imported map.
+pub fn render_map_globe(
+    vertical_flip: bool,
+    map: &ImportedMap,
+    target: &mut impl glium::Surface,
+    gl_prog: &glium::Program,
+    globe_orientation: Basis3<f64>,
+    globe_mesh: &LonLatGlBuffers,
+    zoom : f64,
+    wh_ratio: f32
+) -> Result<(), glium::DrawError> {
+    let uniforms = uniform! {
+        source_image: map.texture.sampled().wrap_function(glium::uniforms::SamplerWrapFunction::Repeat),
+        globe_orientation: Matrix3::from(globe_orientation).cast::<f32>().unwrap().to_array(),
+        flattening: 0.0f32,
+        zoom: zoom as f32,
+        wh_ratio: wh_ratio,
+        texture_vertical_flip: vertical_flip,
+        map_texturing: true,
+        map_equirectangular: map.geometry.projection_type == ProjectionType::Equirectangular,
+        map_left_longitude: map.geometry.left_longitude.0,
+        map_longitude_span: map.geometry.longitude_span.0
     };
 
+    draw_globe(target, gl_prog, globe_mesh, &uniforms)
+}
+
+fn draw_globe(
+    target: &mut impl glium::Surface,
+    gl_prog: &glium::Program,
+    globe_mesh: &LonLatGlBuffers,
+    uniforms: &impl glium::uniforms::Uniforms
+) -> Result<(), glium::DrawError> {
     target.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
     target.draw(
         &*globe_mesh.vertices,
         &*globe_mesh.indices,
         gl_prog,
-        &uniforms,
+        uniforms,
         &glium::DrawParameters{
             depth: glium::Depth{
                 test: glium::DepthTest::IfLess,
@@ -235,23 +593,66 @@ pub fn render_globe(
             },
             ..Default::default()
         }
-    ).unwrap();
+    )
 }
 
 /// Returns `false` if view should be closed.
 pub fn handle_globe_view(
     ui: &imgui::Ui,
     gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
     view: &mut GlobeView,
+    source_view: Option<&SourceView>,
     _long_task_dialog: &RefCell<Option<LongTaskDialog>>,
     _task_sender: &crossbeam::channel::Sender<worker::MainToWorkerMsg>
 ) -> bool {
     let mut opened = true;
 
-    imgui::Window::new(ui, &format!("Globe###globe-view-{}", view.id()))
+    if let Some(source_view) = source_view { update_pinned_frame(view, source_view); }
+    view.render_throttle.set_app_focused(gui_state.app_focused());
+    view.step_navigation(Instant::now(), navigation_duration(gui_state));
+    view.crossfade.set_duration(gui::crossfade::duration(gui_state, projection::frame_crossfade_duration(config)));
+    if view.crossfade.step() { view.render(); }
+    view.render_if_pending();
+
+    let mut visible = false;
+    let contents = match &view.texturing {
+        Texturing::Frames(frames) => format!(
+            "frame {}{}", frames.image_idx + 1, if frames.pinned_frame.is_some() { " (pinned)" } else { "" }
+        ),
+        Texturing::Map(map) => map.file_name.clone()
+    };
+    imgui::Window::new(ui, &format!("Globe - {}###globe-view-{}", contents, view.id()))
         .size([640.0, 640.0], imgui::Condition::FirstUseEver)
         .opened(&mut opened)
         .build(|| {
+            visible = true;
+            if view.render_throttle.set_visible(true) { view.render_if_pending(); }
+
+            let live_frame = view.frames().map(|frames| frames.live_image.0);
+            if let (Some(source_view), Some(live_frame)) = (source_view, live_frame) {
+                if let Some(pinned) = projection::handle_frame_pin_controls(
+                    ui, view.id(), view.pinned_frame(), live_frame, source_view.num_images()
+                ) {
+                    match pinned {
+                        Some(idx) => view.pin_frame(idx, source_view.image(idx)),
+                        None => view.unpin_frame()
+                    }
+                }
+                ui.same_line();
+            }
+
+            projection::handle_display_orientation_controls(ui, view.id(), config, &mut view.display_orientation);
+
+            ui.same_line();
+            if ui.button("Export 3D model...") { ui.open_popup(model_export::TITLE); }
+            gui::tooltip(ui, "Export the globe as a Wavefront OBJ model with an equirectangular texture.");
+            if model_export::handle_model_export_dialog(ui, config, &mut view.model_export) {
+                handle_model_export(ui, gui_state, view);
+            }
+            // the message box has been opened within this window
+            gui::handle_message_box(ui, gui_state, config);
+
             let hidpi_f = gui_state.hidpi_factor() as f32;
             let adjusted = gui::adjust_pos_for_exact_hidpi_scaling(ui, 0.0, hidpi_f);
 
@@ -262,7 +663,8 @@ pub fn handle_globe_view(
 
             let img_pos_in_app_window = ui.cursor_screen_pos();
             let _image_start_pos = ui.cursor_pos();
-            imgui::Image::new(view.display_buf_id(), adjusted.logical_size).build(ui);
+            let (uv0, uv1) = view.display_orientation.uv_bounds();
+            imgui::Image::new(view.display_buf_id(), adjusted.logical_size).uv0(uv0).uv1(uv1).build(ui);
 
             let mouse_pos_in_app_window = ui.io().mouse_pos;
             if ui.is_item_clicked_with_button(imgui::MouseButton::Left) {
@@ -281,15 +683,19 @@ pub fn handle_globe_view(
                 if ui.is_mouse_dragging(imgui::MouseButton::Left) {
                     let delta = ui.mouse_drag_delta_with_button(imgui::MouseButton::Left);
                     if delta[0] != 0.0 || delta[1] != 0.0 {
-                        let drag_start: [f32; 2] = [
-                            -1.0 + 2.0 * (gui_state.mouse_drag_origin[0] / adjusted.logical_size[0]),
-                            -(-1.0 + 2.0 * (gui_state.mouse_drag_origin[1] / adjusted.logical_size[1]))
-                        ];
-
-                        let drag_end = [
-                            drag_start[0] + 2.0 * delta[0] / adjusted.logical_size[0],
-                            drag_start[1] - 2.0 * delta[1] / adjusted.logical_size[1]
-                        ];
+                        // dragging moves the globe as displayed
+                        let orientation = &view.display_orientation;
+                        let origin = orientation.to_data_position([
+                            gui_state.mouse_drag_origin[0] / adjusted.logical_size[0],
+                            gui_state.mouse_drag_origin[1] / adjusted.logical_size[1]
+                        ]);
+                        let end = orientation.to_data_position([
+                            (gui_state.mouse_drag_origin[0] + delta[0]) / adjusted.logical_size[0],
+                            (gui_state.mouse_drag_origin[1] + delta[1]) / adjusted.logical_size[1]
+                        ]);
+
+                        let drag_start: [f32; 2] = [-1.0 + 2.0 * origin[0], -(-1.0 + 2.0 * origin[1])];
+                        let drag_end = [-1.0 + 2.0 * end[0], -(-1.0 + 2.0 * end[1])];
 
                         view.rotate_by_dragging(drag_start, drag_end);
                     }
@@ -302,5 +708,119 @@ pub fn handle_globe_view(
             }
         }
     );
+    // not rendered while collapsed or in an inactive docked tab
+    if !visible { view.render_throttle.set_visible(false); }
+
     opened
 }
+
+fn handle_model_export(ui: &imgui::Ui, gui_state: &mut gui::GuiState, view: &GlobeView) {
+    let path = gui::file_dialog::checked(
+        ui,
+        gui_state,
+        native_dialog::FileDialog::new().add_filter("Wavefront OBJ", &["obj"]).show_save_single_file()
+    ).flatten();
+    let path = match path {
+        Some(path) => path.with_extension("obj"),
+        None => return
+    };
+
+    let (title, message) = match view.export_model(&path) {
+        Ok(note) => ("Export finished", format!("Saved {}.\n\n{}", path.display(), note)),
+        Err(e) => ("Error", format!("Failed to export the 3D model: {}.", e))
+    };
+    gui_state.message_box = Some(gui::MessageBox{ title: title.to_string(), message });
+    ui.open_popup(title);
+}
+
+/// Keeps a pinned frame valid after the source images have been replaced.
+fn update_pinned_frame(view: &mut GlobeView, source_view: &SourceView) {
+    if let Some(idx) = view.pinned_frame() {
+        let idx = idx.min(source_view.num_images() - 1);
+        view.pin_frame(idx, source_view.image(idx));
+    }
+}
+
+mod tests {
+    use super::*;
+    use cgmath::Rotation;
+
+    fn globe_point(longitude: Deg<f64>, latitude: Deg<f64>) -> Vector3<f64> {
+        // as in `globe.vert`
+        Vector3{
+            x: longitude.cos() * latitude.cos(),
+            y: longitude.sin() * latitude.cos(),
+            z: latitude.sin()
+        }
+    }
+
+    fn assert_close(expected: Vector3<f64>, actual: Vector3<f64>) {
+        assert!((expected - actual).magnitude() < 1.0e-9, "expected {:?}, got {:?}", expected, actual);
+    }
+
+    #[test]
+    fn target_faces_observer_with_north_up() {
+        for (lon, lat) in [(0.0, 0.0), (35.0, 0.0), (-120.0, 20.0), (170.0, -65.0)] {
+            let (angle_ns, angle_ew) = facing_angles(Deg(lon), Deg(lat));
+            let orientation = nsew_orientation(angle_ns, angle_ew);
+            assert_close(Vector3::unit_x(), orientation.rotate_vector(globe_point(Deg(lon), Deg(lat))));
+
+            // the north pole is above the target (no roll around the line of sight)
+            let north = orientation.rotate_vector(Vector3::unit_z());
+            assert!(north.y.abs() < 1.0e-9 && north.z >= 0.0);
+        }
+    }
+
+    #[test]
+    fn nsew_angles_are_recovered_from_orientation() {
+        for (ns, ew) in [(0.0, 0.0), (0.3, -1.2), (-1.0, 2.5), (1.4, -3.0)] {
+            let (angle_ns, angle_ew) = nsew_angles(nsew_orientation(Rad(ns), Rad(ew)));
+            assert!((angle_ns.0 - ns).abs() < 1.0e-9);
+            assert!((angle_ew.normalize_signed().0 - ew).abs() < 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn interpolation_reaches_target_along_shortest_path() {
+        let x_at = |orientation: Basis3<f64>| orientation.rotate_vector(Vector3::unit_x());
+        let start = Basis3::from_angle_z(Deg(10.0));
+        let target = Basis3::from_angle_z(Deg(70.0));
+
+        assert_close(x_at(start), x_at(interpolated_orientation(start, target, 0.0)));
+        assert_close(x_at(target), x_at(interpolated_orientation(start, target, 1.0)));
+        // halfway in time is halfway in angle (the easing is symmetric)
+        assert_close(x_at(Basis3::from_angle_z(Deg(40.0))), x_at(interpolated_orientation(start, target, 0.5)));
+
+        // from 170° to -170° the rotation goes through 180°, not through 0°
+        let middle = interpolated_orientation(Basis3::from_angle_z(Deg(170.0)), Basis3::from_angle_z(Deg(-170.0)), 0.5);
+        assert_close(-Vector3::unit_x(), x_at(middle));
+    }
+
+    #[test]
+    fn retargeting_starts_from_current_orientation() {
+        let first = Basis3::from_angle_z(Deg(90.0));
+        let current = interpolated_orientation(Basis3::one(), first, 0.3);
+        let second = Basis3::from_angle_y(Deg(45.0));
+
+        let retargeted = interpolated_orientation(current, second, 0.0);
+        assert_close(current.rotate_vector(Vector3::unit_x()), retargeted.rotate_vector(Vector3::unit_x()));
+        let retargeted = interpolated_orientation(current, second, 1.0);
+        assert_close(second.rotate_vector(Vector3::unit_y()), retargeted.rotate_vector(Vector3::unit_y()));
+    }
+
+    #[test]
+    fn target_longitude_follows_displayed_frame() {
+        let src_params = SourceParameters{
+            num_images: 10,
+            sidereal_rotation_period: Duration::from_secs(36000),
+            ..SourceParameters::test_default()
+        };
+        // 0.6° per frame
+        assert!((displayed_longitude(&src_params, 0, Deg(30.0)).0 - 30.0).abs() < 1.0e-4);
+        assert!((displayed_longitude(&src_params, 5, Deg(30.0)).0 - 33.0).abs() < 1.0e-4);
+        assert!((displayed_longitude(&src_params, 5, Deg(179.0)).0 - -178.0).abs() < 1.0e-4);
+
+        let planet_lon = projection_view::planet_longitude(&src_params, 7, Deg(-20.0));
+        assert!((displayed_longitude(&src_params, 7, planet_lon).0 - -20.0).abs() < 1.0e-4);
+    }
+}
@@ -0,0 +1,275 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Cylindrical maps loaded from files (e.g., WinJUPOS or earlier Vislumino exports) and textured onto the globe
+//! independently of the source view.
+
+use cgmath::{Angle, Deg, Rad};
+use crate::config::Configuration;
+use crate::gui;
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+use crate::projection::projection_view::ProjectionType;
+use ga_image::{Image, PixelFormat};
+use glium::texture::Texture2d;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+pub const TITLE: &str = "Open map on globe";
+
+const MAX_STANDARD_PARALLEL_DEGREES: f32 = 60.0;
+
+/// Placement of a cylindrical map on the globe. The map spans all latitudes (north at the top) and `longitude_span`
+/// of longitude, increasing rightwards from `left_longitude` (relative to the meridian initially facing the observer).
+#[derive(Copy, Clone, PartialEq)]
+pub struct MapGeometry {
+    pub projection_type: ProjectionType,
+    pub left_longitude: Deg<f32>,
+    pub longitude_span: Deg<f32>
+}
+
+impl MapGeometry {
+    /// Returns texture coordinates (within [0; 1], from top-left) of the given location; `None` if the map does not
+    /// cover it. Must match the map lookup in `globe_texturing.frag`.
+    pub fn uv(&self, longitude: Deg<f32>, latitude: Deg<f32>) -> Option<[f32; 2]> {
+        let u = (longitude - self.left_longitude).0.rem_euclid(360.0) / self.longitude_span.0;
+        if u > 1.0 { return None; }
+
+        let v = match self.projection_type {
+            ProjectionType::Equirectangular => (90.0 - latitude.0) / 180.0,
+            ProjectionType::LambertCylindricalEqualArea => (1.0 - latitude.sin()) / 2.0
+        };
+
+        Some([u, v])
+    }
+}
+
+/// Returns the longitude span of a map of the given size spanning all latitudes; `standard_parallel` is used only
+/// by the Lambert projection (see `projection_view::projection_height`).
+pub fn longitude_span(
+    projection_type: ProjectionType,
+    width: u32,
+    height: u32,
+    standard_parallel: Deg<f32>
+) -> Deg<f32> {
+    let aspect_ratio = width as f32 / height as f32;
+    match projection_type {
+        ProjectionType::Equirectangular => Deg(180.0 * aspect_ratio),
+
+        ProjectionType::LambertCylindricalEqualArea => {
+            let cos_sp = standard_parallel.cos();
+            Deg::from(Rad(2.0 * aspect_ratio / (cos_sp * cos_sp)))
+        }
+    }
+}
+
+/// Map textured onto a globe view.
+pub struct ImportedMap {
+    pub texture: Rc<Texture2d>,
+    pub image: Image,
+    pub geometry: MapGeometry,
+    pub file_name: String
+}
+
+impl ImportedMap {
+    /// Returns the map resampled (nearest neighbor) to a north-up equirectangular map `width` pixels wide covering
+    /// 360° of longitude starting at -180°, as used by `model_export`; longitudes not covered by the map are black.
+    pub fn equirectangular_texture(&self, width: u32) -> Image {
+        let height = width / 2;
+        let mut texture = Image::new(width, height, None, PixelFormat::RGB8, None, true);
+        let (src_width, src_height) = (self.image.width() as usize, self.image.height() as usize);
+        for y in 0..height {
+            let latitude = Deg(90.0 - (y as f32 + 0.5) * 180.0 / height as f32);
+            let dest_line = texture.line_mut::<u8>(y);
+            for x in 0..width as usize {
+                let longitude = Deg(-180.0 + (x as f32 + 0.5) * 360.0 / width as f32);
+                if let Some([u, v]) = self.geometry.uv(longitude, latitude) {
+                    let src_x = ((u * src_width as f32) as usize).min(src_width - 1);
+                    let src_y = ((v * src_height as f32) as usize).min(src_height - 1);
+                    let src = &self.image.line::<u8>(src_y as u32)[3 * src_x..3 * src_x + 3];
+                    dest_line[3 * x..3 * x + 3].copy_from_slice(src);
+                }
+            }
+        }
+
+        texture
+    }
+
+    /// Describes which part of an exported model's texture contains real data.
+    pub fn data_note(&self) -> String {
+        format!(
+            "The texture contains the map {}; longitudes not covered by it are black.",
+            self.file_name
+        )
+    }
+}
+
+/// Settings of a map selected to be opened on the globe.
+pub struct MapImportDialog {
+    path: PathBuf,
+    image: Image,
+    projection_type: ProjectionType,
+    left_longitude: f32,
+    standard_parallel: f32
+}
+
+impl MapImportDialog {
+    pub fn new(path: &Path, image: Image) -> MapImportDialog {
+        MapImportDialog{
+            path: path.to_path_buf(),
+            image,
+            projection_type: ProjectionType::Equirectangular,
+            left_longitude: -180.0,
+            standard_parallel: 0.0
+        }
+    }
+
+    fn geometry(&self) -> MapGeometry {
+        MapGeometry{
+            projection_type: self.projection_type,
+            left_longitude: Deg(self.left_longitude),
+            longitude_span: longitude_span(
+                self.projection_type, self.image.width(), self.image.height(), Deg(self.standard_parallel)
+            )
+        }
+    }
+
+    /// Returns the map to be shown, with its texture created on `display`.
+    pub fn into_map(self, display: &glium::Display) -> Result<ImportedMap, Box<dyn std::error::Error>> {
+        let geometry = self.geometry();
+        Ok(ImportedMap{
+            texture: Rc::new(crate::data::create_texture_from_image(&self.image, display)?),
+            image: self.image,
+            geometry,
+            file_name: self.path.file_name().map_or(String::new(), |name| name.to_string_lossy().to_string())
+        })
+    }
+}
+
+pub enum MapImportResult {
+    Pending,
+    Accepted,
+    Cancelled
+}
+
+/// The dialog has to be opened beforehand with `ui.open_popup(TITLE)`.
+pub fn handle_map_import_dialog(
+    ui: &imgui::Ui,
+    config: &mut Configuration,
+    dialog: &mut MapImportDialog
+) -> MapImportResult {
+    let mut result = MapImportResult::Pending;
+
+    modal::modal(ui, config, TITLE, KeyBindings::all(), |key_action, _| {
+        ui.text(format!("{} ({}x{})", dialog.path.display(), dialog.image.width(), dialog.image.height()));
+
+        gui::add_text_before(ui, "projection");
+        if ui.radio_button_bool("equirectangular", dialog.projection_type == ProjectionType::Equirectangular) {
+            dialog.projection_type = ProjectionType::Equirectangular;
+        }
+        ui.same_line();
+        let lambert = ProjectionType::LambertCylindricalEqualArea;
+        if ui.radio_button_bool("Lambert cylindrical equal-area", dialog.projection_type == lambert) {
+            dialog.projection_type = lambert;
+        }
+
+        if dialog.projection_type == ProjectionType::LambertCylindricalEqualArea {
+            gui::add_text_before(ui, "standard parallel");
+            imgui::Slider::new("##map-standard-parallel", 0.0, MAX_STANDARD_PARALLEL_DEGREES)
+                .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                .display_format("%0.1f°")
+                .build(ui, &mut dialog.standard_parallel);
+        }
+
+        gui::add_text_before(ui, "longitude of the left edge");
+        ui.input_float("°##map-left-longitude", &mut dialog.left_longitude).build();
+        gui::tooltip(ui, "Longitude 0° initially faces the observer; e.g., -180° centers a map spanning 360°.");
+
+        let span = dialog.geometry().longitude_span;
+        ui.text(format!("The map spans {:.1}° of longitude.", span.0));
+        if span.0 > 360.5 {
+            ui.text_colored([1.0, 0.8, 0.0, 1.0], "Wider than 360°; check the projection type.");
+        }
+
+        ui.separator();
+        if modal::default_button(ui, "Open") || key_action == KeyAction::Accept {
+            result = MapImportResult::Accepted;
+            ui.close_current_popup();
+        }
+        ui.same_line();
+        if ui.button("Cancel") || key_action == KeyAction::Cancel {
+            result = MapImportResult::Cancelled;
+            ui.close_current_popup();
+        }
+    });
+
+    result
+}
+
+mod tests {
+    use super::*;
+
+    fn assert_uv(expected: [f32; 2], actual: Option<[f32; 2]>) {
+        let actual = actual.unwrap();
+        assert!((expected[0] - actual[0]).abs() < 1.0e-5 && (expected[1] - actual[1]).abs() < 1.0e-5,
+            "expected {:?}, got {:?}", expected, actual);
+    }
+
+    #[test]
+    fn equirectangular_map_uv() {
+        let geometry = MapGeometry{
+            projection_type: ProjectionType::Equirectangular,
+            left_longitude: Deg(-180.0),
+            longitude_span: Deg(360.0)
+        };
+        assert_uv([0.5, 0.5], geometry.uv(Deg(0.0), Deg(0.0)));
+        assert_uv([0.75, 0.0], geometry.uv(Deg(90.0), Deg(90.0)));
+        assert_uv([0.25, 0.75], geometry.uv(Deg(-90.0), Deg(-45.0)));
+        // longitudes wrap around
+        assert_uv([0.75, 0.5], geometry.uv(Deg(-270.0), Deg(0.0)));
+    }
+
+    #[test]
+    fn lambert_map_uv() {
+        let geometry = MapGeometry{
+            projection_type: ProjectionType::LambertCylindricalEqualArea,
+            left_longitude: Deg(30.0),
+            longitude_span: Deg(180.0)
+        };
+        assert_uv([0.0, 0.5], geometry.uv(Deg(30.0), Deg(0.0)));
+        assert_uv([0.5, (1.0 - 0.5) / 2.0], geometry.uv(Deg(120.0), Deg(30.0)));
+        assert_uv([1.0, 1.0], geometry.uv(Deg(210.0), Deg(-90.0)));
+        // not covered by the map
+        assert!(geometry.uv(Deg(0.0), Deg(0.0)).is_none());
+        assert!(geometry.uv(Deg(211.0), Deg(0.0)).is_none());
+    }
+
+    #[test]
+    fn longitude_span_follows_aspect_ratio() {
+        let span = |projection_type, width, height, sp| longitude_span(projection_type, width, height, Deg(sp)).0;
+        assert!((span(ProjectionType::Equirectangular, 2000, 1000, 0.0) - 360.0).abs() < 1.0e-3);
+        assert!((span(ProjectionType::Equirectangular, 1000, 1000, 0.0) - 180.0).abs() < 1.0e-3);
+        // as exported by the projection view (180°): width = D·π/2, height = D/cos²(sp)
+        let lambert_180 = |sp: f32| {
+            let cos_sp = Deg(sp).cos();
+            span(ProjectionType::LambertCylindricalEqualArea, 1571, (1000.0 / (cos_sp * cos_sp)).round() as u32, sp)
+        };
+        assert!((lambert_180(0.0) - 180.0).abs() < 0.05);
+        assert!((lambert_180(45.0) - 180.0).abs() < 0.05);
+    }
+}
@@ -0,0 +1,293 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Link groups: selected source parameters of several sequences (e.g., captured through different filters during
+//! the same session) are kept in sync. Per-sequence parameters (disk center and diameter) are never linked.
+
+use crate::projection::source_view::SourceParameters;
+use crate::subscriber::Subscriber;
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use strum::IntoEnumIterator;
+
+pub const NUM_GROUPS: usize = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq, strum::EnumIter)]
+pub enum LinkedParameter {
+    Roll,
+    Inclination,
+    RotationPeriod,
+    FrameInterval
+}
+
+impl LinkedParameter {
+    pub fn name(&self) -> &str {
+        match self {
+            LinkedParameter::Roll => "roll",
+            LinkedParameter::Inclination => "inclination",
+            LinkedParameter::RotationPeriod => "rotation period",
+            LinkedParameter::FrameInterval => "frame interval"
+        }
+    }
+
+    /// Copies the parameter from `from` to `to`; returns `true` if the value changed.
+    fn copy(&self, from: &SourceParameters, to: &mut SourceParameters) -> bool {
+        fn assign<T: PartialEq + Copy>(from: T, to: &mut T) -> bool {
+            let changed = *to != from;
+            *to = from;
+            changed
+        }
+
+        match self {
+            LinkedParameter::Roll => assign(from.roll, &mut to.roll),
+            LinkedParameter::Inclination => assign(from.inclination, &mut to.inclination),
+            LinkedParameter::RotationPeriod =>
+                assign(from.sidereal_rotation_period, &mut to.sidereal_rotation_period)
+                | assign(from.rotation_direction, &mut to.rotation_direction),
+            LinkedParameter::FrameInterval => assign(from.frame_interval, &mut to.frame_interval)
+        }
+    }
+}
+
+/// Copies `linked` parameters from `from` to `to`; returns `true` if any of them changed.
+pub fn copy_linked(linked: &[LinkedParameter], from: &SourceParameters, to: &mut SourceParameters) -> bool {
+    let mut changed = false;
+    for parameter in linked { changed |= parameter.copy(from, to); }
+    changed
+}
+
+pub trait LinkMember {
+    /// Called when another member of the group has changed its parameters; `values` contains all of that member's
+    /// parameters, of which only `linked` are to be used.
+    fn on_linked_change(&mut self, values: &SourceParameters, linked: &[LinkedParameter]);
+}
+
+pub struct LinkGroup {
+    /// Index of the group (0-based).
+    index: usize,
+    linked: RefCell<Vec<LinkedParameter>>,
+    members: RefCell<Vec<(u32, Weak<RefCell<dyn LinkMember>>)>>,
+    member_id_counter: Cell<u32>,
+    /// Set while a change is being propagated; notifications caused by the propagation itself are ignored
+    /// (otherwise members would keep notifying each other).
+    propagating: Cell<bool>
+}
+
+impl LinkGroup {
+    /// Creates a group with all parameters linked.
+    pub fn new(index: usize) -> Rc<LinkGroup> {
+        Rc::new(LinkGroup{
+            index,
+            linked: RefCell::new(LinkedParameter::iter().collect()),
+            members: RefCell::new(vec![]),
+            member_id_counter: Cell::new(0),
+            propagating: Cell::new(false)
+        })
+    }
+
+    pub fn index(&self) -> usize { self.index }
+
+    pub fn linked(&self) -> Vec<LinkedParameter> { self.linked.borrow().clone() }
+
+    pub fn is_linked(&self, parameter: LinkedParameter) -> bool { self.linked.borrow().contains(&parameter) }
+
+    pub fn set_linked(&self, parameter: LinkedParameter, linked: bool) {
+        let mut params = self.linked.borrow_mut();
+        params.retain(|p| *p != parameter);
+        if linked { params.push(parameter); }
+    }
+
+    pub fn num_members(&self) -> usize {
+        self.members.borrow().iter().filter(|(_, member)| member.strong_count() > 0).count()
+    }
+
+    /// Adds `member` to the group. Returns the subscriber which has to receive the member's source parameter
+    /// notifications; the member stays in the group as long as the subscriber exists.
+    pub fn join(self: &Rc<LinkGroup>, member: Weak<RefCell<dyn LinkMember>>) -> Rc<RefCell<LinkSubscriber>> {
+        let id = self.member_id_counter.get();
+        self.member_id_counter.set(id + 1);
+        self.members.borrow_mut().push((id, member));
+
+        Rc::new(RefCell::new(LinkSubscriber{ group: Rc::clone(self), member_id: id }))
+    }
+
+    fn leave(&self, member_id: u32) {
+        self.members.borrow_mut().retain(|(id, _)| *id != member_id);
+    }
+
+    /// Passes linked parameters of `values` (changed by member `sender_id`) to the other members.
+    fn propagate(&self, sender_id: u32, values: &SourceParameters) {
+        if self.propagating.get() { return; }
+        self.propagating.set(true);
+
+        let linked = self.linked();
+        // not holding the borrow of `members` while notifying, members may join or leave meanwhile
+        let members: Vec<_> = self.members.borrow().iter().filter(|(id, _)| *id != sender_id).cloned().collect();
+        for (_, member) in members {
+            if let Some(member) = member.upgrade() {
+                member.borrow_mut().on_linked_change(values, &linked);
+            }
+        }
+
+        self.propagating.set(false);
+    }
+}
+
+/// Forwards source parameter changes of a group member to the group.
+pub struct LinkSubscriber {
+    group: Rc<LinkGroup>,
+    member_id: u32
+}
+
+impl LinkSubscriber {
+    pub fn group(&self) -> &Rc<LinkGroup> { &self.group }
+}
+
+impl Subscriber<SourceParameters> for LinkSubscriber {
+    fn notify(&mut self, value: &SourceParameters) {
+        self.group.propagate(self.member_id, value);
+    }
+}
+
+impl Drop for LinkSubscriber {
+    fn drop(&mut self) {
+        self.group.leave(self.member_id);
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::subscriber::SubscriberCollection;
+    use cgmath::{Deg, Point2};
+
+    fn params(disk_center_x: f32) -> SourceParameters {
+        SourceParameters{
+            num_images: 10,
+            disk_center: Point2{ x: disk_center_x, y: 100.0 },
+            disk_diameter: 80.0,
+            ..SourceParameters::test_default()
+        }
+    }
+
+    /// Behaves like a source view: applies linked changes and notifies its own subscribers about them.
+    struct MockMember {
+        params: SourceParameters,
+        subscribers: SubscriberCollection<SourceParameters>,
+        num_linked_changes: usize
+    }
+
+    impl MockMember {
+        fn new(params: SourceParameters) -> Rc<RefCell<MockMember>> {
+            Rc::new(RefCell::new(MockMember{ params, subscribers: SubscriberCollection::new(), num_linked_changes: 0 }))
+        }
+    }
+
+    impl LinkMember for MockMember {
+        fn on_linked_change(&mut self, values: &SourceParameters, linked: &[LinkedParameter]) {
+            self.num_linked_changes += 1;
+            if copy_linked(linked, values, &mut self.params) {
+                self.subscribers.notify(&self.params);
+            }
+        }
+    }
+
+    fn join(group: &Rc<LinkGroup>, member: &Rc<RefCell<MockMember>>) -> Rc<RefCell<LinkSubscriber>> {
+        let subscriber = group.join(Rc::downgrade(member) as _);
+        member.borrow_mut().subscribers.add(Rc::downgrade(&subscriber) as _);
+        subscriber
+    }
+
+    /// Changes roll and inclination of `member` and notifies its subscribers.
+    fn change(member: &Rc<RefCell<MockMember>>, roll: f32, inclination: f32) {
+        let mut member = member.borrow_mut();
+        member.params.roll = Deg(roll);
+        member.params.inclination = Deg(inclination);
+        let params = member.params.clone();
+        member.subscribers.notify(&params);
+    }
+
+    #[test]
+    fn change_propagates_to_other_members_once() {
+        let group = LinkGroup::new(0);
+        let red = MockMember::new(params(100.0));
+        let green = MockMember::new(params(102.0));
+        let blue = MockMember::new(params(98.0));
+        let _subscribers = [join(&group, &red), join(&group, &green), join(&group, &blue)];
+
+        change(&red, 15.0, 3.0);
+
+        for member in [&green, &blue] {
+            let member = member.borrow();
+            assert_eq!(Deg(15.0), member.params.roll);
+            assert_eq!(Deg(3.0), member.params.inclination);
+            // re-notifications of green and blue caused by the propagation were ignored
+            assert_eq!(1, member.num_linked_changes);
+        }
+        assert_eq!(0, red.borrow().num_linked_changes);
+
+        // per-sequence parameters stay independent
+        assert_eq!(102.0, green.borrow().params.disk_center.x);
+        assert_eq!(98.0, blue.borrow().params.disk_center.x);
+    }
+
+    #[test]
+    fn unlinked_parameters_are_not_propagated() {
+        let group = LinkGroup::new(0);
+        group.set_linked(LinkedParameter::Inclination, false);
+        assert!(!group.is_linked(LinkedParameter::Inclination));
+        assert!(group.is_linked(LinkedParameter::Roll));
+
+        let red = MockMember::new(params(100.0));
+        let green = MockMember::new(params(102.0));
+        let _subscribers = [join(&group, &red), join(&group, &green)];
+
+        change(&red, 15.0, 3.0);
+
+        assert_eq!(Deg(15.0), green.borrow().params.roll);
+        assert_eq!(Deg(0.0), green.borrow().params.inclination);
+    }
+
+    #[test]
+    fn member_leaves_when_subscriber_dropped() {
+        let group = LinkGroup::new(0);
+        let red = MockMember::new(params(100.0));
+        let green = MockMember::new(params(102.0));
+        let _red_subscriber = join(&group, &red);
+        let green_subscriber = join(&group, &green);
+        assert_eq!(2, group.num_members());
+
+        drop(green_subscriber);
+        assert_eq!(1, group.num_members());
+
+        change(&red, 15.0, 3.0);
+        assert_eq!(Deg(0.0), green.borrow().params.roll);
+    }
+
+    #[test]
+    fn members_in_different_groups_are_independent() {
+        let group1 = LinkGroup::new(0);
+        let group2 = LinkGroup::new(1);
+        let red = MockMember::new(params(100.0));
+        let green = MockMember::new(params(102.0));
+        let _subscribers = [join(&group1, &red), join(&group2, &green)];
+
+        change(&red, 15.0, 3.0);
+        assert_eq!(Deg(0.0), green.borrow().params.roll);
+    }
+}
@@ -0,0 +1,496 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Cache of information determined when loading images (dimensions, planetary disk found in the first frame), so that
+//! loading the same files again skips probing them. Entries are keyed by file path and invalidated when the file's
+//! size or modification time changes.
+
+use cgmath::Point2;
+use crate::color::Interpretation;
+use crate::config;
+use crate::logging;
+use crate::projection::worker::DiskInfo;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// File (stored alongside the configuration file) to which the cache is saved if persistence is enabled.
+pub const FILE_NAME: &str = "vislumino_load_cache.txt";
+
+pub const DEFAULT_MAX_ENTRIES: usize = 5000;
+
+const FILE_HEADER: &str = "Vislumino load cache v1";
+
+const TEMP_FILE_EXT: &str = "tmp";
+
+pub fn default_file_path() -> PathBuf { config::config_dir_file_path(FILE_NAME) }
+
+/// Identifies a particular version of a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileStamp {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Modification time (nanoseconds since the Unix epoch).
+    pub modified_ns: u128
+}
+
+impl FileStamp {
+    /// Returns `None` if the file's metadata cannot be read.
+    pub fn of(path: &Path) -> Option<FileStamp> {
+        let metadata = std::fs::metadata(path).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(std::time::UNIX_EPOCH).ok()?;
+
+        Some(FileStamp{ path: path.to_path_buf(), size: metadata.len(), modified_ns: modified.as_nanos() })
+    }
+}
+
+/// Files of the current source view, as they were when loaded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadedSet {
+    pub stamps: Vec<FileStamp>,
+    pub binning: u32,
+    pub interpretation: Interpretation
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct CachedDisk {
+    binning: u32,
+    interpretation: Interpretation,
+    disk: DiskInfo
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    size: u64,
+    modified_ns: u128,
+    /// Value of `LoadCache::use_counter` at the last access.
+    last_use: u64,
+    /// Dimensions (before binning).
+    dimensions: Option<[u32; 2]>,
+    disks: Vec<CachedDisk>
+}
+
+impl Entry {
+    fn new(stamp: &FileStamp, last_use: u64) -> Entry {
+        Entry{ size: stamp.size, modified_ns: stamp.modified_ns, last_use, dimensions: None, disks: vec![] }
+    }
+
+    fn matches(&self, stamp: &FileStamp) -> bool {
+        self.size == stamp.size && self.modified_ns == stamp.modified_ns
+    }
+}
+
+pub struct LoadCache {
+    entries: HashMap<PathBuf, Entry>,
+    use_counter: u64,
+    /// When exceeded, the least recently used entries are evicted.
+    max_entries: usize,
+    /// If set, the cache is read from and saved to this file.
+    file_path: Option<PathBuf>,
+    loaded: Option<LoadedSet>
+}
+
+impl LoadCache {
+    pub fn new(file_path: Option<PathBuf>, max_entries: usize) -> LoadCache {
+        let mut cache = LoadCache{
+            entries: HashMap::new(),
+            use_counter: 0,
+            max_entries: max_entries.max(1),
+            file_path: None,
+            loaded: None
+        };
+        cache.set_file_path(file_path);
+
+        cache
+    }
+
+    /// Enables (reading entries from `file_path`; entries already present take precedence) or disables persistence.
+    pub fn set_file_path(&mut self, file_path: Option<PathBuf>) {
+        if let Some(file_path) = &file_path {
+            if self.file_path.as_ref() != Some(file_path) {
+                let contents = match std::fs::read_to_string(file_path) {
+                    Ok(contents) => contents,
+                    Err(e) => {
+                        if e.kind() != std::io::ErrorKind::NotFound {
+                            logging::log_warning!(
+                                "Could not read load cache from {}: {}.", file_path.to_string_lossy(), e
+                            );
+                        }
+                        String::new()
+                    }
+                };
+
+                for (path, entry) in parse(&contents) {
+                    self.use_counter = self.use_counter.max(entry.last_use);
+                    self.entries.entry(path).or_insert(entry);
+                }
+                self.evict();
+            }
+        }
+
+        self.file_path = file_path;
+    }
+
+    pub fn num_entries(&self) -> usize { self.entries.len() }
+
+    /// Returns the cached dimensions (before binning) of the file.
+    pub fn dimensions(&mut self, stamp: &FileStamp) -> Option<[u32; 2]> {
+        self.entry(stamp)?.dimensions
+    }
+
+    pub fn set_dimensions(&mut self, stamp: &FileStamp, dimensions: [u32; 2]) {
+        self.entry_or_insert(stamp).dimensions = Some(dimensions);
+    }
+
+    /// Returns the planetary disk found in the file when loaded with the given options.
+    pub fn disk(&mut self, stamp: &FileStamp, binning: u32, interpretation: Interpretation) -> Option<DiskInfo> {
+        self.entry(stamp)?.disks.iter()
+            .find(|d| d.binning == binning && d.interpretation == interpretation)
+            .map(|d| d.disk)
+    }
+
+    pub fn set_disk(&mut self, stamp: &FileStamp, binning: u32, interpretation: Interpretation, disk: DiskInfo) {
+        let disks = &mut self.entry_or_insert(stamp).disks;
+        disks.retain(|d| d.binning != binning || d.interpretation != interpretation);
+        disks.push(CachedDisk{ binning, interpretation, disk });
+    }
+
+    pub fn set_loaded(&mut self, loaded: Option<LoadedSet>) { self.loaded = loaded; }
+
+    /// Returns `true` if `stamps` (the files to load, as they are now) are the same as the files of the current
+    /// source view, loaded with the same options (i.e., the already loaded images can be reused).
+    pub fn is_reusable(&self, stamps: &[Option<FileStamp>], binning: u32, interpretation: Interpretation) -> bool {
+        match &self.loaded {
+            None => false,
+
+            Some(loaded) =>
+                loaded.binning == binning
+                && loaded.interpretation == interpretation
+                && loaded.stamps.len() == stamps.len()
+                && loaded.stamps.iter().zip(stamps.iter()).all(|(l, s)| Some(l) == s.as_ref())
+        }
+    }
+
+    /// Saves the cache (if persistence is enabled).
+    pub fn save(&self) -> Result<(), std::io::Error> {
+        let file_path = match &self.file_path {
+            None => return Ok(()),
+            Some(file_path) => file_path
+        };
+
+        let temp_path = file_path.with_extension(TEMP_FILE_EXT);
+        std::fs::write(&temp_path, self.to_file_contents())?;
+        std::fs::rename(&temp_path, file_path)
+    }
+
+    fn to_file_contents(&self) -> String {
+        let mut contents = format!("{}\n", FILE_HEADER);
+        for (path, entry) in &self.entries {
+            if let Some(line) = format_line(path, entry) {
+                contents += &line;
+                contents.push('\n');
+            }
+        }
+
+        contents
+    }
+
+    fn next_use(&mut self) -> u64 {
+        self.use_counter += 1;
+        self.use_counter
+    }
+
+    /// Returns the entry of `stamp`'s file if it is up to date; an outdated entry is removed.
+    fn entry(&mut self, stamp: &FileStamp) -> Option<&mut Entry> {
+        match self.entries.get(&stamp.path) {
+            None => return None,
+            Some(entry) => if !entry.matches(stamp) {
+                self.entries.remove(&stamp.path);
+                return None;
+            }
+        }
+
+        let last_use = self.next_use();
+        let entry = self.entries.get_mut(&stamp.path).unwrap();
+        entry.last_use = last_use;
+
+        Some(entry)
+    }
+
+    fn entry_or_insert(&mut self, stamp: &FileStamp) -> &mut Entry {
+        if self.entry(stamp).is_none() {
+            let last_use = self.next_use();
+            self.entries.insert(stamp.path.clone(), Entry::new(stamp, last_use));
+            self.evict();
+        }
+
+        self.entries.get_mut(&stamp.path).unwrap()
+    }
+
+    /// Removes the least recently used entries exceeding `max_entries`.
+    fn evict(&mut self) {
+        if self.entries.len() <= self.max_entries { return; }
+
+        let mut by_use: Vec<(u64, PathBuf)> = self.entries.iter().map(|(p, e)| (e.last_use, p.clone())).collect();
+        by_use.sort_unstable();
+        for (_, path) in by_use.iter().take(self.entries.len() - self.max_entries) {
+            self.entries.remove(path);
+        }
+    }
+}
+
+/// Returns entries read from cache file contents; malformed lines are skipped, as is everything if the header
+/// is missing.
+fn parse(contents: &str) -> Vec<(PathBuf, Entry)> {
+    let mut lines = contents.lines();
+    if lines.next() != Some(FILE_HEADER) {
+        if !contents.is_empty() { logging::log_warning!("Unrecognized load cache file contents; ignoring."); }
+        return vec![];
+    }
+
+    lines.filter_map(parse_line).collect()
+}
+
+/// Line format (tab-separated): size, modification time, last use, dimensions ("WxH" or "-"),
+/// disks (";"-separated "binning,interpretation,center x,center y,diameter" or "-"), path.
+fn format_line(path: &Path, entry: &Entry) -> Option<String> {
+    let path = path.to_str()?;
+    if path.contains('\n') || path.contains('\r') { return None; }
+
+    let dimensions = match entry.dimensions {
+        None => "-".to_string(),
+        Some([width, height]) => format!("{}x{}", width, height)
+    };
+
+    let disks = if entry.disks.is_empty() {
+        "-".to_string()
+    } else {
+        entry.disks.iter().map(|d| format!(
+            "{},{},{},{},{}",
+            d.binning, d.interpretation.to_config_string(), d.disk.center.x, d.disk.center.y, d.disk.diameter
+        )).collect::<Vec<_>>().join(";")
+    };
+
+    Some(format!("{}\t{}\t{}\t{}\t{}\t{}", entry.size, entry.modified_ns, entry.last_use, dimensions, disks, path))
+}
+
+fn parse_line(line: &str) -> Option<(PathBuf, Entry)> {
+    let mut fields = line.splitn(6, '\t');
+    let size = fields.next()?.parse::<u64>().ok()?;
+    let modified_ns = fields.next()?.parse::<u128>().ok()?;
+    let last_use = fields.next()?.parse::<u64>().ok()?;
+
+    let dimensions = match fields.next()? {
+        "-" => None,
+        s => {
+            let (width, height) = s.split_once('x')?;
+            Some([width.parse::<u32>().ok()?, height.parse::<u32>().ok()?])
+        }
+    };
+
+    let disks = match fields.next()? {
+        "-" => vec![],
+        s => s.split(';').map(parse_disk).collect::<Option<Vec<_>>>()?
+    };
+
+    let path = fields.next()?;
+    if path.is_empty() { return None; }
+
+    Some((PathBuf::from(path), Entry{ size, modified_ns, last_use, dimensions, disks }))
+}
+
+fn parse_disk(s: &str) -> Option<CachedDisk> {
+    let fields: Vec<&str> = s.split(',').collect();
+    if fields.len() != 5 { return None; }
+
+    let values = fields[2..].iter().map(|v| v.parse::<f32>().ok()).collect::<Option<Vec<_>>>()?;
+    if values.iter().any(|v| !v.is_finite()) || values[2] <= 0.0 { return None; }
+
+    Some(CachedDisk{
+        binning: fields[0].parse::<u32>().ok().filter(|b| *b > 0)?,
+        interpretation: Interpretation::from_config_string(fields[1])?,
+        disk: DiskInfo{ center: Point2{ x: values[0], y: values[1] }, diameter: values[2] }
+    })
+}
+
+mod tests {
+    use super::*;
+
+    fn stamp(path: &str, size: u64, modified_ns: u128) -> FileStamp {
+        FileStamp{ path: PathBuf::from(path), size, modified_ns }
+    }
+
+    fn disk(x: f32) -> DiskInfo { DiskInfo{ center: Point2{ x, y: 50.0 }, diameter: 40.0 } }
+
+    fn test_file(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vislumino-test-load-cache-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(FILE_NAME)
+    }
+
+    #[test]
+    fn entry_is_invalidated_when_file_changes() {
+        let mut cache = LoadCache::new(None, DEFAULT_MAX_ENTRIES);
+        cache.set_dimensions(&stamp("/a.png", 100, 1000), [640, 480]);
+        assert_eq!(Some([640, 480]), cache.dimensions(&stamp("/a.png", 100, 1000)));
+
+        // modified
+        assert_eq!(None, cache.dimensions(&stamp("/a.png", 100, 1001)));
+        // the outdated entry has been removed, so it does not come back for the original stamp
+        assert_eq!(None, cache.dimensions(&stamp("/a.png", 100, 1000)));
+
+        cache.set_dimensions(&stamp("/a.png", 100, 1000), [640, 480]);
+        // size changed, same modification time (e.g., coarse file system timestamps)
+        assert_eq!(None, cache.dimensions(&stamp("/a.png", 101, 1000)));
+        assert_eq!(0, cache.num_entries());
+
+        // different file
+        cache.set_dimensions(&stamp("/a.png", 100, 1000), [640, 480]);
+        assert_eq!(None, cache.dimensions(&stamp("/b.png", 100, 1000)));
+    }
+
+    #[test]
+    fn disk_is_keyed_by_load_options() {
+        let mut cache = LoadCache::new(None, DEFAULT_MAX_ENTRIES);
+        let s = stamp("/a.png", 100, 1000);
+        cache.set_disk(&s, 1, Interpretation::Srgb, disk(10.0));
+        cache.set_disk(&s, 2, Interpretation::Srgb, disk(20.0));
+        cache.set_disk(&s, 1, Interpretation::Gamma(2.2), disk(30.0));
+
+        assert_eq!(Some(disk(10.0)), cache.disk(&s, 1, Interpretation::Srgb));
+        assert_eq!(Some(disk(20.0)), cache.disk(&s, 2, Interpretation::Srgb));
+        assert_eq!(Some(disk(30.0)), cache.disk(&s, 1, Interpretation::Gamma(2.2)));
+        assert_eq!(None, cache.disk(&s, 1, Interpretation::Linear));
+
+        // replaced, not duplicated
+        cache.set_disk(&s, 1, Interpretation::Srgb, disk(11.0));
+        assert_eq!(Some(disk(11.0)), cache.disk(&s, 1, Interpretation::Srgb));
+
+        assert_eq!(None, cache.disk(&stamp("/a.png", 100, 2000), 1, Interpretation::Srgb));
+    }
+
+    #[test]
+    fn least_recently_used_entries_are_evicted() {
+        let mut cache = LoadCache::new(None, 2);
+        cache.set_dimensions(&stamp("/a.png", 1, 1), [1, 1]);
+        cache.set_dimensions(&stamp("/b.png", 1, 1), [2, 2]);
+        // makes "/a.png" more recently used than "/b.png"
+        assert!(cache.dimensions(&stamp("/a.png", 1, 1)).is_some());
+
+        cache.set_dimensions(&stamp("/c.png", 1, 1), [3, 3]);
+        assert_eq!(2, cache.num_entries());
+        assert_eq!(Some([1, 1]), cache.dimensions(&stamp("/a.png", 1, 1)));
+        assert_eq!(None, cache.dimensions(&stamp("/b.png", 1, 1)));
+        assert_eq!(Some([3, 3]), cache.dimensions(&stamp("/c.png", 1, 1)));
+    }
+
+    #[test]
+    fn reusable_only_for_identical_files_and_options() {
+        let mut cache = LoadCache::new(None, DEFAULT_MAX_ENTRIES);
+        let stamps = vec![Some(stamp("/a.png", 1, 1)), Some(stamp("/b.png", 1, 1))];
+        assert!(!cache.is_reusable(&stamps, 1, Interpretation::Srgb));
+
+        cache.set_loaded(Some(LoadedSet{
+            stamps: stamps.iter().map(|s| s.clone().unwrap()).collect(),
+            binning: 1,
+            interpretation: Interpretation::Srgb
+        }));
+        assert!(cache.is_reusable(&stamps, 1, Interpretation::Srgb));
+
+        assert!(!cache.is_reusable(&stamps, 2, Interpretation::Srgb));
+        assert!(!cache.is_reusable(&stamps, 1, Interpretation::Linear));
+        assert!(!cache.is_reusable(&stamps[..1], 1, Interpretation::Srgb));
+        let modified = [Some(stamp("/a.png", 1, 1)), Some(stamp("/b.png", 1, 2))];
+        assert!(!cache.is_reusable(&modified, 1, Interpretation::Srgb));
+        let unreadable = [Some(stamp("/a.png", 1, 1)), None];
+        assert!(!cache.is_reusable(&unreadable, 1, Interpretation::Srgb));
+    }
+
+    #[test]
+    fn cache_persists_across_instances() {
+        let file_path = test_file("persist");
+        let s = stamp("/data/a b.png", 100, 123456789012345678901234);
+        {
+            let mut cache = LoadCache::new(Some(file_path.clone()), DEFAULT_MAX_ENTRIES);
+            cache.set_dimensions(&s, [640, 480]);
+            cache.set_disk(&s, 2, Interpretation::Gamma(1.8), disk(5.5));
+            cache.set_dimensions(&stamp("/data/c.png", 200, 5), [800, 600]);
+            cache.save().unwrap();
+        }
+
+        let mut cache = LoadCache::new(Some(file_path.clone()), DEFAULT_MAX_ENTRIES);
+        assert_eq!(Some([640, 480]), cache.dimensions(&s));
+        assert_eq!(Some(disk(5.5)), cache.disk(&s, 2, Interpretation::Gamma(1.8)));
+        assert_eq!(Some([800, 600]), cache.dimensions(&stamp("/data/c.png", 200, 5)));
+        assert!(!file_path.with_extension(TEMP_FILE_EXT).exists());
+    }
+
+    #[test]
+    fn corrupt_file_contents_are_tolerated() {
+        let file_path = test_file("corrupt");
+        let valid = format_line(Path::new("/a.png"), &Entry{
+            size: 1, modified_ns: 2, last_use: 3, dimensions: Some([4, 5]), disks: vec![]
+        }).unwrap();
+        std::fs::write(&file_path, format!(
+            "{}\n{}\ngarbage\n1\t2\n1\t2\t3\t4x\t-\t/b.png\n1\t2\t3\t-\t1,srgb,1,2\t/c.png\n{}",
+            FILE_HEADER, valid, &valid[..5] // last line truncated
+        )).unwrap();
+
+        let mut cache = LoadCache::new(Some(file_path.clone()), DEFAULT_MAX_ENTRIES);
+        assert_eq!(Some([4, 5]), cache.dimensions(&stamp("/a.png", 1, 2)));
+        assert_eq!(None, cache.dimensions(&stamp("/b.png", 1, 2)));
+        assert_eq!(None, cache.disk(&stamp("/c.png", 1, 2), 1, Interpretation::Srgb));
+
+        std::fs::write(&file_path, [0xFFu8, 0xFE, 0x00, 0x5B]).unwrap();
+        assert_eq!(0, LoadCache::new(Some(file_path.clone()), DEFAULT_MAX_ENTRIES).num_entries());
+
+        std::fs::write(&file_path, format!("Some other header\n{}\n", valid)).unwrap();
+        assert_eq!(0, LoadCache::new(Some(file_path), DEFAULT_MAX_ENTRIES).num_entries());
+    }
+
+    #[test]
+    fn loaded_file_is_bounded() {
+        let file_path = test_file("bounded");
+        {
+            let mut cache = LoadCache::new(Some(file_path.clone()), 10);
+            for i in 0..10 { cache.set_dimensions(&stamp(&format!("/{}.png", i), 1, 1), [i, i]); }
+            cache.save().unwrap();
+        }
+
+        let mut cache = LoadCache::new(Some(file_path), 3);
+        assert_eq!(3, cache.num_entries());
+        // the most recently used ones are kept
+        assert_eq!(Some([9, 9]), cache.dimensions(&stamp("/9.png", 1, 1)));
+        assert_eq!(None, cache.dimensions(&stamp("/0.png", 1, 1)));
+    }
+
+    #[test]
+    fn file_stamp_reflects_changes() {
+        let file_path = test_file("stamp").with_file_name("image.png");
+        std::fs::write(&file_path, [0u8; 10]).unwrap();
+        let before = FileStamp::of(&file_path).unwrap();
+        assert_eq!(10, before.size);
+
+        std::fs::write(&file_path, [0u8; 20]).unwrap();
+        let after = FileStamp::of(&file_path).unwrap();
+        assert_ne!(before, after);
+
+        assert!(FileStamp::of(&file_path.with_file_name("missing.png")).is_none());
+    }
+}
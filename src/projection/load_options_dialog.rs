@@ -0,0 +1,394 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::background;
+use crate::color::{self, Detected, Interpretation};
+use crate::config::Configuration;
+use crate::gui;
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+use crate::image_utils::BitDepth;
+use crossbeam::channel::TryRecvError;
+use std::path::PathBuf;
+
+const BINNING_VALUES: [u32; 3] = [1, 2, 3];
+const BINNING_LABELS: [&str; 3] = ["none", "2×2", "3×3"];
+const MAX_DECIMATION: u32 = 1000;
+const INTERPRETATION_LABELS: [&str; 3] = ["sRGB", "linear", "gamma"];
+const DEFAULT_GAMMA: f32 = 2.2;
+
+/// Selections of at least this many files get their total size on disk determined (in background), and are loaded only
+/// after confirmation.
+pub const LARGE_SELECTION: usize = 100;
+
+pub const DEFAULT_VRAM_BUDGET_MIB: u32 = 2048;
+
+/// Options applied when loading an image sequence.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LoadOptions {
+    /// Only every `decimation`-th frame is loaded.
+    pub decimation: u32,
+    /// Images are averaged in blocks of `binning`×`binning` pixels.
+    pub binning: u32,
+    /// Tone curve of image values; images are converted to sRGB when loaded and back when exported.
+    pub interpretation: Interpretation,
+    /// Files which fail to load are skipped instead of aborting the loading.
+    pub skip_failed_frames: bool
+}
+
+impl Default for LoadOptions {
+    fn default() -> LoadOptions {
+        LoadOptions{ decimation: 1, binning: 1, interpretation: Interpretation::Srgb, skip_failed_frames: false }
+    }
+}
+
+impl LoadOptions {
+    /// Returns an error message if the options cannot be used for images of `dimensions`.
+    pub fn validate(&self, dimensions: [u32; 2]) -> Result<(), String> {
+        if self.decimation == 0 { return Err("Decimation cannot be zero.".to_string()); }
+        if self.binning == 0 { return Err("Binning factor cannot be zero.".to_string()); }
+        if self.binning > dimensions[0] || self.binning > dimensions[1] {
+            return Err(format!(
+                "Images ({}x{} pixels) are too small for {}x{} binning.",
+                dimensions[0], dimensions[1], self.binning, self.binning
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+pub enum LoadOptionsResult {
+    Pending,
+    Accepted,
+    Cancelled
+}
+
+/// Returns the number of frames loaded out of `num_files` with the given decimation.
+pub fn num_frames_to_load(num_files: usize, decimation: u32) -> usize {
+    (num_files + decimation as usize - 1) / decimation as usize
+}
+
+/// Returns estimated GPU memory (in bytes) needed to load `num_files` images of `dimensions` (before binning) and
+/// `bit_depth`.
+pub fn estimate_vram(num_files: usize, dimensions: [u32; 2], bit_depth: BitDepth, options: LoadOptions) -> u64 {
+    // invalid options (see `LoadOptions::validate`) are rejected when loading starts
+    let width = dimensions[0].checked_div(options.binning).unwrap_or(0) as u64;
+    let height = dimensions[1].checked_div(options.binning).unwrap_or(0) as u64;
+
+    num_frames_to_load(num_files, options.decimation) as u64 * width * height * bit_depth.bytes_per_pixel()
+}
+
+/// Returns `true` if loading needs to be confirmed in the dialog: for large selections, or if the current options would
+/// exceed the GPU memory budget.
+fn needs_confirmation(
+    num_files: usize,
+    dimensions: [u32; 2],
+    bit_depth: BitDepth,
+    options: LoadOptions,
+    vram_budget_mib: u32
+) -> bool {
+    num_files >= LARGE_SELECTION
+        || estimate_vram(num_files, dimensions, bit_depth, options) > vram_budget_mib as u64 * (1 << 20)
+}
+
+fn to_mib(bytes: u64) -> f64 {
+    bytes as f64 / (1 << 20) as f64
+}
+
+/// Total size on disk of the selected files, determined in background.
+enum DiskSize {
+    Unknown,
+    Pending(crossbeam::channel::Receiver<u64>),
+    Known(u64)
+}
+
+pub struct LoadOptionsDialog {
+    title: String,
+    options: LoadOptions,
+    vram_budget_mib: u32,
+    disk_size: DiskSize,
+    /// Color information found in the first selected file.
+    detected: Option<Detected>,
+    /// If the selected files are already loaded (and unchanged), their images are reused instead of loading again.
+    reuse_loaded: bool,
+    /// If true, the load cache is kept between sessions.
+    cache_on_disk: bool,
+    /// If false, the selection is loaded with the current options without showing the dialog.
+    confirmation_required: bool
+}
+
+impl LoadOptionsDialog {
+    pub fn new(title: String, options: LoadOptions, vram_budget_mib: u32, cache_on_disk: bool) -> LoadOptionsDialog {
+        LoadOptionsDialog{
+            title,
+            options,
+            vram_budget_mib,
+            disk_size: DiskSize::Unknown,
+            detected: None,
+            reuse_loaded: true,
+            cache_on_disk,
+            confirmation_required: true
+        }
+    }
+
+    pub fn title(&self) -> &str { &self.title }
+
+    pub fn options(&self) -> LoadOptions { self.options }
+
+    pub fn vram_budget_mib(&self) -> u32 { self.vram_budget_mib }
+
+    pub fn reuse_loaded(&self) -> bool { self.reuse_loaded }
+
+    pub fn cache_on_disk(&self) -> bool { self.cache_on_disk }
+
+    pub fn confirmation_required(&self) -> bool { self.confirmation_required }
+
+    /// Determines whether loading `num_files` images needs to be confirmed in the dialog; returns the result.
+    pub fn require_confirmation(&mut self, num_files: usize, dimensions: [u32; 2], bit_depth: BitDepth) -> bool {
+        self.confirmation_required =
+            needs_confirmation(num_files, dimensions, bit_depth, self.options, self.vram_budget_mib);
+        self.confirmation_required
+    }
+
+    /// Prepares the dialog for a new selection; for large selections, starts determining their total size on disk.
+    pub fn set_selection(&mut self, paths: &[PathBuf]) {
+        self.detected = paths.first().and_then(|path| color::detect_file(path));
+        if let Some(Detected::Interpretation(interpretation)) = self.detected {
+            self.options.interpretation = interpretation;
+        }
+
+        if paths.len() < LARGE_SELECTION {
+            self.disk_size = DiskSize::Unknown;
+            return;
+        }
+
+        let (sender, receiver) = crossbeam::channel::bounded(1);
+        let paths = paths.to_vec();
+        std::thread::spawn(move || {
+            background::apply_priority();
+            let total = paths.iter().map(|p| std::fs::metadata(p).map(|m| m.len()).unwrap_or(0)).sum();
+            // the dialog may have been closed in the meantime
+            let _ = sender.send(total);
+        });
+        self.disk_size = DiskSize::Pending(receiver);
+    }
+
+    fn update_disk_size(&mut self) {
+        if let DiskSize::Pending(receiver) = &self.disk_size {
+            match receiver.try_recv() {
+                Ok(total) => self.disk_size = DiskSize::Known(total),
+                Err(TryRecvError::Empty) => (),
+                Err(TryRecvError::Disconnected) => self.disk_size = DiskSize::Unknown
+            }
+        }
+    }
+}
+
+pub fn handle_load_options_dialog(
+    ui: &imgui::Ui,
+    config: &mut Configuration,
+    dialog: &mut LoadOptionsDialog,
+    num_files: usize,
+    dimensions: [u32; 2],
+    bit_depth: BitDepth,
+    reusable: &dyn Fn(LoadOptions) -> bool
+) -> LoadOptionsResult {
+    let mut result = LoadOptionsResult::Pending;
+
+    dialog.update_disk_size();
+
+    let title = dialog.title.clone();
+    modal::modal(ui, config, &title, KeyBindings::all(), |key_action, _| {
+        ui.text(format!("Selected files: {}", num_files));
+        match &dialog.disk_size {
+            DiskSize::Unknown => (),
+            DiskSize::Pending(_) => {
+                const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+                ui.text(format!("Size on disk: {}", SPINNER[(ui.time() * 8.0) as usize % SPINNER.len()]));
+            },
+            DiskSize::Known(total) => ui.text(format!("Size on disk: {:.1} MiB", to_mib(*total)))
+        }
+        ui.text(format!("Image size: {}x{} ({})", dimensions[0], dimensions[1], bit_depth.name()));
+
+        gui::add_text_before(ui, "load every");
+        let mut value = dialog.options.decimation as i32;
+        if ui.input_int("frame##load-decimation", &mut value).build() {
+            dialog.options.decimation = (value.max(1) as u32).min(MAX_DECIMATION);
+        }
+        gui::tooltip(ui, "Load only every N-th frame (frame interval is multiplied accordingly).");
+
+        ui.text(format!("Frames to load: {}", num_frames_to_load(num_files, dialog.options.decimation)));
+
+        gui::add_text_before(ui, "binning");
+        let mut index = BINNING_VALUES.iter().position(|b| *b == dialog.options.binning).unwrap_or(0);
+        if ui.combo_simple_string("##load-binning", &mut index, &BINNING_LABELS) {
+            dialog.options.binning = BINNING_VALUES[index];
+        }
+        gui::tooltip(ui, "Average blocks of pixels to reduce memory use and noise.");
+
+        handle_interpretation_controls(ui, dialog);
+
+        ui.checkbox("skip files which fail to load", &mut dialog.options.skip_failed_frames);
+        gui::tooltip(ui, "Continue loading if some files cannot be read; skipped files are listed afterwards. Files which \
+            are missing, incomplete or still being written are retried once before being skipped.");
+
+        let reusable = reusable(dialog.options);
+        if reusable {
+            ui.checkbox("reuse already loaded images", &mut dialog.reuse_loaded);
+            gui::tooltip(ui, "The selected files are already loaded (with the same options) and have not changed.");
+        }
+
+        ui.checkbox("remember image information between sessions", &mut dialog.cache_on_disk);
+        gui::tooltip(ui, "Keep dimensions and detected disks of loaded files to make loading them again faster.");
+
+        gui::add_text_before(ui, "GPU memory budget");
+        let mut value = dialog.vram_budget_mib as i32;
+        if ui.input_int("MiB##vram-budget", &mut value).step(256).build() {
+            dialog.vram_budget_mib = value.max(1) as u32;
+        }
+
+        let vram = estimate_vram(num_files, dimensions, bit_depth, dialog.options);
+        // reused images are already in GPU memory
+        let within_budget = vram <= dialog.vram_budget_mib as u64 * (1 << 20) || (reusable && dialog.reuse_loaded);
+        ui.text(format!("Estimated GPU memory: {:.1} MiB", to_mib(vram)));
+        if !within_budget {
+            ui.text_colored([1.0, 0.3, 0.3, 1.0], "Exceeds the budget; increase decimation or binning.");
+        }
+
+        ui.separator();
+        let token = ui.begin_disabled(!within_budget);
+        if modal::default_button(ui, "Load") || (key_action == KeyAction::Accept && within_budget) {
+            result = LoadOptionsResult::Accepted;
+            ui.close_current_popup();
+        }
+        token.end();
+        ui.same_line();
+
+        if ui.button("Cancel") || key_action == KeyAction::Cancel {
+            result = LoadOptionsResult::Cancelled;
+            ui.close_current_popup();
+        }
+    });
+
+    if !matches!(result, LoadOptionsResult::Pending) { dialog.disk_size = DiskSize::Unknown; }
+
+    result
+}
+
+fn handle_interpretation_controls(ui: &imgui::Ui, dialog: &mut LoadOptionsDialog) {
+    gui::add_text_before(ui, "values");
+    let mut index = match dialog.options.interpretation {
+        Interpretation::Srgb => 0,
+        Interpretation::Linear => 1,
+        Interpretation::Gamma(_) => 2
+    };
+    if ui.combo_simple_string("##load-interpretation", &mut index, &INTERPRETATION_LABELS) {
+        dialog.options.interpretation = match index {
+            0 => Interpretation::Srgb,
+            1 => Interpretation::Linear,
+            _ => Interpretation::Gamma(DEFAULT_GAMMA)
+        };
+    }
+    gui::tooltip(ui, "Tone curve of the image values; images are converted to sRGB for processing.");
+
+    if let Interpretation::Gamma(gamma) = dialog.options.interpretation {
+        ui.same_line();
+        let mut value = gamma;
+        let w = ui.push_item_width(ui.calc_text_size("MMMMMMMM")[0]);
+        if ui.input_float("##load-gamma", &mut value).step(0.1).display_format("%0.2f").build() {
+            dialog.options.interpretation = Interpretation::Gamma(value.max(0.1).min(10.0));
+        }
+        w.end();
+    }
+
+    match dialog.detected {
+        None => (),
+        Some(Detected::Interpretation(Interpretation::Srgb)) => ui.text_disabled("(file specifies sRGB)"),
+        Some(Detected::Interpretation(Interpretation::Linear)) => ui.text_disabled("(file specifies linear values)"),
+        Some(Detected::Interpretation(Interpretation::Gamma(gamma))) =>
+            ui.text_disabled(format!("(file specifies gamma {:.2})", gamma)),
+        Some(Detected::IccProfile) => ui.text_disabled("(file has an embedded ICC profile, which is not interpreted)")
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_to_load_rounds_up() {
+        assert_eq!(10, num_frames_to_load(10, 1));
+        assert_eq!(4, num_frames_to_load(10, 3));
+        assert_eq!(1, num_frames_to_load(10, 1000));
+    }
+
+    #[test]
+    fn vram_estimate_accounts_for_decimation_and_binning() {
+        let dimensions = [640, 480];
+        let depth = BitDepth::Eight;
+        assert_eq!(
+            5000 * 640 * 480 * 3,
+            estimate_vram(5000, dimensions, depth, LoadOptions{ decimation: 1, binning: 1, ..Default::default() })
+        );
+        assert_eq!(
+            500 * 320 * 240 * 3,
+            estimate_vram(5000, dimensions, depth, LoadOptions{ decimation: 10, binning: 2, ..Default::default() })
+        );
+        // binning discards incomplete blocks
+        assert_eq!(
+            213 * 160 * 3,
+            estimate_vram(1, dimensions, depth, LoadOptions{ decimation: 1, binning: 3, ..Default::default() })
+        );
+    }
+
+    #[test]
+    fn invalid_binning_is_rejected() {
+        let dimensions = [640, 480];
+        assert!(LoadOptions{ binning: 3, ..Default::default() }.validate(dimensions).is_ok());
+        assert!(LoadOptions{ binning: 480, ..Default::default() }.validate(dimensions).is_ok());
+        assert!(LoadOptions{ binning: 0, ..Default::default() }.validate(dimensions).is_err());
+        assert!(LoadOptions{ binning: 481, ..Default::default() }.validate(dimensions).is_err());
+        assert!(LoadOptions{ decimation: 0, ..Default::default() }.validate(dimensions).is_err());
+        assert_eq!(0, estimate_vram(10, dimensions, BitDepth::Eight, LoadOptions{ binning: 0, ..Default::default() }));
+    }
+
+    #[test]
+    fn vram_estimate_doubles_for_16_bit_images() {
+        let options = LoadOptions{ decimation: 1, binning: 1, ..Default::default() };
+        assert_eq!(
+            2 * estimate_vram(100, [640, 480], BitDepth::Eight, options),
+            estimate_vram(100, [640, 480], BitDepth::Sixteen, options)
+        );
+    }
+
+    #[test]
+    fn small_selections_within_budget_are_loaded_without_confirmation() {
+        let options = LoadOptions::default();
+        // 99 × 640 × 480 × 3 B ≈ 87 MiB
+        assert!(!needs_confirmation(99, [640, 480], BitDepth::Eight, options, 100));
+        assert!(needs_confirmation(100, [640, 480], BitDepth::Eight, options, 100));
+    }
+
+    #[test]
+    fn small_selections_over_budget_need_confirmation() {
+        let options = LoadOptions::default();
+        // 10 × 4000 × 3000 × 6 B ≈ 687 MiB
+        assert!(needs_confirmation(10, [4000, 3000], BitDepth::Sixteen, options, 512));
+        assert!(!needs_confirmation(10, [4000, 3000], BitDepth::Sixteen, options, 1024));
+    }
+}
@@ -0,0 +1,247 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Saving of a projection view's current map to a single file, bypassing the sequence export.
+
+use crate::gui::long_task_dialog::ProgressMsg;
+use crate::long_fg_task::LongForegroundTask;
+use crate::projection::Planet;
+use crate::projection::projection_view::ProjectionType;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const OVERWRITE_TITLE: &str = "Overwrite file?";
+
+/// Encoded maps larger than this are written in chunks (with a progress dialog) instead of in one go.
+const CHUNKED_WRITE_THRESHOLD: usize = 16 << 20;
+
+const CHUNK_SIZE: usize = 4 << 20;
+
+const PARTIAL_FILE_EXT: &str = "part";
+
+/// Returns the default file name of the saved map of frame `frame_idx`.
+pub fn default_file_name(planet: Option<Planet>, frame_idx: usize, projection_type: ProjectionType) -> String {
+    format!(
+        "{}_frame{:05}_{}.png",
+        planet.map_or("map".to_string(), |planet| planet.name().to_lowercase()),
+        frame_idx + 1,
+        projection_type.name()
+    )
+}
+
+/// Returns `path` with the ".png" extension appended, unless it already has a supported one (PNG or TIFF).
+pub fn with_image_extension(path: PathBuf) -> PathBuf {
+    let supported = path.extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| ["png", "tif", "tiff"].contains(&ext.to_lowercase().as_str()));
+
+    if supported {
+        path
+    } else {
+        let mut path = path.into_os_string();
+        path.push(".png");
+        path.into()
+    }
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".");
+    partial.push(PARTIAL_FILE_EXT);
+    partial.into()
+}
+
+/// Encodes `image` in the format corresponding to the extension of `path`.
+fn encode(image: &ga_image::Image, path: &Path) -> Result<Vec<u8>, image::ImageError> {
+    let mut bytes = std::io::Cursor::new(vec![]);
+    image::write_buffer_with_format(
+        &mut bytes,
+        image.raw_pixels(),
+        image.width(),
+        image.height(),
+        image::ColorType::Rgb8,
+        image::ImageFormat::from_path(path)?
+    )?;
+
+    Ok(bytes.into_inner())
+}
+
+/// Saves `image` (RGB8) as `path` in one go.
+pub fn write_map(image: &ga_image::Image, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::write(path, encode(image, path)?)?;
+    Ok(())
+}
+
+/// Saves `image` (RGB8) as `path`. Small images are saved immediately (returns `None`); for large ones, returns
+/// the task which writes the file (and the receiver of its progress).
+pub fn save_map(
+    image: &ga_image::Image,
+    path: &Path
+) -> Result<Option<(MapWriteTask, crossbeam::channel::Receiver<ProgressMsg>)>, Box<dyn std::error::Error>> {
+    let bytes = encode(image, path)?;
+    if bytes.len() <= CHUNKED_WRITE_THRESHOLD {
+        std::fs::write(path, &bytes)?;
+        Ok(None)
+    } else {
+        Ok(Some(MapWriteTask::new(path, bytes)?))
+    }
+}
+
+/// Writes an encoded map in chunks, one per GUI frame; the file gets its final name only once complete.
+pub struct MapWriteTask {
+    path: PathBuf,
+    file: Option<std::fs::File>,
+    bytes: Vec<u8>,
+    num_written: usize,
+    /// Dropped when the task ends (which ends the progress dialog).
+    progress_sender: Option<crossbeam::channel::Sender<ProgressMsg>>,
+    end_message: Option<(String, String)>
+}
+
+impl MapWriteTask {
+    fn new(
+        path: &Path,
+        bytes: Vec<u8>
+    ) -> Result<(MapWriteTask, crossbeam::channel::Receiver<ProgressMsg>), std::io::Error> {
+        let file = std::fs::File::create(partial_path(path))?;
+        let (progress_sender, progress_receiver) = crossbeam::channel::bounded(1);
+
+        Ok((MapWriteTask{
+            path: path.to_path_buf(),
+            file: Some(file),
+            bytes,
+            num_written: 0,
+            progress_sender: Some(progress_sender),
+            end_message: None
+        }, progress_receiver))
+    }
+
+    fn write_chunk(&mut self) -> Result<bool, std::io::Error> {
+        let file = self.file.as_mut().unwrap();
+        let end = (self.num_written + CHUNK_SIZE).min(self.bytes.len());
+        file.write_all(&self.bytes[self.num_written..end])?;
+        self.num_written = end;
+        if self.num_written < self.bytes.len() { return Ok(true); }
+
+        file.sync_all()?;
+        self.file = None;
+        std::fs::rename(partial_path(&self.path), &self.path)?;
+
+        Ok(false)
+    }
+
+    /// Ends the task, removing the partially written file (if any).
+    fn end(&mut self, message: Option<(&str, String)>) {
+        if self.file.take().is_some() { let _ = std::fs::remove_file(partial_path(&self.path)); }
+        self.progress_sender = None;
+        self.end_message = message.map(|(title, text)| (title.to_string(), text));
+    }
+}
+
+impl LongForegroundTask for MapWriteTask {
+    fn step(&mut self) -> bool {
+        if self.progress_sender.is_none() { return false; }
+
+        match self.write_chunk() {
+            Ok(true) => {
+                let _ = self.progress_sender.as_ref().unwrap().try_send(ProgressMsg::new(
+                    format!("Saving {}...", self.path.to_string_lossy()),
+                    self.num_written as f32 / self.bytes.len() as f32
+                ));
+                true
+            },
+
+            Ok(false) => {
+                self.end(None);
+                false
+            },
+
+            Err(e) => {
+                let message = format!("Could not save {}: {}.", self.path.to_string_lossy(), e);
+                self.end(Some(("Error", message)));
+                false
+            }
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.end(None);
+    }
+
+    fn end_message(&self) -> Option<(String, String)> { self.end_message.clone() }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_file_name_encodes_planet_frame_and_projection() {
+        assert_eq!(
+            "jupiter_frame00001_equirectangular.png",
+            default_file_name(Some(Planet::Jupiter), 0, ProjectionType::Equirectangular)
+        );
+        assert_eq!(
+            "map_frame00120_lambert.png",
+            default_file_name(None, 119, ProjectionType::LambertCylindricalEqualArea)
+        );
+    }
+
+    #[test]
+    fn png_extension_is_added_unless_supported() {
+        assert_eq!(PathBuf::from("/maps/mars.png"), with_image_extension("/maps/mars".into()));
+        assert_eq!(PathBuf::from("/maps/mars.TIFF"), with_image_extension("/maps/mars.TIFF".into()));
+        assert_eq!(PathBuf::from("/maps/mars.tif"), with_image_extension("/maps/mars.tif".into()));
+        // not replaced, so that a name containing dots stays intact
+        assert_eq!(PathBuf::from("/maps/mars.2022-10-01.png"), with_image_extension("/maps/mars.2022-10-01".into()));
+    }
+
+    #[test]
+    fn large_map_is_written_in_chunks() {
+        let dir = std::env::temp_dir().join(format!("vislumino-test-map-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("map.png");
+        let bytes: Vec<u8> = (0..2 * CHUNK_SIZE + 10).map(|i| (i % 251) as u8).collect();
+
+        let (mut task, _progress) = MapWriteTask::new(&path, bytes.clone()).unwrap();
+        assert!(task.step());
+        assert!(task.step());
+        assert!(!path.exists());
+        assert!(!task.step());
+        assert_eq!(bytes, std::fs::read(&path).unwrap());
+        assert!(!partial_path(&path).exists());
+        assert!(task.end_message().is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cancelled_write_leaves_no_files() {
+        let dir = std::env::temp_dir().join(format!("vislumino-test-map-save-cancel-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("map.tif");
+
+        let (mut task, _progress) = MapWriteTask::new(&path, vec![0; CHUNK_SIZE + 1]).unwrap();
+        assert!(task.step());
+        task.cancel();
+        assert!(!task.step());
+        assert!(!path.exists() && !partial_path(&path).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
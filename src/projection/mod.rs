@@ -17,38 +17,107 @@
 // along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
 //
 
-use crate::config::ProjectionConfig;
+use crate::cancellation::CancelToken;
+use crate::fits;
+use crate::config::{Configuration, ProjectionConfig};
+use crate::gpu::gl_debug;
+use crate::gpu::render_check;
+use crate::gpu::texture_limits;
 use crate::gui;
 use crate::gui::long_task_dialog::LongTaskDialog;
 use crate::image_utils;
+use crate::img_seq;
+use crate::logging;
 use crate::projection;
 use crate::runner;
 use crossbeam::channel::TryRecvError;
-use ga_image::PixelFormat;
 use glium::{CapabilitiesSource, GlObject};
 use std::cell::RefCell;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 use strum::IntoEnumIterator;
 
+mod composite;
+mod composite_view;
+mod contact_sheet;
+mod coverage;
+mod cpu_render;
 mod data;
+mod diagnostic;
+mod diameter_tool;
+mod disk_confirmation;
+mod display_stretch;
+mod ephem;
+mod export_conflicts;
+mod export_comparison;
 mod export_dialog;
+mod export_metadata;
+mod export_presets;
+mod export_preview;
+mod field_rotation;
+mod file_stability;
+mod frame_time;
 mod globe_view;
+mod imported_map;
+mod linking;
+mod map_save;
+mod load_cache;
+mod load_options_dialog;
+mod model_export;
+mod orientation_gizmo;
+mod output_format;
+mod overlay_color;
+mod phase;
+mod polar;
+mod post_export;
 mod projection_view;
+mod seams;
+mod session_check;
+mod session_state;
+mod snapshots;
 mod source_view;
+mod verification;
+mod winjupos;
 mod worker;
 
+pub use composite_view::CompositeView;
 pub use data::ProgramData;
 pub use export_dialog::{ExportDialog, handle_export_dialog};
+pub use export_presets::Preset as ExportPreset;
 pub use globe_view::GlobeView;
-pub use projection_view::ProjectionView;
+pub use overlay_color::OverlaySettings;
+pub use projection_view::{DisplaySettings, ProjectionView};
+pub use session_state::{marked_title, UNSAVED_CHANGES_WARNING};
 pub use source_view::SourceView;
 
-use self::worker::MainToWorkerMsg;
+pub use worker::{MainToWorkerMsg, spawn_worker};
+
+/// Values farther than this many standard deviations from the mean are rejected when stacking with sigma-clipping.
+const STACKING_SIGMA_CLIP_KAPPA: f32 = 2.5;
+
+const CONTEXT_LOST_TITLE: &str = "GPU context lost";
+
+const NO_GLOBE_VIEW_TITLE: &str = "No globe view";
+
+const AUTO_PROJECTION_VIEW_HINT_TITLE: &str = "Projection view";
+
+const CLOSE_CONFIRMATION_TITLE: &str = "Close projection mode";
+
+/// Animated features; each has to be listed here (see `gui::motion`).
+const ANIMATIONS: [&gui::motion::AnimationSource; 4] = [
+    &gui::crossfade::ANIMATION,
+    &globe_view::NAVIGATION_ANIMATION,
+    &verification::BLINK_ANIMATION,
+    &export_comparison::BLINK_ANIMATION
+];
+
+const AUTO_PROJECTION_VIEW_HINT: &str = "A projection view was created automatically \u{2014} adjust disk and roll in \
+    Source images for best results.\n\n(This can be disabled in View \u{2192} Open projection view after loading.)";
 
 #[derive(Copy, Clone, strum::EnumIter, PartialEq)]
 pub enum Planet {
     Jupiter,
-    Mars
+    Mars,
+    Venus
 }
 
 impl Planet {
@@ -56,6 +125,7 @@ impl Planet {
         match self {
             Planet::Jupiter => "Jupiter",
             Planet::Mars => "Mars",
+            Planet::Venus => "Venus"
         }
     }
 
@@ -63,13 +133,48 @@ impl Planet {
         match self {
             Planet::Jupiter => 0.06487,
             Planet::Mars => 0.00589,
+            Planet::Venus => 0.0
         }
     }
 
+    /// Returns the sidereal rotation period of the planet's body.
     pub fn sidereal_rotation(&self) -> std::time::Duration {
         match self {
             Planet::Jupiter => std::time::Duration::from_secs(9 * 3600 + 55 * 60 + 30),
             Planet::Mars => std::time::Duration::from_secs(24 * 3600 + 37 * 60 + 23),
+            Planet::Venus => std::time::Duration::from_secs(20_997_153), // 243.0226 d
+        }
+    }
+
+    /// Returns the rotation period of the visible cloud deck, if it differs markedly from the body's rotation.
+    pub fn atmospheric_rotation(&self) -> Option<std::time::Duration> {
+        match self {
+            Planet::Jupiter | Planet::Mars => None,
+            Planet::Venus => Some(std::time::Duration::from_secs(362_880)), // 4.2 d
+        }
+    }
+
+    /// Returns the period of `rotation`; the body's, if the planet has no separate atmospheric rotation.
+    pub fn rotation_period(&self, rotation: TrackedRotation) -> std::time::Duration {
+        match rotation {
+            TrackedRotation::Body => self.sidereal_rotation(),
+            TrackedRotation::Atmosphere => self.atmospheric_rotation().unwrap_or_else(|| self.sidereal_rotation())
+        }
+    }
+
+    /// Returns true if the planet can be seen with a noticeable phase (then phase handling is offered).
+    pub fn has_phase(&self) -> bool {
+        match self {
+            Planet::Jupiter => false,
+            Planet::Mars | Planet::Venus => true
+        }
+    }
+
+    /// Returns the direction of rotation (of both the body and the atmosphere).
+    pub fn rotation_direction(&self) -> RotationDirection {
+        match self {
+            Planet::Jupiter | Planet::Mars => RotationDirection::Prograde,
+            Planet::Venus => RotationDirection::Retrograde
         }
     }
 
@@ -90,6 +195,183 @@ impl From<usize> for Planet {
     }
 }
 
+/// Direction of a planet's rotation as seen from above its north pole (as defined by the IAU, i.e., on the north
+/// side of the invariable plane).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RotationDirection {
+    /// Counterclockwise; the central meridian's (west) longitude increases with time.
+    Prograde,
+    /// Clockwise (e.g., Venus); features drift across the disk from west to east.
+    Retrograde
+}
+
+impl RotationDirection {
+    pub fn name(&self) -> &str {
+        match self {
+            RotationDirection::Prograde => "prograde",
+            RotationDirection::Retrograde => "retrograde"
+        }
+    }
+
+    /// Returns the sign of the rotation angle per unit of time (1.0 for prograde rotation).
+    pub fn sign(&self) -> f32 {
+        match self {
+            RotationDirection::Prograde => 1.0,
+            RotationDirection::Retrograde => -1.0
+        }
+    }
+}
+
+/// Which rotation a sequence is compensated for.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TrackedRotation {
+    /// Rotation of the planet's body (surface features).
+    Body,
+    /// Rotation of the cloud deck (e.g., the super-rotation of Venus' atmosphere seen in UV).
+    Atmosphere
+}
+
+impl TrackedRotation {
+    pub fn name(&self) -> &str {
+        match self {
+            TrackedRotation::Body => "body",
+            TrackedRotation::Atmosphere => "atmosphere"
+        }
+    }
+}
+
+/// On-screen orientation of a view's contents; does not affect the rendered data, exports or coordinate readouts.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DisplayOrientation {
+    /// Rotated by 180° (classic telescope view).
+    pub south_up: bool,
+    /// Flipped horizontally.
+    pub mirrored: bool
+}
+
+impl DisplayOrientation {
+    pub fn from_config(config: &Configuration) -> DisplayOrientation {
+        DisplayOrientation{
+            south_up: config.view_south_up().unwrap_or(false),
+            mirrored: config.view_mirrored().unwrap_or(false)
+        }
+    }
+
+    /// Returns (horizontal, vertical) flips with respect to the north-up, unmirrored display.
+    fn flips(&self) -> (bool, bool) { (self.south_up != self.mirrored, self.south_up) }
+
+    /// Returns texture coordinates (top-left, bottom-right) to be used for displaying a view's image.
+    pub fn uv_bounds(&self) -> ([f32; 2], [f32; 2]) {
+        let (horizontal, vertical) = self.flips();
+        let (u0, u1) = if horizontal { (1.0, 0.0) } else { (0.0, 1.0) };
+        let (v0, v1) = if vertical { (1.0, 0.0) } else { (0.0, 1.0) };
+
+        ([u0, v0], [u1, v1])
+    }
+
+    /// Converts normalized (within [0; 1], from top-left) position in the displayed image to the corresponding
+    /// position in the north-up, unmirrored image.
+    pub fn to_data_position(&self, pos: [f32; 2]) -> [f32; 2] {
+        let (horizontal, vertical) = self.flips();
+        [
+            if horizontal { 1.0 - pos[0] } else { pos[0] },
+            if vertical { 1.0 - pos[1] } else { pos[1] }
+        ]
+    }
+}
+
+/// Shows display orientation controls; returns `true` if changed (the new orientation is also stored in `config`
+/// as the default for new views).
+fn handle_display_orientation_controls(
+    ui: &imgui::Ui,
+    id: u32,
+    config: &mut Configuration,
+    orientation: &mut DisplayOrientation
+) -> bool {
+    let mut changed = ui.checkbox(format!("view S-up##view-s-up-{}", id), &mut orientation.south_up);
+    gui::tooltip(ui, "Show rotated by 180° (display only; exports are unaffected).");
+
+    ui.same_line();
+    changed |= ui.checkbox(format!("mirror view##mirror-view-{}", id), &mut orientation.mirrored);
+    gui::tooltip(ui, "Show flipped horizontally (display only; exports are unaffected).");
+
+    if changed {
+        config.set_view_south_up(orientation.south_up);
+        config.set_view_mirrored(orientation.mirrored);
+    }
+
+    changed
+}
+
+/// Returns the duration of the crossfade between frames in projection and globe views (zero if disabled).
+fn frame_crossfade_duration(config: &Configuration) -> std::time::Duration {
+    if !config.frame_crossfade().unwrap_or(false) { return std::time::Duration::ZERO; }
+
+    let ms = config.frame_crossfade_ms().unwrap_or(gui::crossfade::DEFAULT_DURATION.as_millis() as u32);
+    std::time::Duration::from_millis(ms as u64).clamp(gui::crossfade::MIN_DURATION, gui::crossfade::MAX_DURATION)
+}
+
+fn handle_frame_crossfade_controls(ui: &imgui::Ui, config: &mut Configuration, reduce_motion: bool) {
+    let enabled = config.frame_crossfade().unwrap_or(false);
+    let token = ui.begin_disabled(reduce_motion);
+    if ui.menu_item_config("Crossfade between frames").selected(enabled).build() {
+        config.set_frame_crossfade(!enabled);
+    }
+    token.end();
+    if reduce_motion && ui.is_item_hovered_with_flags(imgui::ItemHoveredFlags::ALLOW_WHEN_DISABLED) {
+        ui.tooltip_text("Unavailable with Settings \u{2192} Accessibility \u{2192} Reduce motion.");
+    }
+    if reduce_motion { return; }
+    gui::tooltip(ui, "Blend briefly between frames when stepping through them (skipped during fast playback).");
+
+    if enabled {
+        let mut ms = frame_crossfade_duration(config).as_millis() as u32;
+        if imgui::Slider::new(
+            "fade duration",
+            gui::crossfade::MIN_DURATION.as_millis() as u32,
+            gui::crossfade::MAX_DURATION.as_millis() as u32
+        ).display_format("%d ms").build(ui, &mut ms) {
+            config.set_frame_crossfade_ms(ms);
+        }
+    }
+}
+
+/// Explains why the last item (e.g., a group of widgets disabled by an export lock) is disabled.
+fn export_lock_tooltip(ui: &imgui::Ui, locked: bool) {
+    if locked && ui.is_item_hovered_with_flags(imgui::ItemHoveredFlags::ALLOW_WHEN_DISABLED) {
+        ui.tooltip_text(data::EXPORT_LOCK_REASON);
+    }
+}
+
+/// Shows frame pinning controls; returns new pinned frame index (`Some(None)` means "unpinned") if changed.
+fn handle_frame_pin_controls(
+    ui: &imgui::Ui,
+    id: u32,
+    pinned_frame: Option<usize>,
+    live_frame: usize,
+    num_images: usize
+) -> Option<Option<usize>> {
+    let mut result = None;
+
+    let mut pinned = pinned_frame.is_some();
+    if ui.checkbox(format!("pin frame##pin-frame-{}", id), &mut pinned) {
+        result = Some(if pinned { Some(live_frame) } else { None });
+    }
+    gui::tooltip(ui, "Keep showing the selected frame regardless of the current source frame.");
+
+    ui.same_line();
+    let token = ui.begin_disabled(!pinned);
+    let mut value = pinned_frame.unwrap_or(live_frame) as i32 + 1;
+    let w = ui.push_item_width(ui.calc_text_size("MMMMMMMM")[0]);
+    if ui.input_int(format!("##pinned-frame-{}", id), &mut value).build() && pinned {
+        result = Some(Some((value.max(1) as usize).min(num_images) - 1));
+    }
+    w.end();
+    token.end();
+
+    result
+}
+
 fn handle_main_menu(
     ui: &imgui::Ui,
     gui_state: &mut gui::GuiState,
@@ -99,46 +381,185 @@ fn handle_main_menu(
 ) -> Option<runner::FontSizeRequest> {
     let mut about_clicked = false;
     let mut load_images_clicked = false;
+    let mut load_video_clicked = false;
+    let mut open_map_clicked = false;
     let mut new_projection_view_clicked = false;
     let mut new_globe_view_clicked = false;
+    let mut new_composite_view_clicked = false;
     let mut font_size_clicked = false;
+    let mut gpu_inspector_clicked = false;
+    let mut log_clicked = false;
+    let mut shortcuts_clicked = false;
+    let mut format_clicked = false;
+    let mut background_clicked = false;
+    let mut config_backups_clicked = false;
+    let mut close_clicked = false;
 
     match ui.begin_main_menu_bar() {
         None => (),
 
         Some(_) => {
-            ui.menu("File", || { if ui.menu_item("Load images...") { load_images_clicked = true; }});
+            ui.menu("File", || {
+                // the running task may still be using the current images
+                let task_in_progress = program_data.task_in_progress();
+                let token = ui.begin_disabled(task_in_progress);
+                let hovered = || ui.is_item_hovered_with_flags(imgui::ItemHoveredFlags::ALLOW_WHEN_DISABLED);
+                if ui.menu_item("Load images...") { load_images_clicked = true; }
+                let mut load_hovered = hovered();
+                if ui.menu_item("Load video...") { load_video_clicked = true; }
+                load_hovered |= hovered();
+                token.end();
+                if task_in_progress && load_hovered {
+                    ui.tooltip_text("Unavailable until the running task (e.g., export) finishes.");
+                }
+                // does not use the loaded images
+                if ui.menu_item("Open map on globe...") { open_map_clicked = true; }
 
-            ui.menu("View", || {
-                let token = ui.begin_enabled(program_data.source_view().is_some());
+                ui.separator();
+                if ui.menu_item("Close projection mode") { close_clicked = true; }
+            });
 
+            ui.menu("View", || {
+                let has_source = program_data.source_view().is_some();
                 ui.menu("New", || {
-                    if ui.menu_item("Projection") { new_projection_view_clicked = true; }
-                    if ui.menu_item("Globe") { new_globe_view_clicked = true; }
+                    if ui.menu_item_config("Projection").enabled(has_source).build() {
+                        new_projection_view_clicked = true;
+                    }
+                    if ui.menu_item_config("Globe").enabled(has_source).build() { new_globe_view_clicked = true; }
+                    ui.separator();
+                    // combines exported map sequences; does not use the loaded images
+                    if ui.menu_item("Composite (RGB)") { new_composite_view_clicked = true; }
                 });
 
-                token.end();
+                ui.separator();
+                let config = &mut program_data.base().borrow_mut().config;
+                let auto_view = config.auto_projection_view().unwrap_or(true);
+                if ui.menu_item_config("Open projection view after loading").selected(auto_view).build() {
+                    config.set_auto_projection_view(!auto_view);
+                }
+                handle_frame_crossfade_controls(ui, config, gui_state.reduce_motion());
+
+                ui.separator();
+                if ui.menu_item_config("Log").selected(gui_state.log_window.open).build() { log_clicked = true; }
             });
 
-            ui.menu("Settings", || { if ui.menu_item("Font size...") { font_size_clicked = true; }});
+            ui.menu("Settings", || {
+                if ui.menu_item("Font size...") { font_size_clicked = true; }
+                if ui.menu_item("Number format...") { format_clicked = true; }
+                if ui.menu_item("Keyboard shortcuts...") { shortcuts_clicked = true; }
+                if ui.menu_item("Background tasks...") { background_clicked = true; }
+                if ui.menu_item("Configuration backups...") { config_backups_clicked = true; }
+                if ui.menu_item("GPU resource inspector") { gpu_inspector_clicked = true; }
+                let config = &mut program_data.base().borrow_mut().config;
+                let diagnostic_option = config.diagnostic_rendering_option().unwrap_or(false);
+                if ui.menu_item_config("Show diagnostic rendering option").selected(diagnostic_option).build() {
+                    config.set_diagnostic_rendering_option(!diagnostic_option);
+                }
+                let gl_debug_output = crate::config::GuiConfig::gl_debug_output(config).unwrap_or(false);
+                if ui.menu_item_config("Log OpenGL debug output").selected(gl_debug_output).build() {
+                    crate::config::GuiConfig::set_gl_debug_output(config, !gl_debug_output);
+                }
+                gui::tooltip(ui, &format!(
+                    "Takes effect after restart (currently {}; can also be enabled with --{}).",
+                    if gl_debug::requested() { "enabled" } else { "disabled" },
+                    crate::args::cmdline::GL_DEBUG
+                ));
+                ui.menu("Accessibility", || {
+                    let reduce_motion = gui_state.reduce_motion();
+                    if ui.menu_item_config("Reduce motion").selected(reduce_motion).build() {
+                        gui_state.set_reduce_motion(!reduce_motion);
+                        crate::config::GuiConfig::set_reduce_motion(config, !reduce_motion);
+                    }
+                    let names: Vec<&str> = ANIMATIONS.iter().map(|animation| animation.name).collect();
+                    gui::tooltip(ui, &format!("Disables animations ({}).", names.join(", ")));
+                });
+                ui.separator();
+                if ui.menu_item("Run setup again...") { gui_state.setup_requested = true; }
+            });
 
             ui.menu("Help", || { if ui.menu_item("About...") { about_clicked = true; }});
         }
     }
 
-    gui::about_dialog::handle_about_dialog(ui, about_clicked);
+    gui::about_dialog::handle_about_dialog(ui, &mut program_data.base().borrow_mut().config, about_clicked);
+
+    let font_size_request = gui::font_dialog::handle_font_dialog(
+        ui, gui_state, &mut program_data.base().borrow_mut().config, font_size_clicked
+    );
+
+    gui::format_dialog::handle_format_dialog(
+        ui, gui_state, &mut program_data.base().borrow_mut().config, format_clicked
+    );
+
+    gui::shortcuts_dialog::handle_shortcuts_dialog(
+        ui, gui_state, &mut program_data.base().borrow_mut().config, shortcuts_clicked
+    );
+
+    gui::background_dialog::handle_background_dialog(
+        ui, gui_state, &mut program_data.base().borrow_mut().config, background_clicked
+    );
+
+    gui::config_backup_dialog::handle_config_backup_dialog(
+        ui, gui_state, &mut program_data.base().borrow_mut().config, config_backups_clicked
+    );
+
+    if gpu_inspector_clicked { gui_state.gpu_inspector_open = !gui_state.gpu_inspector_open; }
+
+    if log_clicked { gui_state.log_window.open = !gui_state.log_window.open; }
+
+    if gui_state.shortcut_triggered(gui::shortcuts::Action::LoadImages) && !program_data.task_in_progress() {
+        load_images_clicked = true;
+    }
+    if program_data.source_view().is_some() {
+        if gui_state.shortcut_triggered(gui::shortcuts::Action::NewProjectionView) {
+            new_projection_view_clicked = true;
+        }
+        if gui_state.shortcut_triggered(gui::shortcuts::Action::NewGlobeView) { new_globe_view_clicked = true; }
+    }
+
+    if load_images_clicked { handle_load_images(ui, gui_state, program_data); }
 
-    let font_size_request = gui::font_dialog::handle_font_dialog(ui, gui_state, font_size_clicked);
+    if load_video_clicked { handle_load_video(ui, gui_state, program_data); }
 
-    if load_images_clicked { handle_load_images(ui, gui_state, display, program_data); }
+    if open_map_clicked { handle_open_map(ui, gui_state, program_data, display); }
 
     if new_projection_view_clicked { program_data.add_projection_view(display, renderer); }
 
     if new_globe_view_clicked { program_data.add_globe_view(display, renderer); }
 
+    if new_composite_view_clicked { program_data.add_composite_view(display, renderer); }
+
+    if close_clicked {
+        if program_data.session_state().is_dirty() {
+            ui.open_popup(CLOSE_CONFIRMATION_TITLE);
+        } else {
+            program_data.request_close();
+        }
+    }
+    if handle_close_confirmation(ui, &mut program_data.base().borrow_mut().config) { program_data.request_close(); }
+
     font_size_request
 }
 
+/// Returns `true` if closing of the projection mode (discarding unsaved changes) has been confirmed.
+fn handle_close_confirmation(ui: &imgui::Ui, config: &mut Configuration) -> bool {
+    let mut confirmed = false;
+
+    gui::modal::modal(ui, config, CLOSE_CONFIRMATION_TITLE, gui::modal::KeyBindings::all(), |key_action, _| {
+        ui.text(UNSAVED_CHANGES_WARNING);
+        ui.separator();
+
+        if gui::modal::default_button(ui, "Close") || key_action == gui::modal::KeyAction::Accept {
+            confirmed = true;
+            ui.close_current_popup();
+        }
+        ui.same_line();
+        if ui.button("Cancel") || key_action == gui::modal::KeyAction::Cancel { ui.close_current_popup(); }
+    });
+
+    confirmed
+}
+
 pub fn handle_gui(
     program_data: &mut ProgramData,
     ui: &imgui::Ui,
@@ -147,25 +568,76 @@ pub fn handle_gui(
     display: &glium::Display
 ) -> Option<runner::FontSizeRequest> {
     let result = handle_main_menu(ui, gui_state, program_data, renderer, display);
+    report_worker_texture_limit(ui, gui_state, program_data);
 
-    let allow_playback = program_data.long_task_dialog().borrow().is_none();
+    let task_in_progress = program_data.long_task_dialog().borrow().is_some();
+    // a backgrounded export uses its own snapshot of the parameters, so the views may keep playing
+    let allow_playback = program_data.long_task_dialog().borrow().as_ref()
+        .map_or(true, |dialog| dialog.in_background());
+    let params_locked = program_data.export_lock().borrow().is_some();
 
+    let link_groups = program_data.link_groups().to_vec();
+    let mut request = source_view::SourceViewRequest::None;
+    let mut overlay_settings = None;
+    let mut src_params_changed = false;
+    let unsaved = program_data.session_state().source_params_edited();
     if let Some(source_view) = program_data.source_view_mut() {
-        source_view::handle_source_view(ui, gui_state, source_view, allow_playback);
+        request = source_view::handle_source_view(
+            ui, gui_state, source_view, &link_groups, allow_playback, task_in_progress, params_locked, unsaved
+        );
+        overlay_settings = source_view.take_changed_overlay_settings();
+        src_params_changed = source_view.take_src_params_changed();
+    }
+    if src_params_changed { program_data.session_state_mut().mark_source_params_edited(); }
+    if let Some(settings) = overlay_settings {
+        program_data.base().borrow_mut().config.set_source_overlay_settings(&settings);
+    }
+    match request {
+        source_view::SourceViewRequest::None => (),
+        source_view::SourceViewRequest::Stacking => start_frame_stacking(program_data, None),
+        source_view::SourceViewRequest::GroupStacking(group_size) =>
+            start_frame_stacking(program_data, Some(group_size)),
+        source_view::SourceViewRequest::BrightnessMeasurement => start_brightness_measurement(program_data),
+        source_view::SourceViewRequest::LimbMeasurement => start_limb_measurement(program_data),
+        source_view::SourceViewRequest::StoreSnapshot(slot) => store_snapshot(program_data, slot),
+        source_view::SourceViewRequest::ToggleSnapshot => toggle_snapshot(program_data)
     }
 
-    program_data.globe_views().borrow_mut().retain_mut(
-        |view| globe_view::handle_globe_view(
+    let mut closed_globe_views = vec![];
+    program_data.globe_views().borrow_mut().retain_mut(|view| {
+        let opened = globe_view::handle_globe_view(
             ui,
             gui_state,
+            &mut program_data.base().borrow_mut().config,
             &mut view.borrow_mut(),
+            program_data.source_view().as_ref(),
             program_data.long_task_dialog(),
             program_data.bg_task_sender()
+        );
+        if !opened { closed_globe_views.push(Rc::downgrade(view)); }
+        opened
+    });
+
+    program_data.composite_views().borrow_mut().retain(
+        |view| composite_view::handle_composite_view(
+            ui,
+            gui_state,
+            &mut program_data.base().borrow_mut().config,
+            view,
+            program_data.long_task_dialog(),
+            program_data.long_fg_task()
         )
     );
 
-    program_data.projection_views().borrow_mut().retain_mut(
-        |view| projection_view::handle_projection_view(
+    let mut display_settings_broadcast = None;
+    let mut globe_target = None;
+    program_data.linked_cursor().new_frame(program_data.projection_views().borrow().len());
+    let mut closed_projection_views = vec![];
+    let mut edited_views = vec![];
+    let mut focused_view = None;
+    program_data.projection_views().borrow_mut().retain_mut(|view| {
+        let unsaved = program_data.session_state().view_edited(view.borrow().id());
+        let opened = projection_view::handle_projection_view(
             ui,
             gui_state,
             &mut program_data.base().borrow_mut().config,
@@ -173,40 +645,135 @@ pub fn handle_gui(
             program_data.source_view().as_ref().unwrap(),
             program_data.long_task_dialog(),
             program_data.bg_task_sender(),
-            program_data.export_dialog()
-        )
-    );
+            program_data.export_dialog(),
+            program_data.export_result(),
+            program_data.export_lock(),
+            program_data.linked_cursor(),
+            &mut display_settings_broadcast,
+            &mut globe_target,
+            program_data.long_fg_task(),
+            program_data.texture_limits(),
+            unsaved
+        );
+        if view.borrow_mut().take_settings_changed() { edited_views.push(view.borrow().id()); }
+        if view.borrow_mut().take_focused() { focused_view = Some(view.borrow().id()); }
+        if !opened { closed_projection_views.push(Rc::downgrade(view)); }
+        opened
+    });
+    for id in edited_views { program_data.session_state_mut().mark_view_edited(id); }
+    if focused_view.is_some() { program_data.set_active_projection_view(focused_view); }
+    unsubscribe_closed_views(program_data, &closed_globe_views, &closed_projection_views);
+    if let Some(settings) = display_settings_broadcast {
+        for view in program_data.projection_views().borrow().iter() {
+            view.borrow_mut().apply_display_settings(&settings);
+        }
+    }
+    if let Some(target) = globe_target {
+        if program_data.globe_views().borrow().is_empty() {
+            *program_data.pending_globe_target_mut() = Some(target);
+            ui.open_popup(NO_GLOBE_VIEW_TITLE);
+        } else {
+            for view in program_data.globe_views().borrow().iter() { view.borrow_mut().navigate_to(&target); }
+        }
+    }
+    handle_no_globe_view_dialog(ui, program_data, renderer, display);
+
+    handle_export_result(ui, gui_state, program_data);
+
+    handle_context_lost_dialog(ui, gui_state, program_data, display);
 
     let mut in_progress = false;
     if let Some(long_task_dialog) = &mut *program_data.long_task_dialog().borrow_mut() {
         if let Some(long_fg_task) = &mut *program_data.long_fg_task().borrow_mut() {
             long_fg_task.step();
         }
+        // tasks with a cancel token are cancelled by the dialog itself
+        let has_cancel_token = long_task_dialog.cancel_token().is_some();
 
         in_progress = gui::long_task_dialog::handle_long_task(
             ui,
+            &mut program_data.base().borrow_mut().config,
             long_task_dialog,
             || {
                 if let Some(long_fg_task) = &mut *program_data.long_fg_task().borrow_mut() {
                     long_fg_task.cancel();
-                } else {
+                } else if !has_cancel_token {
                     program_data.bg_task_sender().send(MainToWorkerMsg::Cancel).unwrap();
                 }
             }
         );
     }
     if !in_progress {
-        *program_data.long_fg_task().borrow_mut() = None;
+        let long_fg_task = program_data.long_fg_task().borrow_mut().take();
+        if let Some((title, message)) = long_fg_task.and_then(|task| task.end_message()) {
+            gui_state.message_box = Some(gui::MessageBox{ title: title.clone(), message });
+            ui.open_popup(title);
+        }
         *program_data.long_task_dialog().borrow_mut() = None;
     }
 
+    handle_pending_load(ui, gui_state, program_data, display);
+
     handle_image_loading(ui, gui_state, program_data, renderer, display);
 
-    gui::handle_message_box(ui, gui_state);
+    handle_session_check(ui, gui_state, program_data, renderer, display);
+
+    handle_disk_confirmation(ui, gui_state, program_data, renderer, display);
+
+    handle_out_of_order_frame_times(ui, program_data);
+
+    handle_map_import(ui, gui_state, program_data, renderer, display);
+
+    handle_frame_stacking(ui, gui_state, program_data, display);
+
+    handle_brightness_measurement(program_data);
+    handle_limb_measurement(program_data);
+
+    if render_check::take_restart_advice() {
+        // the program may not survive until a clean exit
+        if let Err(e) = program_data.base().borrow_mut().config.store() {
+            logging::log_error!("Error saving configuration: {}.", e);
+        }
+        gui_state.message_box = Some(gui::MessageBox{
+            title: "Error".to_string(),
+            message: render_check::RESTART_ADVICE.to_string()
+        });
+        ui.open_popup("Error");
+    } else if gl_debug::take_warning() {
+        gui_state.message_box = Some(gui::MessageBox{
+            title: "Warning".to_string(),
+            message: gl_debug::HIGH_SEVERITY_WARNING.to_string()
+        });
+        ui.open_popup("Warning");
+    }
+
+    gui::handle_message_box(ui, gui_state, &mut program_data.base().borrow_mut().config);
 
     result
 }
 
+/// Removes the source view subscriptions of views closed during this GUI frame, so that they do not accumulate
+/// over many open/close cycles.
+fn unsubscribe_closed_views(
+    program_data: &mut ProgramData,
+    closed_globe_views: &[Weak<RefCell<GlobeView>>],
+    closed_projection_views: &[Weak<RefCell<ProjectionView>>]
+) {
+    let num_views = program_data.globe_views().borrow().len() + program_data.projection_views().borrow().len();
+    if let Some(source_view) = program_data.source_view_mut() {
+        for view in closed_globe_views { source_view.unsubscribe(view); }
+        for view in closed_projection_views { source_view.unsubscribe(view); }
+
+        // besides the views, source parameters are subscribed to only by the link group forwarder
+        let (num_image_subscribers, num_params_subscribers) = source_view.num_subscribers();
+        debug_assert!(
+            num_image_subscribers <= num_views && num_params_subscribers <= num_views + 1,
+            "leaked subscriptions: {} (current image), {} (source parameters) for {} views",
+            num_image_subscribers, num_params_subscribers, num_views
+        );
+    }
+}
+
 fn handle_image_loading(
     ui: &imgui::Ui,
     gui_state: &mut gui::GuiState,
@@ -216,20 +783,26 @@ fn handle_image_loading(
 ) {
     let mut finished = false;
     let mut loaded = false;
-    let mut disk_info: Option<worker::DiskInfo> = None;
+    let mut first_frame: Option<worker::FirstFrame> = None;
+    let mut skipped: Vec<worker::SkippedFrame> = vec![];
 
     match program_data.image_loading() {
         None => (),
         Some(imgl) => {
             match imgl.receiver.try_recv() {
                 Ok(msg) => match msg {
-                    worker::LoadImagesResultMsg::Success(dinfo) => {
+                    worker::LoadImagesResultMsg::Success(frame, skipped_frames) => {
+                        logging::log_info!(
+                            "Loaded {} of {} files.", imgl.paths.len() - skipped_frames.len(), imgl.paths.len()
+                        );
                         loaded = true;
-                        disk_info = Some(dinfo);
+                        first_frame = Some(frame);
+                        skipped = skipped_frames;
                         finished = true;
                     },
 
                     worker::LoadImagesResultMsg::Error(e) => {
+                        logging::log_error!("Failed to load images: {}.", e);
                         finished = true;
                         gui_state.message_box = Some(gui::MessageBox{
                             title: "Error".to_string(),
@@ -238,7 +811,15 @@ fn handle_image_loading(
                         ui.open_popup("Error");
                     },
 
-                    worker::LoadImagesResultMsg::Cancelled => finished = true,
+                    worker::LoadImagesResultMsg::Cancelled(num_loaded) => {
+                        logging::log_info!("Loading cancelled after {} of {} files.", num_loaded, imgl.paths.len());
+                        finished = true;
+                        gui_state.message_box = Some(gui::MessageBox{
+                            title: "Cancelled".to_string(),
+                            message: format!("Loading cancelled after {} of {} files.", num_loaded, imgl.paths.len())
+                        });
+                        ui.open_popup("Cancelled");
+                    },
                 },
 
                 Err(e) => match e {
@@ -250,54 +831,797 @@ fn handle_image_loading(
     }
 
     if loaded {
-        let image_loading = program_data.image_loading_mut().take().unwrap();
-        let disk_info = disk_info.unwrap();
+        // the load path and options are worth keeping even if the program does not exit cleanly
+        if let Err(e) = program_data.base().borrow_mut().config.flush() {
+            logging::log_error!("Error saving configuration: {}.", e);
+        }
+
+        let mut image_loading = program_data.image_loading_mut().take().unwrap();
+
+        let mut skipped_message = None;
+        if !skipped.is_empty() {
+            skipped_message = Some(skipped_files_message(&image_loading.paths, &skipped));
+            // drops the textures of skipped frames
+            image_loading.textures = worker::remove_skipped(image_loading.textures, &skipped);
+            image_loading.paths = worker::remove_skipped(image_loading.paths, &skipped);
+            image_loading.stamps = worker::remove_skipped(image_loading.stamps, &skipped);
+        }
 
-        match program_data.source_view_mut() {
-            None => *program_data.source_view_mut() = Some(source_view::SourceView::new(
-                &program_data.gl_objects,
-                display,
-                renderer,
-                image_loading.textures,
-                disk_info.center,
-                disk_info.diameter
-            )),
+        let max_gap = session_check::max_gap(&program_data.base().borrow().config);
+        let rotation_period = match program_data.source_view() {
+            Some(source_view) => source_view.src_params().sidereal_rotation_period,
+            None => program_data.base().borrow().config.default_planet().unwrap_or(Planet::Jupiter).sidereal_rotation()
+        };
+        let times = session_check::file_times(&image_loading.paths, &image_loading.stamps).filter(|times| {
+            !session_check::Analysis::new(&times.julian_dates, rotation_period, max_gap).plausible()
+        });
+
+        match times {
+            Some(times) => {
+                logging::log_warning!("Timestamps of the loaded frames suggest more than one observing session.");
+                *program_data.session_check().borrow_mut() = Some(session_check::SessionCheck::new(
+                    image_loading, first_frame.unwrap(), skipped_message, times, rotation_period, max_gap
+                ));
+            },
 
-            Some(source_view) =>
-                source_view.set_images(image_loading.textures, disk_info.center, disk_info.diameter)
+            None => start_disk_confirmation(
+                program_data, renderer, display, image_loading, first_frame.unwrap(), skipped_message
+            )
         }
     }
 
     if finished { *program_data.image_loading_mut() = None; }
 }
 
+/// The images are applied once the user confirms the disk detected in `first_frame`.
+fn start_disk_confirmation(
+    program_data: &ProgramData,
+    renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>,
+    display: &glium::Display,
+    image_loading: data::ImageLoading,
+    first_frame: worker::FirstFrame,
+    skipped_message: Option<String>
+) {
+    *program_data.disk_confirmation().borrow_mut() = Some(disk_confirmation::DiskConfirmation::new(
+        &program_data.gl_objects,
+        display,
+        renderer,
+        image_loading.textures,
+        image_loading.paths,
+        image_loading.stamps,
+        image_loading.load_options,
+        image_loading.bit_depth,
+        first_frame,
+        skipped_message
+    ));
+}
+
+fn handle_session_check(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    program_data: &mut ProgramData,
+    renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>,
+    display: &glium::Display
+) {
+    if program_data.session_check().borrow().is_none() { return; }
+
+    // waits until the loading's progress dialog closes
+    if program_data.long_task_dialog().borrow().is_some() { return; }
+
+    ui.open_popup(session_check::TITLE);
+    let result = session_check::handle_session_check(
+        ui,
+        &mut program_data.base().borrow_mut().config,
+        &gui_state.format,
+        program_data.session_check().borrow_mut().as_mut().unwrap()
+    );
+
+    let keep_largest = match result {
+        session_check::SessionCheckResult::Pending => return,
+        session_check::SessionCheckResult::KeepLargest => true,
+        session_check::SessionCheckResult::ProceedAnyway => false,
+        session_check::SessionCheckResult::Cancel => {
+            logging::log_info!("Loading cancelled due to frames from different sessions.");
+            *program_data.session_check().borrow_mut() = None;
+            return;
+        }
+    };
+
+    let check = program_data.session_check().borrow_mut().take().unwrap();
+    let kept = check.analysis().largest_cluster().indices.clone();
+    let mut image_loading = check.image_loading;
+    let mut first_frame = check.first_frame;
+
+    if keep_largest {
+        logging::log_info!("Keeping {} of {} frames (the largest session).", kept.len(), image_loading.paths.len());
+        image_loading.textures = session_check::select(image_loading.textures, &kept);
+        image_loading.paths = session_check::select(image_loading.paths, &kept);
+        image_loading.stamps = session_check::select(image_loading.stamps, &kept);
+
+        if kept[0] != 0 {
+            // the disk is confirmed in the first remaining frame
+            let image = image_utils::image_from_texture(&image_loading.textures[0])
+                .convert_pix_fmt(ga_image::PixelFormat::Mono8, None);
+            let disk = crate::disk::find_planetary_disk(&image).ok()
+                .map(|(center, diameter)| worker::DiskInfo{ center, diameter });
+            first_frame = worker::FirstFrame{ image, disk };
+        }
+    }
+
+    start_disk_confirmation(program_data, renderer, display, image_loading, first_frame, check.skipped_files_message);
+}
+
+/// Lets the user select a map file to be shown on a new globe view.
+fn handle_open_map(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    program_data: &mut ProgramData,
+    display: &glium::Display
+) {
+    let path = gui::file_dialog::checked(ui, gui_state, native_dialog::FileDialog::new()
+        .add_filter("image files (BMP, PNG, TIFF)", &["bmp", "png", "tif", "tiff"])
+        .add_filter("all files", &["*"])
+        .show_open_single_file()
+    ).flatten();
+    let path = match path { Some(path) => path, None => return };
+
+    let max_texture_size = display.get_capabilities().max_texture_size as u32;
+    let image = image_utils::load_image(&path)
+        .map_err(|e| format!("Could not load {}: {}.", path.display(), e))
+        .and_then(|image| if image.width() > max_texture_size || image.height() > max_texture_size {
+            Err(format!("The map is too large (the maximum is {0}x{0} pixels).", max_texture_size))
+        } else {
+            Ok(image)
+        });
+
+    match image {
+        Ok(image) => *program_data.map_import().borrow_mut() = Some(imported_map::MapImportDialog::new(&path, image)),
+        Err(message) => {
+            gui_state.message_box = Some(gui::MessageBox{ title: "Error".to_string(), message });
+            ui.open_popup("Error");
+        }
+    }
+}
+
+fn handle_map_import(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    program_data: &mut ProgramData,
+    renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>,
+    display: &glium::Display
+) {
+    if program_data.map_import().borrow().is_none() { return; }
+
+    ui.open_popup(imported_map::TITLE);
+    let result = imported_map::handle_map_import_dialog(
+        ui,
+        &mut program_data.base().borrow_mut().config,
+        program_data.map_import().borrow_mut().as_mut().unwrap()
+    );
+
+    match result {
+        imported_map::MapImportResult::Pending => (),
+        imported_map::MapImportResult::Cancelled => *program_data.map_import().borrow_mut() = None,
+        imported_map::MapImportResult::Accepted => {
+            let dialog = program_data.map_import().borrow_mut().take().unwrap();
+            match dialog.into_map(display) {
+                Ok(map) => program_data.add_map_globe_view(display, renderer, map),
+                Err(e) => {
+                    gui_state.message_box = Some(gui::MessageBox{
+                        title: "Error".to_string(),
+                        message: format!("Could not show the map: {}.", e)
+                    });
+                    ui.open_popup("Error");
+                }
+            }
+        }
+    }
+}
+
+/// Asks the user how to treat frames whose times (from the file names) are earlier than those of the preceding frames.
+fn handle_out_of_order_frame_times(ui: &imgui::Ui, program_data: &mut ProgramData) {
+    // waits until the loading (including its dialogs) is complete
+    if program_data.long_task_dialog().borrow().is_some()
+        || program_data.session_check().borrow().is_some()
+        || program_data.disk_confirmation().borrow().is_some() {
+        return;
+    }
+
+    let result = match program_data.source_view() {
+        Some(source_view) if !source_view.out_of_order_frames().is_empty() => {
+            ui.open_popup(frame_time::OUT_OF_ORDER_TITLE);
+            frame_time::handle_out_of_order_prompt(
+                ui,
+                &mut program_data.base().borrow_mut().config,
+                source_view.file_paths(),
+                source_view.out_of_order_frames(),
+                source_view.src_params().frame_interval
+            )
+        },
+        _ => return
+    };
+
+    let use_interval = match result {
+        frame_time::OutOfOrderResult::Pending => return,
+        frame_time::OutOfOrderResult::UseInterval => true,
+        frame_time::OutOfOrderResult::KeepTimestamps => false
+    };
+    program_data.source_view_mut().as_mut().unwrap().resolve_out_of_order_frames(use_interval);
+}
+
+fn handle_disk_confirmation(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    program_data: &mut ProgramData,
+    renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>,
+    display: &glium::Display
+) {
+    if program_data.disk_confirmation().borrow().is_none() { return; }
+
+    // waits until the loading's progress dialog closes
+    if program_data.long_task_dialog().borrow().is_some() { return; }
+
+    ui.open_popup(disk_confirmation::TITLE);
+    let result = disk_confirmation::handle_disk_confirmation(
+        ui,
+        &mut program_data.base().borrow_mut().config,
+        &gui_state.format,
+        program_data.disk_confirmation().borrow_mut().as_mut().unwrap()
+    );
+
+    let (disk, adjust_manually) = match result {
+        disk_confirmation::DiskConfirmationResult::Pending => return,
+        disk_confirmation::DiskConfirmationResult::Accepted(disk) => (disk, false),
+        disk_confirmation::DiskConfirmationResult::AdjustManually(disk) => (disk, true)
+    };
+
+    let confirmation = program_data.disk_confirmation().borrow_mut().take().unwrap();
+    let stamps = confirmation.stamps;
+    let load_options = confirmation.load_options;
+
+    let first_load = program_data.source_view().is_none();
+    let default_planet = program_data.base().borrow().config.default_planet().unwrap_or(Planet::Jupiter);
+    let overlay_settings = program_data.base().borrow().config.source_overlay_settings().unwrap_or_default();
+
+    // on failure, the loaded textures are released here and the previous images (if any) stay in use
+    let applied = match program_data.source_view_mut() {
+        None => source_view::SourceView::new(
+            &program_data.gl_objects,
+            display,
+            renderer,
+            confirmation.textures,
+            confirmation.paths,
+            disk.center,
+            disk.diameter,
+            load_options,
+            confirmation.bit_depth,
+            default_planet
+        ).map(|mut source_view| {
+            source_view.set_overlay_settings(overlay_settings);
+            *program_data.source_view_mut() = Some(source_view);
+        }),
+
+        Some(source_view) => source_view.set_images(
+            confirmation.textures,
+            confirmation.paths,
+            disk.center,
+            disk.diameter,
+            load_options,
+            confirmation.bit_depth
+        )
+    };
+
+    if let Err(e) = applied {
+        logging::log_error!("Could not use the loaded images: {}.", e);
+        gui_state.message_box = Some(gui::MessageBox{
+            title: "Error".to_string(),
+            message: format!("Could not use the loaded images: {}.", e)
+        });
+        ui.open_popup("Error");
+        return;
+    }
+
+    update_load_cache(program_data.load_cache_mut(), &stamps, load_options, disk);
+
+    if adjust_manually { program_data.source_view_mut().as_mut().unwrap().focus_disk_controls(); }
+
+    let auto_view_enabled = program_data.base().borrow().config.auto_projection_view().unwrap_or(true);
+    let auto_view = auto_projection_view_needed(
+        auto_view_enabled, first_load, program_data.projection_views().borrow().len()
+    );
+    if auto_view {
+        let view = program_data.add_projection_view(display, renderer);
+        let mut view = view.borrow_mut();
+        view.set_rotation_comp(None);
+        // otherwise the disk controls are focused
+        if !adjust_manually { view.request_focus(); }
+    }
+
+    start_clean_session(program_data);
+
+    if let Some(message) = confirmation.skipped_files_message {
+        gui_state.message_box = Some(gui::MessageBox{ title: "Skipped files".to_string(), message });
+        ui.open_popup("Skipped files");
+    } else if auto_view && !program_data.base().borrow().config.auto_projection_view_hint_shown().unwrap_or(false) {
+        program_data.base().borrow_mut().config.set_auto_projection_view_hint_shown(true);
+        gui_state.message_box = Some(gui::MessageBox{
+            title: AUTO_PROJECTION_VIEW_HINT_TITLE.to_string(),
+            message: AUTO_PROJECTION_VIEW_HINT.to_string()
+        });
+        ui.open_popup(AUTO_PROJECTION_VIEW_HINT_TITLE);
+    }
+}
+
+/// Starts tracking unsaved changes anew after images have been loaded; changes made by the loading itself (e.g.,
+/// the detected disk) do not count.
+fn start_clean_session(program_data: &mut ProgramData) {
+    if let Some(source_view) = program_data.source_view_mut() {
+        source_view.commit_src_params();
+        source_view.take_src_params_changed();
+    }
+    for view in program_data.projection_views().borrow().iter() { view.borrow_mut().take_settings_changed(); }
+    program_data.session_state_mut().mark_clean();
+}
+
+/// Returns true if a projection view is to be opened automatically after loading images (only for the first
+/// loaded images, and only if no projection views exist yet, e.g., recreated by other means).
+fn auto_projection_view_needed(enabled: bool, first_load: bool, num_projection_views: usize) -> bool {
+    enabled && first_load && num_projection_views == 0
+}
+
+/// Stores information about successfully loaded images (`stamps`; the first one contains `disk`) in the load cache.
+fn update_load_cache(
+    cache: &mut load_cache::LoadCache,
+    stamps: &[Option<load_cache::FileStamp>],
+    options: load_options_dialog::LoadOptions,
+    disk: worker::DiskInfo
+) {
+    if let Some(Some(first)) = stamps.first() {
+        cache.set_disk(first, options.binning, options.interpretation, disk);
+    }
+
+    let all_stamps: Option<Vec<_>> = stamps.iter().cloned().collect();
+    cache.set_loaded(all_stamps.map(|stamps| load_cache::LoadedSet{
+        stamps,
+        binning: options.binning,
+        interpretation: options.interpretation
+    }));
+
+    if let Err(e) = cache.save() {
+        logging::log_error!("Error saving load cache: {}.", e);
+    }
+}
+
+/// Returns message listing files skipped during loading.
+fn skipped_files_message(paths: &[std::path::PathBuf], skipped: &[worker::SkippedFrame]) -> String {
+    const MAX_LISTED: usize = 20;
+
+    let mut message = format!("{} of {} files could not be loaded and were skipped:\n", skipped.len(), paths.len());
+    for s in skipped.iter().take(MAX_LISTED) {
+        message += &format!("\n{}: {}", paths[s.index].to_string_lossy(), s.error);
+    }
+    if skipped.len() > MAX_LISTED { message += &format!("\n(and {} more)", skipped.len() - MAX_LISTED); }
+
+    message
+}
+
+fn handle_export_result(ui: &imgui::Ui, gui_state: &mut gui::GuiState, program_data: &ProgramData) {
+    // the lock is released by `poll_export_end`
+    let view_id = program_data.export_lock().borrow().as_ref().map(|lock| lock.projection_view_id());
+    let end = match data::poll_export_end(program_data.export_result(), program_data.export_lock()) {
+        Some(end) => end,
+        None => return
+    };
+
+    let succeeded = matches!(
+        end,
+        data::ExportEnd::Message(worker::ProjectionResultMsg::Finished)
+            | data::ExportEnd::Message(worker::ProjectionResultMsg::FinishedWithSkippedFrames(_))
+            | data::ExportEnd::Message(worker::ProjectionResultMsg::PostExportCommandFailed(_))
+    );
+    let exporting_view = program_data.projection_views().borrow().iter()
+        .find(|view| Some(view.borrow().id()) == view_id)
+        .cloned();
+    if let Some(view) = exporting_view { view.borrow_mut().on_export_ended(succeeded); }
+
+    let message = match end {
+        data::ExportEnd::Message(worker::ProjectionResultMsg::ContextLost) => {
+            logging::log_error!("Export failed: the GPU context has been lost.");
+            ui.open_popup(CONTEXT_LOST_TITLE);
+            None
+        },
+        data::ExportEnd::Message(msg) => match msg {
+            worker::ProjectionResultMsg::Error(e) => Some(("Error", format!("Export failed: {}.", e))),
+            worker::ProjectionResultMsg::PostExportCommandFailed(log) => Some(("Error", log)),
+            worker::ProjectionResultMsg::FinishedWithSkippedFrames(warnings) => Some(("Warning", format!(
+                "Export finished, but {} file(s) could not be saved:\n\n{}", warnings.len(), warnings.join("\n")
+            ))),
+            worker::ProjectionResultMsg::Cancelled(num_exported, num_total) => Some(("Cancelled", format!(
+                "Export cancelled after {} of {} frames.", num_exported, num_total
+            ))),
+            _ => {
+                logging::log_info!("Export finished.");
+                None
+            }
+        },
+        data::ExportEnd::WorkerDisconnected => Some(("Error", "Export failed: the worker has stopped.".to_string()))
+    };
+    if let Some((title, message)) = message {
+        match title {
+            "Error" => logging::log_error!("{}", message),
+            "Warning" => logging::log_warning!("{}", message),
+            _ => logging::log_info!("{}", message)
+        }
+        gui_state.message_box = Some(gui::MessageBox{ title: title.to_string(), message });
+        ui.open_popup(title);
+    }
+}
+
+/// Offers reloading of the images after their textures have been lost (see `ProjectionResultMsg::ContextLost`).
+fn handle_context_lost_dialog(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    program_data: &mut ProgramData,
+    display: &glium::Display
+) {
+    let mut reload = false;
+    let can_reload = program_data.last_load().is_some()
+        && program_data.image_loading().is_none()
+        && program_data.long_task_dialog().borrow().is_none();
+
+    gui::modal::modal(
+        ui,
+        &mut program_data.base().borrow_mut().config,
+        CONTEXT_LOST_TITLE,
+        gui::modal::KeyBindings::all(),
+        |key_action, _| {
+            ui.text_wrapped(
+                "Export failed: the loaded images are no longer available to the GPU (the graphics driver \
+                may have been reset). Load the images again and repeat the export."
+            );
+            ui.separator();
+
+            let token = ui.begin_disabled(!can_reload);
+            if gui::modal::default_button(ui, "Reload images")
+                || (can_reload && key_action == gui::modal::KeyAction::Accept) {
+                reload = true;
+                ui.close_current_popup();
+            }
+            token.end();
+            ui.same_line();
+            if ui.button("Close") || key_action == gui::modal::KeyAction::Cancel { ui.close_current_popup(); }
+        }
+    );
+
+    if reload {
+        let (mut pending, options) = program_data.last_load().clone().unwrap();
+        // the files may have changed in the meantime
+        pending.stamps = pending.paths.iter().map(|path| load_cache::FileStamp::of(path)).collect();
+        start_image_loading(ui, gui_state, display, program_data, pending, options);
+    }
+}
+
+/// Offers creating a globe view centered on the location double-clicked in a projection view.
+fn handle_no_globe_view_dialog(
+    ui: &imgui::Ui,
+    program_data: &mut ProgramData,
+    renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>,
+    display: &glium::Display
+) {
+    // `Some(create)` once the user has decided
+    let mut decision = None;
+
+    gui::modal::modal(
+        ui,
+        &mut program_data.base().borrow_mut().config,
+        NO_GLOBE_VIEW_TITLE,
+        gui::modal::KeyBindings::all(),
+        |key_action, _| {
+            ui.text("No globe view is open. Create one centered on the selected location?");
+            ui.separator();
+
+            if gui::modal::default_button(ui, "Create globe view") || key_action == gui::modal::KeyAction::Accept {
+                decision = Some(true);
+                ui.close_current_popup();
+            }
+            ui.same_line();
+            if ui.button("Cancel") || key_action == gui::modal::KeyAction::Cancel {
+                decision = Some(false);
+                ui.close_current_popup();
+            }
+        }
+    );
+
+    if let Some(create) = decision {
+        let target = program_data.pending_globe_target_mut().take();
+        if let (true, Some(target)) = (create, target) {
+            program_data.add_globe_view(display, renderer).borrow_mut().navigate_to(&target);
+        }
+    }
+}
+
+/// Stacks all frames into the average frame or (if `group_size` is set) replaces the frames with stacks
+/// of their consecutive groups.
+fn start_frame_stacking(program_data: &mut ProgramData, group_size: Option<usize>) {
+    if program_data.frame_stacking().is_some() || program_data.long_task_dialog().borrow().is_some() { return; }
+
+    let source_view = program_data.source_view().as_ref().unwrap();
+    let sz = source_view.image_size();
+
+    let (result_sender, result_receiver) = crossbeam::channel::unbounded();
+    let (progress_sender, progress_receiver) = crossbeam::channel::bounded(1);
+
+    program_data.bg_task_sender().send(worker::MainToWorkerMsg::StackFrames(worker::StackFrames{
+        image_size: glium::texture::Dimensions::Texture2d{ width: sz[0], height: sz[1] },
+        source_texture_ids: source_view.texture_ids(),
+        source_bit_depth: source_view.bit_depth(),
+        group_size: group_size.unwrap_or_else(|| source_view.num_images()),
+        sigma_clip: if source_view.stacking_sigma_clip() { Some(STACKING_SIGMA_CLIP_KAPPA) } else { None },
+        progress_sender,
+        result_sender
+    })).unwrap();
+
+    *program_data.frame_stacking_mut() =
+        Some(projection::data::FrameStacking{ group_size, receiver: result_receiver });
+
+    *program_data.long_task_dialog().borrow_mut() =
+        Some(LongTaskDialog::new("Stacking frames".to_string(), "".to_string(), progress_receiver));
+}
+
+fn handle_frame_stacking(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    program_data: &mut ProgramData,
+    display: &glium::Display
+) {
+    let mut finished = false;
+    let mut result: Option<Vec<ga_image::Image>> = None;
+
+    match program_data.frame_stacking() {
+        None => (),
+        Some(stacking) => match stacking.receiver.try_recv() {
+            Ok(msg) => {
+                finished = true;
+                match msg {
+                    worker::StackFramesResultMsg::Success(images) => result = Some(images),
+                    worker::StackFramesResultMsg::Cancelled => ()
+                }
+            },
+
+            Err(e) => match e {
+                TryRecvError::Empty => (),
+                _ => panic!("unexpected error {}", e)
+            }
+        }
+    }
+
+    if let Some(mut images) = result {
+        let group_size = program_data.frame_stacking().as_ref().unwrap().group_size;
+        let applied = match program_data.source_view_mut() {
+            None => Ok(()),
+            Some(source_view) => match group_size {
+                None => {
+                    let image = images.pop().unwrap();
+                    // the kept image is used for disk detection and value readout, which expect RGB8
+                    crate::data::create_texture_from_image(&image, display).map(|texture| source_view.set_avg_image(
+                        Rc::new(texture), image.convert_pix_fmt(ga_image::PixelFormat::RGB8, None)
+                    ))
+                },
+
+                Some(group_size) => images.iter()
+                    .map(|image| crate::data::create_texture_from_image(image, display).map(Rc::new))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(|textures| source_view.set_stacked_images(textures, group_size))
+            }
+        };
+
+        if let Err(e) = applied {
+            logging::log_error!("Could not use the stacked frames: {}.", e);
+            gui_state.message_box = Some(gui::MessageBox{
+                title: "Error".to_string(),
+                message: format!("Could not use the stacked frames: {}.", e)
+            });
+            ui.open_popup("Error");
+        }
+    }
+
+    if finished { *program_data.frame_stacking_mut() = None; }
+}
+
+fn start_brightness_measurement(program_data: &mut ProgramData) {
+    if program_data.brightness_measurement().is_some() || program_data.long_task_dialog().borrow().is_some() { return; }
+
+    let source_view = program_data.source_view().as_ref().unwrap();
+    let sz = source_view.image_size();
+
+    let (result_sender, result_receiver) = crossbeam::channel::unbounded();
+    let (progress_sender, progress_receiver) = crossbeam::channel::bounded(1);
+
+    program_data.bg_task_sender().send(worker::MainToWorkerMsg::MeasureBrightness(worker::MeasureBrightness{
+        image_size: glium::texture::Dimensions::Texture2d{ width: sz[0], height: sz[1] },
+        source_texture_ids: source_view.texture_ids(),
+        disk_center: source_view.disk_center(),
+        disk_diameter: source_view.disk_diameter(),
+        progress_sender,
+        result_sender
+    })).unwrap();
+
+    *program_data.brightness_measurement_mut() =
+        Some(projection::data::BrightnessMeasurement{ receiver: result_receiver });
+
+    *program_data.long_task_dialog().borrow_mut() =
+        Some(LongTaskDialog::new("Measuring brightness".to_string(), "".to_string(), progress_receiver));
+}
+
+fn handle_brightness_measurement(program_data: &mut ProgramData) {
+    let mut finished = false;
+    let mut result: Option<Vec<f32>> = None;
+
+    match program_data.brightness_measurement() {
+        None => (),
+        Some(measurement) => match measurement.receiver.try_recv() {
+            Ok(msg) => {
+                finished = true;
+                match msg {
+                    worker::MeasureBrightnessResultMsg::Success(brightness) => result = Some(brightness),
+                    worker::MeasureBrightnessResultMsg::Cancelled => ()
+                }
+            },
+
+            Err(e) => match e {
+                TryRecvError::Empty => (),
+                _ => panic!("unexpected error {}", e)
+            }
+        }
+    }
+
+    if let Some(brightness) = result {
+        if let Some(source_view) = program_data.source_view_mut() {
+            // frames may have been re-loaded in the meantime
+            if brightness.len() == source_view.num_images() { source_view.set_measured_brightness(brightness); }
+        }
+    }
+
+    if finished { *program_data.brightness_measurement_mut() = None; }
+}
+
+/// Informs the user (once) if the worker's context supports drastically smaller textures than the main one.
+fn report_worker_texture_limit(ui: &imgui::Ui, gui_state: &mut gui::GuiState, program_data: &ProgramData) {
+    if gui_state.message_box.is_some() { return; }
+
+    if let Some((main, worker)) = program_data.texture_limits().take_report() {
+        logging::log_warning!("Worker context's max. texture size ({}) is below the main one ({}).", worker, main);
+        gui_state.message_box = Some(gui::MessageBox{
+            title: "Limited texture size".to_string(),
+            message: format!(
+                "The background rendering context supports textures of at most {0}x{0} pixels (the display: \
+                {1}x{1}); it may be provided by another GPU. Exports will read the source images from files; \
+                larger images and maps cannot be loaded or exported.",
+                worker, main
+            )
+        });
+        ui.open_popup("Limited texture size");
+    }
+}
+
+fn store_snapshot(program_data: &mut ProgramData, slot: snapshots::Slot) {
+    let view = program_data.active_projection_view().and_then(|id|
+        program_data.projection_views().borrow().iter()
+            .find(|view| view.borrow().id() == id)
+            .map(|view| (id, view.borrow().projection_settings()))
+    );
+    if let Some(source_view) = program_data.source_view_mut() {
+        let snapshot = snapshots::Snapshot{ src_params: source_view.src_params().clone(), view };
+        source_view.snapshots_mut().store(slot, snapshot);
+    }
+}
+
+/// Applies the other A/B snapshot: source parameters in bulk (subscribers are notified once) and the stored
+/// projection settings to their view (if still open).
+fn toggle_snapshot(program_data: &mut ProgramData) {
+    let toggled = program_data.source_view_mut().as_mut().and_then(|view| view.snapshots_mut().toggle().cloned());
+    let snapshot = match toggled {
+        Some(snapshot) => snapshot,
+        None => return
+    };
+    if let Some((id, settings)) = &snapshot.view {
+        if let Some(view) = program_data.projection_views().borrow().iter().find(|view| view.borrow().id() == *id) {
+            view.borrow_mut().apply_projection_settings(settings);
+        }
+    }
+    program_data.source_view_mut().as_mut().unwrap().apply_params(snapshot.src_params);
+}
+
+fn start_limb_measurement(program_data: &mut ProgramData) {
+    if program_data.limb_measurement().is_some() || program_data.long_task_dialog().borrow().is_some() { return; }
+
+    let source_view = program_data.source_view().as_ref().unwrap();
+    let sz = source_view.image_size();
+
+    let (result_sender, result_receiver) = crossbeam::channel::unbounded();
+    let (progress_sender, progress_receiver) = crossbeam::channel::bounded(1);
+
+    program_data.bg_task_sender().send(worker::MainToWorkerMsg::MeasureLimbAngles(worker::MeasureLimbAngles{
+        image_size: glium::texture::Dimensions::Texture2d{ width: sz[0], height: sz[1] },
+        source_texture_ids: source_view.texture_ids(),
+        disk_center: source_view.disk_center(),
+        disk_diameter: source_view.disk_diameter(),
+        image_mirror: source_view.src_params().image_mirror(),
+        progress_sender,
+        result_sender
+    })).unwrap();
+
+    *program_data.limb_measurement_mut() = Some(projection::data::LimbMeasurement{ receiver: result_receiver });
+
+    *program_data.long_task_dialog().borrow_mut() =
+        Some(LongTaskDialog::new("Measuring limb orientation".to_string(), "".to_string(), progress_receiver));
+}
+
+fn handle_limb_measurement(program_data: &mut ProgramData) {
+    let mut finished = false;
+    let mut result: Option<Vec<Option<cgmath::Deg<f32>>>> = None;
+
+    match program_data.limb_measurement() {
+        None => (),
+        Some(measurement) => match measurement.receiver.try_recv() {
+            Ok(msg) => {
+                finished = true;
+                match msg {
+                    worker::MeasureLimbAnglesResultMsg::Success(angles) => result = Some(angles),
+                    worker::MeasureLimbAnglesResultMsg::Cancelled => ()
+                }
+            },
+
+            Err(e) => match e {
+                TryRecvError::Empty => (),
+                _ => panic!("unexpected error {}", e)
+            }
+        }
+    }
+
+    if let Some(angles) = result {
+        let num_measured = angles.iter().filter(|angle| angle.is_some()).count();
+        logging::log_info!("Limb orientation measured in {} of {} frames.", num_measured, angles.len());
+        if let Some(source_view) = program_data.source_view_mut() {
+            // frames may have been re-loaded in the meantime
+            if angles.len() == source_view.num_images() { source_view.set_measured_limb_angles(angles); }
+        }
+    }
+
+    if finished { *program_data.limb_measurement_mut() = None; }
+}
+
 fn handle_load_images(
     ui: &imgui::Ui,
     gui_state: &mut gui::GuiState,
-    display: &glium::Display,
     program_data: &mut ProgramData
 ) {
     assert!(program_data.image_loading().is_none());
 
-    let max_texture_size = display.get_capabilities().max_texture_size as u32;
-
-    let mut paths = native_dialog::FileDialog::new()
-        .set_location(&(match program_data.base().borrow().config.load_path() { None => "".into(), Some(p) => p }))
-        .add_filter("image files (BMP, PNG, TIFF)", &["bmp", "png", "tif", "tiff"])
+    let location = gui::file_dialog::initial_location(program_data.base().borrow().config.load_path().as_deref());
+    let mut paths = match gui::file_dialog::checked(ui, gui_state, native_dialog::FileDialog::new()
+        .set_location(&location)
+        .add_filter("image files (BMP, PNG, TIFF, FITS)", &["bmp", "png", "tif", "tiff", "fit", "fits", "fts"])
         .add_filter("BMP", &["bmp"])
         .add_filter("PNG", &["png"])
         .add_filter("TIFF", &["tif", "tiff"])
+        .add_filter("FITS", &fits::EXTENSIONS)
         .add_filter("all files", &["*"])
         .show_open_multiple_file()
-        .unwrap();
+    ) {
+        Some(paths) => paths,
+        None => return
+    };
 
     if !paths.is_empty() {
         paths.sort();
+        let stamps: Vec<_> = paths.iter().map(|path| load_cache::FileStamp::of(path)).collect();
 
-        // TODO: handle error gracefully
-        // TODO: handle different pixel formats and bit depths
-        let (width, height) = match image_utils::get_metadata(&paths[0]) {
-            Ok((width, height, _)) => (width, height),
+        // mono images are expanded to RGB; images of other bit depths than the first one's are rejected when loaded
+        let probed = probe_dimensions(program_data.load_cache_mut(), &paths[0], stamps[0].as_ref())
+            .and_then(|dimensions| Ok((dimensions, image_utils::get_bit_depth(&paths[0])?)));
+        let ([width, height], bit_depth) = match probed {
+            Ok(probed) => probed,
 
             Err(e) => {
                 gui_state.message_box = Some(gui::MessageBox{
@@ -309,39 +1633,307 @@ fn handle_load_images(
             }
         };
 
-        if width > max_texture_size || height > max_texture_size {
-            panic!("image too big"); //TODO: handle gracefully
+        program_data.base().borrow_mut().config.set_load_path(paths[0].parent().unwrap().to_str().unwrap()); //TODO: handle non-UTF-8 paths
+
+        let confirm = {
+            let mut dialog = program_data.load_options_dialog().borrow_mut();
+            dialog.set_selection(&paths);
+            dialog.require_confirmation(paths.len(), [width, height], bit_depth)
+        };
+        *program_data.pending_load_mut() = Some(projection::data::PendingLoad{
+            paths,
+            stamps,
+            dimensions: [width, height],
+            bit_depth
+        });
+        if confirm { ui.open_popup(program_data.load_options_dialog().borrow().title()); }
+    }
+}
+
+/// Loads frames of a SER video like a list of images; the video's path stands for each frame's file path.
+fn handle_load_video(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    program_data: &mut ProgramData
+) {
+    assert!(program_data.image_loading().is_none());
+
+    let location = gui::file_dialog::initial_location(program_data.base().borrow().config.load_path().as_deref());
+    let path = match gui::file_dialog::checked(ui, gui_state, native_dialog::FileDialog::new()
+        .set_location(&location)
+        .add_filter("SER videos", &["ser"])
+        .add_filter("all files", &["*"])
+        .show_open_single_file()
+    ) {
+        Some(Some(path)) => path,
+        _ => return
+    };
+
+    let probed = img_seq::open_ser_video(&path).and_then(|mut video| {
+        if video.num_images() == 0 { return Err("the video contains no frames".into()); }
+        let first_frame = video.get_image(0)?;
+        Ok((
+            video.num_images(),
+            [first_frame.width(), first_frame.height()],
+            image_utils::BitDepth::of(first_frame.pixel_format())
+        ))
+    });
+    let (num_frames, dimensions, bit_depth) = match probed {
+        Ok(probed) => probed,
+        Err(e) => {
+            gui_state.message_box = Some(gui::MessageBox{
+                title: "Error".to_string(),
+                message: format!("Could not open {}: {}.", path.to_string_lossy(), e)
+            });
+            ui.open_popup("Error");
+            return;
         }
+    };
+
+    //TODO: handle non-UTF-8 paths
+    program_data.base().borrow_mut().config.set_load_path(path.parent().unwrap().to_str().unwrap());
 
-        let textures: Vec<_> = (0..paths.len()).map(|_| Rc::new(glium::Texture2d::empty_with_format(
-                display,
-                glium::texture::UncompressedFloatFormat::U8U8U8,
-                glium::texture::MipmapsOption::NoMipmap,
-                width,
-                height
-            ).unwrap())
-        ).collect();
+    let confirm = {
+        let mut dialog = program_data.load_options_dialog().borrow_mut();
+        dialog.set_selection(std::slice::from_ref(&path));
+        dialog.require_confirmation(num_frames, dimensions, bit_depth)
+    };
+    *program_data.pending_load_mut() = Some(projection::data::PendingLoad{
+        paths: vec![path; num_frames],
+        // frames share the video's stamp, so they are not cached individually
+        stamps: vec![None; num_frames],
+        dimensions,
+        bit_depth
+    });
+    if confirm { ui.open_popup(program_data.load_options_dialog().borrow().title()); }
+}
 
-        let (result_sender, result_receiver) = crossbeam::channel::unbounded();
+/// Returns dimensions of the image in `path`, using the load cache if possible.
+fn probe_dimensions(
+    cache: &mut load_cache::LoadCache,
+    path: &std::path::Path,
+    stamp: Option<&load_cache::FileStamp>
+) -> Result<[u32; 2], Box<dyn std::error::Error>> {
+    if let Some(dimensions) = stamp.and_then(|stamp| cache.dimensions(stamp)) { return Ok(dimensions); }
 
-        let (progress_sender, progress_receiver) = crossbeam::channel::bounded(1);
+    let (width, height, _) = image_utils::get_metadata(path)?;
+    if let Some(stamp) = stamp { cache.set_dimensions(stamp, [width, height]); }
 
-        program_data.bg_task_sender().send(worker::MainToWorkerMsg::LoadImages(worker::LoadImages{
-            dimensions: [width, height],
-            pixel_format: PixelFormat::RGB8,
-            items: textures.iter().map(|t| t.get_id())
-                .zip(paths.iter())
-                .map(|(id, path)| (id, path.clone()))
-                .collect(),
-            progress_sender,
-            result_sender
-        })).unwrap();
+    Ok([width, height])
+}
 
-        *program_data.image_loading_mut() = Some(projection::data::ImageLoading{ textures, receiver: result_receiver });
+/// Returns `true` if the pending files, loaded with `options`, are the (unchanged) files of the source view.
+fn can_reuse_loaded_images(program_data: &ProgramData, options: load_options_dialog::LoadOptions) -> bool {
+    let pending = match program_data.pending_load() {
+        None => return false,
+        Some(pending) => pending
+    };
+    match program_data.source_view() {
+        None => return false,
+        // the loaded images have been replaced with their stacks
+        Some(source_view) => if source_view.stacked_group_size().is_some() { return false; }
+    }
 
-        *program_data.long_task_dialog().borrow_mut() =
-            Some(LongTaskDialog::new("Image Loading".to_string(), "".to_string(), progress_receiver));
+    let stamps: Vec<_> = pending.stamps.iter().step_by(options.decimation as usize).cloned().collect();
 
-        program_data.base().borrow_mut().config.set_load_path(paths[0].parent().unwrap().to_str().unwrap()); //TODO: handle non-UTF-8 paths
+    program_data.load_cache().is_reusable(&stamps, options.binning, options.interpretation)
+}
+
+/// Shows the load options dialog for files selected via `handle_load_images` and starts loading once accepted.
+fn handle_pending_load(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    program_data: &mut ProgramData,
+    display: &glium::Display
+) {
+    let (num_files, dimensions, bit_depth) = match program_data.pending_load() {
+        None => return,
+        Some(pending) => (pending.paths.len(), pending.dimensions, pending.bit_depth)
+    };
+
+    let result = if !program_data.load_options_dialog().borrow().confirmation_required() {
+        load_options_dialog::LoadOptionsResult::Accepted
+    } else {
+        load_options_dialog::handle_load_options_dialog(
+            ui,
+            &mut program_data.base().borrow_mut().config,
+            &mut program_data.load_options_dialog().borrow_mut(),
+            num_files,
+            dimensions,
+            bit_depth,
+            &|options| can_reuse_loaded_images(program_data, options)
+        )
+    };
+
+    match result {
+        load_options_dialog::LoadOptionsResult::Pending => (),
+
+        load_options_dialog::LoadOptionsResult::Cancelled => *program_data.pending_load_mut() = None,
+
+        load_options_dialog::LoadOptionsResult::Accepted => {
+            let options = program_data.load_options_dialog().borrow().options();
+            let reuse = program_data.load_options_dialog().borrow().reuse_loaded()
+                && can_reuse_loaded_images(program_data, options);
+            let cache_on_disk = program_data.load_options_dialog().borrow().cache_on_disk();
+            let pending = program_data.pending_load_mut().take().unwrap();
+            {
+                let config = &mut program_data.base().borrow_mut().config;
+                config.set_load_decimation(options.decimation);
+                config.set_load_binning(options.binning);
+                config.set_load_interpretation(options.interpretation);
+                config.set_load_skip_failed_frames(options.skip_failed_frames);
+                config.set_vram_budget_mib(program_data.load_options_dialog().borrow().vram_budget_mib());
+                config.set_load_cache_on_disk(cache_on_disk);
+            }
+            program_data.load_cache_mut().set_file_path(
+                if cache_on_disk { Some(load_cache::default_file_path()) } else { None }
+            );
+
+            let reused_disk = if reuse {
+                pending.stamps[0].as_ref().and_then(|stamp| {
+                    program_data.load_cache_mut().disk(stamp, options.binning, options.interpretation)
+                })
+            } else {
+                None
+            };
+
+            match reused_disk {
+                Some(disk) => {
+                    let source_view = program_data.source_view_mut().as_mut().unwrap();
+                    let images = source_view.images().to_vec();
+                    let paths = source_view.file_paths().to_vec();
+                    let bit_depth = source_view.bit_depth();
+                    // the current images have already been validated
+                    source_view.set_images(images, paths, disk.center, disk.diameter, options, bit_depth).unwrap();
+                    start_clean_session(program_data);
+                },
+
+                None => start_image_loading(ui, gui_state, display, program_data, pending, options)
+            }
+        }
+    }
+}
+
+fn start_image_loading(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    display: &glium::Display,
+    program_data: &mut ProgramData,
+    pending: projection::data::PendingLoad,
+    options: load_options_dialog::LoadOptions
+) {
+    if let Err(message) = options.validate(pending.dimensions) {
+        gui_state.message_box = Some(gui::MessageBox{ title: "Error".to_string(), message });
+        ui.open_popup("Error");
+        return;
+    }
+
+    *program_data.last_load_mut() = Some((pending.clone(), options));
+
+    // images are loaded into textures by the worker
+    let max_texture_size = program_data.texture_limits().max_texture_size();
+
+    let is_video = img_seq::is_ser_path(&pending.paths[0]);
+    let frame_indices: Vec<_> = (0..pending.paths.len()).step_by(options.decimation as usize).collect();
+    let paths: Vec<_> = pending.paths.into_iter().step_by(options.decimation as usize).collect();
+    let stamps: Vec<_> = pending.stamps.into_iter().step_by(options.decimation as usize).collect();
+    let first_item_disk = stamps[0].as_ref().and_then(|stamp| {
+        program_data.load_cache_mut().disk(stamp, options.binning, options.interpretation)
+    });
+    let width = pending.dimensions[0] / options.binning;
+    let height = pending.dimensions[1] / options.binning;
+
+    if !texture_limits::fits([width, height], max_texture_size) {
+        gui_state.message_box = Some(gui::MessageBox{
+            title: "Error".to_string(),
+            message: format!(
+                "Images are too large ({} is {}x{} pixels; the maximum is {3}x{3}); select a higher binning.",
+                paths[0].to_string_lossy(), width, height, max_texture_size
+            )
+        });
+        ui.open_popup("Error");
+        return;
+    }
+
+    let textures: Vec<_> = (0..paths.len()).map(|_| Rc::new(glium::Texture2d::empty_with_format(
+            display,
+            pending.bit_depth.texture_format(),
+            glium::texture::MipmapsOption::NoMipmap,
+            width,
+            height
+        ).unwrap())
+    ).collect();
+
+    let (result_sender, result_receiver) = crossbeam::channel::unbounded();
+
+    let (progress_sender, progress_receiver) = crossbeam::channel::bounded(1);
+
+    let cancel = CancelToken::new();
+
+    let texture_ids = textures.iter().map(|t| t.get_id());
+    let items = if is_video {
+        worker::LoadItems::Video(paths[0].clone(), texture_ids.zip(frame_indices).collect())
+    } else {
+        worker::LoadItems::Files(texture_ids.zip(paths.iter().cloned()).collect())
+    };
+
+    logging::log_info!(
+        "Loading {} {} ({}x{}, {}, {:?}).",
+        paths.len(), if is_video { "video frames" } else { "files" }, width, height, pending.bit_depth.name(),
+        options.interpretation
+    );
+    program_data.bg_task_sender().send(worker::MainToWorkerMsg::LoadImages(worker::LoadImages{
+        dimensions: [width, height],
+        binning: options.binning,
+        interpretation: options.interpretation,
+        bit_depth: pending.bit_depth,
+        skip_failed_frames: options.skip_failed_frames,
+        first_item_disk,
+        cancel: cancel.clone(),
+        items,
+        progress_sender,
+        result_sender
+    })).unwrap();
+
+    *program_data.image_loading_mut() = Some(projection::data::ImageLoading{
+        textures,
+        paths,
+        stamps,
+        load_options: options,
+        bit_depth: pending.bit_depth,
+        receiver: result_receiver
+    });
+
+    *program_data.long_task_dialog().borrow_mut() =
+        Some(LongTaskDialog::new("Image Loading".to_string(), "".to_string(), progress_receiver)
+            .with_cancel_token(cancel));
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reduced_motion_disables_all_animations() {
+        let mut gui_state = gui::GuiState::default();
+        for animation in ANIMATIONS {
+            assert!(!(animation.duration)(&gui_state).is_zero(), "{}", animation.name);
+        }
+
+        gui_state.set_reduce_motion(true);
+        for animation in ANIMATIONS {
+            assert!((animation.duration)(&gui_state).is_zero(), "{}", animation.name);
+        }
+    }
+
+    #[test]
+    fn projection_view_is_opened_automatically_only_after_first_load() {
+        assert!(auto_projection_view_needed(true, true, 0));
+        // disabled by the user
+        assert!(!auto_projection_view_needed(false, true, 0));
+        // loading another set of images
+        assert!(!auto_projection_view_needed(true, false, 0));
+        // views already exist
+        assert!(!auto_projection_view_needed(true, true, 1));
+        assert!(!auto_projection_view_needed(true, false, 2));
     }
 }
@@ -0,0 +1,353 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Export of the globe as a 3D model: a UV sphere (Wavefront OBJ with an MTL material) textured with an
+//! equirectangular map.
+//!
+//! The model's Y axis points to the north pole, Z to the central meridian of the rendered frame (texture center).
+
+use cgmath::{Angle, Deg, InnerSpace, Vector3};
+use crate::config::Configuration;
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+use crate::projection::data;
+use ga_image::{Image, PixelFormat};
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+use strum::IntoEnumIterator;
+
+pub const TITLE: &str = "Export 3D model";
+
+/// Spacing of the mesh nodes in longitude and latitude.
+pub const MESH_STEP: Deg<f64> = Deg(2.0);
+
+/// Widths (in pixels) of the texture selectable by the user; the height is half the width.
+const TEXTURE_WIDTHS: [u32; 4] = [1024, 2048, 4096, 8192];
+
+const MATERIAL_NAME: &str = "globe";
+
+/// Filling of the longitudes not covered by the rendered frame (which provides 180° of the map).
+#[derive(Copy, Clone, Debug, PartialEq, strum::EnumIter)]
+pub enum LongitudeFill {
+    Black,
+    /// The available part of the map is repeated.
+    Wrap,
+    /// The edge columns of the available part of the map are extended.
+    ClampEdge
+}
+
+impl LongitudeFill {
+    pub fn name(&self) -> &str {
+        match self {
+            LongitudeFill::Black => "black",
+            LongitudeFill::Wrap => "wrap",
+            LongitudeFill::ClampEdge => "clamp edge"
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelExportSettings {
+    pub texture_width: u32,
+    pub fill: LongitudeFill
+}
+
+impl Default for ModelExportSettings {
+    fn default() -> ModelExportSettings {
+        ModelExportSettings{ texture_width: TEXTURE_WIDTHS[1], fill: LongitudeFill::Black }
+    }
+}
+
+/// Vertex attributes are indexed together (i-th position, normal and UV belong to the same vertex).
+#[derive(Default)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    /// U increases eastwards with longitude (0 at -180°), V northwards (0 at the south pole).
+    pub uvs: Vec<[f32; 2]>,
+    pub triangles: Vec<[u32; 3]>
+}
+
+impl Mesh {
+    /// Adds vertex at the given longitude and latitude (in degrees) of a globe with unit equatorial radius.
+    fn add_vertex(&mut self, lonlat: [f32; 2], flattening: f32) -> u32 {
+        let (lon, lat) = (Deg(lonlat[0]), Deg(lonlat[1]));
+        let sphere_pos = Vector3{ x: lat.cos() * lon.sin(), y: lat.sin(), z: lat.cos() * lon.cos() };
+        let polar_radius = 1.0 - flattening;
+
+        self.positions.push([sphere_pos.x, polar_radius * sphere_pos.y, sphere_pos.z]);
+        // gradient of x² + (y/b)² + z² at the vertex
+        let normal = Vector3{ y: sphere_pos.y / polar_radius, ..sphere_pos }.normalize();
+        self.normals.push([normal.x, normal.y, normal.z]);
+        self.uvs.push([(lon.0 + 180.0) / 360.0, (lat.0 + 90.0) / 180.0]);
+
+        (self.positions.len() - 1) as u32
+    }
+
+    fn faces_outwards(&self, triangle: [u32; 3]) -> bool {
+        let [a, b, c] = triangle.map(|i| Vector3::from(self.positions[i as usize]));
+        (b - a).cross(c - a).dot(a + b + c) > 0.0
+    }
+}
+
+/// Returns the globe mesh (see `data::globe_mesh_data`) with positions and normals of a planet with `flattening`.
+///
+/// The zero-area triangles joining the -180° and 180° columns are omitted; each polar triangle gets its own pole
+/// vertex, with U in the middle of the triangle's longitudes (so that the texture is not skewed near the poles).
+pub fn globe_model(step: Deg<f64>, flattening: f32) -> Mesh {
+    let (vertices, indices) = data::globe_mesh_data(step);
+    let num_grid_vertices = vertices.len() - 2; // the poles are last
+
+    let mut mesh = Mesh::default();
+    for vertex in &vertices[..num_grid_vertices] { mesh.add_vertex(vertex.lonlat(), flattening); }
+
+    let coincide = |a: [f32; 2], b: [f32; 2]| a[1] == b[1] && (a[0] - b[0]).abs() % 360.0 == 0.0;
+
+    for triangle in indices.chunks_exact(3) {
+        let grid_lonlats: Vec<[f32; 2]> = triangle.iter()
+            .filter(|i| (**i as usize) < num_grid_vertices)
+            .map(|i| vertices[*i as usize].lonlat())
+            .collect();
+
+        let degenerate = (0..grid_lonlats.len()).any(|i| (i + 1..grid_lonlats.len()).any(|j| {
+            coincide(grid_lonlats[i], grid_lonlats[j])
+        }));
+        if degenerate { continue; }
+
+        let mut mesh_triangle = [0u32; 3];
+        for (dest, index) in mesh_triangle.iter_mut().zip(triangle) {
+            *dest = if (*index as usize) < num_grid_vertices {
+                *index
+            } else {
+                let pole_lat = vertices[*index as usize].lonlat()[1];
+                let mean_lon = grid_lonlats.iter().map(|lonlat| lonlat[0]).sum::<f32>() / grid_lonlats.len() as f32;
+                mesh.add_vertex([mean_lon, pole_lat], flattening)
+            };
+        }
+        // the source mesh's winding is not consistent; exported faces are counter-clockwise as seen from outside
+        if !mesh.faces_outwards(mesh_triangle) { mesh_triangle.swap(1, 2); }
+        mesh.triangles.push(mesh_triangle);
+    }
+
+    mesh
+}
+
+pub fn write_obj<W: Write>(writer: &mut W, mesh: &Mesh, material_library: &str) -> std::io::Result<()> {
+    writeln!(writer, "# globe exported by Vislumino")?;
+    writeln!(writer, "mtllib {}", material_library)?;
+    writeln!(writer, "o globe")?;
+
+    for p in &mesh.positions { writeln!(writer, "v {} {} {}", p[0], p[1], p[2])?; }
+    for uv in &mesh.uvs { writeln!(writer, "vt {} {}", uv[0], uv[1])?; }
+    for n in &mesh.normals { writeln!(writer, "vn {} {} {}", n[0], n[1], n[2])?; }
+
+    writeln!(writer, "usemtl {}", MATERIAL_NAME)?;
+    writeln!(writer, "s 1")?;
+    for triangle in &mesh.triangles {
+        // indices in OBJ start from 1
+        let [a, b, c] = triangle.map(|i| i + 1);
+        writeln!(writer, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}", a = a, b = b, c = c)?;
+    }
+
+    Ok(())
+}
+
+pub fn material_library(texture_file_name: &str) -> String {
+    format!(
+        "newmtl {}\nKa 1 1 1\nKd 1 1 1\nKs 0 0 0\nd 1\nillum 1\nmap_Kd {}\n",
+        MATERIAL_NAME, texture_file_name
+    )
+}
+
+/// Returns a 360°-wide map (longitude increasing rightwards) containing `hemisphere` (a 180°-wide projection) in
+/// the middle; the remaining longitudes are filled according to `fill`.
+pub fn full_texture(hemisphere: &Image, fill: LongitudeFill) -> Image {
+    assert!(hemisphere.pixel_format() == PixelFormat::RGB8);
+
+    let width = hemisphere.width() as i64;
+    let mut texture = Image::new(2 * hemisphere.width(), hemisphere.height(), None, PixelFormat::RGB8, None, true);
+    let offset = width / 2;
+    for y in 0..hemisphere.height() {
+        let src_line = hemisphere.line::<u8>(y);
+        let dest_line = texture.line_mut::<u8>(y);
+        for x in 0..2 * width {
+            let src_x = match fill {
+                LongitudeFill::Black => Some(x - offset).filter(|src_x| (0..width).contains(src_x)),
+                LongitudeFill::Wrap => Some((x - offset).rem_euclid(width)),
+                LongitudeFill::ClampEdge => Some((x - offset).max(0).min(width - 1))
+            };
+            if let Some(src_x) = src_x {
+                let (src, dest) = (3 * src_x as usize, 3 * x as usize);
+                dest_line[dest..dest + 3].copy_from_slice(&src_line[src..src + 3]);
+            }
+        }
+    }
+
+    texture
+}
+
+/// Describes which part of the texture contains real data.
+pub fn data_note(frame_idx: usize, fill: LongitudeFill) -> String {
+    format!(
+        "The texture contains real data between longitudes -90° and +90° from the central meridian of frame {} \
+        (the middle half of the texture); the remaining longitudes are filled ({}).",
+        frame_idx + 1,
+        fill.name()
+    )
+}
+
+/// Writes `obj_path` and next to it the material library and texture (with extensions "mtl" and "png").
+pub fn export_model(obj_path: &Path, mesh: &Mesh, texture: &Image) -> Result<(), Box<dyn Error>> {
+    let mtl_path = obj_path.with_extension("mtl");
+    let texture_path = obj_path.with_extension("png");
+    let file_name = |path: &Path| path.file_name().unwrap().to_string_lossy().to_string();
+
+    let mut obj = std::io::BufWriter::new(std::fs::File::create(obj_path)?);
+    write_obj(&mut obj, mesh, &file_name(&mtl_path))?;
+    obj.flush()?;
+
+    std::fs::write(&mtl_path, material_library(&file_name(&texture_path)))?;
+
+    image::save_buffer(
+        &texture_path, texture.raw_pixels(), texture.width(), texture.height(), image::ColorType::Rgb8
+    )?;
+
+    Ok(())
+}
+
+/// The dialog has to be opened beforehand with `ui.open_popup(TITLE)`; returns `true` if the user wants to export.
+pub fn handle_model_export_dialog(
+    ui: &imgui::Ui,
+    config: &mut Configuration,
+    settings: &mut ModelExportSettings
+) -> bool {
+    let mut result = false;
+
+    modal::modal(ui, config, TITLE, KeyBindings::all(), |key_action, _| {
+        ui.text("Texture width:");
+        for width in TEXTURE_WIDTHS {
+            ui.same_line();
+            let selected = settings.texture_width == width;
+            if ui.radio_button_bool(format!("{}##model-texture-width-{}", width, width), selected) {
+                settings.texture_width = width;
+            }
+        }
+
+        ui.text("Longitudes without data:");
+        for fill in LongitudeFill::iter() {
+            ui.same_line();
+            if ui.radio_button_bool(fill.name(), settings.fill == fill) { settings.fill = fill; }
+        }
+        ui.text_disabled("The displayed frame provides 180° of longitude.");
+
+        ui.separator();
+        if modal::default_button(ui, "Export...") || key_action == KeyAction::Accept {
+            result = true;
+            ui.close_current_popup();
+        }
+        ui.same_line();
+        if ui.button("Cancel") || key_action == KeyAction::Cancel { ui.close_current_popup(); }
+    });
+
+    result
+}
+
+mod tests {
+    use super::*;
+
+    const STEP: Deg<f64> = Deg(30.0);
+
+    #[test]
+    fn mesh_has_expected_counts() {
+        let mesh = globe_model(STEP, 0.0);
+
+        // 13 columns (-180°..180°) × 5 rows (-60°..60°); 12 non-degenerate triangles per cap, each with own pole
+        assert_eq!(13 * 5 + 2 * 12, mesh.positions.len());
+        assert_eq!(mesh.positions.len(), mesh.normals.len());
+        assert_eq!(mesh.positions.len(), mesh.uvs.len());
+        // 12 quads per row gap (4 gaps) and the caps
+        assert_eq!(12 * 4 * 2 + 2 * 12, mesh.triangles.len());
+        assert!(mesh.triangles.iter().flatten().all(|i| (*i as usize) < mesh.positions.len()));
+    }
+
+    #[test]
+    fn uvs_are_within_texture() {
+        let mesh = globe_model(MESH_STEP, 0.0);
+        assert!(mesh.uvs.iter().flatten().all(|value| (0.0..=1.0).contains(value)));
+
+        // the central meridian (facing +Z) is in the middle of the texture
+        let (idx, _) = mesh.positions.iter().enumerate()
+            .max_by(|(_, a), (_, b)| a[2].partial_cmp(&b[2]).unwrap())
+            .unwrap();
+        assert!((mesh.uvs[idx][0] - 0.5).abs() < 1.0e-6);
+        assert!((mesh.uvs[idx][1] - 0.5).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn flattening_is_applied() {
+        const FLATTENING: f32 = 0.065;
+        let mesh = globe_model(STEP, FLATTENING);
+
+        let max_y = mesh.positions.iter().map(|p| p[1]).fold(0.0f32, f32::max);
+        let max_x = mesh.positions.iter().map(|p| p[0]).fold(0.0f32, f32::max);
+        assert!((max_y - (1.0 - FLATTENING)).abs() < 1.0e-6);
+        assert!((max_x - 1.0).abs() < 1.0e-6);
+
+        for (p, n) in mesh.positions.iter().zip(mesh.normals.iter()) {
+            assert!((n[0] * n[0] + n[1] * n[1] + n[2] * n[2] - 1.0).abs() < 1.0e-5);
+            // normals point outwards
+            assert!(p[0] * n[0] + p[1] * n[1] + p[2] * n[2] > 0.0);
+        }
+    }
+
+    #[test]
+    fn triangles_are_counter_clockwise_from_outside() {
+        let mesh = globe_model(STEP, 0.065);
+        assert!(mesh.triangles.iter().all(|triangle| mesh.faces_outwards(*triangle)));
+    }
+
+    #[test]
+    fn obj_lists_all_elements() {
+        let mesh = globe_model(STEP, 0.0);
+        let mut obj = vec![];
+        write_obj(&mut obj, &mesh, "globe.mtl").unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+
+        let count = |prefix: &str| obj.lines().filter(|line| line.starts_with(prefix)).count();
+        assert_eq!(mesh.positions.len(), count("v "));
+        assert_eq!(mesh.uvs.len(), count("vt "));
+        assert_eq!(mesh.normals.len(), count("vn "));
+        assert_eq!(mesh.triangles.len(), count("f "));
+        assert!(obj.contains("mtllib globe.mtl"));
+        // 1-based indices
+        assert!(!obj.lines().filter(|line| line.starts_with("f ")).any(|line| line.contains(" 0/")));
+    }
+
+    #[test]
+    fn missing_longitudes_are_filled() {
+        // 4 columns: values 10, 20, 30, 40
+        let pixels: Vec<u8> = (1..=4).flat_map(|i| [10 * i; 3]).collect();
+        let hemisphere = Image::new_from_pixels(4, 1, None, PixelFormat::RGB8, None, pixels);
+
+        let red = |image: &Image| image.line::<u8>(0).iter().step_by(3).take(8).cloned().collect::<Vec<u8>>();
+        assert_eq!(vec![0, 0, 10, 20, 30, 40, 0, 0], red(&full_texture(&hemisphere, LongitudeFill::Black)));
+        assert_eq!(vec![30, 40, 10, 20, 30, 40, 10, 20], red(&full_texture(&hemisphere, LongitudeFill::Wrap)));
+        assert_eq!(vec![10, 10, 10, 20, 30, 40, 40, 40], red(&full_texture(&hemisphere, LongitudeFill::ClampEdge)));
+    }
+}
@@ -0,0 +1,273 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Wireframe globe showing the planet's orientation (roll, inclination, flattening) in the source view.
+
+use cgmath::{Deg, Matrix4, Vector4};
+use crate::data::{self, ToArray};
+use crate::gpu::render_check;
+use crate::gui;
+use crate::gui::draw_buffer::{DrawBuffer, Sampling};
+use crate::projection;
+use crate::projection::data::{create_half_meridian, create_half_parallel};
+use crate::projection::source_view::{self, SourceParameters};
+use glium::{Surface, uniform};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// Logical size (in pixels) of the gizmo; doubled when enlarged.
+const SIZE: f32 = 120.0;
+
+/// Distance (in pixels) from the corner of the source image.
+const MARGIN: f32 = 8.0;
+
+/// Length of the drawn rotation axis relative to the polar radius.
+const AXIS_LENGTH: f32 = 1.3;
+
+/// Scale of the globe within the gizmo, leaving room for the axis.
+const GLOBE_SCALE: f32 = 0.7;
+
+const NUM_SEGMENTS: usize = 64;
+
+const LIMB_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const GRID_COLOR: [f32; 4] = [0.45, 0.45, 0.45, 1.0];
+const AXIS_COLOR: [f32; 4] = [1.0, 0.8, 0.0, 1.0];
+const LABEL_COLOR: [f32; 4] = [1.0, 0.8, 0.0, 1.0];
+
+pub struct OrientationGizmo {
+    draw_buf: DrawBuffer,
+    solid_color_3d_prog: Rc<glium::Program>,
+    unit_circle: Rc<glium::VertexBuffer<data::Vertex3>>,
+    half_parallels: Vec<glium::VertexBuffer<data::Vertex3>>,
+    half_meridians: Vec<glium::VertexBuffer<data::Vertex3>>,
+    axis: glium::VertexBuffer<data::Vertex3>,
+    src_params: SourceParameters,
+    enlarged: bool,
+    /// Rendering has failed and is to be repeated.
+    render_pending: Cell<bool>
+}
+
+impl OrientationGizmo {
+    pub fn new(
+        gl_objects: &projection::data::OpenGlObjects,
+        display: &glium::Display,
+        renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>,
+        src_params: &SourceParameters
+    ) -> OrientationGizmo {
+        let mut draw_buf = DrawBuffer::new_with_size(
+            Sampling::Single,
+            &gl_objects.texture_copy_single,
+            &gl_objects.texture_copy_multi,
+            &gl_objects.unit_quad,
+            display,
+            renderer,
+            SIZE as u32,
+            SIZE as u32
+        );
+        draw_buf.register("Source view", "orientation gizmo");
+
+        let axis_points = [
+            data::Vertex3{ position: [0.0, -AXIS_LENGTH, 0.0] },
+            data::Vertex3{ position: [0.0, AXIS_LENGTH, 0.0] }
+        ];
+
+        let gizmo = OrientationGizmo{
+            draw_buf,
+            solid_color_3d_prog: Rc::clone(&gl_objects.solid_color_3d),
+            unit_circle: Rc::clone(&gl_objects.unit_circle),
+            half_parallels: [-60.0, -30.0, 0.0, 30.0, 60.0].iter()
+                .map(|lat| create_half_parallel(Deg(*lat), NUM_SEGMENTS, display))
+                .collect(),
+            half_meridians: [-60.0, -30.0, 0.0, 30.0, 60.0].iter()
+                .map(|lon| create_half_meridian(Deg(*lon), NUM_SEGMENTS, display))
+                .collect(),
+            axis: glium::VertexBuffer::new(display, &axis_points).unwrap(),
+            src_params: src_params.clone(),
+            enlarged: false,
+            render_pending: Cell::new(false)
+        };
+        gizmo.render();
+
+        gizmo
+    }
+
+    fn logical_size(&self) -> f32 { if self.enlarged { 2.0 * SIZE } else { SIZE } }
+
+    pub fn set_src_params(&mut self, src_params: &SourceParameters) {
+        self.src_params = src_params.clone();
+        self.render();
+    }
+
+    fn render(&self) {
+        self.render_pending.set(!render_check::render_checked("orientation gizmo", || self.try_render()));
+    }
+
+    fn try_render(&self) -> Result<(), glium::DrawError> {
+        let mut target = self.draw_buf.frame_buf();
+        target.clear_color(0.1, 0.1, 0.1, 1.0);
+
+        let inclined = gizmo_transform(&self.src_params, true);
+        for line in self.half_parallels.iter().chain(self.half_meridians.iter()) {
+            self.draw_lines(&mut target, line, glium::index::PrimitiveType::LineStrip, &inclined, GRID_COLOR)?;
+        }
+        self.draw_lines(
+            &mut target,
+            &self.unit_circle,
+            glium::index::PrimitiveType::LineLoop,
+            &gizmo_transform(&self.src_params, false),
+            LIMB_COLOR
+        )?;
+        self.draw_lines(&mut target, &self.axis, glium::index::PrimitiveType::LinesList, &inclined, AXIS_COLOR)?;
+
+        self.draw_buf.update_storage_buf()
+    }
+
+    fn draw_lines<S: Surface>(
+        &self,
+        target: &mut S,
+        lines: &glium::VertexBuffer<data::Vertex3>,
+        primitive: glium::index::PrimitiveType,
+        transform: &Matrix4<f32>,
+        color: [f32; 4]
+    ) -> Result<(), glium::DrawError> {
+        let uniforms = uniform! {
+            vertex_transform: transform.to_array(),
+            color: color
+        };
+
+        target.draw(
+            lines,
+            &glium::index::NoIndices(primitive),
+            &self.solid_color_3d_prog,
+            &uniforms,
+            &Default::default()
+        )
+    }
+}
+
+/// Returns transform of a unit globe to the gizmo's (square) draw buffer; shares the orientation with the disk
+/// overlay of the source view.
+fn gizmo_transform(src_params: &SourceParameters, with_inclination: bool) -> Matrix4<f32> {
+    Matrix4::from_nonuniform_scale(GLOBE_SCALE, GLOBE_SCALE, GLOBE_SCALE) *
+    source_view::globe_orientation_transform(src_params, src_params.roll, with_inclination)
+}
+
+/// Returns positions of the north and south end of the drawn rotation axis, normalized to [0; 1] (from top-left)
+/// within the gizmo.
+fn axis_label_positions(src_params: &SourceParameters) -> [[f32; 2]; 2] {
+    let transform = gizmo_transform(src_params, true);
+    let normalized = |y: f32| {
+        let pos = transform * Vector4{ x: 0.0, y, z: 0.0, w: 1.0 };
+        [(pos.x + 1.0) / 2.0, (1.0 - pos.y) / 2.0]
+    };
+
+    [normalized(AXIS_LENGTH), normalized(-AXIS_LENGTH)]
+}
+
+/// Shows the gizmo over the top-right corner of the last item (the source image); clicking toggles its size.
+pub fn handle_orientation_gizmo(ui: &imgui::Ui, hidpi_factor: f32, gizmo: &mut OrientationGizmo) {
+    if gizmo.render_pending.get() { gizmo.render(); }
+
+    let size = gizmo.logical_size();
+    let physical_size = (size * hidpi_factor) as u32;
+    if gizmo.draw_buf.update_size(physical_size, physical_size) { gizmo.render(); }
+
+    let image_min = ui.item_rect_min();
+    let image_max = ui.item_rect_max();
+    if image_max[0] - image_min[0] < size + 2.0 * MARGIN || image_max[1] - image_min[1] < size + 2.0 * MARGIN {
+        return;
+    }
+    let pos = [image_max[0] - size - MARGIN, image_min[1] + MARGIN];
+
+    let cursor_pos = ui.cursor_screen_pos();
+    ui.set_cursor_screen_pos(pos);
+    imgui::Image::new(gizmo.draw_buf.id(), [size, size]).build(ui);
+    if ui.is_item_clicked_with_button(imgui::MouseButton::Left) {
+        gizmo.enlarged = !gizmo.enlarged;
+    }
+    gui::tooltip(ui, "Planet orientation (roll, inclination, flattening). Click to change size.");
+    ui.set_cursor_screen_pos(cursor_pos);
+
+    let draw_list = ui.get_window_draw_list();
+    for (label, label_pos) in ["N", "S"].iter().zip(axis_label_positions(&gizmo.src_params).iter()) {
+        let text_size = ui.calc_text_size(label);
+        let label_pos = [
+            (pos[0] + label_pos[0] * size - text_size[0] / 2.0).max(pos[0]).min(pos[0] + size - text_size[0]),
+            (pos[1] + label_pos[1] * size - text_size[1] / 2.0).max(pos[1]).min(pos[1] + size - text_size[1])
+        ];
+        draw_list.add_text(label_pos, LABEL_COLOR, label);
+    }
+}
+
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: [f32; 2], actual: [f32; 2]) {
+        assert!(
+            (expected[0] - actual[0]).abs() < 1.0e-5 && (expected[1] - actual[1]).abs() < 1.0e-5,
+            "expected {:?}, got {:?}", expected, actual
+        );
+    }
+
+    #[test]
+    fn north_is_up_by_default() {
+        let [north, south] = axis_label_positions(&SourceParameters::test_default());
+        let offset = AXIS_LENGTH * GLOBE_SCALE / 2.0;
+        assert_close([0.5, 0.5 - offset], north);
+        assert_close([0.5, 0.5 + offset], south);
+    }
+
+    #[test]
+    fn labels_follow_roll_and_flip() {
+        let params = SourceParameters{ roll: Deg(30.0), ..SourceParameters::test_default() };
+        let [north, _] = axis_label_positions(&params);
+        // roll is clockwise
+        assert!(north[0] > 0.5 && north[1] < 0.5);
+
+        let params = SourceParameters{ flip_ns: true, ..SourceParameters::test_default() };
+        let [north, south] = axis_label_positions(&params);
+        assert!(north[1] > 0.5 && south[1] < 0.5);
+    }
+
+    #[test]
+    fn inclination_shortens_axis() {
+        let params = SourceParameters{ inclination: Deg(30.0), ..SourceParameters::test_default() };
+        let [north, south] = axis_label_positions(&params);
+        let length = south[1] - north[1];
+        assert!((length - AXIS_LENGTH * GLOBE_SCALE * Deg(30.0f32).0.to_radians().cos()).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn gizmo_matches_disk_overlay() {
+        let params = SourceParameters{
+            roll: Deg(-12.0),
+            inclination: Deg(7.0),
+            flattening: 0.06,
+            ..SourceParameters::test_default()
+        };
+        // the polar radius is 1/1.06, foreshortened by cos 7°, and rotated by 12° counter-clockwise
+        let expected = [-0.194681, 0.915902];
+
+        let pole = Vector4{ x: 0.0, y: 1.0, z: 0.0, w: 1.0 };
+        let gizmo_pole = gizmo_transform(&params, true) * pole;
+        let overlay_pole = source_view::globe_orientation_transform(&params, params.roll, true) * pole;
+        assert_close(expected, [gizmo_pole.x / GLOBE_SCALE, gizmo_pole.y / GLOBE_SCALE]);
+        assert_close(expected, [overlay_pole.x, overlay_pole.y]);
+    }
+}
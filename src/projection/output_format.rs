@@ -0,0 +1,130 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! File formats of exported frames.
+
+use crate::image_utils::{self, BitDepth};
+use std::path::Path;
+
+#[derive(Copy, Clone, Debug, PartialEq, strum::EnumIter)]
+pub enum OutputFormat {
+    Png8,
+    Png16,
+    Tiff8,
+    Tiff16
+}
+
+impl OutputFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Png8 => "PNG 8-bit",
+            OutputFormat::Png16 => "PNG 16-bit",
+            OutputFormat::Tiff8 => "TIFF 8-bit",
+            OutputFormat::Tiff16 => "TIFF 16-bit"
+        }
+    }
+
+    /// Returns the PNG format of `bit_depth`.
+    pub fn png(bit_depth: BitDepth) -> OutputFormat {
+        match bit_depth {
+            BitDepth::Eight => OutputFormat::Png8,
+            BitDepth::Sixteen => OutputFormat::Png16
+        }
+    }
+
+    /// Returns the extension (without the dot) of saved files.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png8 | OutputFormat::Png16 => "png",
+            OutputFormat::Tiff8 | OutputFormat::Tiff16 => "tif"
+        }
+    }
+
+    pub fn bit_depth(&self) -> BitDepth {
+        match self {
+            OutputFormat::Png8 | OutputFormat::Tiff8 => BitDepth::Eight,
+            OutputFormat::Png16 | OutputFormat::Tiff16 => BitDepth::Sixteen
+        }
+    }
+
+    fn image_format(&self) -> image::ImageFormat {
+        match self {
+            OutputFormat::Png8 | OutputFormat::Png16 => image::ImageFormat::Png,
+            OutputFormat::Tiff8 | OutputFormat::Tiff16 => image::ImageFormat::Tiff
+        }
+    }
+}
+
+/// Extensions of all output formats.
+pub const EXTENSIONS: [&str; 2] = ["png", "tif"];
+
+/// Saves `image` (RGB8 or RGB16; its pixel format, not that of `format`, determines the saved bit depth) as `path`
+/// in `format`. If `reproducible`, PNG files are encoded with `image_utils::encode_png_reproducible` (TIFF files
+/// contain no varying data anyway).
+pub fn save(
+    image: &ga_image::Image,
+    path: &Path,
+    format: OutputFormat,
+    reproducible: bool
+) -> Result<(), image::ImageError> {
+    let color_type = if image.pixel_format() == ga_image::PixelFormat::RGB16 {
+        image::ColorType::Rgb16
+    } else {
+        image::ColorType::Rgb8
+    };
+
+    if reproducible && format.image_format() == image::ImageFormat::Png {
+        let png = image_utils::encode_png_reproducible(image.raw_pixels(), image.width(), image.height(), color_type)?;
+        std::fs::write(path, png).map_err(image::ImageError::IoError)
+    } else {
+        image::save_buffer_with_format(
+            path, image.raw_pixels(), image.width(), image.height(), color_type, format.image_format()
+        )
+    }
+}
+
+mod tests {
+    use super::*;
+    use ga_image::{Image, PixelFormat};
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn extensions_follow_formats() {
+        for format in OutputFormat::iter() {
+            assert!(EXTENSIONS.contains(&format.extension()));
+            assert_eq!(Some(format.image_format()), image::ImageFormat::from_extension(format.extension()));
+        }
+        assert_eq!(OutputFormat::Png16, OutputFormat::png(BitDepth::Sixteen));
+    }
+
+    #[test]
+    fn sixteen_bit_tiff_keeps_values() {
+        let values: Vec<u16> = vec![0, 1, 255, 256, 32768, 65535];
+        let image = Image::new_from_pixels(
+            2, 1, None, PixelFormat::RGB16, None, values.iter().flat_map(|v| v.to_ne_bytes()).collect()
+        );
+        let path = std::env::temp_dir().join(format!("vislumino-output-format-{}.tif", std::process::id()));
+
+        save(&image, &path, OutputFormat::Tiff16, false).unwrap();
+        let loaded = image::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(values, loaded.into_rgb16().into_raw());
+    }
+}
@@ -0,0 +1,186 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Color of the disk outline and parallels drawn over source images.
+
+use cgmath::Point2;
+use ga_image::Image;
+
+/// Overlay color used when not chosen automatically.
+pub const DEFAULT_COLOR: [f32; 3] = [1.0, 0.0, 0.0];
+
+/// Colors considered when choosing automatically (ties are resolved in favor of earlier ones).
+const CANDIDATES: [[f32; 3]; 7] = [
+    [1.0, 0.0, 0.0], // red
+    [1.0, 1.0, 0.0], // yellow
+    [0.0, 1.0, 0.0], // green
+    [0.0, 1.0, 1.0], // cyan
+    [0.0, 0.0, 1.0], // blue
+    [1.0, 1.0, 1.0], // white
+    [0.0, 0.0, 0.0]  // black
+];
+
+/// Radii (relative to the disk's) of the circles sampled inside and outside the limb.
+const SAMPLED_RADII: [f32; 2] = [0.95, 1.05];
+
+const NUM_SAMPLES: usize = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OverlaySettings {
+    /// The color is chosen to contrast with the current frame around the limb.
+    pub auto_contrast: bool,
+    /// Color used if `auto_contrast` is off.
+    pub color: [f32; 3]
+}
+
+impl Default for OverlaySettings {
+    fn default() -> OverlaySettings {
+        OverlaySettings{ auto_contrast: false, color: DEFAULT_COLOR }
+    }
+}
+
+impl OverlaySettings {
+    /// Returns settings as semicolon-separated "key=value" pairs.
+    pub fn to_config_string(&self) -> String {
+        let c = &self.color;
+        format!("auto_contrast={};color={},{},{}", self.auto_contrast, c[0], c[1], c[2])
+    }
+
+    /// Parses the output of `to_config_string`; missing and unknown keys are ignored.
+    pub fn from_config_string(s: &str) -> Option<OverlaySettings> {
+        let mut settings = OverlaySettings::default();
+
+        for pair in s.split(';').filter(|pair| !pair.trim().is_empty()) {
+            let (key, value) = pair.split_once('=')?;
+            let value = value.trim();
+            match key.trim() {
+                "auto_contrast" => settings.auto_contrast = value.parse().ok()?,
+                "color" => {
+                    let values: Vec<f32> =
+                        value.split(',').map(|v| v.trim().parse::<f32>()).collect::<Result<_, _>>().ok()?;
+                    if values.len() != 3 { return None; }
+                    for (i, v) in values.iter().enumerate() { settings.color[i] = v.max(0.0).min(1.0); }
+                },
+                _ => ()
+            }
+        }
+
+        Some(settings)
+    }
+}
+
+/// Returns the mean color (in [0; 1]) of `image` (RGB8) at `num_points` points on a circle;
+/// `None` if none of them lie within the image.
+fn mean_on_circle(image: &Image, center: Point2<f32>, radius: f32, num_points: usize) -> Option<[f32; 3]> {
+    let mut sum = [0.0f32; 3];
+    let mut count = 0;
+
+    for i in 0..num_points {
+        let angle = i as f32 / num_points as f32 * 2.0 * std::f32::consts::PI;
+        let x = (center.x + radius * angle.cos()).round();
+        let y = (center.y + radius * angle.sin()).round();
+        if x < 0.0 || y < 0.0 || x >= image.width() as f32 || y >= image.height() as f32 { continue; }
+
+        let pixel = &image.line::<u8>(y as u32)[3 * x as usize..3 * x as usize + 3];
+        for (s, p) in sum.iter_mut().zip(pixel) { *s += *p as f32 / 255.0; }
+        count += 1;
+    }
+
+    if count == 0 { return None; }
+
+    Some(sum.map(|s| s / count as f32))
+}
+
+fn distance(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum::<f32>().sqrt()
+}
+
+/// Returns the overlay color contrasting best with `image` (RGB8) both just inside and just outside the limb
+/// of the disk of `center` and `diameter`; `None` if the limb lies outside the image.
+pub fn contrasting_color(image: &Image, center: Point2<f32>, diameter: f32) -> Option<[f32; 3]> {
+    let surroundings: Vec<[f32; 3]> = SAMPLED_RADII.iter()
+        .filter_map(|r| mean_on_circle(image, center, r * diameter / 2.0, NUM_SAMPLES))
+        .collect();
+
+    if surroundings.is_empty() { return None; }
+
+    let contrast = |color: &[f32; 3]| surroundings.iter().map(|s| distance(color, s)).fold(f32::MAX, f32::min);
+
+    let mut best = CANDIDATES[0];
+    for candidate in &CANDIDATES[1..] {
+        if contrast(candidate) > contrast(&best) { best = *candidate; }
+    }
+
+    Some(best)
+}
+
+mod tests {
+    use super::*;
+
+    /// Returns an RGB8 image with a disk of `disk_color` on a background of `background`.
+    fn disk_image(disk_color: [u8; 3], background: [u8; 3]) -> Image {
+        const SIZE: u32 = 64;
+        let mut pixels = vec![];
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                let (dx, dy) = (x as f32 - 32.0, y as f32 - 32.0);
+                let inside = (dx * dx + dy * dy).sqrt() < 20.0;
+                pixels.extend_from_slice(if inside { &disk_color } else { &background });
+            }
+        }
+
+        Image::new_from_pixels(SIZE, SIZE, None, ga_image::PixelFormat::RGB8, None, pixels)
+    }
+
+    #[test]
+    fn bright_disk_on_dark_background_gets_saturated_color() {
+        let image = disk_image([250, 250, 250], [0, 0, 0]);
+        let color = contrasting_color(&image, Point2{ x: 32.0, y: 32.0 }, 40.0).unwrap();
+        assert_ne!([1.0, 1.0, 1.0], color);
+        assert_ne!([0.0, 0.0, 0.0], color);
+    }
+
+    #[test]
+    fn dark_disk_on_dark_background_gets_white() {
+        let image = disk_image([20, 20, 20], [0, 0, 0]);
+        assert_eq!(Some([1.0, 1.0, 1.0]), contrasting_color(&image, Point2{ x: 32.0, y: 32.0 }, 40.0));
+    }
+
+    #[test]
+    fn red_planet_does_not_get_red() {
+        let image = disk_image([230, 128, 77], [0, 0, 0]);
+        let color = contrasting_color(&image, Point2{ x: 32.0, y: 32.0 }, 40.0).unwrap();
+        assert_ne!(DEFAULT_COLOR, color);
+        assert_eq!([0.0, 1.0, 1.0], color);
+    }
+
+    #[test]
+    fn limb_outside_image_gives_none() {
+        let image = disk_image([20, 20, 20], [0, 0, 0]);
+        assert_eq!(None, contrasting_color(&image, Point2{ x: 500.0, y: 500.0 }, 40.0));
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let settings = OverlaySettings{ auto_contrast: true, color: [0.25, 0.5, 1.0] };
+        assert_eq!(Some(settings.clone()), OverlaySettings::from_config_string(&settings.to_config_string()));
+        assert_eq!(Some(OverlaySettings::default()), OverlaySettings::from_config_string(""));
+        assert_eq!(None, OverlaySettings::from_config_string("color=1,0"));
+    }
+}
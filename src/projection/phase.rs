@@ -0,0 +1,202 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Geometry of the planet's phase: the terminator and the fading out of the image near it (the unlit part of the disk
+//! would otherwise be projected as dark smears into the map).
+
+use cgmath::{Angle, Deg, InnerSpace, Matrix3, Vector3};
+use crate::projection::projection_view;
+use crate::projection::source_view::SourceParameters;
+
+/// Illumination of the planet by the Sun.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Phase {
+    /// Angle Sun-planet-observer; 0 means a fully lit disk (no phase handling).
+    pub angle: Deg<f32>,
+    /// Position angle of the bright limb's midpoint relative to the projected north pole (measured towards east).
+    pub sun_direction: Deg<f32>,
+    /// Solar elevation above which the image is fully used; it fades out towards the terminator, beyond which
+    /// nothing is used.
+    pub terminator_margin: Deg<f32>
+}
+
+impl Default for Phase {
+    fn default() -> Phase {
+        Phase{ angle: Deg(0.0), sun_direction: Deg(0.0), terminator_margin: Deg(10.0) }
+    }
+}
+
+impl Phase {
+    pub fn is_enabled(&self) -> bool { self.angle.0 > 0.0 }
+}
+
+/// Returns the direction towards the Sun in globe coordinates (see `projection.frag`; X points west, Y to the north
+/// pole, Z to the observer at zero inclination).
+pub fn sun_vector(src_params: &SourceParameters) -> Vector3<f32> {
+    let phase = &src_params.phase;
+
+    // directions of the observer, of the projected north pole and of east in the sky
+    let observer = Vector3{ x: 0.0, y: src_params.inclination.sin(), z: src_params.inclination.cos() };
+    let north = (Vector3::unit_y() - observer * observer.y).normalize();
+    let east = observer.cross(north);
+
+    let sky = north * phase.sun_direction.cos() + east * phase.sun_direction.sin();
+
+    observer * phase.angle.cos() + sky * phase.angle.sin()
+}
+
+/// Returns the vector whose dot product with a point of the unit globe (before flattening) is proportional to
+/// the sine of the Sun's elevation at the corresponding point of the flattened planet.
+fn terminator_plane_normal(src_params: &SourceParameters) -> Vector3<f32> {
+    // the surface normal at the flattened globe point (x, (1 - f)·y, z) is proportional to (x, y / (1 - f), z)
+    let sun = sun_vector(src_params);
+    Vector3{ x: sun.x, y: sun.y / (1.0 - src_params.flattening), z: sun.z }
+}
+
+/// Returns the Sun's elevation above the horizon at the given globe coordinates (negative on the night side).
+pub fn solar_elevation(src_params: &SourceParameters, longitude: Deg<f32>, latitude: Deg<f32>) -> Deg<f32> {
+    let sun = sun_vector(src_params);
+    let p = projection_view::globe_position(longitude, latitude);
+    let normal = Vector3{ x: p.x, y: p.y / (1.0 - src_params.flattening), z: p.z }.normalize();
+
+    Deg::asin(normal.dot(sun).max(-1.0).min(1.0))
+}
+
+/// Returns opacity of the projection at the given globe coordinates due to the phase (CPU equivalent of the fading
+/// in `projection.frag`).
+pub fn terminator_alpha(src_params: &SourceParameters, longitude: Deg<f32>, latitude: Deg<f32>) -> f32 {
+    if !src_params.phase.is_enabled() { return 1.0; }
+
+    let sin_elevation = solar_elevation(src_params, longitude, latitude).sin();
+    let margin = src_params.phase.terminator_margin.sin();
+    if margin > 0.0 {
+        projection_view::smoothstep(0.0, margin, sin_elevation)
+    } else if sin_elevation > 0.0 {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Returns the transform of the half-equator (`data::create_half_parallel` at 0°) of the unit globe to the half
+/// of the terminator facing the observer; `None` if the phase is disabled.
+pub fn terminator_transform(src_params: &SourceParameters) -> Option<Matrix3<f32>> {
+    if !src_params.phase.is_enabled() { return None; }
+
+    let normal = terminator_plane_normal(src_params).normalize();
+    let observer = Vector3{ x: 0.0, y: src_params.inclination.sin(), z: src_params.inclination.cos() };
+
+    // the terminator's ends on the limb, and its point nearest to the observer
+    let end = normal.cross(observer);
+    if end.magnitude2() < 1.0e-12 { return None; }
+    let end = end.normalize();
+    let mut middle = normal.cross(end);
+    if middle.dot(observer) < 0.0 { middle = -middle; }
+
+    // the half-equator runs from X (angle 0°) through Z (90°)
+    Some(Matrix3::from_cols(end, normal, middle))
+}
+
+mod tests {
+    use super::*;
+    use cgmath::SquareMatrix;
+    use std::time::Duration;
+
+    fn params(phase: Phase) -> SourceParameters {
+        SourceParameters{
+            sidereal_rotation_period: Duration::from_secs(88642),
+            phase,
+            ..SourceParameters::test_default()
+        }
+    }
+
+    fn half_equator(angle: Deg<f32>) -> Vector3<f32> {
+        Vector3{ x: angle.cos(), y: 0.0, z: angle.sin() }
+    }
+
+    #[test]
+    fn zero_phase_changes_nothing() {
+        let params = params(Default::default());
+        for lon in [-90.0, -89.0, 0.0, 89.0, 90.0] {
+            for lat in [-89.0, 0.0, 60.0] {
+                assert_eq!(1.0, terminator_alpha(&params, Deg(lon), Deg(lat)));
+            }
+        }
+        assert!(terminator_transform(&params).is_none());
+    }
+
+    #[test]
+    fn sun_in_the_east_lights_eastern_hemisphere() {
+        // quadrature: the terminator runs along the central meridian
+        let params = params(Phase{ angle: Deg(90.0), sun_direction: Deg(90.0), terminator_margin: Deg(10.0) });
+
+        // longitudes grow westwards
+        assert_eq!(1.0, terminator_alpha(&params, Deg(-30.0), Deg(20.0)));
+        assert_eq!(0.0, terminator_alpha(&params, Deg(30.0), Deg(20.0)));
+        let alpha = terminator_alpha(&params, Deg(-5.0), Deg(0.0));
+        assert!(alpha > 0.0 && alpha < 1.0);
+        assert!((solar_elevation(&params, Deg(-5.0), Deg(0.0)).0 - 5.0).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn sharp_cut_without_margin() {
+        let params = params(Phase{ angle: Deg(40.0), sun_direction: Deg(-70.0), terminator_margin: Deg(0.0) });
+        for lon in (-90..=90).step_by(5) {
+            let elevation = solar_elevation(&params, Deg(lon as f32), Deg(10.0));
+            let expected = if elevation.0 > 0.0 { 1.0 } else { 0.0 };
+            assert_eq!(expected, terminator_alpha(&params, Deg(lon as f32), Deg(10.0)));
+        }
+    }
+
+    #[test]
+    fn terminator_is_visible_and_unlit() {
+        for (inclination, flattening) in [(0.0, 0.0), (20.0, 0.0), (-15.0, 0.06)] {
+            let params = SourceParameters{
+                inclination: Deg(inclination),
+                flattening,
+                ..params(Phase{ angle: Deg(45.0), sun_direction: Deg(120.0), terminator_margin: Deg(5.0) })
+            };
+            let transform = terminator_transform(&params).unwrap();
+            assert!(transform.determinant().abs() > 0.99);
+
+            let observer = Vector3{ x: 0.0, y: params.inclination.sin(), z: params.inclination.cos() };
+            for step in 0..=18 {
+                let p = transform * half_equator(Deg(10.0 * step as f32));
+                assert!((p.magnitude() - 1.0).abs() < 1.0e-5);
+                assert!(p.dot(observer) > -1.0e-5);
+
+                // the Sun is on the horizon there
+                let (lon, lat) = (Deg::atan2(p.x, p.z), Deg::asin(p.y.max(-1.0).min(1.0)));
+                assert!(solar_elevation(&params, lon, lat).0.abs() < 1.0e-2, "{:?}", (inclination, step));
+            }
+        }
+    }
+
+    #[test]
+    fn phase_angle_is_the_sun_observer_separation() {
+        let params = SourceParameters{
+            inclination: Deg(12.0),
+            ..params(Phase{ angle: Deg(35.0), sun_direction: Deg(-40.0), terminator_margin: Deg(5.0) })
+        };
+        let observer = Vector3{ x: 0.0, y: params.inclination.sin(), z: params.inclination.cos() };
+        let sun = sun_vector(&params);
+        assert!((sun.magnitude() - 1.0).abs() < 1.0e-6);
+        assert!((Deg::acos(sun.dot(observer)).0 - 35.0).abs() < 1.0e-3);
+    }
+}
@@ -0,0 +1,265 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Views of the planet from above a pole, resampled from equirectangular maps.
+//!
+//! The central meridian of the source frame points towards the bottom of the view (i.e., towards the observer).
+
+use cgmath::{Angle, Deg, Rad};
+use ga_image::{Image, PixelFormat};
+
+/// Range of the latitude limit (absolute value) selectable by the user.
+pub const LATITUDE_LIMIT_RANGE: (f64, f64) = (0.0, 80.0);
+
+/// Range of the output size selectable by the user.
+pub const SIZE_RANGE: (u32, u32) = (64, 8192);
+
+#[derive(Copy, Clone, Debug, PartialEq, strum::EnumIter)]
+pub enum Pole {
+    North,
+    South
+}
+
+impl Pole {
+    pub fn name(&self) -> &str {
+        match self {
+            Pole::North => "north",
+            Pole::South => "south"
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, strum::EnumIter)]
+pub enum PolarProjection {
+    Orthographic,
+    Stereographic
+}
+
+impl PolarProjection {
+    pub fn name(&self) -> &str {
+        match self {
+            PolarProjection::Orthographic => "orthographic",
+            PolarProjection::Stereographic => "stereographic"
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PolarView {
+    pub pole: Pole,
+    pub projection: PolarProjection,
+    /// The view extends from the pole to this latitude (absolute value), which touches the image edges.
+    pub latitude_limit: Deg<f64>,
+    /// Width and height of the view in pixels.
+    pub size: u32
+}
+
+impl Default for PolarView {
+    fn default() -> PolarView {
+        PolarView{
+            pole: Pole::North,
+            projection: PolarProjection::Orthographic,
+            latitude_limit: Deg(30.0),
+            size: 1024
+        }
+    }
+}
+
+impl PolarView {
+    fn max_colatitude(&self) -> Deg<f64> { Deg(90.0) - self.latitude_limit }
+
+    /// Horizontal direction of increasing longitude; seen from below the south pole, it is reversed.
+    fn longitude_sign(&self) -> f64 { if self.pole == Pole::North { 1.0 } else { -1.0 } }
+
+    /// Returns the distance from the view's center (1 at `latitude_limit`) of points at `colatitude` (angular
+    /// distance from the pole).
+    fn radius(&self, colatitude: Deg<f64>) -> f64 {
+        let max = self.max_colatitude();
+        match self.projection {
+            PolarProjection::Orthographic => colatitude.sin() / max.sin(),
+            PolarProjection::Stereographic => (colatitude / 2.0).tan() / (max / 2.0).tan()
+        }
+    }
+
+    /// Inverse of `radius`.
+    fn colatitude(&self, radius: f64) -> Deg<f64> {
+        let max = self.max_colatitude();
+        match self.projection {
+            PolarProjection::Orthographic => Rad((radius * max.sin()).asin()).into(),
+            PolarProjection::Stereographic => Deg::from(Rad((radius * (max / 2.0).tan()).atan())) * 2.0
+        }
+    }
+
+    /// Returns position (in pixels, from the view's top-left corner) of the point at `longitude` (relative to the
+    /// central meridian, increasing rightwards as seen by the observer) and `latitude`; `None` if outside the view.
+    pub fn position(&self, longitude: Deg<f64>, latitude: Deg<f64>) -> Option<[f64; 2]> {
+        let colatitude = match self.pole {
+            Pole::North => Deg(90.0) - latitude,
+            Pole::South => Deg(90.0) + latitude
+        };
+        if colatitude.0 < 0.0 || colatitude > self.max_colatitude() { return None; }
+
+        let half_size = self.size as f64 / 2.0;
+        let radius = self.radius(colatitude) * half_size;
+
+        Some([
+            half_size + self.longitude_sign() * radius * longitude.sin(),
+            half_size + radius * longitude.cos()
+        ])
+    }
+
+    /// Inverse of `position`; returns (longitude, latitude).
+    pub fn coords(&self, position: [f64; 2]) -> Option<(Deg<f64>, Deg<f64>)> {
+        let half_size = self.size as f64 / 2.0;
+        let dx = (position[0] - half_size) / half_size;
+        let dy = (position[1] - half_size) / half_size;
+        let radius = dx.hypot(dy);
+        if radius > 1.0 { return None; }
+
+        let colatitude = self.colatitude(radius);
+        let longitude = Deg::atan2(self.longitude_sign() * dx, dy);
+        let latitude = match self.pole {
+            Pole::North => Deg(90.0) - colatitude,
+            Pole::South => colatitude - Deg(90.0)
+        };
+
+        Some((longitude, latitude))
+    }
+
+    /// Resamples `hemisphere` (RGB8 equirectangular map spanning 180° of longitude, with the central meridian in
+    /// the middle, and 180° of latitude) into the polar view; points outside the map are black.
+    pub fn render(&self, hemisphere: &Image) -> Image {
+        assert!(hemisphere.pixel_format() == PixelFormat::RGB8);
+
+        let mut view = Image::new(self.size, self.size, None, PixelFormat::RGB8, None, true);
+        for y in 0..self.size {
+            let line = view.line_mut::<u8>(y);
+            for x in 0..self.size {
+                let (longitude, latitude) = match self.coords([x as f64 + 0.5, y as f64 + 0.5]) {
+                    Some(coords) => coords,
+                    None => continue
+                };
+                if longitude.0.abs() > 90.0 { continue; }
+
+                let src_x = (longitude.0 + 90.0) / 180.0 * hemisphere.width() as f64 - 0.5;
+                let src_y = (90.0 - latitude.0) / 180.0 * hemisphere.height() as f64 - 0.5;
+                let dest = 3 * x as usize;
+                line[dest..dest + 3].copy_from_slice(&bilinear_rgb8(hemisphere, src_x, src_y));
+            }
+        }
+
+        view
+    }
+}
+
+/// Returns the interpolated value of `image` (RGB8) at the given pixel coordinates (clamped to the image).
+fn bilinear_rgb8(image: &Image, x: f64, y: f64) -> [u8; 3] {
+    let max_x = image.width() as f64 - 1.0;
+    let max_y = image.height() as f64 - 1.0;
+    let (x, y) = (x.max(0.0).min(max_x), y.max(0.0).min(max_y));
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(image.width() - 1), (y0 + 1).min(image.height() - 1));
+    let (tx, ty) = (x - x0 as f64, y - y0 as f64);
+
+    let (line0, line1) = (image.line::<u8>(y0), image.line::<u8>(y1));
+    let mut result = [0; 3];
+    for (channel, value) in result.iter_mut().enumerate() {
+        let at = |line: &[u8], x: u32| line[3 * x as usize + channel] as f64;
+        let top = at(line0, x0) * (1.0 - tx) + at(line0, x1) * tx;
+        let bottom = at(line1, x0) * (1.0 - tx) + at(line1, x1) * tx;
+        *value = (top * (1.0 - ty) + bottom * ty).round() as u8;
+    }
+
+    result
+}
+
+mod tests {
+    use super::*;
+
+    fn view(pole: Pole, projection: PolarProjection, latitude_limit: f64) -> PolarView {
+        PolarView{ pole, projection, latitude_limit: Deg(latitude_limit), size: 200 }
+    }
+
+    fn assert_position(expected: [f64; 2], actual: Option<[f64; 2]>) {
+        let actual = actual.unwrap();
+        assert!(
+            (expected[0] - actual[0]).abs() < 0.01 && (expected[1] - actual[1]).abs() < 0.01,
+            "expected {:?}, got {:?}", expected, actual
+        );
+    }
+
+    #[test]
+    fn orthographic_positions() {
+        let north = view(Pole::North, PolarProjection::Orthographic, 0.0);
+        assert_position([100.0, 100.0], north.position(Deg(0.0), Deg(90.0)));
+        // central meridian at the equator: bottom edge
+        assert_position([100.0, 200.0], north.position(Deg(0.0), Deg(0.0)));
+        // sin(60°) = 0.8660
+        assert_position([186.60, 100.0], north.position(Deg(90.0), Deg(30.0)));
+        assert_position([100.0, 13.40], north.position(Deg(180.0), Deg(30.0)));
+
+        let south = view(Pole::South, PolarProjection::Orthographic, 0.0);
+        assert_position([13.40, 100.0], south.position(Deg(90.0), Deg(-30.0)));
+        assert_eq!(None, south.position(Deg(0.0), Deg(30.0)));
+    }
+
+    #[test]
+    fn stereographic_positions() {
+        let north = view(Pole::North, PolarProjection::Stereographic, 30.0);
+        // tan(15°) / tan(30°) = 0.46410
+        assert_position([53.59, 100.0], north.position(Deg(-90.0), Deg(60.0)));
+        assert_position([100.0, 146.41], north.position(Deg(0.0), Deg(60.0)));
+        assert_position([100.0, 200.0], north.position(Deg(0.0), Deg(30.0)));
+        assert_eq!(None, north.position(Deg(0.0), Deg(20.0)));
+    }
+
+    #[test]
+    fn coords_are_inverse_of_position() {
+        for pole in [Pole::North, Pole::South] {
+            for projection in [PolarProjection::Orthographic, PolarProjection::Stereographic] {
+                let view = view(pole, projection, 20.0);
+                let sign = if pole == Pole::North { 1.0 } else { -1.0 };
+                for (lon, lat) in [(-170.0, 25.0), (-45.0, 50.0), (0.0, 89.0), (30.0, 70.0), (120.0, 21.0)] {
+                    let pos = view.position(Deg(lon), Deg(sign * lat)).unwrap();
+                    let (lon2, lat2) = view.coords(pos).unwrap();
+                    assert!((lon - lon2.0).abs() < 1.0e-6 && (sign * lat - lat2.0).abs() < 1.0e-6);
+                }
+                assert_eq!(None, view.coords([0.0, 0.0]));
+            }
+        }
+    }
+
+    #[test]
+    fn rendering_covers_visible_hemisphere_only() {
+        // top half of the map (northern hemisphere) is white
+        let mut hemisphere = Image::new(180, 90, None, PixelFormat::RGB8, None, true);
+        for y in 0..45 {
+            hemisphere.line_mut::<u8>(y)[..3 * 180].fill(255);
+        }
+
+        let polar = view(Pole::North, PolarProjection::Orthographic, 10.0).render(&hemisphere);
+        let pixel = |x: u32, y: u32| polar.line::<u8>(y)[3 * x as usize];
+        assert_eq!(255, pixel(100, 100));
+        // towards the observer (bottom) and on the far side (top, not visible from the observer)
+        assert_eq!(255, pixel(100, 150));
+        assert_eq!(0, pixel(100, 50));
+        // outside of the view
+        assert_eq!(0, pixel(2, 2));
+    }
+}
@@ -0,0 +1,199 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! User-provided command executed after a successful export.
+
+use std::path::Path;
+use std::process::Command;
+
+pub const PLACEHOLDER_OUTPUT_DIR: &str = "{output_dir}";
+pub const PLACEHOLDER_FRAME_COUNT: &str = "{frame_count}";
+pub const PLACEHOLDER_PLANET: &str = "{planet}";
+pub const PLACEHOLDER_PROJECTION: &str = "{projection}";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shell {
+    /// `sh -c`
+    Posix,
+    /// `cmd /C`
+    Windows
+}
+
+impl Shell {
+    pub fn current() -> Shell {
+        if cfg!(target_os = "windows") { Shell::Windows } else { Shell::Posix }
+    }
+}
+
+/// Returns `value` quoted so that the shell passes it as a single argument.
+pub fn quote(shell: Shell, value: &str) -> String {
+    match shell {
+        Shell::Posix => format!("'{}'", value.replace('\'', "'\\''")),
+        Shell::Windows => format!("\"{}\"", escape_in_double_quotes(shell, value))
+    }
+}
+
+/// Escapes `value` for use inside an already double-quoted string.
+fn escape_in_double_quotes(shell: Shell, value: &str) -> String {
+    match shell {
+        Shell::Posix => {
+            let mut result = String::with_capacity(value.len());
+            for c in value.chars() {
+                if matches!(c, '\\' | '"' | '$' | '`') { result.push('\\'); }
+                result.push(c);
+            }
+            result
+        },
+
+        // Windows paths cannot contain double quotes; other characters need no escaping for argument parsing, except
+        // for `%`, which cmd expands even within quotes: it is moved outside of them and escaped (the program's
+        // argument parsing joins the adjacent quoted parts)
+        Shell::Windows => value.replace('"', "\\\"").replace('%', "\"^%\"")
+    }
+}
+
+/// Substitutes `placeholders` (pairs: placeholder, value) in `template`. A placeholder surrounded by double quotes
+/// in the template is replaced by the escaped value; otherwise by the quoted value.
+pub fn expand_template(template: &str, placeholders: &[(&str, String)], shell: Shell) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+
+    'outer: while !rest.is_empty() {
+        for (placeholder, value) in placeholders {
+            if rest.starts_with(placeholder) {
+                let in_quotes = result.ends_with('"') && rest[placeholder.len()..].starts_with('"');
+                if in_quotes {
+                    result += &escape_in_double_quotes(shell, value);
+                } else {
+                    result += &quote(shell, value);
+                }
+                rest = &rest[placeholder.len()..];
+                continue 'outer;
+            }
+        }
+
+        let c = rest.chars().next().unwrap();
+        result.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    result
+}
+
+/// Runs `command` via the system shell in `working_dir`. On failure, returns a log with the command's output.
+pub fn run(command: &str, working_dir: &Path) -> Result<(), String> {
+    let output = shell_command(command).current_dir(working_dir).output();
+
+    match output {
+        Err(e) => Err(format!("Could not execute \"{}\": {}.", command, e)),
+
+        Ok(output) => if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Command \"{}\" failed ({}).\n\nstdout:\n{}\nstderr:\n{}",
+                command,
+                output.status,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ))
+        }
+    }
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+
+    // cmd parses its command line on its own; `arg` would quote the command and escape its quotes with backslashes
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").raw_arg(command);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+mod tests {
+    use super::*;
+
+    fn placeholders() -> Vec<(&'static str, String)> {
+        vec![
+            (PLACEHOLDER_OUTPUT_DIR, "/home/user/my maps/it's".to_string()),
+            (PLACEHOLDER_FRAME_COUNT, "42".to_string()),
+            (PLACEHOLDER_PLANET, "Jupiter".to_string())
+        ]
+    }
+
+    #[test]
+    fn posix_values_are_single_quoted() {
+        assert_eq!(
+            "prep.sh '/home/user/my maps/it'\\''s' '42' 'Jupiter' {projection}",
+            expand_template("prep.sh {output_dir} {frame_count} {planet} {projection}", &placeholders(), Shell::Posix)
+        );
+    }
+
+    #[test]
+    fn posix_values_in_double_quotes_are_escaped() {
+        let placeholders = vec![(PLACEHOLDER_OUTPUT_DIR, "/tmp/$HOME \"x\"".to_string())];
+        assert_eq!(
+            "prep.sh \"/tmp/\\$HOME \\\"x\\\"\"",
+            expand_template("prep.sh \"{output_dir}\"", &placeholders, Shell::Posix)
+        );
+    }
+
+    #[test]
+    fn windows_values_are_double_quoted() {
+        let placeholders = vec![(PLACEHOLDER_OUTPUT_DIR, "C:\\My Maps".to_string())];
+        assert_eq!(
+            "prep.bat \"C:\\My Maps\"",
+            expand_template("prep.bat {output_dir}", &placeholders, Shell::Windows)
+        );
+        // already quoted in the template
+        assert_eq!(
+            "prep.bat \"C:\\My Maps\"",
+            expand_template("prep.bat \"{output_dir}\"", &placeholders, Shell::Windows)
+        );
+    }
+
+    #[test]
+    fn windows_percent_signs_are_not_expanded() {
+        let placeholders = vec![(PLACEHOLDER_OUTPUT_DIR, "C:\\100% %PATH%".to_string())];
+        assert_eq!(
+            "prep.bat \"C:\\100\"^%\" \"^%\"PATH\"^%\"\"",
+            expand_template("prep.bat {output_dir}", &placeholders, Shell::Windows)
+        );
+        assert_eq!(
+            "prep.bat \"C:\\100\"^%\" \"^%\"PATH\"^%\"\"",
+            expand_template("prep.bat \"{output_dir}\"", &placeholders, Shell::Windows)
+        );
+    }
+
+    #[test]
+    fn non_ascii_text_is_preserved() {
+        assert_eq!(
+            "echo zażółć 'Jupiter'",
+            expand_template("echo zażółć {planet}", &placeholders(), Shell::Posix)
+        );
+    }
+}
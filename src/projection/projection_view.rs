@@ -17,37 +17,249 @@
 // along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
 //
 
-use cgmath::{Matrix3, Rotation3, Vector2, SquareMatrix};
+use cgmath::{Angle, Deg, InnerSpace, Matrix3, Point2, Rotation3, Vector2, Vector3, SquareMatrix};
+use crate::cancellation::CancelToken;
+use crate::color::{self, Interpretation};
 use crate::config::{Configuration, ProjectionConfig};
 use crate::data;
 use crate::data::ToArray;
+use crate::fmt;
+use crate::gpu::render_check;
+use crate::gpu::render_throttle::RenderThrottle;
+use crate::gpu::resize_debounce::ResizeDebounce;
+use crate::gpu::texture_limits::{self, TextureLimits};
 use crate::gui;
 use crate::gui::draw_buffer::Sampling;
 use crate::gui::DrawBuffer;
+use crate::gui::crossfade::Crossfade;
 use crate::gui::long_task_dialog::LongTaskDialog;
+use crate::img_seq;
+use crate::logging;
+use crate::long_fg_task::LongForegroundTask;
 use crate::projection;
-use crate::projection::{ExportDialog, handle_export_dialog, SourceView, source_view::SourceParameters, worker};
+use crate::projection::{ExportDialog, handle_export_dialog, post_export, SourceView, source_view::SourceParameters, worker};
+use crate::projection::coverage;
+use crate::projection::diagnostic;
+use crate::projection::display_stretch::{self, DisplayStretch};
+use crate::projection::export_comparison::{self, ExportComparison};
+use crate::projection::globe_view::GlobeTarget;
+use crate::projection::map_save;
+use crate::projection::phase;
+use crate::projection::verification::{self, Verification};
+use crate::projection::data::{ExportLock, TexturesInUse};
+use crate::projection::export_metadata::ExportMetadata;
+use crate::projection::export_preview::ExportPreview;
+use crate::projection::winjupos::WinJuposExport;
 use crate::subscriber::Subscriber;
 use glium::{Surface, uniform};
-use glium::Texture2d;
-use std::cell::RefCell;
+use glium::{GlObject, Texture2d};
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::Instant;
 
 const PI_2: f32 = std::f32::consts::PI / 2.0;
 
+/// Maximum standard parallel of the Lambert cylindrical equal-area projection.
+const MAX_STANDARD_PARALLEL: f32 = 60.0;
+
+const MAX_LIMB_FEATHER_PERCENT: f32 = 15.0;
+
+const MAX_ROTATION_COMP_PIXELS: f32 = 10.0;
+
+const MAX_ROTATION_COMP_DEGREES: f32 = 5.0;
+
+const DEFAULT_MAX_EMISSION_ANGLE: f32 = 60.0;
+
+const MIN_MAX_EMISSION_ANGLE: f32 = 10.0;
+
+const MAX_MAX_EMISSION_ANGLE: f32 = 89.0;
+
+/// Grid spacing is relative to the view's size.
+const MIN_GRID_SPACING: f32 = 0.05;
+
+const MAX_GRID_SPACING: f32 = 0.5;
+
+const MIN_GRID_OPACITY: f32 = 0.05;
+
+/// Logical pixels.
+const COVERAGE_BAR_HEIGHT: f32 = 8.0;
+
+/// Longitudes covered by at least this many frames are shown in full green by the coverage bar.
+const COVERAGE_BAR_MANY_FRAMES: u32 = 5;
+
+const MAX_COVERAGE_BAR_BINS: usize = 512;
+
+const LINKED_CURSOR_COLOR: [f32; 4] = [0.0, 1.0, 1.0, 0.8];
+
+const DRIFT_WARNING_COLOR: [f32; 4] = [1.0, 0.8, 0.0, 1.0];
+
 #[derive(Copy, Clone, PartialEq)]
 pub enum ProjectionType {
     Equirectangular,
     LambertCylindricalEqualArea
 }
 
-struct Grid {
-    show: bool,
-    horz_spacing: f32,
-    vert_spacing: f32,
+impl ProjectionType {
+    pub fn name(&self) -> &str {
+        match self {
+            ProjectionType::Equirectangular => "equirectangular",
+            ProjectionType::LambertCylindricalEqualArea => "lambert"
+        }
+    }
+}
+
+/// Settings of a projection view which affect the generated projection.
+#[derive(Copy, Clone)]
+pub struct ProjectionSettings {
+    pub projection_type: ProjectionType,
+    pub standard_parallel: Deg<f32>,
+    /// `None` means "automatic" (based on rotation period and frame interval).
+    pub rotation_comp: Option<Deg<f32>>
+}
+
+/// Settings of a projection view which affect only its display (not the generated projection; the grid is included
+/// in exports only if requested in the export dialog).
+#[derive(Clone, Debug, PartialEq)]
+pub struct DisplaySettings {
+    pub grid_shown: bool,
+    pub grid_color: [f32; 4],
+    /// Distance between vertical grid lines (see `GridParams`).
+    pub grid_horz_spacing: f32,
+    /// Distance between horizontal grid lines (see `GridParams`).
+    pub grid_vert_spacing: f32,
+    /// Strip above the map showing the number of frames providing usable data at each longitude.
+    pub coverage_bar_shown: bool,
+    /// Frames provide usable data where the emission angle is below this value.
+    pub max_emission_angle: Deg<f32>
+}
+
+impl Default for DisplaySettings {
+    fn default() -> DisplaySettings {
+        DisplaySettings{
+            grid_shown: false,
+            grid_color: [1.0, 0.0, 0.0, 0.75],
+            grid_horz_spacing: 0.25,
+            grid_vert_spacing: 0.25,
+            coverage_bar_shown: false,
+            max_emission_angle: Deg(DEFAULT_MAX_EMISSION_ANGLE)
+        }
+    }
+}
+
+impl DisplaySettings {
+    /// Returns the default settings of new views.
+    pub fn from_config(config: &Configuration) -> DisplaySettings {
+        config.projection_view_display_settings().unwrap_or_default()
+    }
+
+    pub fn grid_params(&self) -> GridParams {
+        GridParams{
+            color: self.grid_color,
+            horz_spacing: self.grid_horz_spacing,
+            vert_spacing: self.grid_vert_spacing
+        }
+    }
+
+    /// Returns settings as semicolon-separated "key=value" pairs.
+    pub fn to_config_string(&self) -> String {
+        let c = &self.grid_color;
+        format!(
+            "grid_shown={};grid_color={},{},{},{};grid_horz_spacing={};grid_vert_spacing={};\
+                coverage_bar_shown={};max_emission_angle={}",
+            self.grid_shown, c[0], c[1], c[2], c[3], self.grid_horz_spacing, self.grid_vert_spacing,
+            self.coverage_bar_shown, self.max_emission_angle.0
+        )
+    }
+
+    /// Parses the output of `to_config_string`; missing and unknown keys are ignored, out-of-range values clamped.
+    pub fn from_config_string(s: &str) -> Option<DisplaySettings> {
+        let mut settings = DisplaySettings::default();
+
+        for pair in s.split(';').filter(|pair| !pair.trim().is_empty()) {
+            let (key, value) = pair.split_once('=')?;
+            let value = value.trim();
+            match key.trim() {
+                "grid_shown" => settings.grid_shown = value.parse().ok()?,
+                "grid_color" => {
+                    let values: Vec<f32> =
+                        value.split(',').map(|v| v.trim().parse::<f32>()).collect::<Result<_, _>>().ok()?;
+                    if values.len() != 4 { return None; }
+                    for (i, v) in values.iter().enumerate() { settings.grid_color[i] = v.max(0.0).min(1.0); }
+                    settings.grid_color[3] = settings.grid_color[3].max(MIN_GRID_OPACITY);
+                },
+                "grid_horz_spacing" => settings.grid_horz_spacing = parse_grid_spacing(value)?,
+                "grid_vert_spacing" => settings.grid_vert_spacing = parse_grid_spacing(value)?,
+                "coverage_bar_shown" => settings.coverage_bar_shown = value.parse().ok()?,
+                "max_emission_angle" => settings.max_emission_angle = Deg(
+                    value.parse::<f32>().ok()?.max(MIN_MAX_EMISSION_ANGLE).min(MAX_MAX_EMISSION_ANGLE)
+                ),
+                _ => ()
+            }
+        }
+
+        Some(settings)
+    }
+}
+
+fn parse_grid_spacing(s: &str) -> Option<f32> {
+    Some(s.parse::<f32>().ok()?.max(MIN_GRID_SPACING).min(MAX_GRID_SPACING))
+}
+
+/// Grid drawn over the projection; shared by the projection view and exports (see `worker::Projection`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct GridParams {
+    pub color: [f32; 4],
+    /// Distance between vertical lines, as a fraction of half of the map height.
+    pub horz_spacing: f32,
+    /// Distance between horizontal lines, as a fraction of half of the map height.
+    pub vert_spacing: f32
+}
+
+pub struct Grid {
     horz_lines: glium::VertexBuffer<data::Vertex2>,
-    vert_lines: glium::VertexBuffer<data::Vertex2>,
-    color: [f32; 4]
+    vert_lines: glium::VertexBuffer<data::Vertex2>
+}
+
+impl Grid {
+    /// Creates grid lines of a map whose width/height ratio is `wh_ratio`.
+    pub fn new<F: ?Sized + glium::backend::Facade>(facade: &F, params: &GridParams, wh_ratio: f32) -> Grid {
+        let (horz_lines, vert_lines) = grid_lines(params, wh_ratio);
+        Grid{
+            horz_lines: glium::VertexBuffer::dynamic(facade, &horz_lines).unwrap(),
+            vert_lines: glium::VertexBuffer::dynamic(facade, &vert_lines).unwrap()
+        }
+    }
+
+    /// Draws the grid over the whole `target` (which shows the north-up map, as rendered by `render_projection`
+    /// without vertical flip).
+    pub fn draw<S: Surface>(
+        &self,
+        target: &mut S,
+        color: [f32; 4],
+        solid_color_2d_prog: &glium::Program
+    ) -> Result<(), glium::DrawError> {
+        let uniforms = uniform! {
+            color: color,
+            vertex_transform: Matrix3::<f32>::identity().to_array()
+        };
+
+        for lines in [&self.vert_lines, &self.horz_lines] {
+            target.draw(
+                lines,
+                &glium::index::NoIndices(glium::index::PrimitiveType::LinesList),
+                solid_color_2d_prog,
+                &uniforms,
+                &glium::DrawParameters{
+                    blend: glium::Blend::alpha_blending(),
+                    ..Default::default()
+                }
+            )?;
+        }
+
+        Ok(())
+    }
 }
 
 pub struct ProjectionView {
@@ -55,6 +267,10 @@ pub struct ProjectionView {
     display: glium::Display,
     source_image: Rc<Texture2d>,
     source_image_idx: usize,
+    /// If set, the view shows this frame instead of following the source view's current frame.
+    pinned_frame: Option<usize>,
+    /// Most recently notified current frame of the source view.
+    live_image: (usize, Rc<Texture2d>),
     src_params: SourceParameters,
     /// Used to generate projection of `source_image`; updated only if `source_image` or projection parameters change.
     projection_draw_buf: DrawBuffer,
@@ -62,12 +278,46 @@ pub struct ProjectionView {
     display_draw_buf: DrawBuffer,
     projection_prog: Rc<glium::Program>,
     texture_copy_prog: Rc<glium::Program>,
+    display_stretch_prog: Rc<glium::Program>,
     solid_color_2d_prog: Rc<glium::Program>,
     unit_quad: Rc<glium::VertexBuffer<data::Vertex2>>,
     wh_ratio: f32,
-    rotation_comp: Option<f32>, // `None` means "automatic" (based on rotation period, disk diameter and frame interval)
+    /// Longitude shift per frame; `None` means "automatic" (based on rotation period and frame interval).
+    rotation_comp: Option<Deg<f32>>,
+    /// Rotation compensation is edited in degrees (instead of pixels) per frame.
+    rotation_comp_in_degrees: bool,
     grid: Grid,
-    projection_type: ProjectionType
+    display_settings: DisplaySettings,
+    projection_type: ProjectionType,
+    /// Standard parallel (the latitude without shape distortion) of the Lambert cylindrical equal-area projection.
+    standard_parallel: Deg<f32>,
+    /// Width (as a fraction of the disk radius) of the band along the limb within which the projected image fades out.
+    limb_feather: f32,
+    /// The diagnostic longitude/latitude pattern is shown instead of the source image (not used for exports).
+    diagnostic_rendering: bool,
+    display_orientation: projection::DisplayOrientation,
+    /// Rendering of `projection_draw_buf` has failed and is to be repeated.
+    projection_pending: bool,
+    /// Rendering of `display_draw_buf` has failed and is to be repeated.
+    render_pending: Cell<bool>,
+    verification: Verification,
+    /// Comparison with the map remembered when the last export of this view started.
+    export_comparison: ExportComparison,
+    /// Shown from the export dialog.
+    export_preview: ExportPreview,
+    /// Applied only when creating `display_draw_buf`.
+    stretch: DisplayStretch,
+    /// The view's window is to be focused in the next GUI frame.
+    focus_requested: bool,
+    /// File to save the current map to, awaiting confirmation of overwriting.
+    pending_map_path: Option<PathBuf>,
+    crossfade: Crossfade,
+    render_throttle: RenderThrottle,
+    resize_debounce: ResizeDebounce,
+    /// Projection settings have changed since the last `take_settings_changed`.
+    settings_changed: bool,
+    /// The view's window has been focused since the last `take_focused`.
+    focused: bool
 }
 
 impl ProjectionView {
@@ -79,22 +329,22 @@ impl ProjectionView {
         source_image: &Rc<Texture2d>,
         source_image_idx: usize,
         src_params: SourceParameters,
-        rotation_comp: f32
+        rotation_comp: f32,
+        display_orientation: projection::DisplayOrientation,
+        display_settings: DisplaySettings
     ) -> ProjectionView {
-        assert!(rotation_comp >= 0.0);
-
-        let projection_draw_buf = DrawBuffer::new_with_size(
+        let mut projection_draw_buf = DrawBuffer::new_with_size(
             Sampling::Single,
             &gl_objects.texture_copy_single,
             &gl_objects.texture_copy_multi,
             &gl_objects.unit_quad,
             display,
             renderer,
-            (src_params.disk_diameter * PI_2 + (src_params.num_images - 1) as f32 * rotation_comp).ceil() as u32,
+            strip_width(&src_params, rotation_comp).ceil() as u32,
             (src_params.disk_diameter * PI_2).ceil() as u32,
         );
 
-        let display_draw_buf = DrawBuffer::new(
+        let mut display_draw_buf = DrawBuffer::new(
             Sampling::Single,
             &gl_objects.texture_copy_single,
             &gl_objects.texture_copy_multi,
@@ -103,6 +353,11 @@ impl ProjectionView {
             renderer
         );
 
+        let owner = format!("Projection view {}", unique_id);
+        projection_draw_buf.register(&owner, "projection");
+        display_draw_buf.register(&owner, "display");
+        let verification = Verification::new(gl_objects, display, renderer, &owner);
+
         let wh_ratio = projection_draw_buf.width() as f32 / projection_draw_buf.height() as f32;
 
         let mut projection_view = ProjectionView{
@@ -110,17 +365,39 @@ impl ProjectionView {
             display: display.clone(),
             projection_prog: Rc::clone(&gl_objects.projection),
             texture_copy_prog: Rc::clone(&gl_objects.texture_copy_single),
+            display_stretch_prog: Rc::clone(&gl_objects.display_stretch),
             solid_color_2d_prog: Rc::clone(&gl_objects.solid_color_2d),
             projection_draw_buf,
             display_draw_buf,
             unit_quad: Rc::clone(&gl_objects.unit_quad),
             source_image: Rc::clone(source_image),
             source_image_idx,
+            pinned_frame: None,
+            live_image: (source_image_idx, Rc::clone(source_image)),
             src_params,
             wh_ratio,
-            rotation_comp: Some(0.0),
-            grid: create_grid(display, false, wh_ratio, 0.25, 0.25, 0.75),
-            projection_type: ProjectionType::Equirectangular
+            rotation_comp: Some(Deg(0.0)),
+            rotation_comp_in_degrees: false,
+            grid: Grid::new(display, &display_settings.grid_params(), wh_ratio),
+            display_settings,
+            projection_type: ProjectionType::Equirectangular,
+            standard_parallel: Deg(0.0),
+            limb_feather: 0.0,
+            diagnostic_rendering: false,
+            display_orientation,
+            projection_pending: false,
+            render_pending: Cell::new(false),
+            verification,
+            export_comparison: ExportComparison::new(gl_objects, display, &owner),
+            export_preview: ExportPreview::new(display, renderer),
+            stretch: Default::default(),
+            focus_requested: false,
+            pending_map_path: None,
+            crossfade: Crossfade::new(&owner, display, &gl_objects.crossfade, &gl_objects.unit_quad),
+            render_throttle: RenderThrottle::new(&owner),
+            resize_debounce: Default::default(),
+            settings_changed: false,
+            focused: false
         };
 
         projection_view.on_image_or_projection_changed();
@@ -136,42 +413,128 @@ impl ProjectionView {
         ]
     }
 
-    fn rotation_comp_value(&self) -> f32 {
+    /// Returns rotation compensation in degrees of longitude per frame.
+    fn rotation_comp_degrees(&self) -> Deg<f32> {
         match self.rotation_comp {
-            None => {
-                let sp = &self.src_params;
-                PI_2 * sp.disk_diameter / (0.5 * sp.sidereal_rotation_period.as_secs_f32() / sp.frame_interval.as_secs_f32())
-            },
-
+            None => coverage::rotation_per_frame(&self.src_params),
             Some(value) => value
         }
     }
 
+    /// Returns rotation compensation in pixels per frame (for the current disk diameter).
+    fn rotation_comp_value(&self) -> f32 {
+        rotation_comp_to_pixels(self.rotation_comp_degrees(), self.src_params.disk_diameter)
+    }
+
+    /// Returns intervals of usable longitudes of all frames (see `coverage`).
+    fn coverage_intervals(&self) -> Vec<coverage::Interval> {
+        coverage::frame_intervals(
+            self.src_params.num_images,
+            coverage::rotation_per_frame(&self.src_params),
+            coverage::usable_half_width(self.display_settings.max_emission_angle, self.src_params.inclination)
+        )
+    }
+
     fn on_image_or_projection_changed(&mut self) {
+        self.stretch.invalidate_histogram();
+        if !self.render_throttle.allows(Instant::now()) {
+            // repeated when allowed (see `render_if_pending`)
+            self.projection_pending = true;
+            self.render();
+            return;
+        }
+
+        let projected = render_check::render_checked("projection", || {
+            render_projection(
+                true,
+                self.source_image_idx,
+                &self.source_image,
+                &mut self.projection_draw_buf.frame_buf(),
+                &self.unit_quad,
+                &self.projection_prog,
+                &self.src_params,
+                self.rotation_comp_value(),
+                self.projection_type,
+                self.limb_feather,
+                self.diagnostic_rendering
+            )?;
+
+            self.projection_draw_buf.update_storage_buf()
+        });
+        self.projection_pending = !projected;
+        if projected { self.export_comparison.update_rms(self.projection_draw_buf.storage_buf()); }
+
+        self.render();
+    }
+
+    /// Renders the current map as exported (north-up, without the grid), encoded according to `interpretation`.
+    fn render_map(&self, interpretation: Interpretation) -> Result<ga_image::Image, Box<dyn Error>> {
+        let size = self.projection_size();
+        let texture = Texture2d::empty_with_format(
+            &self.display,
+            glium::texture::UncompressedFloatFormat::U8U8U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            size[0],
+            size[1]
+        )?;
+
         render_projection(
-            true,
+            false,
             self.source_image_idx,
             &self.source_image,
-            &mut self.projection_draw_buf.frame_buf(),
+            &mut texture.as_surface(),
             &self.unit_quad,
             &self.projection_prog,
             &self.src_params,
             self.rotation_comp_value(),
-            self.projection_type
-        );
+            self.projection_type,
+            self.limb_feather,
+            false
+        )?;
 
-        self.projection_draw_buf.update_storage_buf();
+        let mut image = crate::image_utils::image_from_texture(&texture);
+        color::apply_lut(&mut image, &color::encode_lut(interpretation));
 
-        self.render();
+        Ok(image)
+    }
+
+    pub fn pinned_frame(&self) -> Option<usize> { self.pinned_frame }
+
+    /// Makes the view show frame `idx` (whose texture is `image`) until unpinned.
+    pub fn pin_frame(&mut self, idx: usize, image: &Rc<Texture2d>) {
+        self.pinned_frame = Some(idx);
+        if self.source_image_idx != idx || !Rc::ptr_eq(&self.source_image, image) {
+            self.show_frame(idx, image);
+        }
+    }
+
+    /// Makes the view follow the source view's current frame again.
+    pub fn unpin_frame(&mut self) {
+        self.pinned_frame = None;
+        let (idx, image) = self.live_image.clone();
+        self.show_frame(idx, &image);
+    }
+
+    /// Shows frame `idx` (whose texture is `image`); crossfades if it differs from the displayed one.
+    fn show_frame(&mut self, idx: usize, image: &Rc<Texture2d>) {
+        if idx != self.source_image_idx { self.crossfade.on_frame_changed(&self.display_draw_buf); }
+        self.source_image_idx = idx;
+        self.set_source_image(image);
     }
 
+    /// Index of the displayed frame.
+    pub fn displayed_frame(&self) -> usize { self.source_image_idx }
+
     pub fn set_source_image(&mut self, source_image: &Rc<Texture2d>) {
         self.source_image = Rc::clone(&source_image);
         self.on_image_or_projection_changed();
     }
 
     pub fn update_size(&mut self, width: u32, height: u32) {
-        if height == 0 { return; }
+        if width == 0 || height == 0 { return; }
+
+        let current = [self.display_draw_buf.width(), self.display_draw_buf.height()];
+        if !self.resize_debounce.resize_now(current, [width, height], Instant::now()) { return; }
 
         if self.display_draw_buf.update_size(width, height) {
             self.render()
@@ -179,82 +542,137 @@ impl ProjectionView {
     }
 
     fn render(&self) {
-        let mut target = self.display_draw_buf.frame_buf();
+        if !self.render_throttle.begin_rendering(Instant::now()) {
+            self.render_pending.set(true);
+            return;
+        }
+        self.render_pending.set(!render_check::render_checked("projection view", || self.try_render()));
+    }
 
-        let uniforms = uniform! {
-            source_texture: self.projection_draw_buf.storage_buf().sampled(),
-        };
+    /// Renders again if the previous rendering failed or was skipped.
+    fn render_if_pending(&mut self) {
+        if self.projection_pending {
+            self.on_image_or_projection_changed();
+        } else if self.render_pending.get() {
+            self.render();
+        }
+    }
 
-        target.draw(
-            &*self.unit_quad,
-            &glium::index::NoIndices(glium::index::PrimitiveType::TriangleFan),
-            &self.texture_copy_prog,
-            &uniforms,
-            &Default::default()
-        ).unwrap();
+    fn try_render(&self) -> Result<(), glium::DrawError> {
+        let mut target = self.display_draw_buf.frame_buf();
 
-        if self.grid.show {
+        let (black_point, stretch_scale) = self.stretch.levels().uniforms();
+        if self.export_comparison.is_shown() {
+            self.export_comparison.render(
+                &mut target, self.projection_draw_buf.storage_buf(), (black_point, stretch_scale)
+            )?;
+        } else {
             let uniforms = uniform! {
-                color: self.grid.color,
-                vertex_transform: Matrix3::<f32>::identity().to_array()
+                source_texture: self.projection_draw_buf.storage_buf().sampled(),
+                black_point: black_point,
+                stretch_scale: stretch_scale
             };
 
             target.draw(
-                &self.grid.vert_lines,
-                &glium::index::NoIndices(glium::index::PrimitiveType::LinesList),
-                &self.solid_color_2d_prog,
+                &*self.unit_quad,
+                &glium::index::NoIndices(glium::index::PrimitiveType::TriangleFan),
+                &self.display_stretch_prog,
                 &uniforms,
-                &glium::DrawParameters{
-                    blend: glium::Blend::alpha_blending(),
-                    ..Default::default()
-                }
-            ).unwrap();
+                &Default::default()
+            )?;
+        }
 
-            target.draw(
-                &self.grid.horz_lines,
-                &glium::index::NoIndices(glium::index::PrimitiveType::LinesList),
-                &self.solid_color_2d_prog,
-                &uniforms,
-                &glium::DrawParameters{
-                    blend: glium::Blend::alpha_blending(),
-                    ..Default::default()
-                }
-            ).unwrap();
+        if self.display_settings.grid_shown {
+            self.grid.draw(&mut target, self.display_settings.grid_color, &self.solid_color_2d_prog)?;
         }
 
-        self.display_draw_buf.update_storage_buf();
+        self.display_draw_buf.update_storage_buf()?;
+        self.crossfade.apply(&self.display_draw_buf)
     }
 
     fn display_buf_id(&self) -> imgui::TextureId { self.display_draw_buf.id() }
 
     pub fn id(&self) -> u32 { self.unique_id }
 
+    pub fn request_focus(&mut self) { self.focus_requested = true; }
+
+    /// Returns `true` if projection settings have changed since the last call.
+    pub fn take_settings_changed(&mut self) -> bool { std::mem::take(&mut self.settings_changed) }
+
+    /// Returns `true` if the view's window has been focused since the last call.
+    pub fn take_focused(&mut self) -> bool { std::mem::take(&mut self.focused) }
+
+    /// To be called when an export of this view ends; if it has `succeeded`, its map is remembered for comparison.
+    pub fn on_export_ended(&mut self, succeeded: bool) {
+        self.export_comparison.on_export_ended(succeeded, self.projection_draw_buf.storage_buf());
+        self.render();
+    }
+
     pub fn set_projection_type(&mut self, value: ProjectionType) {
         self.projection_type = value;
+        self.settings_changed = true;
+        self.update_projection_buf_size();
+        self.update_grid();
+        self.on_image_or_projection_changed();
+    }
+
+    pub fn set_standard_parallel(&mut self, value: Deg<f32>) {
+        self.standard_parallel = value;
+        self.settings_changed = true;
         self.update_projection_buf_size();
-        self.grid.vert_lines = create_grid_lines(&self.display, self.grid.vert_spacing / self.wh_ratio, false);
+        self.update_grid();
+        self.on_image_or_projection_changed();
+    }
+
+    pub fn set_limb_feather(&mut self, value: f32) {
+        self.limb_feather = value;
+        self.settings_changed = true;
+        self.on_image_or_projection_changed();
+    }
+
+    pub fn diagnostic_rendering(&self) -> bool { self.diagnostic_rendering }
+
+    pub fn set_diagnostic_rendering(&mut self, value: bool) {
+        self.diagnostic_rendering = value;
         self.on_image_or_projection_changed();
     }
 
-    pub fn set_rotation_comp(&mut self, value: Option<f32>) {
+    pub fn set_rotation_comp(&mut self, value: Option<Deg<f32>>) {
         self.rotation_comp = value;
+        self.settings_changed = true;
 
         self.update_projection_buf_size();
 
-        self.grid.vert_lines = create_grid_lines(&self.display, self.grid.vert_spacing / self.wh_ratio, false);
+        self.update_grid();
+
+        self.on_image_or_projection_changed();
+    }
+
+    pub fn projection_settings(&self) -> ProjectionSettings {
+        ProjectionSettings{
+            projection_type: self.projection_type,
+            standard_parallel: self.standard_parallel,
+            rotation_comp: self.rotation_comp
+        }
+    }
 
+    /// Sets all projection settings at once; the projection is re-rendered only once.
+    pub fn apply_projection_settings(&mut self, settings: &ProjectionSettings) {
+        self.projection_type = settings.projection_type;
+        self.standard_parallel = settings.standard_parallel;
+        self.rotation_comp = settings.rotation_comp;
+        self.settings_changed = true;
+        self.update_projection_buf_size();
+        self.update_grid();
         self.on_image_or_projection_changed();
     }
 
     fn update_projection_buf_size(&mut self) {
-        let new_width = (self.src_params.disk_diameter * PI_2 +
-            (self.src_params.num_images - 1) as f32 * self.rotation_comp_value()).ceil() as u32;
+        let new_width = strip_width(&self.src_params, self.rotation_comp_value()).ceil() as u32;
 
-        let new_height = match self.projection_type {
-            ProjectionType::Equirectangular => (self.src_params.disk_diameter * PI_2).ceil() as u32,
+        let new_height = projection_height(self.projection_type, self.src_params.disk_diameter, self.standard_parallel);
 
-            ProjectionType::LambertCylindricalEqualArea => self.src_params.disk_diameter as u32
-        };
+        if new_width == 0 || new_height == 0 { return; }
 
         self.projection_draw_buf.update_size(new_width, new_height);
 
@@ -262,38 +680,113 @@ impl ProjectionView {
     }
 
     pub fn set_grid_horz_spacing(&mut self, spacing: f32) {
-        self.grid.horz_spacing = spacing;
-        self.grid.vert_lines = create_grid_lines(&self.display, spacing / self.wh_ratio, false);
+        self.display_settings.grid_horz_spacing = spacing;
+        self.update_grid();
         self.render();
     }
 
     pub fn set_grid_vert_spacing(&mut self, spacing: f32) {
-        self.grid.vert_spacing = spacing;
-        self.grid.horz_lines = create_grid_lines(&self.display, spacing, true);
+        self.display_settings.grid_vert_spacing = spacing;
+        self.update_grid();
+        self.render();
+    }
+
+    /// Must be called after the grid spacing or the projection's width/height ratio changes.
+    fn update_grid(&mut self) {
+        self.grid = Grid::new(&self.display, &self.display_settings.grid_params(), self.wh_ratio);
+    }
+
+    pub fn display_settings(&self) -> &DisplaySettings { &self.display_settings }
+
+    pub fn apply_display_settings(&mut self, settings: &DisplaySettings) {
+        self.display_settings = settings.clone();
+        self.update_grid();
         self.render();
     }
 }
 
 impl Subscriber<(usize, Rc<Texture2d>)> for ProjectionView {
     fn notify(&mut self, value: &(usize, Rc<Texture2d>)) {
-        self.source_image_idx = value.0;
-        self.set_source_image(&value.1);
+        self.live_image = (value.0, Rc::clone(&value.1));
+        if self.pinned_frame.is_none() {
+            self.show_frame(value.0, &value.1);
+        }
+    }
+}
+
+/// Returns true if changing source parameters from `old` to `new` changes the projection buffer size (the rotation
+/// compensation in pixels depends on the disk diameter, and in "auto" mode also on the rotation per frame).
+pub fn projection_buf_size_affected(old: &SourceParameters, new: &SourceParameters) -> bool {
+    new.disk_diameter != old.disk_diameter
+        || new.num_images != old.num_images
+        || new.frame_interval != old.frame_interval
+        || new.sidereal_rotation_period != old.sidereal_rotation_period
+}
+
+/// Returns the width (in pixels) of the strip holding all frames' projections shifted by `rotation_comp` pixels
+/// per frame (negative for retrograde rotation).
+pub fn strip_width(src_params: &SourceParameters, rotation_comp: f32) -> f32 {
+    PI_2 * src_params.disk_diameter + (src_params.num_images - 1) as f32 * rotation_comp.abs()
+}
+
+/// Placement of a frame's projection within the strip. Used for rendering (`render_projection`) and for describing
+/// exported maps (see `export_metadata`), so that the two always agree.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FramePlacement {
+    /// X offset (in normalized device coordinates) of the frame's central meridian.
+    pub offset: f32,
+    /// Width of the frame's projection (180° of longitude) relative to the strip.
+    pub rel_img_w: f32
+}
+
+impl FramePlacement {
+    /// Frame 0 occupies the right end of the strip for prograde rotation compensation (subsequent frames are shifted
+    /// westwards, i.e., to the left) and the left end for retrograde one.
+    pub fn new(src_params: &SourceParameters, rotation_comp: f32, source_image_idx: usize) -> FramePlacement {
+        let total_width = strip_width(src_params, rotation_comp);
+        let rel_img_w = PI_2 * src_params.disk_diameter / total_width;
+        let rel_comp = rotation_comp / total_width;
+        let first_offset = if rotation_comp < 0.0 { rel_img_w - 1.0 } else { 1.0 - rel_img_w };
+
+        FramePlacement{ offset: first_offset - 2.0 * rel_comp * source_image_idx as f32, rel_img_w }
+    }
+
+    /// Returns the longitude (relative to the frame's central meridian) at the left edge of the strip.
+    pub fn left_edge_longitude(&self) -> Deg<f32> {
+        Deg(90.0 * (-1.0 - self.offset) / self.rel_img_w)
+    }
+
+    /// Returns degrees of longitude per pixel of a strip rendered `strip_width_px` wide.
+    pub fn deg_per_pixel(&self, strip_width_px: u32) -> f32 {
+        180.0 / (self.rel_img_w * strip_width_px as f32)
     }
 }
 
+/// Converts rotation compensation from degrees of longitude to pixels (per frame); a single frame's projection spans
+/// 180° of longitude over `disk_diameter` · π/2 pixels.
+pub fn rotation_comp_to_pixels(degrees: Deg<f32>, disk_diameter: f32) -> f32 {
+    degrees.0 * PI_2 * disk_diameter / 180.0
+}
+
+/// Inverse of `rotation_comp_to_pixels`.
+pub fn rotation_comp_to_degrees(pixels: f32, disk_diameter: f32) -> Deg<f32> {
+    Deg(pixels * 180.0 / (PI_2 * disk_diameter))
+}
+
 impl Subscriber<SourceParameters> for ProjectionView {
     fn notify(&mut self, value: &SourceParameters) {
-        let dd_changed = value.disk_diameter != self.src_params.disk_diameter;
-        let num_images_changed = value.num_images != self.src_params.num_images;
+        // all changes (however many) arrive in a single notification, so the buffer is resized at most once
+        let resize = projection_buf_size_affected(&self.src_params, value);
         self.src_params = value.clone();
-        if dd_changed || num_images_changed {
+        if resize {
             self.update_projection_buf_size();
         }
         self.on_image_or_projection_changed();
     }
 }
 
-fn create_grid_lines(display: &glium::Display, spacing: f32, horizontal: bool) -> glium::VertexBuffer<data::Vertex2> {
+/// Returns endpoints (consecutive pairs of vertices, in normalized device coordinates) of lines spaced by `spacing`.
+fn grid_line_vertices(spacing: f32, horizontal: bool) -> Vec<data::Vertex2> {
     assert!(spacing > 0.0 && spacing < 2.0);
 
     let mut vertices = vec![];
@@ -305,27 +798,212 @@ fn create_grid_lines(display: &glium::Display, spacing: f32, horizontal: bool) -
         pos += spacing;
     }
 
-    glium::VertexBuffer::dynamic(display, &vertices).unwrap()
+    vertices
 }
 
-fn create_grid(
-    display: &glium::Display,
-    show: bool,
-    wh_ratio: f32,
-    horz_spacing: f32,
-    vert_spacing: f32,
-    opacity: f32
-) -> Grid {
-    Grid{
-        show,
-        horz_spacing,
-        vert_spacing,
-        horz_lines: create_grid_lines(display, horz_spacing, true),
-        vert_lines: create_grid_lines(display, vert_spacing * wh_ratio, false),
-        color: [1.0, 0.0, 0.0, opacity]
+/// Returns endpoints of horizontal and vertical grid lines (see `grid_line_vertices`) of a map whose width/height
+/// ratio is `wh_ratio`.
+pub fn grid_lines(params: &GridParams, wh_ratio: f32) -> (Vec<data::Vertex2>, Vec<data::Vertex2>) {
+    (
+        grid_line_vertices(params.vert_spacing, true),
+        grid_line_vertices(params.horz_spacing / wh_ratio, false)
+    )
+}
+
+/// Returns height (in pixels) of the generated projection; its width is always `disk_diameter` · π/2 for a single frame.
+///
+/// For the Lambert cylindrical equal-area projection, there is no shape distortion at the standard parallel φs;
+/// the width-to-height ratio is (π/2)·cos²(φs). The mapping of normalized vertical coordinate to latitude does not
+/// depend on φs (see `lambert_normalized_y`), only the image height does.
+pub fn projection_height(projection_type: ProjectionType, disk_diameter: f32, standard_parallel: Deg<f32>) -> u32 {
+    match projection_type {
+        ProjectionType::Equirectangular => (disk_diameter * PI_2).ceil() as u32,
+
+        ProjectionType::LambertCylindricalEqualArea => {
+            let cos_sp = standard_parallel.cos();
+            (disk_diameter / (cos_sp * cos_sp)).round() as u32
+        }
+    }
+}
+
+/// Returns the size (in pixels) of the buffer the projection strip is rendered into (also by the worker in exports).
+pub fn map_size(
+    src_params: &SourceParameters,
+    rotation_comp: f32,
+    projection_type: ProjectionType,
+    standard_parallel: Deg<f32>
+) -> [u32; 2] {
+    [
+        strip_width(src_params, rotation_comp).ceil() as u32,
+        projection_height(projection_type, src_params.disk_diameter, standard_parallel)
+    ]
+}
+
+/// Returns normalized (within [0; 1], from south to north) vertical position of `latitude` in the Lambert cylindrical
+/// equal-area projection (CPU equivalent of the mapping in `projection.frag`).
+pub fn lambert_normalized_y(latitude: Deg<f32>) -> f32 {
+    (latitude.sin() + 1.0) / 2.0
+}
+
+/// Returns transformation from globe coordinates to normalized image disk coordinates (see `projection.frag`).
+pub fn globe_transform(src_params: &SourceParameters) -> Matrix3<f32> {
+    rolled_globe_transform(src_params, src_params.roll)
+}
+
+/// Returns `globe_transform` for the given frame (taking per-frame roll into account).
+pub fn frame_globe_transform(src_params: &SourceParameters, frame_idx: usize) -> Matrix3<f32> {
+    rolled_globe_transform(src_params, src_params.frame_roll(frame_idx))
+}
+
+fn rolled_globe_transform(src_params: &SourceParameters, roll: Deg<f32>) -> Matrix3<f32> {
+    let flattening_transform = Matrix3::<f32>::from_nonuniform_scale(1.0, 1.0 - src_params.flattening);
+    let inclination_transform = cgmath::Basis3::from_angle_x(src_params.inclination);
+    let roll_transform = cgmath::Basis3::from_angle_z(roll);
+
+    Matrix3::from(roll_transform) * Matrix3::from(inclination_transform) * flattening_transform
+}
+
+/// Returns position (in pixels) in the source image sampled for the given globe coordinates by the projection
+/// (CPU equivalent of the mapping in `projection.frag`).
+pub fn source_image_position(src_params: &SourceParameters, longitude: Deg<f32>, latitude: Deg<f32>) -> Point2<f32> {
+    transformed_image_position(src_params, &globe_transform(src_params), longitude, latitude)
+}
+
+/// Like `source_image_position`, but for the given frame (taking per-frame roll into account, as `render_projection`).
+pub fn frame_source_image_position(
+    src_params: &SourceParameters,
+    frame_idx: usize,
+    longitude: Deg<f32>,
+    latitude: Deg<f32>
+) -> Point2<f32> {
+    transformed_image_position(src_params, &frame_globe_transform(src_params, frame_idx), longitude, latitude)
+}
+
+fn transformed_image_position(
+    src_params: &SourceParameters,
+    transform: &Matrix3<f32>,
+    longitude: Deg<f32>,
+    latitude: Deg<f32>
+) -> Point2<f32> {
+    let disk_pos = transform * globe_position(longitude, latitude);
+    let mirror = src_params.image_mirror();
+
+    let mirrored_disk_pos = Vector2{ x: disk_pos.x * mirror[0], y: disk_pos.y * mirror[1] };
+
+    src_params.disk_center + mirrored_disk_pos * src_params.disk_diameter / 2.0
+}
+
+/// Returns true if the projection samples the source image (of `image_size`) within its bounds for the given globe
+/// coordinates; the disk may extend outside the image, and such parts produce no contribution (CPU equivalent of the
+/// bounds test in `projection.frag`).
+pub fn within_source_image(
+    src_params: &SourceParameters,
+    image_size: [u32; 2],
+    longitude: Deg<f32>,
+    latitude: Deg<f32>
+) -> bool {
+    let pos = source_image_position(src_params, longitude, latitude);
+    pos.x >= 0.0 && pos.y >= 0.0 && pos.x <= image_size[0] as f32 && pos.y <= image_size[1] as f32
+}
+
+/// Returns (longitude, latitude) of the visible globe point at `position` (in pixels) in the source image; `None`
+/// if it is outside the disk (CPU equivalent of the mapping in `reprojection.frag`, the inverse of
+/// `source_image_position`).
+pub fn globe_coords(src_params: &SourceParameters, position: Point2<f32>) -> Option<(Deg<f32>, Deg<f32>)> {
+    transformed_globe_coords(src_params, &globe_transform(src_params), position)
+}
+
+/// Like `globe_coords`, but for the given frame (taking per-frame roll into account; inverse
+/// of `frame_source_image_position`).
+pub fn frame_globe_coords(
+    src_params: &SourceParameters,
+    frame_idx: usize,
+    position: Point2<f32>
+) -> Option<(Deg<f32>, Deg<f32>)> {
+    transformed_globe_coords(src_params, &frame_globe_transform(src_params, frame_idx), position)
+}
+
+fn transformed_globe_coords(
+    src_params: &SourceParameters,
+    transform: &Matrix3<f32>,
+    position: Point2<f32>
+) -> Option<(Deg<f32>, Deg<f32>)> {
+    let mirror = src_params.image_mirror();
+    let rel_pos = (position - src_params.disk_center) * 2.0 / src_params.disk_diameter;
+    let disk_pos = Vector3{ x: rel_pos.x * mirror[0], y: rel_pos.y * mirror[1], z: 0.0 };
+
+    // the line of sight (along Z) intersects the globe where |a + z·b| = 1; the nearer intersection has the larger z
+    let inverse = transform.invert()?;
+    let a = inverse * disk_pos;
+    let b = inverse * Vector3::unit_z();
+    let (qa, qb, qc) = (b.dot(b), 2.0 * a.dot(b), a.dot(a) - 1.0);
+    let discriminant = qb * qb - 4.0 * qa * qc;
+    if discriminant < 0.0 { return None; }
+
+    let globe_pos = a + b * (-qb + discriminant.sqrt()) / (2.0 * qa);
+
+    Some((Deg::atan2(globe_pos.x, globe_pos.z), Deg::asin(globe_pos.y.max(-1.0).min(1.0))))
+}
+
+/// Returns globe coordinates (see `projection.frag`) of the given point on a unit sphere.
+pub fn globe_position(longitude: Deg<f32>, latitude: Deg<f32>) -> Vector3<f32> {
+    Vector3{
+        x: latitude.cos() * longitude.sin(),
+        y: latitude.sin(),
+        z: latitude.cos() * longitude.cos()
     }
 }
 
+pub fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).max(0.0).min(1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Returns opacity of the projection at the given globe coordinates if it fades out within `limb_feather` (fraction
+/// of the disk radius) of the limb (CPU equivalent of the fading in `projection.frag`).
+pub fn limb_feather_alpha(
+    src_params: &SourceParameters,
+    longitude: Deg<f32>,
+    latitude: Deg<f32>,
+    limb_feather: f32
+) -> f32 {
+    transformed_limb_feather_alpha(src_params, &globe_transform(src_params), longitude, latitude, limb_feather)
+}
+
+/// Like `limb_feather_alpha`, but for the given frame (taking per-frame roll into account, as `render_projection`).
+pub fn frame_limb_feather_alpha(
+    src_params: &SourceParameters,
+    frame_idx: usize,
+    longitude: Deg<f32>,
+    latitude: Deg<f32>,
+    limb_feather: f32
+) -> f32 {
+    transformed_limb_feather_alpha(
+        src_params, &frame_globe_transform(src_params, frame_idx), longitude, latitude, limb_feather
+    )
+}
+
+fn transformed_limb_feather_alpha(
+    src_params: &SourceParameters,
+    transform: &Matrix3<f32>,
+    longitude: Deg<f32>,
+    latitude: Deg<f32>,
+    limb_feather: f32
+) -> f32 {
+    if limb_feather <= 0.0 { return 1.0; }
+
+    let globe_pos = globe_position(longitude, latitude);
+    let polar_scale = 1.0 - src_params.flattening;
+    let normal = transform * Vector3{
+        x: globe_pos.x,
+        y: globe_pos.y / (polar_scale * polar_scale),
+        z: globe_pos.z
+    };
+    let radius = if normal.z > 0.0 { normal.truncate().magnitude() / normal.magnitude() } else { 1.0 };
+
+    1.0 - smoothstep(1.0 - limb_feather, 1.0, radius)
+}
+
 pub fn render_projection(
     vertical_flip: bool,
     source_image_idx: usize,
@@ -335,23 +1013,17 @@ pub fn render_projection(
     projection_prog: &glium::Program,
     src_params: &SourceParameters,
     rotation_comp: f32,
-    projection_type: ProjectionType
-) {
-    let flattening_transform = Matrix3::<f32>::from_nonuniform_scale(1.0, 1.0 - src_params.flattening);
-    let inclination_transform = cgmath::Basis3::from_angle_x(src_params.inclination);
-    let roll_transform = cgmath::Basis3::from_angle_z(src_params.roll);
-    let globe_transform = Matrix3::from(roll_transform) * Matrix3::from(inclination_transform) * flattening_transform;
+    projection_type: ProjectionType,
+    limb_feather: f32,
+    diagnostic: bool
+) -> Result<(), glium::DrawError> {
+    let globe_transform = frame_globe_transform(src_params, source_image_idx);
+    let sun_direction: [f32; 3] = phase::sun_vector(src_params).into();
 
-    let img_width = PI_2 * src_params.disk_diameter;
-    let total_width = img_width + (src_params.num_images - 1) as f32 * rotation_comp;
-    let rel_img_w = img_width / total_width;
-    let rel_comp = rotation_comp / total_width;
+    let FramePlacement{ offset, rel_img_w } = FramePlacement::new(src_params, rotation_comp, source_image_idx);
 
     let image_transform: Matrix3<f32> =
-        Matrix3::from_translation(Vector2{
-            x: 1.0 - rel_img_w - 2.0 * rel_comp * source_image_idx as f32,
-            y: 0.0
-        }) *
+        Matrix3::from_translation(Vector2{ x: offset, y: 0.0 }) *
         Matrix3::from_nonuniform_scale(rel_img_w, if vertical_flip { -1.0 } else { 1.0 });
 
     let uniforms = uniform! {
@@ -359,7 +1031,15 @@ pub fn render_projection(
         disk_diameter: src_params.disk_diameter,
         disk_center: src_params.disk_center.to_array(),
         globe_transform: globe_transform.to_array(),
+        image_mirror: src_params.image_mirror(),
         vertex_transform: image_transform.to_array(),
+        gain: src_params.frame_gain(source_image_idx),
+        flattening: src_params.flattening,
+        limb_feather,
+        diagnostic,
+        phase_enabled: src_params.phase.is_enabled(),
+        sun_direction,
+        terminator_margin: src_params.phase.terminator_margin.sin(),
         equirectangular: match projection_type {
             ProjectionType::Equirectangular => true,
             ProjectionType::LambertCylindricalEqualArea => false,
@@ -374,71 +1054,441 @@ pub fn render_projection(
         projection_prog,
         &uniforms,
         &Default::default()
-    ).unwrap();
+    )
 }
 
-/// Returns `false` if view should be closed.
-pub fn handle_projection_view(
-    ui: &imgui::Ui,
-    gui_state: &mut gui::GuiState,
-    config: &mut Configuration,
-    view: &mut ProjectionView,
-    source_view: &SourceView,
-    long_task_dialog: &RefCell<Option<LongTaskDialog>>,
-    task_sender: &crossbeam::channel::Sender<worker::MainToWorkerMsg>,
-    export_dialog: &RefCell<ExportDialog>
-) -> bool {
-    let mut opened = true;
+/// Returns (longitude relative to the central meridian, latitude) at normalized position `pos` (within [0; 1], from
+/// top-left) of the north-up projection of frame `source_image_idx`; `None` if `pos` is outside the frame.
+pub fn projection_coords(
+    pos: [f32; 2],
+    source_image_idx: usize,
+    src_params: &SourceParameters,
+    rotation_comp: f32,
+    projection_type: ProjectionType
+) -> Option<(Deg<f32>, Deg<f32>)> {
+    // inverse of the frame placement in `render_projection`
+    let FramePlacement{ offset, rel_img_w } = FramePlacement::new(src_params, rotation_comp, source_image_idx);
 
-    let mut export_clicked = false;
+    let frame_x = (2.0 * pos[0] - 1.0 - offset) / rel_img_w;
+    if !(-1.0..=1.0).contains(&frame_x) || !(0.0..=1.0).contains(&pos[1]) { return None; }
 
-    imgui::Window::new(ui, &format!("Projection###projection-view-{}", view.id()))
-        .size([640.0, 640.0], imgui::Condition::FirstUseEver)
-        .opened(&mut opened)
-        .horizontal_scrollbar(true)
-        .build(|| {
-            if ui.button("Export...") { export_clicked = true; }
+    let y = 1.0 - 2.0 * pos[1];
+    let latitude = match projection_type {
+        ProjectionType::Equirectangular => Deg(90.0 * y),
+        ProjectionType::LambertCylindricalEqualArea => Deg::asin(y)
+    };
 
-            ui.separator();
+    Some((Deg(90.0 * frame_x), latitude))
+}
 
-            if ui.radio_button_bool("equirectangular", view.projection_type == ProjectionType::Equirectangular) {
-                view.set_projection_type(ProjectionType::Equirectangular);
-            }
+/// Returns longitudes (as used by `coverage`) at the left and right edge of the north-up projection.
+pub fn map_longitude_range(src_params: &SourceParameters, rotation_comp: f32) -> (f32, f32) {
+    // frame 0 occupies the rightmost (leftmost for retrograde rotation) `img_width` pixels (180° of longitude)
+    // with its central meridian in the middle
+    let img_width = PI_2 * src_params.disk_diameter;
+    let total_width = strip_width(src_params, rotation_comp);
+    let deg_per_pixel = 180.0 / img_width;
+
+    if rotation_comp < 0.0 {
+        (-img_width / 2.0 * deg_per_pixel, (total_width - img_width / 2.0) * deg_per_pixel)
+    } else {
+        (-(total_width - img_width / 2.0) * deg_per_pixel, img_width / 2.0 * deg_per_pixel)
+    }
+}
+
+/// Returns color going from red (no frames) through yellow to green (`COVERAGE_BAR_MANY_FRAMES` or more).
+fn coverage_color(num_frames: u32) -> [f32; 4] {
+    let t = num_frames.min(COVERAGE_BAR_MANY_FRAMES) as f32 / COVERAGE_BAR_MANY_FRAMES as f32;
+    if t < 0.5 { [1.0, 2.0 * t, 0.0, 1.0] } else { [2.0 * (1.0 - t), 1.0, 0.0, 1.0] }
+}
+
+/// Draws the coverage bar of `width` (aligned with the map shown below it) at the cursor position.
+fn draw_coverage_bar(ui: &imgui::Ui, view: &ProjectionView, width: f32) {
+    let num_bins = ((width / 2.0) as usize).max(1).min(MAX_COVERAGE_BAR_BINS);
+    let (start, end) = map_longitude_range(&view.src_params, view.rotation_comp_value());
+    let counts = coverage::count_coverage(&view.coverage_intervals(), start, end, num_bins);
+
+    let pos = ui.cursor_screen_pos();
+    let bin_width = width / num_bins as f32;
+    let draw_list = ui.get_window_draw_list();
+    for display_bin in 0..num_bins {
+        // the map may be displayed flipped horizontally
+        let data_x = view.display_orientation.to_data_position([(display_bin as f32 + 0.5) / num_bins as f32, 0.0])[0];
+        let count = counts[((data_x * num_bins as f32) as usize).min(num_bins - 1)];
+
+        let x = pos[0] + display_bin as f32 * bin_width;
+        draw_list
+            .add_rect([x, pos[1]], [x + bin_width, pos[1] + COVERAGE_BAR_HEIGHT], coverage_color(count))
+            .filled(true)
+            .build();
+    }
+
+    ui.dummy([width, COVERAGE_BAR_HEIGHT]);
+    gui::tooltip(ui, "Number of frames providing usable data at each longitude (red: none, green: many). \
+        Aligned with the map if the rotation compensation matches the planet's rotation (e.g., \"auto\").");
+}
+
+/// Shows the rotation accumulated by the last frame and how much it may be off due to rounding of the frame interval
+/// (entered per captured frame, i.e., before load decimation).
+fn handle_interval_drift(ui: &imgui::Ui, format: &fmt::Preferences, view: &ProjectionView, decimation: u32) {
+    let params = &view.src_params;
+    let period = params.sidereal_rotation_period;
+    let capture_interval = params.frame_interval / decimation.max(1);
+    let second = std::time::Duration::from_secs(1);
+    let to_pixels = |degrees: f64| rotation_comp_to_pixels(Deg(degrees as f32), params.disk_diameter);
+
+    let total = coverage::accumulated_rotation(params.num_images, params.frame_interval, period);
+    ui.text(format!("rotation by the last frame: {}", fmt::format_angle(Deg(total as f32), 2, format)));
+
+    // the error of the captured frame interval accumulates `decimation` times per frame
+    let drift = if capture_interval.subsec_nanos() == 0 {
+        let drift = coverage::max_interval_rounding_drift(params.num_images, second, period) * decimation as f64;
+        ui.text(format!(
+            "whole-second interval: error up to ±{} ({} px)",
+            fmt::format_angle(Deg(drift as f32), 2, format),
+            fmt::format_number(to_pixels(drift) as f64, 1, format)
+        ));
+        drift
+    } else {
+        let drift = coverage::interval_rounding_drift(params.num_images, capture_interval, second, period)
+            * decimation as f64;
+        ui.text(format!(
+            "interval rounded to {}: {} ({} px) off",
+            fmt::format_duration(coverage::rounded_interval(capture_interval, second), format),
+            fmt::format_angle(Deg(drift as f32), 2, format),
+            fmt::format_number(to_pixels(drift) as f64, 1, format)
+        ));
+        drift
+    };
+    gui::tooltip(ui, "Misalignment of the last frame's projection caused by a frame interval error.");
+
+    // vertical grid lines are spaced by a fraction of half of the map height
+    let grid_spacing = view.display_settings.grid_params().horz_spacing * view.projection_size()[1] as f32 / 2.0;
+    if to_pixels(drift).abs() > grid_spacing / 2.0 {
+        ui.text_colored(DRIFT_WARNING_COLOR, "Exceeds half of the grid spacing; enter a more precise frame interval \
+            (e.g., from capture timestamps) in the source view.");
+    }
+}
+
+/// Position of the mouse cursor over a projection view, shown as a crosshair in the other views (at the same
+/// planetary feature) if linking is enabled.
+#[derive(Default)]
+pub struct LinkedCursor {
+    enabled: Cell<bool>,
+    /// Linking is offered only if there are at least 2 projection views.
+    available: Cell<bool>,
+    /// (ID of the hovered view, planetary longitude (see `planet_longitude`), latitude) published in the current
+    /// GUI frame.
+    current: Cell<Option<(u32, Deg<f32>, Deg<f32>)>>,
+    /// As `current`, but from the previous GUI frame (views shown before the hovered one have not seen the current
+    /// position yet).
+    previous: Cell<Option<(u32, Deg<f32>, Deg<f32>)>>
+}
+
+impl LinkedCursor {
+    /// To be called at the start of each GUI frame, before handling the projection views.
+    pub fn new_frame(&self, num_projection_views: usize) {
+        self.previous.set(self.current.take());
+        self.available.set(num_projection_views >= 2);
+    }
+
+    fn is_active(&self) -> bool { self.available.get() && self.enabled.get() }
+
+    fn publish(&self, view_id: u32, longitude: Deg<f32>, latitude: Deg<f32>) {
+        if self.is_active() { self.current.set(Some((view_id, longitude, latitude))); }
+    }
+
+    /// Returns the (planetary longitude, latitude) of the cursor over another view than `view_id`.
+    fn position_for(&self, view_id: u32) -> Option<(Deg<f32>, Deg<f32>)> {
+        if !self.is_active() { return None; }
+
+        match self.current.get().or(self.previous.get()) {
+            Some((id, lon, lat)) if id != view_id => Some((lon, lat)),
+            _ => None
+        }
+    }
+}
+
+/// Returns the longitude relative to the central meridian of frame 0 of the point at `longitude` relative to
+/// the central meridian of frame `source_image_idx`; it is the same for a given planetary feature in all frames.
+pub fn planet_longitude(src_params: &SourceParameters, source_image_idx: usize, longitude: Deg<f32>) -> Deg<f32> {
+    // features move towards higher longitudes with time
+    longitude - coverage::rotation_per_frame(src_params) * source_image_idx as f32
+}
+
+/// Inverse of `projection_coords` for the given planetary longitude (see `planet_longitude`); returns `None` if
+/// the point is not in the projection of frame `source_image_idx`.
+pub fn projection_position(
+    planet_longitude: Deg<f32>,
+    latitude: Deg<f32>,
+    source_image_idx: usize,
+    src_params: &SourceParameters,
+    rotation_comp: f32,
+    projection_type: ProjectionType
+) -> Option<[f32; 2]> {
+    let rotation = coverage::rotation_per_frame(src_params) * source_image_idx as f32;
+    let longitude = (planet_longitude + rotation).normalize_signed();
+    if longitude.0.abs() > 90.0 { return None; }
+
+    let FramePlacement{ offset, rel_img_w } = FramePlacement::new(src_params, rotation_comp, source_image_idx);
+    let frame_x = longitude.0 / 90.0;
+    let y = match projection_type {
+        ProjectionType::Equirectangular => latitude.0 / 90.0,
+        ProjectionType::LambertCylindricalEqualArea => latitude.sin()
+    };
+
+    Some([(frame_x * rel_img_w + offset + 1.0) / 2.0, (1.0 - y) / 2.0])
+}
+
+/// Draws a crosshair at the linked cursor position (normalized, in the north-up image) over the view's image
+/// shown at `img_pos` with `size`; also shows the coordinates in the image's corner.
+fn draw_linked_cursor(
+    ui: &imgui::Ui,
+    format: &fmt::Preferences,
+    view: &ProjectionView,
+    img_pos: [f32; 2],
+    size: [f32; 2],
+    planet_longitude: Deg<f32>,
+    latitude: Deg<f32>
+) {
+    let draw_list = ui.get_window_draw_list();
+
+    let pos = match projection_position(
+        planet_longitude, latitude, view.source_image_idx, &view.src_params, view.rotation_comp_value(),
+        view.projection_type
+    ) {
+        Some(pos) => pos,
+        None => {
+            draw_list.add_text(img_pos, LINKED_CURSOR_COLOR, "linked cursor: not in this frame");
+            return;
+        }
+    };
+    // the conversion is its own inverse
+    let display_pos = view.display_orientation.to_data_position(pos);
+    let x = img_pos[0] + display_pos[0] * size[0];
+    let y = img_pos[1] + display_pos[1] * size[1];
+
+    draw_list.add_line([x, img_pos[1]], [x, img_pos[1] + size[1]], LINKED_CURSOR_COLOR).build();
+    draw_list.add_line([img_pos[0], y], [img_pos[0] + size[0], y], LINKED_CURSOR_COLOR).build();
+
+    let longitude = planet_longitude + coverage::rotation_per_frame(&view.src_params) * view.source_image_idx as f32;
+    draw_list.add_text(img_pos, LINKED_CURSOR_COLOR, format!(
+        "linked cursor: lon. {} (from central meridian), lat. {}",
+        fmt::format_angle(longitude.normalize_signed(), 1, format),
+        fmt::format_angle(latitude, 1, format)
+    ));
+}
+
+/// Returns `false` if view should be closed. Sets `display_settings_broadcast` if the user wants to apply the view's
+/// display settings to all projection views.
+pub fn handle_projection_view(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
+    view: &mut ProjectionView,
+    source_view: &SourceView,
+    long_task_dialog: &RefCell<Option<LongTaskDialog>>,
+    task_sender: &crossbeam::channel::Sender<worker::MainToWorkerMsg>,
+    export_dialog: &RefCell<ExportDialog>,
+    export_result: &RefCell<Option<crossbeam::channel::Receiver<worker::ProjectionResultMsg>>>,
+    export_lock: &RefCell<Option<ExportLock>>,
+    linked_cursor: &LinkedCursor,
+    display_settings_broadcast: &mut Option<DisplaySettings>,
+    globe_target: &mut Option<GlobeTarget>,
+    long_fg_task: &RefCell<Option<Box<dyn LongForegroundTask>>>,
+    texture_limits: &TextureLimits,
+    unsaved: bool
+) -> bool {
+    let mut opened = true;
+
+    let mut export_clicked = false;
+    let mut save_map_clicked = false;
+    let task_in_progress = long_task_dialog.borrow().is_some();
+    let locked = export_lock.borrow().as_ref().map_or(false, |lock| lock.locks_projection_view(view.id()));
+
+    update_pinned_frame(view, source_view);
+    view.render_throttle.set_app_focused(gui_state.app_focused());
+    view.crossfade.set_duration(gui::crossfade::duration(gui_state, projection::frame_crossfade_duration(config)));
+    if view.crossfade.step() { view.render(); }
+    view.render_if_pending();
+
+    let focus_requested = std::mem::replace(&mut view.focus_requested, false);
+
+    let mut visible = false;
+    let title = format!(
+        "Projection - frame {}{}",
+        view.displayed_frame() + 1,
+        if view.pinned_frame().is_some() { " (pinned)" } else { "" }
+    );
+    imgui::Window::new(ui, &format!("{}###projection-view-{}", projection::marked_title(&title, unsaved), view.id()))
+        .size([640.0, 640.0], imgui::Condition::FirstUseEver)
+        .opened(&mut opened)
+        .horizontal_scrollbar(true)
+        .focused(focus_requested)
+        .build(|| {
+            visible = true;
+            if view.render_throttle.set_visible(true) { view.render_if_pending(); }
+            if ui.is_window_focused_with_flags(imgui::WindowFocusedFlags::ROOT_AND_CHILD_WINDOWS) {
+                view.focused = true;
+            }
+
+            // only one task (e.g., a backgrounded export) may run at a time
+            let token = ui.begin_disabled(task_in_progress);
+            if ui.button("Export...") { export_clicked = true; }
+            ui.same_line();
+            if ui.button("Save current map...") { save_map_clicked = true; }
+            token.end();
+            gui::tooltip(ui, "Save the map of the displayed frame (without the grid) to a single file.");
+
+            ui.same_line();
+            if let Some(pinned) = projection::handle_frame_pin_controls(
+                ui, view.id(), view.pinned_frame(), view.live_image.0, source_view.num_images()
+            ) {
+                match pinned {
+                    Some(idx) => view.pin_frame(idx, source_view.image(idx)),
+                    None => view.unpin_frame()
+                }
+            }
+
+            ui.same_line();
+            projection::handle_display_orientation_controls(ui, view.id(), config, &mut view.display_orientation);
+
+            if linked_cursor.available.get() {
+                ui.same_line();
+                let mut enabled = linked_cursor.enabled.get();
+                if ui.checkbox(&format!("link cursors##{}", view.id()), &mut enabled) {
+                    linked_cursor.enabled.set(enabled);
+                }
+                gui::tooltip(ui, "Shows the mouse cursor position over a projection view in all the others.");
+            }
+
+            let view_id = view.id();
+            if export_comparison::handle_export_comparison(
+                ui,
+                gui_state,
+                &mut view.export_comparison,
+                view_id,
+                view.projection_draw_buf.storage_buf(),
+                view.source_image_idx
+            ) {
+                view.render();
+            }
+
+            ui.separator();
+
+            // settings used by the running export
+            let lock_group = ui.begin_group();
+            let lock_token = ui.begin_disabled(locked);
+
+            if ui.radio_button_bool("equirectangular", view.projection_type == ProjectionType::Equirectangular) {
+                view.set_projection_type(ProjectionType::Equirectangular);
+            }
 
             ui.same_line();
             if ui.radio_button_bool("Lambert equal-area", view.projection_type == ProjectionType::LambertCylindricalEqualArea) {
                 view.set_projection_type(ProjectionType::LambertCylindricalEqualArea);
             }
 
+            if view.projection_type == ProjectionType::LambertCylindricalEqualArea {
+                gui::add_text_before(ui, "standard parallel");
+                gui::tooltip(ui, "Latitude without shape distortion (e.g., 30° for Behrmann, 45° for Gall-Peters).");
+                let mut value = view.standard_parallel.0;
+                if imgui::Slider::new("##standard-parallel", 0.0, MAX_STANDARD_PARALLEL)
+                    .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                    .display_format("%0.1f°")
+                    .build(ui, &mut value)
+                {
+                    view.set_standard_parallel(Deg(value));
+                }
+            }
+
+            gui::add_text_before(ui, "limb feather");
+            gui::tooltip(ui, "Fades out the image near the limb (as a percentage of the disk radius) to suppress \
+                limb artifacts.");
+            let mut value = view.limb_feather * 100.0;
+            if imgui::Slider::new("##limb-feather", 0.0, MAX_LIMB_FEATHER_PERCENT)
+                .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                .display_format("%0.1f%%")
+                .build(ui, &mut value)
+            {
+                view.set_limb_feather(value / 100.0);
+            }
+
             gui::add_text_before(ui, "rotation comp.");
-            gui::tooltip(ui, "Planet rotation compensation.");
+            gui::tooltip(ui, "Planet rotation compensation; negative for retrograde rotation.");
 
             let mut rot_comp_auto = view.rotation_comp.is_none();
             if ui.checkbox("auto##rotation-comp-auto", &mut rot_comp_auto) {
-                view.set_rotation_comp(if rot_comp_auto { None } else { Some(view.rotation_comp_value()) });
+                view.set_rotation_comp(if rot_comp_auto { None } else { Some(view.rotation_comp_degrees()) });
             }
             ui.same_line();
 
             let token = ui.begin_disabled(rot_comp_auto);
-            let mut value = view.rotation_comp_value();
-            if imgui::Slider::new("##rotation-comp", 0.0, 10.0)
-                .flags(imgui::SliderFlags::ALWAYS_CLAMP)
-                .display_format("%0.3f px/frame")
-                .build(ui, &mut value)
-            {
-                view.set_rotation_comp(Some(value));
+            let disk_diameter = view.src_params.disk_diameter;
+            if view.rotation_comp_in_degrees {
+                let mut value = view.rotation_comp_degrees().0;
+                if imgui::Slider::new("##rotation-comp", -MAX_ROTATION_COMP_DEGREES, MAX_ROTATION_COMP_DEGREES)
+                    .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                    .display_format("%0.4f°/frame")
+                    .build(ui, &mut value)
+                {
+                    view.set_rotation_comp(Some(Deg(value)));
+                }
+            } else {
+                let mut value = view.rotation_comp_value();
+                if imgui::Slider::new("##rotation-comp", -MAX_ROTATION_COMP_PIXELS, MAX_ROTATION_COMP_PIXELS)
+                    .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                    .display_format("%0.3f px/frame")
+                    .build(ui, &mut value)
+                {
+                    view.set_rotation_comp(Some(rotation_comp_to_degrees(value, disk_diameter)));
+                }
             }
             token.end();
 
+            lock_token.end();
+            lock_group.end();
+            projection::export_lock_tooltip(ui, locked);
+
+            ui.same_line();
+            if view.rotation_comp_in_degrees {
+                let value = fmt::format_number(view.rotation_comp_value() as f64, 3, &gui_state.format);
+                ui.text(format!("= {} px/frame", value));
+            } else {
+                ui.text(format!("= {}/frame", fmt::format_angle(view.rotation_comp_degrees(), 4, &gui_state.format)));
+            }
+
+            ui.same_line();
+            if ui.radio_button_bool("px##rotation-comp-px", !view.rotation_comp_in_degrees) {
+                view.rotation_comp_in_degrees = false;
+            }
+            ui.same_line();
+            if ui.radio_button_bool("°##rotation-comp-deg", view.rotation_comp_in_degrees) {
+                view.rotation_comp_in_degrees = true;
+            }
+            gui::tooltip(ui, "Unit of the rotation compensation. The value is kept in degrees, so it remains valid \
+                after the disk diameter changes.");
+
+            if config.diagnostic_rendering_option().unwrap_or(false) {
+                let mut diagnostic_rendering = view.diagnostic_rendering;
+                if ui.checkbox("diagnostic rendering", &mut diagnostic_rendering) {
+                    view.set_diagnostic_rendering(diagnostic_rendering);
+                }
+                gui::tooltip(ui, "Shows a longitude/latitude pattern (hue: longitude, brightness: latitude, \
+                    30° checkerboard) instead of the image, to reveal distortions of the mapping. Not exported.");
+            } else if view.diagnostic_rendering {
+                view.set_diagnostic_rendering(false);
+            }
+
             ui.tree_node_config("grid").build(|| {
-                if ui.checkbox("show", &mut view.grid.show) {
+                if ui.checkbox("show", &mut view.display_settings.grid_shown) {
                     view.render();
                 }
 
-                let token = ui.begin_disabled(!view.grid.show);
+                let token = ui.begin_disabled(!view.display_settings.grid_shown);
 
                 ui.same_line();
-                if imgui::ColorEdit4::new("color##grid-color", &mut view.grid.color)
+                if imgui::ColorEdit4::new("color##grid-color", &mut view.display_settings.grid_color)
                     .alpha(false)
                     .inputs(false)
                     .build(ui)
@@ -447,21 +1497,21 @@ pub fn handle_projection_view(
                 }
 
                 gui::add_text_before(ui, "opacity");
-                let mut value = view.grid.color[3] * 100.0;
-                if imgui::Slider::new("##grid-opacity", 5.0, 100.0)
+                let mut value = view.display_settings.grid_color[3] * 100.0;
+                if imgui::Slider::new("##grid-opacity", MIN_GRID_OPACITY * 100.0, 100.0)
                     .display_format("%0.1f%%")
                     .flags(imgui::SliderFlags::ALWAYS_CLAMP)
                     .build(ui, &mut value)
                 {
-                    if value >= 5.0 && value <= 100.0 {
-                        view.grid.color[3] = value / 100.0;
+                    if value >= MIN_GRID_OPACITY * 100.0 && value <= 100.0 {
+                        view.display_settings.grid_color[3] = value / 100.0;
                         view.render();
                     }
                 }
 
                 gui::add_text_before(ui, "horz. spacing");
-                let mut value = view.grid.horz_spacing;
-                if imgui::Slider::new("##grid-horz-spacing", 0.05, 0.5)
+                let mut value = view.display_settings.grid_horz_spacing;
+                if imgui::Slider::new("##grid-horz-spacing", MIN_GRID_SPACING, MAX_GRID_SPACING)
                     .flags(imgui::SliderFlags::ALWAYS_CLAMP)
                     .display_format("%0.4f")
                     .build(ui, &mut value)
@@ -470,8 +1520,8 @@ pub fn handle_projection_view(
                 }
 
                 gui::add_text_before(ui, "vert. spacing");
-                let mut value = view.grid.vert_spacing;
-                if imgui::Slider::new("##grid-vert-spacing", 0.05, 0.5)
+                let mut value = view.display_settings.grid_vert_spacing;
+                if imgui::Slider::new("##grid-vert-spacing", MIN_GRID_SPACING, MAX_GRID_SPACING)
                     .flags(imgui::SliderFlags::ALWAYS_CLAMP)
                     .display_format("%0.4f")
                     .build(ui, &mut value)
@@ -482,8 +1532,93 @@ pub fn handle_projection_view(
                 token.end();
             });
 
+            ui.tree_node_config("coverage").build(|| {
+                ui.checkbox("show##coverage-show", &mut view.display_settings.coverage_bar_shown);
+
+                gui::add_text_before(ui, "max. emission angle");
+                let mut value = view.display_settings.max_emission_angle.0;
+                if imgui::Slider::new("##max-emission-angle", MIN_MAX_EMISSION_ANGLE, MAX_MAX_EMISSION_ANGLE)
+                    .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+                    .display_format("%0.0f°")
+                    .build(ui, &mut value)
+                {
+                    view.display_settings.max_emission_angle = Deg(value);
+                }
+                gui::tooltip(ui, "Frames provide usable data where the surface is seen at an angle below this value \
+                    (measured on the equator).");
+            });
+
+            ui.tree_node_config("frame interval precision").build(|| {
+                handle_interval_drift(ui, &gui_state.format, view, source_view.load_options().decimation);
+            });
+
+            ui.tree_node_config("display stretch").build(|| {
+                let changed = display_stretch::handle_stretch_controls(ui, &mut view.stretch, || {
+                    display_stretch::compute_histogram(
+                        view.projection_draw_buf.storage_buf(),
+                        &view.display,
+                        &view.unit_quad,
+                        &view.texture_copy_prog
+                    )
+                });
+                if changed { view.render(); }
+            });
+
+            ui.tree_node_config("verify").build(|| {
+                verification::handle_verification(
+                    ui,
+                    gui_state,
+                    &mut view.verification,
+                    source_view,
+                    &view.src_params,
+                    view.source_image_idx,
+                    view.projection_type,
+                    view.standard_parallel
+                );
+                gui::handle_message_box(ui, gui_state, config);
+            });
+
+            if ui.button("Apply to all projection views") {
+                *display_settings_broadcast = Some(view.display_settings.clone());
+            }
+            gui::tooltip(ui, "Copies the grid and coverage settings of this view to all other projection views.");
+
+            ui.same_line();
+            if ui.button("Set as default") {
+                config.set_projection_view_display_settings(&view.display_settings);
+            }
+            gui::tooltip(ui, "Newly created projection views will use the grid and coverage settings of this view.");
+
+            if view.display_settings.coverage_bar_shown {
+                let coverage = coverage::circumference_coverage(&view.coverage_intervals());
+                ui.text(format!(
+                    "Coverage: {}% of circumference, largest gap: {}",
+                    fmt::format_number(100.0 * coverage.covered_fraction as f64, 1, &gui_state.format),
+                    fmt::format_angle(Deg(coverage.largest_gap), 1, &gui_state.format)
+                ));
+            }
+
+            if !view.stretch.levels().is_neutral() {
+                ui.text_colored(
+                    display_stretch::BADGE_COLOR, "Display stretch active (exported images are not affected)"
+                );
+                ui.same_line();
+                if ui.small_button("reset##stretch-badge") {
+                    view.stretch.reset();
+                    view.render();
+                }
+            }
+
             if view.projection_size()[1] != 0 {
-                let adjusted_logical_sz = gui::fill_vertically(view.projection_size(), ui.content_region_avail());
+                let mut avail = ui.content_region_avail();
+                if view.display_settings.coverage_bar_shown {
+                    avail[1] -= COVERAGE_BAR_HEIGHT + ui.clone_style().item_spacing[1];
+                }
+                // the window may be too small to leave any space
+                avail = [avail[0].max(0.0), avail[1].max(0.0)];
+                let adjusted_logical_sz = gui::fill_vertically(view.projection_size(), avail);
+
+                if view.display_settings.coverage_bar_shown { draw_coverage_bar(ui, view, adjusted_logical_sz[0]); }
 
                 let hidpi_f = gui_state.hidpi_factor() as f32;
                 let adjusted = gui::adjust_pos_size_for_exact_hidpi_scaling(ui, hidpi_f, adjusted_logical_sz);
@@ -493,51 +1628,1066 @@ pub fn handle_projection_view(
                     adjusted.physical_size[1]
                 );
 
-                imgui::Image::new(view.display_buf_id(), adjusted.logical_size).build(ui);
+                let img_pos = ui.cursor_screen_pos();
+                let (uv0, uv1) = view.display_orientation.uv_bounds();
+                imgui::Image::new(view.display_buf_id(), adjusted.logical_size).uv0(uv0).uv1(uv1).build(ui);
+
+                if ui.is_item_hovered() {
+                    let mouse_pos = ui.io().mouse_pos;
+                    let cursor = [
+                        (mouse_pos[0] - img_pos[0]) / adjusted.logical_size[0],
+                        (mouse_pos[1] - img_pos[1]) / adjusted.logical_size[1]
+                    ];
+                    let pos = view.display_orientation.to_data_position(cursor);
+                    let coords = projection_coords(
+                        pos, view.source_image_idx, &view.src_params, view.rotation_comp_value(), view.projection_type
+                    );
+                    let coords_text = coords.map(|(lon, lat)| format!(
+                        "lon. {} (from central meridian), lat. {}",
+                        fmt::format_angle(lon, 1, &gui_state.format),
+                        fmt::format_angle(lat, 1, &gui_state.format)
+                    ));
+                    // the loupe replaces the tooltip
+                    if gui::loupe::active(ui, gui_state) {
+                        let map_size = view.projection_size();
+                        let mut info = format!(
+                            "x: {}, y: {}",
+                            ((pos[0] * map_size[0] as f32) as u32).min(map_size[0] - 1),
+                            ((pos[1] * map_size[1] as f32) as u32).min(map_size[1] - 1)
+                        );
+                        if let Some(text) = &coords_text { info += &format!("\n{}", text); }
+                        if let (true, Some((lon, lat))) = (view.diagnostic_rendering, coords) {
+                            let [red, green, blue] = diagnostic::diagnostic_color(lon, lat);
+                            info += &format!("\npattern: {:.3} {:.3} {:.3}", red, green, blue);
+                        }
+                        gui::loupe::show(
+                            ui,
+                            gui_state,
+                            view.display_buf_id(),
+                            [view.display_draw_buf.width(), view.display_draw_buf.height()],
+                            cursor,
+                            (uv0, uv1),
+                            &info
+                        );
+                    } else if let Some(text) = &coords_text {
+                        ui.tooltip_text(format!("{}\n(double-click to center in globe views)", text));
+                    }
+                    if let Some((lon, lat)) = coords {
+                        let planet_lon = planet_longitude(&view.src_params, view.source_image_idx, lon);
+                        linked_cursor.publish(view.id(), planet_lon, lat);
+                        if ui.is_mouse_double_clicked(imgui::MouseButton::Left) {
+                            *globe_target = Some(GlobeTarget{ planet_longitude: planet_lon, latitude: lat });
+                        }
+                    }
+                } else if let Some((lon, lat)) = linked_cursor.position_for(view.id()) {
+                    draw_linked_cursor(ui, &gui_state.format, view, img_pos, adjusted.logical_size, lon, lat);
+                }
             }
         }
     );
+    // not rendered while collapsed or in an inactive docked tab
+    if !visible { view.render_throttle.set_visible(false); }
 
     if export_clicked {
         ui.open_popup(&export_dialog.borrow().title());
     }
 
+    if save_map_clicked { handle_save_map(ui, gui_state, config, view, source_view, long_task_dialog, long_fg_task); }
+    handle_map_overwrite_dialog(ui, gui_state, config, view, source_view, long_task_dialog, long_fg_task);
+
     handle_export(
-        ui, gui_state, config, view, source_view, long_task_dialog, task_sender, &mut export_dialog.borrow_mut()
+        ui,
+        gui_state,
+        config,
+        view,
+        source_view,
+        long_task_dialog,
+        task_sender,
+        &mut export_dialog.borrow_mut(),
+        export_result,
+        export_lock,
+        texture_limits
     );
 
     opened
 }
 
-fn handle_export(
+/// Asks for the file to save the current map to; if it exists, asks for confirmation first.
+fn handle_save_map(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
+    view: &mut ProjectionView,
+    source_view: &SourceView,
+    long_task_dialog: &RefCell<Option<LongTaskDialog>>,
+    long_fg_task: &RefCell<Option<Box<dyn LongForegroundTask>>>
+) {
+    let location = gui::file_dialog::initial_location(config.single_map_export_path().as_deref());
+    let file_name = map_save::default_file_name(source_view.planet(), view.displayed_frame(), view.projection_type);
+    let path = gui::file_dialog::checked(ui, gui_state, native_dialog::FileDialog::new()
+        .set_location(&location)
+        .set_filename(&file_name)
+        .add_filter("PNG", &["png"])
+        .add_filter("TIFF", &["tif", "tiff"])
+        .show_save_single_file()
+    ).flatten();
+    let path = match path {
+        Some(path) => map_save::with_image_extension(path),
+        None => return
+    };
+
+    if let Some(dir) = path.parent() { config.set_single_map_export_path(&dir.to_string_lossy()); }
+
+    if path.exists() {
+        view.pending_map_path = Some(path);
+        ui.open_popup(map_save::OVERWRITE_TITLE);
+    } else {
+        save_map(ui, gui_state, view, source_view, &path, long_task_dialog, long_fg_task);
+    }
+}
+
+fn handle_map_overwrite_dialog(
     ui: &imgui::Ui,
     gui_state: &mut gui::GuiState,
     config: &mut Configuration,
+    view: &mut ProjectionView,
+    source_view: &SourceView,
+    long_task_dialog: &RefCell<Option<LongTaskDialog>>,
+    long_fg_task: &RefCell<Option<Box<dyn LongForegroundTask>>>
+) {
+    let path = match &view.pending_map_path {
+        Some(path) => path.clone(),
+        None => return
+    };
+
+    // `Some(overwrite)` once the user has decided
+    let mut decision = None;
+    gui::modal::modal(ui, config, map_save::OVERWRITE_TITLE, gui::modal::KeyBindings::all(), |key_action, _| {
+        ui.text(format!("{} already exists. Overwrite it?", path.to_string_lossy()));
+        ui.separator();
+
+        if gui::modal::default_button(ui, "Overwrite") || key_action == gui::modal::KeyAction::Accept {
+            decision = Some(true);
+            ui.close_current_popup();
+        }
+        ui.same_line();
+        if ui.button("Cancel") || key_action == gui::modal::KeyAction::Cancel {
+            decision = Some(false);
+            ui.close_current_popup();
+        }
+    });
+
+    if let Some(overwrite) = decision {
+        view.pending_map_path = None;
+        if overwrite { save_map(ui, gui_state, view, source_view, &path, long_task_dialog, long_fg_task); }
+    }
+}
+
+/// Saves the current map as `path`; a large file is written by a long foreground task.
+fn save_map(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
     view: &ProjectionView,
     source_view: &SourceView,
+    path: &Path,
+    long_task_dialog: &RefCell<Option<LongTaskDialog>>,
+    long_fg_task: &RefCell<Option<Box<dyn LongForegroundTask>>>
+) {
+    let result = view.render_map(source_view.load_options().interpretation)
+        .and_then(|image| map_save::save_map(&image, path));
+
+    match result {
+        Ok(None) => logging::log_info!("Saved {}.", path.to_string_lossy()),
+
+        Ok(Some((task, progress_receiver))) => {
+            *long_fg_task.borrow_mut() = Some(Box::new(task));
+            *long_task_dialog.borrow_mut() =
+                Some(LongTaskDialog::new("Saving map".to_string(), "".to_string(), progress_receiver));
+        },
+
+        Err(e) => {
+            gui_state.message_box = Some(gui::MessageBox{
+                title: "Error".to_string(),
+                message: format!("Could not save {}: {}.", path.to_string_lossy(), e)
+            });
+            ui.open_popup("Error");
+        }
+    }
+}
+
+/// Keeps a pinned frame valid after the source images have been replaced.
+fn update_pinned_frame(view: &mut ProjectionView, source_view: &SourceView) {
+    if let Some(idx) = view.pinned_frame() {
+        let idx = idx.min(source_view.num_images() - 1);
+        view.pin_frame(idx, source_view.image(idx));
+    }
+}
+
+fn handle_export(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    config: &mut Configuration,
+    view: &mut ProjectionView,
+    source_view: &SourceView,
     long_task_dialog: &RefCell<Option<LongTaskDialog>>,
     task_sender: &crossbeam::channel::Sender<worker::MainToWorkerMsg>,
-    export_dialog: &mut ExportDialog
+    export_dialog: &mut ExportDialog,
+    export_result: &RefCell<Option<crossbeam::channel::Receiver<worker::ProjectionResultMsg>>>,
+    export_lock: &RefCell<Option<ExportLock>>,
+    texture_limits: &TextureLimits
 ) {
-    if handle_export_dialog(ui, gui_state, export_dialog) {
+    let winjupos_unavailable = if source_view.planet().is_none() {
+        Some("planet not selected in the source view")
+    } else if source_view.observation_jd().is_none() {
+        Some("observation time not entered in the source view")
+    } else {
+        None
+    };
+
+    let precision_reduced = projection::export_dialog::precision_reduced(
+        &view.src_params.frame_gains,
+        source_view.load_options().interpretation,
+        source_view.bit_depth(),
+        export_dialog.eight_bit_output(source_view.bit_depth())
+    );
+
+    view.export_preview.set_num_frames(source_view.num_images());
+    let accepted = handle_export_dialog(
+        ui,
+        gui_state,
+        config,
+        export_dialog,
+        &mut view.export_preview,
+        winjupos_unavailable,
+        precision_reduced,
+        source_view.bit_depth()
+    );
+
+    if accepted {
         let (progress_sender, progress_receiver) = crossbeam::channel::bounded(1);
+        let (result_sender, result_receiver) = crossbeam::channel::unbounded();
+
+        let output_dir = export_dialog.output_path();
+        let (task, textures) = export_task(
+            view,
+            source_view,
+            export_dialog,
+            precision_reduced,
+            texture_limits.worker_limited(),
+            output_dir,
+            progress_sender,
+            result_sender
+        );
+        let size = map_size(&task.src_params, task.rotation_comp, task.projection_type, task.standard_parallel);
+        let max_texture_size = texture_limits.max_texture_size();
+        if !texture_limits::fits(size, max_texture_size) {
+            gui_state.message_box = Some(gui::MessageBox{
+                title: "Error".to_string(),
+                message: format!(
+                    "The exported maps would be too large ({}x{} pixels; the maximum is {2}x{2}).",
+                    size[0], size[1], max_texture_size
+                )
+            });
+            ui.open_popup("Error");
+            return;
+        }
+        *export_lock.borrow_mut() = Some(ExportLock::new(view.id(), textures));
+        let cancel = task.cancel.clone();
 
-        let sz = source_view.image_size();
-
-        task_sender.send(worker::MainToWorkerMsg::Projection(worker::Projection{
-            output_dir: export_dialog.output_path(),
-            sender: progress_sender,
-            source_texture_ids: source_view.texture_ids(),
-            bounce_back: export_dialog.bounce_back(),
-            image_size: glium::texture::Dimensions::Texture2d{ width: sz[0], height: sz[1] },
-            src_params: view.src_params.clone(),
-            rotation_comp: view.rotation_comp_value(),
-            projection_type: view.projection_type
-        })).unwrap();
+        task_sender.send(worker::MainToWorkerMsg::Projection(task)).unwrap();
+        view.export_comparison.on_export_started(&view.projection_draw_buf, view.source_image_idx);
 
-        *long_task_dialog.borrow_mut() =
-            Some(LongTaskDialog::new("Exporting".to_string(), "".to_string(), progress_receiver));
+        *long_task_dialog.borrow_mut() = Some(
+            LongTaskDialog::new("Exporting".to_string(), "".to_string(), progress_receiver)
+                .with_cancel_token(cancel)
+                .with_background_allowed()
+        );
+        *export_result.borrow_mut() = Some(result_receiver);
 
         config.set_projection_export_path(export_dialog.output_path().to_str().unwrap()); //TODO: handle non-UTF-8 paths
     }
+
+    if let Some(frame_idx) = view.export_preview.take_render_request() {
+        // nothing is saved, and progress and results of the export are not reported
+        let (task, textures) = export_task(
+            view,
+            source_view,
+            export_dialog,
+            precision_reduced,
+            texture_limits.worker_limited(),
+            PathBuf::new(),
+            crossbeam::channel::bounded(1).0,
+            crossbeam::channel::unbounded().0
+        );
+        let (image_sender, image_receiver) = crossbeam::channel::bounded(1);
+        task_sender.send(worker::MainToWorkerMsg::PreviewFrame(worker::PreviewFrame{
+            task,
+            frame_idx,
+            image_sender
+        })).unwrap();
+        view.export_preview.set_pending(frame_idx, image_receiver, textures);
+    }
+}
+
+/// Returns the export task for the current settings of `export_dialog` and the source textures it uses (which must
+/// stay alive until the task ends). Used for both exports and their previews, so that they are rendered identically.
+///
+/// `worker_limited`: the worker's context has a drastically smaller texture size limit (it may be provided by another
+/// GPU); the source images are then read from files, as in a low-memory export.
+fn export_task(
+    view: &ProjectionView,
+    source_view: &SourceView,
+    export_dialog: &ExportDialog,
+    precision_reduced: bool,
+    worker_limited: bool,
+    output_dir: PathBuf,
+    progress_sender: crossbeam::channel::Sender<gui::long_task_dialog::ProgressMsg>,
+    result_sender: crossbeam::channel::Sender<worker::ProjectionResultMsg>
+) -> (worker::Projection, Option<TexturesInUse>) {
+    let sz = source_view.image_size();
+
+    // stacked frames exist only as textures; frames of a video are not separate files
+    let from_video = source_view.file_paths().first().map_or(false, |path| img_seq::is_ser_path(path));
+    let from_files = (export_dialog.low_memory() || worker_limited) && !from_video;
+    let (source, textures) = if from_files && source_view.stacked_group_size().is_none() {
+        (worker::ProjectionSource::Files{
+            paths: source_view.file_paths().to_vec(),
+            binning: source_view.load_options().binning,
+            interpretation: source_view.load_options().interpretation
+        }, None)
+    } else {
+        // the worker uses only the IDs; the textures must stay alive until the task ends
+        let textures = TexturesInUse::new(source_view.images(), |texture| texture.get_id());
+        (worker::ProjectionSource::Textures(textures.ids().to_vec()), Some(textures))
+    };
+    if let worker::ProjectionSource::Textures(ids) = &source {
+        debug_assert!(textures.as_ref().map_or(false, |textures| textures.contains_all(ids)));
+    }
+
+    let winjupos = if export_dialog.winjupos() {
+        Some(WinJuposExport{
+            start_jd: source_view.observation_jd().unwrap(),
+            frame_interval: view.src_params.frame_interval,
+            planet: source_view.planet_name().to_string()
+        })
+    } else {
+        None
+    };
+
+    let polar = if export_dialog.polar() { Some(export_dialog.polar_view().clone()) } else { None };
+
+    // WinJUPOS maps and polar views are created from equirectangular maps with each frame's central meridian
+    // in the middle
+    let (projection_type, rotation_comp) = if winjupos.is_some() || polar.is_some() {
+        (ProjectionType::Equirectangular, 0.0)
+    } else {
+        (view.projection_type, view.rotation_comp_value())
+    };
+
+    let post_export_command = export_dialog.post_export_command().map(|template| {
+        post_export::expand_template(
+            template,
+            &[
+                (post_export::PLACEHOLDER_OUTPUT_DIR, output_dir.to_string_lossy().to_string()),
+                (post_export::PLACEHOLDER_FRAME_COUNT, source_view.num_images().to_string()),
+                (post_export::PLACEHOLDER_PLANET, source_view.planet_name().to_string()),
+                (post_export::PLACEHOLDER_PROJECTION, projection_type.name().to_string())
+            ],
+            post_export::Shell::current()
+        )
+    });
+
+    let task = worker::Projection{
+        output_dir,
+        sender: progress_sender,
+        result_sender,
+        source,
+        bounce_back: export_dialog.bounce_back(),
+        frame_prefix: export_dialog.frame_prefix().to_string(),
+        first_frame_number: export_dialog.first_frame_number(),
+        overwrite: export_dialog.overwrite(),
+        image_size: glium::texture::Dimensions::Texture2d{ width: sz[0], height: sz[1] },
+        src_params: view.src_params.clone(),
+        rotation_comp,
+        projection_type,
+        standard_parallel: view.standard_parallel,
+        limb_feather: view.limb_feather,
+        post_export_command,
+        interpretation: source_view.load_options().interpretation,
+        contact_sheet: export_dialog.contact_sheet(),
+        skip_failed_frames: export_dialog.skip_failed_frames(),
+        grid: if export_dialog.include_grid() { Some(view.display_settings.grid_params()) } else { None },
+        match_seams: export_dialog.match_seams(),
+        winjupos,
+        metadata: if export_dialog.metadata() {
+            Some(ExportMetadata{
+                start_jd: source_view.observation_jd(),
+                frame_offsets: source_view.frame_time_offsets()
+            })
+        } else {
+            None
+        },
+        polar,
+        dithering: export_dialog.dithering(precision_reduced),
+        source_bit_depth: source_view.bit_depth(),
+        output_format: export_dialog.output_format(source_view.bit_depth()),
+        reproducible: export_dialog.reproducible(),
+        cancel: CancelToken::new()
+    };
+
+    (task, textures)
+}
+
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+
+    /// Returns (horizontal, vertical) scale in pixels per unit length on a unit sphere at `latitude` for a single
+    /// frame's Lambert projection.
+    fn lambert_scales(disk_diameter: f32, standard_parallel: Deg<f32>, latitude: Deg<f32>) -> (f32, f32) {
+        let width = disk_diameter * PI_2;
+        let height = projection_height(ProjectionType::LambertCylindricalEqualArea, disk_diameter, standard_parallel);
+
+        // width covers 180° of longitude
+        let horizontal = width / std::f32::consts::PI / latitude.cos();
+
+        let delta = Deg(0.01);
+        let dy = (lambert_normalized_y(latitude + delta) - lambert_normalized_y(latitude - delta)) * height as f32;
+        let vertical = dy / (2.0 * cgmath::Rad::from(delta).0);
+
+        (horizontal, vertical)
+    }
+
+    #[test]
+    fn lambert_height_for_standard_parallels() {
+        assert_eq!(1000, projection_height(ProjectionType::LambertCylindricalEqualArea, 1000.0, Deg(0.0)));
+        assert_eq!(1333, projection_height(ProjectionType::LambertCylindricalEqualArea, 1000.0, Deg(30.0)));
+        assert_eq!(2000, projection_height(ProjectionType::LambertCylindricalEqualArea, 1000.0, Deg(45.0)));
+        assert_eq!(1571, projection_height(ProjectionType::Equirectangular, 1000.0, Deg(45.0)));
+    }
+
+    #[test]
+    fn lambert_has_no_shape_distortion_at_standard_parallel() {
+        for sp in [Deg(0.0), Deg(30.0), Deg(45.0)] {
+            let (horizontal, vertical) = lambert_scales(1000.0, sp, sp);
+            assert!((horizontal / vertical - 1.0).abs() < 0.01, "{:?}: {} vs. {}", sp, horizontal, vertical);
+        }
+
+        let (horizontal, vertical) = lambert_scales(1000.0, Deg(45.0), Deg(0.0));
+        assert!((horizontal / vertical - 0.5).abs() < 0.01);
+    }
+
+    /// Returns longitude (within ±90°) of the point at `latitude` which is sampled from source image `position`.
+    fn find_longitude(src_params: &SourceParameters, position: Point2<f32>, latitude: Deg<f32>) -> Deg<f32> {
+        (-900..=900)
+            .map(|l| Deg(l as f32 / 10.0))
+            .min_by(|l1, l2| {
+                let d1 = (source_image_position(src_params, *l1, latitude) - position).magnitude();
+                let d2 = (source_image_position(src_params, *l2, latitude) - position).magnitude();
+                d1.partial_cmp(&d2).unwrap()
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn mirrored_sequence_maps_features_to_correct_longitudes() {
+        const IMAGE_WIDTH: f32 = 400.0;
+        const LATITUDE: Deg<f32> = Deg(15.0);
+
+        let params = SourceParameters{
+            num_images: 5,
+            inclination: Deg(5.0),
+            roll: Deg(10.0),
+            disk_center: Point2{ x: 150.0, y: 210.0 },
+            flattening: projection::Planet::Jupiter.flattening(),
+            ..SourceParameters::test_default()
+        };
+
+        // the same sequence captured via a star diagonal
+        let mut mirrored_params = params.clone();
+        mirrored_params.disk_center.x = IMAGE_WIDTH - params.disk_center.x;
+        mirrored_params.mirror_ew = true;
+
+        let mut unmirrored_params = mirrored_params.clone();
+        unmirrored_params.mirror_ew = false;
+
+        let mut prev_unmirrored_found: Option<Deg<f32>> = None;
+        for idx in 0..params.num_images {
+            // feature rotating with the planet
+            let longitude = Deg(-20.0 + 8.0 * idx as f32);
+
+            let position = source_image_position(&params, longitude, LATITUDE);
+            let mirrored_position = Point2{ x: IMAGE_WIDTH - position.x, y: position.y };
+
+            let found = find_longitude(&mirrored_params, mirrored_position, LATITUDE);
+            assert!((found - longitude).0.abs() < 0.2, "frame {}: {:?} vs. {:?}", idx, found, longitude);
+
+            // without un-mirroring, longitudes run backwards
+            let found = find_longitude(&unmirrored_params, mirrored_position, LATITUDE);
+            if let Some(prev) = prev_unmirrored_found { assert!(found < prev); }
+            prev_unmirrored_found = Some(found);
+        }
+    }
+
+    #[test]
+    fn globe_coords_invert_source_image_position() {
+        let base = SourceParameters{
+            inclination: Deg(10.0),
+            roll: Deg(-25.0),
+            disk_center: Point2{ x: 300.0, y: 200.0 },
+            disk_diameter: 250.0,
+            flattening: projection::Planet::Jupiter.flattening(),
+            ..SourceParameters::test_default()
+        };
+
+        for params in [
+            base.clone(),
+            SourceParameters{ mirror_ew: true, flip_ns: true, ..base.clone() },
+            SourceParameters{ inclination: Deg(-10.0), flattening: 0.0, ..base.clone() }
+        ] {
+            for lon in (-50..=50).step_by(10) {
+                for lat in (-50..=50).step_by(10) {
+                    let (lon, lat) = (Deg(lon as f32), Deg(lat as f32));
+                    let position = source_image_position(&params, lon, lat);
+                    let (found_lon, found_lat) = globe_coords(&params, position).unwrap();
+                    assert!(
+                        (found_lon - lon).0.abs() < 1.0e-3 && (found_lat - lat).0.abs() < 1.0e-3,
+                        "({:?}, {:?}) found as ({:?}, {:?})", lon, lat, found_lon, found_lat
+                    );
+                }
+            }
+        }
+
+        assert!(globe_coords(&base, Point2{ x: 300.0 + 130.0, y: 200.0 }).is_none());
+        let (lon, _) = globe_coords(&base, base.disk_center).unwrap();
+        assert!(lon.0.abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn flipped_image_sampled_consistently() {
+        let mut params = SourceParameters{
+            inclination: Deg(-3.0),
+            roll: Deg(-20.0),
+            disk_diameter: 80.0,
+            flattening: projection::Planet::Mars.flattening(),
+            sidereal_rotation_period: projection::Planet::Mars.sidereal_rotation(),
+            ..SourceParameters::test_default()
+        };
+        let position = source_image_position(&params, Deg(30.0), Deg(40.0));
+
+        params.flip_ns = true;
+        let flipped = source_image_position(&params, Deg(30.0), Deg(40.0));
+
+        assert!((position.x - flipped.x).abs() < 1.0e-4);
+        assert!((position.y - params.disk_center.y + flipped.y - params.disk_center.y).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn coordinate_readout_compensates_display_orientation() {
+        let params = SourceParameters{
+            num_images: 3,
+            ..SourceParameters::test_default()
+        };
+        let rotation_comp = 10.0;
+
+        // a feature at longitude 45°, latitude 30° in frame 1: the strip is 2π·100 + 2·10 px wide, frame 1 starts
+        // at x = 10, and the feature is 3/4 of the frame's width from there; north-up it is at (0.742288, 1/3)
+        let (x, y) = (0.742288, 1.0 / 3.0);
+
+        for (south_up, mirrored, displayed_pos) in [
+            (false, false, [x, y]),
+            // rotated by 180°
+            (true, false, [1.0 - x, 1.0 - y]),
+            (false, true, [1.0 - x, y]),
+            // rotated by 180° and flipped horizontally, i.e., flipped vertically
+            (true, true, [x, 1.0 - y])
+        ] {
+            let orientation = projection::DisplayOrientation{ south_up, mirrored };
+            let (lon, lat) = projection_coords(
+                orientation.to_data_position(displayed_pos), 1, &params, rotation_comp, ProjectionType::Equirectangular
+            ).unwrap();
+            assert!((lon.0 - 45.0).abs() < 1.0e-3, "{:?}: {:?}", orientation, lon);
+            assert!((lat.0 - 30.0).abs() < 1.0e-3, "{:?}: {:?}", orientation, lat);
+        }
+
+        // outside of frame 0
+        assert!(projection_coords([0.0, 0.5], 0, &params, rotation_comp, ProjectionType::Equirectangular).is_none());
+    }
+
+    #[test]
+    fn map_longitudes_follow_frame_central_meridians() {
+        let params = SourceParameters{
+            num_images: 4,
+            frame_interval: std::time::Duration::from_secs(120),
+            ..SourceParameters::test_default()
+        };
+        // compensation matching the planet's rotation (as in "auto" mode)
+        let img_width = PI_2 * params.disk_diameter;
+        let rotation = coverage::rotation_per_frame(&params);
+        let rotation_comp = img_width * rotation.0 / 180.0;
+
+        let (start, end) = map_longitude_range(&params, rotation_comp);
+        assert!((end - 90.0).abs() < 1.0e-3);
+
+        for idx in 0..params.num_images {
+            // find the central meridian of frame `idx` in the map
+            let cm_pos = (0..=10000)
+                .map(|x| x as f32 / 10000.0)
+                .filter_map(|x| projection_coords(
+                    [x, 0.5], idx, &params, rotation_comp, ProjectionType::Equirectangular
+                ).map(|(lon, _)| (x, lon.0.abs())))
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                .unwrap()
+                .0;
+
+            let map_longitude = start + cm_pos * (end - start);
+            let expected = -(idx as f32) * rotation.0;
+            assert!((map_longitude - expected).abs() < 0.1, "frame {}: {} vs. {}", idx, map_longitude, expected);
+        }
+    }
+
+    #[test]
+    fn retrograde_features_stay_in_place_in_map() {
+        let params = SourceParameters{
+            num_images: 5,
+            frame_interval: std::time::Duration::from_secs(3600),
+            flattening: projection::Planet::Venus.flattening(),
+            sidereal_rotation_period: projection::Planet::Venus.atmospheric_rotation().unwrap(),
+            rotation_direction: projection::Planet::Venus.rotation_direction(),
+            ..SourceParameters::test_default()
+        };
+        let rotation = coverage::rotation_per_frame(&params);
+        assert!(rotation.0 < 0.0);
+        let rotation_comp = rotation_comp_to_pixels(rotation, params.disk_diameter);
+
+        // frame 0 is at the left end of the map
+        let (start, end) = map_longitude_range(&params, rotation_comp);
+        assert!((start + 90.0).abs() < 1.0e-3);
+        let deg_per_pixel = 180.0 / (PI_2 * params.disk_diameter);
+        assert!((end - start - strip_width(&params, rotation_comp) * deg_per_pixel).abs() < 1.0e-3);
+
+        // position (within [0; 1]) in the map of `longitude` (relative to the central meridian) of frame `idx`
+        let map_x = |idx: usize, longitude: Deg<f32>, rotation_comp: f32| -> f32 {
+            (0..=20000)
+                .map(|x| x as f32 / 20000.0)
+                .filter_map(|x| projection_coords(
+                    [x, 0.5], idx, &params, rotation_comp, ProjectionType::Equirectangular
+                ).map(|(lon, _)| (x, (lon - longitude).0.abs())))
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                .unwrap()
+                .0
+        };
+
+        // a cloud feature drifts across the disk in the direction opposite to that of a prograde rotator
+        let feature = |idx: usize| Deg(30.0 + rotation.0 * idx as f32);
+        let last = params.num_images - 1;
+
+        let first_x = map_x(0, feature(0), rotation_comp);
+        for idx in 1..params.num_images {
+            let x = map_x(idx, feature(idx), rotation_comp);
+            assert!((x - first_x).abs() < 1.0e-3, "frame {}: {} vs. {}", idx, x, first_x);
+        }
+
+        // compensating in the prograde direction smears the feature
+        let first_x = map_x(0, feature(0), -rotation_comp);
+        assert!((map_x(last, feature(last), -rotation_comp) - first_x).abs() > 0.05);
+    }
+
+    #[test]
+    fn lambert_preserves_area() {
+        for sp in [Deg(0.0), Deg(30.0), Deg(45.0)] {
+            let (h_equator, v_equator) = lambert_scales(1000.0, sp, Deg(0.0));
+            let (h_60, v_60) = lambert_scales(1000.0, sp, Deg(60.0));
+            assert!((h_equator * v_equator / (h_60 * v_60) - 1.0).abs() < 0.01);
+        }
+    }
+
+    /// Returns rows (from north to south) of the equirectangular map of a uniformly white disk (400×400 source image)
+    /// rendered by `projection.frag` into an 8-bit target, as in an export; `None` (the calling test is then skipped)
+    /// if no OpenGL context is available.
+    fn exported_map(src_params: &SourceParameters, limb_feather: f32, diagnostic: bool) -> Option<Vec<Vec<[u8; 3]>>> {
+        use glium::texture::{ClientFormat, MipmapsOption, RawImage2d, UncompressedFloatFormat};
+
+        let display = match crate::gpu::headless::create_renderer() {
+            Some(display) => display,
+            None => {
+                eprintln!("No OpenGL context available; skipping the test.");
+                return None;
+            }
+        };
+
+        let source = Texture2d::with_format(
+            &display,
+            RawImage2d{
+                data: std::borrow::Cow::from(vec![255u8; 3 * 400 * 400]),
+                width: 400,
+                height: 400,
+                format: ClientFormat::U8U8U8
+            },
+            UncompressedFloatFormat::U8U8U8,
+            MipmapsOption::NoMipmap
+        ).unwrap();
+
+        let [width, height] = map_size(src_params, 0.0, ProjectionType::Equirectangular, Deg(0.0));
+        let target = Texture2d::empty_with_format(
+            &display, UncompressedFloatFormat::U8U8U8, MipmapsOption::NoMipmap, width, height
+        ).unwrap();
+
+        let program = glium::program!(&display,
+            330 => {
+                vertex: include_str!("../resources/shaders/transform_2d.vert"),
+                fragment: include_str!("../resources/shaders/projection.frag"),
+            }
+        ).unwrap();
+
+        render_projection(
+            false,
+            0,
+            &source,
+            &mut target.as_surface(),
+            &projection::data::create_unit_quad(&display),
+            &program,
+            src_params,
+            0.0,
+            ProjectionType::Equirectangular,
+            limb_feather,
+            diagnostic
+        ).unwrap();
+
+        // rows are read back starting from the bottom (southern) one
+        let rows: Vec<Vec<(u8, u8, u8, u8)>> = target.read();
+        Some(rows.iter().rev().map(|row| row.iter().map(|(r, g, b, _)| [*r, *g, *b]).collect()).collect())
+    }
+
+    /// Returns values (from north to south) of the central meridian column of the map from `exported_map`.
+    fn exported_central_meridian(src_params: &SourceParameters, limb_feather: f32) -> Option<Vec<u8>> {
+        let rows = exported_map(src_params, limb_feather, false)?;
+        Some(rows.iter().map(|row| row[row.len() / 2][0]).collect())
+    }
+
+    fn feather_test_params(flattening: f32) -> SourceParameters {
+        SourceParameters{
+            disk_center: Point2{ x: 200.0, y: 200.0 },
+            disk_diameter: 300.0,
+            flattening,
+            ..SourceParameters::test_default()
+        }
+    }
+
+    #[test]
+    fn clipped_disk_contributes_only_within_image() {
+        // the disk spans X from 50 to 350, the image ends at 300
+        let params = feather_test_params(0.0);
+        let image_size = [300, 400];
+
+        assert!(within_source_image(&params, image_size, Deg(0.0), Deg(0.0)));
+        assert!(within_source_image(&params, image_size, Deg(30.0), Deg(0.0)));
+        assert!(within_source_image(&params, image_size, Deg(-60.0), Deg(0.0)));
+        // X = 200 + 150·sin(60°) ≈ 330
+        assert!(!within_source_image(&params, image_size, Deg(60.0), Deg(0.0)));
+        // closer to the pole, the same longitude is within the image
+        assert!(within_source_image(&params, image_size, Deg(60.0), Deg(60.0)));
+    }
+
+    #[test]
+    fn no_limb_feather_leaves_map_unchanged() {
+        let column = match exported_central_meridian(&feather_test_params(0.0), 0.0) {
+            Some(column) => column,
+            None => return
+        };
+        assert!(column.iter().all(|value| *value == 255));
+    }
+
+    #[test]
+    fn limb_feather_ramps_smoothly_towards_map_edges() {
+        const FEATHER: f32 = 0.1;
+        let params = feather_test_params(0.0);
+        let (column, narrow_column) =
+            match (exported_central_meridian(&params, FEATHER), exported_central_meridian(&params, FEATHER / 2.0)) {
+                (Some(column), Some(narrow_column)) => (column, narrow_column),
+                _ => return
+            };
+        let height = column.len();
+
+        // the map is symmetric; check the northern half (from the equator to the pole)
+        let northern: Vec<u8> = column[..height / 2].iter().rev().cloned().collect();
+        for (n, s) in northern.iter().zip(column[height - height / 2..].iter()) {
+            assert!((*n as i32 - *s as i32).abs() <= 1);
+        }
+
+        for (i, value) in northern.iter().enumerate() {
+            let row = height / 2 - 1 - i;
+            let latitude = Deg(90.0 - 180.0 * (row as f32 + 0.5) / height as f32);
+            // on the central meridian, normalized distance from the disk center is sin(latitude)
+            if latitude.sin() <= 1.0 - FEATHER { assert_eq!(255, *value, "latitude {:?}", latitude); }
+        }
+
+        // monotonic, without abrupt steps, fading out completely at the pole
+        for pair in northern.windows(2) {
+            assert!(pair[1] <= pair[0]);
+            assert!(pair[0] - pair[1] < 32, "step from {} to {}", pair[0], pair[1]);
+        }
+        assert!(*northern.last().unwrap() < 5);
+
+        // the ramp spans the latitudes where the normalized distance is above 1 - `FEATHER`
+        let ramp_rows = northern.iter().filter(|value| **value < 255).count() as f32;
+        let expected_rows = (90.0 - Deg::asin(1.0 - FEATHER).0) / 180.0 * height as f32;
+        assert!((ramp_rows - expected_rows).abs() <= 2.0, "{} vs. {} rows", ramp_rows, expected_rows);
+
+        let narrow_ramp_rows = narrow_column.iter().filter(|v| **v < 255).count();
+        assert!(2 * narrow_ramp_rows < column.iter().filter(|v| **v < 255).count());
+    }
+
+    #[test]
+    fn diagnostic_pattern_matches_cpu_colors() {
+        let params = feather_test_params(0.0);
+        let rows = match exported_map(&params, 0.0, true) {
+            Some(rows) => rows,
+            None => return
+        };
+        let (width, height) = (rows[0].len(), rows.len());
+
+        let mut num_checked = 0;
+        for y in (0..height).step_by(7) {
+            for x in (0..width).step_by(7) {
+                let pos = [(x as f32 + 0.5) / width as f32, (y as f32 + 0.5) / height as f32];
+                let (lon, lat) = projection_coords(pos, 0, &params, 0.0, ProjectionType::Equirectangular).unwrap();
+                // cell brightness changes abruptly at cell boundaries; skip pixels close to them
+                let near_boundary = |angle: Deg<f32>| {
+                    let within_cell = angle.0.rem_euclid(diagnostic::CELL_SIZE.0);
+                    within_cell < 1.0 || within_cell > diagnostic::CELL_SIZE.0 - 1.0
+                };
+                if near_boundary(lon) || near_boundary(lat) { continue; }
+
+                let expected = diagnostic::diagnostic_color(lon, lat).map(|value| value * 255.0);
+                let actual = rows[y][x];
+                assert!(
+                    (0..3).all(|i| (expected[i] - actual[i] as f32).abs() <= 2.0),
+                    "at lon. {:.1}°, lat. {:.1}°: expected {:?}, got {:?}", lon.0, lat.0, expected, actual
+                );
+                num_checked += 1;
+            }
+        }
+        assert!(num_checked > 100);
+    }
+
+    #[test]
+    fn limb_feather_reaches_poles_of_flattened_planet() {
+        let column =
+            match exported_central_meridian(&feather_test_params(projection::Planet::Jupiter.flattening()), 0.05) {
+                Some(column) => column,
+                None => return
+            };
+        assert!(column[0] < 5 && column[column.len() - 1] < 5);
+        assert_eq!(255, column[column.len() / 2]);
+    }
+
+    #[test]
+    fn display_settings_round_trip() {
+        let settings = DisplaySettings{
+            grid_shown: true,
+            grid_color: [0.25, 0.5, 1.0, 0.6],
+            grid_horz_spacing: 0.125,
+            grid_vert_spacing: 0.3,
+            coverage_bar_shown: true,
+            max_emission_angle: Deg(45.0)
+        };
+        assert_eq!(Some(settings.clone()), DisplaySettings::from_config_string(&settings.to_config_string()));
+        assert_eq!(
+            Some(DisplaySettings::default()),
+            DisplaySettings::from_config_string(&DisplaySettings::default().to_config_string())
+        );
+    }
+
+    #[test]
+    fn display_settings_parsing_tolerates_missing_and_unknown_keys() {
+        let settings = DisplaySettings::from_config_string("grid_shown=true; sharpening=3").unwrap();
+        assert_eq!(DisplaySettings{ grid_shown: true, ..Default::default() }, settings);
+        assert_eq!(Some(DisplaySettings::default()), DisplaySettings::from_config_string(""));
+    }
+
+    #[test]
+    fn display_settings_parsing_rejects_malformed_values_and_clamps() {
+        assert_eq!(None, DisplaySettings::from_config_string("grid_shown=maybe"));
+        assert_eq!(None, DisplaySettings::from_config_string("grid_color=1,0,0"));
+        assert_eq!(None, DisplaySettings::from_config_string("grid_horz_spacing"));
+
+        let settings = DisplaySettings::from_config_string(
+            "grid_horz_spacing=5;grid_vert_spacing=0;grid_color=2,0,0,0;max_emission_angle=90"
+        ).unwrap();
+        assert_eq!(MAX_GRID_SPACING, settings.grid_horz_spacing);
+        assert_eq!(MIN_GRID_SPACING, settings.grid_vert_spacing);
+        assert_eq!([1.0, 0.0, 0.0, MIN_GRID_OPACITY], settings.grid_color);
+        assert_eq!(Deg(MAX_MAX_EMISSION_ANGLE), settings.max_emission_angle);
+    }
+
+    #[test]
+    fn rotation_comp_conversions_are_inverse() {
+        // a single frame's projection spans 180° over `disk_diameter` · π/2 pixels
+        assert!((rotation_comp_to_pixels(Deg(180.0), 100.0) - 50.0 * std::f32::consts::PI).abs() < 1.0e-3);
+
+        for (degrees, diameter) in [(0.5, 200.0), (2.25, 37.0), (0.0, 80.0)] {
+            let pixels = rotation_comp_to_pixels(Deg(degrees), diameter);
+            assert!((rotation_comp_to_degrees(pixels, diameter).0 - degrees).abs() < 1.0e-5);
+        }
+    }
+
+    #[test]
+    fn rotation_comp_in_degrees_scales_with_disk_diameter() {
+        let degrees = Deg(1.5);
+        let pixels = rotation_comp_to_pixels(degrees, 120.0);
+        // a refined disk fit keeps the longitude shift, while the pixel shift follows the diameter
+        assert!((rotation_comp_to_pixels(degrees, 150.0) - pixels * 150.0 / 120.0).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn automatic_rotation_comp_matches_previous_pixel_formula() {
+        let params = feather_test_params(0.0);
+        let previous = PI_2 * params.disk_diameter
+            / (0.5 * params.sidereal_rotation_period.as_secs_f32() / params.frame_interval.as_secs_f32());
+        let pixels = rotation_comp_to_pixels(coverage::rotation_per_frame(&params), params.disk_diameter);
+        assert!((pixels - previous).abs() < 1.0e-4 * previous);
+    }
+
+    #[test]
+    fn rotation_changes_affect_buffer_size() {
+        let params = feather_test_params(0.0);
+        let mut changed = params.clone();
+        changed.frame_interval = std::time::Duration::from_secs(90);
+        assert!(projection_buf_size_affected(&params, &changed));
+
+        let mut changed = params.clone();
+        changed.roll = Deg(10.0);
+        assert!(!projection_buf_size_affected(&params, &changed));
+    }
+
+    /// Returns pixel positions (along X for vertical lines, Y for horizontal ones) of lines in a buffer `size` pixels
+    /// long, as rasterized from normalized device coordinates.
+    fn line_pixels(lines: &[data::Vertex2], horizontal: bool, size: u32) -> Vec<u32> {
+        lines.iter().step_by(2).map(|v| {
+            let pos = if horizontal { v.position[1] } else { v.position[0] };
+            ((pos + 1.0) / 2.0 * size as f32).floor() as u32
+        }).collect()
+    }
+
+    #[test]
+    fn exported_grid_matches_preview() {
+        let params = GridParams{ color: [1.0; 4], horz_spacing: 0.3, vert_spacing: 0.45 };
+        let (export_width, export_height) = (157, 60);
+        let wh_ratio = export_width as f32 / export_height as f32;
+        // the preview shows the projection enlarged
+        let (preview_width, preview_height) = (3 * export_width, 3 * export_height);
+
+        let (horz_lines, vert_lines) = grid_lines(&params, wh_ratio);
+        assert!(!horz_lines.is_empty() && !vert_lines.is_empty());
+
+        for (lines, horizontal, export_size, preview_size) in [
+            (&horz_lines, true, export_height, preview_height),
+            (&vert_lines, false, export_width, preview_width)
+        ] {
+            let exported = line_pixels(lines, horizontal, export_size);
+            let preview = line_pixels(lines, horizontal, preview_size);
+            for (exported, preview) in exported.iter().zip(preview.iter()) {
+                assert_eq!(*exported, preview / 3);
+            }
+        }
+    }
+
+    #[test]
+    fn grid_spacing_is_relative_to_map_height() {
+        let params = GridParams{ color: [1.0; 4], horz_spacing: 0.5, vert_spacing: 0.25 };
+        let (width, height) = (400, 100);
+        let (horz_lines, vert_lines) = grid_lines(&params, width as f32 / height as f32);
+
+        // horizontal spacing is the distance between vertical lines
+        let columns = line_pixels(&vert_lines, false, width);
+        assert_eq!(vec![25, 50, 75], columns[..3].to_vec());
+        assert_eq!(15, columns.len());
+
+        let rows = line_pixels(&horz_lines, true, height);
+        assert_eq!(vec![12, 25, 37, 50, 62, 75, 87], rows);
+    }
+
+    fn linked_cursor_params() -> SourceParameters {
+        SourceParameters{
+            num_images: 4,
+            frame_interval: std::time::Duration::from_secs(300),
+            ..SourceParameters::test_default()
+        }
+    }
+
+    #[test]
+    fn linked_cursor_lands_on_the_same_feature() {
+        let params = linked_cursor_params();
+        let auto_comp = rotation_comp_to_pixels(coverage::rotation_per_frame(&params), params.disk_diameter);
+        let (planet_lon, lat) = (Deg(-25.0), Deg(30.0));
+
+        // (displayed frame, rotation compensation, projection type) of the linked views
+        let views = [
+            (0, 0.0, ProjectionType::Equirectangular),
+            (2, auto_comp, ProjectionType::LambertCylindricalEqualArea),
+            (3, 0.5 * auto_comp, ProjectionType::Equirectangular)
+        ];
+        for (frame, rotation_comp, projection_type) in views {
+            let pos = projection_position(planet_lon, lat, frame, &params, rotation_comp, projection_type).unwrap();
+            let (lon, found_lat) = projection_coords(pos, frame, &params, rotation_comp, projection_type).unwrap();
+            assert!((found_lat - lat).0.abs() < 1.0e-3);
+            assert!((planet_longitude(&params, frame, lon) - planet_lon).0.abs() < 1.0e-3, "frame {}", frame);
+        }
+    }
+
+    #[test]
+    fn linked_cursor_stays_in_place_with_matching_compensation() {
+        for direction in [projection::RotationDirection::Prograde, projection::RotationDirection::Retrograde] {
+            let params = SourceParameters{ rotation_direction: direction, ..linked_cursor_params() };
+            let auto_comp = rotation_comp_to_pixels(coverage::rotation_per_frame(&params), params.disk_diameter);
+
+            let positions: Vec<[f32; 2]> = (0..params.num_images).map(|frame| projection_position(
+                Deg(10.0), Deg(-20.0), frame, &params, auto_comp, ProjectionType::Equirectangular
+            ).unwrap()).collect();
+            for pos in &positions[1..] {
+                assert!((pos[0] - positions[0][0]).abs() < 1.0e-5, "{:?}: {:?}", direction, positions);
+                assert!((pos[1] - positions[0][1]).abs() < 1.0e-5);
+            }
+
+            // without compensation, the feature moves with the planet's rotation
+            let first = projection_position(Deg(10.0), Deg(-20.0), 0, &params, 0.0, ProjectionType::Equirectangular);
+            let last = projection_position(Deg(10.0), Deg(-20.0), 3, &params, 0.0, ProjectionType::Equirectangular);
+            assert!((last.unwrap()[0] - first.unwrap()[0]) * direction.sign() > 0.0);
+        }
+    }
+
+    #[test]
+    fn linked_cursor_outside_of_frame() {
+        let params = linked_cursor_params();
+        let rotation = coverage::rotation_per_frame(&params);
+
+        // the feature rotates past the limb by frame 3
+        let planet_lon = Deg(89.0) - rotation;
+        assert!(projection_position(planet_lon, Deg(0.0), 0, &params, 0.0, ProjectionType::Equirectangular).is_some());
+        assert!(projection_position(planet_lon, Deg(0.0), 3, &params, 0.0, ProjectionType::Equirectangular).is_none());
+        // the far side
+        assert!(projection_position(Deg(180.0), Deg(0.0), 0, &params, 0.0, ProjectionType::Equirectangular).is_none());
+    }
+
+    #[test]
+    #[ignore]
+    fn perf_projection_coords_and_source_image_position() {
+        let params = SourceParameters{ disk_diameter: 1000.0, ..linked_cursor_params() };
+        let width = strip_width(&params, 0.0).ceil() as u32;
+        let height = projection_height(ProjectionType::Equirectangular, params.disk_diameter, Deg(0.0));
+
+        crate::bench::measure(&format!("lon/lat mapping of a {}x{} map", width, height), 5, || {
+            let mut sum = 0.0;
+            for y in 0..height {
+                for x in 0..width {
+                    let pos = [(x as f32 + 0.5) / width as f32, (y as f32 + 0.5) / height as f32];
+                    if let Some((lon, lat)) = projection_coords(pos, 0, &params, 0.0, ProjectionType::Equirectangular) {
+                        sum += source_image_position(&params, lon, lat).x;
+                    }
+                }
+            }
+            sum
+        }).assert_within(std::time::Duration::from_millis(500));
+    }
 }
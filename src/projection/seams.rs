@@ -0,0 +1,194 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Matching of brightness at seams between consecutive frames of a rotation-compensated strip: each frame's
+//! brightness is compared with the next one's where they overlap, and per-frame gains are fitted which minimize
+//! the remaining steps.
+
+use crate::fmt;
+use std::error::Error;
+use std::path::Path;
+
+pub const FILE_NAME: &str = "seam_gains.txt";
+
+/// Pixels darker than this (in either frame) are not compared (background, faded-out limb).
+const MIN_VALUE: f32 = 16.0;
+
+/// Minimum number of compared pixels for a ratio to be used.
+const MIN_OVERLAP: usize = 100;
+
+/// Weight (relative to a single seam) of keeping the gains close to 1; prevents drift of the gains along long
+/// sequences.
+const REGULARIZATION: f64 = 0.01;
+
+/// Returns the median ratio of brightness of `image` to that of `next_image` (both RGB8 renderings of
+/// consecutive frames into the same strip) over pixels where both are lit; `None` if they barely overlap.
+pub fn overlap_ratio(image: &ga_image::Image, next_image: &ga_image::Image) -> Option<f32> {
+    assert!(image.width() == next_image.width() && image.height() == next_image.height());
+
+    let mut ratios = vec![];
+    for y in 0..image.height() {
+        let line = image.line::<u8>(y);
+        let next_line = next_image.line::<u8>(y);
+        for (pixel, next_pixel) in line.chunks_exact(3).zip(next_line.chunks_exact(3)) {
+            let value = brightness(pixel);
+            let next_value = brightness(next_pixel);
+            if value >= MIN_VALUE && next_value >= MIN_VALUE {
+                ratios.push(value / next_value);
+            }
+        }
+    }
+
+    if ratios.len() < MIN_OVERLAP { return None; }
+
+    let mid = ratios.len() / 2;
+    let (_, median, _) = ratios.select_nth_unstable_by(mid, |a, b| a.partial_cmp(b).unwrap());
+    Some(*median)
+}
+
+fn brightness(pixel: &[u8]) -> f32 {
+    (pixel[0] as f32 + pixel[1] as f32 + pixel[2] as f32) / 3.0
+}
+
+/// Returns per-frame gains given brightness ratios of subsequent frame pairs (see `overlap_ratio`; `ratios[i]`
+/// concerns frames `i` and `i + 1`, `None` if unknown).
+///
+/// In the log domain (x = ln gain, d = ln ratio), minimizes Σ (x[i+1] − x[i] − d[i])² + λ·Σ x[i]²; the solution's
+/// mean is 0, i.e., the overall brightness is preserved.
+pub fn chain_gains(ratios: &[Option<f32>]) -> Vec<f32> {
+    let n = ratios.len() + 1;
+
+    // tridiagonal system (λ·I + Lᵀ·L)·x = Lᵀ·d, where L is the (weighted) difference operator
+    let mut diag = vec![REGULARIZATION; n];
+    let mut off_diag = vec![0.0; n - 1];
+    let mut rhs = vec![0.0; n];
+    for (i, ratio) in ratios.iter().enumerate() {
+        if let Some(ratio) = ratio {
+            let d = (*ratio as f64).ln();
+            diag[i] += 1.0;
+            diag[i + 1] += 1.0;
+            off_diag[i] -= 1.0;
+            rhs[i] -= d;
+            rhs[i + 1] += d;
+        }
+    }
+
+    solve_tridiagonal(&diag, &off_diag, &rhs).iter().map(|x| x.exp() as f32).collect()
+}
+
+/// Solves a symmetric tridiagonal system (Thomas algorithm); `off_diag[i]` is the element at (i, i + 1).
+fn solve_tridiagonal(diag: &[f64], off_diag: &[f64], rhs: &[f64]) -> Vec<f64> {
+    let n = diag.len();
+    let mut c = vec![0.0; n];
+    let mut d = vec![0.0; n];
+
+    for i in 0..n {
+        let lower = if i > 0 { off_diag[i - 1] } else { 0.0 };
+        let denominator = diag[i] - lower * if i > 0 { c[i - 1] } else { 0.0 };
+        if i < n - 1 { c[i] = off_diag[i] / denominator; }
+        d[i] = (rhs[i] - lower * if i > 0 { d[i - 1] } else { 0.0 }) / denominator;
+    }
+
+    let mut x = vec![0.0; n];
+    for i in (0..n).rev() {
+        x[i] = d[i] - if i < n - 1 { c[i] * x[i + 1] } else { 0.0 };
+    }
+
+    x
+}
+
+/// Saves the gains (one line per frame) in `output_dir`.
+pub fn save(output_dir: &Path, ratios: &[Option<f32>], gains: &[f32]) -> Result<(), Box<dyn Error>> {
+    let mut contents = "# frame, seam brightness ratio to the next frame, applied gain\n".to_string();
+    for (idx, gain) in gains.iter().enumerate() {
+        let ratio = match ratios.get(idx) {
+            Some(Some(ratio)) => fmt::machine_number(*ratio as f64, 4),
+            _ => "-".to_string()
+        };
+        contents += &format!("{}, {}, {}\n", idx + 1, ratio, fmt::machine_number(*gain as f64, 4));
+    }
+
+    std::fs::write(output_dir.join(FILE_NAME), contents)?;
+
+    Ok(())
+}
+
+mod tests {
+    use super::*;
+    use ga_image::{Image, PixelFormat};
+
+    fn assert_close(expected: f32, actual: f32, tolerance: f32) {
+        assert!((expected - actual).abs() < tolerance, "expected {}, got {}", expected, actual);
+    }
+
+    /// Returns ratios of frames whose brightness is inversely proportional to `gains`.
+    fn ratios_of(gains: &[f32]) -> Vec<Option<f32>> {
+        gains.windows(2).map(|pair| Some(pair[1] / pair[0])).collect()
+    }
+
+    #[test]
+    fn injected_steps_are_removed() {
+        let true_gains = [1.0, 1.1, 0.95, 1.05, 1.2, 0.9];
+        let gains = chain_gains(&ratios_of(&true_gains));
+
+        // corrected brightness (true brightness 1 / true_gain, times the gain) is constant
+        let corrected: Vec<f32> =
+            gains.iter().zip(true_gains.iter()).map(|(gain, true_gain)| gain / true_gain).collect();
+        for value in &corrected {
+            assert_close(corrected[0], *value, 0.01);
+        }
+
+        // the overall brightness is preserved
+        let log_mean: f32 = gains.iter().map(|gain| gain.ln()).sum::<f32>() / gains.len() as f32;
+        assert_close(0.0, log_mean, 1.0e-5);
+    }
+
+    #[test]
+    fn unknown_ratios_split_the_chain() {
+        let gains = chain_gains(&[Some(0.8), None, Some(1.25)]);
+        // no information about frames 1 and 2 relative to each other; each pair is matched separately, keeping
+        // its overall brightness
+        assert_close(0.8, gains[1] / gains[0], 0.01);
+        assert_close(1.25, gains[3] / gains[2], 0.01);
+        assert_close(1.0, gains[0] * gains[1], 1.0e-5);
+        assert_close(1.0, gains[2] * gains[3], 1.0e-5);
+    }
+
+    #[test]
+    fn single_frame_is_unchanged() {
+        assert_eq!(vec![1.0], chain_gains(&[]));
+    }
+
+    #[test]
+    fn ratio_is_measured_over_lit_overlap() {
+        let (width, height) = (40, 10);
+        let mut image = Image::new(width, height, None, PixelFormat::RGB8, None, true);
+        let mut next_image = Image::new(width, height, None, PixelFormat::RGB8, None, true);
+        for y in 0..height {
+            // `image` covers columns 0-29, `next_image` columns 10-39
+            for x in 0..30 { image.line_mut::<u8>(y)[3 * x as usize..3 * x as usize + 3].fill(120); }
+            for x in 10..40 { next_image.line_mut::<u8>(y)[3 * x as usize..3 * x as usize + 3].fill(100); }
+        }
+
+        assert_close(1.2, overlap_ratio(&image, &next_image).unwrap(), 1.0e-6);
+
+        let empty = Image::new(width, height, None, PixelFormat::RGB8, None, true);
+        assert!(overlap_ratio(&image, &empty).is_none());
+    }
+}
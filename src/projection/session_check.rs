@@ -0,0 +1,367 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Detection of frames taken during different observing sessions (e.g., on different nights).
+
+use crate::config::{Configuration, ProjectionConfig};
+use crate::fmt;
+use crate::gui::modal::{self, KeyAction, KeyBindings};
+use crate::projection::data::ImageLoading;
+use crate::projection::ephem;
+use crate::projection::frame_time;
+use crate::projection::load_cache::FileStamp;
+use crate::projection::winjupos;
+use crate::projection::worker::FirstFrame;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const TITLE: &str = "Frames from different sessions?";
+
+pub const DEFAULT_MAX_GAP_MINUTES: u32 = 60;
+
+const MAX_GAP_MINUTES_RANGE: (i32, i32) = (1, 24 * 60);
+
+/// The frames should not span more than this many rotations of the planet.
+const MAX_SPAN_ROTATIONS: f64 = 1.5;
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+const UNIX_EPOCH_JULIAN_DATE: f64 = 2440587.5;
+
+/// Frames whose consecutive timestamps differ by no more than the maximum gap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cluster {
+    /// Indices of the frames, in ascending order.
+    pub indices: Vec<usize>,
+    /// Julian date of the earliest frame.
+    pub start: f64,
+    /// Julian date of the latest frame.
+    pub end: f64
+}
+
+/// Returns clusters of `times` (Julian dates) separated by gaps longer than `max_gap`, in chronological order.
+pub fn clusters(times: &[f64], max_gap: Duration) -> Vec<Cluster> {
+    let max_gap = max_gap.as_secs_f64() / SECONDS_PER_DAY;
+
+    let mut order: Vec<usize> = (0..times.len()).collect();
+    order.sort_by(|&a, &b| times[a].partial_cmp(&times[b]).unwrap());
+
+    let mut clusters: Vec<Cluster> = vec![];
+    for idx in order {
+        match clusters.last_mut() {
+            Some(cluster) if times[idx] - cluster.end <= max_gap => {
+                cluster.indices.push(idx);
+                cluster.end = times[idx];
+            },
+            _ => clusters.push(Cluster{ indices: vec![idx], start: times[idx], end: times[idx] })
+        }
+    }
+    for cluster in &mut clusters { cluster.indices.sort_unstable(); }
+
+    clusters
+}
+
+/// Plausibility assessment of frame timestamps.
+#[derive(Debug)]
+pub struct Analysis {
+    /// Time between the earliest and the latest frame.
+    pub span: Duration,
+    /// The frames span more than `MAX_SPAN_ROTATIONS` rotations of the planet.
+    pub span_too_long: bool,
+    pub clusters: Vec<Cluster>
+}
+
+impl Analysis {
+    /// Analyzes `times` (Julian dates) of frames of a planet with the given rotation period.
+    pub fn new(times: &[f64], rotation_period: Duration, max_gap: Duration) -> Analysis {
+        let clusters = clusters(times, max_gap);
+        let span = match (clusters.first(), clusters.last()) {
+            (Some(first), Some(last)) => Duration::from_secs_f64((last.end - first.start) * SECONDS_PER_DAY),
+            _ => Duration::ZERO
+        };
+
+        Analysis{
+            span,
+            span_too_long: span.as_secs_f64() > MAX_SPAN_ROTATIONS * rotation_period.as_secs_f64(),
+            clusters
+        }
+    }
+
+    /// Returns `true` if the frames may come from a single session.
+    pub fn plausible(&self) -> bool { !self.span_too_long && self.clusters.len() <= 1 }
+
+    /// Returns the cluster with the most frames (the earliest one in case of a tie).
+    pub fn largest_cluster(&self) -> &Cluster {
+        self.clusters.iter().rev().max_by_key(|cluster| cluster.indices.len()).unwrap()
+    }
+}
+
+/// Timestamps of loaded files.
+pub struct FileTimes {
+    pub julian_dates: Vec<f64>,
+    /// The timestamps come from WinJUPOS-style file names (otherwise from file modification times).
+    pub from_names: bool
+}
+
+/// Returns timestamps of `paths` taken from their names (if all of them are WinJUPOS-style), otherwise from the
+/// modification times in `stamps`; `None` if neither is available for all files.
+pub fn file_times(paths: &[PathBuf], stamps: &[Option<FileStamp>]) -> Option<FileTimes> {
+    let from_names: Option<Vec<f64>> = paths.iter()
+        .map(|path| path.file_name().and_then(|name| name.to_str()).and_then(winjupos::parse_file_name))
+        .map(|parsed| parsed.map(|(julian_date, _)| julian_date))
+        .collect();
+    if let Some(mut julian_dates) = from_names {
+        frame_time::correct_day_rollover_jd(&mut julian_dates);
+        return Some(FileTimes{ julian_dates, from_names: true });
+    }
+
+    let from_stamps: Option<Vec<f64>> = stamps.iter()
+        .map(|stamp| stamp.as_ref().map(|stamp| {
+            UNIX_EPOCH_JULIAN_DATE + stamp.modified_ns as f64 / 1.0e9 / SECONDS_PER_DAY
+        }))
+        .collect();
+
+    from_stamps.map(|julian_dates| FileTimes{ julian_dates, from_names: false })
+}
+
+/// Returns the elements of `items` at `indices` (sorted in ascending order).
+pub fn select<T>(items: Vec<T>, indices: &[usize]) -> Vec<T> {
+    let mut indices = indices.iter().peekable();
+
+    items.into_iter().enumerate().filter_map(|(idx, item)| {
+        if indices.peek() == Some(&&idx) {
+            indices.next();
+            Some(item)
+        } else {
+            None
+        }
+    }).collect()
+}
+
+pub fn max_gap(config: &Configuration) -> Duration {
+    Duration::from_secs(60 * config.session_gap_minutes().unwrap_or(DEFAULT_MAX_GAP_MINUTES) as u64)
+}
+
+/// Returns "YYYY-MM-DD HH:MM" for the time `julian_date` (UTC).
+fn format_utc(julian_date: f64) -> String {
+    // days start at Julian date N - 0.5
+    let minutes = ((julian_date + 0.5) * 1440.0).round() as i64;
+    let (year, month, day) = ephem::calendar_date(minutes.div_euclid(1440));
+    let minute_of_day = minutes.rem_euclid(1440);
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, minute_of_day / 60, minute_of_day % 60)
+}
+
+/// Returns a description of the problems found by `analysis`.
+pub fn summary(analysis: &Analysis, rotation_period: Duration, max_gap: Duration, format: &fmt::Preferences) -> String {
+    let mut lines = vec![];
+    if analysis.span_too_long {
+        lines.push(format!(
+            "The frames span {}, i.e., {} rotations of the planet.",
+            fmt::format_duration(analysis.span, format),
+            fmt::format_number(analysis.span.as_secs_f64() / rotation_period.as_secs_f64(), 1, format)
+        ));
+    }
+    if analysis.clusters.len() > 1 {
+        lines.push(format!(
+            "They form {} groups separated by gaps longer than {}:",
+            analysis.clusters.len(),
+            fmt::format_duration(max_gap, format)
+        ));
+        for (idx, cluster) in analysis.clusters.iter().enumerate() {
+            lines.push(format!(
+                "  {}. {} – {} UTC: {} frame(s)",
+                idx + 1,
+                format_utc(cluster.start),
+                format_utc(cluster.end),
+                cluster.indices.len()
+            ));
+        }
+    }
+    if lines.is_empty() { lines.push("With these settings the frames form a single session.".to_string()); }
+
+    lines.join("\n")
+}
+
+pub enum SessionCheckResult {
+    Pending,
+    /// Only the frames of the largest cluster are to be used.
+    KeepLargest,
+    ProceedAnyway,
+    /// The loaded frames are to be discarded.
+    Cancel
+}
+
+/// Loaded frames with implausible timestamps, awaiting the user's decision.
+pub struct SessionCheck {
+    pub image_loading: ImageLoading,
+    pub first_frame: FirstFrame,
+    pub skipped_files_message: Option<String>,
+    times: FileTimes,
+    rotation_period: Duration,
+    analysis: Analysis,
+    max_gap_minutes: i32
+}
+
+impl SessionCheck {
+    pub fn new(
+        image_loading: ImageLoading,
+        first_frame: FirstFrame,
+        skipped_files_message: Option<String>,
+        times: FileTimes,
+        rotation_period: Duration,
+        max_gap: Duration
+    ) -> SessionCheck {
+        let analysis = Analysis::new(&times.julian_dates, rotation_period, max_gap);
+        SessionCheck{
+            image_loading,
+            first_frame,
+            skipped_files_message,
+            times,
+            rotation_period,
+            analysis,
+            max_gap_minutes: (max_gap.as_secs() / 60) as i32
+        }
+    }
+
+    pub fn analysis(&self) -> &Analysis { &self.analysis }
+
+    fn max_gap(&self) -> Duration { Duration::from_secs(60 * self.max_gap_minutes as u64) }
+}
+
+pub fn handle_session_check(
+    ui: &imgui::Ui,
+    config: &mut Configuration,
+    format: &fmt::Preferences,
+    check: &mut SessionCheck
+) -> SessionCheckResult {
+    let mut result = SessionCheckResult::Pending;
+
+    // the loaded images must not be discarded by accident, so Escape does nothing
+    let bindings = KeyBindings{ escape_cancels: false, enter_accepts: true };
+    modal::modal(ui, config, TITLE, bindings, |key_action, config| {
+        ui.text(format!(
+            "The frames' timestamps (taken from {}) suggest they come from more than one observing session.",
+            if check.times.from_names { "file names" } else { "file modification times" }
+        ));
+        ui.separator();
+        ui.text(summary(&check.analysis, check.rotation_period, check.max_gap(), format));
+        ui.separator();
+
+        let w = ui.push_item_width(ui.calc_text_size("M")[0] * 8.0);
+        if ui.input_int("max. gap within a session (min)", &mut check.max_gap_minutes).step(10).build() {
+            check.max_gap_minutes = check.max_gap_minutes.clamp(MAX_GAP_MINUTES_RANGE.0, MAX_GAP_MINUTES_RANGE.1);
+            config.set_session_gap_minutes(check.max_gap_minutes as u32);
+            check.analysis = Analysis::new(&check.times.julian_dates, check.rotation_period, check.max_gap());
+        }
+        w.end();
+        ui.separator();
+
+        let multiple_clusters = check.analysis.clusters.len() > 1;
+        let token = ui.begin_disabled(!multiple_clusters);
+        let label = format!("Keep largest group ({} frames)", check.analysis.largest_cluster().indices.len());
+        if modal::default_button(ui, &label) || (key_action == KeyAction::Accept && multiple_clusters) {
+            result = SessionCheckResult::KeepLargest;
+            ui.close_current_popup();
+        }
+        token.end();
+        ui.same_line();
+
+        if ui.button("Proceed anyway") {
+            result = SessionCheckResult::ProceedAnyway;
+            ui.close_current_popup();
+        }
+        ui.same_line();
+
+        if ui.button("Cancel loading") {
+            result = SessionCheckResult::Cancel;
+            ui.close_current_popup();
+        }
+    });
+
+    result
+}
+
+mod tests {
+    use super::*;
+
+    const JUPITER_ROTATION: Duration = Duration::from_secs(9 * 3600 + 55 * 60 + 30);
+
+    /// Returns Julian dates of frames taken every `interval_min` minutes starting at `start_min` minutes after
+    /// an arbitrary epoch.
+    fn frames(start_min: f64, interval_min: f64, count: usize) -> Vec<f64> {
+        (0..count).map(|i| 2460000.5 + (start_min + i as f64 * interval_min) / 1440.0).collect()
+    }
+
+    #[test]
+    fn single_session_is_plausible() {
+        let times = frames(0.0, 2.0, 60);
+        let analysis = Analysis::new(&times, JUPITER_ROTATION, Duration::from_secs(3600));
+        assert!(analysis.plausible());
+        assert_eq!(1, analysis.clusters.len());
+        assert_eq!((0..60).collect::<Vec<_>>(), analysis.clusters[0].indices);
+    }
+
+    #[test]
+    fn two_sessions_are_split() {
+        // the second night's frames are listed first
+        let mut times = frames(24.0 * 60.0, 3.0, 10);
+        times.extend(frames(0.0, 3.0, 20));
+        let analysis = Analysis::new(&times, JUPITER_ROTATION, Duration::from_secs(3600));
+        assert!(!analysis.plausible());
+        assert!(analysis.span_too_long);
+        assert_eq!(2, analysis.clusters.len());
+        assert_eq!((10..30).collect::<Vec<_>>(), analysis.largest_cluster().indices);
+        assert_eq!((0..10).collect::<Vec<_>>(), analysis.clusters[1].indices);
+    }
+
+    #[test]
+    fn gap_within_rotation_is_detected() {
+        let mut times = frames(0.0, 5.0, 10);
+        times.extend(frames(180.0, 5.0, 10));
+        let analysis = Analysis::new(&times, JUPITER_ROTATION, Duration::from_secs(3600));
+        assert!(!analysis.span_too_long);
+        assert_eq!(2, analysis.clusters.len());
+        // the earlier cluster wins a tie
+        assert_eq!((0..10).collect::<Vec<_>>(), analysis.largest_cluster().indices);
+
+        assert!(Analysis::new(&times, JUPITER_ROTATION, Duration::from_secs(4 * 3600)).plausible());
+    }
+
+    #[test]
+    fn long_span_without_gaps_is_detected() {
+        let times = frames(0.0, 30.0, 40);
+        let analysis = Analysis::new(&times, JUPITER_ROTATION, Duration::from_secs(3600));
+        assert_eq!(1, analysis.clusters.len());
+        assert!(analysis.span_too_long);
+        assert!(!analysis.plausible());
+    }
+
+    #[test]
+    fn selects_items() {
+        assert_eq!(vec!['b', 'd'], select(vec!['a', 'b', 'c', 'd'], &[1, 3]));
+        assert!(select(vec!['a', 'b'], &[]).is_empty());
+    }
+
+    #[test]
+    fn formats_utc() {
+        assert_eq!("2000-01-01 12:00", format_utc(2451545.0));
+        assert_eq!("1999-12-31 23:59", format_utc(2451544.4993));
+    }
+}
@@ -0,0 +1,88 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Tracking of changes which have not been saved yet, shown as a marker in window titles.
+
+use std::collections::HashSet;
+
+#[derive(Default)]
+pub struct SessionState {
+    /// Source parameters have been edited.
+    source_params_edited: bool,
+    /// IDs of views whose settings have been edited.
+    edited_views: HashSet<u32>
+}
+
+impl SessionState {
+    /// Returns `true` if there are any unsaved changes.
+    pub fn is_dirty(&self) -> bool { self.source_params_edited || !self.edited_views.is_empty() }
+
+    pub fn source_params_edited(&self) -> bool { self.source_params_edited }
+
+    pub fn view_edited(&self, view_id: u32) -> bool { self.edited_views.contains(&view_id) }
+
+    pub fn mark_source_params_edited(&mut self) { self.source_params_edited = true; }
+
+    pub fn mark_view_edited(&mut self, view_id: u32) { self.edited_views.insert(view_id); }
+
+    /// To be called when the session has been saved or replaced (e.g., by loading new images).
+    pub fn mark_clean(&mut self) {
+        self.source_params_edited = false;
+        self.edited_views.clear();
+    }
+}
+
+/// Shown when confirming an action which discards unsaved changes.
+pub const UNSAVED_CHANGES_WARNING: &str =
+    "Source parameters or view settings have been changed; the changes will be lost.";
+
+/// Returns `title` prefixed with the unsaved changes marker if `unsaved`.
+pub fn marked_title(title: &str, unsaved: bool) -> String {
+    if unsaved { format!("*{}", title) } else { title.to_string() }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_make_session_dirty_until_cleaned() {
+        let mut state = SessionState::default();
+        assert!(!state.is_dirty());
+
+        state.mark_source_params_edited();
+        assert!(state.is_dirty() && state.source_params_edited());
+
+        state.mark_view_edited(3);
+        state.mark_view_edited(3);
+        assert!(state.view_edited(3) && !state.view_edited(4));
+
+        state.mark_clean();
+        assert!(!state.is_dirty() && !state.source_params_edited() && !state.view_edited(3));
+
+        state.mark_view_edited(4);
+        assert!(state.is_dirty() && !state.source_params_edited());
+    }
+
+    #[test]
+    fn unsaved_changes_are_marked_in_title() {
+        assert_eq!("Vislumino", marked_title("Vislumino", false));
+        assert_eq!("*Vislumino", marked_title("Vislumino", true));
+        assert_eq!("*Projection - frame 3", marked_title("Projection - frame 3", true));
+    }
+}
@@ -0,0 +1,197 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! A/B comparison of source parameter sets: two stored snapshots the user toggles between.
+
+use crate::projection::projection_view::{ProjectionSettings, ProjectionType};
+use crate::projection::source_view::SourceParameters;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Slot { A, B }
+
+impl Slot {
+    pub fn name(&self) -> &str {
+        match self {
+            Slot::A => "A",
+            Slot::B => "B"
+        }
+    }
+
+    fn other(&self) -> Slot {
+        match self {
+            Slot::A => Slot::B,
+            Slot::B => Slot::A
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Snapshot {
+    pub src_params: SourceParameters,
+    /// ID and settings of the projection view which was active when storing the snapshot (if any).
+    pub view: Option<(u32, ProjectionSettings)>
+}
+
+#[derive(Default)]
+pub struct Snapshots {
+    a: Option<Snapshot>,
+    b: Option<Snapshot>,
+    /// Slot whose snapshot has been stored or applied most recently.
+    active: Option<Slot>
+}
+
+impl Snapshots {
+    pub fn get(&self, slot: Slot) -> Option<&Snapshot> {
+        match slot {
+            Slot::A => self.a.as_ref(),
+            Slot::B => self.b.as_ref()
+        }
+    }
+
+    pub fn active(&self) -> Option<Slot> { self.active }
+
+    pub fn store(&mut self, slot: Slot, snapshot: Snapshot) {
+        match slot {
+            Slot::A => self.a = Some(snapshot),
+            Slot::B => self.b = Some(snapshot)
+        }
+        self.active = Some(slot);
+    }
+
+    /// Returns `true` if both slots are filled.
+    pub fn can_toggle(&self) -> bool { self.a.is_some() && self.b.is_some() }
+
+    /// Switches to the other slot and returns its snapshot (to be applied). Does nothing if a slot is empty.
+    pub fn toggle(&mut self) -> Option<&Snapshot> {
+        if !self.can_toggle() { return None; }
+        let slot = self.active.map_or(Slot::A, |active| active.other());
+        self.active = Some(slot);
+        self.get(slot)
+    }
+
+    /// Returns names of parameters which differ between the stored snapshots (empty if a slot is empty).
+    pub fn differences(&self) -> Vec<&'static str> {
+        match (&self.a, &self.b) {
+            (Some(a), Some(b)) => differences(a, b),
+            _ => vec![]
+        }
+    }
+}
+
+pub fn differences(a: &Snapshot, b: &Snapshot) -> Vec<&'static str> {
+    let (pa, pb) = (&a.src_params, &b.src_params);
+    let mut result = vec![];
+    let mut check = |name, differs: bool| if differs { result.push(name); };
+
+    check("inclination", pa.inclination != pb.inclination);
+    check("frame interval", pa.frame_interval != pb.frame_interval);
+    check("roll", pa.roll != pb.roll);
+    check("disk center", pa.disk_center != pb.disk_center);
+    check("disk diameter", pa.disk_diameter != pb.disk_diameter);
+    check("flattening", pa.flattening != pb.flattening);
+    check("rotation period", pa.sidereal_rotation_period != pb.sidereal_rotation_period);
+    check("rotation direction", pa.rotation_direction != pb.rotation_direction);
+    check("phase", pa.phase != pb.phase);
+    check("exposure normalization", pa.frame_gains != pb.frame_gains);
+    check("per-frame roll", pa.frame_rolls != pb.frame_rolls);
+    check("mirror E-W", pa.mirror_ew != pb.mirror_ew);
+    check("flip N-S", pa.flip_ns != pb.flip_ns);
+
+    // view settings can only be compared if both snapshots refer to the same view
+    if let (Some((id_a, va)), Some((id_b, vb))) = (&a.view, &b.view) {
+        if id_a == id_b {
+            check("projection", va.projection_type != vb.projection_type);
+            check(
+                "standard parallel",
+                va.projection_type == ProjectionType::LambertCylindricalEqualArea
+                    && va.standard_parallel != vb.standard_parallel
+            );
+            check("rotation compensation", va.rotation_comp != vb.rotation_comp);
+        }
+    }
+
+    result
+}
+
+mod tests {
+    use super::*;
+    use cgmath::Deg;
+    use crate::projection::Planet;
+
+    fn snapshot(view_id: Option<u32>) -> Snapshot {
+        Snapshot{
+            src_params: SourceParameters{
+                num_images: 10,
+                disk_diameter: 50.0,
+                flattening: Planet::Jupiter.flattening(),
+                ..SourceParameters::test_default()
+            },
+            view: view_id.map(|id| (id, ProjectionSettings{
+                projection_type: ProjectionType::Equirectangular,
+                standard_parallel: Deg(30.0),
+                rotation_comp: None
+            }))
+        }
+    }
+
+    #[test]
+    fn toggling_requires_both_slots_and_alternates() {
+        let mut snapshots = Snapshots::default();
+        assert!(snapshots.toggle().is_none());
+
+        snapshots.store(Slot::A, snapshot(None));
+        assert_eq!(Some(Slot::A), snapshots.active());
+        assert!(snapshots.toggle().is_none());
+        assert_eq!(Some(Slot::A), snapshots.active());
+
+        let mut b = snapshot(None);
+        b.src_params.roll = Deg(5.0);
+        snapshots.store(Slot::B, b);
+        assert_eq!(Some(Slot::B), snapshots.active());
+
+        assert_eq!(Deg(0.0), snapshots.toggle().unwrap().src_params.roll);
+        assert_eq!(Some(Slot::A), snapshots.active());
+        assert_eq!(Deg(5.0), snapshots.toggle().unwrap().src_params.roll);
+        assert_eq!(Some(Slot::B), snapshots.active());
+    }
+
+    #[test]
+    fn differing_parameters_are_listed() {
+        let mut snapshots = Snapshots::default();
+        snapshots.store(Slot::A, snapshot(Some(1)));
+        assert!(snapshots.differences().is_empty());
+
+        let mut b = snapshot(Some(1));
+        snapshots.store(Slot::B, b.clone());
+        assert!(snapshots.differences().is_empty());
+
+        b.src_params.disk_diameter = 51.0;
+        b.src_params.mirror_ew = true;
+        b.view.as_mut().unwrap().1.rotation_comp = Some(Deg(1.0));
+        // standard parallel is irrelevant for the equirectangular projection
+        b.view.as_mut().unwrap().1.standard_parallel = Deg(20.0);
+        snapshots.store(Slot::B, b.clone());
+        assert_eq!(vec!["disk diameter", "mirror E-W", "rotation compensation"], snapshots.differences());
+
+        // settings of different views are not compared
+        b.view.as_mut().unwrap().0 = 2;
+        snapshots.store(Slot::B, b);
+        assert_eq!(vec!["disk diameter", "mirror E-W"], snapshots.differences());
+    }
+}
@@ -21,22 +21,101 @@ use cgmath::{Basis3, Deg, EuclideanSpace, Matrix3, Matrix4, Point2, Point3, Rota
 use glium::GlObject;
 use crate::data;
 use crate::data::{TextureId, ToArray};
+use crate::fmt;
+use crate::gpu::{registry, render_check, render_throttle::RenderThrottle, resize_debounce::ResizeDebounce};
 use crate::gui;
 use crate::gui::{draw_buffer::{DrawBuffer, Sampling}, GuiState};
+use crate::gui::shortcuts::{Action, WindowKind};
+use crate::image_utils;
+use crate::logging;
 use crate::projection;
-use crate::projection::{data::create_half_parallel, Planet};
+use crate::projection::{data::create_half_parallel, ephem, Planet, RotationDirection, TrackedRotation};
+use crate::projection::diameter_tool::{self, DiameterTool};
+use crate::projection::field_rotation::{self, Site};
+use crate::projection::frame_time::{self, FrameTimes};
+use crate::projection::linking::{self, LinkedParameter, LinkGroup, LinkMember, LinkSubscriber};
+use crate::projection::load_options_dialog::LoadOptions;
+use crate::projection::orientation_gizmo::{self, OrientationGizmo};
+use crate::projection::overlay_color::{self, OverlaySettings};
+use crate::projection::phase::{self, Phase};
+use crate::projection::snapshots::{Slot, Snapshots};
 use crate::subscriber::{Subscriber, SubscriberCollection};
 use glium::{Surface, texture::Texture2d, uniform};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::{Rc, Weak};
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use strum::IntoEnumIterator;
+
+/// Roll values must stay within the range covered by the roll controls.
+const MAX_ROLL: f32 = 49.99;
+
+/// Smallest disk diameter (in pixels) which can be set.
+const MIN_DISK_DIAMETER: f32 = 10.0;
+
+/// Owner name of resources shown in the GPU resource registry.
+const REGISTRY_OWNER: &str = "Source view";
+
+/// Number of recent frame changes over which the achieved playback FPS is measured.
+const FPS_MEASUREMENT_FRAMES: usize = 30;
+
+/// Minimum and maximum width of the sliders in the playback controls (in widths of "M").
+const PLAYBACK_SLIDER_WIDTH: (f32, f32) = (5.0, 16.0);
+
+/// Default number of frames stacked together by "Stack in groups".
+const DEFAULT_STACK_GROUP_SIZE: usize = 4;
+
+/// Widths of the playback controls (without spacing between them).
+struct PlaybackWidths {
+    /// The "playback" label with the play and bounce buttons.
+    buttons: f32,
+    fps_label: f32,
+    every_frame: f32,
+    spacing: f32,
+    min_slider: f32,
+    max_slider: f32
+}
+
+#[derive(Debug, PartialEq)]
+struct PlaybackLayout {
+    fps_on_new_line: bool,
+    every_frame_on_new_line: bool,
+    fps_slider_width: f32
+}
+
+/// Returns the layout of the playback controls wrapped to fit in `avail` width (as far as possible).
+fn playback_layout(avail: f32, widths: &PlaybackWidths) -> PlaybackLayout {
+    let min_fps_width = widths.fps_label + widths.spacing + widths.min_slider;
+
+    let fps_on_new_line = widths.buttons + widths.spacing + min_fps_width > avail;
+    let fps_start = if fps_on_new_line { 0.0 } else { widths.buttons + widths.spacing };
+    let every_frame_on_new_line = fps_start + min_fps_width + widths.spacing + widths.every_frame > avail;
+
+    let mut slider_space = avail - fps_start - widths.fps_label - widths.spacing;
+    if !every_frame_on_new_line { slider_space -= widths.spacing + widths.every_frame; }
+
+    PlaybackLayout{
+        fps_on_new_line,
+        every_frame_on_new_line,
+        fps_slider_width: slider_space.min(widths.max_slider).max(widths.min_slider)
+    }
+}
 
 struct Playback {
     enabled: bool,
     tstart: Option<std::time::Instant>,
     first_frame: Option<usize>,
     initial_bouncing_back: Option<bool>,
-    current_bouncing_back: Option<bool>
+    current_bouncing_back: Option<bool>,
+    /// Show every frame (advancing by at most one frame per GUI frame) instead of keeping to the FPS value.
+    every_frame: bool,
+    /// Number of frames advanced since `tstart`.
+    frame_count: usize,
+    /// Number of frames skipped since `tstart` (when FPS exceeds the GUI frame rate).
+    skipped_frames: usize,
+    /// Times of the most recent frame changes.
+    frame_times: VecDeque<Instant>
 }
 
 #[derive(Clone)]
@@ -50,6 +129,145 @@ pub struct SourceParameters {
     /// Value: 1.0 - polar_radius / equatorial_radius.
     pub flattening: f32,
     pub sidereal_rotation_period: Duration,
+    /// Direction of the rotation described by `sidereal_rotation_period`; retrograde rotation reverses the drift of
+    /// features between frames.
+    pub rotation_direction: RotationDirection,
+    pub phase: Phase,
+    /// Per-frame brightness gains; empty if exposure normalization is disabled.
+    pub frame_gains: Vec<f32>,
+    /// Per-frame roll offsets (added to `roll`, e.g., compensating field rotation); empty if roll is constant.
+    pub frame_rolls: Vec<Deg<f32>>,
+    /// Source images are mirrored east-west (e.g., captured via a star diagonal). Mirroring is applied after roll,
+    /// i.e., roll refers to the un-mirrored image.
+    pub mirror_ew: bool,
+    /// Source images are flipped north-south.
+    pub flip_ns: bool
+}
+
+impl SourceParameters {
+    /// Returns sign multipliers of image disk coordinates (X, Y) undoing mirroring of source images.
+    pub fn image_mirror(&self) -> [f32; 2] {
+        [if self.mirror_ew { -1.0 } else { 1.0 }, if self.flip_ns { -1.0 } else { 1.0 }]
+    }
+
+    pub fn frame_gain(&self, idx: usize) -> f32 {
+        self.frame_gains.get(idx).copied().unwrap_or(1.0)
+    }
+
+    pub fn frame_roll(&self, idx: usize) -> Deg<f32> {
+        self.roll + self.frame_rolls.get(idx).copied().unwrap_or(Deg(0.0))
+    }
+
+    /// Returns parameters used as a base by tests: a single frame of a spherical, prograde-rotating planet (with
+    /// Jupiter's rotation period) of diameter 100 centered at (100, 100), with no inclination, roll or mirroring.
+    pub fn test_default() -> SourceParameters {
+        SourceParameters{
+            num_images: 1,
+            inclination: Deg(0.0),
+            frame_interval: Duration::from_secs(60),
+            roll: Deg(0.0),
+            disk_center: Point2{ x: 100.0, y: 100.0 },
+            disk_diameter: 100.0,
+            flattening: 0.0,
+            sidereal_rotation_period: Planet::Jupiter.sidereal_rotation(),
+            rotation_direction: RotationDirection::Prograde,
+            phase: Default::default(),
+            frame_gains: vec![],
+            frame_rolls: vec![],
+            mirror_ew: false,
+            flip_ns: false
+        }
+    }
+}
+
+/// Holds source parameters and notifies subscribers about their changes. Changes are batched: any number of them
+/// (e.g., all made during a single GUI frame) results in one notification, sent by `commit`.
+struct SourceParamsController {
+    params: SourceParameters,
+    dirty: bool,
+    subscribers: SubscriberCollection<SourceParameters>
+}
+
+impl SourceParamsController {
+    fn new(params: SourceParameters) -> SourceParamsController {
+        SourceParamsController{ params, dirty: false, subscribers: Default::default() }
+    }
+
+    fn get(&self) -> &SourceParameters { &self.params }
+
+    /// Gives access to parameters for modification; subscribers will be notified on the next `commit`.
+    fn edit(&mut self) -> &mut SourceParameters {
+        self.dirty = true;
+        &mut self.params
+    }
+
+    /// Replaces all parameters; subscribers will be notified on the next `commit`.
+    fn replace(&mut self, params: SourceParameters) {
+        *self.edit() = params;
+    }
+
+    fn subscribe(&mut self, subscriber: Weak<RefCell<dyn Subscriber<SourceParameters>>>) {
+        self.subscribers.add(subscriber);
+    }
+
+    fn unsubscribe(&mut self, subscriber: &Weak<RefCell<dyn Subscriber<SourceParameters>>>) {
+        self.subscribers.remove(subscriber);
+    }
+
+    /// Notifies subscribers if there were any changes since the previous commit; returns `true` if so.
+    fn commit(&mut self) -> bool {
+        if !self.dirty { return false; }
+
+        self.dirty = false;
+        self.subscribers.notify(&self.params);
+
+        true
+    }
+}
+
+/// Receives linked parameter changes made by other members of the view's link group; they are applied during
+/// the view's next GUI frame.
+#[derive(Default)]
+struct LinkInbox {
+    pending: Option<(SourceParameters, Vec<LinkedParameter>)>
+}
+
+impl LinkMember for LinkInbox {
+    fn on_linked_change(&mut self, values: &SourceParameters, linked: &[LinkedParameter]) {
+        self.pending = Some((values.clone(), linked.to_vec()));
+    }
+}
+
+/// Frames replaced by stacks of their consecutive groups; restored by "Revert stacking".
+struct Unstacked {
+    images: Vec<Rc<Texture2d>>,
+    file_paths: Vec<PathBuf>,
+    group_size: usize
+}
+
+/// Membership in a link group.
+struct Link {
+    inbox: Rc<RefCell<LinkInbox>>,
+    /// Forwards the view's parameter changes to the group; dropping it leaves the group.
+    subscriber: Rc<RefCell<LinkSubscriber>>
+}
+
+/// Action requested via the source view's controls.
+#[derive(PartialEq)]
+pub enum SourceViewRequest {
+    None,
+    /// Stack all frames into the average pseudo-frame.
+    Stacking,
+    /// Replace the frames with stacks of their consecutive groups of the specified size.
+    GroupStacking(usize),
+    /// Measure brightness of all frames for exposure normalization.
+    BrightnessMeasurement,
+    /// Measure orientation of the limb in all frames for per-frame roll.
+    LimbMeasurement,
+    /// Store the current parameters (and settings of the active projection view) in the A/B snapshot slot.
+    StoreSnapshot(Slot),
+    /// Apply the other A/B snapshot.
+    ToggleSnapshot
 }
 
 /// Shows source images and planet outline.
@@ -59,7 +277,25 @@ pub struct SourceView {
     draw_buffer: DrawBuffer,
     wh_ratio: f32,
     images: Vec<Rc<Texture2d>>,
-    texture_copy_prog: Rc<glium::Program>,
+    /// Files from which `images` were loaded.
+    file_paths: Vec<PathBuf>,
+    /// Times of `images` (seconds since the earliest one) taken from the file names, if available.
+    frame_timestamps: Option<Vec<f64>>,
+    /// Frames whose timestamps are earlier than those of the preceding frames, awaiting the user's decision
+    /// (see `frame_time::handle_out_of_order_prompt`).
+    out_of_order_frames: Vec<usize>,
+    /// Entered in the time seeking field.
+    seek_time: String,
+    /// Average of all frames; shown as a pseudo-frame, not included in `images` nor in `src_params.num_images`.
+    avg_image: Option<(Rc<Texture2d>, ga_image::Image)>,
+    showing_avg: bool,
+    stacking_sigma_clip: bool,
+    /// Group size for "Stack in groups".
+    stack_group_size: usize,
+    /// Original frames (if replaced by stacks of their groups).
+    unstacked: Option<Unstacked>,
+    /// Copies the source image (optionally highlighting saturated pixels).
+    texturing_prog: Rc<glium::Program>,
     solid_color_3d_prog: Rc<glium::Program>,
     unit_quad: Rc<glium::VertexBuffer<data::Vertex2>>,
     unit_circle: Rc<glium::VertexBuffer<data::Vertex3>>,
@@ -67,9 +303,61 @@ pub struct SourceView {
     current_img_idx: usize,
     image_size: [u32; 2],
     planet: Option<Planet>, // `None` means "custom",
-    src_params: SourceParameters,
+    /// Rotation of the selected planet the sequence is compensated for.
+    tracked_rotation: TrackedRotation,
+    src_params: SourceParamsController,
+    /// Options the current images were loaded with.
+    load_options: LoadOptions,
+    /// Bit depth the current images were loaded with (the stacks made by `set_stacked_images` are 8-bit).
+    bit_depth: image_utils::BitDepth,
+    /// Interval between captured frames; `src_params.frame_interval` is this value times the load decimation.
+    capture_frame_interval: Duration,
+    /// UTC date and time of observation as entered by the user ("YYYY-MM-DD HH:MM[:SS]").
+    observation_time: String,
+    /// Julian date corresponding to `observation_time` (if valid).
+    observation_jd: Option<f64>,
+    /// Mean brightness inside the disk of each frame.
+    measured_brightness: Option<Vec<f32>>,
+    normalize_exposure: bool,
+    /// Per-frame roll offsets (relative to the first frame) from field rotation analysis.
+    roll_offsets: Option<Vec<Deg<f32>>>,
+    /// Apply `roll_offsets` (otherwise roll is constant).
+    per_frame_roll: bool,
+    /// The most recent limb measurement did not produce roll offsets.
+    limb_measurement_failed: bool,
+    /// Observing site for the parallactic angle model of field rotation.
+    site: Site,
     current_image_subscribers: SubscriberCollection<(usize, Rc<Texture2d>)>,
-    src_params_subscribers: SubscriberCollection<SourceParameters>
+    /// Registrations of `images` and `avg_image` in the GPU resource registry.
+    texture_registrations: Vec<registry::Registration>,
+    link: Option<Link>,
+    /// Rendering has failed or has been skipped and is to be repeated.
+    render_pending: Cell<bool>,
+    render_throttle: RenderThrottle,
+    resize_debounce: ResizeDebounce,
+    /// The window is to be focused with the disk controls opened (once).
+    focus_disk_controls: bool,
+    /// The terminator is drawn over the source image (if phase handling is enabled).
+    terminator_shown: bool,
+    /// Saturated pixels of the source image are highlighted.
+    saturation_shown: bool,
+    /// Color of the disk outline and parallels.
+    overlay: OverlaySettings,
+    /// `overlay` has been changed by the user and is to be saved.
+    overlay_changed: bool,
+    /// Source parameters have changed since the last `take_src_params_changed`.
+    src_params_changed: bool,
+    /// Automatically chosen overlay color with the frame (`None`: average frame), disk center and diameter
+    /// it has been chosen for.
+    auto_overlay_color: RefCell<Option<((Option<usize>, Point2<f32>, f32), [f32; 3])>>,
+    /// The frame inspected with the loupe, read back from the GPU.
+    loupe_frame: RefCell<Option<(TextureId, ga_image::Image)>>,
+    orientation_gizmo: OrientationGizmo,
+    /// Parameter sets stored for A/B comparison.
+    snapshots: Snapshots,
+    diameter_tool: DiameterTool,
+    /// Completing a diameter measurement also sets roll.
+    diameter_tool_sets_roll: bool
 }
 
 impl SourceView {
@@ -78,10 +366,16 @@ impl SourceView {
         display: &glium::Display,
         renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>,
         src_images: Vec<Rc<Texture2d>>, // all images must have the same dimensions
+        file_paths: Vec<PathBuf>,
         disk_center: Point2<f32>,
-        disk_diameter: f32
-    ) -> SourceView {
-        let draw_buffer = DrawBuffer::new(
+        disk_diameter: f32,
+        load_options: LoadOptions,
+        bit_depth: image_utils::BitDepth,
+        planet: Planet
+    ) -> Result<SourceView, String> {
+        let image_size = common_size(&texture_sizes(&src_images), &file_paths)?;
+
+        let mut draw_buffer = DrawBuffer::new(
             Sampling::Single,
             &gl_objects.texture_copy_single,
             &gl_objects.texture_copy_multi,
@@ -89,25 +383,56 @@ impl SourceView {
             display,
             renderer
         );
-
-        let image_size = check_sizes_match(&src_images);
-        if image_size[0] == 0 || image_size[1] == 0 { panic!("image width nor height cannot be zero"); }
+        draw_buffer.register(REGISTRY_OWNER, "view");
 
         let num_images = src_images.len();
+        let capture_frame_interval = Duration::from_secs(60);
+        let frame_timestamps = frame_time::timestamps_from_names(&file_paths);
+
+        let src_params = SourceParameters{
+            num_images,
+            inclination: Deg(0.0),
+            frame_interval: capture_frame_interval * load_options.decimation,
+            roll: Deg(0.0),
+            disk_center,
+            disk_diameter,
+            flattening: planet.flattening(),
+            sidereal_rotation_period: planet.sidereal_rotation(),
+            rotation_direction: planet.rotation_direction(),
+            phase: Default::default(),
+            frame_gains: vec![],
+            frame_rolls: vec![],
+            mirror_ew: false,
+            flip_ns: false
+        };
+        let orientation_gizmo = OrientationGizmo::new(gl_objects, display, renderer, &src_params);
 
-        SourceView{
+        let mut source_view = SourceView{
             playback: Playback {
                 enabled: false,
                 first_frame: None,
                 tstart: None,
                 initial_bouncing_back: Some(false),
-                current_bouncing_back: Some(false)
+                current_bouncing_back: Some(false),
+                every_frame: false,
+                frame_count: 0,
+                skipped_frames: 0,
+                frame_times: VecDeque::new()
             },
             fps: 25,
             draw_buffer,
             wh_ratio: image_size[0] as f32 / image_size[1] as f32,
             images: src_images,
-            texture_copy_prog: Rc::clone(&gl_objects.texture_copy_single),
+            out_of_order_frames: frame_timestamps.as_deref().map_or(vec![], frame_time::out_of_order),
+            frame_timestamps,
+            seek_time: String::new(),
+            file_paths,
+            avg_image: None,
+            showing_avg: false,
+            stacking_sigma_clip: false,
+            stack_group_size: DEFAULT_STACK_GROUP_SIZE,
+            unstacked: None,
+            texturing_prog: Rc::clone(&gl_objects.texturing_saturation),
             solid_color_3d_prog: Rc::clone(&gl_objects.solid_color_3d),
             unit_quad: Rc::clone(&gl_objects.unit_quad),
             unit_circle: Rc::clone(&gl_objects.unit_circle),
@@ -118,19 +443,63 @@ impl SourceView {
             ],
             current_img_idx: 0,
             image_size,
-            src_params: SourceParameters{
-                num_images,
-                inclination: Deg(0.0),
-                frame_interval: Duration::from_secs(60),
-                roll: Deg(0.0),
-                disk_center,
-                disk_diameter,
-                flattening: Planet::Jupiter.flattening(),
-                sidereal_rotation_period: Planet::Jupiter.sidereal_rotation()
-            },
-            planet: Some(Planet::Jupiter),
+            src_params: SourceParamsController::new(src_params),
+            planet: Some(planet),
+            tracked_rotation: TrackedRotation::Body,
+            load_options,
+            bit_depth,
+            capture_frame_interval,
+            observation_time: String::new(),
+            observation_jd: None,
+            measured_brightness: None,
+            normalize_exposure: false,
+            roll_offsets: None,
+            per_frame_roll: false,
+            limb_measurement_failed: false,
+            site: Site{ latitude: Deg(0.0), longitude: Deg(0.0) },
             current_image_subscribers: Default::default(),
-            src_params_subscribers: Default::default()
+            texture_registrations: vec![],
+            link: None,
+            render_pending: Cell::new(false),
+            render_throttle: RenderThrottle::new(REGISTRY_OWNER),
+            resize_debounce: Default::default(),
+            focus_disk_controls: false,
+            terminator_shown: true,
+            saturation_shown: false,
+            overlay: Default::default(),
+            overlay_changed: false,
+            src_params_changed: false,
+            auto_overlay_color: RefCell::new(None),
+            loupe_frame: RefCell::new(None),
+            orientation_gizmo,
+            snapshots: Default::default(),
+            diameter_tool: Default::default(),
+            diameter_tool_sets_roll: false
+        };
+        source_view.update_texture_registrations();
+
+        Ok(source_view)
+    }
+
+    fn update_texture_registrations(&mut self) {
+        self.texture_registrations.clear();
+        let format = self.bit_depth().texture_format();
+        for (idx, image) in self.images.iter().enumerate() {
+            self.texture_registrations.push(registry::register_texture(
+                REGISTRY_OWNER, &format!("frame {}", idx + 1), image, format
+            ));
+        }
+        if let Some((avg_image, _)) = &self.avg_image {
+            self.texture_registrations.push(registry::register_texture(
+                REGISTRY_OWNER, "average frame", avg_image, self.bit_depth().texture_format()
+            ));
+        }
+        if let Some(unstacked) = &self.unstacked {
+            for (idx, image) in unstacked.images.iter().enumerate() {
+                self.texture_registrations.push(registry::register_texture(
+                    REGISTRY_OWNER, &format!("original frame {}", idx + 1), image, self.bit_depth.texture_format()
+                ));
+            }
         }
     }
 
@@ -138,23 +507,96 @@ impl SourceView {
         self.images.iter().map(|img| img.get_id()).collect()
     }
 
+    /// Fails (leaving the view unchanged) if the images do not all have the same dimensions.
     pub fn set_images(
         &mut self,
-        src_images: Vec<Rc<Texture2d>>, // all images must have the same dimensions
+        src_images: Vec<Rc<Texture2d>>,
+        file_paths: Vec<PathBuf>,
         disk_center: Point2<f32>,
-        disk_diameter: f32
-    ) {
-        self.image_size = check_sizes_match(&src_images);
-        self.images = src_images;
+        disk_diameter: f32,
+        load_options: LoadOptions,
+        bit_depth: image_utils::BitDepth
+    ) -> Result<(), String> {
+        let image_size = common_size(&texture_sizes(&src_images), &file_paths)?;
+
+        self.unstacked = None;
+        self.src_params.edit().disk_center = disk_center;
+        self.src_params.edit().disk_diameter = disk_diameter;
+        self.load_options = load_options;
+        self.bit_depth = bit_depth;
+        self.replace_images(src_images, file_paths, image_size);
+
+        Ok(())
+    }
+
+    /// Replaces the frames with stacks of their consecutive groups of `group_size` frames (the originals are kept
+    /// for `revert_stacking`). The frame interval becomes `group_size` times longer.
+    pub fn set_stacked_images(&mut self, stacked_images: Vec<Rc<Texture2d>>, group_size: usize) {
+        if self.unstacked.is_some() { return; }
+
+        // each stacked frame is named after the first frame of its group
+        let file_paths = crate::stacking::group_ranges(self.images.len(), group_size).iter()
+            .filter_map(|group| self.file_paths.get(group.start).cloned())
+            .collect();
+        self.unstacked = Some(Unstacked{
+            images: std::mem::take(&mut self.images),
+            file_paths: std::mem::take(&mut self.file_paths),
+            group_size
+        });
+        // stacks have the size of their frames
+        self.replace_images(stacked_images, file_paths, self.image_size);
+    }
+
+    /// Restores the frames replaced by `set_stacked_images`.
+    pub fn revert_stacking(&mut self) {
+        if let Some(unstacked) = self.unstacked.take() {
+            self.replace_images(unstacked.images, unstacked.file_paths, self.image_size);
+        }
+    }
 
-        self.src_params.num_images = self.images.len();
-        self.src_params.disk_center = disk_center;
-        self.src_params.disk_diameter = disk_diameter;
+    /// Returns the size of groups the current frames are stacks of (if any).
+    pub fn stacked_group_size(&self) -> Option<usize> { self.unstacked.as_ref().map(|unstacked| unstacked.group_size) }
+
+    fn stack_group_size(&self) -> usize { self.stack_group_size }
+
+    /// Returns the bit depth of the current frames.
+    pub fn bit_depth(&self) -> image_utils::BitDepth {
+        if self.unstacked.is_some() { image_utils::BitDepth::Eight } else { self.bit_depth }
+    }
+
+    fn set_stack_group_size(&mut self, value: usize) { self.stack_group_size = value; }
+
+    /// Returns the ratio of `src_params.frame_interval` to `capture_frame_interval`.
+    fn frame_interval_factor(&self) -> u32 {
+        self.load_options.decimation * self.stacked_group_size().unwrap_or(1) as u32
+    }
+
+    /// Sets new frames (of `image_size`) and resets all per-frame data.
+    fn replace_images(&mut self, src_images: Vec<Rc<Texture2d>>, file_paths: Vec<PathBuf>, image_size: [u32; 2]) {
+        debug_assert!(texture_sizes(&src_images).iter().all(|size| *size == image_size));
+        self.image_size = image_size;
+        self.images = src_images;
+        self.frame_timestamps = frame_time::timestamps_from_names(&file_paths);
+        self.out_of_order_frames = self.frame_timestamps.as_deref().map_or(vec![], frame_time::out_of_order);
+        self.file_paths = file_paths;
+        self.avg_image = None;
+        self.showing_avg = false;
+        self.auto_overlay_color.replace(None);
+        self.loupe_frame.replace(None);
+        self.update_texture_registrations();
+
+        self.src_params.edit().num_images = self.images.len();
+        self.src_params.edit().frame_interval = self.capture_frame_interval * self.frame_interval_factor();
+        self.measured_brightness = None;
+        self.src_params.edit().frame_gains.clear();
+        self.roll_offsets = None;
+        self.limb_measurement_failed = false;
+        self.src_params.edit().frame_rolls.clear();
 
         self.current_img_idx = 0;
         let current_image = Rc::clone(&self.current_image());
         self.current_image_subscribers.notify(&(self.current_img_idx, current_image));
-        self.src_params_subscribers.notify(&self.src_params);
+        self.src_params.commit();
 
         self.render();
         self.on_reset_playback();
@@ -162,23 +604,126 @@ impl SourceView {
 
     pub fn num_images(&self) -> usize { self.images.len() }
 
-    pub/*temp*/ fn current_image(&self) -> &Rc<Texture2d> { &self.images[self.current_img_idx] }
+    pub fn file_paths(&self) -> &[PathBuf] { &self.file_paths }
+
+    /// Returns times of the frames: from the file names if available, otherwise following from the frame interval.
+    pub fn frame_times(&self) -> FrameTimes {
+        match &self.frame_timestamps {
+            Some(timestamps) => FrameTimes::Timestamps(timestamps),
+            None => FrameTimes::Uniform{ num_frames: self.images.len(), interval: self.src_params.get().frame_interval }
+        }
+    }
+
+    /// Returns the frames whose timestamps (from the file names) are earlier than those of the preceding frames;
+    /// empty once the user has decided how to treat them.
+    pub fn out_of_order_frames(&self) -> &[usize] { &self.out_of_order_frames }
+
+    /// Makes timestamps of the out-of-order frames follow from the frame interval (if `use_interval`), or keeps them.
+    pub fn resolve_out_of_order_frames(&mut self, use_interval: bool) {
+        let frames = std::mem::take(&mut self.out_of_order_frames);
+        if let (true, Some(timestamps)) = (use_interval, &mut self.frame_timestamps) {
+            frame_time::fill_with_interval(timestamps, &frames, self.src_params.get().frame_interval);
+            logging::log_info!("Times of {} out-of-order frame(s) follow from the frame interval.", frames.len());
+        }
+    }
+
+    /// Returns times of the frames relative to the first one (not the earliest one), if taken from the file names.
+    pub fn frame_time_offsets(&self) -> Option<Vec<f64>> {
+        self.frame_timestamps.as_ref().map(|times| times.iter().map(|time| time - times[0]).collect())
+    }
+
+    pub fn image(&self, idx: usize) -> &Rc<Texture2d> { &self.images[idx] }
+
+    pub fn images(&self) -> &[Rc<Texture2d>] { &self.images }
+
+    pub/*temp*/ fn current_image(&self) -> &Rc<Texture2d> {
+        match &self.avg_image {
+            Some((avg_image, _)) if self.showing_avg => avg_image,
+            _ => &self.images[self.current_img_idx]
+        }
+    }
 
     pub fn image_size(&self) -> [u32; 2] { self.image_size }
 
+    /// Returns the value of the current frame's pixel at `pos`. Not available during playback (each frame would
+    /// have to be read back from the GPU).
+    fn pixel_value(&self, pos: [u32; 2]) -> Option<[u8; 3]> {
+        if pos[0] >= self.image_size[0] || pos[1] >= self.image_size[1] { return None; }
+
+        let value_of = |image: &ga_image::Image| {
+            let line = image.line::<u8>(pos[1]);
+            let idx = 3 * pos[0] as usize;
+            [line[idx], line[idx + 1], line[idx + 2]]
+        };
+
+        if let Some((_, image)) = &self.avg_image {
+            if self.showing_avg { return Some(value_of(image)); }
+        }
+        if self.playing() { return None; }
+
+        let texture = &self.images[self.current_img_idx];
+        let mut loupe_frame = self.loupe_frame.borrow_mut();
+        if loupe_frame.as_ref().map_or(true, |(id, _)| *id != texture.get_id()) {
+            *loupe_frame = Some((texture.get_id(), image_utils::image_from_texture(texture)));
+        }
+
+        loupe_frame.as_ref().map(|(_, image)| value_of(image))
+    }
+
     pub fn current_image_idx(&self) -> usize { self.current_img_idx }
 
     fn set_image_idx(&mut self, idx: usize) {
         if idx >= self.images.len() { return; }
 
         self.current_img_idx = idx;
+        self.showing_avg = false;
+        self.render();
+        let current_image = Rc::clone(&self.current_image());
+        self.current_image_subscribers.notify(&(self.current_img_idx, current_image));
+    }
+
+    /// Sets the average of all frames (`texture` and `image` must have the same contents) and shows it.
+    pub fn set_avg_image(&mut self, texture: Rc<Texture2d>, image: ga_image::Image) {
+        assert!(texture.width() == self.image_size[0] && texture.height() == self.image_size[1]);
+        self.avg_image = Some((texture, image));
+        self.update_texture_registrations();
+        self.show_avg_image();
+    }
+
+    pub fn has_avg_image(&self) -> bool { self.avg_image.is_some() }
+
+    pub fn showing_avg_image(&self) -> bool { self.showing_avg }
+
+    fn show_avg_image(&mut self) {
+        if self.avg_image.is_none() || self.playing() { return; }
+
+        self.showing_avg = true;
         self.render();
         let current_image = Rc::clone(&self.current_image());
         self.current_image_subscribers.notify(&(self.current_img_idx, current_image));
     }
 
+    pub fn stacking_sigma_clip(&self) -> bool { self.stacking_sigma_clip }
+
+    fn set_stacking_sigma_clip(&mut self, value: bool) { self.stacking_sigma_clip = value; }
+
+    /// Detects the planetary disk in the average image and applies the result.
+    fn detect_disk_in_avg_image(&mut self) -> Result<(), ()> {
+        let (center, diameter) = match &self.avg_image {
+            Some((_, image)) => crate::disk::find_planetary_disk(image)?,
+            None => return Err(())
+        };
+        self.src_params.edit().disk_center = center;
+        self.src_params.edit().disk_diameter = diameter;
+
+        Ok(())
+    }
+
     pub fn update_size(&mut self, width: u32, height: u32) {
-        if height == 0 { return; }
+        if width == 0 || height == 0 { return; }
+
+        let current = [self.draw_buffer.width(), self.draw_buffer.height()];
+        if !self.resize_debounce.resize_now(current, [width, height], Instant::now()) { return; }
 
         if self.draw_buffer.update_size(width, height) {
             self.wh_ratio = width as f32 / height as f32;
@@ -188,59 +733,68 @@ impl SourceView {
 
     pub fn display_buf_id(&self) -> imgui::TextureId { self.draw_buffer.id() }
 
-    fn disk_transform(&self, with_inclination: bool) -> Matrix4<f32> {
-        let dc_f32 = self.src_params.disk_center.cast::<f32>().unwrap();
-        let normalized_disk_center = Point3{
-            x: dc_f32.x / self.image_size[0] as f32,
-            y: -dc_f32.y / self.image_size[1] as f32,
-            z: 0.0
-        };
+    /// Makes the view focus its window and open the disk controls (e.g., for a manual adjustment of a detected disk).
+    pub fn focus_disk_controls(&mut self) { self.focus_disk_controls = true; }
 
-        let xy_scale = self.src_params.disk_diameter / self.images[0].width() as f32;
+    fn disk_transform(&self, with_inclination: bool) -> Matrix4<f32> {
+        disk_outline_transform(
+            self.src_params.get().disk_center,
+            self.src_params.get().disk_diameter,
+            self.image_size,
+            self.wh_ratio
+        ) *
+        globe_orientation_transform(self.src_params.get(), self.shown_frame_roll(), with_inclination)
+    }
 
-        Matrix4::<f32>::from_translation(Vector3{ x: -1.0, y: 1.0, z: 0.0 } + normalized_disk_center.to_vec() * 2.0) *
-        Matrix4::<f32>::from_nonuniform_scale(xy_scale, xy_scale, 1.0) *
-        Matrix4::<f32>::from_nonuniform_scale(1.0, self.wh_ratio, 1.0) *
-        Matrix4::from(Matrix3::from(Basis3::<f32>::from_angle_z(-self.src_params.roll))) *
-        if with_inclination {
-            Matrix4::from(Matrix3::from(Basis3::<f32>::from_angle_x(-self.src_params.inclination)))
-        } else {
-            Matrix4::identity()
-        } *
-        Matrix4::<f32>::from_nonuniform_scale(1.0, 1.0/(1.0 + self.src_params.flattening), 1.0)
+    /// Returns roll of the shown frame (the average frame uses the constant roll).
+    fn shown_frame_roll(&self) -> Deg<f32> {
+        let src_params = self.src_params.get();
+        if self.showing_avg { src_params.roll } else { src_params.frame_roll(self.current_img_idx) }
     }
 
     fn render(&self) {
+        if !self.render_throttle.begin_rendering(Instant::now()) {
+            self.render_pending.set(true);
+            return;
+        }
+        self.render_pending.set(!render_check::render_checked("source view", || self.try_render()));
+    }
+
+    /// Renders again if the previous rendering failed or was skipped.
+    fn render_if_pending(&self) {
+        if self.render_pending.get() { self.render(); }
+    }
+
+    fn try_render(&self) -> Result<(), glium::DrawError> {
         let mut target = self.draw_buffer.frame_buf();
 
         let uniforms = uniform! {
-            source_texture: self.current_image().sampled()
+            source_texture: self.current_image().sampled(),
+            highlight_saturated: self.saturation_shown,
+            saturation_level: crate::disk::SATURATION_LEVEL as f32 / 255.0
         };
 
         target.draw(
             &*self.unit_quad,
             &glium::index::NoIndices(glium::index::PrimitiveType::TriangleFan),
-            &self.texture_copy_prog,
+            &self.texturing_prog,
             &uniforms,
             &Default::default()
-        ).unwrap();
+        )?;
 
-        let uniforms = uniform! {
-            vertex_transform: self.disk_transform(false).to_array(),
-            color: [1.0f32, 0.0f32, 0.0f32, 1.0f32]
-        };
+        let overlay_color = self.overlay_color();
 
-        target.draw(
-            &*self.unit_circle,
-            &glium::index::NoIndices(glium::index::PrimitiveType::LineLoop),
+        draw_disk_outline(
+            &mut target,
+            &self.unit_circle,
             &self.solid_color_3d_prog,
-            &uniforms,
-            &Default::default()
-        ).unwrap();
+            &self.disk_transform(false),
+            overlay_color
+        )?;
 
         let uniforms = uniform! {
             vertex_transform: self.disk_transform(true).to_array(),
-            color: [1.0f32, 0.0f32, 0.0f32, 1.0f32]
+            color: [overlay_color[0], overlay_color[1], overlay_color[2], 1.0f32]
         };
 
         for half_parallel in &self.half_parallels {
@@ -250,54 +804,294 @@ impl SourceView {
                 &self.solid_color_3d_prog,
                 &uniforms,
                 &Default::default()
-            ).unwrap();
+            )?;
         }
 
-        self.draw_buffer.update_storage_buf();
+        if let (true, Some(transform)) = (self.terminator_shown, phase::terminator_transform(self.src_params.get())) {
+            let uniforms = uniform! {
+                vertex_transform: (self.disk_transform(true) * Matrix4::from(transform)).to_array(),
+                color: [1.0f32, 0.9f32, 0.0f32, 1.0f32]
+            };
+            // the half-equator (`half_parallels[1]`) is transformed onto the terminator
+            target.draw(
+                &self.half_parallels[1],
+                &glium::index::NoIndices(glium::index::PrimitiveType::LineStrip),
+                &self.solid_color_3d_prog,
+                &uniforms,
+                &Default::default()
+            )?;
+        }
+
+        self.draw_buffer.update_storage_buf()
     }
 
-    pub fn inclination(&self) -> Deg<f32> { self.src_params.inclination }
+    pub fn inclination(&self) -> Deg<f32> { self.src_params.get().inclination }
 
     pub fn set_inclination(&mut self, value: Deg<f32>) {
-        self.src_params.inclination = value;
-        self.src_params_subscribers.notify(&self.src_params);
-        self.render();
+        self.src_params.edit().inclination = value;
     }
 
-    pub fn roll(&self) -> Deg<f32> { self.src_params.roll }
+    pub fn roll(&self) -> Deg<f32> { self.src_params.get().roll }
 
-    pub fn flattening(&self) -> f32 { self.src_params.flattening }
+    pub fn flattening(&self) -> f32 { self.src_params.get().flattening }
 
     pub fn set_flattening(&mut self, value: f32) {
         if self.planet.is_some() { panic!("cannot set flattening if a known planet is selected"); }
-        self.src_params.flattening = value;
-        self.src_params_subscribers.notify(&self.src_params);
-        self.render();
+        self.src_params.edit().flattening = value;
     }
 
     pub fn set_roll(&mut self, value: Deg<f32>) {
-        self.src_params.roll = value;
-        self.src_params_subscribers.notify(&self.src_params);
+        self.src_params.edit().roll = value;
+    }
+
+    pub fn mirror_ew(&self) -> bool { self.src_params.get().mirror_ew }
+
+    pub fn set_mirror_ew(&mut self, value: bool) {
+        self.src_params.edit().mirror_ew = value;
+    }
+
+    pub fn flip_ns(&self) -> bool { self.src_params.get().flip_ns }
+
+    pub fn set_flip_ns(&mut self, value: bool) {
+        self.src_params.edit().flip_ns = value;
+    }
+
+    pub fn normalize_exposure(&self) -> bool { self.normalize_exposure }
+
+    fn phase(&self) -> Phase { self.src_params.get().phase }
+
+    fn set_phase(&mut self, value: Phase) {
+        self.src_params.edit().phase = value;
+    }
+
+    fn set_terminator_shown(&mut self, value: bool) {
+        self.terminator_shown = value;
+        self.render();
+    }
+
+    fn set_saturation_shown(&mut self, value: bool) {
+        self.saturation_shown = value;
+        self.render();
+    }
+
+    pub fn set_overlay_settings(&mut self, value: OverlaySettings) {
+        self.overlay = value;
         self.render();
     }
 
+    fn change_overlay_settings(&mut self, value: OverlaySettings) {
+        self.set_overlay_settings(value);
+        self.overlay_changed = true;
+    }
+
+    /// Returns `true` if source parameters have changed (and been committed) since the last call.
+    pub fn take_src_params_changed(&mut self) -> bool { std::mem::take(&mut self.src_params_changed) }
+
+    pub fn snapshots_mut(&mut self) -> &mut Snapshots { &mut self.snapshots }
+
+    /// Returns the overlay settings if changed by the user since the last call.
+    pub fn take_changed_overlay_settings(&mut self) -> Option<OverlaySettings> {
+        if std::mem::take(&mut self.overlay_changed) { Some(self.overlay.clone()) } else { None }
+    }
+
+    /// Returns the color of the disk outline and parallels.
+    fn overlay_color(&self) -> [f32; 3] {
+        if !self.overlay.auto_contrast { return self.overlay.color; }
+
+        let params = self.src_params.get();
+        let key = (
+            if self.showing_avg { None } else { Some(self.current_img_idx) },
+            params.disk_center,
+            params.disk_diameter
+        );
+        let mut auto_color = self.auto_overlay_color.borrow_mut();
+        match &*auto_color {
+            // during playback the color is kept, as reading back every frame would slow it down
+            Some(((frame, center, diameter), color))
+                if (*frame == key.0 || self.playing()) && *center == key.1 && *diameter == key.2 => *color,
+
+            _ => {
+                let image = image_utils::image_from_texture(self.current_image());
+                let color = overlay_color::contrasting_color(&image, params.disk_center, params.disk_diameter)
+                    .unwrap_or(self.overlay.color);
+                *auto_color = Some((key, color));
+                color
+            }
+        }
+    }
+
+    fn set_normalize_exposure(&mut self, value: bool) {
+        self.normalize_exposure = value;
+        self.update_frame_gains();
+    }
+
+    pub fn has_measured_brightness(&self) -> bool { self.measured_brightness.is_some() }
+
+    pub fn set_measured_brightness(&mut self, brightness: Vec<f32>) {
+        assert!(brightness.len() == self.images.len());
+        self.measured_brightness = Some(brightness);
+        self.update_frame_gains();
+    }
+
+    fn update_frame_gains(&mut self) {
+        self.src_params.edit().frame_gains = match &self.measured_brightness {
+            Some(brightness) if self.normalize_exposure => crate::normalization::exposure_gains(brightness),
+            _ => vec![]
+        };
+    }
+
+    fn per_frame_roll(&self) -> bool { self.per_frame_roll }
+
+    fn set_per_frame_roll(&mut self, value: bool) {
+        self.per_frame_roll = value;
+        self.update_frame_rolls();
+    }
+
+    /// Sets per-frame roll from limb angles measured in all frames (see `field_rotation::limb_angle`).
+    pub fn set_measured_limb_angles(&mut self, angles: Vec<Option<Deg<f32>>>) {
+        assert!(angles.len() == self.images.len());
+        match field_rotation::limb_roll_offsets(&angles) {
+            Some(offsets) => self.set_roll_offsets(offsets),
+            None => self.limb_measurement_failed = true
+        }
+    }
+
+    /// Sets per-frame roll from the parallactic angle model; requires the planet and observation time.
+    fn apply_parallactic_roll(&mut self) {
+        if let (Some(planet), Some(jd)) = (self.planet, self.observation_jd) {
+            let offsets = field_rotation::parallactic_roll_offsets(
+                planet.into(), self.site, jd, self.src_params.get().frame_interval, self.images.len()
+            );
+            self.set_roll_offsets(offsets);
+        }
+    }
+
+    fn set_roll_offsets(&mut self, offsets: Vec<Deg<f32>>) {
+        self.roll_offsets = Some(offsets);
+        self.limb_measurement_failed = false;
+        self.per_frame_roll = true;
+        self.update_frame_rolls();
+    }
+
+    fn update_frame_rolls(&mut self) {
+        self.src_params.edit().frame_rolls = match &self.roll_offsets {
+            Some(offsets) if self.per_frame_roll => offsets.clone(),
+            _ => vec![]
+        };
+    }
+
+    fn observation_time(&self) -> &str { &self.observation_time }
+
+    fn set_observation_time(&mut self, value: String) {
+        self.observation_jd = ephem::parse_utc(&value);
+        self.observation_time = value;
+    }
+
+    /// Julian date of the first frame, i.e., of the observation time (if entered and valid); for stacked frames,
+    /// of the middle of the first group.
+    pub fn observation_jd(&self) -> Option<f64> {
+        let group_offset = match self.stacked_group_size() {
+            Some(group_size) => {
+                let unstacked_interval = self.capture_frame_interval * self.load_options.decimation;
+                (group_size - 1) as f64 / 2.0 * unstacked_interval.as_secs_f64() / 86400.0
+            },
+            None => 0.0
+        };
+        self.observation_jd.map(|jd| jd + group_offset)
+    }
+
+    /// Returns the orientation of the rotation axis predicted for the selected planet and observation time.
+    fn predicted_orientation(&self) -> Option<ephem::AxisOrientation> {
+        match (self.planet, self.observation_jd) {
+            (Some(planet), Some(jd)) => Some(ephem::axis_orientation(planet.into(), jd)),
+            _ => None
+        }
+    }
+
     pub fn subscribe_current_img(&mut self, subscriber: Weak<RefCell<dyn Subscriber<(usize, Rc<Texture2d>)>>>) {
         self.current_image_subscribers.add(subscriber);
     }
 
+    /// Notifies subscribers about source parameter changes made since the previous call (if any).
+    pub fn commit_src_params(&mut self) {
+        if self.src_params.commit() {
+            self.src_params_changed = true;
+            self.orientation_gizmo.set_src_params(self.src_params.get());
+            self.render();
+        }
+    }
+
     pub fn subscribe_src_params(&mut self, subscriber: Weak<RefCell<dyn Subscriber<SourceParameters>>>) {
-        self.src_params_subscribers.add(subscriber);
+        self.src_params.subscribe(subscriber);
+    }
+
+    /// Removes all subscriptions of a (closed) view.
+    pub fn unsubscribe<V>(&mut self, view: &Weak<RefCell<V>>)
+    where V: Subscriber<(usize, Rc<Texture2d>)> + Subscriber<SourceParameters> + 'static
+    {
+        self.current_image_subscribers.remove(&(view.clone() as _));
+        self.src_params.unsubscribe(&(view.clone() as _));
+    }
+
+    /// Returns the numbers of current image and source parameter subscribers (for diagnostics).
+    pub fn num_subscribers(&self) -> (usize, usize) {
+        (self.current_image_subscribers.len(), self.src_params.subscribers.len())
+    }
+
+    /// Returns index of the link group the view belongs to.
+    fn link_group(&self) -> Option<usize> {
+        self.link.as_ref().map(|link| link.subscriber.borrow().group().index())
+    }
+
+    /// Leaves the current link group (if any) and joins `group`.
+    fn set_link_group(&mut self, group: Option<&Rc<LinkGroup>>) {
+        if let Some(link) = self.link.take() {
+            self.src_params.unsubscribe(&(Rc::downgrade(&link.subscriber) as _));
+        }
+        if let Some(group) = group {
+            let inbox = Rc::new(RefCell::new(LinkInbox::default()));
+            let subscriber = group.join(Rc::downgrade(&inbox) as _);
+            self.src_params.subscribe(Rc::downgrade(&subscriber) as _);
+            self.link = Some(Link{ inbox, subscriber });
+        }
+    }
+
+    /// Applies parameter changes received from the link group; they are committed with the view's other changes.
+    fn apply_linked_changes(&mut self) {
+        let pending = self.link.as_ref().and_then(|link| link.inbox.borrow_mut().pending.take());
+        if let Some((values, linked)) = pending {
+            let mut params = self.src_params.get().clone();
+            // the group also passes back the values it received from this view; those do not cause a change
+            if linking::copy_linked(&linked, &values, &mut params) {
+                self.capture_frame_interval = params.frame_interval / self.frame_interval_factor();
+                self.src_params.replace(params);
+            }
+        }
     }
 
     fn playing(&self) -> bool { self.playback.enabled }
 
+    /// Advances playback (called once per GUI frame, so the subscribers are notified at most once per GUI frame).
     fn play(&mut self) {
         if self.playback.enabled {
             let t_from_start = self.playback.tstart.as_ref().unwrap().elapsed();
+            let playback = &mut self.playback;
+            let frame_count = playback_frame_count(
+                (t_from_start.as_secs_f32() * self.fps as f32) as usize,
+                playback.frame_count,
+                playback.every_frame
+            );
+            if frame_count != playback.frame_count {
+                playback.skipped_frames += frame_count - playback.frame_count - 1;
+                playback.frame_count = frame_count;
+                if playback.frame_times.len() == FPS_MEASUREMENT_FRAMES { playback.frame_times.pop_front(); }
+                playback.frame_times.push_back(Instant::now());
+            }
+
             let prev_frame = self.current_img_idx;
             self.current_img_idx = advance_current_frame(
                 *self.playback.first_frame.as_ref().unwrap(),
-                (t_from_start.as_secs_f32() * self.fps as f32) as usize,
+                frame_count,
                 self.images.len(),
                 &self.playback.initial_bouncing_back,
                 &mut self.playback.current_bouncing_back
@@ -316,9 +1110,23 @@ impl SourceView {
         self.on_reset_playback();
     }
 
+    fn every_frame(&self) -> bool { self.playback.every_frame }
+
+    fn set_every_frame(&mut self, every_frame: bool) {
+        self.playback.every_frame = every_frame;
+        self.on_reset_playback();
+    }
+
+    /// Returns the recently achieved playback FPS (`None` if not yet known).
+    fn achieved_fps(&self) -> Option<f32> { frame_rate(&self.playback.frame_times) }
+
+    /// Returns the number of frames skipped since the start of playback (or the last FPS change).
+    fn skipped_frames(&self) -> usize { self.playback.skipped_frames }
+
     fn toggle_playing(&mut self) {
         self.playback.enabled = !self.playback.enabled;
         if self.playback.enabled {
+            if self.showing_avg { self.set_image_idx(self.current_img_idx); }
             self.on_reset_playback();
         } else {
             self.playback.first_frame = None;
@@ -330,6 +1138,9 @@ impl SourceView {
         self.playback.tstart = Some(std::time::Instant::now());
         self.playback.first_frame = Some(self.current_img_idx);
         self.playback.initial_bouncing_back = self.playback.current_bouncing_back;
+        self.playback.frame_count = 0;
+        self.playback.skipped_frames = 0;
+        self.playback.frame_times.clear();
     }
 
     fn toggle_bouncing_back(&mut self) {
@@ -347,82 +1158,228 @@ impl SourceView {
         self.playback.initial_bouncing_back.is_some()
     }
 
-    fn planet(&self) -> Option<Planet> { self.planet }
+    pub fn planet(&self) -> Option<Planet> { self.planet }
+
+    pub fn planet_name(&self) -> &str {
+        match &self.planet {
+            Some(planet) => planet.name(),
+            None => "custom"
+        }
+    }
 
     fn set_planet(&mut self, planet: Option<Planet>) {
         self.planet = planet;
         match &self.planet {
             Some(planet) => {
-                self.src_params.flattening = planet.flattening();
-                self.src_params.sidereal_rotation_period = planet.sidereal_rotation();
-                self.src_params_subscribers.notify(&self.src_params);
-                self.src_params_subscribers.notify(&self.src_params);
-                self.render();
+                if planet.atmospheric_rotation().is_none() { self.tracked_rotation = TrackedRotation::Body; }
+                self.src_params.edit().flattening = planet.flattening();
+                self.src_params.edit().sidereal_rotation_period = planet.rotation_period(self.tracked_rotation);
+                self.src_params.edit().rotation_direction = planet.rotation_direction();
+                if !planet.has_phase() { self.src_params.edit().phase.angle = Deg(0.0); }
             },
 
             None => ()
         }
     }
 
-    fn frame_interval(&self) -> Duration { self.src_params.frame_interval }
+    fn tracked_rotation(&self) -> TrackedRotation { self.tracked_rotation }
 
-    fn set_frame_interval(&mut self, interval: Duration) {
-        self.src_params.frame_interval = interval;
-        self.src_params_subscribers.notify(&self.src_params);
+    fn set_tracked_rotation(&mut self, value: TrackedRotation) {
+        self.tracked_rotation = value;
+        self.set_planet(self.planet);
     }
 
-    pub fn src_params(&self) -> &SourceParameters { &self.src_params }
+    fn rotation_direction(&self) -> RotationDirection { self.src_params.get().rotation_direction }
+
+    fn set_rotation_direction(&mut self, value: RotationDirection) {
+        self.src_params.edit().rotation_direction = value;
+    }
 
-    fn sidereal_rotation_period(&self) -> Duration { self.src_params.sidereal_rotation_period }
+    /// Returns interval between captured frames (not taking into account load decimation nor stacking).
+    fn capture_frame_interval(&self) -> Duration { self.capture_frame_interval }
+
+    fn set_capture_frame_interval(&mut self, interval: Duration) {
+        self.capture_frame_interval = interval;
+        self.src_params.edit().frame_interval = interval * self.frame_interval_factor();
+    }
+
+    pub fn load_options(&self) -> LoadOptions { self.load_options }
+
+    pub fn src_params(&self) -> &SourceParameters { self.src_params.get() }
+
+    /// Sets all source parameters at once (e.g., when restoring saved settings); subscribers are notified and
+    /// the view is re-rendered only once. `params.num_images` is ignored (it follows from the loaded images).
+    pub fn apply_params(&mut self, params: SourceParameters) {
+        let known = Planet::iter()
+            .flat_map(|planet| [TrackedRotation::Body, TrackedRotation::Atmosphere].map(|rotation| (planet, rotation)))
+            .find(|(planet, rotation)|
+                planet.flattening() == params.flattening
+                && planet.rotation_period(*rotation) == params.sidereal_rotation_period
+                && planet.rotation_direction() == params.rotation_direction
+            );
+        self.planet = known.map(|(planet, _)| planet);
+        if let Some((_, rotation)) = known { self.tracked_rotation = rotation; }
+        self.capture_frame_interval = params.frame_interval / self.frame_interval_factor();
+        self.src_params.replace(SourceParameters{ num_images: self.images.len(), ..params });
+        self.commit_src_params();
+    }
+
+    fn sidereal_rotation_period(&self) -> Duration { self.src_params.get().sidereal_rotation_period }
 
     fn set_sidereal_rotation_period(&mut self, value: Duration) {
-        self.src_params.sidereal_rotation_period = value;
-        self.src_params_subscribers.notify(&self.src_params);
+        self.src_params.edit().sidereal_rotation_period = value;
     }
 
-    fn disk_diameter(&self) -> f32 { self.src_params.disk_diameter }
+    pub fn disk_diameter(&self) -> f32 { self.src_params.get().disk_diameter }
 
     fn set_disk_diameter(&mut self, value: f32) {
-        self.src_params.disk_diameter = value;
-        self.src_params_subscribers.notify(&self.src_params);
-        self.render();
+        self.src_params.edit().disk_diameter = value;
     }
 
-    fn disk_center(&self) -> Point2<f32> { self.src_params.disk_center }
+    pub fn disk_center(&self) -> Point2<f32> { self.src_params.get().disk_center }
 
     fn set_disk_center(&mut self, value: Point2<f32>) {
-        self.src_params.disk_center = value;
-        self.src_params_subscribers.notify(&self.src_params);
-        self.render();
+        self.src_params.edit().disk_center = value;
+    }
+
+    /// Sets disk diameter and center (and roll, if enabled and within the controls' range) from a measurement.
+    fn apply_diameter_measurement(&mut self, measurement: &diameter_tool::Measurement) {
+        if measurement.diameter <= MIN_DISK_DIAMETER { return; }
+
+        self.set_disk_diameter(measurement.diameter);
+        self.set_disk_center(measurement.center);
+        if self.diameter_tool_sets_roll {
+            let roll = measurement.roll(self.src_params.get().image_mirror());
+            if roll.0.abs() <= MAX_ROLL { self.set_roll(roll); }
+        }
     }
 }
 
-fn check_sizes_match(src_images: &[Rc<Texture2d>]) -> [u32; 2 ] {
-    let mut image_size: Option<[u32; 2]> = None;
+/// Returns transform of a unit globe (Y towards the north pole, Z towards the observer) to its orientation in source
+/// images (without the disk position and size); the same for the disk overlay and the orientation gizmo. `roll` is
+/// `src_params.roll` or the roll of a particular frame (see `SourceParameters::frame_roll`).
+pub fn globe_orientation_transform(
+    src_params: &SourceParameters,
+    roll: Deg<f32>,
+    with_inclination: bool
+) -> Matrix4<f32> {
+    let mirror = src_params.image_mirror();
+
+    Matrix4::<f32>::from_nonuniform_scale(mirror[0], mirror[1], 1.0) *
+    Matrix4::from(Matrix3::from(Basis3::<f32>::from_angle_z(-roll))) *
+    if with_inclination {
+        Matrix4::from(Matrix3::from(Basis3::<f32>::from_angle_x(-src_params.inclination)))
+    } else {
+        Matrix4::identity()
+    } *
+    Matrix4::<f32>::from_nonuniform_scale(1.0, 1.0/(1.0 + src_params.flattening), 1.0)
+}
+
+/// Returns transform of the unit circle to the outline of a disk in an image of `image_size` (shown in a draw buffer
+/// of `wh_ratio`).
+pub fn disk_outline_transform(
+    disk_center: Point2<f32>,
+    disk_diameter: f32,
+    image_size: [u32; 2],
+    wh_ratio: f32
+) -> Matrix4<f32> {
+    let normalized_disk_center = Point3{
+        x: disk_center.x / image_size[0] as f32,
+        y: -disk_center.y / image_size[1] as f32,
+        z: 0.0
+    };
+
+    let xy_scale = disk_diameter / image_size[0] as f32;
+
+    Matrix4::<f32>::from_translation(Vector3{ x: -1.0, y: 1.0, z: 0.0 } + normalized_disk_center.to_vec() * 2.0) *
+    Matrix4::<f32>::from_nonuniform_scale(xy_scale, xy_scale, 1.0) *
+    Matrix4::<f32>::from_nonuniform_scale(1.0, wh_ratio, 1.0)
+}
+
+/// Draws `unit_circle` transformed by `transform` (see `disk_outline_transform`).
+pub fn draw_disk_outline<S: Surface>(
+    target: &mut S,
+    unit_circle: &glium::VertexBuffer<data::Vertex3>,
+    solid_color_3d_prog: &glium::Program,
+    transform: &Matrix4<f32>,
+    color: [f32; 3]
+) -> Result<(), glium::DrawError> {
+    let uniforms = uniform! {
+        vertex_transform: transform.to_array(),
+        color: [color[0], color[1], color[2], 1.0f32]
+    };
+
+    target.draw(
+        unit_circle,
+        &glium::index::NoIndices(glium::index::PrimitiveType::LineLoop),
+        solid_color_3d_prog,
+        &uniforms,
+        &Default::default()
+    )
+}
+
+fn texture_sizes(textures: &[Rc<Texture2d>]) -> Vec<[u32; 2]> {
+    textures.iter().map(|texture| [texture.width(), texture.height()]).collect()
+}
 
-    for image in src_images {
-        match image_size {
-            None => image_size = Some([image.width(), image.height()]),
-            Some(image_size) => assert!(image_size[0] == image.width() && image_size[1] == image.height())
-        }
+/// Returns the size shared by all images (of `sizes`, loaded from the corresponding `file_paths`), or an error naming
+/// the first image which is empty or differs in size from the first one.
+fn common_size(sizes: &[[u32; 2]], file_paths: &[PathBuf]) -> Result<[u32; 2], String> {
+    let name = |idx: usize| file_paths.get(idx)
+        .map_or_else(|| format!("image {}", idx + 1), |path| path.to_string_lossy().to_string());
+
+    let first = *sizes.first().ok_or_else(|| "no images to show".to_string())?;
+    if first[0] == 0 || first[1] == 0 { return Err(format!("{} has zero width or height", name(0))); }
+
+    match sizes.iter().position(|size| *size != first) {
+        Some(idx) => Err(format!(
+            "{} has different dimensions ({}x{}) than the preceding images ({}x{})",
+            name(idx), sizes[idx][0], sizes[idx][1], first[0], first[1]
+        )),
+        None => Ok(first)
     }
-
-    image_size.unwrap()
 }
 
+/// `task_in_progress`: another task cannot be started (it may still allow playback, see `allow_playback`).
+/// `params_locked`: the source parameters are used by a running export and cannot be edited.
 pub fn handle_source_view(
     ui: &imgui::Ui,
     gui_state: &mut GuiState,
     view: &mut SourceView,
-    allow_playback: bool
-) {
-    imgui::Window::new(ui, &format!("Source images"))
+    link_groups: &[Rc<LinkGroup>],
+    allow_playback: bool,
+    task_in_progress: bool,
+    params_locked: bool,
+    unsaved: bool
+) -> SourceViewRequest {
+    let mut request = SourceViewRequest::None;
+
+    view.apply_linked_changes();
+    view.render_throttle.set_app_focused(gui_state.app_focused());
+    view.render_if_pending();
+
+    let focus_disk_controls = std::mem::replace(&mut view.focus_disk_controls, false);
+
+    let mut visible = false;
+    imgui::Window::new(ui, &format!("{}###source-view", projection::marked_title("Source images", unsaved)))
         .size([640.0, 640.0], imgui::Condition::FirstUseEver)
+        .focused(focus_disk_controls)
         .build(|| {
+            visible = true;
+            if view.render_throttle.set_visible(true) { view.render_if_pending(); }
+
+            handle_shortcuts(ui, gui_state, view);
+
+            let lock_group = ui.begin_group();
+            let lock_token = ui.begin_disabled(params_locked);
+
+            if let Some(snapshot_request) = handle_snapshot_controls(ui, view) { request = snapshot_request; }
+
             {
                 let planet_names = [
                     Planet::Jupiter.name(),
                     Planet::Mars.name(),
+                    Planet::Venus.name(),
                     "custom"
                 ];
                 let index_custom = planet_names.len() - 1;
@@ -441,6 +1398,23 @@ pub fn handle_source_view(
                 }
             }
 
+            handle_link_group_controls(ui, view, link_groups);
+
+            // Observation time --------------------------------------------
+
+            gui::add_text_before(ui, "observation time");
+            gui::tooltip(ui, "UTC date and time of observation; used to predict the orientation of planet's rotation axis.");
+            let mut value = view.observation_time().to_string();
+            if ui.input_text("##observation-time", &mut value).hint("YYYY-MM-DD HH:MM").build() {
+                view.set_observation_time(value);
+            }
+            if !view.observation_time().is_empty() && view.observation_jd.is_none() {
+                ui.same_line();
+                ui.text_disabled("(invalid)");
+            }
+
+            let predicted = view.predicted_orientation();
+
             // Flattening slider --------------------------------------------
 
             gui::add_text_before(ui, "flattening");
@@ -470,28 +1444,79 @@ pub fn handle_source_view(
                 if value > 0 { view.set_sidereal_rotation_period(Duration::from_secs(value as u64)); }
             }
             token.end();
+            ui.same_line();
+            ui.text_disabled(format!("= {}", fmt::format_duration(view.sidereal_rotation_period(), &gui_state.format)));
+
+            if view.planet().map_or(false, |planet| planet.atmospheric_rotation().is_some()) {
+                gui::add_text_before(ui, "tracked rotation");
+                gui::tooltip(ui, "Rotation the sequence is compensated for: of the planet's body (surface features) \
+                    or of its cloud deck (e.g., Venus in UV).");
+                for rotation in [TrackedRotation::Body, TrackedRotation::Atmosphere] {
+                    if ui.radio_button_bool(rotation.name(), view.tracked_rotation() == rotation) {
+                        view.set_tracked_rotation(rotation);
+                    }
+                    ui.same_line();
+                }
+                ui.new_line();
+            }
+
+            let token = ui.begin_disabled(view.planet().is_some());
+            let mut retrograde = view.rotation_direction() == RotationDirection::Retrograde;
+            if ui.checkbox("retrograde rotation", &mut retrograde) {
+                view.set_rotation_direction(
+                    if retrograde { RotationDirection::Retrograde } else { RotationDirection::Prograde }
+                );
+            }
+            token.end();
+            gui::tooltip(ui, "The planet (or its atmosphere) rotates clockwise as seen from above the north pole; \
+                features drift across the disk from west to east.");
+            ui.same_line();
+            ui.text_disabled(match view.planet() {
+                Some(_) => format!(
+                    "({} rotation, {})", view.tracked_rotation().name(), view.rotation_direction().name()
+                ),
+                None => format!("({})", view.rotation_direction().name())
+            });
 
             // Inclination slider --------------------------------------------
 
             gui::add_text_before(ui, "inclination");
             gui::tooltip(ui, "Inclination of planet's rotation axis towards observer.");
             let mut value = view.inclination().0;
-            if imgui::Slider::new("##planet-inclination", -5.0, 5.0)
+            let max_inclination = match view.planet() {
+                Some(Planet::Mars) | None => 30.0,
+                Some(Planet::Venus) => 10.0,
+                Some(Planet::Jupiter) => 5.0
+            };
+            if imgui::Slider::new("##planet-inclination", -max_inclination, max_inclination)
                 .flags(imgui::SliderFlags::ALWAYS_CLAMP)
                 .display_format("%0.2f°")
                 .build(ui, &mut value)
             {
                 view.set_inclination(Deg(value));
             }
+            if let Some(predicted) = &predicted {
+                let inclination = predicted.sub_earth_latitude as f32;
+                if handle_predicted_value(ui, &gui_state.format, "inclination", inclination) {
+                    view.set_inclination(Deg(inclination.max(-max_inclination).min(max_inclination)));
+                }
+                gui::tooltip(ui, "Latitude of the sub-Earth point; positive if the north pole is tilted towards the observer.");
+            }
 
             // Disk -----------------------------------
 
-            ui.tree_node_config("disk").build(|| {
+            let mut disk_node = ui.tree_node_config("disk");
+            if focus_disk_controls { disk_node = disk_node.opened(true, imgui::Condition::Always); }
+            disk_node.build(|| {
                 gui::add_text_before(ui, "diameter");
                 gui::tooltip(ui, "Disk diameter (equatorial) in pixels.");
+                if focus_disk_controls {
+                    ui.set_scroll_here_y();
+                    ui.set_keyboard_focus_here();
+                }
                 let mut value = view.disk_diameter();
                 if ui.input_float("##disk-diameter", &mut value).step(0.1).step_fast(1.0).display_format("%0.1f").build() {
-                    if value > 10.0 { view.set_disk_diameter(value); }
+                    if value > MIN_DISK_DIAMETER { view.set_disk_diameter(value); }
                 }
 
                 let mut value = view.disk_center();
@@ -505,30 +1530,144 @@ pub fn handle_source_view(
                 if ui.input_float("##disk-center-y", &mut value.y).step(0.1).step_fast(1.0).display_format("%0.1f").build() {
                     view.set_disk_center(value);
                 }
+
+                let token = ui.begin_disabled(!view.has_avg_image());
+                if ui.button("Detect in average frame") {
+                    if view.detect_disk_in_avg_image().is_err() {
+                        gui_state.message_box = Some(gui::MessageBox{
+                            title: "Error".to_string(),
+                            message: "Could not find planetary disk in the average frame.".to_string()
+                        });
+                        ui.open_popup("Error");
+                    }
+                }
+                token.end();
+                gui::tooltip(ui, "Detect disk center and diameter in the average of all frames.");
+
+                let token = ui.begin_disabled(view.playing());
+                if view.diameter_tool.active() {
+                    if ui.button("Cancel measurement") { view.diameter_tool.cancel(); }
+                } else if ui.button("Measure diameter") {
+                    view.diameter_tool.activate();
+                }
+                token.end();
+                gui::tooltip(ui, "Click the two ends of the equator in the image to set disk diameter and center \
+                    (Esc cancels). Not available during playback.");
+                ui.same_line();
+                ui.checkbox("and roll", &mut view.diameter_tool_sets_roll);
+                gui::tooltip(ui, "Also set roll from the direction of the equator (if within the range of the roll \
+                    controls).");
+                if view.diameter_tool.active() {
+                    ui.same_line();
+                    ui.text_disabled(match view.diameter_tool.first_point() {
+                        None => "(click one end of the equator)",
+                        Some(_) => "(click the other end)"
+                    });
+                }
+
+                let mut shown = view.saturation_shown;
+                if ui.checkbox("highlight saturated", &mut shown) { view.set_saturation_shown(shown); }
+                gui::tooltip(ui, "Mark saturated (overexposed) pixels with stripes; their detail is lost.");
+
+                let mut auto_contrast = view.overlay.auto_contrast;
+                if ui.checkbox("auto overlay color", &mut auto_contrast) {
+                    view.change_overlay_settings(OverlaySettings{ auto_contrast, ..view.overlay.clone() });
+                }
+                gui::tooltip(ui, "Choose the color of the disk outline and parallels to contrast with the frame \
+                    around the limb.");
+                ui.same_line();
+                let c = view.overlay.color;
+                let mut color = [c[0], c[1], c[2], 1.0];
+                if imgui::ColorEdit4::new("##overlay-color", &mut color).alpha(false).inputs(false).build(ui) {
+                    view.change_overlay_settings(
+                        OverlaySettings{ auto_contrast: false, color: [color[0], color[1], color[2]] }
+                    );
+                }
+                gui::tooltip(ui, "Color of the disk outline and parallels; choosing it turns off the automatic color.");
             });
 
             // Frame interval --------------------------------------------
 
             gui::add_text_before(ui, "frame interval");
-            gui::tooltip(ui, "Time interval between frames.");
-            let mut value = view.frame_interval().as_secs() as i32;
-            if ui.input_int("##frame-interval", &mut value)
-                .display_format("%d s")
+            gui::tooltip(ui, "Time interval between captured frames; for long sequences, a precise value keeps \
+                the last frames aligned (see \"frame interval precision\" in projection views).");
+            let mut value = view.capture_frame_interval().as_secs_f32();
+            if ui.input_float("##frame-interval", &mut value)
+                .display_format("%0.3f s")
                 .enter_returns_true(true)
                 .build()
             {
-                if value > 0 && value < 10_000 { view.set_frame_interval(Duration::from_secs(value as u64)); }
+                if value > 0.0 && value < 10_000.0 {
+                    view.set_capture_frame_interval(Duration::from_millis((value * 1000.0).round() as u64));
+                }
             }
+            if view.load_options().decimation > 1 {
+                ui.same_line();
+                ui.text_disabled(format!("×{} (load decimation)", view.load_options().decimation));
+            }
+            if let Some(group_size) = view.stacked_group_size() {
+                ui.same_line();
+                ui.text_disabled(format!("×{} (stacking)", group_size));
+            }
+
+            // Mirroring --------------------------------------------
+
+            let mut value = view.mirror_ew();
+            if ui.checkbox("mirror E-W", &mut value) { view.set_mirror_ew(value); }
+            gui::tooltip(ui, "Source images are mirrored east-west (e.g., captured using a star diagonal).");
+            ui.same_line();
+            let mut value = view.flip_ns();
+            if ui.checkbox("flip N-S", &mut value) { view.set_flip_ns(value); }
+            gui::tooltip(ui, "Source images are flipped north-south.");
 
             // Roll --------------------------------------------
 
             handle_roll_controls(ui, view);
+            if let Some(predicted) = &predicted {
+                let roll = predicted_roll(predicted);
+                let token = ui.begin_disabled(roll.abs() > MAX_ROLL);
+                if handle_predicted_value(ui, &gui_state.format, "roll", roll) { view.set_roll(Deg(roll)); }
+                token.end();
+                gui::tooltip(ui, &format!(
+                    "Position angle of the north pole: {} (measured from celestial north towards east).\n\
+                    Predicted roll assumes the image (after un-mirroring) has north up and east to the left.",
+                    fmt::format_angle(Deg(predicted.position_angle as f32), 2, &gui_state.format)
+                ));
+            }
+
+            ui.tree_node_config("field rotation").build(|| {
+                let rotation_request = handle_field_rotation_controls(ui, &gui_state.format, view, !task_in_progress);
+                if rotation_request != SourceViewRequest::None { request = rotation_request; }
+            });
+
+            // Phase --------------------------------------------
+
+            if view.planet().map_or(true, |planet| planet.has_phase()) {
+                ui.tree_node_config("phase").build(|| {
+                    handle_phase_controls(ui, &gui_state.format, view, predicted.as_ref());
+                });
+            }
+
+            lock_token.end();
+            lock_group.end();
+            projection::export_lock_tooltip(ui, params_locked);
 
             // Playback controls -----------------------------------------------
 
             ui.separator();
 
             let bsize = [ui.calc_text_size("MM")[0], 0.0];
+            let m_width = ui.calc_text_size("M")[0];
+            let spacing = ui.clone_style().item_spacing[0];
+            let layout = playback_layout(ui.content_region_avail()[0], &PlaybackWidths{
+                buttons: ui.calc_text_size("playback")[0] + 2.0 * (spacing + bsize[0]),
+                fps_label: ui.calc_text_size("FPS")[0],
+                every_frame: ui.frame_height() + ui.clone_style().item_inner_spacing[0]
+                    + ui.calc_text_size("every frame")[0],
+                spacing,
+                min_slider: PLAYBACK_SLIDER_WIDTH.0 * m_width,
+                max_slider: PLAYBACK_SLIDER_WIDTH.1 * m_width
+            });
 
             gui::add_text_before(ui, "playback");
 
@@ -552,15 +1691,114 @@ pub fn handle_source_view(
             if let Some(token) = token { token.pop(); }
             gui::tooltip(ui, "Play frames with bouncing back.");
 
-            ui.same_line();
+            if !layout.fps_on_new_line { ui.same_line(); }
             gui::add_text_before(ui, "FPS");
             let mut value = view.fps();
+            let w = ui.push_item_width(layout.fps_slider_width);
             if imgui::Slider::new("###playback-fps", 1, 200)
                 .flags(imgui::SliderFlags::ALWAYS_CLAMP)
                 .build(ui, &mut value)
             {
                 view.set_fps(value);
             }
+            w.end();
+            gui::tooltip(ui, "Frames are shown at most once per display refresh; at higher FPS values some frames \
+                are skipped.");
+
+            if !layout.every_frame_on_new_line { ui.same_line(); }
+            let mut every_frame = view.every_frame();
+            if ui.checkbox("every frame", &mut every_frame) { view.set_every_frame(every_frame); }
+            gui::tooltip(ui, "Show every frame (e.g., to check frames before exporting); the playback rate is limited \
+                by the display's refresh rate.");
+
+            if view.playing() {
+                match view.achieved_fps() {
+                    Some(fps) => ui.text(format!(
+                        "achieved: {} FPS", fmt::format_number(fps as f64, 1, &gui_state.format)
+                    )),
+                    None => ui.text("achieved: -")
+                }
+                if view.skipped_frames() > 0 {
+                    ui.same_line();
+                    ui.text_colored([1.0, 0.8, 0.0, 1.0], format!("skipped frames: {}", view.skipped_frames()));
+                }
+            }
+
+            // Stacked preview --------------------------------------------
+
+            gui::add_text_before(ui, "average frame");
+            let token = ui.begin_disabled(view.playing() || task_in_progress);
+            if ui.button("Stack all frames") { request = SourceViewRequest::Stacking; }
+            gui::tooltip(ui, "Average all frames into a low-noise preview frame (used for parameter tuning only).");
+            ui.same_line();
+            let mut sigma_clip = view.stacking_sigma_clip();
+            if ui.checkbox("sigma-clipping", &mut sigma_clip) { view.set_stacking_sigma_clip(sigma_clip); }
+            gui::tooltip(ui, "Reject outlying values (beyond 2.5 standard deviations) when averaging \
+                (also when stacking in groups).");
+
+            gui::add_text_before(ui, "stacked frames");
+            match view.stacked_group_size() {
+                None => {
+                    let w = ui.push_item_width(ui.calc_text_size("MMMMMM")[0]);
+                    let mut value = view.stack_group_size() as i32;
+                    if ui.input_int("##stack-group-size", &mut value).build() {
+                        view.set_stack_group_size(value.max(2) as usize);
+                    }
+                    w.end();
+                    gui::tooltip(ui, "Number of consecutive frames to stack together.");
+                    ui.same_line();
+                    if ui.button("Stack in groups") {
+                        request = SourceViewRequest::GroupStacking(view.stack_group_size());
+                    }
+                    gui::tooltip(ui, "Replace the frames with stacks of their consecutive groups to reduce noise; \
+                        the frame interval is multiplied by the group size.");
+                },
+
+                Some(group_size) => {
+                    ui.text(format!("groups of {}", group_size));
+                    ui.same_line();
+                    if ui.button("Revert stacking") { view.revert_stacking(); }
+                    gui::tooltip(ui, "Restore the original frames.");
+                }
+            }
+            token.end();
+
+            // Exposure normalization --------------------------------------------
+
+            ui.tree_node_config("exposure normalization").build(|| {
+                // frame gains are source parameters
+                let token = ui.begin_disabled(task_in_progress || params_locked);
+                let mut value = view.normalize_exposure();
+                if ui.checkbox("normalize exposure", &mut value) {
+                    view.set_normalize_exposure(value);
+                    if value && !view.has_measured_brightness() { request = SourceViewRequest::BrightnessMeasurement; }
+                }
+                gui::tooltip(ui, "Equalize frames' brightness (measured inside the disk) to the median of the sequence.");
+                ui.same_line();
+                if ui.button("Measure") { request = SourceViewRequest::BrightnessMeasurement; }
+                gui::tooltip(ui, "Measure frames' brightness again (e.g., after changing the disk parameters).");
+                token.end();
+
+                if let Some(brightness) = &view.measured_brightness {
+                    let gains = crate::normalization::exposure_gains(brightness);
+                    let normalized: Vec<f32> = brightness.iter().zip(gains.iter()).map(|(b, g)| b * g).collect();
+                    let max_value = brightness.iter().chain(normalized.iter()).copied().fold(0.0, f32::max);
+                    let graph_size = [ui.content_region_avail()[0], ui.calc_text_size("M")[1] * 4.0];
+
+                    ui.plot_lines("##measured-brightness", brightness)
+                        .overlay_text("measured brightness")
+                        .scale_min(0.0)
+                        .scale_max(max_value)
+                        .graph_size(graph_size)
+                        .build();
+                    ui.plot_lines("##normalized-brightness", &normalized)
+                        .overlay_text("normalized brightness")
+                        .scale_min(0.0)
+                        .scale_max(max_value)
+                        .graph_size(graph_size)
+                        .build();
+                }
+            });
 
             // Current frame --------------------------------------------
 
@@ -582,17 +1820,50 @@ pub fn handle_source_view(
             gui::tooltip(ui, "Next frame.");
             ui.same_line();
 
-            let mut value = view.current_image_idx() as u32 + 1;
-            if imgui::Slider::new(format!("{}/{}###source-image-idx", value, view.num_images()), 1, view.num_images() as u32)
+            // if there is an average frame, it is placed at the slider's left end (value 0)
+            let min_value = if view.has_avg_image() { 0 } else { 1 };
+            let mut value = if view.showing_avg_image() { 0 } else { view.current_image_idx() as u32 + 1 };
+            let label = if view.showing_avg_image() {
+                "AVG###source-image-idx".to_string()
+            } else {
+                format!("{}/{}###source-image-idx", value, view.num_images())
+            };
+            // leaves room for the label (shown to the right), but does not let the slider collapse
+            let label_width = ui.calc_text_size(format!("{}/{}", view.num_images(), view.num_images()))[0];
+            let slider_width = ui.content_region_avail()[0] - label_width - ui.clone_style().item_inner_spacing[0];
+            let w = ui.push_item_width(slider_width.max(PLAYBACK_SLIDER_WIDTH.0 * m_width));
+            if imgui::Slider::new(label, min_value, view.num_images() as u32)
                 .flags(imgui::SliderFlags::ALWAYS_CLAMP)
                 .build(ui, &mut value)
             {
-                let new_idx = value as usize - 1;
-                view.set_image_idx(new_idx);
+                if value == 0 {
+                    view.show_avg_image();
+                } else {
+                    view.set_image_idx(value as usize - 1);
+                }
+            }
+            if ui.is_item_hovered() {
+                // slider value under the mouse cursor (approximate, as the grab's width is not taken into account)
+                let (min, max) = (ui.item_rect_min(), ui.item_rect_max());
+                let fraction = ((ui.io().mouse_pos[0] - min[0]) / (max[0] - min[0]).max(1.0)).max(0.0).min(1.0);
+                let hovered = min_value + (fraction * (view.num_images() as u32 - min_value) as f32).round() as u32;
+                if hovered > 0 {
+                    let elapsed = view.frame_times().elapsed(hovered as usize - 1);
+                    ui.tooltip_text(format!("frame {}: {}", hovered, fmt::format_hms(elapsed)));
+                }
             }
+            w.end();
+
+            handle_frame_time_controls(ui, view);
 
             token.end();
 
+            if !view.showing_avg_image() {
+                if let Some(path) = view.file_paths().get(view.current_image_idx()) {
+                    handle_frame_file_controls(ui, path);
+                }
+            }
+
             // Source image --------------------------------------------
 
             let hidpi_f = gui_state.hidpi_factor() as f32;
@@ -610,13 +1881,345 @@ pub fn handle_source_view(
                 adjusted.physical_size[1]
             );
 
+            let img_pos = ui.cursor_screen_pos();
             imgui::Image::new(view.display_buf_id(), adjusted.logical_size).build(ui);
+            // the frame must not change between the clicks
+            if view.playing() { view.diameter_tool.cancel(); }
+            if view.diameter_tool.active() { handle_diameter_tool(ui, view, img_pos, adjusted.logical_size); }
+            if ui.is_item_hovered() && gui::loupe::active(ui, gui_state) {
+                handle_loupe(ui, gui_state, view, img_pos, adjusted.logical_size);
+            }
+            orientation_gizmo::handle_orientation_gizmo(ui, hidpi_f, &mut view.orientation_gizmo);
         }
     );
+    // not rendered while collapsed or in an inactive docked tab; playback continues
+    if !visible { view.render_throttle.set_visible(false); }
 
     if allow_playback {
         view.play(); //TODO: make it future-proof if e.g. Dear ImGUI moves to doing only limited number of refreshes on no user input
     }
+
+    // all parameter changes made above result in a single notification
+    view.commit_src_params();
+
+    request
+}
+
+/// Shows the current frame's time and the sequence's span, and seeks to the frame nearest to the entered time.
+fn handle_frame_time_controls(ui: &imgui::Ui, view: &mut SourceView) {
+    gui::add_text_before(ui, "time");
+    let times = view.frame_times();
+    let from_names = matches!(times, FrameTimes::Timestamps(_));
+    let elapsed = if view.showing_avg_image() {
+        "-".to_string()
+    } else {
+        fmt::format_hms(times.elapsed(view.current_image_idx()))
+    };
+    ui.text(format!("{} / {}", elapsed, fmt::format_hms(times.span())));
+    gui::tooltip(ui, if from_names {
+        "Time of the current frame since the first one / time of the last frame (from the file names)."
+    } else {
+        "Time of the current frame since the first one / time of the last frame (following from the frame interval)."
+    });
+
+    ui.same_line();
+    let w = ui.push_item_width(ui.calc_text_size("MMMMMMM")[0]);
+    if ui.input_text("##seek-time", &mut view.seek_time).hint("H:MM:SS").enter_returns_true(true).build() {
+        if let Some(time) = fmt::parse_hms(&view.seek_time) {
+            let idx = view.frame_times().nearest_frame(time);
+            view.set_image_idx(idx);
+        }
+    }
+    w.end();
+    gui::tooltip(ui, "Go to the frame nearest to the entered time since the first frame (H:MM:SS, M:SS or seconds).");
+}
+
+/// Places points of the diameter measurement over the source image displayed at `img_pos` with `size` (logical
+/// pixels); to be called right after the image widget.
+fn handle_diameter_tool(ui: &imgui::Ui, view: &mut SourceView, img_pos: [f32; 2], size: [f32; 2]) {
+    const MARKER_COLOR: [f32; 4] = [1.0, 0.3, 0.3, 1.0];
+
+    if ui.is_key_pressed(imgui::Key::Escape)
+        && ui.is_window_focused_with_flags(imgui::WindowFocusedFlags::ROOT_AND_CHILD_WINDOWS)
+    {
+        view.diameter_tool.cancel();
+        return;
+    }
+
+    let mouse_pos = ui.io().mouse_pos;
+    let hovered = ui.is_item_hovered();
+    if hovered && ui.is_item_clicked_with_button(imgui::MouseButton::Left) {
+        let pos = diameter_tool::image_position(mouse_pos, img_pos, size, view.image_size);
+        if let Some(measurement) = view.diameter_tool.click(pos) { view.apply_diameter_measurement(&measurement); }
+    }
+
+    if let Some(first) = view.diameter_tool.first_point() {
+        let draw_list = ui.get_window_draw_list();
+        let start = diameter_tool::screen_position(first, img_pos, size, view.image_size);
+        draw_list.add_circle(start, 3.0, MARKER_COLOR).filled(true).build();
+        if hovered { draw_list.add_line(start, mouse_pos, MARKER_COLOR).build(); }
+    }
+}
+
+/// Shows the loupe over the source image displayed at `img_pos` with `size` (logical pixels).
+fn handle_loupe(ui: &imgui::Ui, gui_state: &mut GuiState, view: &SourceView, img_pos: [f32; 2], size: [f32; 2]) {
+    let mouse_pos = ui.io().mouse_pos;
+    let cursor = [(mouse_pos[0] - img_pos[0]) / size[0], (mouse_pos[1] - img_pos[1]) / size[1]];
+    let pixel = [
+        ((cursor[0] * view.image_size[0] as f32) as u32).min(view.image_size[0] - 1),
+        ((cursor[1] * view.image_size[1] as f32) as u32).min(view.image_size[1] - 1)
+    ];
+    let mut info = format!("x: {}, y: {}", pixel[0], pixel[1]);
+    if let Some(value) = view.pixel_value(pixel) {
+        info += &format!("\nR: {}, G: {}, B: {}", value[0], value[1], value[2]);
+    }
+
+    gui::loupe::show(
+        ui,
+        gui_state,
+        view.display_buf_id(),
+        [view.draw_buffer.width(), view.draw_buffer.height()],
+        cursor,
+        ([0.0, 0.0], [1.0, 1.0]),
+        &info
+    );
+}
+
+/// Reports the window's focus (for dispatching of shortcuts in the next frame) and handles the triggered shortcut.
+fn handle_shortcuts(ui: &imgui::Ui, gui_state: &mut GuiState, view: &mut SourceView) {
+    if ui.is_window_focused_with_flags(imgui::WindowFocusedFlags::ROOT_AND_CHILD_WINDOWS) {
+        gui_state.focused_window = Some(WindowKind::SourceView);
+    }
+
+    if gui_state.shortcut_triggered(Action::TogglePlayback) {
+        view.toggle_playing();
+    }
+
+    // frame selection is disabled during playback
+    if view.playing() { return; }
+
+    let current_idx = view.current_image_idx();
+    if gui_state.shortcut_triggered(Action::NextFrame) {
+        view.set_image_idx(current_idx + 1);
+    } else if gui_state.shortcut_triggered(Action::PreviousFrame) && current_idx > 0 {
+        view.set_image_idx(current_idx - 1);
+    } else if gui_state.shortcut_triggered(Action::FirstFrame) {
+        view.set_image_idx(0);
+    } else if gui_state.shortcut_triggered(Action::LastFrame) {
+        view.set_image_idx(view.num_images().saturating_sub(1));
+    }
+}
+
+/// Handles the A/B snapshot toolbar; returns the requested snapshot operation (if any).
+fn handle_snapshot_controls(ui: &imgui::Ui, view: &SourceView) -> Option<SourceViewRequest> {
+    const DIFFERENCE_COLOR: [f32; 4] = [1.0, 0.6, 0.2, 1.0];
+
+    let mut request = None;
+    for slot in [Slot::A, Slot::B] {
+        if ui.button(format!("Store {}", slot.name())) { request = Some(SourceViewRequest::StoreSnapshot(slot)); }
+        gui::tooltip(ui, "Store the source parameters and the projection settings of the active projection view.");
+        ui.same_line();
+    }
+    let token = ui.begin_disabled(!view.snapshots.can_toggle());
+    if ui.button("A/B") { request = Some(SourceViewRequest::ToggleSnapshot); }
+    token.end();
+    gui::tooltip(ui, "Switch between the stored parameter sets.");
+    ui.same_line();
+    match view.snapshots.active() {
+        Some(slot) => ui.text(format!("active: {}", slot.name())),
+        None => ui.text_disabled("(nothing stored)")
+    }
+
+    let differences = view.snapshots.differences();
+    if !differences.is_empty() {
+        ui.text_disabled("differs:");
+        for name in differences {
+            ui.same_line();
+            ui.text_colored(DIFFERENCE_COLOR, name);
+        }
+    }
+    ui.separator();
+
+    request
+}
+
+fn handle_link_group_controls(ui: &imgui::Ui, view: &mut SourceView, link_groups: &[Rc<LinkGroup>]) {
+    let mut group_names = vec!["none".to_string()];
+    group_names.extend((1..=link_groups.len()).map(|i| i.to_string()));
+
+    let prev_index = view.link_group().map(|i| i + 1).unwrap_or(0);
+    let mut index = prev_index;
+    gui::add_text_before(ui, "link group");
+    gui::tooltip(ui, "Source views in the same group share the linked parameters (e.g., sequences captured through \
+        different filters); disk center and diameter are always independent.");
+    ui.combo_simple_string("##link-group", &mut index, &group_names);
+    if index != prev_index {
+        view.set_link_group(if index == 0 { None } else { Some(&link_groups[index - 1]) });
+    }
+
+    if let Some(group) = view.link_group().map(|i| &link_groups[i]) {
+        ui.same_line();
+        ui.text_disabled(format!("{} member(s); linked:", group.num_members()));
+        for parameter in LinkedParameter::iter() {
+            ui.same_line();
+            let mut linked = group.is_linked(parameter);
+            if ui.checkbox(format!("{}##linked-{:?}", parameter.name(), parameter), &mut linked) {
+                group.set_linked(parameter, linked);
+            }
+        }
+    }
+}
+
+/// Shows a value predicted from ephemeris; returns `true` if it is to be applied.
+fn handle_predicted_value(ui: &imgui::Ui, format: &fmt::Preferences, id: &str, value: f32) -> bool {
+    ui.text_disabled(format!("predicted: {}", fmt::format_angle(Deg(value), 2, format)));
+    ui.same_line();
+    ui.small_button(format!("apply predicted##apply-predicted-{}", id))
+}
+
+/// Returns roll corresponding to the predicted axis orientation in an image with north up and east to the left
+/// (i.e., non-mirrored, or un-mirrored via `SourceParameters::mirror_ew`); the north pole's position angle grows
+/// counter-clockwise, roll grows clockwise.
+fn predicted_roll(orientation: &ephem::AxisOrientation) -> f32 {
+    -orientation.position_angle as f32
+}
+
+/// Returns the direction towards the Sun (see `Phase::sun_direction`) corresponding to the predicted orientation.
+fn predicted_sun_direction(orientation: &ephem::AxisOrientation) -> f32 {
+    let direction = (orientation.bright_limb_position_angle - orientation.position_angle).rem_euclid(360.0);
+    (if direction > 180.0 { direction - 360.0 } else { direction }) as f32
+}
+
+fn handle_phase_controls(
+    ui: &imgui::Ui,
+    format: &fmt::Preferences,
+    view: &mut SourceView,
+    predicted: Option<&ephem::AxisOrientation>
+) {
+    let mut phase = view.phase();
+    let mut changed = false;
+
+    gui::add_text_before(ui, "phase angle");
+    gui::tooltip(ui, "Angle Sun-planet-observer; 0° disables the phase handling.");
+    changed |= imgui::Slider::new("##phase-angle", 0.0, 180.0)
+        .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+        .display_format("%0.1f°")
+        .build(ui, &mut phase.angle.0);
+    if let Some(predicted) = predicted {
+        let angle = predicted.phase_angle as f32;
+        if handle_predicted_value(ui, format, "phase-angle", angle) {
+            phase.angle = Deg(angle);
+            changed = true;
+        }
+    }
+
+    gui::add_text_before(ui, "bright limb");
+    gui::tooltip(ui, "Direction of the bright limb's midpoint relative to the north pole (measured towards east).");
+    changed |= imgui::Slider::new("##phase-sun-direction", -180.0, 180.0)
+        .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+        .display_format("%0.1f°")
+        .build(ui, &mut phase.sun_direction.0);
+    if let Some(predicted) = predicted {
+        let direction = predicted_sun_direction(predicted);
+        if handle_predicted_value(ui, format, "sun-direction", direction) {
+            phase.sun_direction = Deg(direction);
+            changed = true;
+        }
+    }
+
+    gui::add_text_before(ui, "terminator margin");
+    gui::tooltip(ui, "Solar elevation below which the image fades out; nothing beyond the terminator is used.");
+    changed |= imgui::Slider::new("##terminator-margin", 0.0, 30.0)
+        .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+        .display_format("%0.1f°")
+        .build(ui, &mut phase.terminator_margin.0);
+
+    if changed { view.set_phase(phase); }
+
+    let token = ui.begin_disabled(!phase.is_enabled());
+    let mut shown = view.terminator_shown;
+    if ui.checkbox("show terminator", &mut shown) { view.set_terminator_shown(shown); }
+    token.end();
+}
+
+/// Shows the name of the current frame's file and actions for it.
+fn handle_frame_file_controls(ui: &imgui::Ui, path: &Path) {
+    if ui.small_button("Show in folder") { gui::file_manager::reveal(path); }
+    gui::tooltip(ui, "Open the folder containing the frame's file in the file manager.");
+    ui.same_line();
+    if ui.small_button("Copy path") { ui.set_clipboard_text(path.to_string_lossy()); }
+    gui::tooltip(ui, "Copy the full path of the frame's file to the clipboard.");
+    ui.same_line();
+
+    let name = path.file_name().map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy());
+    ui.text(gui::truncate_middle(&name, ui.content_region_avail()[0], |text| ui.calc_text_size(text)[0]));
+    gui::tooltip(ui, &path.to_string_lossy());
+}
+
+fn handle_field_rotation_controls(
+    ui: &imgui::Ui,
+    format: &fmt::Preferences,
+    view: &mut SourceView,
+    allow_measurement: bool
+) -> SourceViewRequest {
+    let mut request = SourceViewRequest::None;
+
+    let token = ui.begin_disabled(view.roll_offsets.is_none());
+    let mut value = view.per_frame_roll();
+    if ui.checkbox("per-frame roll", &mut value) { view.set_per_frame_roll(value); }
+    token.end();
+    gui::tooltip(ui, "Apply roll changing across the sequence (e.g., due to field rotation of an alt-az mount); \
+        otherwise, roll is constant.");
+
+    gui::add_text_before(ui, "site latitude");
+    gui::tooltip(ui, "Latitude of the observing site (positive towards north).");
+    let mut value = view.site.latitude.0 as f32;
+    if ui.input_float("##site-latitude", &mut value).display_format("%0.2f°").enter_returns_true(true).build() {
+        view.site.latitude = Deg(value.max(-90.0).min(90.0) as f64);
+    }
+
+    gui::add_text_before(ui, "site longitude");
+    gui::tooltip(ui, "Longitude of the observing site (positive towards east).");
+    let mut value = view.site.longitude.0 as f32;
+    if ui.input_float("##site-longitude", &mut value).display_format("%0.2f°").enter_returns_true(true).build() {
+        view.site.longitude = Deg(value.max(-180.0).min(180.0) as f64);
+    }
+
+    let token = ui.begin_disabled(view.planet.is_none() || view.observation_jd.is_none());
+    if ui.button("From parallactic angle") { view.apply_parallactic_roll(); }
+    token.end();
+    gui::tooltip(ui, "Predict roll changes from the parallactic angle at the observing site (requires the planet \
+        and the observation time of the first frame). Assumes the camera keeps its orientation relative to \
+        the horizon.");
+    ui.same_line();
+    let token = ui.begin_disabled(!allow_measurement);
+    if ui.button("Measure limb") { request = SourceViewRequest::LimbMeasurement; }
+    token.end();
+    gui::tooltip(ui, "Estimate roll changes from the orientation of the flattened limb in each frame (requires \
+        a noticeably flattened planet, e.g., Jupiter).");
+
+    if view.limb_measurement_failed {
+        ui.text_colored([1.0, 0.8, 0.0, 1.0], "Limb orientation could not be measured.");
+    }
+
+    if let Some(offsets) = &view.roll_offsets {
+        let rolls: Vec<f32> = offsets.iter().map(|offset| (view.roll() + *offset).0).collect();
+        let min_roll = rolls.iter().copied().fold(f32::MAX, f32::min);
+        let max_roll = rolls.iter().copied().fold(f32::MIN, f32::max);
+        let graph_size = [ui.content_region_avail()[0], ui.calc_text_size("M")[1] * 4.0];
+        let overlay = format!(
+            "roll: {} .. {}", fmt::format_angle(Deg(min_roll), 2, format), fmt::format_angle(Deg(max_roll), 2, format)
+        );
+
+        ui.plot_lines("##frame-roll", &rolls)
+            .overlay_text(&overlay)
+            .scale_min(min_roll - 0.5)
+            .scale_max(max_roll + 0.5)
+            .graph_size(graph_size)
+            .build();
+    }
+
+    request
 }
 
 fn handle_roll_controls(ui: &imgui::Ui, view: &mut SourceView) {
@@ -666,6 +2269,25 @@ fn handle_roll_controls(ui: &imgui::Ui, view: &mut SourceView) {
     }
 }
 
+/// Returns the number of frames (since the start of playback) to advance to, given the number due according to
+/// the elapsed time (`due`) and the current number (`current`). In `every_frame` mode, playback advances by at most
+/// one frame per call.
+fn playback_frame_count(due: usize, current: usize, every_frame: bool) -> usize {
+    if every_frame {
+        if due > current { current + 1 } else { current }
+    } else {
+        due.max(current)
+    }
+}
+
+/// Returns the rate of events which happened at `times` (in chronological order).
+fn frame_rate(times: &VecDeque<Instant>) -> Option<f32> {
+    if times.len() < 2 { return None; }
+
+    let span = times.back().unwrap().duration_since(*times.front().unwrap()).as_secs_f32();
+    if span > 0.0 { Some((times.len() - 1) as f32 / span) } else { None }
+}
+
 fn advance_current_frame(
     start: usize,
     count_from_start: usize,
@@ -708,6 +2330,210 @@ fn advance_current_frame(
 mod tests {
     use super::*;
 
+    const WIDTHS: PlaybackWidths = PlaybackWidths{
+        buttons: 100.0, fps_label: 20.0, every_frame: 80.0, spacing: 10.0, min_slider: 50.0, max_slider: 150.0
+    };
+
+    #[test]
+    fn wide_playback_controls_fit_in_one_line() {
+        assert_eq!(
+            PlaybackLayout{ fps_on_new_line: false, every_frame_on_new_line: false, fps_slider_width: 150.0 },
+            playback_layout(1000.0, &WIDTHS)
+        );
+        // the slider shrinks first
+        assert_eq!(
+            PlaybackLayout{ fps_on_new_line: false, every_frame_on_new_line: false, fps_slider_width: 70.0 },
+            playback_layout(300.0, &WIDTHS)
+        );
+    }
+
+    #[test]
+    fn narrow_playback_controls_are_wrapped() {
+        assert_eq!(
+            PlaybackLayout{ fps_on_new_line: false, every_frame_on_new_line: true, fps_slider_width: 70.0 },
+            playback_layout(210.0, &WIDTHS)
+        );
+        assert_eq!(
+            PlaybackLayout{ fps_on_new_line: true, every_frame_on_new_line: true, fps_slider_width: 70.0 },
+            playback_layout(100.0, &WIDTHS)
+        );
+    }
+
+    #[test]
+    fn playback_slider_keeps_minimum_width() {
+        let layout = playback_layout(0.0, &WIDTHS);
+        assert!(layout.fps_on_new_line && layout.every_frame_on_new_line);
+        assert_eq!(WIDTHS.min_slider, layout.fps_slider_width);
+    }
+
+    #[derive(Default)]
+    struct NotificationCounter {
+        count: usize,
+        last_inclination: Deg<f32>
+    }
+
+    impl Subscriber<SourceParameters> for NotificationCounter {
+        fn notify(&mut self, value: &SourceParameters) {
+            self.count += 1;
+            self.last_inclination = value.inclination;
+        }
+    }
+
+    fn test_params() -> SourceParameters {
+        SourceParameters{
+            num_images: 10,
+            disk_diameter: 50.0,
+            flattening: Planet::Jupiter.flattening(),
+            ..SourceParameters::test_default()
+        }
+    }
+
+    /// Counts notifications and the resulting projection buffer resizes of a `ProjectionView`.
+    struct ResizeCounter {
+        params: SourceParameters,
+        notifications: usize,
+        resizes: usize
+    }
+
+    impl Subscriber<SourceParameters> for ResizeCounter {
+        fn notify(&mut self, value: &SourceParameters) {
+            self.notifications += 1;
+            if projection::projection_view::projection_buf_size_affected(&self.params, value) { self.resizes += 1; }
+            self.params = value.clone();
+        }
+    }
+
+    #[test]
+    fn bulk_update_results_in_single_notification_and_resize() {
+        let counter = Rc::new(RefCell::new(ResizeCounter{ params: test_params(), notifications: 0, resizes: 0 }));
+        let mut controller = SourceParamsController::new(test_params());
+        controller.subscribe(Rc::downgrade(&counter) as _);
+
+        controller.replace(SourceParameters{
+            num_images: 20,
+            inclination: Deg(3.0),
+            roll: Deg(-5.0),
+            disk_center: Point2{ x: 120.0, y: 90.0 },
+            disk_diameter: 70.0,
+            ..test_params()
+        });
+        assert!(controller.commit());
+
+        assert_eq!(1, counter.borrow().notifications);
+        assert_eq!(1, counter.borrow().resizes);
+        assert_eq!(20, counter.borrow().params.num_images);
+        assert_eq!(70.0, counter.borrow().params.disk_diameter);
+    }
+
+    #[test]
+    fn parameter_changes_result_in_single_notification() {
+        let counter = Rc::new(RefCell::new(NotificationCounter::default()));
+        let mut controller = SourceParamsController::new(test_params());
+        controller.subscribe(Rc::downgrade(&counter) as _);
+
+        controller.edit().inclination = Deg(1.0);
+        controller.edit().roll = Deg(2.0);
+        controller.edit().inclination = Deg(3.0);
+        controller.edit().disk_diameter = 60.0;
+        assert_eq!(0, counter.borrow().count);
+
+        assert!(controller.commit());
+        assert_eq!(1, counter.borrow().count);
+        assert_eq!(Deg(3.0), counter.borrow().last_inclination);
+    }
+
+    /// Renders (as recorded by the debug counts of its `RenderThrottle`) on each notification, like
+    /// a `ProjectionView`.
+    struct RenderingView(RenderThrottle);
+
+    impl Subscriber<SourceParameters> for RenderingView {
+        fn notify(&mut self, _: &SourceParameters) {
+            self.0.begin_rendering(Instant::now());
+        }
+    }
+
+    #[test]
+    fn slider_drag_renders_each_view_once_per_frame() {
+        let views = ["projection 1", "projection 2"].map(|owner| Rc::new(RefCell::new(RenderingView(
+            RenderThrottle::new(owner)
+        ))));
+        let mut controller = SourceParamsController::new(test_params());
+        for view in &views { controller.subscribe(Rc::downgrade(view) as _); }
+
+        const NUM_GUI_FRAMES: u64 = 5;
+        for i in 0..NUM_GUI_FRAMES {
+            // a drag may change several values within a GUI frame
+            controller.edit().inclination = Deg(i as f32);
+            controller.edit().disk_center.x += 1.0;
+            controller.edit().disk_diameter += 0.5;
+            controller.commit();
+        }
+
+        let counts = crate::gpu::render_throttle::counts();
+        for owner in ["projection 1", "projection 2"] {
+            assert_eq!(NUM_GUI_FRAMES, counts.iter().find(|c| c.owner == owner).unwrap().rendered);
+        }
+    }
+
+    #[test]
+    fn no_notification_without_changes() {
+        let counter = Rc::new(RefCell::new(NotificationCounter::default()));
+        let mut controller = SourceParamsController::new(test_params());
+        controller.subscribe(Rc::downgrade(&counter) as _);
+
+        assert!(!controller.commit());
+
+        controller.edit().roll = Deg(2.0);
+        assert!(controller.commit());
+        assert!(!controller.commit());
+        assert_eq!(1, counter.borrow().count);
+    }
+
+    #[test]
+    fn timed_playback_skips_frames() {
+        assert_eq!(3, playback_frame_count(3, 0, false));
+        assert_eq!(3, playback_frame_count(3, 3, false));
+        // the count never goes back (e.g., after the FPS value has been lowered, playback is restarted)
+        assert_eq!(5, playback_frame_count(4, 5, false));
+    }
+
+    #[test]
+    fn every_frame_playback_advances_one_frame_at_a_time() {
+        assert_eq!(1, playback_frame_count(3, 0, true));
+        assert_eq!(2, playback_frame_count(3, 1, true));
+        // not due yet
+        assert_eq!(3, playback_frame_count(3, 3, true));
+
+        // frames shown in subsequent GUI frames: consecutive, regardless of how much time has passed
+        let mut count = 0;
+        let shown: Vec<usize> = [4, 8, 12, 16].iter().map(|due| {
+            count = playback_frame_count(*due, count, true);
+            advance_current_frame(0, count, 3, &None, &mut None)
+        }).collect();
+        assert_eq!(vec![1, 2, 0, 1], shown);
+    }
+
+    #[test]
+    fn achieved_frame_rate() {
+        let start = Instant::now();
+        let times: VecDeque<Instant> = (0..5).map(|i| start + Duration::from_millis(i * 25)).collect();
+        assert!((40.0 - frame_rate(&times).unwrap()).abs() < 1.0e-3);
+
+        assert_eq!(None, frame_rate(&VecDeque::from(vec![start])));
+        assert_eq!(None, frame_rate(&VecDeque::from(vec![start, start])));
+    }
+
+    #[test]
+    fn outline_of_disk_with_center_outside_image() {
+        let image_size = [120, 100];
+        let transform = disk_outline_transform(Point2{ x: -8.0, y: 40.0 }, 60.0, image_size, 1.2);
+
+        // the visible rightmost point of the limb is at X = 22 px
+        let rightmost = transform * cgmath::Vector4{ x: 1.0, y: 0.0, z: 0.0, w: 1.0 };
+        assert!((rightmost.x - (-1.0 + 2.0 * 22.0 / 120.0)).abs() < 1.0e-6);
+        assert!((rightmost.y - (1.0 - 2.0 * 40.0 / 100.0)).abs() < 1.0e-6);
+    }
+
     #[test]
     fn count_frames_without_wrap() {
         // 0 1 2 3 4 5 6 | 0 1 2 3 4 5 6
@@ -771,4 +2597,34 @@ mod tests {
         assert_eq!(3, advance_current_frame(2, 7, 5, &initial_bouncing_back, &mut current_bouncing_back));
         assert_eq!(true, *current_bouncing_back.as_ref().unwrap());
     }
+
+    #[test]
+    #[ignore]
+    fn perf_advance_current_frame() {
+        const NUM_FRAMES: usize = 100_000;
+        for initial_bouncing_back in [None, Some(false), Some(true)] {
+            let mut current_bouncing_back = initial_bouncing_back;
+            crate::bench::measure(
+                &format!("advance_current_frame ({} frames, bouncing back: {:?})", NUM_FRAMES, initial_bouncing_back),
+                10,
+                || (0..2 * NUM_FRAMES).map(|count| advance_current_frame(
+                    NUM_FRAMES / 3, count, NUM_FRAMES, &initial_bouncing_back, &mut current_bouncing_back
+                )).sum::<usize>()
+            ).assert_within(std::time::Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn images_of_different_sizes_are_rejected() {
+        let paths = vec![PathBuf::from("a.png"), PathBuf::from("b.png"), PathBuf::from("c.png")];
+        assert_eq!(Ok([640, 480]), common_size(&[[640, 480], [640, 480], [640, 480]], &paths));
+
+        let error = common_size(&[[640, 480], [640, 480], [4000, 3000]], &paths).unwrap_err();
+        assert!(error.contains("c.png") && error.contains("4000x3000"), "{}", error);
+
+        // without paths, images are named by position
+        assert!(common_size(&[[640, 480], [320, 240]], &[]).unwrap_err().contains("image 2"));
+        assert!(common_size(&[[0, 480]], &paths).unwrap_err().contains("a.png"));
+        assert!(common_size(&[], &[]).is_err());
+    }
 }
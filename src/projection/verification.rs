@@ -0,0 +1,527 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Round-trip verification of the source parameters: the projection of one frame is mapped back onto the disk of
+//! another frame (taking the planet's rotation into account) and compared with it. Wrong parameters (e.g.,
+//! flattening or inclination) result in residuals concentrated at specific latitudes.
+
+use cgmath::{Angle, Deg, Point2, Rad, SquareMatrix};
+use crate::data::{self, ToArray};
+use crate::fmt;
+use crate::gui;
+use crate::gui::draw_buffer::{DrawBuffer, Sampling};
+use crate::gui::motion::AnimationSource;
+use crate::projection;
+use crate::projection::coverage;
+use crate::projection::projection_view::{self, ProjectionType};
+use crate::projection::source_view::SourceParameters;
+use crate::projection::SourceView;
+use glium::{Surface, Texture2d, uniform};
+use std::cell::RefCell;
+use std::error::Error;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use strum::IntoEnumIterator;
+
+/// Logical size (in pixels) of the displayed result.
+const PREVIEW_SIZE: f32 = 240.0;
+
+/// Size (in pixels) of the downscaled difference used to compute the residual.
+const RESIDUAL_SIZE: u32 = 128;
+
+/// Size of the source image region shown around the disk, relative to the disk diameter.
+const REGION_SCALE: f32 = 1.1;
+
+/// Multiplier of the displayed difference (the residual is computed without it).
+const DIFFERENCE_GAIN: f32 = 4.0;
+
+const BLINK_INTERVAL: Duration = Duration::from_millis(500);
+
+pub const BLINK_ANIMATION: AnimationSource = AnimationSource{ name: "verification blink", duration: blink_interval };
+
+/// Returns the interval of switching images in the blink display; zero if they are to be switched manually.
+fn blink_interval(gui_state: &gui::GuiState) -> Duration { gui_state.animation_duration(BLINK_INTERVAL) }
+
+/// Values of the `mode` uniform of `reprojection.frag`.
+#[derive(Copy, Clone)]
+enum Mode {
+    Reprojected = 0,
+    Source = 1,
+    Difference = 2,
+    Validity = 3
+}
+
+#[derive(Copy, Clone, PartialEq, strum::EnumIter)]
+enum Display {
+    Difference,
+    /// Alternates between the reprojected and the actual frame.
+    Blink
+}
+
+impl Display {
+    fn name(&self) -> &str {
+        match self {
+            Display::Difference => "difference",
+            Display::Blink => "blink"
+        }
+    }
+}
+
+/// Data of the most recent verification.
+struct Input {
+    /// Projection (covering 180° of longitude) of frame `map_frame_idx`.
+    map: Texture2d,
+    map_frame_idx: usize,
+    frame_idx: usize,
+    source_image: Rc<Texture2d>,
+    src_params: SourceParameters,
+    projection_type: ProjectionType
+}
+
+pub struct Verification {
+    /// Frame the projection is mapped back onto.
+    frame_idx: usize,
+    display: Display,
+    input: Option<Input>,
+    /// RMS difference (as a fraction of the full brightness range) over the disk area covered by the projection.
+    residual: Option<f32>,
+    preview: DrawBuffer,
+    /// Last change of the blinking preview.
+    blink_time: Instant,
+    blink_shows_source: bool,
+    gl_display: glium::Display,
+    projection_prog: Rc<glium::Program>,
+    reprojection_prog: Rc<glium::Program>,
+    unit_quad: Rc<glium::VertexBuffer<data::Vertex2>>
+}
+
+impl Verification {
+    pub fn new(
+        gl_objects: &projection::data::OpenGlObjects,
+        display: &glium::Display,
+        renderer: &Rc<RefCell<imgui_glium_renderer::Renderer>>,
+        registry_owner: &str
+    ) -> Verification {
+        let mut preview = DrawBuffer::new_with_size(
+            Sampling::Single,
+            &gl_objects.texture_copy_single,
+            &gl_objects.texture_copy_multi,
+            &gl_objects.unit_quad,
+            display,
+            renderer,
+            PREVIEW_SIZE as u32,
+            PREVIEW_SIZE as u32
+        );
+        preview.register(registry_owner, "verification");
+
+        Verification{
+            frame_idx: 0,
+            display: Display::Difference,
+            input: None,
+            residual: None,
+            preview,
+            blink_time: Instant::now(),
+            blink_shows_source: false,
+            gl_display: display.clone(),
+            projection_prog: Rc::clone(&gl_objects.projection),
+            reprojection_prog: Rc::clone(&gl_objects.reprojection),
+            unit_quad: Rc::clone(&gl_objects.unit_quad)
+        }
+    }
+
+    /// Maps the projection of frame `map_frame_idx` back onto frame `self.frame_idx`; computes the residual.
+    fn verify(
+        &mut self,
+        source_view: &SourceView,
+        src_params: &SourceParameters,
+        map_frame_idx: usize,
+        projection_type: ProjectionType,
+        standard_parallel: Deg<f32>
+    ) -> Result<(), Box<dyn Error>> {
+        self.input = None;
+        self.residual = None;
+
+        let map = Texture2d::empty_with_format(
+            &self.gl_display,
+            glium::texture::UncompressedFloatFormat::U8U8U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            (src_params.disk_diameter * std::f32::consts::PI / 2.0).ceil() as u32,
+            projection_view::projection_height(projection_type, src_params.disk_diameter, standard_parallel)
+        )?;
+        projection_view::render_projection(
+            false,
+            map_frame_idx,
+            source_view.image(map_frame_idx),
+            &mut map.as_surface(),
+            &self.unit_quad,
+            &self.projection_prog,
+            src_params,
+            0.0,
+            projection_type,
+            0.0,
+            false
+        )?;
+
+        let input = Input{
+            map,
+            map_frame_idx,
+            frame_idx: self.frame_idx,
+            source_image: Rc::clone(source_view.image(self.frame_idx)),
+            src_params: src_params.clone(),
+            projection_type
+        };
+
+        let render_residual_buf = |mode| -> Result<ga_image::Image, Box<dyn Error>> {
+            let texture = Texture2d::empty_with_format(
+                &self.gl_display,
+                glium::texture::UncompressedFloatFormat::U8U8U8,
+                glium::texture::MipmapsOption::NoMipmap,
+                RESIDUAL_SIZE,
+                RESIDUAL_SIZE
+            )?;
+            self.draw(&mut texture.as_surface(), &input, mode, 1.0)?;
+            Ok(crate::image_utils::image_from_texture(&texture))
+        };
+        let difference = render_residual_buf(Mode::Difference)?;
+        let validity = render_residual_buf(Mode::Validity)?;
+
+        self.residual = rms_residual(&difference, &validity);
+        self.input = Some(input);
+        self.blink_time = Instant::now();
+        self.blink_shows_source = false;
+        self.render_preview()?;
+
+        Ok(())
+    }
+
+    fn render_preview(&self) -> Result<(), glium::DrawError> {
+        let input = match &self.input {
+            Some(input) => input,
+            None => return Ok(())
+        };
+
+        let mode = match self.display {
+            Display::Difference => Mode::Difference,
+            Display::Blink => if self.blink_shows_source { Mode::Source } else { Mode::Reprojected }
+        };
+        self.draw(&mut self.preview.frame_buf(), input, mode, DIFFERENCE_GAIN)?;
+
+        self.preview.update_storage_buf()
+    }
+
+    fn draw<S: Surface>(
+        &self,
+        target: &mut S,
+        input: &Input,
+        mode: Mode,
+        difference_gain: f32
+    ) -> Result<(), glium::DrawError> {
+        let src_params = &input.src_params;
+        let shift = longitude_shift(
+            input.map_frame_idx, input.frame_idx, coverage::rotation_per_frame(src_params)
+        );
+        let inverse_globe_transform =
+            projection_view::frame_globe_transform(src_params, input.frame_idx).invert().unwrap();
+
+        let uniforms = uniform! {
+            map_image: input.map.sampled(),
+            source_image: input.source_image.sampled(),
+            equirectangular: input.projection_type == ProjectionType::Equirectangular,
+            disk_diameter: src_params.disk_diameter,
+            disk_center: src_params.disk_center.to_array(),
+            region_size: REGION_SCALE * src_params.disk_diameter,
+            inverse_globe_transform: inverse_globe_transform.to_array(),
+            image_mirror: src_params.image_mirror(),
+            longitude_shift: Rad::from(shift).0,
+            source_gain: src_params.frame_gain(input.frame_idx),
+            difference_gain: difference_gain,
+            mode: mode as i32
+        };
+
+        target.clear_color(0.0, 0.0, 0.0, 1.0);
+        target.draw(
+            &*self.unit_quad,
+            &glium::index::NoIndices(glium::index::PrimitiveType::TriangleFan),
+            &self.reprojection_prog,
+            &uniforms,
+            &Default::default()
+        )
+    }
+}
+
+/// Returns the value to be added to longitudes relative to the central meridian of frame `frame_idx` to obtain
+/// longitudes relative to the central meridian of frame `map_frame_idx` (see `coverage`).
+fn longitude_shift(map_frame_idx: usize, frame_idx: usize, rotation_per_frame: Deg<f32>) -> Deg<f32> {
+    -rotation_per_frame * (frame_idx as f32 - map_frame_idx as f32)
+}
+
+/// Returns normalized position (within [0; 1], from the bottom-left corner) in the map (covering 180° of longitude)
+/// of frame `map_frame_idx` which is sampled for the globe point seen at `position` (in pixels) in frame `frame_idx`;
+/// `None` if `position` is outside the disk or the point is not covered by the map (CPU equivalent of the mapping
+/// in `reprojection.frag`).
+pub fn map_position(
+    src_params: &SourceParameters,
+    projection_type: ProjectionType,
+    map_frame_idx: usize,
+    frame_idx: usize,
+    position: Point2<f32>
+) -> Option<[f32; 2]> {
+    let (longitude, latitude) = projection_view::frame_globe_coords(src_params, frame_idx, position)?;
+    let longitude = longitude + longitude_shift(map_frame_idx, frame_idx, coverage::rotation_per_frame(src_params));
+
+    let x = (longitude.0 + 90.0) / 180.0;
+    if !(0.0..=1.0).contains(&x) { return None; }
+
+    let y = match projection_type {
+        ProjectionType::Equirectangular => (latitude.0 + 90.0) / 180.0,
+        ProjectionType::LambertCylindricalEqualArea => projection_view::lambert_normalized_y(latitude)
+    };
+
+    Some([x, y])
+}
+
+/// Inverse of `map_position`: returns position (in pixels) in frame `frame_idx` of the globe point at normalized
+/// position `map_pos` in the map of frame `map_frame_idx` (the point may be on the far side of the globe).
+pub fn source_position(
+    src_params: &SourceParameters,
+    projection_type: ProjectionType,
+    map_frame_idx: usize,
+    frame_idx: usize,
+    map_pos: [f32; 2]
+) -> Point2<f32> {
+    let longitude = Deg(180.0 * map_pos[0] - 90.0)
+        - longitude_shift(map_frame_idx, frame_idx, coverage::rotation_per_frame(src_params));
+    let latitude = match projection_type {
+        ProjectionType::Equirectangular => Deg(180.0 * map_pos[1] - 90.0),
+        ProjectionType::LambertCylindricalEqualArea => Deg::asin(2.0 * map_pos[1] - 1.0)
+    };
+
+    projection_view::frame_source_image_position(src_params, frame_idx, longitude, latitude)
+}
+
+/// Returns the RMS value (as a fraction of the full range) of `difference` pixels which are white in `validity`
+/// (both images are RGB8); `None` if there are no such pixels.
+fn rms_residual(difference: &ga_image::Image, validity: &ga_image::Image) -> Option<f32> {
+    let mut sum_sq = 0.0f64;
+    let mut num_values = 0usize;
+
+    for y in 0..difference.height() {
+        let diff_line = difference.line::<u8>(y);
+        let valid_line = validity.line::<u8>(y);
+        for (diff, valid) in diff_line.chunks_exact(3).zip(valid_line.chunks_exact(3)) {
+            if valid[0] < 128 { continue; }
+            for value in diff {
+                sum_sq += (*value as f64 / 255.0).powi(2);
+                num_values += 1;
+            }
+        }
+    }
+
+    if num_values == 0 { None } else { Some((sum_sq / num_values as f64).sqrt() as f32) }
+}
+
+/// Shows the verification panel for the projection of frame `map_frame_idx`.
+pub fn handle_verification(
+    ui: &imgui::Ui,
+    gui_state: &mut gui::GuiState,
+    verification: &mut Verification,
+    source_view: &SourceView,
+    src_params: &SourceParameters,
+    map_frame_idx: usize,
+    projection_type: ProjectionType,
+    standard_parallel: Deg<f32>
+) {
+    verification.frame_idx = verification.frame_idx.min(source_view.num_images() - 1);
+
+    gui::add_text_before(ui, "frame");
+    let mut value = verification.frame_idx as u32 + 1;
+    if imgui::Slider::new("##verification-frame", 1, source_view.num_images() as u32)
+        .flags(imgui::SliderFlags::ALWAYS_CLAMP)
+        .build(ui, &mut value)
+    {
+        verification.frame_idx = value as usize - 1;
+    }
+    gui::tooltip(ui, "Frame onto which the displayed projection is mapped back. Choose a frame other than \
+        the displayed one to check also the rotation period and frame interval.");
+
+    ui.same_line();
+    if ui.button("Render##verification") {
+        let result = verification.verify(source_view, src_params, map_frame_idx, projection_type, standard_parallel);
+        if let Err(e) = result {
+            gui_state.message_box = Some(gui::MessageBox{
+                title: "Error".to_string(),
+                message: format!("Verification failed: {}.", e)
+            });
+            ui.open_popup("Error");
+        }
+    }
+
+    for display in Display::iter() {
+        ui.same_line();
+        if ui.radio_button_bool(format!("{}##verification-display", display.name()), verification.display == display) {
+            verification.display = display;
+            let _ = verification.render_preview();
+        }
+    }
+
+    let input = match &verification.input {
+        Some(input) => input,
+        None => {
+            ui.text_disabled("Not verified yet.");
+            return;
+        }
+    };
+
+    ui.text(format!("Frame {} mapped onto frame {}", input.map_frame_idx + 1, input.frame_idx + 1));
+    match verification.residual {
+        Some(residual) => ui.text(format!(
+            "RMS residual: {}% of full range", fmt::format_number(100.0 * residual as f64, 2, &gui_state.format)
+        )),
+        None => ui.text_disabled("No overlap with the projection.")
+    }
+
+    if verification.display == Display::Blink {
+        let interval = blink_interval(gui_state);
+        let switch = if interval.is_zero() {
+            let clicked = ui.button("Switch##verification-blink");
+            gui::tooltip(ui, "Switches between the images (automatic blinking is off due to reduced motion).");
+            ui.same_line();
+            clicked
+        } else {
+            verification.blink_time.elapsed() >= interval
+        };
+        if switch {
+            verification.blink_shows_source = !verification.blink_shows_source;
+            verification.blink_time = Instant::now();
+            let _ = verification.render_preview();
+        }
+        ui.text_disabled(if verification.blink_shows_source { "actual frame" } else { "mapped back" });
+    }
+
+    imgui::Image::new(verification.preview.id(), [PREVIEW_SIZE, PREVIEW_SIZE]).build(ui);
+}
+
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+    use ga_image::{Image, PixelFormat};
+
+    #[test]
+    fn later_frames_see_earlier_longitudes() {
+        // in frame 2 the planet has rotated by 10° since frame 0; its central meridian is at -10° in frame 0's map
+        assert_eq!(Deg(-10.0), longitude_shift(0, 2, Deg(5.0)));
+        assert_eq!(Deg(10.0), longitude_shift(2, 0, Deg(5.0)));
+        assert_eq!(Deg(0.0), longitude_shift(3, 3, Deg(5.0)));
+    }
+
+    fn round_trip_params() -> SourceParameters {
+        SourceParameters{
+            num_images: 4,
+            inclination: Deg(-3.0),
+            frame_interval: Duration::from_secs(180),
+            roll: Deg(20.0),
+            disk_center: Point2{ x: 210.0, y: 190.0 },
+            disk_diameter: 300.0,
+            flattening: projection::Planet::Jupiter.flattening(),
+            frame_rolls: vec![Deg(0.0), Deg(1.5), Deg(-2.0), Deg(4.0)],
+            mirror_ew: true,
+            ..SourceParameters::test_default()
+        }
+    }
+
+    #[test]
+    fn map_position_inverts_source_position() {
+        let params = round_trip_params();
+        for projection_type in [ProjectionType::Equirectangular, ProjectionType::LambertCylindricalEqualArea] {
+            for (map_frame_idx, frame_idx) in [(0, 0), (0, 3), (2, 1)] {
+                // globe points on the near side in frame `frame_idx`, expressed in the map of `map_frame_idx`
+                let shift = longitude_shift(map_frame_idx, frame_idx, coverage::rotation_per_frame(&params));
+                for lon in (-60..=60).step_by(15) {
+                    for lat in (-60..=60).step_by(15) {
+                        let latitude = Deg(lat as f32);
+                        let map_pos = [
+                            (lon as f32 + shift.0 + 90.0) / 180.0,
+                            match projection_type {
+                                ProjectionType::Equirectangular => (latitude.0 + 90.0) / 180.0,
+                                ProjectionType::LambertCylindricalEqualArea => (latitude.sin() + 1.0) / 2.0
+                            }
+                        ];
+
+                        let position = source_position(&params, projection_type, map_frame_idx, frame_idx, map_pos);
+                        let found = map_position(&params, projection_type, map_frame_idx, frame_idx, position)
+                            .unwrap();
+                        for (expected, actual) in map_pos.iter().zip(found.iter()) {
+                            assert!(
+                                (expected - actual).abs() < 1.0e-4,
+                                "{}, frames {}/{}, lon. {}°, lat. {}°: expected {:?}, got {:?}",
+                                projection_type.name(), map_frame_idx, frame_idx, lon, lat, map_pos, found
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn source_position_inverts_map_position() {
+        let params = round_trip_params();
+        for projection_type in [ProjectionType::Equirectangular, ProjectionType::LambertCylindricalEqualArea] {
+            for (map_frame_idx, frame_idx) in [(0, 0), (3, 0), (1, 2)] {
+                for x in (80..=340).step_by(20) {
+                    for y in (60..=320).step_by(20) {
+                        let position = Point2{ x: x as f32, y: y as f32 };
+                        let map_pos = match map_position(&params, projection_type, map_frame_idx, frame_idx, position) {
+                            Some(map_pos) => map_pos,
+                            // outside the disk or the map
+                            None => continue
+                        };
+
+                        let found = source_position(&params, projection_type, map_frame_idx, frame_idx, map_pos);
+                        assert!(
+                            (found - position).magnitude() < 0.01,
+                            "{}, frames {}/{}: expected {:?}, got {:?}",
+                            projection_type.name(), map_frame_idx, frame_idx, position, found
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn residual_covers_valid_pixels_only() {
+        let difference = Image::new_from_pixels(3, 1, None, PixelFormat::RGB8, None, vec![
+            51, 51, 51,
+            0, 0, 0,
+            255, 255, 255
+        ]);
+        let validity = Image::new_from_pixels(3, 1, None, PixelFormat::RGB8, None, vec![
+            255, 255, 255,
+            255, 255, 255,
+            0, 0, 0
+        ]);
+
+        let residual = rms_residual(&difference, &validity).unwrap();
+        assert!((residual - (0.2f32 * 0.2 / 2.0).sqrt()).abs() < 1.0e-6);
+
+        let nothing_valid = Image::new(3, 1, None, PixelFormat::RGB8, None, true);
+        assert!(rms_residual(&difference, &nothing_valid).is_none());
+    }
+}
@@ -0,0 +1,237 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+//! Maps in the form accepted by WinJUPOS: equirectangular, spanning 360° × 180°, longitude increasing leftwards,
+//! files named after the observation time.
+
+use crate::projection::ephem;
+use ga_image::{Image, PixelFormat};
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+/// Map parameters are written to this file in the output folder.
+pub const INFO_FILE_NAME: &str = "winjupos_maps.txt";
+
+const TENTHS_OF_MINUTE_PER_DAY: i64 = 24 * 60 * 10;
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+
+pub struct WinJuposExport {
+    /// Julian date (UTC) of the first frame.
+    pub start_jd: f64,
+    pub frame_interval: Duration,
+    pub planet: String
+}
+
+impl WinJuposExport {
+    /// Returns Julian date (UTC) of frame `idx`.
+    pub fn frame_time(&self, idx: usize) -> f64 {
+        self.start_jd + idx as f64 * self.frame_interval.as_secs_f64() / SECONDS_PER_DAY
+    }
+
+    pub fn file_name(&self, idx: usize) -> String { file_name(self.frame_time(idx), &self.planet) }
+
+    /// Writes `INFO_FILE_NAME` describing `num_frames` maps of the given size.
+    pub fn write_info(
+        &self,
+        output_dir: &Path,
+        num_frames: usize,
+        width: u32,
+        height: u32
+    ) -> Result<(), Box<dyn Error>> {
+        let mut file = std::fs::File::create(output_dir.join(INFO_FILE_NAME))?;
+
+        writeln!(file, "Planet: {}", self.planet)?;
+        writeln!(file, "Projection: equirectangular (simple cylindrical)")?;
+        writeln!(file, "Map size: {} x {} pixels", width, height)?;
+        writeln!(file, "Longitude: 360°, increasing leftwards; central meridian of each frame at the map's center")?;
+        writeln!(file, "Latitude: +90° (top) to -90° (bottom)")?;
+        writeln!(file, "Frame interval: {} s", self.frame_interval.as_secs_f64())?;
+        writeln!(file)?;
+        for idx in 0..num_frames {
+            writeln!(file, "{}", self.file_name(idx))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns "YYYY-MM-DD-HHMM_T-Planet.png" for the time `julian_date` (UTC); T is the tenth of a minute.
+pub fn file_name(julian_date: f64, planet: &str) -> String {
+    // days start at Julian date N - 0.5
+    let tenths = ((julian_date + 0.5) * TENTHS_OF_MINUTE_PER_DAY as f64).round() as i64;
+    let (year, month, day) = ephem::calendar_date(tenths.div_euclid(TENTHS_OF_MINUTE_PER_DAY));
+    let tenths_of_day = tenths.rem_euclid(TENTHS_OF_MINUTE_PER_DAY);
+
+    format!(
+        "{:04}-{:02}-{:02}-{:02}{:02}_{}-{}.png",
+        year, month, day, tenths_of_day / 600, tenths_of_day / 10 % 60, tenths_of_day % 10, planet
+    )
+}
+
+/// Parses a file name created by `file_name` (with any extension); returns the Julian date (UTC) and the planet.
+pub fn parse_file_name(name: &str) -> Option<(f64, String)> {
+    let stem = Path::new(name).file_stem()?.to_str()?;
+    let (time, planet) = stem.split_once('_')?;
+    let (tenths, planet) = planet.split_once('-')?;
+    if tenths.len() != 1 || planet.is_empty() { return None; }
+
+    let fields: Vec<&str> = time.split('-').collect();
+    if fields.len() != 4 || fields[3].len() != 4 || !fields.iter().all(|f| f.bytes().all(|b| b.is_ascii_digit())) {
+        return None;
+    }
+    let year: i32 = fields[0].parse().ok()?;
+    let (month, day): (u32, u32) = (fields[1].parse().ok()?, fields[2].parse().ok()?);
+    let (hour, minute): (u32, u32) = (fields[3][..2].parse().ok()?, fields[3][2..].parse().ok()?);
+    let tenths: u32 = tenths.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 { return None; }
+
+    Some((ephem::julian_date(year, month, day, hour, minute, tenths as f64 * 6.0), planet.to_string()))
+}
+
+/// Returns a 360°-wide map (longitude increasing leftwards) containing `hemisphere` (a 180°-wide projection with
+/// longitude increasing rightwards) in the middle; the rest of the map is black.
+pub fn full_map(hemisphere: &Image) -> Image {
+    let bytes_per_pixel = rgb_bytes_per_pixel(hemisphere);
+
+    let width = hemisphere.width();
+    let mut map = Image::new(2 * width, hemisphere.height(), None, hemisphere.pixel_format(), None, true);
+    let offset = bytes_per_pixel * (width / 2) as usize;
+    for y in 0..hemisphere.height() {
+        let src_line = &hemisphere.line::<u8>(y)[..bytes_per_pixel * width as usize];
+        map.line_mut::<u8>(y)[offset..offset + src_line.len()].copy_from_slice(src_line);
+    }
+
+    flip_longitude_axis(&mut map);
+
+    map
+}
+
+/// Mirrors `image` (RGB8 or RGB16) horizontally.
+pub fn flip_longitude_axis(image: &mut Image) {
+    let bytes_per_pixel = rgb_bytes_per_pixel(image);
+
+    let width = image.width() as usize;
+    for y in 0..image.height() {
+        let line = &mut image.line_mut::<u8>(y)[..bytes_per_pixel * width];
+        for x in 0..width / 2 {
+            for byte in 0..bytes_per_pixel {
+                line.swap(bytes_per_pixel * x + byte, bytes_per_pixel * (width - 1 - x) + byte);
+            }
+        }
+    }
+}
+
+/// Returns the number of bytes per pixel of `image` (RGB8 or RGB16).
+fn rgb_bytes_per_pixel(image: &Image) -> usize {
+    match image.pixel_format() {
+        PixelFormat::RGB8 => 3,
+        PixelFormat::RGB16 => 6,
+        _ => panic!("expected an RGB8 or RGB16 image")
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_name_follows_winjupos_convention() {
+        let jd = ephem::julian_date(2022, 8, 15, 1, 30, 24.0);
+        assert_eq!("2022-08-15-0130_4-Jupiter.png", file_name(jd, "Jupiter"));
+
+        let jd = ephem::julian_date(2022, 12, 31, 23, 59, 59.0);
+        // rounded to the nearest tenth of a minute, which moves it to the next day
+        assert_eq!("2023-01-01-0000_0-Mars.png", file_name(jd, "Mars"));
+    }
+
+    #[test]
+    fn file_name_is_parsed_back() {
+        let jd = ephem::julian_date(2022, 8, 15, 1, 30, 24.0);
+        let (parsed_jd, planet) = parse_file_name(&file_name(jd, "Jupiter")).unwrap();
+        assert!((parsed_jd - jd).abs() * SECONDS_PER_DAY < 0.01);
+        assert_eq!("Jupiter", planet);
+
+        let (parsed_jd, planet) = parse_file_name("2023-01-01-0000_0-Mars.tif").unwrap();
+        assert!((parsed_jd - ephem::julian_date(2023, 1, 1, 0, 0, 0.0)).abs() * SECONDS_PER_DAY < 0.01);
+        assert_eq!("Mars", planet);
+
+        assert!(parse_file_name("jupiter_frame00001_equirectangular.png").is_none());
+        assert!(parse_file_name("2022-13-15-0130_4-Jupiter.png").is_none());
+        assert!(parse_file_name("2022-08-15-130_4-Jupiter.png").is_none());
+    }
+
+    #[test]
+    fn frame_times_advance_by_interval() {
+        let export = WinJuposExport{
+            start_jd: ephem::julian_date(2022, 8, 15, 23, 58, 0.0),
+            frame_interval: Duration::from_secs(90),
+            planet: "Jupiter".to_string()
+        };
+
+        assert_eq!("2022-08-15-2358_0-Jupiter.png", export.file_name(0));
+        assert_eq!("2022-08-15-2359_5-Jupiter.png", export.file_name(1));
+        assert_eq!("2022-08-16-0001_0-Jupiter.png", export.file_name(2));
+    }
+
+    #[test]
+    fn longitude_axis_is_flipped() {
+        let mut image = Image::new(3, 2, None, PixelFormat::RGB8, None, true);
+        for y in 0..2 {
+            for (i, value) in image.line_mut::<u8>(y).iter_mut().enumerate() { *value = i as u8; }
+        }
+
+        flip_longitude_axis(&mut image);
+
+        for y in 0..2 {
+            assert_eq!(&[6, 7, 8, 3, 4, 5, 0, 1, 2], &image.line::<u8>(y)[..9]);
+        }
+    }
+
+    #[test]
+    fn sixteen_bit_pixels_are_flipped_whole() {
+        let mut image = Image::new(2, 1, None, PixelFormat::RGB16, None, true);
+        image.line_mut::<u16>(0)[..6].copy_from_slice(&[1, 2, 3, 256, 512, 65535]);
+
+        flip_longitude_axis(&mut image);
+
+        assert_eq!(&[256, 512, 65535, 1, 2, 3], &image.line::<u16>(0)[..6]);
+    }
+
+    #[test]
+    fn hemisphere_is_placed_in_the_middle_of_full_map() {
+        let mut hemisphere = Image::new(4, 4, None, PixelFormat::RGB8, None, true);
+        // mark the leftmost column
+        for y in 0..4 { hemisphere.line_mut::<u8>(y)[..3].copy_from_slice(&[255, 255, 255]); }
+
+        let map = full_map(&hemisphere);
+        assert_eq!(8, map.width());
+        assert_eq!(4, map.height());
+
+        // hemisphere occupies columns 2..6; after the flip its leftmost column becomes column 5
+        for y in 0..4 {
+            let line = map.line::<u8>(y);
+            for x in 0..8 {
+                let expected = if x == 5 { 255 } else { 0 };
+                assert_eq!(expected, line[3 * x], "x = {}", x);
+            }
+        }
+    }
+}
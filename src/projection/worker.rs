@@ -18,19 +18,36 @@
 //
 
 use cgmath::Point2;
+use crate::background::{self, SystemClock, WriteThrottle};
+use crate::cancellation::CancelToken;
+use crate::color::{self, Interpretation};
 use crate::data;
 use crate::data::TextureId;
+use crate::dither;
+use crate::gpu::gl_debug;
 use crate::gui::long_task_dialog::ProgressMsg;
-use crate::image_utils;
+use crate::image_utils::{self, BitDepth};
+use crate::img_seq::{self, ImageSequence};
+use crate::logging;
+use crate::normalization;
 use crate::projection;
-use crate::projection::projection_view::ProjectionType;
+use crate::projection::contact_sheet::ContactSheet;
+use crate::projection::export_conflicts;
+use crate::projection::export_metadata::{self, ExportMetadata};
+use crate::projection::file_stability;
+use crate::projection::output_format::{self, OutputFormat};
+use crate::projection::polar::PolarView;
+use crate::projection::winjupos::WinJuposExport;
+use crate::stacking::{self, Stacker};
 use crossbeam::channel::TrySendError;
-use glium::{glutin, Texture2d, program};
+use glium::{CapabilitiesSource, glutin, Surface, Texture2d, program};
+use std::cell::RefCell;
 use std::error::Error;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
-const PI_2: f32 = std::f32::consts::PI / 2.0;
+/// Delay before retrying a failed save of an output frame.
+const SAVE_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
 
 pub struct ProcessTexture {
     pub id: TextureId,
@@ -41,45 +58,353 @@ pub struct DummyJob {
     pub sender: crossbeam::channel::Sender<ProgressMsg>
 }
 
+/// Source frames of an export.
+pub enum ProjectionSource {
+    /// Frames already loaded into textures.
+    Textures(Vec<TextureId>),
+    /// Frames loaded one at a time from files into a single scratch texture (memory use does not depend
+    /// on sequence length).
+    Files{ paths: Vec<PathBuf>, binning: u32, interpretation: Interpretation }
+}
+
+impl ProjectionSource {
+    fn len(&self) -> usize {
+        match self {
+            ProjectionSource::Textures(ids) => ids.len(),
+            ProjectionSource::Files{ paths, .. } => paths.len()
+        }
+    }
+}
+
+pub enum ProjectionResultMsg {
+    Finished,
+    /// Export finished, but some frames could not be saved; contains the corresponding warnings.
+    FinishedWithSkippedFrames(Vec<String>),
+    /// Contains the number of frames exported before the cancellation and the total number of frames.
+    Cancelled(usize, usize),
+    Error(String),
+    /// Export succeeded, but the post-export command failed; contains the command's output.
+    PostExportCommandFailed(String),
+    /// The source textures (shared with the main context) appear to have been lost, e.g., after a GPU driver reset;
+    /// the images need to be loaded again.
+    ContextLost
+}
+
 pub struct Projection {
     pub sender: crossbeam::channel::Sender<ProgressMsg>,
+    pub result_sender: crossbeam::channel::Sender<ProjectionResultMsg>,
     pub image_size: glium::texture::Dimensions,
-    pub source_texture_ids: Vec<TextureId>,
+    pub source: ProjectionSource,
     pub output_dir: std::path::PathBuf,
     /// If true, outputs processed images twice (except the last one), in forward and reverse order.
     pub bounce_back: bool,
+    /// Prefix of names of saved frames (except WinJUPOS maps).
+    pub frame_prefix: String,
+    /// Number in the name of the first saved frame (except WinJUPOS maps).
+    pub first_frame_number: usize,
+    /// If false, the export fails (before saving anything) if any of the files to be saved (frames or other files,
+    /// see `other_output_paths`) already exists.
+    pub overwrite: bool,
     pub src_params: projection::source_view::SourceParameters,
+    /// Rotation compensation in pixels per frame; negative for retrograde rotation.
     pub rotation_comp: f32,
-    pub projection_type: projection::projection_view::ProjectionType
+    pub projection_type: projection::projection_view::ProjectionType,
+    /// Used for `ProjectionType::LambertCylindricalEqualArea`.
+    pub standard_parallel: cgmath::Deg<f32>,
+    /// Width (as a fraction of the disk radius) of the band along the limb within which the images fade out.
+    pub limb_feather: f32,
+    /// Shell command (with placeholders already substituted) to run after a successful export.
+    pub post_export_command: Option<String>,
+    /// Tone curve of the source images; output images are converted back to it from sRGB.
+    pub interpretation: Interpretation,
+    /// Checked before each frame and while loading source files (in addition to `MainToWorkerMsg::Cancel`).
+    pub cancel: CancelToken,
+    /// If true, `contact_sheet::FILE_NAME` is created in `output_dir` after all frames are exported.
+    pub contact_sheet: bool,
+    /// If true, frames which cannot be saved (even after a retry) are skipped instead of aborting the export.
+    pub skip_failed_frames: bool,
+    /// If set, the grid is drawn over each frame (at the same positions as in the projection view).
+    pub grid: Option<projection::projection_view::GridParams>,
+    /// If true, frame brightness is adjusted to minimize steps at the seams between consecutive frames (see `seams`;
+    /// requires rotation compensation); the gains are saved as `seams::FILE_NAME` in `output_dir`.
+    pub match_seams: bool,
+    /// If set, each frame is saved as a 360° map named after its observation time (`bounce_back` is ignored;
+    /// expects `ProjectionType::Equirectangular` and no rotation compensation).
+    pub winjupos: Option<WinJuposExport>,
+    /// If set, geometry of the saved maps is written to `export_metadata::FILE_NAME` in `output_dir`.
+    pub metadata: Option<ExportMetadata>,
+    /// If set, each frame is saved as a view from above a pole (expects `ProjectionType::Equirectangular`
+    /// and no rotation compensation).
+    pub polar: Option<PolarView>,
+    /// If set, frames are rendered with 16 bits per channel and dithered when converted to 8 bits for saving
+    /// (ignored if `output_bit_depth` is `BitDepth::Sixteen`).
+    pub dithering: Option<dither::Method>,
+    /// Bit depth of the source images.
+    pub source_bit_depth: BitDepth,
+    /// Format of saved frames (also determines their extension).
+    pub output_format: OutputFormat,
+    /// If true, frames are rendered on the CPU (see `cpu_render`; the grid is not drawn) and PNG files are encoded
+    /// with `image_utils::encode_png_reproducible`. Together with the export having no stochastic steps (dithering
+    /// is deterministic) and sorted sidecar entries, identical input and parameters then give identical files
+    /// regardless of the GPU and driver.
+    pub reproducible: bool
+}
+
+/// Renders a single frame of an export as it would be saved, without saving anything (for previewing).
+impl Projection {
+    /// Returns the bit depth of saved frames: that of `output_format`, except for polar views (always 8-bit).
+    pub fn output_bit_depth(&self) -> BitDepth {
+        if self.polar.is_some() { BitDepth::Eight } else { self.output_format.bit_depth() }
+    }
+
+    /// Returns the paths under which frame `idx` (of `num_images`) is saved.
+    fn output_paths(&self, idx: usize, num_images: usize) -> Vec<PathBuf> {
+        let extension = self.output_format.extension();
+        if let Some(winjupos) = &self.winjupos {
+            return vec![self.output_dir.join(winjupos.file_name(idx)).with_extension(extension)];
+        }
+
+        let file_name = |number| export_conflicts::frame_file_name(&self.frame_prefix, number, extension);
+        let mut paths = vec![self.output_dir.join(file_name(self.first_frame_number + idx))];
+        if self.bounce_back && idx < num_images - 1 {
+            // frames in reverse order follow the last one
+            paths.push(self.output_dir.join(file_name(self.first_frame_number + 2 * num_images - 2 - idx)));
+        }
+
+        paths
+    }
+
+    /// Returns the paths of files other than frames saved by the export.
+    fn other_output_paths(&self) -> Vec<PathBuf> {
+        let mut file_names = vec![];
+        if self.match_seams { file_names.push(projection::seams::FILE_NAME); }
+        if self.winjupos.is_some() { file_names.push(projection::winjupos::INFO_FILE_NAME); }
+        if self.metadata.is_some() { file_names.push(export_metadata::FILE_NAME); }
+        if self.contact_sheet { file_names.push(projection::contact_sheet::FILE_NAME); }
+
+        file_names.iter().map(|file_name| self.output_dir.join(file_name)).collect()
+    }
+}
+
+pub struct PreviewFrame {
+    /// No progress or result messages of the export are sent.
+    pub task: Projection,
+    pub frame_idx: usize,
+    pub image_sender: crossbeam::channel::Sender<Result<ga_image::Image, String>>
 }
 
 pub struct LoadImages {
+    /// Dimensions after binning.
     pub dimensions: [u32; 2],
-    pub pixel_format: ga_image::PixelFormat,
-    pub items: Vec<(TextureId, PathBuf)>,
+    /// Images are binned (averaged in blocks of `binning`×`binning` pixels) before being uploaded.
+    pub binning: u32,
+    /// Tone curve of image values; images are converted to sRGB before being uploaded.
+    pub interpretation: Interpretation,
+    /// Bit depth of the textures; an image of a different depth aborts the loading (regardless of
+    /// `skip_failed_frames`).
+    pub bit_depth: BitDepth,
+    /// If true, files which fail to load are skipped instead of aborting the loading.
+    pub skip_failed_frames: bool,
+    /// Disk found previously in the first item's image (loaded with the same options); if set, the disk detection
+    /// is skipped for this image.
+    pub first_item_disk: Option<DiskInfo>,
+    /// Checked before each item and while decoding a file (in addition to `MainToWorkerMsg::Cancel`).
+    pub cancel: CancelToken,
+    pub items: LoadItems,
     pub progress_sender: crossbeam::channel::Sender<ProgressMsg>,
     pub result_sender: crossbeam::channel::Sender<LoadImagesResultMsg>
 }
 
+/// Images to load, each with its destination texture.
+pub enum LoadItems {
+    /// Each image is read from a separate file.
+    Files(Vec<(TextureId, PathBuf)>),
+    /// Images are frames (at the given indices) of a SER video; the video is opened once for all of them.
+    Video(PathBuf, Vec<(TextureId, usize)>)
+}
+
+impl LoadItems {
+    fn len(&self) -> usize {
+        match self {
+            LoadItems::Files(files) => files.len(),
+            LoadItems::Video(_, frames) => frames.len()
+        }
+    }
+
+    fn texture_id(&self, idx: usize) -> TextureId {
+        match self {
+            LoadItems::Files(files) => files[idx].0,
+            LoadItems::Video(_, frames) => frames[idx].0
+        }
+    }
+
+    /// Returns the item's description for the log and progress messages.
+    fn describe(&self, idx: usize) -> String {
+        match self {
+            LoadItems::Files(files) => files[idx].1.to_string_lossy().to_string(),
+            LoadItems::Video(path, frames) => format!("frame {} of {}", frames[idx].1 + 1, path.to_string_lossy())
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct DiskInfo {
     pub center: Point2<f32>,
     pub diameter: f32
 }
 
+/// Error of an image whose bit depth differs from that of the other images.
+#[derive(Debug)]
+pub struct BitDepthMismatch {
+    expected: BitDepth,
+    found: BitDepth
+}
+
+impl std::fmt::Display for BitDepthMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} image among {} ones; all images must have the same bit depth",
+            self.found.name(), self.expected.name()
+        )
+    }
+}
+
+impl Error for BitDepthMismatch {}
+
+/// Fails if the image last loaded into `staging` has been converted from a different bit depth.
+fn check_bit_depth(staging: &image_utils::StagingBuffers) -> Result<(), Box<dyn Error>> {
+    if staging.source_depth() == staging.depth() { return Ok(()); }
+
+    Err(Box::new(BitDepthMismatch{ expected: staging.depth(), found: staging.source_depth() }))
+}
+
+/// The first successfully loaded frame (converted to Mono8 from the sRGB values uploaded to its texture) and the disk
+/// found in it (`None` if detection failed).
+pub struct FirstFrame {
+    pub image: ga_image::Image,
+    pub disk: Option<DiskInfo>
+}
+
+/// Frame which has not been loaded.
+pub struct SkippedFrame {
+    /// Index in `LoadImages::items`.
+    pub index: usize,
+    pub error: String
+}
+
+/// Returns `items` without the elements at indices of `skipped` (sorted in ascending order).
+pub fn remove_skipped<T>(items: Vec<T>, skipped: &[SkippedFrame]) -> Vec<T> {
+    let mut skipped = skipped.iter().map(|s| s.index).peekable();
+
+    items.into_iter().enumerate().filter_map(|(idx, item)| {
+        if skipped.peek() == Some(&idx) {
+            skipped.next();
+            None
+        } else {
+            Some(item)
+        }
+    }).collect()
+}
+
 pub enum LoadImagesResultMsg {
-    Success(DiskInfo),
+    /// Contains the first loaded frame and frames skipped due to errors (sorted by index).
+    Success(FirstFrame, Vec<SkippedFrame>),
     Error(String),
+    /// Contains the number of files loaded before the cancellation.
+    Cancelled(usize)
+}
+
+pub struct StackFrames {
+    pub image_size: glium::texture::Dimensions,
+    pub source_texture_ids: Vec<TextureId>,
+    /// Bit depth of the source textures; the stacked frames have the same depth.
+    pub source_bit_depth: BitDepth,
+    /// Consecutive groups of this many frames are stacked separately (the last group may be smaller).
+    pub group_size: usize,
+    /// If set, values outside mean ± `sigma_clip` · standard deviation are rejected.
+    pub sigma_clip: Option<f32>,
+    pub progress_sender: crossbeam::channel::Sender<ProgressMsg>,
+    pub result_sender: crossbeam::channel::Sender<StackFramesResultMsg>
+}
+
+pub enum StackFramesResultMsg {
+    /// Contains the stacked frame of each group.
+    Success(Vec<ga_image::Image>),
+    Cancelled
+}
+
+pub struct MeasureBrightness {
+    pub image_size: glium::texture::Dimensions,
+    pub source_texture_ids: Vec<TextureId>,
+    pub disk_center: Point2<f32>,
+    pub disk_diameter: f32,
+    pub progress_sender: crossbeam::channel::Sender<ProgressMsg>,
+    pub result_sender: crossbeam::channel::Sender<MeasureBrightnessResultMsg>
+}
+
+pub enum MeasureBrightnessResultMsg {
+    /// Mean brightness inside the disk for each frame (0.0 if the disk does not cover any pixels).
+    Success(Vec<f32>),
+    Cancelled
+}
+
+pub struct MeasureLimbAngles {
+    pub image_size: glium::texture::Dimensions,
+    pub source_texture_ids: Vec<TextureId>,
+    pub disk_center: Point2<f32>,
+    pub disk_diameter: f32,
+    /// See `SourceParameters::image_mirror`.
+    pub image_mirror: [f32; 2],
+    pub progress_sender: crossbeam::channel::Sender<ProgressMsg>,
+    pub result_sender: crossbeam::channel::Sender<MeasureLimbAnglesResultMsg>
+}
+
+pub enum MeasureLimbAnglesResultMsg {
+    /// Limb angle of each frame (see `field_rotation::limb_angle`; `None` if not measurable).
+    Success(Vec<Option<cgmath::Deg<f32>>>),
     Cancelled
 }
 
 pub enum MainToWorkerMsg {
     Cancel,
     Projection(Projection),
-    LoadImages(LoadImages)
+    PreviewFrame(PreviewFrame),
+    LoadImages(LoadImages),
+    StackFrames(StackFrames),
+    MeasureBrightness(MeasureBrightness),
+    MeasureLimbAngles(MeasureLimbAngles)
 }
 
-pub fn worker(context: glutin::Context<glutin::NotCurrent>, receiver: crossbeam::channel::Receiver<MainToWorkerMsg>) {
+/// Starts the worker thread (using `context`, which shares lists with the main thread's context). The thread runs
+/// until all senders of the returned channel are dropped, so it can serve successive program modes.
+///
+/// Also returns the receiver of the worker context's maximum texture size (sent once at startup; the context may be
+/// provided by a different GPU than the main one).
+pub fn spawn_worker(
+    context: glutin::Context<glutin::NotCurrent>
+) -> (crossbeam::channel::Sender<MainToWorkerMsg>, crossbeam::channel::Receiver<u32>) {
+    let (sender, receiver) = crossbeam::channel::unbounded();
+    let (limit_sender, limit_receiver) = crossbeam::channel::bounded(1);
+    std::thread::spawn(move || {
+        background::apply_priority();
+        worker(context, receiver, limit_sender);
+    });
+
+    (sender, limit_receiver)
+}
+
+fn worker(
+    context: glutin::Context<glutin::NotCurrent>,
+    receiver: crossbeam::channel::Receiver<MainToWorkerMsg>,
+    limit_sender: crossbeam::channel::Sender<u32>
+) {
     let headless = glium::HeadlessRenderer::new(context).unwrap();
+    gl_debug::install(gl_debug::GlContext::Worker, &headless);
+
+    let max_texture_size = headless.get_capabilities().max_texture_size as u32;
+    logging::log_info!("Worker context's max. texture size: {}.", max_texture_size);
+    let _ = limit_sender.send(max_texture_size);
 
     let unit_quad = projection::data::create_unit_quad(&headless);
     let projection = Rc::new(program!(&headless,
@@ -88,21 +413,50 @@ pub fn worker(context: glutin::Context<glutin::NotCurrent>, receiver: crossbeam:
             fragment: include_str!("../resources/shaders/projection.frag"),
         }
     ).unwrap());
+    let solid_color_2d = program!(&headless,
+        330 => {
+            vertex: include_str!("../resources/shaders/transform_2d.vert"),
+            fragment: include_str!("../resources/shaders/solid_color.frag"),
+        }
+    ).unwrap();
 
     loop {
-        match receiver.recv() {
+        let msg = receiver.recv();
+        // a changed setting applies to subsequent tasks
+        background::apply_priority();
+
+        match msg {
             Ok(msg) => match msg {
                 MainToWorkerMsg::Projection(task) => on_projection(
                     task,
                     &headless,
                     &unit_quad,
                     &projection,
+                    &solid_color_2d,
+                    &GlContextCheck,
+                    &receiver
+                ),
+
+                MainToWorkerMsg::PreviewFrame(preview) => on_preview_frame(
+                    preview,
+                    &headless,
+                    &unit_quad,
+                    &projection,
+                    &solid_color_2d,
+                    &GlContextCheck,
                     &receiver
                 ),
 
-                MainToWorkerMsg::Cancel => panic!("unexpected message received"),
+                // the task to be cancelled has already finished
+                MainToWorkerMsg::Cancel => (),
 
-                MainToWorkerMsg::LoadImages(task) => on_load_images(task, &headless, &receiver)
+                MainToWorkerMsg::LoadImages(task) => on_load_images(task, &headless, &receiver),
+
+                MainToWorkerMsg::StackFrames(task) => on_stack_frames(task, &headless, &receiver),
+
+                MainToWorkerMsg::MeasureBrightness(task) => on_measure_brightness(task, &headless, &receiver),
+
+                MainToWorkerMsg::MeasureLimbAngles(task) => on_measure_limb_angles(task, &headless, &receiver)
             },
 
             Err(_) => break
@@ -115,89 +469,149 @@ fn on_projection(
     display: &dyn glium::backend::Facade,
     unit_quad: &glium::VertexBuffer<data::Vertex2>,
     projection_prog: &glium::Program,
+    solid_color_2d_prog: &glium::Program,
+    context_check: &dyn ContextCheck,
     receiver: &crossbeam::channel::Receiver<MainToWorkerMsg>
 ) {
-    //TODO: refactor DrawBuffer to also work w/out "imgui texture id"
-
-    // let projection_draw_buf = DrawBuffer::new_with_size(
-    //     Sampling::Single,
-    //     &gl_objects.texture_copy_single,
-    //     &gl_objects.texture_copy_multi,
-    //     &unit_quad,
-    //     display,
-    //     //renderer,
-    //     (disk_diameter * PI_2).ceil() as u32,
-    //     (disk_diameter * PI_2).ceil() as u32,
-    // );
-
-    // using a plain texture as the render target for now
-    let draw_buffer = Texture2d::empty_with_format(
-        display,
-        glium::texture::UncompressedFloatFormat::U8U8U8,
-        glium::texture::MipmapsOption::NoMipmap,
-        (task.src_params.disk_diameter * PI_2 + (task.src_params.num_images - 1) as f32 * task.rotation_comp).ceil() as u32,
-        match task.projection_type {
-            ProjectionType::Equirectangular => (task.src_params.disk_diameter * PI_2).ceil() as u32,
-            ProjectionType::LambertCylindricalEqualArea => task.src_params.disk_diameter as u32
+    let targets = ExportTargets::new(&task, display);
+    let draw_buffer = &targets.draw_buffer;
+
+    let num_images = task.source.len();
+    logging::log_info!("Exporting {} frames to {}.", num_images, task.output_dir.to_string_lossy());
+    if let Some(polar) = &task.polar {
+        logging::log_info!(
+            "Frames are exported as {} views of the {} pole, down to latitude {:.0}°, {}×{} pixels.",
+            polar.projection.name(), polar.pole.name(), polar.latitude_limit.0, polar.size, polar.size
+        );
+    }
+
+    logging::log_info!(
+        "Frames are saved as {} {} images.", task.output_bit_depth().name(), task.output_format.extension()
+    );
+    if let (Some(method), BitDepth::Eight) = (task.dithering, task.output_bit_depth()) {
+        logging::log_info!("Frames are dithered ({}) when converted to 8 bits.", method.name());
+    }
+    if task.reproducible {
+        logging::log_info!("Frames are rendered on the CPU and encoded reproducibly.");
+        if task.grid.is_some() { logging::log_warning!("The grid is not drawn in reproducible exports."); }
+    }
+
+    if !task.overwrite {
+        if let Some(existing) = (0..num_images)
+            .flat_map(|idx| task.output_paths(idx, num_images))
+            .chain(task.other_output_paths())
+            .find(|path| path.exists())
+        {
+            task.result_sender.send(ProjectionResultMsg::Error(format!(
+                "{} already exists (enable overwriting existing files to replace it)", existing.to_string_lossy()
+            ))).unwrap();
+            return;
         }
-    ).unwrap();
+    }
 
-    let num_images = task.source_texture_ids.len();
+    let mut contact_sheet = if task.contact_sheet { Some(ContactSheet::new()) } else { None };
 
-    for (idx, source_texture_id) in task.source_texture_ids.iter().enumerate() {
-        match receiver.try_recv() {
-            Ok(msg) => match msg {
-                MainToWorkerMsg::Cancel => break,
-                _ => panic!("unexpected message received")
-            },
+    let mut skip_warnings = vec![];
 
-            _ => ()
+    let mut metadata_entries = vec![];
+
+    let mut throttle = background::settings().max_write_rate.map(|rate| WriteThrottle::new(rate, SystemClock::new()));
+
+    let renderer = FrameRenderer::new(&task, display, unit_quad, projection_prog, solid_color_2d_prog, &targets);
+
+    let src_params = match match_seams(&renderer, receiver) {
+        Ok(None) => task.src_params.clone(),
+        Ok(Some(matching)) => {
+            if let Err(e) = projection::seams::save(&task.output_dir, &matching.ratios, &matching.gains) {
+                task.result_sender.send(ProjectionResultMsg::Error(format!(
+                    "failed to save {}: {}", projection::seams::FILE_NAME, e
+                ))).unwrap();
+                return;
+            }
+            matching.apply(&task.src_params)
+        },
+        Err(failure) => {
+            task.result_sender.send(failure.into_message(0, num_images)).unwrap();
+            return;
         }
+    };
 
-        let source_texture = unsafe { glium::Texture2d::from_id(
-            display,
-            glium::texture::UncompressedFloatFormat::U8U8U8,
-            *source_texture_id,
-            false,
-            glium::texture::MipmapsOption::NoMipmap,
-            task.image_size
-        ) };
+    for idx in 0..num_images {
+        if cancel_requested(&task.cancel, receiver) {
+            task.result_sender.send(ProjectionResultMsg::Cancelled(idx, num_images)).unwrap();
+            return;
+        }
 
-        projection::projection_view::render_projection(
-            false,
-            idx,
-            &source_texture,
-            &mut draw_buffer.as_surface(),
-            unit_quad,
-            projection_prog,
-            &task.src_params,
-            task.rotation_comp,
-            task.projection_type
-        );
+        // checking the first frame suffices
+        let context_check = if idx == 0 { Some(context_check) } else { None };
+        let output_img = match renderer.output_image(idx, &src_params, context_check) {
+            Ok(image) => image,
+            Err(failure) => {
+                task.result_sender.send(failure.into_message(idx, num_images)).unwrap();
+                return;
+            }
+        };
+        if let Some(contact_sheet) = &mut contact_sheet {
+            if output_img.pixel_format() == ga_image::PixelFormat::RGB16 {
+                contact_sheet.add(&output_img.convert_pix_fmt(ga_image::PixelFormat::RGB8, None));
+            } else {
+                contact_sheet.add(&output_img);
+            }
+        }
 
-        let output_img = image_utils::image_from_texture(&draw_buffer);
-        let output_path = Path::new(&task.output_dir).join(format!("output_{:05}.png", idx + 1));
+        let output_paths = task.output_paths(idx, num_images);
 
-        image::save_buffer(
-            &output_path, output_img.raw_pixels(), output_img.width(), output_img.height(), image::ColorType::Rgb8
-        ).unwrap();
+        let geometry = task.metadata.as_ref().map(|_| if task.winjupos.is_some() {
+            export_metadata::MapGeometry::winjupos(&src_params, idx, draw_buffer.width(), draw_buffer.height())
+        } else {
+            export_metadata::MapGeometry::strip(
+                &src_params, task.rotation_comp, idx, draw_buffer.width(), draw_buffer.height()
+            )
+        });
+
+        let mut progress_msg = String::new();
+        for output_path in &output_paths {
+            let result = save_with_retry(&output_img, output_path, task.output_format, task.reproducible);
+            if let (Some(throttle), Ok(())) = (&mut throttle, &result) {
+                let num_bytes = std::fs::metadata(output_path)
+                    .map_or(output_img.raw_pixels().len() as u64, |metadata| metadata.len());
+                throttle.wrote(num_bytes);
+            }
+            if let Err(e) = result {
+                let warning = format!("failed to save {}: {}", output_path.to_string_lossy(), e);
+                if task.skip_failed_frames {
+                    logging::log_warning!("Export: {}; skipping.", warning);
+                    skip_warnings.push(warning);
+                    continue;
+                } else {
+                    task.result_sender.send(ProjectionResultMsg::Error(warning)).unwrap();
+                    return;
+                }
+            }
 
-        let mut progress_msg = format!("Saved {}", output_path.as_os_str().to_string_lossy());
+            if let (Some(metadata), Some(geometry)) = (&task.metadata, &geometry) {
+                metadata_entries.push(export_metadata::FrameEntry{
+                    file_name: output_path.file_name().unwrap().to_string_lossy().to_string(),
+                    source_frame: idx,
+                    time_jd: metadata.frame_time(&src_params, idx),
+                    geometry: geometry.clone()
+                });
+            }
 
-        if task.bounce_back && idx < num_images - 1 {
-            let output_path = Path::new(&task.output_dir).join(format!("output_{:05}.png", 2 * num_images - (idx + 1)));
-            image::save_buffer(
-                &output_path, output_img.raw_pixels(), output_img.width(), output_img.height(), image::ColorType::Rgb8
-            ).unwrap();
-            progress_msg += ", ";
-            progress_msg += &output_path.file_name().unwrap().to_string_lossy();
+            if progress_msg.is_empty() {
+                progress_msg = format!("Saved {}", output_path.as_os_str().to_string_lossy());
+            } else {
+                progress_msg += ", ";
+                progress_msg += &output_path.file_name().unwrap().to_string_lossy();
+            }
         }
+        if progress_msg.is_empty() { progress_msg = format!("Skipped frame {}", idx + 1); }
 
         progress_msg += ".";
 
         match task.sender.try_send(ProgressMsg::new(
             progress_msg,
-            idx as f32 / task.source_texture_ids.len() as f32
+            idx as f32 / num_images as f32
         )) {
             Ok(()) => (),
             Err(err) => match err {
@@ -206,106 +620,1458 @@ fn on_projection(
             }
         }
     }
-}
 
-fn load_single_image(
-    expected_width: u32,
-    expected_height: u32,
-    expected_pix_fmt: ga_image::PixelFormat,
-    path: &Path,
-    texture: &glium::texture::Texture2d
-) -> Result<ga_image::Image, Box<dyn Error>> {
-    let image = image_utils::load_image(&path)?;
-    if image.width() != expected_width || image.height() != expected_height {
-        return Err(format!(
-            "unexpected image dimensions (expected {}x{}, found {}x{})",
-            expected_width, expected_height, image.width(), image.height()
-        ).into());
+    if let Some(winjupos) = &task.winjupos {
+        if let Err(e) = winjupos.write_info(
+            &task.output_dir, num_images, 2 * draw_buffer.width(), draw_buffer.height()
+        ) {
+            task.result_sender.send(ProjectionResultMsg::Error(format!(
+                "failed to save {}: {}", projection::winjupos::INFO_FILE_NAME, e
+            ))).unwrap();
+            return;
+        }
     }
 
-    if image.pixel_format() != expected_pix_fmt {
-        return Err(format!(
-            "unexpected pixel format (expected {:?}, found {:?})",
-            expected_pix_fmt, image.pixel_format()
-        ).into());
+    if task.metadata.is_some() {
+        metadata_entries.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+        if let Err(e) = export_metadata::save(
+            &task.output_dir, task.projection_type, task.standard_parallel, &metadata_entries
+        ) {
+            task.result_sender.send(ProjectionResultMsg::Error(format!(
+                "failed to save {}: {}", export_metadata::FILE_NAME, e
+            ))).unwrap();
+            return;
+        }
     }
 
-    //TODO: handle more pixel formats
-    let image = image.convert_pix_fmt(ga_image::PixelFormat::RGB8, None);
+    if let Some(contact_sheet) = &contact_sheet {
+        let _ = task.sender.try_send(ProgressMsg::new("Creating contact sheet.".to_string(), 1.0));
+        if let Err(e) = contact_sheet.save(&task.output_dir, task.reproducible) {
+            task.result_sender.send(
+                ProjectionResultMsg::Error(format!("failed to save contact sheet: {}", e))
+            ).unwrap();
+            return;
+        }
+    }
 
-    let source = glium::texture::RawImage2d{
-        data: std::borrow::Cow::<[u8]>::from(image.pixels::<u8>()),
-        width: image.width(),
-        height: image.height(),
-        format: glium::texture::ClientFormat::U8U8U8
-    };
+    if let Some(command) = &task.post_export_command {
+        let _ = task.sender.try_send(ProgressMsg::new("Running post-export command.".to_string(), 1.0));
+        if let Err(log) = projection::post_export::run(command, &task.output_dir) {
+            task.result_sender.send(ProjectionResultMsg::PostExportCommandFailed(log)).unwrap();
+            return;
+        }
+    }
 
-    texture.write(glium::Rect{ left: 0, bottom: 0, width: image.width(), height: image.height() }, source);
+    task.result_sender.send(if skip_warnings.is_empty() {
+        ProjectionResultMsg::Finished
+    } else {
+        ProjectionResultMsg::FinishedWithSkippedFrames(skip_warnings)
+    }).unwrap();
+}
+
+/// Checks for symptoms of a lost GPU context or of lost textures shared with the main context.
+pub trait ContextCheck {
+    /// Returns (and clears) the oldest OpenGL error recorded in the current context (`gl::NO_ERROR` if none).
+    fn take_gl_error(&self) -> u32;
 
-    Ok(image)
+    /// Clears errors recorded before the checked calls (e.g., by unrelated earlier ones), so that they are not taken
+    /// for a symptom of context loss.
+    fn clear_gl_errors(&self) {
+        // a lost context may keep reporting an error
+        for _ in 0..MAX_PENDING_GL_ERRORS {
+            if self.take_gl_error() == gl::NO_ERROR { break; }
+        }
+    }
 }
 
-fn on_load_images(
-    task: LoadImages,
+/// Maximum number of recorded OpenGL errors cleared by `ContextCheck::clear_gl_errors`.
+const MAX_PENDING_GL_ERRORS: usize = 32;
+
+struct GlContextCheck;
+
+impl ContextCheck for GlContextCheck {
+    fn take_gl_error(&self) -> u32 { unsafe { gl::GetError() } }
+}
+
+/// Number of sampled rows and columns of a rendered frame.
+const CONTEXT_CHECK_SAMPLES: u32 = 8;
+
+/// Returns true if `output` (RGB8; the first frame rendered from the loaded source textures) or the OpenGL error state
+/// indicate that the shared textures have been lost. The source images are not blank (a disk has been found
+/// in them), so an all-black projection is suspicious.
+fn context_lost(check: &dyn ContextCheck, output: &ga_image::Image) -> bool {
+    let error = check.take_gl_error();
+    if error == gl::CONTEXT_LOST || error == gl::INVALID_OPERATION || error == gl::INVALID_VALUE { return true; }
+
+    let positions = |size: u32| (1..=CONTEXT_CHECK_SAMPLES).map(move |i| i * size / (CONTEXT_CHECK_SAMPLES + 1));
+    positions(output.height()).all(|y| {
+        let line = output.line::<u8>(y);
+        positions(output.width()).all(|x| {
+            line[3 * x as usize..3 * (x as usize + 1)].iter().all(|value| *value == 0)
+        })
+    })
+}
+
+/// Renders a frame of an export in the same way as `on_projection` and sends the output image.
+fn on_preview_frame(
+    preview: PreviewFrame,
     display: &dyn glium::backend::Facade,
+    unit_quad: &glium::VertexBuffer<data::Vertex2>,
+    projection_prog: &glium::Program,
+    solid_color_2d_prog: &glium::Program,
+    context_check: &dyn ContextCheck,
     receiver: &crossbeam::channel::Receiver<MainToWorkerMsg>
 ) {
-    let mut disk_info: Option<DiskInfo> = None;
+    let task = &preview.task;
+    let targets = ExportTargets::new(task, display);
+    let renderer = FrameRenderer::new(task, display, unit_quad, projection_prog, solid_color_2d_prog, &targets);
 
-    for (idx, (texture_id, path)) in task.items.iter().enumerate() {
-        match receiver.try_recv() {
-            Ok(msg) => match msg {
-                MainToWorkerMsg::Cancel => {
-                    task.result_sender.send(LoadImagesResultMsg::Cancelled).unwrap();
-                    return;
-                },
-                _ => panic!("unexpected message received")
-            },
+    let result = match_seams(&renderer, receiver).and_then(|matching| {
+        let src_params = match matching {
+            Some(matching) => matching.apply(&task.src_params),
+            None => task.src_params.clone()
+        };
+        renderer.output_image(preview.frame_idx, &src_params, Some(context_check)).map(into_rgb8)
+    });
 
-            _ => ()
+    // the preview may have been closed in the meantime
+    let _ = preview.image_sender.send(result.map_err(|failure| failure.description()));
+}
+
+enum FrameFailure {
+    Cancelled,
+    Error(String),
+    /// See `ProjectionResultMsg::ContextLost`.
+    ContextLost
+}
+
+impl FrameFailure {
+    /// Returns the message reporting the failure of frame `idx` of `num_images`.
+    fn into_message(self, idx: usize, num_images: usize) -> ProjectionResultMsg {
+        match self {
+            FrameFailure::Cancelled => ProjectionResultMsg::Cancelled(idx, num_images),
+            FrameFailure::Error(message) => ProjectionResultMsg::Error(message),
+            FrameFailure::ContextLost => ProjectionResultMsg::ContextLost
         }
+    }
 
-        let texture = unsafe { glium::Texture2d::from_id(
+    fn description(self) -> String {
+        match self {
+            FrameFailure::Cancelled => "cancelled".to_string(),
+            FrameFailure::Error(message) => message,
+            FrameFailure::ContextLost => "the loaded images have been lost (e.g., after a GPU driver reset)".to_string()
+        }
+    }
+}
+
+/// Render targets of an export.
+struct ExportTargets {
+    draw_buffer: Texture2d,
+    /// Used for `ProjectionSource::Files`.
+    scratch_texture: Option<Texture2d>,
+    grid: Option<projection::projection_view::Grid>
+}
+
+impl ExportTargets {
+    fn new(task: &Projection, display: &dyn glium::backend::Facade) -> ExportTargets {
+        //TODO: refactor DrawBuffer to also work w/out "imgui texture id"
+
+        // let projection_draw_buf = DrawBuffer::new_with_size(
+        //     Sampling::Single,
+        //     &gl_objects.texture_copy_single,
+        //     &gl_objects.texture_copy_multi,
+        //     &unit_quad,
+        //     display,
+        //     //renderer,
+        //     (disk_diameter * PI_2).ceil() as u32,
+        //     (disk_diameter * PI_2).ceil() as u32,
+        // );
+
+        // using a plain texture as the render target for now; RGBA, as 16-bit RGB need not be color-renderable
+        let size = projection::projection_view::map_size(
+            &task.src_params, task.rotation_comp, task.projection_type, task.standard_parallel
+        );
+        let draw_buffer = Texture2d::empty_with_format(
             display,
-            glium::texture::UncompressedFloatFormat::U8U8U8,
-            *texture_id,
-            false,
+            if task.dithering.is_some() || task.output_bit_depth() == BitDepth::Sixteen {
+                glium::texture::UncompressedFloatFormat::U16U16U16U16
+            } else {
+                glium::texture::UncompressedFloatFormat::U8U8U8
+            },
             glium::texture::MipmapsOption::NoMipmap,
-            glium::texture::Dimensions::Texture2d{ width: task.dimensions[0], height: task.dimensions[1] }
-        ) };
+            size[0],
+            size[1]
+        ).unwrap();
 
-        match load_single_image(task.dimensions[0], task.dimensions[1], task.pixel_format, path, &texture) {
-            Err(e) => {
-                task.result_sender.send(LoadImagesResultMsg::Error(e.to_string())).unwrap();
-                return;
+        let grid = task.grid.as_ref().map(|params| projection::projection_view::Grid::new(
+            display, params, draw_buffer.width() as f32 / draw_buffer.height() as f32
+        ));
+
+        let (width, height) = match task.image_size {
+            glium::texture::Dimensions::Texture2d{ width, height } => (width, height),
+            _ => unreachable!()
+        };
+
+        let scratch_texture = match &task.source {
+            ProjectionSource::Textures(_) => None,
+            ProjectionSource::Files{ .. } => Some(Texture2d::empty_with_format(
+                display,
+                task.source_bit_depth.texture_format(),
+                glium::texture::MipmapsOption::NoMipmap,
+                width,
+                height
+            ).unwrap())
+        };
+
+        ExportTargets{ draw_buffer, scratch_texture, grid }
+    }
+}
+
+/// Renders projections and output images of source frames of an export.
+struct FrameRenderer<'a> {
+    task: &'a Projection,
+    display: &'a dyn glium::backend::Facade,
+    unit_quad: &'a glium::VertexBuffer<data::Vertex2>,
+    projection_prog: &'a glium::Program,
+    /// Used for `ProjectionSource::Files`.
+    scratch_texture: Option<&'a Texture2d>,
+    /// Used for `ProjectionSource::Files`.
+    staging: RefCell<image_utils::StagingBuffers>,
+    draw_buffer: &'a Texture2d,
+    grid: Option<GridOverlay>,
+    encoding: OutputEncoding
+}
+
+impl<'a> FrameRenderer<'a> {
+    fn new(
+        task: &'a Projection,
+        display: &'a dyn glium::backend::Facade,
+        unit_quad: &'a glium::VertexBuffer<data::Vertex2>,
+        projection_prog: &'a glium::Program,
+        solid_color_2d_prog: &'a glium::Program,
+        targets: &'a ExportTargets
+    ) -> FrameRenderer<'a> {
+        // the CPU renderer does not draw the grid
+        let grid = match (&targets.grid, &task.grid) {
+            (Some(grid), Some(params)) if !task.reproducible => match GridOverlay::new(
+                display, grid, params.color, solid_color_2d_prog, targets.draw_buffer.dimensions()
+            ) {
+                Ok(overlay) => Some(overlay),
+                Err(e) => {
+                    logging::log_warning!("Failed to render the grid, it will not be drawn: {}.", e);
+                    None
+                }
             },
+            _ => None
+        };
 
-            Ok(img) => if idx == 0 {
-                match crate::disk::find_planetary_disk(&img) {
-                    Ok((center, diameter)) => disk_info = Some(DiskInfo{ center, diameter }),
+        FrameRenderer{
+            task,
+            display,
+            unit_quad,
+            projection_prog,
+            scratch_texture: targets.scratch_texture.as_ref(),
+            staging: RefCell::new(image_utils::StagingBuffers::new(task.source_bit_depth)),
+            draw_buffer: &targets.draw_buffer,
+            grid,
+            encoding: OutputEncoding::new(task.interpretation, task.output_bit_depth(), task.dithering)
+        }
+    }
 
-                    Err(_) => {
-                        task.result_sender.send(
-                            LoadImagesResultMsg::Error("could not find planetary disk".into())
-                        ).unwrap();
-                        return;
-                    }
+    /// Returns the output image of frame `idx`, as saved by the export. If `context_check` is set, also checks
+    /// if the shared source textures have been lost.
+    fn output_image(
+        &self,
+        idx: usize,
+        src_params: &projection::source_view::SourceParameters,
+        context_check: Option<&dyn ContextCheck>
+    ) -> Result<ga_image::Image, FrameFailure> {
+        let task = self.task;
+        let pixel_format = self.encoding.rendered_pixel_format();
+
+        if let Some(check) = context_check { check.clear_gl_errors(); }
+
+        let rendered = if task.reproducible {
+            let image = self.render_on_cpu(idx, src_params, pixel_format)?;
+            if let (Some(check), ProjectionSource::Textures(_)) = (context_check, &task.source) {
+                if context_lost(check, &into_rgb8(image.clone())) { return Err(FrameFailure::ContextLost); }
+            }
+            image
+        } else {
+            self.render(idx, src_params)?;
+            // textures loaded by this thread (`ProjectionSource::Files`) are not affected by loss of the shared ones
+            if let (Some(check), ProjectionSource::Textures(_)) = (context_check, &task.source) {
+                if context_lost(check, &image_utils::image_from_texture(self.draw_buffer)) {
+                    return Err(FrameFailure::ContextLost);
                 }
             }
-        }
+            read_back(self.draw_buffer, pixel_format)
+        };
 
-        match task.progress_sender.try_send(ProgressMsg::new(
-            format!("Loaded {}.", path.as_os_str().to_string_lossy()),
-            idx as f32 / task.items.len() as f32
-        )) {
-            Ok(()) => (),
-            Err(err) => match err {
-                TrySendError::Full(_) => (),
-                TrySendError::Disconnected(_) => panic!("channel disconnected unexpectedly")
+        Ok(self.encoding.output_image(rendered, self.grid.as_ref(), task.winjupos.is_some(), task.polar.as_ref()))
+    }
+
+    /// Returns the projection of frame `idx` (RGB8 or RGB16, as `pixel_format`) rendered by `cpu_render`; the source
+    /// image is read back from its texture, so that it has been binned and converted exactly as for rendering
+    /// on the GPU.
+    fn render_on_cpu(
+        &self,
+        idx: usize,
+        src_params: &projection::source_view::SourceParameters,
+        pixel_format: ga_image::PixelFormat
+    ) -> Result<ga_image::Image, FrameFailure> {
+        let task = self.task;
+        let source = self.with_source_texture(idx, |texture| read_back(texture, match task.source_bit_depth {
+            BitDepth::Eight => ga_image::PixelFormat::RGB8,
+            BitDepth::Sixteen => ga_image::PixelFormat::RGB16
+        }))?;
+
+        Ok(projection::cpu_render::render_projection(
+            &source,
+            idx,
+            [self.draw_buffer.width(), self.draw_buffer.height()],
+            pixel_format,
+            src_params,
+            task.rotation_comp,
+            task.projection_type,
+            task.limb_feather
+        ))
+    }
+
+    /// Renders projection of frame `idx` into `draw_buffer`.
+    fn render(
+        &self,
+        idx: usize,
+        src_params: &projection::source_view::SourceParameters
+    ) -> Result<(), FrameFailure> {
+        let task = self.task;
+
+        self.with_source_texture(idx, |source_texture| projection::projection_view::render_projection(
+            false,
+            idx,
+            source_texture,
+            &mut self.draw_buffer.as_surface(),
+            self.unit_quad,
+            self.projection_prog,
+            src_params,
+            task.rotation_comp,
+            task.projection_type,
+            task.limb_feather,
+            false
+        ).map_err(|e| FrameFailure::Error(format!("rendering failed: {}", e))))?
+    }
+
+    /// Calls `f` with the texture holding source frame `idx` (loading it first for `ProjectionSource::Files`).
+    fn with_source_texture<R>(&self, idx: usize, f: impl FnOnce(&Texture2d) -> R) -> Result<R, FrameFailure> {
+        let task = self.task;
+
+        let texture_from_id;
+        let source_texture: &Texture2d = match &task.source {
+            ProjectionSource::Textures(ids) => {
+                texture_from_id = unsafe { glium::Texture2d::from_id(
+                    self.display,
+                    task.source_bit_depth.texture_format(),
+                    ids[idx],
+                    false,
+                    glium::texture::MipmapsOption::NoMipmap,
+                    task.image_size
+                ) };
+                &texture_from_id
+            },
+
+            ProjectionSource::Files{ paths, binning, interpretation } => {
+                let scratch_texture = self.scratch_texture.unwrap();
+                if let Err(e) = load_single_image(
+                    scratch_texture.width(),
+                    scratch_texture.height(),
+                    *binning,
+                    *interpretation,
+                    &paths[idx],
+                    scratch_texture,
+                    &mut self.staging.borrow_mut(),
+                    &task.cancel
+                ) {
+                    if task.cancel.is_cancelled() { return Err(FrameFailure::Cancelled); }
+                    return Err(FrameFailure::Error(format!("failed to load {}: {}", paths[idx].to_string_lossy(), e)));
+                }
+                scratch_texture
+            }
+        };
+
+        Ok(f(source_texture))
+    }
+}
+
+/// Conversion of rendered frames to the saved output images.
+struct OutputEncoding {
+    lut: [u8; 256],
+    /// Used if the output bit depth is `BitDepth::Sixteen`.
+    lut16: Option<Vec<u16>>,
+    /// Used if dithering is enabled (and the output is 8-bit).
+    curve: Option<(dither::Method, Vec<f32>)>
+}
+
+impl OutputEncoding {
+    fn new(
+        interpretation: Interpretation,
+        output_bit_depth: BitDepth,
+        dithering: Option<dither::Method>
+    ) -> OutputEncoding {
+        OutputEncoding{
+            lut: color::encode_lut(interpretation),
+            lut16: if output_bit_depth == BitDepth::Sixteen { Some(color::encode_lut16(interpretation)) } else { None },
+            curve: dithering.map(|method| (method, color::encode_curve(interpretation)))
+        }
+    }
+
+    /// Returns the pixel format frames are to be rendered with.
+    fn rendered_pixel_format(&self) -> ga_image::PixelFormat {
+        if self.lut16.is_some() || self.curve.is_some() {
+            ga_image::PixelFormat::RGB16
+        } else {
+            ga_image::PixelFormat::RGB8
+        }
+    }
+
+    /// Returns the output image (as saved by the export) of `rendered` (in `rendered_pixel_format`). The grid
+    /// is drawn after encoding, so that it has the chosen color regardless of the interpretation.
+    fn output_image(
+        &self,
+        mut rendered: ga_image::Image,
+        grid: Option<&GridOverlay>,
+        winjupos: bool,
+        polar: Option<&PolarView>
+    ) -> ga_image::Image {
+        let mut output_img = match (&self.lut16, &self.curve) {
+            (Some(lut16), _) => {
+                color::apply_lut16(&mut rendered, lut16);
+                rendered
+            },
+            (None, Some((method, curve))) => dither::to_rgb8(&rendered, curve, *method),
+            (None, None) => {
+                color::apply_lut(&mut rendered, &self.lut);
+                rendered
+            }
+        };
+        if let Some(grid) = grid { grid.draw(&mut output_img); }
+        if winjupos { output_img = projection::winjupos::full_map(&output_img); }
+        if let Some(polar) = polar { output_img = polar.render(&output_img); }
+
+        output_img
+    }
+}
+
+/// Grid drawn over output images.
+struct GridOverlay {
+    /// RGB8 image of the projection's size; non-zero where the grid is drawn.
+    mask: ga_image::Image,
+    color: [f32; 4]
+}
+
+impl GridOverlay {
+    fn new(
+        display: &dyn glium::backend::Facade,
+        grid: &projection::projection_view::Grid,
+        color: [f32; 4],
+        solid_color_2d_prog: &glium::Program,
+        size: (u32, u32)
+    ) -> Result<GridOverlay, Box<dyn Error>> {
+        let texture = Texture2d::empty_with_format(
+            display,
+            glium::texture::UncompressedFloatFormat::U8U8U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            size.0,
+            size.1
+        )?;
+        let mut surface = texture.as_surface();
+        surface.clear_color(0.0, 0.0, 0.0, 1.0);
+        grid.draw(&mut surface, [1.0, 1.0, 1.0, 1.0], solid_color_2d_prog)?;
+
+        Ok(GridOverlay{ mask: image_utils::image_from_texture(&texture), color })
+    }
+
+    /// Draws the grid over `image` (RGB8 or RGB16, of the mask's size).
+    fn draw(&self, image: &mut ga_image::Image) {
+        let image_width = image.width() as usize;
+        for y in 0..image.height() {
+            let mask = self.mask.line::<u8>(y);
+            match image.pixel_format() {
+                ga_image::PixelFormat::RGB16 => {
+                    let color = self.color.map(|c| (c * 65535.0).round() as u16);
+                    let line = image.line_mut::<u16>(y);
+                    for x in (0..3 * image_width).step_by(3).filter(|&x| mask[x] != 0) {
+                        line[x..x + 3].copy_from_slice(&color[..3]);
+                    }
+                },
+                _ => {
+                    let color = self.color.map(|c| (c * 255.0).round() as u8);
+                    let line = image.line_mut::<u8>(y);
+                    for x in (0..3 * image_width).step_by(3).filter(|&x| mask[x] != 0) {
+                        line[x..x + 3].copy_from_slice(&color[..3]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the contents of `texture` as an RGB8 or RGB16 image.
+fn read_back(texture: &Texture2d, pixel_format: ga_image::PixelFormat) -> ga_image::Image {
+    match pixel_format {
+        ga_image::PixelFormat::RGB16 => image_utils::image_from_texture_rgb16(texture),
+        _ => image_utils::image_from_texture(texture)
+    }
+}
+
+/// Measured seam brightness ratios and the resulting frame gains (see `Projection::match_seams`).
+struct SeamMatching {
+    ratios: Vec<Option<f32>>,
+    gains: Vec<f32>
+}
+
+impl SeamMatching {
+    /// Returns `src_params` with the gains applied.
+    fn apply(
+        &self,
+        src_params: &projection::source_view::SourceParameters
+    ) -> projection::source_view::SourceParameters {
+        let mut src_params = src_params.clone();
+        src_params.frame_gains =
+            self.gains.iter().enumerate().map(|(idx, gain)| gain * src_params.frame_gain(idx)).collect();
+
+        src_params
+    }
+}
+
+/// Measures seam brightness if the export requires it.
+fn match_seams(
+    renderer: &FrameRenderer,
+    receiver: &crossbeam::channel::Receiver<MainToWorkerMsg>
+) -> Result<Option<SeamMatching>, FrameFailure> {
+    let task = renderer.task;
+    if !task.match_seams || task.source.len() < 2 || task.rotation_comp == 0.0 { return Ok(None); }
+
+    let ratios = measure_seams(renderer, &task.src_params, receiver)?;
+    let gains = projection::seams::chain_gains(&ratios);
+
+    Ok(Some(SeamMatching{ ratios, gains }))
+}
+
+/// Returns brightness ratios of consecutive frames in their overlap (see `seams::overlap_ratio`).
+fn measure_seams(
+    renderer: &FrameRenderer,
+    src_params: &projection::source_view::SourceParameters,
+    receiver: &crossbeam::channel::Receiver<MainToWorkerMsg>
+) -> Result<Vec<Option<f32>>, FrameFailure> {
+    let task = renderer.task;
+    let num_images = task.source.len();
+
+    let mut ratios = vec![];
+    let mut prev_image: Option<ga_image::Image> = None;
+    for idx in 0..num_images {
+        if cancel_requested(&task.cancel, receiver) { return Err(FrameFailure::Cancelled); }
+
+        let image = if task.reproducible {
+            renderer.render_on_cpu(idx, src_params, ga_image::PixelFormat::RGB8)?
+        } else {
+            renderer.render(idx, src_params)?;
+            image_utils::image_from_texture(renderer.draw_buffer)
+        };
+        if let Some(prev_image) = &prev_image {
+            ratios.push(projection::seams::overlap_ratio(prev_image, &image));
+        }
+        prev_image = Some(image);
+
+        let _ = task.sender.try_send(ProgressMsg::new(
+            format!("Measuring seam brightness (frame {}).", idx + 1),
+            idx as f32 / num_images as f32
+        ));
+    }
+
+    Ok(ratios)
+}
+
+/// Converts `image` to RGB8 if it is RGB16 (e.g., to be displayed).
+fn into_rgb8(image: ga_image::Image) -> ga_image::Image {
+    if image.pixel_format() == ga_image::PixelFormat::RGB16 {
+        image.convert_pix_fmt(ga_image::PixelFormat::RGB8, None)
+    } else {
+        image
+    }
+}
+
+/// Saves `image` (see `output_format::save`); if it fails (e.g., due to a transient network error), retries once.
+fn save_with_retry(
+    image: &ga_image::Image,
+    path: &Path,
+    format: OutputFormat,
+    reproducible: bool
+) -> Result<(), image::ImageError> {
+    let save = || output_format::save(image, path, format, reproducible);
+
+    save().or_else(|_| {
+        std::thread::sleep(SAVE_RETRY_DELAY);
+        save()
+    })
+}
+
+/// Returns `true` if the task has been cancelled (via `cancel` or `MainToWorkerMsg::Cancel`).
+fn cancel_requested(cancel: &CancelToken, receiver: &crossbeam::channel::Receiver<MainToWorkerMsg>) -> bool {
+    if cancel.is_cancelled() { return true; }
+
+    match receiver.try_recv() {
+        Ok(MainToWorkerMsg::Cancel) => true,
+        Ok(_) => panic!("unexpected message received"),
+        Err(_) => false
+    }
+}
+
+/// Loads image from `path` into `texture` via `staging` (which holds the converted image afterwards). Once `staging`
+/// has grown to the frame size, no per-frame allocations of pixel data are made.
+fn load_single_image(
+    expected_width: u32,
+    expected_height: u32,
+    binning: u32,
+    interpretation: Interpretation,
+    path: &Path,
+    texture: &glium::texture::Texture2d,
+    staging: &mut image_utils::StagingBuffers,
+    cancel: &CancelToken
+) -> Result<(), Box<dyn Error>> {
+    let state = file_stability::stable_state(path, file_stability::STABILITY_INTERVAL, std::time::SystemTime::now())?;
+    image_utils::load_image_staged(path, cancel, staging)?;
+    // a file written to while being decoded may have yielded a partial image
+    if file_stability::FileState::of(path)? != state { return Err(Box::new(file_stability::FileChanged)); }
+    upload_staged(expected_width, expected_height, binning, interpretation, texture, staging)
+}
+
+/// Like `load_single_image`, but loads the frame at `index` of `video`.
+fn load_video_frame(
+    expected_width: u32,
+    expected_height: u32,
+    binning: u32,
+    interpretation: Interpretation,
+    video: &mut dyn ImageSequence,
+    index: usize,
+    texture: &glium::texture::Texture2d,
+    staging: &mut image_utils::StagingBuffers
+) -> Result<(), Box<dyn Error>> {
+    staging.set_frame(&video.get_image(index)?);
+    upload_staged(expected_width, expected_height, binning, interpretation, texture, staging)
+}
+
+/// Bins the image held in `staging`, converts it to sRGB and writes it (at the bit depth of `staging`) to `texture`.
+fn upload_staged(
+    expected_width: u32,
+    expected_height: u32,
+    binning: u32,
+    interpretation: Interpretation,
+    texture: &glium::texture::Texture2d,
+    staging: &mut image_utils::StagingBuffers
+) -> Result<(), Box<dyn Error>> {
+    check_bit_depth(staging)?;
+    staging.bin(binning)?;
+    if staging.width() != expected_width || staging.height() != expected_height {
+        return Err(format!(
+            "unexpected image dimensions (expected {}x{}, found {}x{})",
+            expected_width, expected_height, staging.width(), staging.height()
+        ).into());
+    }
+
+    let rect = glium::Rect{ left: 0, bottom: 0, width: staging.width(), height: staging.height() };
+    match staging.depth() {
+        BitDepth::Eight => {
+            color::apply_lut_to_values(staging.pixels_mut(), &color::decode_lut(interpretation));
+            texture.write(rect, glium::texture::RawImage2d{
+                data: std::borrow::Cow::Borrowed(staging.pixels()),
+                width: staging.width(),
+                height: staging.height(),
+                format: glium::texture::ClientFormat::U8U8U8
+            });
+        },
+
+        BitDepth::Sixteen => {
+            color::apply_lut16_to_values(staging.pixels16_mut(), &color::decode_lut16(interpretation));
+            texture.write(rect, glium::texture::RawImage2d{
+                data: std::borrow::Cow::Borrowed(staging.pixels16()),
+                width: staging.width(),
+                height: staging.height(),
+                format: glium::texture::ClientFormat::U16U16U16
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn on_load_images(
+    task: LoadImages,
+    display: &dyn glium::backend::Facade,
+    receiver: &crossbeam::channel::Receiver<MainToWorkerMsg>
+) {
+    let mut first_frame: Option<FirstFrame> = None;
+    let mut skipped = vec![];
+    let mut staging = image_utils::StagingBuffers::new(task.bit_depth);
+
+    let mut video = match &task.items {
+        LoadItems::Files(_) => None,
+        LoadItems::Video(path, _) => match img_seq::open_ser_video(path) {
+            Ok(video) => Some(video),
+            Err(e) => {
+                task.result_sender.send(LoadImagesResultMsg::Error(
+                    format!("could not open {}: {}", path.to_string_lossy(), e)
+                )).unwrap();
+                return;
+            }
+        }
+    };
+
+    for idx in 0..task.items.len() {
+        if cancel_requested(&task.cancel, receiver) {
+            task.result_sender.send(LoadImagesResultMsg::Cancelled(idx)).unwrap();
+            return;
+        }
+
+        let texture = unsafe { glium::Texture2d::from_id(
+            display,
+            task.bit_depth.texture_format(),
+            task.items.texture_id(idx),
+            false,
+            glium::texture::MipmapsOption::NoMipmap,
+            glium::texture::Dimensions::Texture2d{ width: task.dimensions[0], height: task.dimensions[1] }
+        ) };
+
+        let mut load = |staging: &mut image_utils::StagingBuffers| match &task.items {
+            LoadItems::Files(files) => load_single_image(
+                task.dimensions[0],
+                task.dimensions[1],
+                task.binning,
+                task.interpretation,
+                &files[idx].1,
+                &texture,
+                staging,
+                &task.cancel
+            ),
+
+            LoadItems::Video(_, frames) => load_video_frame(
+                task.dimensions[0],
+                task.dimensions[1],
+                task.binning,
+                task.interpretation,
+                video.as_mut().unwrap().as_mut(),
+                frames[idx].1,
+                &texture,
+                staging
+            )
+        };
+
+        let mut result = load(&mut staging);
+        // a file about to be skipped gets one more chance if it may have been only temporarily unavailable
+        let retry = match &result {
+            Err(e) if task.skip_failed_frames && !task.cancel.is_cancelled() => {
+                let transient = file_stability::classify(e.as_ref()).transient();
+                if transient {
+                    let error = file_stability::describe(e.as_ref());
+                    logging::log_warning!("Retrying {}: {}.", task.items.describe(idx), error);
+                }
+                transient
+            },
+            _ => false
+        };
+        if retry {
+            std::thread::sleep(file_stability::RETRY_DELAY);
+            result = load(&mut staging);
+        }
+
+        match result {
+            Err(_) if task.cancel.is_cancelled() => {
+                task.result_sender.send(LoadImagesResultMsg::Cancelled(idx)).unwrap();
+                return;
+            },
+
+            // a mismatched image is not just a bad frame; the whole set would have to be loaded at a different depth
+            Err(e) => if task.skip_failed_frames && !e.is::<BitDepthMismatch>() {
+                let error = file_stability::describe(e.as_ref());
+                logging::log_warning!("Skipping {}: {}.", task.items.describe(idx), error);
+                skipped.push(SkippedFrame{ index: idx, error });
+            } else {
+                // the textures are released by the main thread once it receives the result
+                task.result_sender.send(LoadImagesResultMsg::Error(
+                    format!("{}: {}", task.items.describe(idx), file_stability::describe(e.as_ref()))
+                )).unwrap();
+                return;
+            },
+
+            Ok(()) => if first_frame.is_none() {
+                // only the mono version is kept (for disk detection); the pixels stay in `staging` for the next frame
+                let image = staging.mono8_image();
+                let disk = if idx == 0 && task.first_item_disk.is_some() {
+                    task.first_item_disk
+                } else {
+                    // a failed detection is reported to (and can be corrected by) the user
+                    crate::disk::find_planetary_disk(&image).ok().map(|(center, diameter)| DiskInfo{ center, diameter })
+                };
+                first_frame = Some(FirstFrame{ image, disk });
+            }
+        }
+
+        match task.progress_sender.try_send(ProgressMsg::new(
+            format!("Loaded {}.", task.items.describe(idx)),
+            idx as f32 / task.items.len() as f32
+        )) {
+            Ok(()) => (),
+            Err(err) => match err {
+                TrySendError::Full(_) => (),
+                TrySendError::Disconnected(_) => panic!("channel disconnected unexpectedly")
             }
         }
     }
 
     unsafe { gl::Finish(); } // required, otherwise a few final textures would not be seen as loaded on the main thread
-    task.result_sender.send(LoadImagesResultMsg::Success(disk_info.unwrap())).unwrap();
+    task.result_sender.send(match first_frame {
+        Some(first_frame) => LoadImagesResultMsg::Success(first_frame, skipped),
+        None => LoadImagesResultMsg::Error("none of the images could be loaded".into())
+    }).unwrap();
+}
+
+fn on_stack_frames(
+    task: StackFrames,
+    display: &dyn glium::backend::Facade,
+    receiver: &crossbeam::channel::Receiver<MainToWorkerMsg>
+) {
+    let (width, height) = match task.image_size {
+        glium::texture::Dimensions::Texture2d{ width, height } => (width, height),
+        _ => unreachable!()
+    };
+
+    let num_frames = task.source_texture_ids.len();
+    let num_passes = if task.sigma_clip.is_some() { 2 } else { 1 };
+    let num_steps = num_passes * num_frames;
+    let mut step = 0;
+    let mut results = vec![];
+
+    for group in stacking::group_ranges(num_frames, task.group_size) {
+        let mut stacker = Stacker::new(width, height, task.source_bit_depth);
+
+        for pass in 0..num_passes {
+            if pass == 1 { stacker.begin_clipping(task.sigma_clip.unwrap()); }
+
+            for idx in group.clone() {
+                match receiver.try_recv() {
+                    Ok(msg) => match msg {
+                        MainToWorkerMsg::Cancel => {
+                            task.result_sender.send(StackFramesResultMsg::Cancelled).unwrap();
+                            return;
+                        },
+                        _ => panic!("unexpected message received")
+                    },
+
+                    _ => ()
+                }
+
+                let source_texture = unsafe { glium::Texture2d::from_id(
+                    display,
+                    task.source_bit_depth.texture_format(),
+                    task.source_texture_ids[idx],
+                    false,
+                    glium::texture::MipmapsOption::NoMipmap,
+                    task.image_size
+                ) };
+
+                let image = match task.source_bit_depth {
+                    BitDepth::Eight => image_utils::image_from_texture(&source_texture),
+                    BitDepth::Sixteen => image_utils::image_from_texture_rgb16(&source_texture)
+                };
+                if pass == 0 { stacker.add(&image); } else { stacker.add_clipped(&image); }
+
+                match task.progress_sender.try_send(ProgressMsg::new(
+                    format!("Stacking frame {}/{}{}.", idx + 1, num_frames,
+                        if num_passes == 2 { format!(" (pass {}/2)", pass + 1) } else { "".to_string() }),
+                    step as f32 / num_steps as f32
+                )) {
+                    Ok(()) => (),
+                    Err(err) => match err {
+                        TrySendError::Full(_) => (),
+                        TrySendError::Disconnected(_) => panic!("channel disconnected unexpectedly")
+                    }
+                }
+                step += 1;
+            }
+        }
+
+        results.push(stacker.result());
+    }
+
+    task.result_sender.send(StackFramesResultMsg::Success(results)).unwrap();
+}
+
+/// Returns results of `measure` applied to each source texture (read into an RGB8 image); `None` if cancelled.
+fn measure_frames<T>(
+    source_texture_ids: &[TextureId],
+    image_size: glium::texture::Dimensions,
+    progress_sender: &crossbeam::channel::Sender<ProgressMsg>,
+    display: &dyn glium::backend::Facade,
+    receiver: &crossbeam::channel::Receiver<MainToWorkerMsg>,
+    measure: impl Fn(&ga_image::Image) -> T
+) -> Option<Vec<T>> {
+    let mut results = Vec::with_capacity(source_texture_ids.len());
+
+    for (idx, source_texture_id) in source_texture_ids.iter().enumerate() {
+        match receiver.try_recv() {
+            Ok(msg) => match msg {
+                MainToWorkerMsg::Cancel => return None,
+                _ => panic!("unexpected message received")
+            },
+
+            _ => ()
+        }
+
+        let source_texture = unsafe { glium::Texture2d::from_id(
+            display,
+            glium::texture::UncompressedFloatFormat::U8U8U8,
+            *source_texture_id,
+            false,
+            glium::texture::MipmapsOption::NoMipmap,
+            image_size
+        ) };
+
+        let image = image_utils::image_from_texture(&source_texture);
+        results.push(measure(&image));
+
+        match progress_sender.try_send(ProgressMsg::new(
+            format!("Measuring frame {}/{}.", idx + 1, source_texture_ids.len()),
+            idx as f32 / source_texture_ids.len() as f32
+        )) {
+            Ok(()) => (),
+            Err(err) => match err {
+                TrySendError::Full(_) => (),
+                TrySendError::Disconnected(_) => panic!("channel disconnected unexpectedly")
+            }
+        }
+    }
+
+    Some(results)
+}
+
+fn on_measure_brightness(
+    task: MeasureBrightness,
+    display: &dyn glium::backend::Facade,
+    receiver: &crossbeam::channel::Receiver<MainToWorkerMsg>
+) {
+    let brightness = measure_frames(
+        &task.source_texture_ids, task.image_size, &task.progress_sender, display, receiver,
+        |image| normalization::disk_mean_brightness(image, task.disk_center, task.disk_diameter).unwrap_or(0.0)
+    );
+
+    task.result_sender.send(match brightness {
+        Some(brightness) => MeasureBrightnessResultMsg::Success(brightness),
+        None => MeasureBrightnessResultMsg::Cancelled
+    }).unwrap();
+}
+
+fn on_measure_limb_angles(
+    task: MeasureLimbAngles,
+    display: &dyn glium::backend::Facade,
+    receiver: &crossbeam::channel::Receiver<MainToWorkerMsg>
+) {
+    let angles = measure_frames(
+        &task.source_texture_ids, task.image_size, &task.progress_sender, display, receiver,
+        |image| projection::field_rotation::limb_angle(image, task.disk_center, task.disk_diameter, task.image_mirror)
+    );
+
+    task.result_sender.send(match angles {
+        Some(angles) => MeasureLimbAnglesResultMsg::Success(angles),
+        None => MeasureLimbAnglesResultMsg::Cancelled
+    }).unwrap();
+}
+
+mod tests {
+    use super::*;
+    use glium::GlObject;
+
+    fn skipped(indices: &[usize]) -> Vec<SkippedFrame> {
+        indices.iter().map(|index| SkippedFrame{ index: *index, error: String::new() }).collect()
+    }
+
+    #[test]
+    fn skipped_items_are_removed() {
+        let items = vec!["a", "b", "c", "d", "e"];
+        assert_eq!(vec!["b", "d"], remove_skipped(items.clone(), &skipped(&[0, 2, 4])));
+        assert_eq!(items, remove_skipped(items.clone(), &skipped(&[])));
+        assert!(remove_skipped(items, &skipped(&[0, 1, 2, 3, 4])).is_empty());
+    }
+
+    #[test]
+    fn textures_and_paths_stay_paired_after_removal() {
+        let textures = vec![10, 11, 12, 13];
+        let paths = vec!["f0", "f1", "f2", "f3"];
+        let skip = skipped(&[1, 3]);
+
+        let textures = remove_skipped(textures, &skip);
+        let paths = remove_skipped(paths, &skip);
+        assert_eq!(vec![(10, "f0"), (12, "f2")], textures.into_iter().zip(paths.into_iter()).collect::<Vec<_>>());
+    }
+
+    /// Reports the given OpenGL error.
+    struct FakeContextCheck(u32);
+
+    impl ContextCheck for FakeContextCheck {
+        fn take_gl_error(&self) -> u32 { self.0 }
+    }
+
+    /// Returns an RGB8 image with a bright disk in the middle.
+    fn rendered_frame() -> ga_image::Image {
+        let mut image = ga_image::Image::new(90, 60, None, ga_image::PixelFormat::RGB8, None, true);
+        for y in 0..60 {
+            for (x, value) in image.line_mut::<u8>(y).iter_mut().take(3 * 90).enumerate() {
+                let (dx, dy) = ((x / 3) as i32 - 45, y as i32 - 30);
+                if dx * dx + dy * dy < 25 * 25 { *value = 200; }
+            }
+        }
+
+        image
+    }
+
+    /// Reports the queued OpenGL errors, oldest first.
+    struct QueuedGlErrors(RefCell<Vec<u32>>);
+
+    impl ContextCheck for QueuedGlErrors {
+        fn take_gl_error(&self) -> u32 {
+            let mut errors = self.0.borrow_mut();
+            if errors.is_empty() { gl::NO_ERROR } else { errors.remove(0) }
+        }
+    }
+
+    #[test]
+    fn earlier_gl_errors_are_cleared_before_check() {
+        let check = QueuedGlErrors(RefCell::new(vec![gl::INVALID_VALUE, gl::INVALID_OPERATION]));
+        assert!(context_lost(&check, &rendered_frame()));
+
+        let check = QueuedGlErrors(RefCell::new(vec![gl::INVALID_VALUE, gl::INVALID_OPERATION]));
+        check.clear_gl_errors();
+        assert!(!context_lost(&check, &rendered_frame()));
+    }
+
+    #[test]
+    fn persistent_gl_error_is_still_reported_after_clearing() {
+        let check = FakeContextCheck(gl::CONTEXT_LOST);
+        check.clear_gl_errors();
+        assert!(context_lost(&check, &rendered_frame()));
+    }
+
+    #[test]
+    fn rendered_frame_passes_context_check() {
+        assert!(!context_lost(&FakeContextCheck(gl::NO_ERROR), &rendered_frame()));
+    }
+
+    #[test]
+    fn gl_errors_indicate_context_loss() {
+        for error in [gl::CONTEXT_LOST, gl::INVALID_OPERATION, gl::INVALID_VALUE] {
+            assert!(context_lost(&FakeContextCheck(error), &rendered_frame()));
+        }
+        // unrelated to shared textures
+        assert!(!context_lost(&FakeContextCheck(gl::OUT_OF_MEMORY), &rendered_frame()));
+    }
+
+    #[test]
+    fn black_frame_indicates_context_loss() {
+        let black = ga_image::Image::new(90, 60, None, ga_image::PixelFormat::RGB8, None, true);
+        assert!(context_lost(&FakeContextCheck(gl::NO_ERROR), &black));
+    }
+
+    /// Returns a source frame with a disk whose brightness varies with position (different for each `idx`).
+    /// Returns the color of pixel (10, 5) of the output image of a black frame with a grid drawn only there.
+    fn grid_pixel(pixel_format: ga_image::PixelFormat, output_bit_depth: BitDepth) -> [f64; 3] {
+        let mut mask = ga_image::Image::new(20, 10, None, ga_image::PixelFormat::RGB8, None, true);
+        mask.line_mut::<u8>(5)[30..33].copy_from_slice(&[255; 3]);
+        let grid = GridOverlay{ mask, color: [0.25, 0.5, 1.0, 1.0] };
+        let encoding = OutputEncoding::new(Interpretation::Srgb, output_bit_depth, None);
+        let rendered = ga_image::Image::new(20, 10, None, pixel_format, None, true);
+        let output = encoding.output_image(rendered, Some(&grid), false, None);
+        match output.pixel_format() {
+            ga_image::PixelFormat::RGB16 => {
+                let line = output.line::<u16>(5);
+                [0, 1, 2].map(|ch| line[30 + ch] as f64 / 65535.0)
+            },
+            _ => {
+                let line = output.line::<u8>(5);
+                [0, 1, 2].map(|ch| line[30 + ch] as f64 / 255.0)
+            }
+        }
+    }
+
+    #[test]
+    fn grid_color_is_not_affected_by_output_encoding() {
+        let expected = [0.25, 0.5, 1.0];
+        for (pixel_format, bit_depth, tolerance) in [
+            (ga_image::PixelFormat::RGB8, BitDepth::Eight, 0.5 / 255.0),
+            (ga_image::PixelFormat::RGB16, BitDepth::Sixteen, 0.5 / 65535.0)
+        ] {
+            let actual = grid_pixel(pixel_format, bit_depth);
+            for ch in 0..3 {
+                assert!((actual[ch] - expected[ch]).abs() <= tolerance, "{:?} != {:?}", actual, expected);
+            }
+        }
+    }
+
+    fn source_frame(idx: usize) -> ga_image::Image {
+        let mut image = ga_image::Image::new(120, 100, None, ga_image::PixelFormat::RGB8, None, true);
+        for y in 0..100 {
+            for (x, value) in image.line_mut::<u8>(y).iter_mut().take(3 * 120).enumerate() {
+                let (dx, dy) = ((x / 3) as i32 - 60, y as i32 - 50);
+                if dx * dx + dy * dy < 40 * 40 { *value = (50 + (x * 7 + y as usize * 3 + idx * 11) % 150) as u8; }
+            }
+        }
+
+        image
+    }
+
+    /// Returns parameters matching the disks of `source_frame`.
+    fn source_frame_params() -> projection::source_view::SourceParameters {
+        projection::source_view::SourceParameters{
+            num_images: 3,
+            inclination: cgmath::Deg(3.0),
+            frame_interval: std::time::Duration::from_secs(120),
+            roll: cgmath::Deg(10.0),
+            disk_center: Point2{ x: 60.0, y: 50.0 },
+            disk_diameter: 80.0,
+            flattening: 0.06,
+            frame_gains: vec![1.0, 1.1, 0.9],
+            frame_rolls: vec![cgmath::Deg(0.0), cgmath::Deg(0.5), cgmath::Deg(1.0)],
+            ..projection::source_view::SourceParameters::test_default()
+        }
+    }
+
+    /// Renders (on the CPU), encodes and saves frames as a reproducible export does; returns hashes of the files.
+    fn export_reproducibly(name: &str) -> Vec<u64> {
+        use std::hash::{Hash, Hasher};
+
+        let dir = std::env::temp_dir().join(format!("vislumino-test-reproducible-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let src_params = source_frame_params();
+        let rotation_comp = 4.0;
+        let projection_type = projection::projection_view::ProjectionType::Equirectangular;
+        let size = projection::projection_view::map_size(&src_params, rotation_comp, projection_type, cgmath::Deg(0.0));
+        let encoding = OutputEncoding::new(Interpretation::Srgb, BitDepth::Eight, Some(dither::Method::FloydSteinberg));
+
+        let hashes = (0..src_params.num_images).map(|idx| {
+            let rendered = projection::cpu_render::render_projection(
+                &source_frame(idx),
+                idx,
+                size,
+                encoding.rendered_pixel_format(),
+                &src_params,
+                rotation_comp,
+                projection_type,
+                0.1
+            );
+            let path = dir.join(export_conflicts::frame_file_name("frame_", idx + 1, "png"));
+            let output_img = encoding.output_image(rendered, None, false, None);
+            save_with_retry(&output_img, &path, OutputFormat::Png8, true).unwrap();
+
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            std::fs::read(&path).unwrap().hash(&mut hasher);
+            hasher.finish()
+        }).collect();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        hashes
+    }
+
+    #[test]
+    fn reproducible_export_gives_identical_files() {
+        let first = export_reproducibly("first");
+        let second = export_reproducibly("second");
+
+        assert_eq!(first, second);
+        // the frames differ, so identical hashes are not a coincidence of, e.g., empty files
+        assert!(first[0] != first[1] && first[1] != first[2]);
+    }
+
+    /// Returns an export task of `source_frame`s (with default settings) reading them from `source`.
+    fn source_frames_task(source: ProjectionSource) -> Projection {
+        Projection{
+            sender: crossbeam::channel::bounded(1).0,
+            result_sender: crossbeam::channel::unbounded().0,
+            image_size: glium::texture::Dimensions::Texture2d{ width: 120, height: 100 },
+            source,
+            output_dir: std::env::temp_dir(),
+            bounce_back: false,
+            frame_prefix: "frame_".to_string(),
+            first_frame_number: 1,
+            overwrite: false,
+            src_params: source_frame_params(),
+            rotation_comp: 4.0,
+            projection_type: projection::projection_view::ProjectionType::Equirectangular,
+            standard_parallel: cgmath::Deg(0.0),
+            limb_feather: 0.1,
+            post_export_command: None,
+            interpretation: Interpretation::Gamma(2.2),
+            cancel: CancelToken::new(),
+            contact_sheet: false,
+            skip_failed_frames: false,
+            grid: None,
+            match_seams: false,
+            winjupos: None,
+            metadata: None,
+            polar: None,
+            dithering: None,
+            source_bit_depth: BitDepth::Eight,
+            output_format: OutputFormat::Png8,
+            reproducible: false
+        }
+    }
+
+    fn file_names(paths: &[PathBuf]) -> Vec<String> {
+        paths.iter().map(|path| path.file_name().unwrap().to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn output_paths_start_at_first_frame_number() {
+        let mut task = source_frames_task(ProjectionSource::Textures(vec![]));
+        task.first_frame_number = 7;
+
+        assert_eq!(vec!["frame_00007.png"], file_names(&task.output_paths(0, 3)));
+        assert_eq!(vec!["frame_00009.png"], file_names(&task.output_paths(2, 3)));
+    }
+
+    #[test]
+    fn bounced_back_output_paths_continue_after_the_last_frame() {
+        let mut task = source_frames_task(ProjectionSource::Textures(vec![]));
+        task.first_frame_number = 7;
+        task.bounce_back = true;
+
+        // saved order: 0, 1, 2, 1, 0
+        assert_eq!(vec!["frame_00007.png", "frame_00011.png"], file_names(&task.output_paths(0, 3)));
+        assert_eq!(vec!["frame_00008.png", "frame_00010.png"], file_names(&task.output_paths(1, 3)));
+        assert_eq!(vec!["frame_00009.png"], file_names(&task.output_paths(2, 3)));
+    }
+
+    #[test]
+    fn other_output_paths_follow_options() {
+        let mut task = source_frames_task(ProjectionSource::Textures(vec![]));
+        assert!(task.other_output_paths().is_empty());
+
+        task.contact_sheet = true;
+        task.match_seams = true;
+        task.metadata = Some(ExportMetadata{ start_jd: None, frame_offsets: None });
+        assert_eq!(
+            vec![projection::seams::FILE_NAME, export_metadata::FILE_NAME, projection::contact_sheet::FILE_NAME],
+            file_names(&task.other_output_paths())
+        );
+    }
+
+    /// Returns output images of all frames of `task` (rendered as by `on_projection`).
+    fn render_output_images(task: &Projection, display: &glium::HeadlessRenderer) -> Vec<ga_image::Image> {
+        let unit_quad = projection::data::create_unit_quad(display);
+        let projection_prog = program!(display,
+            330 => {
+                vertex: include_str!("../resources/shaders/transform_2d.vert"),
+                fragment: include_str!("../resources/shaders/projection.frag"),
+            }
+        ).unwrap();
+        let solid_color_2d_prog = program!(display,
+            330 => {
+                vertex: include_str!("../resources/shaders/transform_2d.vert"),
+                fragment: include_str!("../resources/shaders/solid_color.frag"),
+            }
+        ).unwrap();
+
+        let targets = ExportTargets::new(task, display);
+        let renderer = FrameRenderer::new(task, display, &unit_quad, &projection_prog, &solid_color_2d_prog, &targets);
+
+        (0..task.source.len()).map(|idx| {
+            renderer.output_image(idx, &task.src_params, None)
+                .unwrap_or_else(|failure| panic!("frame {}: {}", idx, failure.description()))
+        }).collect()
+    }
+
+    #[test]
+    fn files_source_gives_same_frames_as_textures() {
+        let display = match crate::gpu::headless::create_renderer() {
+            Some(display) => display,
+            None => {
+                eprintln!("No OpenGL context available; skipping the test.");
+                return;
+            }
+        };
+
+        let dir = std::env::temp_dir().join(format!("vislumino-test-files-source-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths: Vec<PathBuf> = (0..3).map(|idx| {
+            let path = dir.join(format!("source_{}.png", idx));
+            output_format::save(&source_frame(idx), &path, OutputFormat::Png8, false).unwrap();
+            path
+        }).collect();
+
+        // loaded as by `on_load_images`
+        let interpretation = Interpretation::Gamma(2.2);
+        let mut staging = image_utils::StagingBuffers::new(BitDepth::Eight);
+        let textures: Vec<Texture2d> = paths.iter().map(|path| {
+            let texture = Texture2d::empty_with_format(
+                &display,
+                BitDepth::Eight.texture_format(),
+                glium::texture::MipmapsOption::NoMipmap,
+                120,
+                100
+            ).unwrap();
+            load_single_image(120, 100, 1, interpretation, path, &texture, &mut staging, &CancelToken::new()).unwrap();
+            texture
+        }).collect();
+
+        let from_textures = render_output_images(
+            &source_frames_task(ProjectionSource::Textures(textures.iter().map(|t| t.get_id()).collect())),
+            &display
+        );
+        let from_files = render_output_images(
+            &source_frames_task(ProjectionSource::Files{ paths, binning: 1, interpretation }),
+            &display
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(3, from_files.len());
+        for (idx, (expected, actual)) in from_textures.iter().zip(from_files.iter()).enumerate() {
+            assert!(expected.raw_pixels() == actual.raw_pixels(), "frame {} differs", idx);
+        }
+        assert!(from_files[0].raw_pixels().iter().any(|value| *value != 0));
+    }
+
+    #[test]
+    fn preview_matches_exported_frames() {
+        let display = match crate::gpu::headless::create_renderer() {
+            Some(display) => display,
+            None => {
+                eprintln!("No OpenGL context available; skipping the test.");
+                return;
+            }
+        };
+
+        let textures: Vec<Texture2d> = (0..3).map(|idx| {
+            let image = source_frame(idx);
+            Texture2d::with_format(
+                &display,
+                glium::texture::RawImage2d::from_raw_rgb(image.raw_pixels().to_vec(), (120, 100)),
+                BitDepth::Eight.texture_format(),
+                glium::texture::MipmapsOption::NoMipmap
+            ).unwrap()
+        }).collect();
+
+        let dir = std::env::temp_dir().join(format!("vislumino-test-preview-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (progress_sender, _progress_receiver) = crossbeam::channel::bounded(1);
+        let (result_sender, result_receiver) = crossbeam::channel::unbounded();
+        let task = || Projection{
+            sender: progress_sender.clone(),
+            result_sender: result_sender.clone(),
+            output_dir: dir.clone(),
+            overwrite: true,
+            match_seams: true,
+            ..source_frames_task(ProjectionSource::Textures(textures.iter().map(|t| t.get_id()).collect()))
+        };
+
+        let unit_quad = projection::data::create_unit_quad(&display);
+        let projection_prog = program!(&display,
+            330 => {
+                vertex: include_str!("../resources/shaders/transform_2d.vert"),
+                fragment: include_str!("../resources/shaders/projection.frag"),
+            }
+        ).unwrap();
+        let solid_color_2d_prog = program!(&display,
+            330 => {
+                vertex: include_str!("../resources/shaders/transform_2d.vert"),
+                fragment: include_str!("../resources/shaders/solid_color.frag"),
+            }
+        ).unwrap();
+        let context_check = FakeContextCheck(gl::NO_ERROR);
+        let (_, receiver) = crossbeam::channel::unbounded();
+
+        on_projection(task(), &display, &unit_quad, &projection_prog, &solid_color_2d_prog, &context_check, &receiver);
+        assert!(matches!(result_receiver.try_recv(), Ok(ProjectionResultMsg::Finished)));
+
+        for idx in 0..3 {
+            let (image_sender, image_receiver) = crossbeam::channel::bounded(1);
+            on_preview_frame(
+                PreviewFrame{ task: task(), frame_idx: idx, image_sender },
+                &display,
+                &unit_quad,
+                &projection_prog,
+                &solid_color_2d_prog,
+                &context_check,
+                &receiver
+            );
+            let preview = image_receiver.try_recv().unwrap().unwrap();
+
+            let saved = image::open(&task().output_paths(idx, 3)[0]).unwrap().to_rgb8();
+            assert_eq!((saved.width(), saved.height()), (preview.width(), preview.height()));
+            assert!(saved.as_raw().as_slice() == preview.raw_pixels(), "frame {} differs", idx);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// The display stretch is applied only when the projection view is drawn, so exported frames must not change after
+    /// drawing a stretched view.
+    #[test]
+    fn display_stretch_does_not_affect_export() {
+        let display = match crate::gpu::headless::create_renderer() {
+            Some(display) => display,
+            None => {
+                eprintln!("No OpenGL context available; skipping the test.");
+                return;
+            }
+        };
+
+        let textures: Vec<Texture2d> = (0..3).map(|idx| {
+            let image = source_frame(idx);
+            Texture2d::with_format(
+                &display,
+                glium::texture::RawImage2d::from_raw_rgb(image.raw_pixels().to_vec(), (120, 100)),
+                BitDepth::Eight.texture_format(),
+                glium::texture::MipmapsOption::NoMipmap
+            ).unwrap()
+        }).collect();
+        let task = || source_frames_task(ProjectionSource::Textures(textures.iter().map(|t| t.get_id()).collect()));
+
+        let unstretched = render_output_images(&task(), &display);
+
+        // drawn as by `ProjectionView::try_render`
+        let display_stretch_prog = program!(&display,
+            330 => {
+                vertex: include_str!("../resources/shaders/pass-through.vert"),
+                fragment: include_str!("../resources/shaders/display_stretch.frag"),
+            }
+        ).unwrap();
+        let unit_quad = projection::data::create_unit_quad(&display);
+        let displayed = Texture2d::empty_with_format(
+            &display,
+            glium::texture::UncompressedFloatFormat::U8U8U8,
+            glium::texture::MipmapsOption::NoMipmap,
+            120,
+            100
+        ).unwrap();
+        let (black_point, stretch_scale) = projection::display_stretch::Levels{ black: 0.2, white: 0.6 }.uniforms();
+        displayed.as_surface().draw(
+            &*unit_quad,
+            &glium::index::NoIndices(glium::index::PrimitiveType::TriangleFan),
+            &display_stretch_prog,
+            &glium::uniform!{
+                source_texture: textures[0].sampled(),
+                black_point: black_point,
+                stretch_scale: stretch_scale
+            },
+            &Default::default()
+        ).unwrap();
+        assert!(
+            image_utils::image_from_texture(&displayed).raw_pixels()
+                != image_utils::image_from_texture(&textures[0]).raw_pixels()
+        );
+
+        let stretched = render_output_images(&task(), &display);
+
+        assert_eq!(3, stretched.len());
+        for (idx, (expected, actual)) in unstretched.iter().zip(stretched.iter()).enumerate() {
+            assert!(expected.raw_pixels() == actual.raw_pixels(), "frame {} differs", idx);
+        }
+    }
 }
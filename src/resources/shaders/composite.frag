@@ -0,0 +1,46 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// Combines three mono maps into a color one, applying per-channel gain and offset.
+
+#version 330 core
+
+in vec2 tex_coord;
+out vec4 output_color;
+
+// mono maps (sampled at normalized coordinates, which resamples them to the output size)
+uniform sampler2D red_map;
+uniform sampler2D green_map;
+uniform sampler2D blue_map;
+
+uniform vec3 gains;
+uniform vec3 offsets;
+// 1.0 for assigned channels, 0.0 otherwise
+uniform vec3 assigned;
+
+float mono_value(sampler2D map)
+{
+    return dot(texture(map, tex_coord).rgb, vec3(1.0 / 3.0));
+}
+
+void main()
+{
+    vec3 values = vec3(mono_value(red_map), mono_value(green_map), mono_value(blue_map));
+    output_color = vec4(clamp(values * gains + offsets, 0.0, 1.0) * assigned, 1.0);
+}
@@ -0,0 +1,34 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// Draws the previous contents of a view (to be alpha-blended over the new ones) during a crossfade.
+
+#version 330 core
+
+in vec2 tex_coord;
+out vec4 output_color;
+
+/// Has the orientation of a draw buffer's storage buffer, i.e., opposite to that of `tex_coord`.
+uniform sampler2D previous_texture;
+uniform float opacity;
+
+void main()
+{
+    output_color = vec4(texture(previous_texture, vec2(tex_coord.x, 1.0 - tex_coord.y)).rgb, opacity);
+}
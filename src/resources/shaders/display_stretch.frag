@@ -0,0 +1,37 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// Variant of `texturing.frag` with a linear stretch of values between the black and white point (used only
+// for display).
+
+#version 330 core
+
+in vec2 tex_coord;
+out vec4 output_color;
+
+uniform sampler2D source_texture;
+uniform float black_point;
+/// Equals 1 / (white point - black point).
+uniform float stretch_scale;
+
+void main()
+{
+    vec4 color = texture(source_texture, tex_coord);
+    output_color = vec4(clamp((color.rgb - black_point) * stretch_scale, 0.0, 1.0), color.a);
+}
@@ -34,12 +34,49 @@ uniform vec2 disk_center; // value in pixels
 /// on flattening and inclination).
 ///
 uniform mat3 globe_transform;
+/// Sign multipliers (-1 or 1) of image disk coordinates (X, Y); undo mirroring of the source image.
+uniform vec2 image_mirror;
+/// Brightness multiplier of the source image (exposure normalization).
+uniform float gain;
 uniform bool texture_vertical_flip;
+/// If true, `source_image` is a cylindrical map (north at the top) looked up by longitude and latitude; the disk
+/// parameters above are not used.
+uniform bool map_texturing;
+/// Projection of the map; if false, Lambert cylindrical equal-area.
+uniform bool map_equirectangular;
+/// Longitude (in degrees) of the map's left edge.
+uniform float map_left_longitude;
+/// Longitude span (in degrees) of the map.
+uniform float map_longitude_span;
 
 out vec4 output_color;
 
+/// Returns the map's color at the given location (in degrees); must match `MapGeometry::uv` in `imported_map.rs`.
+vec3 map_color(float lon_deg, float lat_deg)
+{
+    float u = mod(lon_deg - map_left_longitude, 360.0) / map_longitude_span;
+    if (u > 1.0)
+    {
+        return vec3(0.0);
+    }
+
+    float v = map_equirectangular ? (90.0 - lat_deg) / 180.0 : (1.0 - sin(radians(lat_deg))) / 2.0;
+    if (!texture_vertical_flip)
+    {
+        v = 1.0 - v;
+    }
+
+    return texture(source_image, vec2(u, v)).rgb;
+}
+
 void main()
 {
+    if (map_texturing)
+    {
+        output_color = vec4(map_color(lonlat_out.x, lonlat_out.y), 1.0);
+        return;
+    }
+
     vec2 source_size = vec2(textureSize(source_image, 0));
 
     float lon = radians(lonlat_out.x);
@@ -52,6 +89,7 @@ void main()
     );
 
     vec3 corrected_disk_pos = globe_transform * globe_pos;
+    corrected_disk_pos.xy *= image_mirror;
     if (texture_vertical_flip)
     {
         corrected_disk_pos.y = -corrected_disk_pos.y;
@@ -61,7 +99,7 @@ void main()
 
     if (corrected_disk_pos.z >= 0.0)
     {
-        output_color = vec4(texture(source_image, image_disk_pos).rgb, 1.0);
+        output_color = vec4(gain * texture(source_image, image_disk_pos).rgb, 1.0);
     }
     else
     {
@@ -0,0 +1,55 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// Compares the current map of a projection view with the one remembered at its last export.
+
+#version 330 core
+
+#define MODE_CURRENT 0
+#define MODE_EXPORTED 1
+#define MODE_DIFFERENCE 2
+
+in vec2 tex_coord;
+out vec4 output_color;
+
+uniform sampler2D current_map;
+/// Downscaled copy of `current_map` as it was when the export started (same orientation).
+uniform sampler2D exported_map;
+uniform int mode;
+/// Multiplier of the displayed difference.
+uniform float difference_gain;
+/// Display stretch (see `display_stretch.frag`) applied to the current and the exported map.
+uniform float black_point;
+uniform float stretch_scale;
+
+void main()
+{
+    vec3 current = texture(current_map, tex_coord).rgb;
+    vec3 exported = texture(exported_map, tex_coord).rgb;
+
+    vec3 color;
+    if (mode == MODE_CURRENT)
+        color = clamp((current - black_point) * stretch_scale, 0.0, 1.0);
+    else if (mode == MODE_EXPORTED)
+        color = clamp((exported - black_point) * stretch_scale, 0.0, 1.0);
+    else
+        color = min(difference_gain * abs(current - exported), vec3(1.0, 1.0, 1.0));
+
+    output_color = vec4(color, 1.0);
+}
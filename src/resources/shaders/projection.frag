@@ -36,9 +36,54 @@ uniform bool equirectangular; // if false, equal-area projection is used
 /// on flattening and inclination).
 ///
 uniform mat3 globe_transform;
+/// Sign multipliers (-1 or 1) of image disk coordinates (X, Y); undo mirroring of the source image.
+uniform vec2 image_mirror;
+/// Brightness multiplier of the source image (exposure normalization).
+uniform float gain;
+uniform float flattening;
+/// Width (as a fraction of the disk radius) of the band along the limb within which the image fades out;
+/// 0 disables the fading.
+uniform float limb_feather;
+/// If true, the image fades out towards the terminator (see `phase.rs`).
+uniform bool phase_enabled;
+/// Direction towards the Sun in globe coordinates.
+uniform vec3 sun_direction;
+/// Sine of the solar elevation above which the image is fully used; 0 cuts it off sharply at the terminator.
+uniform float terminator_margin;
+/// If true, the procedural longitude/latitude pattern is drawn instead of the source image.
+uniform bool diagnostic;
 
 out vec4 output_color;
 
+/// Returns opacity of the point of the globe whose surface normal (in image disk coordinates) is `normal`.
+float limb_alpha(vec3 normal)
+{
+    // distance from the disk center normalized so that the limb is at 1 (for a spherical planet, it equals
+    // the distance in image disk coordinates); points beyond the limb are fully transparent
+    float radius = normal.z > 0.0 ? length(normal.xy) / length(normal) : 1.0;
+
+    return 1.0 - smoothstep(1.0 - limb_feather, 1.0, radius);
+}
+
+/// Returns fully saturated color of the given hue (0 to 1).
+vec3 hue_to_rgb(float hue)
+{
+    return clamp(abs(mod(hue * 6.0 + vec3(0.0, 4.0, 2.0), 6.0) - 3.0) - 1.0, 0.0, 1.0);
+}
+
+/// Returns the diagnostic pattern's color; must match `diagnostic_color` in `diagnostic.rs`.
+vec3 diagnostic_color(float lon_deg, float lat_deg)
+{
+    const float CELL_SIZE = 30.0;
+    const float DARK_CELL_BRIGHTNESS = 0.5;
+
+    float brightness = 0.25 + 0.75 * clamp((lat_deg + 90.0) / 180.0, 0.0, 1.0);
+    float cell = floor(lon_deg / CELL_SIZE) + floor(lat_deg / CELL_SIZE);
+    float cell_brightness = mod(cell, 2.0) == 0.0 ? 1.0 : DARK_CELL_BRIGHTNESS;
+
+    return hue_to_rgb(mod(lon_deg / 360.0, 1.0)) * brightness * cell_brightness;
+}
+
 void main()
 {
     vec2 source_size = vec2(textureSize(source_image, 0));
@@ -65,9 +110,40 @@ void main()
         cos_lat * cos(lon)
     );
 
-    vec2 corrected_disk_pos = (globe_transform * globe_pos).xy;
+    vec2 corrected_disk_pos = (globe_transform * globe_pos).xy * image_mirror;
 
     vec2 image_disk_pos = disk_center / source_size + (corrected_disk_pos * disk_diameter / 2) / source_size;
 
-    output_color = vec4(texture(source_image, image_disk_pos).rgb, 1.0);
+    float alpha = 1.0;
+    if (limb_feather > 0.0)
+    {
+        // normal of the flattened globe; the roll (included in `globe_transform`) does not change its Z
+        float polar_scale = 1.0 - flattening;
+        vec3 normal = globe_transform * vec3(globe_pos.x, globe_pos.y / (polar_scale * polar_scale), globe_pos.z);
+        alpha = limb_alpha(normal);
+    }
+
+    if (phase_enabled)
+    {
+        float polar_scale = 1.0 - flattening;
+        vec3 normal = normalize(vec3(globe_pos.x, globe_pos.y / polar_scale, globe_pos.z));
+        float sin_elevation = dot(normal, sun_direction);
+        alpha *= terminator_margin > 0.0
+            ? smoothstep(0.0, terminator_margin, sin_elevation)
+            : (sin_elevation > 0.0 ? 1.0 : 0.0);
+    }
+
+    // the disk may extend outside the source image; such parts produce no contribution (instead of sampling values
+    // according to the texture's wrap mode)
+    if (any(lessThan(image_disk_pos, vec2(0.0, 0.0))) || any(greaterThan(image_disk_pos, vec2(1.0, 1.0))))
+    {
+        alpha = 0.0;
+    }
+
+    vec3 color = diagnostic
+        ? diagnostic_color(degrees(lon), degrees(asin(sin_lat)))
+        : gain * texture(source_image, image_disk_pos).rgb;
+
+    // the target is cleared to black beforehand, so fading out by premultiplying gives the same result as blending
+    output_color = vec4(alpha * color, 1.0);
 }
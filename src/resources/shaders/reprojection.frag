@@ -0,0 +1,107 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// Inverse of `projection.frag`: maps a projection (covering 180° of longitude around its central meridian) back onto
+// the disk as seen in a source image.
+
+#version 330 core
+
+#define PI 3.14159265358979323846
+
+#define MODE_REPROJECTED 0
+#define MODE_SOURCE 1
+#define MODE_DIFFERENCE 2
+#define MODE_VALIDITY 3
+
+in vec2 tex_coord;
+
+uniform sampler2D map_image;
+uniform sampler2D source_image;
+uniform bool equirectangular; // if false, `map_image` is an equal-area projection
+uniform float disk_diameter; // value in pixels
+uniform vec2 disk_center; // value in pixels
+/// Size (in pixels) of the square region of the source image around the disk center covered by the output.
+uniform float region_size;
+/// Inverse of `globe_transform` (see `projection.frag`).
+uniform mat3 inverse_globe_transform;
+/// Sign multipliers (-1 or 1) of image disk coordinates (X, Y); undo mirroring of the source image.
+uniform vec2 image_mirror;
+/// Added to longitudes (relative to the source image's central meridian) to obtain longitudes in `map_image`.
+uniform float longitude_shift;
+/// Brightness multiplier of the source image (exposure normalization).
+uniform float source_gain;
+/// Multiplier of the displayed difference.
+uniform float difference_gain;
+uniform int mode;
+
+out vec4 output_color;
+
+void main()
+{
+    vec2 source_size = vec2(textureSize(source_image, 0));
+    vec2 source_pos = (disk_center + (tex_coord - vec2(0.5, 0.5)) * region_size) / source_size;
+    vec3 source_color = source_gain * texture(source_image, source_pos).rgb;
+    // the disk may extend outside the source image
+    bool source_valid =
+        all(greaterThanEqual(source_pos, vec2(0.0, 0.0))) && all(lessThanEqual(source_pos, vec2(1.0, 1.0)));
+    if (!source_valid) source_color = vec3(0.0, 0.0, 0.0);
+
+    // the globe point seen at `disk_pos` is the nearer intersection of the line of sight (along Z) with the globe
+    vec2 disk_pos = (tex_coord - vec2(0.5, 0.5)) * region_size / (disk_diameter / 2) * image_mirror;
+    vec3 a = inverse_globe_transform * vec3(disk_pos, 0.0);
+    vec3 b = inverse_globe_transform * vec3(0.0, 0.0, 1.0);
+    float qa = dot(b, b);
+    float qb = 2.0 * dot(a, b);
+    float qc = dot(a, a) - 1.0;
+    float discriminant = qb * qb - 4.0 * qa * qc;
+
+    bool valid = false;
+    vec3 reprojected = vec3(0.0, 0.0, 0.0);
+
+    if (discriminant >= 0.0)
+    {
+        vec3 globe_pos = a + b * (-qb + sqrt(discriminant)) / (2.0 * qa);
+
+        float lon = atan(globe_pos.x, globe_pos.z) + longitude_shift;
+        float sin_lat = clamp(globe_pos.y, -1.0, 1.0);
+
+        vec2 map_pos = vec2(
+            (lon + PI / 2) / PI,
+            equirectangular ? (asin(sin_lat) + PI / 2) / PI : (sin_lat + 1.0) / 2.0
+        );
+
+        if (source_valid && map_pos.x >= 0.0 && map_pos.x <= 1.0)
+        {
+            valid = true;
+            reprojected = texture(map_image, map_pos).rgb;
+        }
+    }
+
+    vec3 color;
+    if (mode == MODE_REPROJECTED)
+        color = reprojected;
+    else if (mode == MODE_SOURCE)
+        color = source_color;
+    else if (mode == MODE_DIFFERENCE)
+        color = valid ? min(difference_gain * abs(reprojected - source_color), vec3(1.0, 1.0, 1.0)) : vec3(0.0, 0.0, 0.0);
+    else
+        color = valid ? vec3(1.0, 1.0, 1.0) : vec3(0.0, 0.0, 0.0);
+
+    output_color = vec4(color, 1.0);
+}
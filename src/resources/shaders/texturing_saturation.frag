@@ -0,0 +1,43 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+// Variant of `texturing.frag` which can highlight saturated (clipped) pixels with stripes (used only for display).
+
+#version 330 core
+
+in vec2 tex_coord;
+out vec4 output_color;
+
+uniform sampler2D source_texture;
+uniform bool highlight_saturated;
+/// Pixels with any channel at or above this value are treated as saturated.
+uniform float saturation_level;
+
+/// Width of the stripes in pixels.
+const float STRIPE_WIDTH = 4.0;
+
+void main()
+{
+    vec4 color = texture(source_texture, tex_coord);
+    if (highlight_saturated && max(color.r, max(color.g, color.b)) >= saturation_level) {
+        bool odd_stripe = mod(floor((gl_FragCoord.x + gl_FragCoord.y) / STRIPE_WIDTH), 2.0) >= 1.0;
+        color.rgb = odd_stripe ? vec3(1.0, 0.0, 1.0) : vec3(0.0, 0.0, 0.0);
+    }
+    output_color = color;
+}
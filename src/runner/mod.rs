@@ -17,15 +17,38 @@
 // along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
 //
 
-use glium::{glutin, Surface};
+use crate::{gpu::gl_debug, logging};
+use glium::{glutin, CapabilitiesSource, Surface};
 use std::cell::RefCell;
 use std::rc::Rc;
 
 mod clipboard_support;
 
+pub const WINDOW_TITLE: &str = "Vislumino";
+
 #[derive(Copy, Clone)]
 pub struct FontSizeRequest(pub f32);
 
+pub struct WindowTitleRequest(pub String);
+
+/// Changes of the main window requested by the UI in a frame.
+#[derive(Default)]
+pub struct FrameRequests {
+    pub font_size: Option<FontSizeRequest>,
+    pub window_title: Option<WindowTitleRequest>
+}
+
+/// Window events which occurred since the previous UI frame.
+#[derive(Copy, Clone, Default)]
+pub struct FrameEvents {
+    /// The main window has lost focus.
+    pub focus_lost: bool,
+    /// Focus of the main window after its latest change (if any).
+    pub focused: Option<bool>,
+    /// The user has requested closing the main window; the UI decides whether to exit (by clearing the `run` flag).
+    pub close_requested: bool
+}
+
 pub struct Runner {
     event_loop: glium::glutin::event_loop::EventLoop<()>,
     display: glium::Display,
@@ -34,7 +57,7 @@ pub struct Runner {
     renderer: Rc<RefCell<imgui_glium_renderer::Renderer>>
 }
 
-fn load_raw_gl_functions<F: Fn(&str) -> *const std::ffi::c_void>(loader: F) {
+pub fn load_raw_gl_functions<F: Fn(&str) -> *const std::ffi::c_void>(loader: F) {
     gl::BindBuffer::load_with(&loader);
     gl::BindTexture::load_with(&loader);
     gl::GenTextures::load_with(&loader);
@@ -45,6 +68,22 @@ fn load_raw_gl_functions<F: Fn(&str) -> *const std::ffi::c_void>(loader: F) {
     gl::TexImage2D::load_with(&loader);
     gl::TexParameteri::load_with(&loader);
     gl::Finish::load_with(&loader);
+    gl::Enable::load_with(&loader);
+    gl::DebugMessageCallback::load_with(&loader);
+}
+
+/// Reports an unrecoverable failure to draw or present the main window (e.g., lost GPU context).
+fn show_context_lost_error(error: &str) {
+    logging::log_error!("Failed to render the main window: {}.", error);
+    let _ = native_dialog::MessageDialog::new()
+        .set_type(native_dialog::MessageType::Error)
+        .set_title("Vislumino")
+        .set_text(&format!(
+            "Failed to render the main window ({}); the GPU context may have been lost. \
+            Vislumino will now exit (settings will be saved).",
+            error
+        ))
+        .show_alert();
 }
 
 fn create_font(physical_font_size: f32) -> imgui::FontSource<'static> {
@@ -68,11 +107,12 @@ fn create_font(physical_font_size: f32) -> imgui::FontSource<'static> {
     }.into()
 }
 
+/// Debug output of the created contexts is requested if enabled via `gl_debug::set_requested`.
 pub fn create_runner(logical_font_size: f32) -> (Runner, glium::glutin::Context<glium::glutin::NotCurrent>) {
     let event_loop = glium::glutin::event_loop::EventLoop::new();
-    let context = glium::glutin::ContextBuilder::new().with_vsync(true);
+    let context = glium::glutin::ContextBuilder::new().with_vsync(true).with_gl_debug_flag(gl_debug::requested());
     let builder = glium::glutin::window::WindowBuilder::new()
-        .with_title("Vislumino".to_owned())
+        .with_title(WINDOW_TITLE.to_owned())
         .with_inner_size(glium::glutin::dpi::LogicalSize::new(1280f64, 768f64));
     let display =
         glium::Display::new(builder, context, &event_loop).expect("failed to initialize display");
@@ -83,7 +123,7 @@ pub fn create_runner(logical_font_size: f32) -> (Runner, glium::glutin::Context<
     if let Some(backend) = clipboard_support::init() {
         imgui.set_clipboard_backend(backend);
     } else {
-        eprintln!("Failed to initialize clipboard.");
+        logging::log_warning!("Failed to initialize clipboard.");
     }
 
     let mut platform = imgui_winit_support::WinitPlatform::init(&mut imgui);
@@ -104,15 +144,28 @@ pub fn create_runner(logical_font_size: f32) -> (Runner, glium::glutin::Context<
 
     let renderer = imgui_glium_renderer::Renderer::init(&mut imgui, &display).expect("failed to initialize renderer");
 
+    let gl_info = format!(
+        "OpenGL {} ({}, {}); max. texture size: {}",
+        display.get_opengl_version_string(),
+        display.get_opengl_vendor_string(),
+        display.get_opengl_renderer_string(),
+        display.get_capabilities().max_texture_size
+    );
+    logging::log_info!("{}.", gl_info);
+    crate::crash_report::set_gl_info(gl_info);
+
     let worker_context;
 
     {
         let window = display.gl_window();
         let context = window.context();
-        let worker_context_builder = glium::glutin::ContextBuilder::new().with_shared_lists(context);
+        let worker_context_builder = glium::glutin::ContextBuilder::new()
+            .with_shared_lists(context)
+            .with_gl_debug_flag(gl_debug::requested());
         let event_loop = glium::glutin::event_loop::EventLoop::new();
 
         load_raw_gl_functions(|symbol| window.context().get_proc_address(symbol) as _);
+        gl_debug::install(gl_debug::GlContext::Main, &display);
 
         worker_context = worker_context_builder.build_headless(&event_loop, glutin::dpi::PhysicalSize{ width: 128, height: 128 }).unwrap();
     }
@@ -140,8 +193,9 @@ impl Runner {
             &mut bool,
             &mut imgui::Ui,
             &glium::Display,
-            &Rc<RefCell<imgui_glium_renderer::Renderer>>
-        ) -> Option<FontSizeRequest> + 'static
+            &Rc<RefCell<imgui_glium_renderer::Renderer>>,
+            &FrameEvents
+        ) -> FrameRequests + 'static
     {
         let Runner {
             event_loop,
@@ -154,6 +208,11 @@ impl Runner {
 
         let mut last_frame = std::time::Instant::now();
 
+        // no UI frames are processed or rendered while minimized
+        let mut minimized = false;
+
+        let mut frame_events = FrameEvents::default();
+
         event_loop.run(move |event, _, control_flow| match event {
             glium::glutin::event::Event::NewEvents(_) => {
                 let now = std::time::Instant::now();
@@ -162,6 +221,16 @@ impl Runner {
             },
 
             glium::glutin::event::Event::MainEventsCleared => {
+                if minimized {
+                    if *control_flow == glium::glutin::event_loop::ControlFlow::Poll {
+                        *control_flow = glium::glutin::event_loop::ControlFlow::Wait;
+                    }
+                    return;
+                }
+                if *control_flow == glium::glutin::event_loop::ControlFlow::Wait {
+                    *control_flow = glium::glutin::event_loop::ControlFlow::Poll;
+                }
+
                 let gl_window = display.gl_window();
                 platform
                     .prepare_frame(imgui.io_mut(), &gl_window.window())
@@ -169,13 +238,14 @@ impl Runner {
                 gl_window.window().request_redraw();
             },
 
-            glium::glutin::event::Event::RedrawRequested(_) => {
-                let font_size_request;
+            glium::glutin::event::Event::RedrawRequested(_) if !minimized => {
+                let requests;
                 {
                     let mut ui = imgui.frame();
 
                     let mut run = true;
-                    font_size_request = run_ui(&mut run, &mut ui, &display, &renderer);
+                    requests = run_ui(&mut run, &mut ui, &display, &renderer, &frame_events);
+                    frame_events = FrameEvents::default();
                     if !run {
                         *control_flow = glium::glutin::event_loop::ControlFlow::Exit;
                     }
@@ -185,12 +255,20 @@ impl Runner {
                     target.clear_color_srgb(0.5, 0.5, 0.5, 1.0);
                     platform.prepare_render(&ui, gl_window.window());
                     let draw_data = imgui.render();
-                    renderer.borrow_mut()
-                        .render(&mut target, draw_data)
-                        .expect("rendering failed");
-                    target.finish().expect("failed to swap buffers");
+                    let render_result = renderer.borrow_mut().render(&mut target, draw_data).map_err(|e| e.to_string());
+                    // must be called even if rendering failed (an unfinished frame panics when dropped)
+                    let finish_result = target.finish().map_err(|e| e.to_string());
+                    if let Err(e) = render_result.and(finish_result) {
+                        // the configuration is saved when the GUI state (owned by `run_ui`) is dropped on exit
+                        show_context_lost_error(&e);
+                        *control_flow = glium::glutin::event_loop::ControlFlow::Exit;
+                        return;
+                    }
+                }
+                if let Some(title) = requests.window_title {
+                    display.gl_window().window().set_title(&title.0);
                 }
-                if let Some(fsr) = font_size_request {
+                if let Some(fsr) = requests.font_size {
                     imgui.fonts().clear();
                     imgui.fonts().add_font(&[create_font(platform.hidpi_factor() as f32 * fsr.0)]);
                     renderer.borrow_mut().reload_font_texture(&mut imgui).unwrap();
@@ -200,9 +278,28 @@ impl Runner {
             glium::glutin::event::Event::WindowEvent {
                 event: glium::glutin::event::WindowEvent::CloseRequested,
                 ..
-            } => *control_flow = glium::glutin::event_loop::ControlFlow::Exit,
+            } => {
+                // no UI frame would handle the request while minimized
+                if minimized {
+                    *control_flow = glium::glutin::event_loop::ControlFlow::Exit;
+                } else {
+                    frame_events.close_requested = true;
+                }
+            },
 
             event => {
+                if let glium::glutin::event::Event::WindowEvent{
+                    event: glium::glutin::event::WindowEvent::Resized(size), ..
+                } = &event {
+                    minimized = size.width == 0 || size.height == 0;
+                }
+                if let glium::glutin::event::Event::WindowEvent{
+                    event: glium::glutin::event::WindowEvent::Focused(focused), ..
+                } = &event {
+                    if !focused { frame_events.focus_lost = true; }
+                    frame_events.focused = Some(*focused);
+                }
+
                 let converted_event = convert_touch_to_mouse(event);
 
                 let gl_window = display.gl_window();
@@ -0,0 +1,281 @@
+//
+// Vislumino - Astronomy Visualization Tools
+// Copyright (c) 2022 Filip Szczerek <ga.software@yahoo.com>
+//
+// This file is part of Vislumino.
+//
+// Vislumino is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3.
+//
+// Vislumino is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Vislumino.  If not, see <http://www.gnu.org/licenses/>.
+//
+
+use crate::image_utils::BitDepth;
+use ga_image::{Image, PixelFormat};
+use std::ops::Range;
+
+/// Splits `num_frames` frames into consecutive groups of `group_size` frames (the last group may be smaller).
+pub fn group_ranges(num_frames: usize, group_size: usize) -> Vec<Range<usize>> {
+    assert!(group_size > 0);
+    (0..num_frames).step_by(group_size).map(|start| start..(start + group_size).min(num_frames)).collect()
+}
+
+/// Averages RGB8 or RGB16 (as selected by the bit depth) frames of equal size. Sums are kept in `f64`, so there
+/// is no risk of overflow.
+///
+/// Without sigma-clipping, call `add` for every frame and then `result`. With sigma-clipping, call `add` for every
+/// frame, then `begin_clipping`, then `add_clipped` for every frame (in any order) and then `result`.
+pub struct Stacker {
+    width: u32,
+    height: u32,
+    depth: BitDepth,
+    num_frames: usize,
+    sum: Vec<f64>,
+    sum_sq: Vec<f64>,
+    clipping: Option<Clipping>
+}
+
+struct Clipping {
+    lower: Vec<f32>,
+    upper: Vec<f32>,
+    sum: Vec<f64>,
+    count: Vec<u32>
+}
+
+impl Stacker {
+    pub fn new(width: u32, height: u32, depth: BitDepth) -> Stacker {
+        let num_values = (width * height * 3) as usize;
+        Stacker{
+            width,
+            height,
+            depth,
+            num_frames: 0,
+            sum: vec![0.0; num_values],
+            sum_sq: vec![0.0; num_values],
+            clipping: None
+        }
+    }
+
+    pub fn add(&mut self, image: &Image) {
+        assert!(self.clipping.is_none());
+        self.check_image(image);
+
+        let values_per_line = self.width as usize * 3;
+        for y in 0..self.height {
+            let offset = y as usize * values_per_line;
+            match self.depth {
+                BitDepth::Eight => self.accumulate(offset, &image.line::<u8>(y)[..values_per_line]),
+                BitDepth::Sixteen => self.accumulate(offset, &image.line::<u16>(y)[..values_per_line])
+            }
+        }
+
+        self.num_frames += 1;
+    }
+
+    fn accumulate<T: Copy + Into<f64>>(&mut self, offset: usize, values: &[T]) {
+        for (i, value) in values.iter().enumerate() {
+            let value: f64 = (*value).into();
+            self.sum[offset + i] += value;
+            self.sum_sq[offset + i] += value * value;
+        }
+    }
+
+    /// Sets the per-value acceptance ranges to mean ± `kappa` · standard deviation.
+    pub fn begin_clipping(&mut self, kappa: f32) {
+        assert!(self.num_frames > 0);
+
+        let n = self.num_frames as f64;
+        let mut lower = Vec::with_capacity(self.sum.len());
+        let mut upper = Vec::with_capacity(self.sum.len());
+        for (sum, sum_sq) in self.sum.iter().zip(self.sum_sq.iter()) {
+            let mean = sum / n;
+            let std_dev = (sum_sq / n - mean * mean).max(0.0).sqrt();
+            lower.push((mean - kappa as f64 * std_dev) as f32);
+            upper.push((mean + kappa as f64 * std_dev) as f32);
+        }
+
+        self.clipping = Some(Clipping{
+            lower,
+            upper,
+            sum: vec![0.0; self.sum.len()],
+            count: vec![0; self.sum.len()]
+        });
+    }
+
+    pub fn add_clipped(&mut self, image: &Image) {
+        self.check_image(image);
+        let clipping = self.clipping.as_mut().expect("`begin_clipping` not called");
+
+        let values_per_line = self.width as usize * 3;
+        for y in 0..self.height {
+            let offset = y as usize * values_per_line;
+            match self.depth {
+                BitDepth::Eight => clipping.accumulate(offset, &image.line::<u8>(y)[..values_per_line]),
+                BitDepth::Sixteen => clipping.accumulate(offset, &image.line::<u16>(y)[..values_per_line])
+            }
+        }
+    }
+
+    pub fn num_frames(&self) -> usize { self.num_frames }
+
+    pub fn result(&self) -> Image {
+        assert!(self.num_frames > 0);
+
+        let n = self.num_frames as f64;
+        let means = self.sum.iter().enumerate().map(|(i, sum)| match &self.clipping {
+            // if all values got rejected (cannot happen for kappa ⩾ 1), fall back to the plain mean
+            Some(clipping) if clipping.count[i] > 0 => clipping.sum[i] / clipping.count[i] as f64,
+            _ => sum / n
+        });
+
+        let (pixel_format, pixels): (_, Vec<u8>) = match self.depth {
+            BitDepth::Eight => (PixelFormat::RGB8, means.map(|mean| to_integer(mean, u8::MAX) as u8).collect()),
+            BitDepth::Sixteen => (
+                PixelFormat::RGB16,
+                means.flat_map(|mean| (to_integer(mean, u16::MAX) as u16).to_ne_bytes()).collect()
+            )
+        };
+
+        Image::new_from_pixels(self.width, self.height, None, pixel_format, None, pixels)
+    }
+
+    fn check_image(&self, image: &Image) {
+        assert!(image.width() == self.width && image.height() == self.height);
+        assert!(image.pixel_format() == match self.depth {
+            BitDepth::Eight => PixelFormat::RGB8,
+            BitDepth::Sixteen => PixelFormat::RGB16
+        });
+    }
+}
+
+impl Clipping {
+    fn accumulate<T: Copy + Into<f64>>(&mut self, offset: usize, values: &[T]) {
+        for (i, value) in values.iter().enumerate() {
+            let value: f64 = (*value).into();
+            let idx = offset + i;
+            if value >= self.lower[idx] as f64 && value <= self.upper[idx] as f64 {
+                self.sum[idx] += value;
+                self.count[idx] += 1;
+            }
+        }
+    }
+}
+
+/// Returns `value` rounded and clamped to [0; `max`].
+fn to_integer(value: f64, max: impl Into<f64>) -> u32 {
+    value.round().max(0.0).min(max.into()) as u32
+}
+
+mod tests {
+    use super::*;
+
+    /// Returns frames with `base` value plus noise in [-`amplitude`, `amplitude`].
+    fn noisy_frames(num_frames: usize, base: i32, amplitude: i32) -> Vec<Image> {
+        let mut state: u32 = 12345;
+        let mut frames = vec![];
+        for _ in 0..num_frames {
+            let pixels: Vec<u8> = (0..5 * 3 * 3).map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                let noise = (state >> 16) as i32 % (2 * amplitude + 1) - amplitude;
+                (base + noise).max(0).min(255) as u8
+            }).collect();
+            frames.push(Image::new_from_pixels(5, 3, None, PixelFormat::RGB8, None, pixels));
+        }
+        frames
+    }
+
+    #[test]
+    fn groups_cover_all_frames() {
+        assert_eq!(vec![0..3, 3..6, 6..7], group_ranges(7, 3));
+        assert_eq!(vec![0..2, 2..4], group_ranges(4, 2));
+        assert_eq!(vec![0..5], group_ranges(5, 10));
+        assert!(group_ranges(0, 4).is_empty());
+    }
+
+    #[test]
+    fn stacking_groups_averages_each_group_separately() {
+        let frames: Vec<Image> = [10u8, 20, 30, 100, 110, 250].iter()
+            .map(|value| Image::new_from_pixels(5, 3, None, PixelFormat::RGB8, None, vec![*value; 5 * 3 * 3]))
+            .collect();
+
+        let results: Vec<u8> = group_ranges(frames.len(), 4).into_iter().map(|range| {
+            let mut stacker = Stacker::new(5, 3, BitDepth::Eight);
+            for frame in &frames[range] { stacker.add(frame); }
+            stacker.result().line::<u8>(0)[0]
+        }).collect();
+
+        assert_eq!(vec![40, 180], results);
+    }
+
+    #[test]
+    fn averaging_reduces_noise() {
+        let frames = noisy_frames(200, 100, 20);
+        let mut stacker = Stacker::new(5, 3, BitDepth::Eight);
+        for frame in &frames { stacker.add(frame); }
+
+        for value in stacker.result().line::<u8>(1) {
+            assert!((*value as i32 - 100).abs() <= 4);
+        }
+    }
+
+    #[test]
+    fn no_overflow_for_many_saturated_frames() {
+        let frame = Image::new_from_pixels(5, 3, None, PixelFormat::RGB8, None, vec![255; 5 * 3 * 3]);
+        let mut stacker = Stacker::new(5, 3, BitDepth::Eight);
+        for _ in 0..100_000 { stacker.add(&frame); }
+
+        assert!(stacker.result().line::<u8>(2).iter().all(|value| *value == 255));
+    }
+
+    fn rgb16_frame(value: u16) -> Image {
+        let pixels = [value; 5 * 3 * 3].iter().flat_map(|v| v.to_ne_bytes()).collect();
+        Image::new_from_pixels(5, 3, None, PixelFormat::RGB16, None, pixels)
+    }
+
+    #[test]
+    fn sixteen_bit_frames_are_averaged_without_losing_precision() {
+        let mut stacker = Stacker::new(5, 3, BitDepth::Sixteen);
+        // mean 1001⅓; at 8 bits, all three values would be 4
+        for value in [1000, 1001, 1003] { stacker.add(&rgb16_frame(value)); }
+
+        let result = stacker.result();
+        assert_eq!(PixelFormat::RGB16, result.pixel_format());
+        assert!(result.line::<u16>(1)[..5 * 3].iter().all(|value| *value == 1001));
+    }
+
+    #[test]
+    fn no_overflow_for_many_saturated_16_bit_frames() {
+        let frame = rgb16_frame(u16::MAX);
+        let mut stacker = Stacker::new(5, 3, BitDepth::Sixteen);
+        for _ in 0..100_000 { stacker.add(&frame); }
+
+        assert!(stacker.result().line::<u16>(2)[..5 * 3].iter().all(|value| *value == u16::MAX));
+    }
+
+    #[test]
+    fn sigma_clipping_rejects_outliers() {
+        let mut frames = noisy_frames(50, 100, 5);
+        frames.push(Image::new_from_pixels(5, 3, None, PixelFormat::RGB8, None, vec![255; 5 * 3 * 3]));
+
+        let mut plain = Stacker::new(5, 3, BitDepth::Eight);
+        for frame in &frames { plain.add(frame); }
+        let plain_result = plain.result();
+        assert!(plain_result.line::<u8>(0).iter().all(|value| *value >= 102));
+
+        let mut clipped = Stacker::new(5, 3, BitDepth::Eight);
+        for frame in &frames { clipped.add(frame); }
+        clipped.begin_clipping(2.5);
+        for frame in &frames { clipped.add_clipped(frame); }
+
+        for value in clipped.result().line::<u8>(0) {
+            assert!((*value as i32 - 100).abs() <= 2);
+        }
+    }
+}
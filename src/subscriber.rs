@@ -54,7 +54,105 @@ impl<T> SubscriberCollection<T> {
         });
     }
 
+    /// Also removes the subscribers no longer available.
     pub fn add(&mut self, subscriber: Weak<RefCell<dyn Subscriber<T>>>) {
+        self.prune();
         self.subscribers.push(subscriber);
     }
+
+    /// Removes `subscriber` (and those no longer available); returns `false` if it was not subscribed. Removal
+    /// works also after the subscriber has been dropped.
+    pub fn remove(&mut self, subscriber: &Weak<RefCell<dyn Subscriber<T>>>) -> bool {
+        let num_before = self.subscribers.len();
+        // comparing only addresses, as vtable pointers of the same type may differ
+        self.subscribers.retain(|s| s.as_ptr() as *const () != subscriber.as_ptr() as *const ());
+        let removed = self.subscribers.len() < num_before;
+        self.prune();
+
+        removed
+    }
+
+    /// Removes the subscribers no longer available.
+    pub fn prune(&mut self) {
+        self.subscribers.retain(|subscriber| subscriber.strong_count() > 0);
+    }
+
+    /// Returns the number of subscribers, including those no longer available but not yet removed.
+    pub fn len(&self) -> usize { self.subscribers.len() }
+
+    pub fn is_empty(&self) -> bool { self.subscribers.is_empty() }
+}
+
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[derive(Default)]
+    struct Counter {
+        sum: i32
+    }
+
+    impl Subscriber<i32> for Counter {
+        fn notify(&mut self, value: &i32) { self.sum += value; }
+    }
+
+    fn subscribe(
+        collection: &mut SubscriberCollection<i32>
+    ) -> (Rc<RefCell<Counter>>, Weak<RefCell<dyn Subscriber<i32>>>) {
+        let counter = Rc::new(RefCell::new(Counter::default()));
+        let weak = Rc::downgrade(&counter) as Weak<RefCell<dyn Subscriber<i32>>>;
+        collection.add(weak.clone());
+        (counter, weak)
+    }
+
+    #[test]
+    fn removed_subscriber_is_not_notified() {
+        let mut collection = SubscriberCollection::new();
+        let (first, first_weak) = subscribe(&mut collection);
+        let (second, _) = subscribe(&mut collection);
+
+        collection.notify(&1);
+        assert!(collection.remove(&first_weak));
+        collection.notify(&10);
+
+        assert_eq!(1, first.borrow().sum);
+        assert_eq!(11, second.borrow().sum);
+        assert_eq!(1, collection.len());
+    }
+
+    #[test]
+    fn double_remove_is_reported() {
+        let mut collection = SubscriberCollection::new();
+        let (_counter, weak) = subscribe(&mut collection);
+
+        assert!(collection.remove(&weak));
+        assert!(!collection.remove(&weak));
+        assert!(collection.is_empty());
+    }
+
+    #[test]
+    fn dropped_subscriber_can_be_removed() {
+        let mut collection = SubscriberCollection::new();
+        let (counter, weak) = subscribe(&mut collection);
+        let (_other, _) = subscribe(&mut collection);
+
+        drop(counter);
+        assert!(collection.remove(&weak));
+        assert_eq!(1, collection.len());
+    }
+
+    #[test]
+    fn repeated_subscriptions_do_not_accumulate() {
+        let mut collection = SubscriberCollection::new();
+        let (_persistent, _) = subscribe(&mut collection);
+        for _ in 0..100 {
+            let (counter, _) = subscribe(&mut collection);
+            drop(counter);
+        }
+
+        // the most recently dropped one is pruned only by the next change
+        assert_eq!(2, collection.len());
+        collection.prune();
+        assert_eq!(1, collection.len());
+    }
 }